@@ -16,17 +16,20 @@ pub struct VirtIOBlk<'a> {
     header: &'static mut VirtIOHeader,
     queue: VirtQueue<'a>,
     capacity: usize,
+    features: BlkFeature,
 }
 
 impl VirtIOBlk<'_> {
     /// Create a new VirtIO-Blk driver.
     pub fn new(header: &'static mut VirtIOHeader) -> Result<Self> {
+        let mut negotiated = BlkFeature::empty();
         header.begin_init(|features| {
             let features = BlkFeature::from_bits_truncate(features);
             info!("device features: {:?}", features);
             // negotiate these flags only
-            let supported_features = BlkFeature::empty();
-            (features & supported_features).bits()
+            let supported_features = BlkFeature::FLUSH | BlkFeature::DISCARD;
+            negotiated = features & supported_features;
+            negotiated.bits()
         });
 
         // read configuration space
@@ -44,6 +47,7 @@ impl VirtIOBlk<'_> {
             header,
             queue,
             capacity: config.capacity.read() as usize,
+            features: negotiated,
         })
     }
 
@@ -226,6 +230,149 @@ impl VirtIOBlk<'_> {
         self.queue.pop_used().map(|p| p.0)
     }
 
+    /// Whether the device has a volatile write cache that can be flushed.
+    pub fn supports_flush(&self) -> bool {
+        self.features.contains(BlkFeature::FLUSH)
+    }
+
+    /// Whether the device accepts discard requests.
+    pub fn supports_discard(&self) -> bool {
+        self.features.contains(BlkFeature::DISCARD)
+    }
+
+    /// Capacity of the device in 512 bytes sectors.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of free descriptors in the queue.
+    ///
+    /// A request built by the `*_nb` methods takes one descriptor for the header,
+    /// one for the response, and one or more for each data buffer.
+    pub fn available_desc(&self) -> usize {
+        self.queue.available_desc()
+    }
+
+    /// Read consecutive sectors starting at `sector` into several buffers in a
+    /// non-blocking way.
+    ///
+    /// Unlike [VirtIOBlk::read_block_nb()], the request header is provided by the
+    /// caller so that it stays valid until the request is completed.
+    ///
+    /// # Safety
+    ///
+    /// `req`, `resp` and every buffer in `bufs` are still borrowed by the device
+    /// after this method returns. The caller must keep them alive and untouched
+    /// until the returned token is fetched through [VirtIOBlk::pop_used()].
+    pub unsafe fn read_blocks_nb(
+        &mut self,
+        sector: usize,
+        bufs: &mut [&mut [u8]],
+        req: &mut BlkReq,
+        resp: &mut BlkResp,
+    ) -> Result<u16> {
+        *req = BlkReq {
+            type_: ReqType::In,
+            reserved: 0,
+            sector: sector as u64,
+        };
+        let mut output_buf = Vec::<&mut [u8]>::new();
+        for buf in bufs.iter_mut() {
+            if buf.len() % BLK_SIZE != 0 {
+                return Err(Error::InvalidParam);
+            }
+            let buf = core::slice::from_raw_parts_mut(buf.as_mut_ptr(), buf.len());
+            output_buf.append(&mut Self::get_bufs_mut(buf));
+        }
+        output_buf.push(resp.as_buf_mut());
+        let token = self.queue.add(&[req.as_buf()], output_buf.as_slice())?;
+        self.header.notify(0);
+        Ok(token)
+    }
+
+    /// Write several buffers to consecutive sectors starting at `sector` in a
+    /// non-blocking way.
+    ///
+    /// # Safety
+    ///
+    /// See also [VirtIOBlk::read_blocks_nb()].
+    pub unsafe fn write_blocks_nb(
+        &mut self,
+        sector: usize,
+        bufs: &[&[u8]],
+        req: &mut BlkReq,
+        resp: &mut BlkResp,
+    ) -> Result<u16> {
+        *req = BlkReq {
+            type_: ReqType::Out,
+            reserved: 0,
+            sector: sector as u64,
+        };
+        let mut input_buf = Vec::<&[u8]>::new();
+        input_buf.push(req.as_buf());
+        for buf in bufs.iter() {
+            if buf.len() % BLK_SIZE != 0 {
+                return Err(Error::InvalidParam);
+            }
+            input_buf.append(&mut Self::get_bufs_ref(buf));
+        }
+        let token = self.queue.add(input_buf.as_slice(), &[resp.as_buf_mut()])?;
+        self.header.notify(0);
+        Ok(token)
+    }
+
+    /// Flush the volatile write cache of the device in a non-blocking way.
+    ///
+    /// # Safety
+    ///
+    /// See also [VirtIOBlk::read_blocks_nb()].
+    pub unsafe fn flush_nb(&mut self, req: &mut BlkReq, resp: &mut BlkResp) -> Result<u16> {
+        if !self.supports_flush() {
+            return Err(Error::InvalidParam);
+        }
+        *req = BlkReq {
+            type_: ReqType::Flush,
+            reserved: 0,
+            sector: 0,
+        };
+        let token = self.queue.add(&[req.as_buf()], &[resp.as_buf_mut()])?;
+        self.header.notify(0);
+        Ok(token)
+    }
+
+    /// Discard `nr_sectors` sectors starting at `sector` in a non-blocking way.
+    ///
+    /// # Safety
+    ///
+    /// `seg` is borrowed by the device as well. See also [VirtIOBlk::read_blocks_nb()].
+    pub unsafe fn discard_nb(
+        &mut self,
+        sector: usize,
+        nr_sectors: usize,
+        req: &mut BlkReq,
+        seg: &mut BlkDiscard,
+        resp: &mut BlkResp,
+    ) -> Result<u16> {
+        if !self.supports_discard() {
+            return Err(Error::InvalidParam);
+        }
+        *req = BlkReq {
+            type_: ReqType::Discard,
+            reserved: 0,
+            sector: 0,
+        };
+        *seg = BlkDiscard {
+            sector: sector as u64,
+            num_sectors: nr_sectors as u32,
+            flags: 0,
+        };
+        let token = self
+            .queue
+            .add(&[req.as_buf(), seg.as_buf()], &[resp.as_buf_mut()])?;
+        self.header.notify(0);
+        Ok(token)
+    }
+
     /// Return size of its VirtQueue.
     /// It can be used to tell the caller how many channels he should monitor on.
     pub fn virt_queue_size(&self) -> u16 {
@@ -251,14 +398,34 @@ struct BlkConfig {
     // ... ignored
 }
 
+/// Header of a VirtIOBlk request.
 #[repr(C)]
 #[derive(Debug)]
-struct BlkReq {
+pub struct BlkReq {
     type_: ReqType,
     reserved: u32,
     sector: u64,
 }
 
+impl Default for BlkReq {
+    fn default() -> Self {
+        BlkReq {
+            type_: ReqType::In,
+            reserved: 0,
+            sector: 0,
+        }
+    }
+}
+
+/// A segment of a discard request.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct BlkDiscard {
+    sector: u64,
+    num_sectors: u32,
+    flags: u32,
+}
+
 /// Response of a VirtIOBlk request.
 #[repr(C)]
 #[derive(Debug)]
@@ -358,3 +525,4 @@ bitflags! {
 
 unsafe impl AsBuf for BlkReq {}
 unsafe impl AsBuf for BlkResp {}
+unsafe impl AsBuf for BlkDiscard {}
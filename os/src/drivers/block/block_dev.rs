@@ -1,19 +1,223 @@
 use core::any::Any;
+use core::slice::{from_raw_parts, from_raw_parts_mut};
 
+use super::BlockStats;
 use crate::hal::BLOCK_SZ;
-/// We should regulate the behavior of this trait on FAILURE
-/// e.g. What if buf.len()>BLOCK_SZ for read_block?
-/// e.g. Does read_block clean the rest part of the block to be zero for buf.len()!=BLOCK_SZ in write_block() & read_block()
-/// e.g. What if buf.len()<BLOCK_SZ for write_block?
+use crate::syscall::errno::*;
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicIsize, Ordering};
+
+/// 块设备请求的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockOp {
+    /// 读入`segments`
+    Read,
+    /// 写出`segments`
+    Write,
+    /// 将设备的易失性写缓存刷到持久存储上
+    Flush,
+    /// 通知设备`[block_id, block_id + nr_blocks)`不再使用，不带数据段
+    Discard,
+}
+
+bitflags! {
+    pub struct BlockReqFlags: u32 {
+        /// 执行本请求之前先刷新设备的写缓存（前置屏障）
+        const PREFLUSH = 1 << 0;
+        /// 本请求写入的数据在完成时必须已经落到持久存储上
+        const FUA = 1 << 1;
+    }
+}
+
+/// 请求中的一个数据段，指向一段内核地址空间中的缓冲区
+/// # 注意
+/// 数据段不持有缓冲区，提交者需要保证缓冲区在请求完成之前有效并且不被访问
+#[derive(Debug, Clone, Copy)]
+pub struct BlockSegment {
+    addr: usize,
+    len: usize,
+}
+
+impl BlockSegment {
+    pub fn new(buf: &[u8]) -> Self {
+        Self {
+            addr: buf.as_ptr() as usize,
+            len: buf.len(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// # Safety
+    /// 调用者需要保证缓冲区仍然有效
+    pub unsafe fn as_slice(&self) -> &'static [u8] {
+        from_raw_parts(self.addr as *const u8, self.len)
+    }
+    /// # Safety
+    /// 调用者需要保证缓冲区仍然有效，且没有其他引用
+    pub unsafe fn as_mut_slice(&self) -> &'static mut [u8] {
+        from_raw_parts_mut(self.addr as *mut u8, self.len)
+    }
+}
+
+/// 请求完成时的回调，参数为请求的结果
+pub type BlockEndIo = Box<dyn FnOnce(Result<(), isize>) + Send>;
+
+/// 一个块设备请求
+/// + 读写请求的数据按顺序分散在`segments`中（scatter-gather），
+///   每个数据段的长度必须是`BLOCK_SZ`的整数倍，设备上的位置从`block_id`开始连续
+/// + `Flush`请求忽略`block_id`和`segments`
+/// + `Discard`请求的范围由`block_id`和`nr_blocks`给出
+pub struct BlockRequest {
+    pub op: BlockOp,
+    pub flags: BlockReqFlags,
+    pub block_id: usize,
+    pub nr_blocks: usize,
+    pub segments: Vec<BlockSegment>,
+    /// 请求完成（成功或失败）时恰好被调用一次
+    pub end_io: Option<BlockEndIo>,
+}
+
+impl BlockRequest {
+    fn new(op: BlockOp, block_id: usize, segments: Vec<BlockSegment>) -> Self {
+        let nr_blocks = segments.iter().map(|seg| seg.len).sum::<usize>() / BLOCK_SZ;
+        Self {
+            op,
+            flags: BlockReqFlags::empty(),
+            block_id,
+            nr_blocks,
+            segments,
+            end_io: None,
+        }
+    }
+    pub fn read(block_id: usize, segments: Vec<BlockSegment>) -> Self {
+        Self::new(BlockOp::Read, block_id, segments)
+    }
+    pub fn write(block_id: usize, segments: Vec<BlockSegment>) -> Self {
+        Self::new(BlockOp::Write, block_id, segments)
+    }
+    pub fn flush() -> Self {
+        Self::new(BlockOp::Flush, 0, Vec::new())
+    }
+    pub fn discard(block_id: usize, nr_blocks: usize) -> Self {
+        let mut req = Self::new(BlockOp::Discard, block_id, Vec::new());
+        req.nr_blocks = nr_blocks;
+        req
+    }
+    pub fn with_flags(mut self, flags: BlockReqFlags) -> Self {
+        self.flags |= flags;
+        self
+    }
+    pub fn on_complete(mut self, end_io: BlockEndIo) -> Self {
+        self.end_io = Some(end_io);
+        self
+    }
+    /// 检查数据段的长度和请求的范围
+    pub fn check(&self) -> Result<(), isize> {
+        match self.op {
+            BlockOp::Read | BlockOp::Write => {
+                if self.segments.is_empty()
+                    || self.segments.iter().any(|seg| seg.len % BLOCK_SZ != 0)
+                {
+                    return Err(EINVAL);
+                }
+                Ok(())
+            }
+            BlockOp::Flush => Ok(()),
+            BlockOp::Discard => {
+                if self.nr_blocks == 0 {
+                    Err(EINVAL)
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+    /// 以`result`结束请求，调用完成回调
+    pub fn complete(mut self, result: Result<(), isize>) {
+        if let Some(end_io) = self.end_io.take() {
+            end_io(result);
+        }
+    }
+}
+
+/// 用同步的读写接口依次执行一个请求，供不支持异步请求的设备使用
+pub fn execute_request_sync<T: BlockDevice + ?Sized>(
+    dev: &T,
+    req: &BlockRequest,
+) -> Result<(), isize> {
+    if req.flags.contains(BlockReqFlags::PREFLUSH) {
+        dev.flush()?;
+    }
+    match req.op {
+        BlockOp::Read => {
+            let mut block_id = req.block_id;
+            for seg in req.segments.iter() {
+                dev.try_read_block(block_id, unsafe { seg.as_mut_slice() })?;
+                block_id += seg.len / BLOCK_SZ;
+            }
+        }
+        BlockOp::Write => {
+            let mut block_id = req.block_id;
+            for seg in req.segments.iter() {
+                dev.try_write_block(block_id, unsafe { seg.as_slice() })?;
+                block_id += seg.len / BLOCK_SZ;
+            }
+        }
+        BlockOp::Flush => return dev.flush(),
+        BlockOp::Discard => return Err(EOPNOTSUPP),
+    }
+    if req.flags.contains(BlockReqFlags::FUA) {
+        dev.flush()?;
+    }
+    Ok(())
+}
+
+/// 提交请求并等待其完成，返回请求的结果
+pub fn submit_and_wait<T: BlockDevice + ?Sized>(dev: &T, req: BlockRequest) -> Result<(), isize> {
+    // 1表示请求尚未完成
+    let status = Arc::new(AtomicIsize::new(1));
+    let status_in_callback = status.clone();
+    let mut req = req;
+    let end_io = req.end_io.take();
+    let req = req.on_complete(Box::new(move |result| {
+        if let Some(end_io) = end_io {
+            end_io(result);
+        }
+        let code = match result {
+            Ok(()) => SUCCESS,
+            Err(errno) => errno,
+        };
+        status_in_callback.store(code, Ordering::Release);
+    }));
+    dev.submit(req)?;
+    loop {
+        match status.load(Ordering::Acquire) {
+            1 => {
+                if dev.poll() == 0 {
+                    core::hint::spin_loop();
+                }
+            }
+            SUCCESS => return Ok(()),
+            errno => return Err(errno),
+        }
+    }
+}
+/// 块设备
+/// # 出错时的行为
+/// + 缓冲区的长度必须是`BLOCK_SZ`的整数倍，读写的是从`block_id`开始的`buf.len() / BLOCK_SZ`个块，
+///   不存在只读写半个块、其余部分补零的情况。长度不合法的请求返回`EINVAL`
+/// + `try_*`接口与`submit`在设备出错时返回错误码（通常为`EIO`），不会崩溃；
+///   `read_block`/`write_block`只用于无法处理错误的调用者，出错时崩溃
+/// + 读失败时缓冲区的内容未定义；写失败时设备上这些块的内容未定义，可能只写入了一部分
+/// + 一个请求被拆成多条设备命令时，任何一条失败都使整个请求失败，回调只被调用一次
 pub trait BlockDevice: Send + Sync + Any {
     /// 从块设备对象读一个块
     /// # 参数
     /// * `block_id`: 要读取的第一个块的块号
     /// * `buf`: 来存储读取数据的buffer
     /// # 崩溃
-    /// 当buf大小不为BLOCK_SZ的整数倍的时候，该函数会崩溃
-    /// 但是现在更改块设备对象了，应该不会崩溃了？
-    /// 可能还得再试试
+    /// 当buf大小不为BLOCK_SZ的整数倍或设备出错时崩溃
     fn read_block(&self, block_id: usize, buf: &mut [u8]);
 
     /// 将块写回块设备对象
@@ -21,7 +225,7 @@ pub trait BlockDevice: Send + Sync + Any {
     /// * `block_id`: 要写入内容的第一个块号
     /// * `buf`: 存储要写入内容的buffer
     /// # 崩溃
-    /// 当buf大小不为BLOCK_SZ的整数倍的时候，该函数会崩溃
+    /// 当buf大小不为BLOCK_SZ的整数倍或设备出错时崩溃
     fn write_block(&self, block_id: usize, buf: &[u8]);
 
     /// # 注意
@@ -37,4 +241,69 @@ pub trait BlockDevice: Send + Sync + Any {
             self.write_block(i, &[num; BLOCK_SZ]);
         }
     }
+
+    /// 带错误返回的`read_block`，设备出错时返回`EIO`而不是崩溃。
+    /// 默认实现调用`read_block`，只适用于不会出错的设备
+    fn try_read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), isize> {
+        if buf.len() % BLOCK_SZ != 0 {
+            return Err(EINVAL);
+        }
+        self.read_block(block_id, buf);
+        Ok(())
+    }
+
+    /// 带错误返回的`write_block`，设备出错时返回`EIO`而不是崩溃。
+    /// 默认实现调用`write_block`，只适用于不会出错的设备
+    fn try_write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), isize> {
+        if buf.len() % BLOCK_SZ != 0 {
+            return Err(EINVAL);
+        }
+        self.write_block(block_id, buf);
+        Ok(())
+    }
+
+    /// 设备最多能同时处理的请求数
+    fn queue_depth(&self) -> usize {
+        1
+    }
+
+    /// 提交一个请求
+    /// # 返回值
+    /// + 请求被接受时返回`Ok`，请求的结果通过`end_io`回调报告，回调可能在本函数返回之前就被调用
+    /// + 请求不合法时返回错误，此时`end_io`不会被调用
+    /// # 注意
+    /// 默认实现同步地执行请求
+    fn submit(&self, req: BlockRequest) -> Result<(), isize> {
+        req.check()?;
+        let result = execute_request_sync(self, &req);
+        req.complete(result);
+        Ok(())
+    }
+
+    /// 回收已经完成的请求并调用其回调，返回本次完成的请求数
+    /// 异步设备在没有中断时需要由等待者轮询
+    fn poll(&self) -> usize {
+        0
+    }
+
+    /// 将设备的写缓存刷到持久存储上，没有写缓存的设备直接返回
+    fn flush(&self) -> Result<(), isize> {
+        Ok(())
+    }
+
+    /// 丢弃`[block_id, block_id + cnt)`，不支持该操作的设备返回`EOPNOTSUPP`
+    fn discard(&self, _block_id: usize, _cnt: usize) -> Result<(), isize> {
+        Err(EOPNOTSUPP)
+    }
+
+    /// 开始积累请求，在配对的`unplug`之前提交的请求暂不派发给驱动，以便合并
+    fn plug(&self) {}
+
+    /// 结束积累，派发积累的请求
+    fn unplug(&self) {}
+
+    /// 设备的 I/O 统计信息，只有经过 I/O 调度器的设备才有
+    fn stats(&self) -> Option<BlockStats> {
+        None
+    }
 }
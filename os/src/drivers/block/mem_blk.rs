@@ -1,5 +1,5 @@
-use super::BlockDevice;
-use crate::{config::DISK_IMAGE_BASE, hal::BLOCK_SZ};
+use super::{BlockDevice, BlockOp, BlockRequest};
+use crate::hal::{boot_info, BLOCK_SZ};
 use core::slice::{from_raw_parts, from_raw_parts_mut};
 use spin::Mutex;
 struct MemBlock(usize);
@@ -20,10 +20,10 @@ pub struct MemBlockWrapper(Mutex<MemBlock>);
 
 #[allow(unused)]
 impl MemBlockWrapper {
-    const BASE_ADDR: usize = DISK_IMAGE_BASE;
-    /// 这个MemBlockWrapper在RiscV下可能有问题
+    /// 使用启动时确定的内存盘：引导程序加载的initrd，或者从物理内存中保留的一段
     pub fn new() -> Self {
-        Self(Mutex::new(MemBlock(MemBlockWrapper::BASE_ADDR)))
+        let ram_disk = boot_info().ram_disk.expect("[mem_blk] no RAM disk");
+        Self(Mutex::new(MemBlock(ram_disk.start)))
     }
 }
 use log::info;
@@ -47,4 +47,32 @@ impl BlockDevice for MemBlockWrapper {
         let blk = self.0.lock();
         blk.block_refmut(block_id, buf.len()).copy_from_slice(buf);
     }
+    /// 内存盘没有队列，请求在提交时就在同一把锁下完成
+    fn submit(&self, req: BlockRequest) -> Result<(), isize> {
+        req.check()?;
+        {
+            let blk = self.0.lock();
+            let mut block_id = req.block_id;
+            for seg in req.segments.iter() {
+                match req.op {
+                    BlockOp::Read => unsafe {
+                        seg.as_mut_slice()
+                            .copy_from_slice(blk.block_ref(block_id, seg.len()))
+                    },
+                    BlockOp::Write => unsafe {
+                        blk.block_refmut(block_id, seg.len())
+                            .copy_from_slice(seg.as_slice())
+                    },
+                    BlockOp::Flush | BlockOp::Discard => {}
+                }
+                block_id += seg.len() / MemBlock::BLOCK_SIZE;
+            }
+        }
+        req.complete(Ok(()));
+        Ok(())
+    }
+    /// 内存盘上的数据直接生效，丢弃的块保持原样即可
+    fn discard(&self, _block_id: usize, _cnt: usize) -> Result<(), isize> {
+        Ok(())
+    }
 }
@@ -1,8 +1,13 @@
 mod block_dev;
 mod mem_blk;
 mod sata_blk;
+mod scheduler;
 mod virtio_blk;
-pub use block_dev::BlockDevice;
+pub use block_dev::{
+    execute_request_sync, submit_and_wait, BlockDevice, BlockEndIo, BlockOp, BlockReqFlags,
+    BlockRequest, BlockSegment,
+};
+pub use scheduler::{BlockStats, IoScheduler, LATENCY_BUCKETS};
 #[cfg(feature = "block_mem")]
 type BlockDeviceImpl = mem_blk::MemBlockWrapper;
 #[cfg(feature = "block_sata")]
@@ -10,12 +15,22 @@ type BlockDeviceImpl = sata_blk::SataBlock;
 #[cfg(feature = "block_virt")]
 type BlockDeviceImpl = virtio_blk::VirtIOBlock;
 
+/// 块设备在`/proc/diskstats`中的名字和主设备号
+#[cfg(feature = "block_mem")]
+pub const BLOCK_DEVICE_NAME: (&str, usize) = ("ram0", 1);
+#[cfg(feature = "block_sata")]
+pub const BLOCK_DEVICE_NAME: (&str, usize) = ("sda", 8);
+#[cfg(feature = "block_virt")]
+pub const BLOCK_DEVICE_NAME: (&str, usize) = ("vda", 254);
+
 use crate::hal::BLOCK_SZ;
 use alloc::sync::Arc;
 use lazy_static::*;
 
 lazy_static! {
-    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new());
+    /// 所有的块设备请求都经过 I/O 调度器
+    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> =
+        Arc::new(IoScheduler::new(Arc::new(BlockDeviceImpl::new())));
 }
 
 #[allow(unused)]
@@ -1,7 +1,7 @@
 use crate::config::PAGE_SIZE;
 use crate::drivers::block::BlockDevice;
-use crate::hal::BLOCK_SZ;
-use crate::mm::{frame_alloc, frame_dealloc, PhysAddr};
+use crate::hal::{boot_info, BLOCK_SZ};
+use crate::mm::{frame_alloc_contiguous, frame_dealloc_contiguous, PhysAddr, ZoneType};
 use isomorphic_drivers::{
     block::ahci::{AHCI, BLOCK_SIZE},
     provider,
@@ -17,6 +17,8 @@ impl SataBlock {
     }
 }
 
+/// isomorphic_drivers 的 AHCI 驱动每次只使用一个命令槽，
+/// 请求接口沿用`BlockDevice`的默认实现，按顺序同步执行
 impl BlockDevice for SataBlock {
     fn read_block(&self, mut block_id: usize, buf: &mut [u8]) {
         // 内核BLOCK_SZ为2048，SATA驱动中BLOCK_SIZE为512，四倍转化关系
@@ -42,36 +44,18 @@ impl provider::Provider for Provider {
     const PAGE_SIZE: usize = PAGE_SIZE;
     fn alloc_dma(size: usize) -> (usize, usize) {
         let pages = size / PAGE_SIZE;
-        let mut base = 0;
-        for i in 0..pages {
-            let frame = frame_alloc().unwrap();
-            let frame_pa: PhysAddr = frame.ppn.into();
-            let frame_pa = frame_pa.into();
-            core::mem::forget(frame);
-            if i == 0 {
-                base = frame_pa;
-            }
-            assert_eq!(frame_pa, base + i * PAGE_SIZE);
-        }
-        let base_page = base / PAGE_SIZE;
-        info!("virtio_dma_alloc: {:#x} {}", base_page, pages);
-        (base, base)
+        let base_page = frame_alloc_contiguous(pages, ZoneType::Dma32).unwrap();
+        let base: PhysAddr = base_page.into();
+        info!("virtio_dma_alloc: {:#x} {}", base_page.0, pages);
+        (base.0, base.0)
     }
 
     fn dealloc_dma(va: usize, size: usize) {
         info!("dealloc_dma: {:x} {:x}", va, size);
-        let pages = size / PAGE_SIZE;
-        let mut pa = va;
-        for _ in 0..pages {
-            frame_dealloc(PhysAddr::from(pa).into());
-            pa += PAGE_SIZE;
-        }
+        frame_dealloc_contiguous(PhysAddr::from(va).into(), size / PAGE_SIZE);
     }
 }
 
-// 扫描pci设备
-// 查看手册得知，配置空间位于 0xFE_0000_0000
-const PCI_CONFIG_ADDRESS: usize = 0xFE_0000_0000;
 const PCI_COMMAND: u16 = 0x04;
 
 struct UnusedPort;
@@ -103,14 +87,10 @@ unsafe fn enable(loc: Location) {
     am.write32(ops, loc, PCI_COMMAND, (orig | 0xf) as u32);
 }
 
+// 扫描pci设备，配置空间的地址在启动时从ACPI的MCFG表或开发板的手册得到
 pub fn pci_init() -> Option<AHCI<Provider>> {
-    for dev in unsafe {
-        scan_bus(
-            &UnusedPort,
-            CSpaceAccessMethod::MemoryMapped,
-            PCI_CONFIG_ADDRESS,
-        )
-    } {
+    let ecam = boot_info().pci_ecam?;
+    for dev in unsafe { scan_bus(&UnusedPort, CSpaceAccessMethod::MemoryMapped, ecam.start) } {
         info!(
             "pci: {:02x}:{:02x}.{} {:#x} {:#x} ({} {}) irq: {}:{:?}",
             dev.loc.bus,
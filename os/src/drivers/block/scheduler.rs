@@ -0,0 +1,509 @@
+use super::{submit_and_wait, BlockDevice, BlockEndIo, BlockOp, BlockRequest, BlockSegment};
+use crate::hal::BLOCK_SZ;
+use crate::timer::get_time_us;
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+
+/// 读请求的最长排队时间（微秒），超时后优先派发
+pub const READ_EXPIRE_US: usize = 50_000;
+/// 写请求的最长排队时间（微秒）
+pub const WRITE_EXPIRE_US: usize = 500_000;
+/// 有写请求排队时，最多连续派发多少批读请求后必须派发一次写请求
+pub const WRITES_STARVED: usize = 2;
+/// 同一方向上按扇区顺序连续派发的请求数
+pub const FIFO_BATCH: usize = 16;
+/// 合并后单个请求的最大块数
+pub const MAX_MERGE_BLOCKS: usize = 128;
+/// 延迟直方图的桶数，第`i`个桶统计`[2^i, 2^(i+1))`微秒的请求，最后一个桶不设上限
+pub const LATENCY_BUCKETS: usize = 16;
+
+const READ: usize = 0;
+const WRITE: usize = 1;
+
+/// 块设备的统计信息，下标0为读，1为写
+#[derive(Clone, Default)]
+pub struct BlockStats {
+    /// 完成的请求数（合并前）
+    pub ios: [usize; 2],
+    /// 被合并进其他请求的请求数
+    pub merges: [usize; 2],
+    /// 传输的字节数
+    pub bytes: [usize; 2],
+    /// 请求从提交到完成的总延迟（微秒）
+    pub latency_us: [usize; 2],
+    /// 延迟直方图
+    pub latency_hist: [[usize; LATENCY_BUCKETS]; 2],
+    /// 出错的请求数
+    pub errors: [usize; 2],
+    pub flushes: usize,
+    pub discards: usize,
+    /// 当前已派发给驱动但尚未完成的请求数
+    pub in_flight: usize,
+}
+
+impl BlockStats {
+    fn account(&mut self, dir: usize, bytes: usize, latency: usize, ok: bool) {
+        self.ios[dir] += 1;
+        self.bytes[dir] += bytes;
+        self.latency_us[dir] += latency;
+        let bucket = (usize::BITS - latency.leading_zeros()) as usize;
+        self.latency_hist[dir][bucket.saturating_sub(1).min(LATENCY_BUCKETS - 1)] += 1;
+        if !ok {
+            self.errors[dir] += 1;
+        }
+    }
+}
+
+/// 统计一组原始请求并调用其回调
+fn complete_origins(
+    dir: usize,
+    mut origins: Vec<Origin>,
+    result: Result<(), isize>,
+    stats: &Mutex<BlockStats>,
+) {
+    let now = get_time_us();
+    {
+        let mut stats = stats.lock();
+        for origin in origins.iter() {
+            stats.account(
+                dir,
+                origin.bytes,
+                now.saturating_sub(origin.submitted_at),
+                result.is_ok(),
+            );
+        }
+    }
+    for origin in origins.iter_mut() {
+        if let Some(end_io) = origin.end_io.take() {
+            end_io(result);
+        }
+    }
+}
+
+/// 合并进调度器队列中某个请求的一个原始请求
+struct Origin {
+    end_io: Option<BlockEndIo>,
+    bytes: usize,
+    submitted_at: usize,
+}
+
+/// 调度器队列中的请求
+struct QueuedRequest {
+    req: BlockRequest,
+    origins: Vec<Origin>,
+    /// 超过该时间（微秒）仍未派发则优先派发
+    deadline: usize,
+}
+
+struct SchedulerInner {
+    requests: BTreeMap<usize, QueuedRequest>,
+    /// 每个方向上按（起始块号，编号）排序的请求
+    sorted: [BTreeSet<(usize, usize)>; 2],
+    /// 每个方向上按到达顺序排列的请求
+    fifo: [VecDeque<usize>; 2],
+    next_id: usize,
+    /// 每个方向上上一次派发的请求的结束块号
+    next_block: [usize; 2],
+    /// 当前批次的方向和剩余请求数
+    batch_dir: usize,
+    batch_left: usize,
+    /// 连续派发读请求的批次数
+    starved: usize,
+    /// 大于0时暂不派发，积累请求以便合并
+    plugged: usize,
+}
+
+impl SchedulerInner {
+    fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    fn insert(&mut self, dir: usize, queued: QueuedRequest) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.sorted[dir].insert((queued.req.block_id, id));
+        self.fifo[dir].push_back(id);
+        self.requests.insert(id, queued);
+    }
+
+    fn remove(&mut self, dir: usize, id: usize) -> QueuedRequest {
+        let queued = self.requests.remove(&id).unwrap();
+        self.sorted[dir].remove(&(queued.req.block_id, id));
+        self.fifo[dir].retain(|fifo_id| *fifo_id != id);
+        queued
+    }
+
+    /// 队列中`dir`方向上是否有请求与`[start, end)`重叠
+    fn overlaps(&self, dir: usize, start: usize, end: usize) -> bool {
+        self.sorted[dir].range(..(end, 0)).any(|(_, id)| {
+            let req = &self.requests[id].req;
+            req.block_id + req.nr_blocks > start
+        })
+    }
+
+    /// `dir`方向上对`[start, end)`的请求是否与队列中的请求冲突。
+    /// 读和读可以任意重排，其余情况（读后写、写后读、写后写）都必须保持提交顺序，
+    /// 而派发顺序只取决于块号和期限
+    fn conflicts(&self, dir: usize, start: usize, end: usize) -> bool {
+        self.overlaps(WRITE, start, end) || (dir == WRITE && self.overlaps(READ, start, end))
+    }
+
+    /// 尝试把`req`合并到队列中相邻的请求上，成功时返回`None`
+    fn try_merge(
+        &mut self,
+        dir: usize,
+        req: BlockRequest,
+        origin: Origin,
+    ) -> Option<(BlockRequest, Origin)> {
+        let start = req.block_id;
+        let end = req.block_id + req.nr_blocks;
+        // 后向合并：队列中的请求正好在 req 之前结束
+        let back = self.sorted[dir]
+            .range(..(start, 0))
+            .next_back()
+            .map(|(_, id)| *id)
+            .filter(|id| {
+                let queued = &self.requests[id].req;
+                queued.block_id + queued.nr_blocks == start
+                    && queued.nr_blocks + req.nr_blocks <= MAX_MERGE_BLOCKS
+            });
+        if let Some(id) = back {
+            let queued = self.requests.get_mut(&id).unwrap();
+            queued.req.nr_blocks += req.nr_blocks;
+            queued.req.segments.extend(req.segments);
+            queued.origins.push(origin);
+            return None;
+        }
+        // 前向合并：队列中的请求正好从 req 结束的地方开始
+        let front = self.sorted[dir]
+            .range((end, 0)..(end + 1, 0))
+            .next()
+            .map(|(_, id)| *id)
+            .filter(|id| self.requests[id].req.nr_blocks + req.nr_blocks <= MAX_MERGE_BLOCKS);
+        if let Some(id) = front {
+            self.sorted[dir].remove(&(end, id));
+            self.sorted[dir].insert((start, id));
+            let queued = self.requests.get_mut(&id).unwrap();
+            let mut segments = req.segments;
+            segments.extend(queued.req.segments.drain(..));
+            queued.req.segments = segments;
+            queued.req.block_id = start;
+            queued.req.nr_blocks += req.nr_blocks;
+            queued.origins.insert(0, origin);
+            return None;
+        }
+        Some((req, origin))
+    }
+
+    /// 按 deadline 策略选出下一个要派发的请求
+    /// + 读优先，但有写请求排队时最多连续派发`WRITES_STARVED`批读请求
+    /// + 同一批次内按块号升序派发，FIFO 队首超时时从队首开始新的批次
+    fn pick(&mut self, now: usize) -> Option<(usize, QueuedRequest)> {
+        if self.is_empty() {
+            return None;
+        }
+        let dir = if self.batch_left > 0 && !self.fifo[self.batch_dir].is_empty() {
+            self.batch_left -= 1;
+            self.batch_dir
+        } else {
+            let dir = if !self.fifo[READ].is_empty()
+                && (self.fifo[WRITE].is_empty() || self.starved < WRITES_STARVED)
+            {
+                if !self.fifo[WRITE].is_empty() {
+                    self.starved += 1;
+                }
+                READ
+            } else {
+                self.starved = 0;
+                WRITE
+            };
+            self.batch_dir = dir;
+            self.batch_left = FIFO_BATCH - 1;
+            // 新批次从超时的队首或上次的位置开始
+            let head = self.fifo[dir][0];
+            if self.requests[&head].deadline <= now {
+                self.next_block[dir] = self.requests[&head].req.block_id;
+            }
+            dir
+        };
+        let id = self.sorted[dir]
+            .range((self.next_block[dir], 0)..)
+            .next()
+            .or_else(|| self.sorted[dir].iter().next())
+            .map(|(_, id)| *id)
+            .unwrap();
+        let queued = self.remove(dir, id);
+        self.next_block[dir] = queued.req.block_id + queued.req.nr_blocks;
+        Some((dir, queued))
+    }
+}
+
+/// 位于页缓存/块缓存与块设备驱动之间的 I/O 调度器
+/// + 相邻的读写请求在队列中合并
+/// + 按 deadline 策略排序派发，保证读请求不会被大量写请求饿死
+/// + 刷新、丢弃和带`PREFLUSH`/`FUA`的请求作为屏障，先派发队列中已有的请求
+/// + 与排队中或已派发的请求重叠且其中有写的请求也作为屏障，保证同一块上的读写按提交顺序完成
+/// + 统计每个设备的请求数、合并数、字节数和延迟
+pub struct IoScheduler {
+    dev: Arc<dyn BlockDevice>,
+    inner: Mutex<SchedulerInner>,
+    /// 已派发给驱动但尚未完成的请求数
+    in_flight: Arc<AtomicUsize>,
+    /// 已派发给驱动但尚未完成的请求的方向与块范围，以派发序号为键。
+    /// 支持多个请求同时在队列中的驱动（如NCQ）可能以任意顺序执行它们
+    in_flight_ranges: Arc<Mutex<BTreeMap<usize, (usize, usize, usize)>>>,
+    next_issue: AtomicUsize,
+    stats: Arc<Mutex<BlockStats>>,
+}
+
+impl IoScheduler {
+    pub fn new(dev: Arc<dyn BlockDevice>) -> Self {
+        Self {
+            dev,
+            inner: Mutex::new(SchedulerInner {
+                requests: BTreeMap::new(),
+                sorted: [BTreeSet::new(), BTreeSet::new()],
+                fifo: [VecDeque::new(), VecDeque::new()],
+                next_id: 0,
+                next_block: [0, 0],
+                batch_dir: READ,
+                batch_left: 0,
+                starved: 0,
+                plugged: 0,
+            }),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            in_flight_ranges: Arc::new(Mutex::new(BTreeMap::new())),
+            next_issue: AtomicUsize::new(0),
+            stats: Arc::new(Mutex::new(BlockStats::default())),
+        }
+    }
+
+    /// 把请求派发给驱动，完成时统计每个原始请求并调用其回调
+    fn issue(&self, dir: usize, queued: QueuedRequest) {
+        let QueuedRequest { req, origins, .. } = queued;
+        // 驱动拒绝请求时回调不会被调用，需要由这里结束原始请求
+        let origins = Arc::new(Mutex::new(Some(origins)));
+        let origins_in_callback = origins.clone();
+        let in_flight = self.in_flight.clone();
+        let in_flight_ranges = self.in_flight_ranges.clone();
+        let stats = self.stats.clone();
+        let seq = self.next_issue.fetch_add(1, Ordering::Relaxed);
+        self.in_flight_ranges
+            .lock()
+            .insert(seq, (dir, req.block_id, req.block_id + req.nr_blocks));
+        in_flight.fetch_add(1, Ordering::AcqRel);
+        let req = req.on_complete(Box::new(move |result| {
+            in_flight_ranges.lock().remove(&seq);
+            in_flight.fetch_sub(1, Ordering::AcqRel);
+            if let Some(origins) = origins_in_callback.lock().take() {
+                complete_origins(dir, origins, result, &stats);
+            }
+        }));
+        if let Err(errno) = self.dev.submit(req) {
+            log::error!("[io_sched] submit failed, errno: {}", errno);
+            self.in_flight_ranges.lock().remove(&seq);
+            self.in_flight.fetch_sub(1, Ordering::AcqRel);
+            if let Some(origins) = origins.lock().take() {
+                complete_origins(dir, origins, Err(errno), &self.stats);
+            }
+        }
+    }
+
+    /// 派发队列中的请求
+    /// # 参数
+    /// + `unplug`: 为真时忽略`plug`，用于等待者轮询
+    /// + `drain`: 为真时还忽略驱动的队列深度，派发全部请求，用于屏障
+    fn dispatch(&self, unplug: bool, drain: bool) {
+        let depth = self.dev.queue_depth().max(1);
+        loop {
+            if !drain && self.in_flight.load(Ordering::Acquire) >= depth {
+                return;
+            }
+            let picked = {
+                let mut inner = self.inner.lock();
+                if inner.plugged > 0 && !unplug && !drain {
+                    return;
+                }
+                inner.pick(get_time_us())
+            };
+            match picked {
+                // 在释放调度器的锁之后提交，同步驱动会在提交时直接调用回调
+                Some((dir, queued)) => self.issue(dir, queued),
+                None => return,
+            }
+        }
+    }
+
+    /// 派发队列中的全部请求并等待它们完成
+    fn drain(&self) {
+        self.dispatch(true, true);
+        while self.in_flight.load(Ordering::Acquire) != 0 {
+            if self.dev.poll() == 0 {
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    /// 先排空队列，再直接提交给驱动、不进入调度队列的请求
+    fn submit_barrier(&self, req: BlockRequest) -> Result<(), isize> {
+        self.drain();
+        let dir = match req.op {
+            BlockOp::Read => Some(READ),
+            BlockOp::Write => Some(WRITE),
+            BlockOp::Flush => {
+                self.stats.lock().flushes += 1;
+                None
+            }
+            BlockOp::Discard => {
+                self.stats.lock().discards += 1;
+                None
+            }
+        };
+        let mut req = req;
+        match dir {
+            Some(dir) => {
+                let origin = Origin {
+                    bytes: req.nr_blocks * BLOCK_SZ,
+                    end_io: req.end_io.take(),
+                    submitted_at: get_time_us(),
+                };
+                self.issue(
+                    dir,
+                    QueuedRequest {
+                        req,
+                        origins: vec![origin],
+                        deadline: 0,
+                    },
+                );
+                Ok(())
+            }
+            None => self.dev.submit(req),
+        }
+    }
+
+    /// `dir`方向上对`[start, end)`的请求是否与已派发、尚未完成的请求冲突，规则同`conflicts`
+    fn conflicts_in_flight(&self, dir: usize, start: usize, end: usize) -> bool {
+        self.in_flight_ranges
+            .lock()
+            .values()
+            .any(|&(flight_dir, flight_start, flight_end)| {
+                (dir == WRITE || flight_dir == WRITE) && flight_start < end && start < flight_end
+            })
+    }
+
+    pub fn snapshot_stats(&self) -> BlockStats {
+        let mut stats = self.stats.lock().clone();
+        stats.in_flight = self.in_flight.load(Ordering::Acquire);
+        stats
+    }
+}
+
+impl BlockDevice for IoScheduler {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.try_read_block(block_id, buf)
+            .expect("Error when reading block device");
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.try_write_block(block_id, buf)
+            .expect("Error when writing block device");
+    }
+    fn try_read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), isize> {
+        submit_and_wait(
+            self,
+            BlockRequest::read(block_id, vec![BlockSegment::new(buf)]),
+        )
+    }
+    fn try_write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), isize> {
+        submit_and_wait(
+            self,
+            BlockRequest::write(block_id, vec![BlockSegment::new(buf)]),
+        )
+    }
+    fn clear_block(&self, block_id: usize, num: u8) {
+        self.dev.clear_block(block_id, num)
+    }
+    fn clear_mult_block(&self, block_id: usize, cnt: usize, num: u8) {
+        self.dev.clear_mult_block(block_id, cnt, num)
+    }
+    fn queue_depth(&self) -> usize {
+        self.dev.queue_depth()
+    }
+    fn submit(&self, req: BlockRequest) -> Result<(), isize> {
+        req.check()?;
+        let dir = match req.op {
+            BlockOp::Read if req.flags.is_empty() => READ,
+            BlockOp::Write if req.flags.is_empty() => WRITE,
+            _ => return self.submit_barrier(req),
+        };
+        let now = get_time_us();
+        let mut req = req;
+        {
+            let mut inner = self.inner.lock();
+            // 与排队中或已派发的请求访问相同的块且其中有写时，不能交给调度器重排，
+            // 等它们全部完成后再提交
+            let (start, end) = (req.block_id, req.block_id + req.nr_blocks);
+            if inner.conflicts(dir, start, end) || self.conflicts_in_flight(dir, start, end) {
+                drop(inner);
+                return self.submit_barrier(req);
+            }
+            let origin = Origin {
+                bytes: req.nr_blocks * BLOCK_SZ,
+                end_io: req.end_io.take(),
+                submitted_at: now,
+            };
+            match inner.try_merge(dir, req, origin) {
+                None => self.stats.lock().merges[dir] += 1,
+                Some((req, origin)) => {
+                    let expire = if dir == READ {
+                        READ_EXPIRE_US
+                    } else {
+                        WRITE_EXPIRE_US
+                    };
+                    inner.insert(
+                        dir,
+                        QueuedRequest {
+                            req,
+                            origins: vec![origin],
+                            deadline: now + expire,
+                        },
+                    );
+                }
+            }
+        }
+        self.dispatch(false, false);
+        Ok(())
+    }
+    /// 等待者轮询时解除积累，派发队列中的请求
+    fn poll(&self) -> usize {
+        let completed = self.dev.poll();
+        self.dispatch(true, false);
+        completed
+    }
+    fn flush(&self) -> Result<(), isize> {
+        self.drain();
+        self.stats.lock().flushes += 1;
+        self.dev.flush()
+    }
+    fn discard(&self, block_id: usize, cnt: usize) -> Result<(), isize> {
+        self.drain();
+        self.stats.lock().discards += 1;
+        self.dev.discard(block_id, cnt)
+    }
+    fn plug(&self) {
+        self.inner.lock().plugged += 1;
+    }
+    fn unplug(&self) {
+        self.inner.lock().plugged -= 1;
+        self.dispatch(false, false);
+    }
+    fn stats(&self) -> Option<BlockStats> {
+        Some(self.snapshot_stats())
+    }
+}
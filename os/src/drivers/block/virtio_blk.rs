@@ -1,74 +1,364 @@
-use super::BlockDevice;
+use super::{submit_and_wait, BlockDevice, BlockOp, BlockReqFlags, BlockRequest, BlockSegment};
+use crate::hal::{boot_info, BLOCK_SZ};
 use crate::mm::{
-    frame_alloc, frame_dealloc, kernel_token, FrameTracker, PageTable, PageTableImpl, PhysAddr,
-    PhysPageNum, StepByOne, VirtAddr,
+    frame_alloc_contiguous, frame_dealloc_contiguous, kernel_token, PageTable, PageTableImpl,
+    PhysAddr, VirtAddr, ZoneType,
+};
+use crate::syscall::errno::*;
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, VecDeque},
+    vec,
+    vec::Vec,
 };
-use alloc::{sync::Arc, vec::Vec};
 use lazy_static::*;
 use spin::Mutex;
-use virtio_drivers::{VirtIOBlk, VirtIOHeader};
-// const VIRT_IO_BLOCK_SZ: usize = 512;
-const VIRT_IO_BLOCK_SZ: usize = 2048;
-#[allow(unused)]
-const VIRTIO0: usize = 0x10001000;
+use virtio_drivers::{
+    BlkDiscard, BlkReq, BlkResp, DeviceType, Error, RespStatus, VirtIOBlk, VirtIOHeader,
+};
+/// virtio-blk 的扇区大小
+const VIRT_IO_SECTOR_SZ: usize = 512;
+/// 一个描述符链至少占用的描述符数：请求头、数据、状态
+const DESC_PER_CHAIN: usize = 3;
 
-pub struct VirtIOBlock(Mutex<VirtIOBlk<'static>>);
+/// 已经放入队列的一个描述符链，请求头和状态必须在设备完成之前保持有效
+struct InflightChain {
+    /// 所属请求的编号
+    parent: usize,
+    #[allow(unused)]
+    header: Box<BlkReq>,
+    resp: Box<BlkResp>,
+    #[allow(unused)]
+    discard: Option<Box<BlkDiscard>>,
+}
 
-lazy_static! {
-    static ref QUEUE_FRAMES: Mutex<Vec<Arc<FrameTracker>>> = Mutex::new(Vec::new());
+/// 一个尚未完成的请求，可能被拆成多个描述符链
+struct InflightRequest {
+    req: BlockRequest,
+    /// 尚未完成的描述符链数
+    pending: usize,
+    /// 请求是否还在提交中，提交完所有的链之前不能结束
+    submitting: bool,
+    result: Result<(), isize>,
 }
 
-impl BlockDevice for VirtIOBlock {
-    fn read_block(&self, mut block_id: usize, buf: &mut [u8]) {
-        for buf in buf.chunks_mut(VIRT_IO_BLOCK_SZ) {
-            self.0
-                .lock()
-                .read_block(block_id, buf)
-                .expect("Error when reading VirtIOBlk");
-            block_id += 1;
+struct VirtIOBlockInner {
+    blk: VirtIOBlk<'static>,
+    chains: BTreeMap<u16, InflightChain>,
+    requests: BTreeMap<usize, InflightRequest>,
+    next_id: usize,
+    /// 数据已经写完、但因队列已满还没能放入`FUA`刷新的请求，按完成顺序排列
+    deferred_flushes: VecDeque<usize>,
+}
+
+pub struct VirtIOBlock(Mutex<VirtIOBlockInner>);
+
+fn resp_to_result(resp: &BlkResp) -> Result<(), isize> {
+    match resp.status() {
+        RespStatus::Ok => Ok(()),
+        RespStatus::Unsupported => Err(EOPNOTSUPP),
+        _ => Err(EIO),
+    }
+}
+
+impl VirtIOBlockInner {
+    /// 把请求`id`的一个描述符链放入队列
+    /// # 参数
+    /// + `op`: 描述符链的类型，`FUA`的刷新也通过`Flush`实现
+    /// + `block_id`: 数据段在设备上的起始块号
+    /// + `seg`: 读写的数据段
+    /// # 返回值
+    /// 队列已满时返回`Err(EAGAIN)`
+    fn add_chain(
+        &mut self,
+        id: usize,
+        op: BlockOp,
+        block_id: usize,
+        nr_blocks: usize,
+        seg: Option<BlockSegment>,
+    ) -> Result<(), isize> {
+        let sector = block_id * (BLOCK_SZ / VIRT_IO_SECTOR_SZ);
+        let mut header = Box::new(BlkReq::default());
+        let mut resp = Box::new(BlkResp::default());
+        let mut discard = None;
+        let token = unsafe {
+            match op {
+                BlockOp::Read => self.blk.read_blocks_nb(
+                    sector,
+                    &mut [seg.unwrap().as_mut_slice()],
+                    &mut header,
+                    &mut resp,
+                ),
+                BlockOp::Write => self.blk.write_blocks_nb(
+                    sector,
+                    &[seg.unwrap().as_slice()],
+                    &mut header,
+                    &mut resp,
+                ),
+                BlockOp::Flush => self.blk.flush_nb(&mut header, &mut resp),
+                BlockOp::Discard => {
+                    let mut range = Box::new(BlkDiscard::default());
+                    let token = self.blk.discard_nb(
+                        sector,
+                        nr_blocks * (BLOCK_SZ / VIRT_IO_SECTOR_SZ),
+                        &mut header,
+                        &mut range,
+                        &mut resp,
+                    );
+                    discard = Some(range);
+                    token
+                }
+            }
+        };
+        match token {
+            Ok(token) => {
+                self.chains.insert(
+                    token,
+                    InflightChain {
+                        parent: id,
+                        header,
+                        resp,
+                        discard,
+                    },
+                );
+                self.requests.get_mut(&id).unwrap().pending += 1;
+                Ok(())
+            }
+            Err(Error::BufferTooSmall) => Err(EAGAIN),
+            Err(_) => Err(EIO),
+        }
+    }
+
+    /// 提交中的请求遇到队列已满时调用，回收已完成的链直到有空闲的描述符
+    fn add_chain_wait(
+        &mut self,
+        id: usize,
+        op: BlockOp,
+        block_id: usize,
+        nr_blocks: usize,
+        seg: Option<BlockSegment>,
+        done: &mut Vec<(BlockRequest, Result<(), isize>)>,
+    ) -> Result<(), isize> {
+        loop {
+            match self.add_chain(id, op, block_id, nr_blocks, seg) {
+                Err(EAGAIN) => {
+                    if self.reap(done) == 0 {
+                        core::hint::spin_loop();
+                    }
+                }
+                result => return result,
+            }
         }
     }
-    fn write_block(&self, mut block_id: usize, buf: &[u8]) {
-        for buf in buf.chunks(VIRT_IO_BLOCK_SZ) {
-            self.0
-                .lock()
-                .write_block(block_id, buf)
-                .expect("Error when writing VirtIOBlk");
-            block_id += 1;
+
+    /// 请求的所有描述符链都已完成，需要`FUA`时再追加一次刷新
+    fn try_finish(&mut self, id: usize, done: &mut Vec<(BlockRequest, Result<(), isize>)>) {
+        let request = self.requests.get_mut(&id).unwrap();
+        if request.pending != 0 || request.submitting {
+            return;
+        }
+        if request.result.is_ok()
+            && request.req.flags.contains(BlockReqFlags::FUA)
+            && self.blk.supports_flush()
+        {
+            request.req.flags.remove(BlockReqFlags::FUA);
+            match self.add_chain(id, BlockOp::Flush, 0, 0, None) {
+                Ok(()) => return,
+                // 同一批完成的链可能已经把空出的描述符用掉了，留到下次回收后再试
+                Err(EAGAIN) => {
+                    self.deferred_flushes.push_back(id);
+                    return;
+                }
+                Err(errno) => self.requests.get_mut(&id).unwrap().result = Err(errno),
+            }
         }
+        let request = self.requests.remove(&id).unwrap();
+        done.push((request.req, request.result));
+    }
+
+    /// 按顺序重新放入被推迟的`FUA`刷新，队列再次满时停下，剩下的留到下次回收
+    fn submit_deferred_flushes(&mut self, done: &mut Vec<(BlockRequest, Result<(), isize>)>) {
+        while let Some(&id) = self.deferred_flushes.front() {
+            match self.add_chain(id, BlockOp::Flush, 0, 0, None) {
+                Err(EAGAIN) => break,
+                Ok(()) => {}
+                Err(errno) => {
+                    let request = self.requests.remove(&id).unwrap();
+                    done.push((request.req, Err(errno)));
+                }
+            }
+            self.deferred_flushes.pop_front();
+        }
+    }
+
+    /// 回收设备已经完成的描述符链，结束的请求被放入`done`，返回回收的链数
+    fn reap(&mut self, done: &mut Vec<(BlockRequest, Result<(), isize>)>) -> usize {
+        let mut count = 0;
+        while let Ok(token) = self.blk.pop_used() {
+            count += 1;
+            let chain = match self.chains.remove(&token) {
+                Some(chain) => chain,
+                None => {
+                    log::error!("[virtio_blk] unknown token {}", token);
+                    continue;
+                }
+            };
+            let result = resp_to_result(&chain.resp);
+            let request = self.requests.get_mut(&chain.parent).unwrap();
+            request.pending -= 1;
+            if let Err(errno) = result {
+                log::warn!("[virtio_blk] request failed, errno: {}", errno);
+                if request.result.is_ok() {
+                    request.result = Err(errno);
+                }
+            }
+            self.try_finish(chain.parent, done);
+        }
+        self.submit_deferred_flushes(done);
+        count
+    }
+}
+
+/// 在释放设备锁之后调用完成回调，回调中可以再次提交请求
+fn complete_all(done: Vec<(BlockRequest, Result<(), isize>)>) -> usize {
+    let count = done.len();
+    for (req, result) in done {
+        req.complete(result);
+    }
+    count
+}
+
+impl BlockDevice for VirtIOBlock {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.try_read_block(block_id, buf)
+            .expect("Error when reading VirtIOBlk");
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.try_write_block(block_id, buf)
+            .expect("Error when writing VirtIOBlk");
+    }
+    fn try_read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), isize> {
+        submit_and_wait(
+            self,
+            BlockRequest::read(block_id, vec![BlockSegment::new(buf)]),
+        )
+    }
+    fn try_write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), isize> {
+        submit_and_wait(
+            self,
+            BlockRequest::write(block_id, vec![BlockSegment::new(buf)]),
+        )
+    }
+    fn queue_depth(&self) -> usize {
+        self.0.lock().blk.virt_queue_size() as usize / DESC_PER_CHAIN
+    }
+    /// 每个数据段对应一个描述符链，多个请求可以同时在队列中，由`poll`回收
+    fn submit(&self, req: BlockRequest) -> Result<(), isize> {
+        req.check()?;
+        let (supports_flush, supports_discard) = {
+            let inner = self.0.lock();
+            (inner.blk.supports_flush(), inner.blk.supports_discard())
+        };
+        match req.op {
+            BlockOp::Discard if !supports_discard => return Err(EOPNOTSUPP),
+            // 没有写缓存的设备不需要刷新
+            BlockOp::Flush if !supports_flush => {
+                req.complete(Ok(()));
+                return Ok(());
+            }
+            _ => {}
+        }
+        if req.flags.contains(BlockReqFlags::PREFLUSH) {
+            self.flush()?;
+        }
+        let op = req.op;
+        let block_id = req.block_id;
+        let nr_blocks = req.nr_blocks;
+        let segments = req.segments.clone();
+        let mut done = Vec::new();
+        {
+            let mut inner = self.0.lock();
+            let id = inner.next_id;
+            inner.next_id += 1;
+            inner.requests.insert(
+                id,
+                InflightRequest {
+                    req,
+                    pending: 0,
+                    submitting: true,
+                    result: Ok(()),
+                },
+            );
+            let result = match op {
+                BlockOp::Read | BlockOp::Write => {
+                    let mut block_id = block_id;
+                    let mut result = Ok(());
+                    for seg in segments {
+                        result = inner.add_chain_wait(id, op, block_id, 0, Some(seg), &mut done);
+                        if result.is_err() {
+                            break;
+                        }
+                        block_id += seg.len() / BLOCK_SZ;
+                    }
+                    result
+                }
+                BlockOp::Flush | BlockOp::Discard => {
+                    inner.add_chain_wait(id, op, block_id, nr_blocks, None, &mut done)
+                }
+            };
+            let request = inner.requests.get_mut(&id).unwrap();
+            request.submitting = false;
+            if let Err(errno) = result {
+                request.result = Err(errno);
+            }
+            inner.try_finish(id, &mut done);
+        }
+        complete_all(done);
+        Ok(())
+    }
+    fn poll(&self) -> usize {
+        let mut done = Vec::new();
+        self.0.lock().reap(&mut done);
+        complete_all(done)
+    }
+    fn flush(&self) -> Result<(), isize> {
+        submit_and_wait(self, BlockRequest::flush())
+    }
+    fn discard(&self, block_id: usize, cnt: usize) -> Result<(), isize> {
+        submit_and_wait(self, BlockRequest::discard(block_id, cnt))
     }
 }
 
 impl VirtIOBlock {
+    /// 使用启动时发现的第一个virtio-mmio块设备，没有接设备的槽位ID为0
     #[allow(unused)]
     pub fn new() -> Self {
-        Self(Mutex::new(
-            VirtIOBlk::new(unsafe { &mut *(VIRTIO0 as *mut VirtIOHeader) }).unwrap(),
-        ))
+        let header = boot_info()
+            .virtio_mmio
+            .iter()
+            .map(|region| unsafe { &mut *(region.start as *mut VirtIOHeader) })
+            .find(|header| header.verify() && header.device_type() == DeviceType::Block)
+            .expect("[virtio_blk] no virtio-mmio block device");
+        Self(Mutex::new(VirtIOBlockInner {
+            blk: VirtIOBlk::new(header).unwrap(),
+            chains: BTreeMap::new(),
+            requests: BTreeMap::new(),
+            next_id: 0,
+            deferred_flushes: VecDeque::new(),
+        }))
     }
 }
 
 #[no_mangle]
 pub extern "C" fn virtio_dma_alloc(pages: usize) -> PhysAddr {
-    let mut ppn_base = PhysPageNum(0);
-    for i in 0..pages {
-        let frame = frame_alloc().unwrap();
-        if i == 0 {
-            ppn_base = frame.ppn;
-        }
-        assert_eq!(frame.ppn.0, ppn_base.0 + i);
-        QUEUE_FRAMES.lock().push(frame);
-    }
-    ppn_base.into()
+    frame_alloc_contiguous(pages, ZoneType::Dma32)
+        .expect("[virtio_dma_alloc] out of contiguous frames")
+        .into()
 }
 
 #[no_mangle]
 pub extern "C" fn virtio_dma_dealloc(pa: PhysAddr, pages: usize) -> i32 {
-    let mut ppn_base: PhysPageNum = pa.into();
-    for _ in 0..pages {
-        frame_dealloc(ppn_base);
-        ppn_base.step();
-    }
+    frame_dealloc_contiguous(pa.into(), pages);
     0
 }
 
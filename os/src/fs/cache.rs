@@ -1,11 +1,20 @@
 use crate::config::MEMORY_HIGH_BASE;
 use crate::config::{PAGE_SIZE, PAGE_SIZE_BITS};
+use crate::drivers::block::{BlockRequest, BlockSegment};
 use crate::hal::{BLOCK_SZ, BUFFER_CACHE_NUM};
-use crate::mm::{frame_alloc, FrameTracker, KERNEL_SPACE};
-use alloc::sync::Arc;
+use crate::mm::{
+    frame_alloc, lru_add_file, mark_page_accessed, page_clear_flags, page_set_flags, ppn_to_page,
+    FrameTracker, PageFlags, PageOwner, KERNEL_SPACE,
+};
+use alloc::boxed::Box;
+use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
+use core::ops::Range;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use spin::Mutex;
 
+use super::readahead::RA_MAX_PAGES;
+use super::writeback::{account_page_cleaned, account_page_dirtied};
 use super::BlockDevice;
 
 pub trait Cache {
@@ -24,7 +33,7 @@ pub trait Cache {
     /// # 参数
     /// + `block_ids`: cache内的块号
     /// + `block_device`: 块设备对象
-    fn sync(&self, _block_ids: Vec<usize>, _block_device: &Arc<dyn BlockDevice>) {}
+    fn sync(&mut self, _block_ids: Vec<usize>, _block_device: &Arc<dyn BlockDevice>) {}
 }
 
 /// 优先级上限
@@ -124,6 +133,21 @@ impl BlockCacheManager {
             }
         }
     }
+    /// 写回所有脏块，但保留在缓存中
+    /// # 返回值
+    /// 写回的块数量
+    pub fn sync_all(&self, block_device: &Arc<dyn BlockDevice>) -> usize {
+        let mut written = 0;
+        for buffer_cache in &self.cache_pool {
+            let mut locked = buffer_cache.lock();
+            if locked.dirty && locked.block_id != usize::MAX {
+                block_device.write_block(locked.block_id, locked.buffer.as_ref());
+                locked.dirty = false;
+                written += 1;
+            }
+        }
+        written
+    }
     fn alloc_buffer_cache(&self, block_device: &Arc<dyn BlockDevice>) -> Arc<Mutex<BufferCache>> {
         loop {
             for buffer_cache in &self.cache_pool {
@@ -200,10 +224,21 @@ pub struct PageCache {
     /// 每次发生oom的情况，这个数字（优先级）会减少1，并且至少为0
     /// 当其变为0的时候，并且Arc的强引用数量为1（one in inode），这个PageCache会被释放
     priority: usize,
+    /// 页面内容是否比磁盘上的新，所有经过`modify`的写入都会置位
+    dirty: bool,
     page_ptr: &'static mut [u8; PAGE_SIZE],
     tracker: Arc<FrameTracker>,
 }
 
+impl Drop for PageCache {
+    fn drop(&mut self) {
+        // 未写回就被丢弃（如截断文件）的脏页不再计数
+        if self.dirty {
+            account_page_cleaned();
+        }
+    }
+}
+
 impl Cache for PageCache {
     fn read<T, V>(&self, offset: usize, f: impl FnOnce(&T) -> V) -> V {
         debug_assert!(offset.saturating_add(core::mem::size_of::<T>()) <= PAGE_SIZE);
@@ -219,6 +254,7 @@ impl Cache for PageCache {
 
     fn modify<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
         debug_assert!(offset.saturating_add(core::mem::size_of::<T>()) <= PAGE_SIZE);
+        self.mark_dirty();
         f(unsafe {
             self.page_ptr
                 .as_mut_ptr()
@@ -229,27 +265,24 @@ impl Cache for PageCache {
         })
     }
 
-    fn sync(&self, block_ids: Vec<usize>, block_device: &Arc<dyn BlockDevice>) {
-        let lock = KERNEL_SPACE.try_lock();
-        match lock {
-            Some(lock) => {
-                if !lock.is_dirty(self.tracker.ppn).unwrap() {
-                    return;
-                }
-            }
-            None => {}
+    fn sync(&mut self, block_ids: Vec<usize>, block_device: &Arc<dyn BlockDevice>) {
+        if !self.dirty {
+            return;
         }
-        self.write_back(block_ids, block_device)
+        self.write_back(block_ids, block_device);
+        self.mark_clean();
     }
 }
 
 impl PageCache {
     pub fn new() -> Self {
         let tracker = unsafe { crate::mm::frame_alloc_uninit().unwrap() };
+        page_set_flags(tracker.ppn, PageFlags::PAGE_CACHE);
         let page_ptr = (tracker.ppn.0 << PAGE_SIZE_BITS) as *mut [u8; PAGE_SIZE];
         let page_ptr = unsafe { page_ptr.as_mut().unwrap() };
         Self {
             priority: 0,
+            dirty: false,
             page_ptr,
             tracker,
         }
@@ -259,6 +292,37 @@ impl PageCache {
         self.tracker.clone()
     }
 
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// 页面被共享映射直接写入（页表项的脏位置位），与`modify`一样计为脏页
+    pub fn mark_dirty(&mut self) {
+        if !self.dirty {
+            self.dirty = true;
+            page_set_flags(self.tracker.ppn, PageFlags::DIRTY);
+            account_page_dirtied();
+        }
+    }
+
+    /// 页面内容已经与磁盘一致（写回之后，或写入已经直接落盘）
+    pub fn mark_clean(&mut self) {
+        if self.dirty {
+            self.dirty = false;
+            page_clear_flags(self.tracker.ppn, PageFlags::DIRTY);
+            account_page_cleaned();
+        }
+    }
+
+    /// 在页描述符中标记页面正在与磁盘交换数据
+    fn set_locked(&self, locked: bool) {
+        if locked {
+            page_set_flags(self.tracker.ppn, PageFlags::LOCKED);
+        } else {
+            page_clear_flags(self.tracker.ppn, PageFlags::LOCKED);
+        }
+    }
+
     /// 读取一个缓存
     /// # 参数
     /// + block_id：块号
@@ -272,6 +336,7 @@ impl PageCache {
         // PAGE_BUFFERS 大小为 2
         // 也就是每页只有两块
         assert!(block_ids.len() <= PAGE_BUFFERS);
+        self.set_locked(true);
 
         // 初始化变量
         // 当前连续块序列的起始块号
@@ -322,6 +387,7 @@ impl PageCache {
         };
         block_device.read_block(start_block_id, buf);
         self.page_ptr[block_ids.len() * BUFFER_SIZE..].fill(0);
+        self.set_locked(false);
         #[cfg(feature = "loongarch64")]
         KERNEL_SPACE
             .lock()
@@ -334,47 +400,71 @@ impl PageCache {
         //     .unwrap();
     }
 
+    /// 把页内的块按磁盘上是否连续分组：(起始块号, 页内起始块下标, 块数)
+    fn block_runs(block_ids: &[usize]) -> Vec<(usize, usize, usize)> {
+        let mut runs: Vec<(usize, usize, usize)> = Vec::new();
+        for (buf_id, block_id) in block_ids.iter().enumerate() {
+            match runs.last_mut() {
+                Some((start_block_id, _, len)) if *start_block_id + *len == *block_id => *len += 1,
+                _ => runs.push((*block_id, buf_id, 1)),
+            }
+        }
+        runs
+    }
+
+    fn run_buf(&self, start_buf_id: usize, len: usize) -> &[u8] {
+        &self.page_ptr[start_buf_id * BUFFER_SIZE..(start_buf_id + len) * BUFFER_SIZE]
+    }
+
     /// 写回
     /// # 参数
     /// + block_ids: 块号
     /// + block_device: 块设备对象
     pub fn write_back(&self, block_ids: Vec<usize>, block_device: &Arc<dyn BlockDevice>) {
-        // 如果块号为空，直接返回
-        if block_ids.is_empty() {
-            return;
+        self.set_locked(true);
+        for (start_block_id, start_buf_id, len) in Self::block_runs(&block_ids) {
+            block_device.write_block(start_block_id, self.run_buf(start_buf_id, len));
         }
+        self.set_locked(false);
+    }
 
-        // 获取起始块号
-        let mut start_block_id = usize::MAX;
-        let mut con_length = 0;
-        let mut start_buf_id = 0;
-        for block_id in block_ids.iter() {
-            if con_length == 0 {
-                start_block_id = *block_id;
-                con_length = 1;
-            } else if *block_id != start_block_id + con_length {
-                let buf = unsafe {
-                    core::slice::from_raw_parts(
-                        self.page_ptr.as_ptr().add(start_buf_id * BUFFER_SIZE),
-                        con_length * BUFFER_SIZE,
-                    )
-                };
-                block_device.write_block(start_block_id, buf);
-
-                start_buf_id += con_length;
-                start_block_id = *block_id;
-                con_length = 1;
-            } else {
-                con_length += 1;
+    /// 异步写回，每组连续块提交一个写请求，请求完成时`inflight`减一
+    /// # 注意
+    /// 调用者需要持有页缓存的锁直到`inflight`归零，再清除页描述符中的`LOCKED`
+    fn submit_write_back(
+        &self,
+        block_ids: Vec<usize>,
+        block_device: &Arc<dyn BlockDevice>,
+        inflight: &Arc<AtomicUsize>,
+    ) {
+        self.set_locked(true);
+        for (start_block_id, start_buf_id, len) in Self::block_runs(&block_ids) {
+            inflight.fetch_add(1, Ordering::AcqRel);
+            let inflight = inflight.clone();
+            let req = BlockRequest::write(
+                start_block_id,
+                alloc::vec![BlockSegment::new(self.run_buf(start_buf_id, len))],
+            )
+            .on_complete(Box::new(move |result| {
+                if let Err(errno) = result {
+                    log::error!("[page_cache] write back failed, errno: {}", errno);
+                }
+                inflight.fetch_sub(1, Ordering::Release);
+            }));
+            if let Err(errno) = block_device.submit(req) {
+                log::error!("[page_cache] submit failed, errno: {}", errno);
+                inflight.fetch_sub(1, Ordering::Release);
             }
         }
-        let buf = unsafe {
-            core::slice::from_raw_parts(
-                self.page_ptr.as_ptr().add(start_buf_id * BUFFER_SIZE),
-                con_length * BUFFER_SIZE,
-            )
-        };
-        block_device.write_block(start_block_id, buf);
+    }
+}
+
+/// 轮询块设备直到`inflight`个请求全部完成
+fn wait_inflight(inflight: &AtomicUsize, block_device: &Arc<dyn BlockDevice>) {
+    while inflight.load(Ordering::Acquire) != 0 {
+        if block_device.poll() == 0 {
+            core::hint::spin_loop();
+        }
     }
 }
 
@@ -383,16 +473,28 @@ pub struct PageCacheManager {
     cache_pool: Mutex<Vec<Option<Arc<Mutex<PageCache>>>>>,
     /// 已经分配的缓存
     allocated_cache: Mutex<Vec<usize>>,
+    /// 指向自身，页描述符通过它找到页所在的缓存池，回收时删除单个页
+    this: Weak<PageCacheManager>,
 }
 
 impl PageCacheManager {
     pub const CACHE_SZ: usize = PAGE_SIZE;
 
-    pub fn new() -> Self {
-        Self {
+    pub fn new() -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
             cache_pool: Mutex::new(Vec::new()),
             allocated_cache: Mutex::new(Vec::new()),
+            this: this.clone(),
+        })
+    }
+
+    /// 新的缓存放入缓存池后调用，记录它的位置并加入LRU链表
+    fn attach(&self, page_cache: &PageCache, inner_cache_id: usize) {
+        let ppn = page_cache.tracker.ppn;
+        if let Some(page) = ppn_to_page(ppn) {
+            page.set_owner(self.this.clone(), inner_cache_id);
         }
+        lru_add_file(ppn);
     }
 
     #[allow(unused)]
@@ -407,6 +509,7 @@ impl PageCacheManager {
             if locked.priority < PRIORITY_UPPERBOUND {
                 locked.priority += 1;
             }
+            mark_page_accessed(locked.tracker.ppn);
         }
         page_cache
     }
@@ -447,6 +550,7 @@ impl PageCacheManager {
                 let mut new_page_cache = PageCache::new();
                 // 关键步骤：从块设备对象加载数据，块号由neighbor闭包提供
                 new_page_cache.read_in(neighbor(), &block_device);
+                self.attach(&new_page_cache, inner_cache_id);
                 // 包装成线程安全对象
                 let new_page_cache = Arc::new(Mutex::new(new_page_cache));
                 // 将缓存池存入池中
@@ -463,12 +567,132 @@ impl PageCacheManager {
         if inner_lock.priority < PRIORITY_UPPERBOUND {
             inner_lock.priority += 1;
         }
+        mark_page_accessed(inner_lock.tracker.ppn);
         // 释放锁
         drop(inner_lock);
         // 返回缓存
         page_cache
     }
 
+    /// 预读，把`range`内尚未缓存的页读入，每批至多`RA_MAX_PAGES`页
+    /// # 参数
+    /// + range: cache内的页号范围
+    /// + neighbor: 闭包，返回一个缓存对应的块号
+    /// + block_device: 块设备对象
+    /// # 返回值
+    /// 新读入的页数量
+    pub fn readahead<FUNC>(
+        &self,
+        range: Range<usize>,
+        neighbor: FUNC,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize
+    where
+        FUNC: Fn(usize) -> Vec<usize>,
+    {
+        let mut read = 0;
+        let mut start = range.start;
+        while start < range.end {
+            let end = (start + RA_MAX_PAGES).min(range.end);
+            read += self.readahead_batch(start..end, &neighbor, block_device);
+            start = end;
+        }
+        read
+    }
+
+    /// 一次性读入`range`内尚未缓存的页
+    /// 块号连续的多个页合并为一次块设备读取，再分发到各个页中
+    fn readahead_batch<FUNC>(
+        &self,
+        range: Range<usize>,
+        neighbor: &FUNC,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize
+    where
+        FUNC: Fn(usize) -> Vec<usize>,
+    {
+        let missing: Vec<usize> = {
+            let lock = self.cache_pool.lock();
+            range
+                .filter(|inner_cache_id| lock.get(*inner_cache_id).map_or(true, |c| c.is_none()))
+                .collect()
+        };
+        if missing.is_empty() {
+            return 0;
+        }
+        crate::mm::frame_reserve(missing.len());
+        let mut pages: Vec<(usize, PageCache, Vec<usize>)> = missing
+            .into_iter()
+            .map(|inner_cache_id| (inner_cache_id, PageCache::new(), neighbor(inner_cache_id)))
+            .filter(|(_, _, block_ids)| !block_ids.is_empty())
+            .collect();
+        // 连续块组成的读请求：(起始块号, 块数, [(页下标, 页内块下标, 块数)])
+        let mut runs: Vec<(usize, usize, Vec<(usize, usize, usize)>)> = Vec::new();
+        for (page_idx, (_, _, block_ids)) in pages.iter().enumerate() {
+            for (buf_id, block_id) in block_ids.iter().enumerate() {
+                match runs.last_mut() {
+                    Some((start, len, segs)) if *start + *len == *block_id => {
+                        *len += 1;
+                        match segs.last_mut() {
+                            Some((idx, seg_start, seg_len))
+                                if *idx == page_idx && *seg_start + *seg_len == buf_id =>
+                            {
+                                *seg_len += 1
+                            }
+                            _ => segs.push((page_idx, buf_id, 1)),
+                        }
+                    }
+                    _ => runs.push((*block_id, 1, alloc::vec![(page_idx, buf_id, 1)])),
+                }
+            }
+        }
+        // 每组连续块作为一个分散读请求提交，所有请求同时在设备队列中
+        let inflight = Arc::new(AtomicUsize::new(runs.len()));
+        block_device.plug();
+        for (start_block_id, _, segs) in runs {
+            let segments = segs
+                .into_iter()
+                .map(|(page_idx, buf_id, seg_len)| {
+                    BlockSegment::new(
+                        &pages[page_idx].1.page_ptr
+                            [buf_id * BUFFER_SIZE..(buf_id + seg_len) * BUFFER_SIZE],
+                    )
+                })
+                .collect();
+            let inflight = inflight.clone();
+            let req =
+                BlockRequest::read(start_block_id, segments).on_complete(Box::new(move |result| {
+                    if let Err(errno) = result {
+                        log::error!("[readahead] read failed, errno: {}", errno);
+                    }
+                    inflight.fetch_sub(1, Ordering::Release);
+                }));
+            if let Err(errno) = block_device.submit(req) {
+                log::error!("[readahead] submit failed, errno: {}", errno);
+                inflight.fetch_sub(1, Ordering::Release);
+            }
+        }
+        block_device.unplug();
+        wait_inflight(&inflight, block_device);
+        let mut lock = self.cache_pool.lock();
+        let mut read = 0;
+        for (inner_cache_id, mut page, block_ids) in pages {
+            page.page_ptr[block_ids.len() * BUFFER_SIZE..].fill(0);
+            while inner_cache_id >= lock.len() {
+                lock.push(None);
+            }
+            // 读取期间可能已经被其他人缓存
+            if lock[inner_cache_id].is_some() {
+                continue;
+            }
+            self.attach(&page, inner_cache_id);
+            lock[inner_cache_id] = Some(Arc::new(Mutex::new(page)));
+            self.allocated_cache.lock().push(inner_cache_id);
+            read += 1;
+        }
+        read
+    }
+
     pub fn oom<FUNC>(&self, neighbor: FUNC, block_device: &Arc<dyn BlockDevice>) -> usize
     where
         FUNC: Fn(usize) -> Vec<usize>,
@@ -479,7 +703,11 @@ impl PageCacheManager {
 
         for inner_cache_id in self.allocated_cache.lock().iter() {
             let inner_cache_id = *inner_cache_id;
-            let inner = lock[inner_cache_id].as_ref().unwrap();
+            let inner = match lock[inner_cache_id].as_ref() {
+                Some(inner) => inner,
+                // 已经被页面回收删除
+                None => continue,
+            };
             if Arc::strong_count(inner) > 1 {
                 new_allocated_cache.push(inner_cache_id);
                 continue;
@@ -502,6 +730,49 @@ impl PageCacheManager {
         dropped
     }
 
+    /// 写回所有脏页，但保留在缓存中
+    /// # 参数
+    /// + neighbor: 闭包，返回一个缓存对应的块号
+    /// + block_device: 块设备对象
+    /// # 返回值
+    /// 写回的页数量
+    pub fn sync_all<FUNC>(&self, neighbor: FUNC, block_device: &Arc<dyn BlockDevice>) -> usize
+    where
+        FUNC: Fn(usize) -> Vec<usize>,
+    {
+        let caches: Vec<(usize, Arc<Mutex<PageCache>>)> = {
+            let lock = self.cache_pool.lock();
+            self.allocated_cache
+                .lock()
+                .iter()
+                .filter_map(|inner_cache_id| {
+                    lock[*inner_cache_id]
+                        .as_ref()
+                        .map(|cache| (*inner_cache_id, cache.clone()))
+                })
+                .collect()
+        };
+        // 所有脏页的写请求一起提交，由 I/O 调度器合并相邻的请求
+        let inflight = Arc::new(AtomicUsize::new(0));
+        let mut locked = Vec::new();
+        block_device.plug();
+        for (inner_cache_id, cache) in caches.iter() {
+            let inner_lock = cache.lock();
+            if inner_lock.dirty {
+                inner_lock.submit_write_back(neighbor(*inner_cache_id), block_device, &inflight);
+                locked.push(inner_lock);
+            }
+        }
+        block_device.unplug();
+        wait_inflight(&inflight, block_device);
+        let written = locked.len();
+        for mut inner_lock in locked {
+            inner_lock.set_locked(false);
+            inner_lock.mark_clean();
+        }
+        written
+    }
+
     pub fn notify_new_size(&self, new_size: usize) {
         let mut lock = self.cache_pool.lock();
         let new_pages = (new_size + PAGE_SIZE - 1) / PAGE_SIZE;
@@ -519,3 +790,30 @@ impl PageCacheManager {
             .retain(|cache_id| *cache_id < new_pages);
     }
 }
+
+impl PageOwner for PageCacheManager {
+    /// 只删除没有被映射、也没有被其他人持有的干净页
+    fn evict(&self, index: usize) -> bool {
+        // 分配页缓存时会持有缓存池的锁进入回收
+        let mut lock = match self.cache_pool.try_lock() {
+            Some(lock) => lock,
+            None => return false,
+        };
+        let evictable = match lock.get(index) {
+            Some(Some(page_cache)) => {
+                Arc::strong_count(page_cache) == 1
+                    && page_cache.try_lock().map_or(false, |inner| {
+                        !inner.dirty && Arc::strong_count(&inner.tracker) == 1
+                    })
+            }
+            _ => false,
+        };
+        if evictable {
+            lock[index] = None;
+            self.allocated_cache
+                .lock()
+                .retain(|inner_cache_id| *inner_cache_id != index);
+        }
+        evictable
+    }
+}
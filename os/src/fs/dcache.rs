@@ -0,0 +1,318 @@
+use super::directory_tree::DirectoryTreeNode;
+use alloc::{
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use lazy_static::*;
+use spin::Mutex;
+
+/// 目录项缓存的容量上限
+const DCACHE_CAPACITY: usize = 2048;
+/// 哈希桶数量，必须是2的幂
+const DCACHE_BUCKETS: usize = 512;
+/// 空索引
+const NIL: usize = usize::MAX;
+
+lazy_static! {
+    /// 全局目录项缓存，以（父目录，文件名）为键
+    pub static ref DENTRY_CACHE: Mutex<DentryCache> = Mutex::new(DentryCache::new());
+}
+
+/// 目录项缓存的查找结果
+#[derive(Clone)]
+pub enum Dentry {
+    /// 文件存在，指向目录树节点
+    Positive(Weak<DirectoryTreeNode>),
+    /// 文件不存在（负缓存）
+    Negative,
+}
+
+struct DentryNode {
+    /// 父目录，持有弱引用可以保证父目录地址在缓存项存活期间不会被复用
+    parent: Weak<DirectoryTreeNode>,
+    name: String,
+    hash: usize,
+    dentry: Dentry,
+    /// LRU 链表中的前驱（更新）
+    prev: usize,
+    /// LRU 链表中的后继（更旧）
+    next: usize,
+    /// 哈希桶链表中的后继
+    hnext: usize,
+}
+
+/// 目录项缓存
+/// + 哈希桶 + 拉链法查找
+/// + 以下标串起的双向链表维护 LRU 顺序，表头为最近使用的项
+pub struct DentryCache {
+    nodes: Vec<Option<DentryNode>>,
+    free: Vec<usize>,
+    buckets: Vec<usize>,
+    head: usize,
+    tail: usize,
+    len: usize,
+    hits: usize,
+    misses: usize,
+}
+
+/// FNV-1a 哈希，混合父目录地址与文件名
+fn dentry_hash(parent: usize, name: &str) -> usize {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in parent.to_le_bytes().iter().chain(name.as_bytes().iter()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash as usize
+}
+
+#[allow(unused)]
+impl DentryCache {
+    pub fn new() -> Self {
+        let mut buckets = Vec::with_capacity(DCACHE_BUCKETS);
+        buckets.resize(DCACHE_BUCKETS, NIL);
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            buckets,
+            head: NIL,
+            tail: NIL,
+            len: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn node(&self, idx: usize) -> &DentryNode {
+        self.nodes[idx].as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, idx: usize) -> &mut DentryNode {
+        self.nodes[idx].as_mut().unwrap()
+    }
+
+    /// 在哈希桶中查找，返回节点下标
+    fn find(&self, parent: usize, name: &str, hash: usize) -> Option<usize> {
+        let mut idx = self.buckets[hash & (DCACHE_BUCKETS - 1)];
+        while idx != NIL {
+            let node = self.node(idx);
+            if node.hash == hash
+                && Weak::as_ptr(&node.parent) as usize == parent
+                && node.name == name
+            {
+                return Some(idx);
+            }
+            idx = node.hnext;
+        }
+        None
+    }
+
+    /// 从 LRU 链表中摘下
+    fn lru_unlink(&mut self, idx: usize) {
+        let (prev, next) = {
+            let node = self.node(idx);
+            (node.prev, node.next)
+        };
+        if prev != NIL {
+            self.node_mut(prev).next = next;
+        } else {
+            self.head = next;
+        }
+        if next != NIL {
+            self.node_mut(next).prev = prev;
+        } else {
+            self.tail = prev;
+        }
+    }
+
+    /// 插入到 LRU 链表表头
+    fn lru_push_front(&mut self, idx: usize) {
+        let head = self.head;
+        {
+            let node = self.node_mut(idx);
+            node.prev = NIL;
+            node.next = head;
+        }
+        if head != NIL {
+            self.node_mut(head).prev = idx;
+        } else {
+            self.tail = idx;
+        }
+        self.head = idx;
+    }
+
+    /// 从哈希桶和 LRU 链表中删除一个节点
+    fn remove_at(&mut self, idx: usize) {
+        self.lru_unlink(idx);
+        let (hash, hnext) = {
+            let node = self.node(idx);
+            (node.hash, node.hnext)
+        };
+        let bucket = hash & (DCACHE_BUCKETS - 1);
+        if self.buckets[bucket] == idx {
+            self.buckets[bucket] = hnext;
+        } else {
+            let mut cur = self.buckets[bucket];
+            while self.node(cur).hnext != idx {
+                cur = self.node(cur).hnext;
+            }
+            self.node_mut(cur).hnext = hnext;
+        }
+        self.nodes[idx] = None;
+        self.free.push(idx);
+        self.len -= 1;
+    }
+
+    /// 查找（父目录，文件名）对应的缓存项
+    /// # 返回值
+    /// + `None`：未命中，需要访问具体文件系统
+    /// + `Some(Dentry::Negative)`：确认文件不存在
+    /// + `Some(Dentry::Positive(_))`：文件存在，且对应节点仍然存活
+    pub fn lookup(&mut self, parent: &DirectoryTreeNode, name: &str) -> Option<Dentry> {
+        let parent = parent as *const DirectoryTreeNode as usize;
+        let hash = dentry_hash(parent, name);
+        let idx = match self.find(parent, name, hash) {
+            Some(idx) => idx,
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        };
+        if let Dentry::Positive(node) = &self.node(idx).dentry {
+            // 节点已经被释放，缓存项失效
+            if node.strong_count() == 0 {
+                self.remove_at(idx);
+                self.misses += 1;
+                return None;
+            }
+        }
+        self.lru_unlink(idx);
+        self.lru_push_front(idx);
+        self.hits += 1;
+        Some(self.node(idx).dentry.clone())
+    }
+
+    /// 插入或更新一个缓存项，容量不足时淘汰最久未使用的项
+    pub fn insert(&mut self, parent: &Weak<DirectoryTreeNode>, name: &str, dentry: Dentry) {
+        let parent_ptr = Weak::as_ptr(parent) as usize;
+        let hash = dentry_hash(parent_ptr, name);
+        if let Some(idx) = self.find(parent_ptr, name, hash) {
+            self.node_mut(idx).dentry = dentry;
+            self.lru_unlink(idx);
+            self.lru_push_front(idx);
+            return;
+        }
+        if self.len >= DCACHE_CAPACITY {
+            self.shrink(1);
+        }
+        let bucket = hash & (DCACHE_BUCKETS - 1);
+        let node = DentryNode {
+            parent: parent.clone(),
+            name: name.to_string(),
+            hash,
+            dentry,
+            prev: NIL,
+            next: NIL,
+            hnext: self.buckets[bucket],
+        };
+        let idx = match self.free.pop() {
+            Some(idx) => {
+                self.nodes[idx] = Some(node);
+                idx
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        self.buckets[bucket] = idx;
+        self.lru_push_front(idx);
+        self.len += 1;
+    }
+
+    /// 使（父目录，文件名）对应的缓存项失效，用于创建、删除和重命名
+    pub fn invalidate(&mut self, parent: &DirectoryTreeNode, name: &str) {
+        let parent = parent as *const DirectoryTreeNode as usize;
+        let hash = dentry_hash(parent, name);
+        if let Some(idx) = self.find(parent, name, hash) {
+            self.remove_at(idx);
+        }
+    }
+
+    /// 使某个目录下的所有缓存项失效
+    pub fn invalidate_dir(&mut self, parent: &DirectoryTreeNode) {
+        let parent = parent as *const DirectoryTreeNode as usize;
+        let mut idx = self.head;
+        while idx != NIL {
+            let next = self.node(idx).next;
+            if Weak::as_ptr(&self.node(idx).parent) as usize == parent {
+                self.remove_at(idx);
+            }
+            idx = next;
+        }
+    }
+
+    /// 从 LRU 表尾开始淘汰至多`count`个缓存项，返回实际淘汰的数量
+    pub fn shrink(&mut self, count: usize) -> usize {
+        let mut dropped = 0;
+        while dropped < count && self.tail != NIL {
+            self.remove_at(self.tail);
+            dropped += 1;
+        }
+        if self.len == 0 {
+            self.nodes.clear();
+            self.nodes.shrink_to_fit();
+            self.free.clear();
+            self.free.shrink_to_fit();
+        }
+        dropped
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// 命中与未命中次数，调试使用
+    pub fn stat(&self) -> (usize, usize) {
+        (self.hits, self.misses)
+    }
+}
+
+/// 查找目录项缓存
+pub fn dcache_lookup(parent: &DirectoryTreeNode, name: &str) -> Option<Dentry> {
+    DENTRY_CACHE.lock().lookup(parent, name)
+}
+
+/// 记录一个存在的文件
+pub fn dcache_insert_positive(
+    parent: &Weak<DirectoryTreeNode>,
+    name: &str,
+    child: &Arc<DirectoryTreeNode>,
+) {
+    DENTRY_CACHE
+        .lock()
+        .insert(parent, name, Dentry::Positive(Arc::downgrade(child)));
+}
+
+/// 记录一个不存在的文件
+pub fn dcache_insert_negative(parent: &Weak<DirectoryTreeNode>, name: &str) {
+    DENTRY_CACHE.lock().insert(parent, name, Dentry::Negative);
+}
+
+/// 使缓存项失效
+pub fn dcache_invalidate(parent: &DirectoryTreeNode, name: &str) {
+    DENTRY_CACHE.lock().invalidate(parent, name);
+}
+
+/// 使某个目录下的所有缓存项失效
+pub fn dcache_invalidate_dir(parent: &DirectoryTreeNode) {
+    DENTRY_CACHE.lock().invalidate_dir(parent);
+}
+
+/// 内存不足时淘汰一半的缓存项
+#[cfg(feature = "oom_handler")]
+pub fn dcache_oom() -> usize {
+    let mut lock = DENTRY_CACHE.lock();
+    let count = (lock.len() + 1) / 2;
+    lock.shrink(count)
+}
@@ -0,0 +1,292 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::{format, string::String, sync::Arc, vec};
+use spin::Mutex;
+
+use crate::{
+    drivers::block::{BlockStats, BLOCK_DEVICE, BLOCK_DEVICE_NAME, LATENCY_BUCKETS},
+    fs::{directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat, StatMode},
+    hal::BLOCK_SZ,
+    mm::UserBuffer,
+    syscall::errno::{EINVAL, ENOTDIR},
+};
+
+/// `/proc/diskstats`：与 Linux 相同格式的块设备统计
+pub fn diskstats() -> String {
+    let stats = BLOCK_DEVICE.stats().unwrap_or_default();
+    let sectors = |bytes: usize| bytes / 512;
+    // 主设备号 次设备号 设备名 读完成数 读合并数 读扇区数 读耗时(ms)
+    // 写完成数 写合并数 写扇区数 写耗时(ms) 正在进行的I/O数 I/O耗时(ms) 加权I/O耗时(ms)
+    // 丢弃完成数 丢弃合并数 丢弃扇区数 丢弃耗时(ms) 刷新完成数 刷新耗时(ms)
+    format!(
+        "{:4} {:7} {} {} {} {} {} {} {} {} {} {} {} {} 0 0 0 0 {} 0\n",
+        BLOCK_DEVICE_NAME.1,
+        0,
+        BLOCK_DEVICE_NAME.0,
+        stats.ios[0],
+        stats.merges[0],
+        sectors(stats.bytes[0]),
+        stats.latency_us[0] / 1000,
+        stats.ios[1],
+        stats.merges[1],
+        sectors(stats.bytes[1]),
+        stats.latency_us[1] / 1000,
+        stats.in_flight,
+        (stats.latency_us[0] + stats.latency_us[1]) / 1000,
+        (stats.latency_us[0] + stats.latency_us[1]) / 1000,
+        stats.flushes,
+    )
+}
+
+/// `/proc/disklatency`：读写延迟直方图，供性能测试使用
+/// 每行为一个桶的下界（微秒）以及落在该桶内的读、写请求数
+pub fn disklatency() -> String {
+    let stats: BlockStats = BLOCK_DEVICE.stats().unwrap_or_default();
+    let mut content = format!(
+        "device: {}\nblock size: {}\nerrors: read {} write {}\nusecs\treads\twrites\n",
+        BLOCK_DEVICE_NAME.0, BLOCK_SZ, stats.errors[0], stats.errors[1]
+    );
+    for bucket in 0..LATENCY_BUCKETS {
+        let lower = if bucket == 0 { 0 } else { 1usize << bucket };
+        let last = if bucket == LATENCY_BUCKETS - 1 {
+            "+"
+        } else {
+            ""
+        };
+        content.push_str(&format!(
+            "{}{}\t{}\t{}\n",
+            lower, last, stats.latency_hist[0][bucket], stats.latency_hist[1][bucket],
+        ));
+    }
+    content
+}
+
+/// 内容在每次读取时由`generate`重新生成的文件。
+/// 带有`store`时文件可写，每次写入的内容去掉首尾空白后交给`store`，类似sysfs的属性文件
+pub struct DiskStats {
+    generate: fn() -> String,
+    store: Option<fn(&str) -> Result<(), isize>>,
+    offset: Mutex<usize>,
+}
+
+impl DiskStats {
+    pub fn new(generate: fn() -> String) -> Self {
+        Self {
+            generate,
+            store: None,
+            offset: Mutex::new(0),
+        }
+    }
+    pub fn new_writable(generate: fn() -> String, store: fn(&str) -> Result<(), isize>) -> Self {
+        Self {
+            generate,
+            store: Some(store),
+            offset: Mutex::new(0),
+        }
+    }
+    fn read_content(&self, offset: Option<usize>, buf: &mut dyn FnMut(&[u8]) -> usize) -> usize {
+        let content = (self.generate)();
+        let mut lock = self.offset.lock();
+        let pos = offset.unwrap_or(*lock);
+        if pos >= content.len() {
+            return 0;
+        }
+        let read = buf(&content.as_bytes()[pos..]);
+        if offset.is_none() {
+            *lock += read;
+        }
+        read
+    }
+    fn store_content(&self, buf: &[u8]) -> usize {
+        let store = match self.store {
+            Some(store) => store,
+            None => return EINVAL as usize,
+        };
+        let content = match core::str::from_utf8(buf) {
+            Ok(content) => content,
+            Err(_) => return EINVAL as usize,
+        };
+        match store(content.trim()) {
+            Ok(()) => buf.len(),
+            Err(errno) => errno as usize,
+        }
+    }
+}
+
+#[allow(unused)]
+impl File for DiskStats {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(DiskStats {
+            generate: self.generate,
+            store: self.store,
+            offset: Mutex::new(*self.offset.lock()),
+        })
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        self.store.is_some()
+    }
+
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        match offset {
+            Some(offset) => {
+                let read = self.read_content(Some(*offset), &mut |content| {
+                    let len = content.len().min(buf.len());
+                    buf[..len].copy_from_slice(&content[..len]);
+                    len
+                });
+                *offset += read;
+                read
+            }
+            None => self.read_content(None, &mut |content| {
+                let len = content.len().min(buf.len());
+                buf[..len].copy_from_slice(&content[..len]);
+                len
+            }),
+        }
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        self.store_content(buf)
+    }
+
+    fn r_ready(&self) -> bool {
+        true
+    }
+
+    fn w_ready(&self) -> bool {
+        self.store.is_some()
+    }
+
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        self.read_content(offset, &mut |content| buf.write(content))
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        let mut content = vec![0u8; buf.len()];
+        buf.read(&mut content);
+        self.store_content(&content)
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            StatMode::S_IFREG.bits() | if self.store.is_some() { 0o644 } else { 0o444 },
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(&self, dirnode_ptr: alloc::sync::Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        Arc::new(DiskStats {
+            generate: self.generate,
+            store: self.store,
+            offset: Mutex::new(0),
+        })
+    }
+
+    fn open_subfile(
+        &self,
+    ) -> Result<alloc::vec::Vec<(alloc::string::String, alloc::sync::Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> alloc::vec::Vec<Dirent> {
+        alloc::vec::Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::SeekWhence) -> Result<usize, isize> {
+        let mut lock = self.offset.lock();
+        let new_offset = match whence {
+            crate::fs::SeekWhence::SEEK_SET => offset,
+            crate::fs::SeekWhence::SEEK_CUR => *lock as isize + offset,
+            _ => return Err(EINVAL),
+        };
+        if new_offset < 0 {
+            return Err(EINVAL);
+        }
+        *lock = new_offset as usize;
+        Ok(*lock)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        // 属性文件被O_TRUNC打开时不报错
+        match self.store {
+            Some(_) => Ok(()),
+            None => Err(EINVAL),
+        }
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        offset: usize,
+    ) -> Result<Arc<spin::Mutex<crate::fs::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(
+        &self,
+    ) -> Result<alloc::vec::Vec<Arc<spin::Mutex<crate::fs::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
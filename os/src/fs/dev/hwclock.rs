@@ -64,7 +64,7 @@ impl File for Hwclock {
     fn get_dirtree_node(
         &self,
     ) -> Option<alloc::sync::Arc<crate::fs::directory_tree::DirectoryTreeNode>> {
-        todo!()
+        None
     }
 
     fn open(&self, flags: crate::fs::OpenFlags, special_use: bool) -> alloc::sync::Arc<dyn File> {
@@ -81,6 +81,7 @@ impl File for Hwclock {
         &self,
         name: &str,
         file_type: DiskInodeType,
+        mode: u32,
     ) -> Result<alloc::sync::Arc<dyn File>, isize> {
         todo!()
     }
@@ -1,6 +1,8 @@
+pub mod diskstats;
 pub mod hwclock;
 pub mod null;
 pub mod pipe;
+pub mod registry;
 pub mod socket;
 pub mod tty;
 pub mod zero;
@@ -78,7 +78,7 @@ impl File for Null {
     }
 
     fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
-        todo!()
+        None
     }
 
     fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
@@ -91,7 +91,12 @@ impl File for Null {
         Err(ENOTDIR)
     }
 
-    fn create(&self, name: &str, file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
         todo!()
     }
 
@@ -1,13 +1,12 @@
 use crate::fs::directory_tree::DirectoryTreeNode;
 use crate::fs::dirent::Dirent;
+use crate::fs::layout::OpenFlags;
 use crate::fs::layout::Stat;
+use crate::fs::poll::{signal_pending, PollEvent, PollWaitQueue, PollWaiter};
 use crate::fs::DiskInodeType;
 use crate::fs::StatMode;
 use crate::syscall::errno::*;
-use crate::task::block_current_and_run_next;
 use crate::task::current_task;
-use crate::task::wait_with_timeout;
-use crate::timer::TimeSpec;
 use crate::{fs::file_trait::File, mm::UserBuffer};
 use alloc::boxed::Box;
 use alloc::sync::{Arc, Weak};
@@ -21,19 +20,29 @@ pub struct Pipe {
 }
 
 impl Pipe {
-    pub fn read_end_with_buffer(buffer: Arc<Mutex<PipeRingBuffer>>) -> Self {
+    // 新建一端并计入缓冲区的读写端计数
+    fn new(buffer: Arc<Mutex<PipeRingBuffer>>, readable: bool, writable: bool) -> Self {
+        let mut ring = buffer.lock();
+        if readable {
+            ring.readers += 1;
+            ring.r_counter += 1;
+        }
+        if writable {
+            ring.writers += 1;
+            ring.w_counter += 1;
+        }
+        drop(ring);
         Self {
-            readable: true,
-            writable: false,
+            readable,
+            writable,
             buffer,
         }
     }
+    pub fn read_end_with_buffer(buffer: Arc<Mutex<PipeRingBuffer>>) -> Self {
+        Self::new(buffer, true, false)
+    }
     pub fn write_end_with_buffer(buffer: Arc<Mutex<PipeRingBuffer>>) -> Self {
-        Self {
-            readable: false,
-            writable: true,
-            buffer,
-        }
+        Self::new(buffer, false, true)
     }
 }
 
@@ -54,12 +63,20 @@ pub struct PipeRingBuffer {
     head: usize,
     tail: usize,
     status: RingBufferStatus,
-    write_end: Option<Weak<Pipe>>,
-    read_end: Option<Weak<Pipe>>,
+    /// 仍打开的读端数量
+    readers: usize,
+    /// 仍打开的写端数量
+    writers: usize,
+    /// 读端被打开的总次数，打开命名管道时据此判断是否有读者到来过
+    r_counter: usize,
+    /// 写端被打开的总次数
+    w_counter: usize,
+    /// 读写两端共享的等待队列
+    wait_queue: Arc<PollWaitQueue>,
 }
 
 impl PipeRingBuffer {
-    fn new() -> Self {
+    pub fn new() -> Self {
         // let mut vec = Vec::<u8>::with_capacity(RING_DEFAULT_BUFFER_SIZE);
         // unsafe {
         //     vec.set_len(RING_DEFAULT_BUFFER_SIZE);
@@ -69,8 +86,11 @@ impl PipeRingBuffer {
             head: 0,
             tail: 0,
             status: RingBufferStatus::EMPTY,
-            write_end: None,
-            read_end: None,
+            readers: 0,
+            writers: 0,
+            r_counter: 0,
+            w_counter: 0,
+            wait_queue: Arc::new(PollWaitQueue::new()),
         }
     }
     #[allow(unused)]
@@ -132,17 +152,37 @@ impl PipeRingBuffer {
         };
         write_bytes
     }
-    fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
-        self.write_end = Some(Arc::downgrade(write_end));
+    /// 读出`read_bytes`字节后更新状态，并唤醒等待写入的一方
+    fn after_read(&mut self, read_bytes: usize) {
+        if read_bytes == 0 {
+            return;
+        }
+        self.status = if self.head == self.tail {
+            RingBufferStatus::EMPTY
+        } else {
+            RingBufferStatus::NORMAL
+        };
+        self.wait_queue
+            .notify(PollEvent::POLLOUT | PollEvent::POLLWRNORM);
     }
-    fn set_read_end(&mut self, read_end: &Arc<Pipe>) {
-        self.read_end = Some(Arc::downgrade(read_end));
+    /// 写入`write_bytes`字节后更新状态，并唤醒等待读取的一方
+    fn after_write(&mut self, write_bytes: usize) {
+        if write_bytes == 0 {
+            return;
+        }
+        self.status = if self.head == self.tail {
+            RingBufferStatus::FULL
+        } else {
+            RingBufferStatus::NORMAL
+        };
+        self.wait_queue
+            .notify(PollEvent::POLLIN | PollEvent::POLLRDNORM);
     }
     fn all_write_ends_closed(&self) -> bool {
-        self.write_end.as_ref().unwrap().upgrade().is_none()
+        self.writers == 0
     }
     fn all_read_ends_closed(&self) -> bool {
-        self.read_end.as_ref().unwrap().upgrade().is_none()
+        self.readers == 0
     }
 }
 
@@ -151,12 +191,92 @@ pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
     let buffer = Arc::new(Mutex::new(PipeRingBuffer::new()));
     // buffer仅剩两个强引用，这样读写端关闭后就会被释放
     let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
-    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
-    buffer.lock().set_write_end(&write_end);
-    buffer.lock().set_read_end(&read_end);
+    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer));
     (read_end, write_end)
 }
 
+/// 按`flags`打开命名管道的一端，`buffer`由同一FIFO的所有打开者共享。
+/// 只读打开会等待写者到来，只写打开会等待读者到来（非阻塞且没有读者时返回`ENXIO`），
+/// 读写打开不会阻塞
+pub fn open_fifo(buffer: Arc<Mutex<PipeRingBuffer>>, flags: OpenFlags) -> Result<Arc<Pipe>, isize> {
+    let nonblock = flags.contains(OpenFlags::O_NONBLOCK);
+    let (readable, writable) = if flags.contains(OpenFlags::O_RDWR) {
+        (true, true)
+    } else if flags.contains(OpenFlags::O_WRONLY) {
+        (false, true)
+    } else {
+        (true, false)
+    };
+    if writable && !readable && nonblock && buffer.lock().readers == 0 {
+        return Err(ENXIO);
+    }
+    let wait_queue = buffer.lock().wait_queue.clone();
+    let pipe = Arc::new(Pipe::new(buffer, readable, writable));
+    // 新的一端可能正是对端在等待的
+    wait_queue.notify(if readable {
+        PollEvent::POLLOUT
+    } else {
+        PollEvent::POLLIN
+    });
+    if readable == writable || nonblock {
+        return Ok(pipe);
+    }
+    let mut waiter = PollWaiter::new();
+    waiter.register_queue(
+        Some(wait_queue),
+        if readable {
+            PollEvent::POLLIN
+        } else {
+            PollEvent::POLLOUT
+        },
+    );
+    // 记录打开时的计数，期间有对端打开过（即使又已关闭）即可返回
+    let ring = pipe.buffer.lock();
+    let (w_counter, r_counter) = (ring.w_counter, ring.r_counter);
+    drop(ring);
+    loop {
+        let ring = pipe.buffer.lock();
+        let arrived = if readable {
+            ring.writers > 0 || ring.w_counter != w_counter
+        } else {
+            ring.readers > 0 || ring.r_counter != r_counter
+        };
+        drop(ring);
+        if arrived {
+            return Ok(pipe);
+        }
+        if signal_pending() {
+            return Err(EINTR);
+        }
+        waiter.sleep(None);
+    }
+}
+
+impl Drop for Pipe {
+    /// 一端关闭时唤醒另一端，读端因此读到EOF，写端因此得知对端已关闭
+    fn drop(&mut self) {
+        let mut ring = self.buffer.lock();
+        if self.readable {
+            ring.readers -= 1;
+        }
+        if self.writable {
+            ring.writers -= 1;
+        }
+        let wait_queue = ring.wait_queue.clone();
+        drop(ring);
+        wait_queue.notify(PollEvent::POLLHUP | PollEvent::POLLIN | PollEvent::POLLOUT);
+    }
+}
+
+impl Pipe {
+    /// 睡眠直到管道上发生`events`（或对端关闭）
+    fn wait_for(&self, events: PollEvent) {
+        let mut waiter = PollWaiter::new();
+        waiter.register_queue(Some(self.buffer.lock().wait_queue.clone()), events);
+        waiter.sleep(None);
+    }
+}
+
 #[allow(unused)]
 impl File for Pipe {
     fn deep_clone(&self) -> Arc<dyn File> {
@@ -190,11 +310,7 @@ impl File for Pipe {
                     return read_size;
                 }
                 drop(ring);
-                let task = current_task().unwrap();
-                wait_with_timeout(Arc::downgrade(&task), TimeSpec::now());
-                drop(task);
-                block_current_and_run_next();
-                // suspend_current_and_run_next();
+                self.wait_for(PollEvent::POLLIN);
                 continue;
             }
             // We guarantee that this operation will read at least one byte
@@ -202,12 +318,10 @@ impl File for Pipe {
                 let read_bytes = ring.buffer_read(&mut buf[read_size..]);
                 read_size += read_bytes;
                 if ring.head == ring.tail {
-                    ring.status = RingBufferStatus::EMPTY;
-                    return read_size;
+                    break;
                 }
             }
-
-            ring.status = RingBufferStatus::NORMAL;
+            ring.after_read(read_size);
             return read_size;
         }
     }
@@ -232,24 +346,18 @@ impl File for Pipe {
                     return write_size;
                 }
                 drop(ring);
-                let task = current_task().unwrap();
-                wait_with_timeout(Arc::downgrade(&task), TimeSpec::now());
-                drop(task);
-                block_current_and_run_next();
-                // suspend_current_and_run_next();
+                self.wait_for(PollEvent::POLLOUT);
                 continue;
             }
             // We guarantee that this operation will write at least one byte
-            // So we modify status first
             while write_size < buf.len() {
                 let write_bytes = ring.buffer_write(&buf[write_size..]);
                 write_size += write_bytes;
                 if ring.head == ring.tail {
-                    ring.status = RingBufferStatus::FULL;
-                    return write_size;
+                    break;
                 }
             }
-            ring.status = RingBufferStatus::NORMAL;
+            ring.after_write(write_size);
             return write_size;
         }
     }
@@ -284,29 +392,23 @@ impl File for Pipe {
                     return read_size;
                 }
                 drop(ring);
-                let task = current_task().unwrap();
-                wait_with_timeout(Arc::downgrade(&task), TimeSpec::now());
-                drop(task);
-                block_current_and_run_next();
-                // suspend_current_and_run_next();
+                self.wait_for(PollEvent::POLLIN);
                 continue;
             }
             // We guarantee that this operation will read at least one byte
-            // So we modify status first
-            for buf in buf.buffers {
+            'copy: for buf in buf.buffers {
                 let mut buf_start = 0;
                 while buf_start < buf.len() {
                     let read_bytes = ring.buffer_read(&mut buf[buf_start..]);
                     buf_start += read_bytes;
                     if ring.head == ring.tail {
-                        ring.status = RingBufferStatus::EMPTY;
                         read_size += buf_start;
-                        return read_size;
+                        break 'copy;
                     }
                 }
                 read_size += buf_start;
             }
-            ring.status = RingBufferStatus::NORMAL;
+            ring.after_read(read_size);
             return read_size;
         }
     }
@@ -330,29 +432,23 @@ impl File for Pipe {
                     return write_size;
                 }
                 drop(ring);
-                let task = current_task().unwrap();
-                wait_with_timeout(Arc::downgrade(&task), TimeSpec::now());
-                drop(task);
-                block_current_and_run_next();
-                // suspend_current_and_run_next();
+                self.wait_for(PollEvent::POLLOUT);
                 continue;
             }
             // We guarantee that this operation will write at least one byte
-            // So we modify status first
-            for buf in buf.buffers {
+            'copy: for buf in buf.buffers {
                 let mut buf_start = 0;
                 while buf_start < buf.len() {
                     let write_bytes = ring.buffer_write(&buf[buf_start..]);
                     buf_start += write_bytes;
                     if ring.head == ring.tail {
-                        ring.status = RingBufferStatus::FULL;
                         write_size += buf_start;
-                        return write_size;
+                        break 'copy;
                     }
                 }
                 write_size += buf_start;
             }
-            ring.status = RingBufferStatus::NORMAL;
+            ring.after_write(write_size);
             return write_size;
         }
     }
@@ -384,7 +480,7 @@ impl File for Pipe {
     }
 
     fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
-        todo!()
+        None
     }
 
     fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
@@ -397,7 +493,12 @@ impl File for Pipe {
         Err(ENOTDIR)
     }
 
-    fn create(&self, name: &str, file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
         todo!()
     }
 
@@ -455,6 +556,10 @@ impl File for Pipe {
         }
     }
 
+    fn poll_queue(&self) -> Option<Arc<PollWaitQueue>> {
+        Some(self.buffer.lock().wait_queue.clone())
+    }
+
     fn fcntl(&self, cmd: u32, arg: u32) -> isize {
         // use crate::config::PAGE_SIZE;
         // use crate::syscall::fs::Fcntl_Command;
@@ -0,0 +1,265 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+};
+use lazy_static::*;
+use spin::RwLock;
+
+use crate::{
+    fs::{directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat},
+    mm::UserBuffer,
+    syscall::errno::{EBUSY, EINVAL, ENOTDIR, ENXIO, ESPIPE},
+};
+
+use super::{hwclock::Hwclock, null::Null, tty::TTY, urandom::Urandom, zero::Zero};
+
+/// 由次设备号打开设备，没有对应设备时返回`None`
+pub type DeviceOpen = fn(minor: u32) -> Option<Arc<dyn File>>;
+
+/// 内存设备（`/dev/null`等）的主设备号
+pub const MEM_MAJOR: u32 = 1;
+/// 终端的主设备号
+pub const TTYAUX_MAJOR: u32 = 5;
+/// 实时时钟的主设备号
+pub const RTC_MAJOR: u32 = 254;
+
+/// 主设备号，与Linux的`new_decode_dev`一致
+pub fn major(dev: u32) -> u32 {
+    (dev & 0xfff00) >> 8
+}
+
+/// 次设备号
+pub fn minor(dev: u32) -> u32 {
+    (dev & 0xff) | ((dev >> 12) & 0xfff00)
+}
+
+/// 由主次设备号组成设备号，与Linux的`new_encode_dev`一致
+pub fn mkdev(major: u32, minor: u32) -> u32 {
+    (minor & 0xff) | (major << 8) | ((minor & !0xff) << 12)
+}
+
+fn open_mem(minor: u32) -> Option<Arc<dyn File>> {
+    match minor {
+        3 => Some(Arc::new(Null {})),
+        5 => Some(Arc::new(Zero {})),
+        8 | 9 => Some(Arc::new(Urandom {})),
+        _ => None,
+    }
+}
+
+fn open_tty(minor: u32) -> Option<Arc<dyn File>> {
+    match minor {
+        0 => Some(TTY.clone()),
+        _ => None,
+    }
+}
+
+fn open_rtc(minor: u32) -> Option<Arc<dyn File>> {
+    match minor {
+        0 => Some(Arc::new(Hwclock {})),
+        _ => None,
+    }
+}
+
+lazy_static! {
+    // 字符设备，以主设备号为键
+    static ref CHRDEVS: RwLock<BTreeMap<u32, DeviceOpen>> = {
+        let mut map: BTreeMap<u32, DeviceOpen> = BTreeMap::new();
+        map.insert(MEM_MAJOR, open_mem);
+        map.insert(TTYAUX_MAJOR, open_tty);
+        map.insert(RTC_MAJOR, open_rtc);
+        RwLock::new(map)
+    };
+    // 块设备，以主设备号为键
+    static ref BLKDEVS: RwLock<BTreeMap<u32, DeviceOpen>> = RwLock::new(BTreeMap::new());
+}
+
+fn registry(file_type: DiskInodeType) -> Result<&'static RwLock<BTreeMap<u32, DeviceOpen>>, isize> {
+    match file_type {
+        DiskInodeType::Character => Ok(&*CHRDEVS),
+        DiskInodeType::Block => Ok(&*BLKDEVS),
+        _ => Err(EINVAL),
+    }
+}
+
+/// 注册字符设备驱动，主设备号已被占用时返回`EBUSY`
+pub fn register_chrdev(major: u32, open: DeviceOpen) -> Result<(), isize> {
+    register_device(DiskInodeType::Character, major, open)
+}
+
+/// 注册块设备驱动，主设备号已被占用时返回`EBUSY`
+pub fn register_blkdev(major: u32, open: DeviceOpen) -> Result<(), isize> {
+    register_device(DiskInodeType::Block, major, open)
+}
+
+fn register_device(file_type: DiskInodeType, major: u32, open: DeviceOpen) -> Result<(), isize> {
+    let mut lock = registry(file_type)?.write();
+    if lock.contains_key(&major) {
+        return Err(EBUSY);
+    }
+    lock.insert(major, open);
+    Ok(())
+}
+
+/// 打开设备号为`rdev`的字符设备或块设备，没有对应的驱动时返回`ENXIO`
+pub fn open_device(file_type: DiskInodeType, rdev: u32) -> Result<Arc<dyn File>, isize> {
+    let open = match registry(file_type)?.read().get(&major(rdev)) {
+        Some(open) => *open,
+        None => return Err(ENXIO),
+    };
+    open(minor(rdev)).ok_or(ENXIO)
+}
+
+/// 内存中的设备节点，只记录类型与设备号。
+/// 打开时由目录树经`open_device`分派到驱动，本身不可读写
+pub struct DeviceNode {
+    file_type: DiskInodeType,
+    rdev: u32,
+}
+
+impl DeviceNode {
+    pub fn new(file_type: DiskInodeType, rdev: u32) -> Self {
+        Self { file_type, rdev }
+    }
+}
+
+#[allow(unused)]
+impl File for DeviceNode {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(Self::new(self.file_type, self.rdev))
+    }
+
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        unreachable!()
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        unreachable!()
+    }
+
+    fn r_ready(&self) -> bool {
+        false
+    }
+
+    fn w_ready(&self) -> bool {
+        false
+    }
+
+    fn read_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        unreachable!()
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        unreachable!()
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 5),
+            1,
+            self.file_type.stat_mode().bits() | 0o666,
+            1,
+            self.rdev as u64,
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        self.file_type
+    }
+
+    fn info_dirtree_node(&self, dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        open_device(self.file_type, self.rdev).unwrap()
+    }
+
+    fn open_subfile(
+        &self,
+    ) -> Result<alloc::vec::Vec<(alloc::string::String, alloc::sync::Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> alloc::vec::Vec<Dirent> {
+        alloc::vec::Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        offset: usize,
+    ) -> Result<Arc<spin::Mutex<crate::fs::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(
+        &self,
+    ) -> Result<alloc::vec::Vec<Arc<spin::Mutex<crate::fs::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
@@ -66,7 +66,7 @@ impl File for Socket {
     }
 
     fn get_dirtree_node(&self) -> Option<Arc<crate::fs::directory_tree::DirectoryTreeNode>> {
-        todo!()
+        None
     }
 
     fn open(&self, flags: crate::fs::OpenFlags, special_use: bool) -> Arc<dyn File> {
@@ -79,7 +79,12 @@ impl File for Socket {
         Err(ENOTDIR)
     }
 
-    fn create(&self, name: &str, file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
         todo!()
     }
 
@@ -2,6 +2,7 @@ use crate::fs::directory_tree::DirectoryTreeNode;
 use crate::fs::dirent::Dirent;
 use crate::fs::file_trait::File;
 use crate::fs::layout::Stat;
+use crate::fs::poll::{PollEvent, PollWaitQueue, PollWaiter};
 use crate::fs::DiskInodeType;
 use crate::fs::StatMode;
 use crate::hal::console_getchar;
@@ -60,12 +61,31 @@ impl Default for TeletypeInner {
 #[derive(Default)]
 pub struct Teletype {
     inner: Mutex<TeletypeInner>,
+    /// 串口没有输入中断，由`poll_tick()`在时钟中断中检查输入并唤醒等待者
+    wait_queue: Arc<PollWaitQueue>,
 }
 
 impl Teletype {
     pub fn new() -> Self {
         Default::default()
     }
+    /// 有等待者时检查是否有新输入，有则唤醒它们
+    pub fn poll_tick(&self) {
+        if self.wait_queue.is_empty() {
+            return;
+        }
+        // k210 上`r_ready()`另有语义，读者醒来后会自行阻塞读取
+        if cfg!(feature = "board_k210") || self.r_ready() {
+            self.wait_queue
+                .notify(PollEvent::POLLIN | PollEvent::POLLRDNORM);
+        }
+    }
+    /// 睡眠直到有输入
+    fn wait_for_input(&self) {
+        let mut waiter = PollWaiter::new();
+        waiter.register_queue(Some(self.wait_queue.clone()), PollEvent::POLLIN);
+        waiter.sleep(None);
+    }
 }
 
 // TODO: independ of rust sbi
@@ -174,9 +194,13 @@ impl File for Teletype {
                 if count > 0 {
                     return count;
                 }
-                //we read no char, suspend the procedure
-                crate::task::suspend_current_and_run_next;
-                inner.last_char = console_getchar() as u8;
+                //we read no char, sleep until the next tick finds input
+                drop(inner);
+                self.wait_for_input();
+                inner = self.inner.lock();
+                if inner.last_char == 255 {
+                    inner.last_char = console_getchar() as u8;
+                }
             }
             //we can guarantee last_char isn't a illegal char
             unsafe {
@@ -238,7 +262,7 @@ impl File for Teletype {
     }
 
     fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
-        todo!()
+        None
     }
 
     fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
@@ -251,7 +275,12 @@ impl File for Teletype {
         Err(ENOTDIR)
     }
 
-    fn create(&self, name: &str, file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
         todo!()
     }
 
@@ -302,6 +331,10 @@ impl File for Teletype {
         false
     }
 
+    fn poll_queue(&self) -> Option<Arc<PollWaitQueue>> {
+        Some(self.wait_queue.clone())
+    }
+
     fn ioctl(&self, cmd: u32, argp: usize) -> isize {
         info!(
             "[tty_ioctl] cmd: {:?}, arg: {:X}",
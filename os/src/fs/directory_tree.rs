@@ -2,17 +2,29 @@ use super::inode::DiskInodeType;
 use super::vfs::VFS;
 use super::{
     cache::BlockCacheManager,
-    dev::{null::Null, tty::Teletype, zero::Zero},
+    dcache::{
+        dcache_insert_negative, dcache_insert_positive, dcache_invalidate, dcache_invalidate_dir,
+        dcache_lookup, Dentry,
+    },
+    dev::{
+        pipe::{open_fifo, PipeRingBuffer},
+        registry::{mkdev, open_device, DeviceNode, MEM_MAJOR, RTC_MAJOR, TTYAUX_MAJOR},
+    },
     file_trait::File,
     filesystem::FileSystem,
-    layout::OpenFlags,
-    Hwclock,
+    inotify::{fsnotify_detach, fsnotify_marks, next_cookie, InotifyMark, InotifyMask},
+    layout::{OpenFlags, StatMode},
+    locks::FileLockContext,
+    permission::{check_owner, check_sticky, generic_permission, MAY_EXEC, MAY_READ, MAY_WRITE},
+    shmfs::ShmDir,
+    writeback::mark_inode_dirty,
 };
-use crate::fs::dev::urandom::Urandom;
+use crate::fs::dev::diskstats::{disklatency, diskstats, DiskStats};
 use crate::fs::fat32::FatOSInode;
 #[cfg(feature = "oom_handler")]
-use crate::mm::tlb_invalidate;
+use crate::mm::{tlb_invalidate, FileMappings};
 use crate::syscall::errno::*;
+use crate::task::{capable, current_task, Capabilities};
 use crate::{drivers::BLOCK_DEVICE, fs::filesystem::FS_Type};
 use alloc::{
     collections::BTreeMap,
@@ -20,6 +32,7 @@ use alloc::{
     sync::{Arc, Weak},
     vec::Vec,
 };
+use core::sync::atomic::{AtomicBool, Ordering};
 use lazy_static::*;
 use spin::{Mutex, MutexGuard, RwLock, RwLockWriteGuard};
 
@@ -101,6 +114,16 @@ pub struct DirectoryTreeNode {
     father: Mutex<Weak<Self>>,
     // 子节点
     children: RwLock<Option<BTreeMap<String, Arc<Self>>>>,
+    // 监视该节点的inotify实例
+    inotify_marks: Mutex<Vec<InotifyMark>>,
+    // flock()锁与fcntl()记录锁
+    file_locks: FileLockContext,
+    // 命名管道的缓冲区，由所有打开者共享，全部关闭后释放
+    fifo: Mutex<Weak<Mutex<PipeRingBuffer>>>,
+    // 映射了该文件的地址空间
+    mappings: Arc<FileMappings>,
+    // 正被用作交换文件，swapoff前不能写入或截断
+    swapfile: AtomicBool,
 }
 
 // 实现 Drop 特征，当一个 DirectoryTreeNode 被销毁时，会调用 delete_directory_vec 函数
@@ -127,6 +150,11 @@ impl DirectoryTreeNode {
             father: Mutex::new(father),
             // 子节点初始化为 None
             children: RwLock::new(None),
+            inotify_marks: Mutex::new(Vec::new()),
+            file_locks: FileLockContext::new(),
+            fifo: Mutex::new(Weak::new()),
+            mappings: FileMappings::new(),
+            swapfile: AtomicBool::new(false),
         });
         *node.selfptr.lock() = Arc::downgrade(&node);
         node.file.info_dirtree_node(Arc::downgrade(&node));
@@ -142,6 +170,175 @@ impl DirectoryTreeNode {
         *self.spe_usage.lock() -= 1;
     }
 
+    /// swapon时固定交换文件：以写方式打开、删除和被rename覆盖都会失败
+    pub fn pin_swapfile(&self) {
+        self.add_special_use();
+        self.swapfile.store(true, Ordering::Relaxed);
+    }
+
+    pub fn unpin_swapfile(&self) {
+        self.swapfile.store(false, Ordering::Relaxed);
+        self.sub_special_use();
+    }
+
+    pub fn is_swapfile(&self) -> bool {
+        self.swapfile.load(Ordering::Relaxed)
+    }
+
+    pub fn inotify_marks(&self) -> &Mutex<Vec<InotifyMark>> {
+        &self.inotify_marks
+    }
+
+    pub fn file_locks(&self) -> &FileLockContext {
+        &self.file_locks
+    }
+
+    pub fn mappings(&self) -> Arc<FileMappings> {
+        self.mappings.clone()
+    }
+
+    // 产生关于本节点的inotify事件，监视其父目录的实例同样会收到，并附带本节点的名字
+    pub fn fsnotify(&self, mask: InotifyMask) {
+        let mask = if self.file.is_dir() {
+            mask | InotifyMask::IN_ISDIR
+        } else {
+            mask
+        };
+        fsnotify_marks(&self.inotify_marks, mask, None, 0);
+        let father = self.father.lock().upgrade();
+        if let Some(father) = father {
+            fsnotify_marks(&father.inotify_marks, mask, Some(&self.name), 0);
+        }
+    }
+
+    // 产生关于目录下子项`child`的inotify事件（创建、删除、移入、移出）
+    fn fsnotify_child(&self, child: &Self, mask: InotifyMask, name: &str, cookie: u32) {
+        let mask = if child.file.is_dir() {
+            mask | InotifyMask::IN_ISDIR
+        } else {
+            mask
+        };
+        fsnotify_marks(&self.inotify_marks, mask, Some(name), cookie);
+    }
+
+    // 节点已从目录树中删除
+    fn fsnotify_delete_self(&self) {
+        fsnotify_marks(&self.inotify_marks, InotifyMask::IN_DELETE_SELF, None, 0);
+        fsnotify_detach(&self.inotify_marks);
+    }
+
+    // 按当前任务的文件系统ID检查对本节点的访问权限，mask由MAY_READ等组成
+    // 内核自身的访问（没有当前任务）不受限制，拥有CAP_DAC_OVERRIDE时读写与目录搜索不必读取inode
+    pub fn permission(&self, mask: u32) -> Result<(), isize> {
+        let task = match current_task() {
+            Some(task) => task,
+            None => return Ok(()),
+        };
+        let cred = task.cred.lock();
+        if cred.capable(Capabilities::CAP_DAC_OVERRIDE)
+            && (mask & MAY_EXEC == 0 || self.file.is_dir())
+        {
+            return Ok(());
+        }
+        generic_permission(&self.file.get_stat(), mask, &cred)
+    }
+
+    // 检查能否从本目录中删除或移走子节点child：需要目录的写和搜索权限，
+    // 目录设置了粘着位时还要求是文件或目录的属主
+    fn may_delete(&self, child: &Self) -> Result<(), isize> {
+        let task = match current_task() {
+            Some(task) => task,
+            None => return Ok(()),
+        };
+        let cred = task.cred.lock();
+        if cred.capable(Capabilities::CAP_DAC_OVERRIDE | Capabilities::CAP_FOWNER) {
+            return Ok(());
+        }
+        let stat = self.file.get_stat();
+        generic_permission(&stat, MAY_WRITE | MAY_EXEC, &cred)?;
+        check_sticky(&stat, &child.file.get_stat(), &cred)
+    }
+
+    // 修改权限位，只有属主和拥有CAP_FOWNER的任务可以修改
+    // 不在文件属组中且没有CAP_FSETID时清除set-group-ID位
+    pub fn chmod(&self, mode: u32) -> Result<(), isize> {
+        let mut mode = mode & 0o7777;
+        if let Some(task) = current_task() {
+            let cred = task.cred.lock();
+            let stat = self.file.get_stat();
+            check_owner(&stat, &cred)?;
+            if !cred.in_group(stat.get_gid()) && !cred.capable(Capabilities::CAP_FSETID) {
+                mode &= !StatMode::S_ISGID.bits();
+            }
+        }
+        self.file.chmod(mode)?;
+        self.fsnotify(InotifyMask::IN_ATTRIB);
+        Ok(())
+    }
+
+    // 修改属主和属组，None表示不修改
+    // 只有拥有CAP_CHOWN的任务可以把文件交给其他用户，属主可以把属组改为自己所在的组
+    pub fn chown(&self, uid: Option<u32>, gid: Option<u32>) -> Result<(), isize> {
+        let stat = self.file.get_stat();
+        let (old_uid, old_gid) = (stat.get_uid(), stat.get_gid());
+        if let Some(task) = current_task() {
+            let cred = task.cred.lock();
+            if !cred.capable(Capabilities::CAP_CHOWN) {
+                let is_owner = cred.fsuid == old_uid;
+                if uid.map_or(false, |uid| !is_owner || uid != old_uid) {
+                    return Err(EPERM);
+                }
+                if gid.map_or(false, |gid| {
+                    !is_owner || (gid != old_gid && !cred.in_group(gid))
+                }) {
+                    return Err(EPERM);
+                }
+            }
+        }
+        if uid.is_none() && gid.is_none() {
+            return Ok(());
+        }
+        self.file
+            .chown(uid.unwrap_or(old_uid), gid.unwrap_or(old_gid))?;
+        // 普通文件换了属主后清除set-user-ID位，组可执行时同时清除set-group-ID位
+        let mode = stat.get_mode();
+        if !self.file.is_dir() {
+            let mut kill = StatMode::S_ISUID.bits();
+            if mode & StatMode::S_IXGRP.bits() != 0 {
+                kill |= StatMode::S_ISGID.bits();
+            }
+            if mode & kill != 0 {
+                self.file.chmod(mode & 0o7777 & !kill)?;
+            }
+        }
+        self.fsnotify(InotifyMask::IN_ATTRIB);
+        Ok(())
+    }
+
+    // 打开命名管道或设备节点
+    fn open_special(&self, flags: OpenFlags) -> Result<Arc<dyn File>, isize> {
+        let file_type = self.file.get_file_type();
+        if file_type != DiskInodeType::FIFO {
+            return open_device(file_type, self.file.get_stat().get_rdev());
+        }
+        let mut fifo = self.fifo.lock();
+        let buffer = match fifo.upgrade() {
+            Some(buffer) => buffer,
+            None => {
+                let buffer = Arc::new(Mutex::new(PipeRingBuffer::new()));
+                *fifo = Arc::downgrade(&buffer);
+                buffer
+            }
+        };
+        drop(fifo);
+        Ok(open_fifo(buffer, flags)?)
+    }
+
+    // 获取所在文件系统的编号
+    pub fn fs_id(&self) -> usize {
+        self.filesystem.fs_id
+    }
+
     // 获取当前工作目录，返回一个 String 类型（绝对路径）
     pub fn get_cwd(&self) -> String {
         // 创建一个pathv变量，最多容量为8（个String变量）,
@@ -247,6 +444,32 @@ impl DirectoryTreeNode {
         }
     }
 
+    // 查找子节点，优先查询目录项缓存，未命中时再访问 children
+    // 查找结果（包括文件不存在）会被记录到目录项缓存中
+    fn lookup_child(&self, name: &str) -> Result<Arc<Self>, isize> {
+        self.permission(MAY_EXEC)?;
+        match dcache_lookup(self, name) {
+            Some(Dentry::Positive(child)) => {
+                if let Some(child) = child.upgrade() {
+                    return Ok(child);
+                }
+            }
+            Some(Dentry::Negative) => return Err(ENOENT),
+            None => {}
+        }
+        let mut lock = self.children.write();
+        let result = self.try_to_open_subfile(name, &mut lock);
+        // 持有 children 锁时更新缓存，避免与创建/删除操作交错
+        let selfptr = self.selfptr.lock().clone();
+        match &result {
+            Ok(child) => dcache_insert_positive(&selfptr, name, child),
+            Err(errno) if *errno == ENOENT => dcache_insert_negative(&selfptr, name),
+            Err(_) => {}
+        }
+        drop(lock);
+        result
+    }
+
     // 通过一个动态数组 components 来进入某个目录
     pub fn cd_comp(&self, components: &Vec<&str>) -> Result<Arc<Self>, isize> {
         let mut current_inode = self.get_arc();
@@ -263,13 +486,8 @@ impl DirectoryTreeNode {
                 }
                 continue;
             }
-            let mut lock = current_inode.children.write();
-            match current_inode.try_to_open_subfile(component, &mut lock) {
-                Ok(child_inode) => {
-                    let child_inode = child_inode.clone();
-                    drop(lock);
-                    current_inode = child_inode.clone()
-                }
+            match current_inode.lookup_child(component) {
+                Ok(child_inode) => current_inode = child_inode,
                 Err(errno) => return Err(errno),
             }
         }
@@ -289,19 +507,85 @@ impl DirectoryTreeNode {
 
     // 创建一个子文件，文件名和文件类型由参数提供
     // file_type: 文件是常规文件还是目录
-    fn create(&self, name: &str, file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+    // mode: 新文件的权限位，已经去掉了umask屏蔽的位
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
         // if name == "" || !self.file.is_dir() {
         //     debug_assert!(false);
         // }
-        self.file.create(name, file_type)
+        self.file.create(name, file_type, mode)
     }
 
-    // 模拟文件系统的 open 调用
+    // 在当前目录下创建一个子节点，同时更新 children 和目录项缓存
+    // 若同名文件已经存在，返回 EEXIST
+    fn create_child(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<Self>, isize> {
+        self.create_child_with(name, || self.create(name, file_type, mode))
+    }
+
+    // 同 create_child，子文件由 create 在确认同名文件不存在后创建
+    fn create_child_with(
+        &self,
+        name: &str,
+        create: impl FnOnce() -> Result<Arc<dyn File>, isize>,
+    ) -> Result<Arc<Self>, isize> {
+        let mut lock = self.children.write();
+        match self.try_to_open_subfile(name, &mut lock) {
+            Ok(_) => return Err(EEXIST),
+            Err(ENOENT) => {}
+            Err(errno) => return Err(errno),
+        }
+        self.permission(MAY_WRITE | MAY_EXEC)?;
+        let new_file = create()?;
+        // 新文件的属主为创建者的文件系统ID，不记录属主的文件系统忽略这一步
+        if let Some(task) = current_task() {
+            let cred = task.cred.lock();
+            if cred.fsuid != 0 || cred.fsgid != 0 {
+                let _ = new_file.chown(cred.fsuid, cred.fsgid);
+            }
+        }
+        let key = name.to_string();
+        let selfptr = self.selfptr.lock().clone();
+        let new_inode = Self::new(
+            key.clone(),
+            self.filesystem.clone(),
+            new_file,
+            selfptr.clone(),
+        );
+        lock.as_mut().unwrap().insert(key, new_inode.clone());
+        dcache_insert_positive(&selfptr, name, &new_inode);
+        // 新的目录项写在当前目录中
+        mark_inode_dirty(&self.get_arc());
+        drop(lock);
+        self.fsnotify_child(&new_inode, InotifyMask::IN_CREATE, name, 0);
+        Ok(new_inode)
+    }
+
+    // 模拟文件系统的 open 调用，内核自己创建的文件权限为0o644
     pub fn open(
         &self,
         path: &str,
         flags: OpenFlags,
         special_use: bool,
+    ) -> Result<Arc<dyn File>, isize> {
+        self.open_with_mode(path, flags, 0o644, special_use)
+    }
+
+    // 同 open，O_CREAT 创建文件时权限位为 mode
+    pub fn open_with_mode(
+        &self,
+        path: &str,
+        flags: OpenFlags,
+        mode: u32,
+        special_use: bool,
     ) -> Result<Arc<dyn File>, isize> {
         log::debug!("[open]: cwd: {}, path: {}", self.get_cwd(), path);
 
@@ -335,10 +619,15 @@ impl DirectoryTreeNode {
             &self
         };
 
+        // 本次打开是否新建了文件，新建的文件不再检查访问权限
+        let mut created = false;
         // 获取路径缓存
         let mut path_cache_lock = PATH_CACHE.lock();
+        // 路径缓存跳过了沿途目录的搜索权限检查，只供可以越过搜索权限的任务使用
+        let privileged = capable(Capabilities::CAP_DAC_READ_SEARCH);
         // 如果路径以 '/' 开头，且路径等于缓存路径，且缓存路径的弱引用存在
-        let inode = if path.starts_with('/')
+        let inode = if privileged
+            && path.starts_with('/')
             && path == path_cache_lock.0
             && path_cache_lock.1.upgrade().is_some()
         {
@@ -356,32 +645,17 @@ impl DirectoryTreeNode {
             };
             // 若最后一个组件存在，则进行处理
             if let Some(last_comp) = last_comp {
-                let mut lock = inode.children.write();
-                match inode.try_to_open_subfile(last_comp, &mut lock) {
+                // 先查询目录项缓存，只有需要创建文件时才持有 children 锁
+                match inode.lookup_child(last_comp) {
                     Ok(inode) => {
                         if flags.contains(OpenFlags::O_CREAT | OpenFlags::O_EXCL) {
                             return Err(EEXIST);
                         }
                         inode
                     }
-                    Err(ENOENT) => {
-                        if !flags.contains(OpenFlags::O_CREAT) {
-                            return Err(ENOENT);
-                        }
-                        let new_file = match inode.create(last_comp, DiskInodeType::File) {
-                            Ok(file) => file,
-                            Err(errno) => return Err(errno),
-                        };
-                        let key = (*last_comp).to_string();
-                        let value = Self::new(
-                            key.clone(),
-                            inode.filesystem.clone(),
-                            new_file,
-                            Arc::downgrade(&inode.get_arc()),
-                        );
-                        let new_inode = value.clone();
-                        lock.as_mut().unwrap().insert(key, value);
-                        new_inode
+                    Err(ENOENT) if flags.contains(OpenFlags::O_CREAT) => {
+                        created = true;
+                        inode.create_child(last_comp, DiskInodeType::File, mode)?
                     }
                     Err(errno) => {
                         return Err(errno);
@@ -392,20 +666,42 @@ impl DirectoryTreeNode {
             }
         };
 
-        if flags.contains(OpenFlags::O_TRUNC) {
-            match inode.file.truncate_size(0) {
-                Ok(_) => {}
-                Err(errno) => return Err(errno),
+        // O_PATH只获得文件的位置，不检查也不授予读写权限
+        let path_only = flags.contains(OpenFlags::O_PATH);
+        if !created && !path_only {
+            let mut mask = match flags.bits() & 0b11 {
+                0 => MAY_READ,
+                1 => MAY_WRITE,
+                _ => MAY_READ | MAY_WRITE,
+            };
+            if flags.contains(OpenFlags::O_TRUNC) {
+                mask |= MAY_WRITE;
             }
+            inode.permission(mask)?;
         }
 
+        // 正在执行的文件和交换文件不能写入，也不能被O_TRUNC截断
         if inode.file.is_file()
             && *inode.spe_usage.lock() > 0
-            && (flags.contains(OpenFlags::O_WRONLY) || flags.contains(OpenFlags::O_RDWR))
+            && (flags.contains(OpenFlags::O_WRONLY)
+                || flags.contains(OpenFlags::O_RDWR)
+                || (flags.contains(OpenFlags::O_TRUNC) && !path_only))
         {
             return Err(ETXTBSY);
         }
 
+        let special = inode.file.get_file_type().is_special();
+        if flags.contains(OpenFlags::O_TRUNC) && !special && !path_only {
+            let old_size = inode.file.get_size();
+            match inode.file.truncate_size(0) {
+                Ok(_) => {}
+                Err(errno) => return Err(errno),
+            }
+            if old_size > 0 {
+                inode.mappings.unmap_beyond_eof();
+            }
+        }
+
         if inode.file.is_dir()
             && (flags.contains(OpenFlags::O_WRONLY) || flags.contains(OpenFlags::O_RDWR))
         {
@@ -423,12 +719,21 @@ impl DirectoryTreeNode {
         if path.starts_with('/') && path != path_cache_lock.0 {
             *path_cache_lock = (path.to_string(), Arc::downgrade(&inode.get_arc()));
         }
+        drop(path_cache_lock);
 
-        Ok(inode.file.open(flags, special_use))
+        let file = if special && path_only {
+            inode.file.clone()
+        } else if special {
+            inode.open_special(flags)?
+        } else {
+            inode.file.open(flags, special_use)
+        };
+        inode.fsnotify(InotifyMask::IN_OPEN);
+        Ok(file)
     }
 
     // 创建一个文件夹
-    pub fn mkdir(&self, path: &str) -> Result<(), isize> {
+    pub fn mkdir(&self, path: &str, mode: u32) -> Result<(), isize> {
         let inode = if path.starts_with("/") {
             &**ROOT
         } else {
@@ -443,31 +748,7 @@ impl DirectoryTreeNode {
         };
 
         if let Some(last_comp) = last_comp {
-            let mut lock = inode.children.write();
-            match inode.try_to_open_subfile(last_comp, &mut lock) {
-                Ok(_) => {
-                    return Err(EEXIST);
-                }
-                Err(ENOENT) => {
-                    let new_file = match inode.create(last_comp, DiskInodeType::Directory) {
-                        Ok(file) => file,
-                        Err(errno) => return Err(errno),
-                    };
-                    let key = (*last_comp).to_string();
-                    let value = Self::new(
-                        key.clone(),
-                        inode.filesystem.clone(),
-                        new_file,
-                        Arc::downgrade(&inode.get_arc()),
-                    );
-                    let new_inode = value.clone();
-                    lock.as_mut().unwrap().insert(key, value);
-                    new_inode
-                }
-                Err(errno) => {
-                    return Err(errno);
-                }
-            }
+            inode.create_child(last_comp, DiskInodeType::Directory, mode)?;
         } else {
             return Err(EEXIST);
         };
@@ -475,6 +756,32 @@ impl DirectoryTreeNode {
         Ok(())
     }
 
+    // 创建命名管道或设备节点，rdev为设备号
+    pub fn mknod(
+        &self,
+        path: &str,
+        file_type: DiskInodeType,
+        rdev: u32,
+        mode: u32,
+    ) -> Result<(), isize> {
+        let inode = if path.starts_with("/") {
+            &**ROOT
+        } else {
+            &self
+        };
+
+        let mut components = Self::parse_dir_path(path);
+        let last_comp = match components.pop() {
+            Some(last_comp) => last_comp,
+            None => return Err(EEXIST),
+        };
+        let inode = inode.cd_comp(&components)?;
+        inode.create_child_with(last_comp, || {
+            inode.file.mknod(last_comp, file_type, rdev, mode)
+        })?;
+        Ok(())
+    }
+
     // 删除一个文件夹或文件
     pub fn delete(&self, path: &str, delete_directory: bool) -> Result<(), isize> {
         if path.split('/').last().map_or(true, |x| x == ".") {
@@ -508,14 +815,24 @@ impl DirectoryTreeNode {
 
         match inode.father.lock().upgrade() {
             Some(par_inode) => {
+                par_inode.may_delete(&inode)?;
                 let mut lock = par_inode.children.write();
                 match inode.file.unlink(true) {
                     Ok(_) => {
                         let key = last_comp.to_string();
                         lock.as_mut().unwrap().remove(&key);
+                        // 文件已经被删除，记录为负缓存
+                        dcache_insert_negative(&Arc::downgrade(&par_inode), last_comp);
+                        if inode.file.is_dir() {
+                            dcache_invalidate_dir(&inode);
+                        }
+                        mark_inode_dirty(&par_inode);
                     }
                     Err(errno) => return Err(errno),
                 }
+                drop(lock);
+                par_inode.fsnotify_child(&inode, InotifyMask::IN_DELETE, last_comp, 0);
+                inode.fsnotify_delete_self();
             }
             None => return Err(EACCES),
         }
@@ -580,8 +897,12 @@ impl DirectoryTreeNode {
         if old_inode.filesystem.fs_id != new_par_inode.filesystem.fs_id {
             return Err(EXDEV);
         }
+        old_par_inode.may_delete(&old_inode)?;
+        new_par_inode.permission(MAY_WRITE | MAY_EXEC)?;
         let old_key = old_last_comp.to_string();
         let new_key = new_last_comp.to_string();
+        // 被覆盖的目标文件
+        let mut replaced = None;
         match new_par_inode.try_to_open_subfile(new_last_comp, &mut (*new_lock.lock())) {
             Ok(new_inode) => {
                 if new_inode.file.is_dir() && !old_inode.file.is_dir() {
@@ -593,10 +914,13 @@ impl DirectoryTreeNode {
                 if *new_inode.spe_usage.lock() > 0 {
                     return Err(EBUSY);
                 }
+                new_par_inode.may_delete(&new_inode)?;
                 // delete
                 match new_par_inode.file.unlink(true) {
                     Ok(_) => {
                         new_lock.lock().as_mut().unwrap().remove(&new_key);
+                        dcache_invalidate(&new_par_inode, new_last_comp);
+                        replaced = Some(new_inode);
                     }
                     Err(errno) => return Err(errno),
                 }
@@ -622,7 +946,23 @@ impl DirectoryTreeNode {
             FS_Type::Null => return Err(EACCES),
         }
         *value.father.lock() = Arc::downgrade(&new_par_inode.get_arc());
-        new_lock.lock().as_mut().unwrap().insert(new_key, value);
+        dcache_insert_negative(&Arc::downgrade(&old_par_inode), old_last_comp);
+        dcache_insert_positive(&Arc::downgrade(&new_par_inode), new_last_comp, &value);
+        new_lock
+            .lock()
+            .as_mut()
+            .unwrap()
+            .insert(new_key, value.clone());
+        mark_inode_dirty(&old_par_inode);
+        mark_inode_dirty(&new_par_inode);
+
+        let cookie = next_cookie();
+        old_par_inode.fsnotify_child(&value, InotifyMask::IN_MOVED_FROM, old_last_comp, cookie);
+        new_par_inode.fsnotify_child(&value, InotifyMask::IN_MOVED_TO, new_last_comp, cookie);
+        fsnotify_marks(&value.inotify_marks, InotifyMask::IN_MOVE_SELF, None, 0);
+        if let Some(replaced) = replaced {
+            replaced.fsnotify_delete_self();
+        }
 
         Ok(())
     }
@@ -637,6 +977,8 @@ pub fn oom() -> usize {
     const MAX_FAIL_TIME: usize = 3;
     let mut fail_time = 0;
     log::warn!("[oom] start oom");
+    let dentries = super::dcache::dcache_oom();
+    log::warn!("[oom] evict dentries: {}", dentries);
     let mut lock = DIRECTORY_VEC.lock();
     update_directory_vec(&mut lock);
     loop {
@@ -661,11 +1003,12 @@ pub fn init_fs() {
     init_device_directory();
     init_tmp_directory();
     init_proc_directory();
+    init_sys_directory();
 }
 #[allow(unused)]
 // 初始化设备目录
 fn init_device_directory() {
-    ROOT.mkdir("/dev");
+    ROOT.mkdir("/dev", 0o755);
 
     let dev_inode = match ROOT.cd_path("/dev") {
         Ok(inode) => inode,
@@ -674,44 +1017,39 @@ fn init_device_directory() {
 
     println!("[kernel] /dev init Successfully!");
 
-    dev_inode.mkdir("shm");
-    dev_inode.mkdir("misc");
+    dev_inode.mkdir("misc", 0o755);
 
-    println!("[kernel] shm and misc init Successfully!");
-
-    let null_dev = DirectoryTreeNode::new(
-        "null".to_string(),
-        Arc::new(FileSystem::new(FS_Type::Null)),
-        Arc::new(Null {}),
-        Arc::downgrade(&dev_inode.get_arc()),
-    );
-    println!("[kernel] null_dev init successfully!");
-    let zero_dev = DirectoryTreeNode::new(
-        "zero".to_string(),
+    // /dev/shm中的文件只保存在内存中，替换磁盘上可能存在的同名目录
+    let shm_dir = DirectoryTreeNode::new(
+        "shm".to_string(),
         Arc::new(FileSystem::new(FS_Type::Null)),
-        Arc::new(Zero {}),
-        Arc::downgrade(&dev_inode.get_arc()),
-    );
-    println!("[kernel] zero_dev init successfully!");
-    let urandom_dev = DirectoryTreeNode::new(
-        "urandom".to_string(),
-        Arc::new(FileSystem::new(FS_Type::Null)),
-        Arc::new(Urandom {}),
-        Arc::downgrade(&dev_inode.get_arc()),
-    );
-    println!("[kernel] urandom_dev init successfully!");
-    let tty_dev = DirectoryTreeNode::new(
-        "tty".to_string(),
-        Arc::new(FileSystem::new(FS_Type::Null)),
-        Arc::new(Teletype::new()),
+        Arc::new(ShmDir::new()),
         Arc::downgrade(&dev_inode.get_arc()),
     );
+    let mut lock = dev_inode.children.write();
+    lock.as_mut().unwrap().insert("shm".to_string(), shm_dir);
+    drop(lock);
+
+    println!("[kernel] shm and misc init Successfully!");
 
-    println!("[kernel] tty_dev init successfully!");
+    // 设备节点只记录设备号，打开时经设备注册表找到驱动
+    let devices = [
+        ("null", mkdev(MEM_MAJOR, 3)),
+        ("zero", mkdev(MEM_MAJOR, 5)),
+        ("urandom", mkdev(MEM_MAJOR, 9)),
+        ("tty", mkdev(TTYAUX_MAJOR, 0)),
+    ];
     let mut lock = dev_inode.children.write();
-    lock.as_mut().unwrap().insert("null".to_string(), null_dev);
-    lock.as_mut().unwrap().insert("zero".to_string(), zero_dev);
-    lock.as_mut().unwrap().insert("tty".to_string(), tty_dev);
+    for (name, rdev) in devices {
+        let node = DirectoryTreeNode::new(
+            name.to_string(),
+            Arc::new(FileSystem::new(FS_Type::Null)),
+            Arc::new(DeviceNode::new(DiskInodeType::Character, rdev)),
+            Arc::downgrade(&dev_inode.get_arc()),
+        );
+        lock.as_mut().unwrap().insert(name.to_string(), node);
+        println!("[kernel] {}_dev init successfully!", name);
+    }
     drop(lock);
 
     let misc_inode = match dev_inode.cd_path("./misc") {
@@ -721,7 +1059,10 @@ fn init_device_directory() {
     let hwclock_dev = DirectoryTreeNode::new(
         "rtc".to_string(),
         Arc::new(FileSystem::new(FS_Type::Null)),
-        Arc::new(Hwclock {}),
+        Arc::new(DeviceNode::new(
+            DiskInodeType::Character,
+            mkdev(RTC_MAJOR, 0),
+        )),
         Arc::downgrade(&misc_inode.get_arc()),
     );
     let mut lock = misc_inode.children.write();
@@ -733,14 +1074,14 @@ fn init_device_directory() {
 }
 // 初始化临时文件目录
 fn init_tmp_directory() {
-    match ROOT.mkdir("/tmp") {
+    match ROOT.mkdir("/tmp", 0o1777) {
         _ => {}
     }
     println!("[kernel] init_tmp_directory successfully!");
 }
 // 初始化进程目录
 fn init_proc_directory() {
-    match ROOT.mkdir("/proc") {
+    match ROOT.mkdir("/proc", 0o555) {
         _ => {}
     }
     println!("[kernel] init_proc_directory successfully!");
@@ -752,4 +1093,82 @@ fn init_proc_directory() {
         _ => {}
     }
     println!("[kernel] init_proc_mounts_directory successfully!");
+    let proc_inode = match ROOT.cd_path("/proc") {
+        Ok(inode) => inode,
+        Err(_) => panic!("proc directory doesn't exist"),
+    };
+    let diskstats = DirectoryTreeNode::new(
+        "diskstats".to_string(),
+        Arc::new(FileSystem::new(FS_Type::Null)),
+        Arc::new(DiskStats::new(diskstats)),
+        Arc::downgrade(&proc_inode.get_arc()),
+    );
+    let disklatency = DirectoryTreeNode::new(
+        "disklatency".to_string(),
+        Arc::new(FileSystem::new(FS_Type::Null)),
+        Arc::new(DiskStats::new(disklatency)),
+        Arc::downgrade(&proc_inode.get_arc()),
+    );
+    let slabinfo = DirectoryTreeNode::new(
+        "slabinfo".to_string(),
+        Arc::new(FileSystem::new(FS_Type::Null)),
+        Arc::new(DiskStats::new(crate::mm::slabinfo)),
+        Arc::downgrade(&proc_inode.get_arc()),
+    );
+    let mut lock = proc_inode.children.write();
+    proc_inode.cache_all_subfile(&mut lock);
+    lock.as_mut()
+        .unwrap()
+        .insert("diskstats".to_string(), diskstats);
+    lock.as_mut()
+        .unwrap()
+        .insert("disklatency".to_string(), disklatency);
+    lock.as_mut()
+        .unwrap()
+        .insert("slabinfo".to_string(), slabinfo);
+    drop(lock);
+    #[cfg(feature = "swap")]
+    {
+        let swaps = DirectoryTreeNode::new(
+            "swaps".to_string(),
+            Arc::new(FileSystem::new(FS_Type::Null)),
+            Arc::new(DiskStats::new(super::swap::swaps)),
+            Arc::downgrade(&proc_inode.get_arc()),
+        );
+        let mut lock = proc_inode.children.write();
+        lock.as_mut().unwrap().insert("swaps".to_string(), swaps);
+    }
+    println!("[kernel] init_proc_diskstats successfully!");
+}
+// 初始化sysfs目录，目前只有zram的属性文件
+fn init_sys_directory() {
+    #[cfg(feature = "zram")]
+    {
+        for path in ["/sys", "/sys/block", "/sys/block/zram0"] {
+            match ROOT.mkdir(path, 0o755) {
+                _ => {}
+            }
+        }
+        let zram_inode = match ROOT.cd_path("/sys/block/zram0") {
+            Ok(inode) => inode,
+            Err(_) => panic!("zram0 directory doesn't exist"),
+        };
+        let mut lock = zram_inode.children.write();
+        zram_inode.cache_all_subfile(&mut lock);
+        for (name, show, store) in crate::mm::zram_attrs() {
+            let file = match store {
+                Some(store) => DiskStats::new_writable(show, store),
+                None => DiskStats::new(show),
+            };
+            let node = DirectoryTreeNode::new(
+                name.to_string(),
+                Arc::new(FileSystem::new(FS_Type::Null)),
+                Arc::new(file),
+                Arc::downgrade(&zram_inode.get_arc()),
+            );
+            lock.as_mut().unwrap().insert(name.to_string(), node);
+        }
+        drop(lock);
+        println!("[kernel] init_sys_zram successfully!");
+    }
 }
@@ -0,0 +1,533 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use spin::Mutex;
+
+use crate::{
+    fs::{
+        directory_tree::DirectoryTreeNode,
+        file_trait::File,
+        layout::Stat,
+        poll::{signal_pending, PollCallback, PollEvent, PollWaitQueue, PollWaiter},
+        StatMode,
+    },
+    mm::UserBuffer,
+    syscall::errno::{EEXIST, EINTR, EINVAL, ELOOP, ENOENT, ENOTDIR, ESPIPE},
+    timer::TimeSpec,
+};
+
+pub const EPOLL_CTL_ADD: usize = 1;
+pub const EPOLL_CTL_DEL: usize = 2;
+pub const EPOLL_CTL_MOD: usize = 3;
+/// 与`O_CLOEXEC`相同
+pub const EPOLL_CLOEXEC: usize = 0o2000000;
+/// epoll实例之间互相关注的最大嵌套深度
+const EP_MAX_NESTS: usize = 4;
+
+bitflags! {
+    /// epoll事件，低16位与`PollEvent`含义相同
+    pub struct EpollEventMask: u32 {
+        const EPOLLIN = 0x001;
+        const EPOLLPRI = 0x002;
+        const EPOLLOUT = 0x004;
+        const EPOLLERR = 0x008;
+        const EPOLLHUP = 0x010;
+        const EPOLLRDNORM = 0x040;
+        const EPOLLRDBAND = 0x080;
+        const EPOLLWRNORM = 0x100;
+        const EPOLLWRBAND = 0x200;
+        const EPOLLMSG = 0x400;
+        const EPOLLRDHUP = 0x2000;
+        /// 多个epoll实例关注同一文件时，每次事件只唤醒其中一个
+        const EPOLLEXCLUSIVE = 1 << 28;
+        const EPOLLWAKEUP = 1 << 29;
+        /// 上报一次后禁用，直到`EPOLL_CTL_MOD`重新启用
+        const EPOLLONESHOT = 1 << 30;
+        /// 边沿触发：只有状态变化时才上报
+        const EPOLLET = 1 << 31;
+    }
+}
+
+impl EpollEventMask {
+    fn poll_events(&self) -> PollEvent {
+        PollEvent::from_bits_truncate(self.bits() as u16)
+    }
+}
+
+/// 用户态的`struct epoll_event`，只有x86_64上它是紧凑布局
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EpollEvent {
+    pub events: u32,
+    pub data: u64,
+}
+
+/// 兴趣列表中的一项
+struct EpollItem {
+    file: Weak<dyn File>,
+    events: EpollEventMask,
+    data: u64,
+    /// 在文件等待队列上注册的回调，`None`表示该文件只能被轮询
+    registration: Option<(Arc<PollWaitQueue>, usize)>,
+    /// 是否已在就绪链表中
+    queued: bool,
+    /// 设置了`EPOLLONESHOT`且已经上报过
+    disabled: bool,
+}
+
+impl EpollItem {
+    fn unregister(&self) {
+        if let Some((queue, key)) = self.registration.as_ref() {
+            queue.remove(*key);
+        }
+    }
+}
+
+struct EpollInner {
+    /// 兴趣列表，以fd为键
+    items: BTreeMap<usize, EpollItem>,
+    /// 可能就绪的fd，收集事件时再逐个确认
+    ready: VecDeque<usize>,
+}
+
+impl EpollInner {
+    fn enqueue(&mut self, fd: usize) -> bool {
+        match self.items.get_mut(&fd) {
+            Some(item) if !item.disabled && !item.queued => {
+                item.queued = true;
+                self.ready.push_back(fd);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// `epoll_create1()`创建的epoll实例。
+/// 被关注的文件状态变化时通过其等待队列回调将对应fd放入就绪链表，
+/// `epoll_pwait()`只需检查就绪链表中的文件。
+pub struct EpollInstance {
+    inner: Arc<Mutex<EpollInner>>,
+    /// 在`epoll_pwait()`中睡眠的任务，以及关注本实例的其他epoll实例
+    wait_queue: Arc<PollWaitQueue>,
+}
+
+impl EpollInstance {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(EpollInner {
+                items: BTreeMap::new(),
+                ready: VecDeque::new(),
+            })),
+            wait_queue: Arc::new(PollWaitQueue::new()),
+        }
+    }
+    /// 生成注册在`fd`对应文件等待队列上的回调
+    fn callback(&self, fd: usize) -> PollCallback {
+        let inner = Arc::downgrade(&self.inner);
+        let wait_queue = Arc::downgrade(&self.wait_queue);
+        Arc::new(move |events| {
+            let inner = match inner.upgrade() {
+                Some(inner) => inner,
+                None => return false,
+            };
+            let mut lock = inner.lock();
+            let interested = match lock.items.get(&fd) {
+                Some(item) => {
+                    !item.disabled
+                        && events.intersects(
+                            item.events.poll_events() | PollEvent::POLLERR | PollEvent::POLLHUP,
+                        )
+                }
+                None => false,
+            };
+            if !interested {
+                return false;
+            }
+            lock.enqueue(fd);
+            drop(lock);
+            if let Some(wait_queue) = wait_queue.upgrade() {
+                wait_queue.notify(PollEvent::POLLIN | PollEvent::POLLRDNORM);
+            }
+            true
+        })
+    }
+    /// 本实例是否（间接地）关注了`target`
+    fn watches(&self, target: &Arc<Mutex<EpollInner>>, depth: usize) -> Result<bool, isize> {
+        if depth >= EP_MAX_NESTS {
+            return Err(ELOOP);
+        }
+        let files: Vec<Arc<dyn File>> = self
+            .inner
+            .lock()
+            .items
+            .values()
+            .filter_map(|item| item.file.upgrade())
+            .collect();
+        for file in files {
+            if let Some(epoll) = file.downcast_ref::<EpollInstance>() {
+                if Arc::ptr_eq(&epoll.inner, target) || epoll.watches(target, depth + 1)? {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+    /// 修改兴趣列表，`op`为`EPOLL_CTL_*`
+    pub fn ctl(
+        &self,
+        op: usize,
+        fd: usize,
+        file: Arc<dyn File>,
+        event: EpollEvent,
+    ) -> Result<(), isize> {
+        let events = EpollEventMask::from_bits_truncate(event.events);
+        // EPOLLEXCLUSIVE只能与这些标志一起使用
+        let exclusive_allowed = EpollEventMask::EPOLLIN
+            | EpollEventMask::EPOLLOUT
+            | EpollEventMask::EPOLLERR
+            | EpollEventMask::EPOLLHUP
+            | EpollEventMask::EPOLLWAKEUP
+            | EpollEventMask::EPOLLET
+            | EpollEventMask::EPOLLEXCLUSIVE;
+        match op {
+            EPOLL_CTL_ADD => {
+                if events.contains(EpollEventMask::EPOLLEXCLUSIVE)
+                    && !exclusive_allowed.contains(events)
+                {
+                    return Err(EINVAL);
+                }
+                if let Some(epoll) = file.downcast_ref::<EpollInstance>() {
+                    if Arc::ptr_eq(&epoll.inner, &self.inner) || epoll.watches(&self.inner, 0)? {
+                        return Err(ELOOP);
+                    }
+                }
+                if self.inner.lock().items.contains_key(&fd) {
+                    return Err(EEXIST);
+                }
+                let registration = file.poll_queue().map(|queue| {
+                    let key = queue.add(
+                        events.poll_events(),
+                        events.contains(EpollEventMask::EPOLLEXCLUSIVE),
+                        self.callback(fd),
+                    );
+                    (queue, key)
+                });
+                let mut inner = self.inner.lock();
+                inner.items.insert(
+                    fd,
+                    EpollItem {
+                        file: Arc::downgrade(&file),
+                        events,
+                        data: event.data,
+                        registration,
+                        queued: false,
+                        disabled: false,
+                    },
+                );
+                // 加入时文件可能已经就绪，交给收集事件时确认
+                inner.enqueue(fd);
+            }
+            EPOLL_CTL_MOD => {
+                if events.contains(EpollEventMask::EPOLLEXCLUSIVE) {
+                    return Err(EINVAL);
+                }
+                let old = {
+                    let mut inner = self.inner.lock();
+                    match inner.items.get_mut(&fd) {
+                        Some(item) if item.events.contains(EpollEventMask::EPOLLEXCLUSIVE) => {
+                            return Err(EINVAL);
+                        }
+                        Some(item) => {
+                            item.events = events;
+                            item.data = event.data;
+                            item.disabled = false;
+                            item.registration.take()
+                        }
+                        None => return Err(ENOENT),
+                    }
+                };
+                // 以新的事件掩码重新注册
+                let registration = old.map(|(queue, key)| {
+                    queue.remove(key);
+                    let key = queue.add(events.poll_events(), false, self.callback(fd));
+                    (queue, key)
+                });
+                let mut inner = self.inner.lock();
+                if let Some(item) = inner.items.get_mut(&fd) {
+                    item.registration = registration;
+                }
+                inner.enqueue(fd);
+            }
+            EPOLL_CTL_DEL => {
+                let mut inner = self.inner.lock();
+                match inner.items.remove(&fd) {
+                    Some(item) => item.unregister(),
+                    None => return Err(ENOENT),
+                }
+                inner.ready.retain(|ready| *ready != fd);
+            }
+            _ => return Err(EINVAL),
+        }
+        self.wait_queue
+            .notify(PollEvent::POLLIN | PollEvent::POLLRDNORM);
+        Ok(())
+    }
+    /// 检查就绪链表，收集至多`max`个事件
+    fn harvest(&self, max: usize) -> Vec<EpollEvent> {
+        let candidates: Vec<(usize, Weak<dyn File>, EpollEventMask, u64)> = {
+            let mut lock = self.inner.lock();
+            let inner = &mut *lock;
+            // 没有等待队列的文件无法通知我们，每次都要检查
+            for (fd, item) in inner.items.iter_mut() {
+                if item.registration.is_none() && !item.disabled && !item.queued {
+                    item.queued = true;
+                    inner.ready.push_back(*fd);
+                }
+            }
+            let items = &mut inner.items;
+            inner
+                .ready
+                .drain(..)
+                .filter_map(|fd| {
+                    let item = items.get_mut(&fd)?;
+                    item.queued = false;
+                    Some((fd, item.file.clone(), item.events, item.data))
+                })
+                .collect()
+        };
+        // 检查文件状态时不持有实例的锁，文件可能在其中通知等待队列
+        let mut events = Vec::new();
+        let mut requeue = Vec::new();
+        let mut disable = Vec::new();
+        let mut dead = Vec::new();
+        for (fd, file, mask, data) in candidates {
+            if events.len() >= max {
+                requeue.push(fd);
+                continue;
+            }
+            let file = match file.upgrade() {
+                Some(file) => file,
+                None => {
+                    dead.push(fd);
+                    continue;
+                }
+            };
+            let revents = file.poll(mask.poll_events())
+                & (mask.poll_events() | PollEvent::POLLERR | PollEvent::POLLHUP);
+            if revents.is_empty() {
+                continue;
+            }
+            events.push(EpollEvent {
+                events: revents.bits() as u32,
+                data,
+            });
+            if mask.contains(EpollEventMask::EPOLLONESHOT) {
+                disable.push(fd);
+            } else if !mask.contains(EpollEventMask::EPOLLET) {
+                // 水平触发：下次仍需检查
+                requeue.push(fd);
+            }
+        }
+        let mut inner = self.inner.lock();
+        for fd in dead {
+            // 文件已经被关闭
+            if let Some(item) = inner.items.remove(&fd) {
+                item.unregister();
+            }
+        }
+        for fd in disable {
+            if let Some(item) = inner.items.get_mut(&fd) {
+                item.disabled = true;
+            }
+        }
+        for fd in requeue {
+            inner.enqueue(fd);
+        }
+        events
+    }
+    /// 等待至多`max`个事件，直到有事件、到达`timeout`或被信号打断
+    pub fn wait(&self, max: usize, timeout: Option<TimeSpec>) -> Result<Vec<EpollEvent>, isize> {
+        let mut waiter = PollWaiter::new();
+        waiter.register_queue(Some(self.wait_queue.clone()), PollEvent::POLLIN);
+        loop {
+            let events = self.harvest(max);
+            if !events.is_empty() {
+                return Ok(events);
+            }
+            if let Some(timeout) = timeout {
+                if TimeSpec::now() >= timeout {
+                    return Ok(events);
+                }
+            }
+            if signal_pending() {
+                return Err(EINTR);
+            }
+            if self
+                .inner
+                .lock()
+                .items
+                .values()
+                .any(|item| item.registration.is_none() && !item.disabled)
+            {
+                waiter.register_queue(None, PollEvent::empty());
+            }
+            waiter.sleep(timeout);
+        }
+    }
+}
+
+impl Drop for EpollInstance {
+    fn drop(&mut self) {
+        for item in self.inner.lock().items.values() {
+            item.unregister();
+        }
+    }
+}
+
+#[allow(unused)]
+impl File for EpollInstance {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        todo!()
+    }
+
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn r_ready(&self) -> bool {
+        !self.inner.lock().ready.is_empty()
+    }
+
+    fn w_ready(&self) -> bool {
+        false
+    }
+
+    fn read_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        EINVAL as usize
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        EINVAL as usize
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 13),
+            1,
+            StatMode::S_IFREG.bits() | 0o600,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(&self, dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        todo!()
+    }
+
+    fn open_subfile(
+        &self,
+    ) -> Result<alloc::vec::Vec<(alloc::string::String, alloc::sync::Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> alloc::vec::Vec<Dirent> {
+        alloc::vec::Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        offset: usize,
+    ) -> Result<Arc<spin::Mutex<crate::fs::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(
+        &self,
+    ) -> Result<alloc::vec::Vec<Arc<spin::Mutex<crate::fs::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn poll_queue(&self) -> Option<Arc<PollWaitQueue>> {
+        Some(self.wait_queue.clone())
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
@@ -0,0 +1,271 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::sync::{Arc, Weak};
+use spin::Mutex;
+
+use crate::{
+    fs::{
+        directory_tree::DirectoryTreeNode,
+        file_trait::File,
+        layout::Stat,
+        poll::{signal_pending, PollEvent, PollWaitQueue, PollWaiter},
+        StatMode,
+    },
+    mm::UserBuffer,
+    syscall::errno::{EAGAIN, EINTR, EINVAL, ENOTDIR, ESPIPE},
+};
+
+/// 每次读取只将计数器减一
+pub const EFD_SEMAPHORE: usize = 1;
+/// 与`O_NONBLOCK`相同
+pub const EFD_NONBLOCK: usize = 0o4000;
+/// 与`O_CLOEXEC`相同
+pub const EFD_CLOEXEC: usize = 0o2000000;
+
+/// 计数器的上限，写入会使计数超过它时阻塞
+const EVENTFD_MAX: u64 = u64::MAX - 1;
+
+/// `eventfd2()`创建的事件计数器。
+/// 写入将8字节的值加到计数器上，读取返回计数器的值并将其清零（信号量模式下返回1并减一）。
+pub struct EventFd {
+    count: Mutex<u64>,
+    semaphore: bool,
+    nonblock: bool,
+    wait_queue: Arc<PollWaitQueue>,
+}
+
+impl EventFd {
+    pub fn new(initval: u64, flags: usize) -> Self {
+        Self {
+            count: Mutex::new(initval),
+            semaphore: flags & EFD_SEMAPHORE != 0,
+            nonblock: flags & EFD_NONBLOCK != 0,
+            wait_queue: Arc::new(PollWaitQueue::new()),
+        }
+    }
+    /// 不断尝试`op`直到成功；计数器不满足条件时在等待队列上睡眠，直到`events`发生
+    fn wait_until(
+        &self,
+        events: PollEvent,
+        mut op: impl FnMut(&mut u64) -> Option<u64>,
+    ) -> Result<u64, isize> {
+        let mut waiter = PollWaiter::new();
+        waiter.register_queue(Some(self.wait_queue.clone()), events);
+        loop {
+            if let Some(value) = op(&mut self.count.lock()) {
+                return Ok(value);
+            }
+            if self.nonblock {
+                return Err(EAGAIN);
+            }
+            if signal_pending() {
+                return Err(EINTR);
+            }
+            waiter.sleep(None);
+        }
+    }
+    fn read_value(&self) -> Result<u64, isize> {
+        let value = self.wait_until(PollEvent::POLLIN, |count| {
+            if *count == 0 {
+                return None;
+            }
+            let value = if self.semaphore { 1 } else { *count };
+            *count -= value;
+            Some(value)
+        })?;
+        self.wait_queue
+            .notify(PollEvent::POLLOUT | PollEvent::POLLWRNORM);
+        Ok(value)
+    }
+    fn write_value(&self, value: u64) -> Result<(), isize> {
+        if value == u64::MAX {
+            return Err(EINVAL);
+        }
+        self.wait_until(PollEvent::POLLOUT, |count| {
+            if EVENTFD_MAX - *count < value {
+                return None;
+            }
+            *count += value;
+            Some(value)
+        })?;
+        if value != 0 {
+            self.wait_queue
+                .notify(PollEvent::POLLIN | PollEvent::POLLRDNORM);
+        }
+        Ok(())
+    }
+}
+
+#[allow(unused)]
+impl File for EventFd {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        todo!()
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        if buf.len() < 8 {
+            return EINVAL as usize;
+        }
+        match self.read_value() {
+            Ok(value) => {
+                buf[..8].copy_from_slice(&value.to_ne_bytes());
+                8
+            }
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        if buf.len() < 8 {
+            return EINVAL as usize;
+        }
+        let mut value = [0u8; 8];
+        value.copy_from_slice(&buf[..8]);
+        match self.write_value(u64::from_ne_bytes(value)) {
+            Ok(()) => 8,
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn r_ready(&self) -> bool {
+        *self.count.lock() > 0
+    }
+
+    fn w_ready(&self) -> bool {
+        *self.count.lock() < EVENTFD_MAX
+    }
+
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        if buf.len() < 8 {
+            return EINVAL as usize;
+        }
+        match self.read_value() {
+            Ok(value) => buf.write(&value.to_ne_bytes()),
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        if buf.len() < 8 {
+            return EINVAL as usize;
+        }
+        let mut value = [0u8; 8];
+        buf.read(&mut value);
+        match self.write_value(u64::from_ne_bytes(value)) {
+            Ok(()) => 8,
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 13),
+            1,
+            StatMode::S_IFREG.bits() | 0o600,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(&self, dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        todo!()
+    }
+
+    fn open_subfile(
+        &self,
+    ) -> Result<alloc::vec::Vec<(alloc::string::String, alloc::sync::Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> alloc::vec::Vec<Dirent> {
+        alloc::vec::Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        offset: usize,
+    ) -> Result<Arc<spin::Mutex<crate::fs::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(
+        &self,
+    ) -> Result<alloc::vec::Vec<Arc<spin::Mutex<crate::fs::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn poll_queue(&self) -> Option<Arc<PollWaitQueue>> {
+        Some(self.wait_queue.clone())
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
@@ -169,6 +169,25 @@ impl Ext4Inode {
         self.links_count = links_count;
     }
 
+    /// 完整的32位属主，高16位在`osd2`中
+    pub fn owner_uid(&self) -> u32 {
+        self.uid as u32 | ((self.osd2.l_i_uid_high as u32) << 16)
+    }
+
+    pub fn set_owner_uid(&mut self, uid: u32) {
+        self.uid = uid as u16;
+        self.osd2.l_i_uid_high = (uid >> 16) as u16;
+    }
+
+    pub fn owner_gid(&self) -> u32 {
+        self.gid as u32 | ((self.osd2.l_i_gid_high as u32) << 16)
+    }
+
+    pub fn set_owner_gid(&mut self, gid: u32) {
+        self.gid = gid as u16;
+        self.osd2.l_i_gid_high = (gid >> 16) as u16;
+    }
+
     pub fn blocks_count(&self) -> u64 {
         let mut blocks = self.blocks as u64;
         if self.osd2.l_i_blocks_high != 0 {
@@ -206,6 +225,25 @@ impl Ext4Inode {
         self.block = block;
     }
 
+    /// 设备文件的设备号，与Linux相同地保存在`i_block`中：
+    /// 主次设备号都小于256时使用旧格式存于`i_block[0]`，否则使用新格式存于`i_block[1]`
+    pub fn rdev(&self) -> u32 {
+        if self.block[0] != 0 {
+            self.block[0] & 0xffff
+        } else {
+            self.block[1]
+        }
+    }
+
+    pub fn set_rdev(&mut self, rdev: u32) {
+        self.block = [0; 15];
+        if rdev & !0xffff == 0 {
+            self.block[0] = rdev;
+        } else {
+            self.block[1] = rdev;
+        }
+    }
+
     pub fn generation(&self) -> u32 {
         self.generation
     }
@@ -709,6 +747,14 @@ impl InodeTrait for Ext4Inode {
         todo!()
     }
 
+    fn sync(&self, datasync: bool) -> Result<(), isize> {
+        todo!()
+    }
+
+    fn readahead(&self, range: core::ops::Range<usize>) -> usize {
+        todo!()
+    }
+
     fn modify_size_lock(
         &self,
         inode_lock: &RwLockWriteGuard<InodeLock>,
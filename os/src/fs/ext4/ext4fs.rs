@@ -135,7 +135,7 @@ impl Ext4FileSystem {
                 if is_goal {
                     inode_mode = ftype;
                 } else {
-                    inode_mode = InodeFileType::S_IFDIR.bits();
+                    inode_mode = InodeFileType::S_IFDIR.bits() | 0o755;
                 }
 
                 let new_inode_ref = self.create(*parent, current_path, inode_mode)?;
@@ -218,7 +218,13 @@ impl Ext4FileSystem {
         // start from root
         let mut parent = ROOT_INODE;
 
-        let r = self.generic_open(path, &mut parent, true, filetype.bits(), &mut nameoff);
+        let r = self.generic_open(
+            path,
+            &mut parent,
+            true,
+            filetype.bits() | 0o755,
+            &mut nameoff,
+        );
         Ok(EOK)
     }
     pub fn unlink(
@@ -289,4 +295,7 @@ impl VFS for Ext4FileSystem {
     fn get_filesystem_type(&self) -> FS_Type {
         FS_Type::Ext4
     }
+    fn sync(&self) {
+        self.cache_mgr.lock().sync_all(&self.block_device);
+    }
 }
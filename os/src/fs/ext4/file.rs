@@ -305,7 +305,7 @@ impl Ext4FileSystem {
         let mut inode = Ext4Inode::default();
 
         // 设置文件类型和权限
-        inode.set_mode(inode_mode | 0o777);
+        inode.set_mode(inode_mode);
 
         // set extra size
         let inode_size = self.superblock.inode_size();
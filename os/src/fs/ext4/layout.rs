@@ -7,15 +7,18 @@ use crate::{
         ext4::{
             block_group::Block,
             direntry::{DirEntryType, Ext4DirEntryTail},
-            InodeFileType, PageCache, BLOCK_SIZE,
+            InodeFileType, PageCache, BLOCK_SIZE, EXT4_INODE_MODE_TYPE_MASK,
         },
         file_trait::File,
         inode::{InodeLock, InodeTrait},
+        inotify::InotifyMask,
+        locks::LockOwner,
+        readahead::{pages_of, FileReadahead, POSIX_FADV_WILLNEED},
         vfs::VFS,
         DiskInodeType, OpenFlags, SeekWhence, Stat, StatMode,
     },
     lang_items::Bytes,
-    mm::UserBuffer,
+    mm::{FileMappings, UserBuffer},
     syscall::errno::{EINVAL, ENOTDIR, ENOTEMPTY},
 };
 use alloc::{
@@ -29,7 +32,9 @@ use spin::{Mutex, RwLock};
 use core::{
     convert::TryInto,
     fmt::Debug,
-    mem, panic,
+    mem,
+    ops::Range,
+    panic,
     ptr::{addr_of, addr_of_mut, read},
 };
 
@@ -37,7 +42,7 @@ use super::{
     direntry::Ext4DirEntry,
     ext4fs::Ext4FileSystem,
     file::{Ext4FileContent, Ext4FileContentWrapper},
-    Cache, Ext4Inode, Ext4InodeRef, InodePerm, PageCacheManager,
+    Cache, Ext4Inode, Ext4InodeRef, PageCacheManager,
 };
 
 // 可能后续会用到？
@@ -57,6 +62,8 @@ pub struct Ext4OSInode {
     special_use: bool,
     /// 是否追加
     append: bool,
+    /// 由`open()`得到，释放时放掉它持有的文件锁并产生inotify关闭事件
+    opened: bool,
     /// 具体的Inode
     inode: Arc<Mutex<Ext4InodeRef>>,
     /// 文件偏移
@@ -69,6 +76,8 @@ pub struct Ext4OSInode {
     inode_lock: Arc<RwLock<InodeLock>>,
     /// 文件缓存
     file_cache_manager: Arc<PageCacheManager>,
+    /// 预读状态
+    ra: Mutex<FileReadahead>,
 }
 
 impl Ext4OSInode {
@@ -80,16 +89,24 @@ impl Ext4OSInode {
             writable: true,
             special_use: true,
             append: false,
+            opened: false,
             inode: Arc::new(Mutex::new(root_inode)),
             offset: Mutex::new(0),
             dirnode_ptr: Arc::new(Mutex::new(Weak::new())),
             ext4fs,
-            file_cache_manager: Arc::new(PageCacheManager::new()),
+            file_cache_manager: PageCacheManager::new(),
+            ra: Mutex::new(FileReadahead::new()),
         })
     }
 }
 
 impl Ext4OSInode {
+    // 文件内容或大小被修改后产生inotify修改事件
+    fn notify_modify(&self) {
+        if let Some(node) = self.get_dirtree_node() {
+            node.fsnotify(InotifyMask::IN_MODIFY);
+        }
+    }
     pub fn first_root_inode(ext4fs: &Arc<dyn VFS>) -> Arc<dyn File> {
         let ext4fs_concrete = Arc::downcast::<Ext4FileSystem>(ext4fs.clone()).unwrap();
         // 先获取ROOT_INODE
@@ -109,6 +126,19 @@ impl Drop for Ext4OSInode {
                 None => {}
             }
         }
+        if self.opened {
+            if let Some(inode) = self.get_dirtree_node() {
+                // 最后一个引用被释放，该打开文件持有的flock()锁与OFD锁随之释放
+                inode
+                    .file_locks()
+                    .remove_owner(LockOwner::File(self as *const Self as usize));
+                inode.fsnotify(if self.writable {
+                    InotifyMask::IN_CLOSE_WRITE
+                } else {
+                    InotifyMask::IN_CLOSE_NOWRITE
+                });
+            }
+        }
     }
 }
 
@@ -129,11 +159,13 @@ impl File for Ext4OSInode {
             writable: self.writable,
             special_use: self.special_use,
             append: self.append,
+            opened: false,
             inode: self.inode.clone(),
             offset: Mutex::new(*self.offset.lock()),
             dirnode_ptr: self.dirnode_ptr.clone(),
             ext4fs: self.ext4fs.clone(),
             file_cache_manager: self.file_cache_manager.clone(),
+            ra: Mutex::new(self.ra.lock().clone()),
         })
     }
 
@@ -157,6 +189,7 @@ impl File for Ext4OSInode {
                 if start >= end {
                     return 0;
                 }
+                self.ondemand_readahead(start, end - start, &Arc::new(inode_ref.clone()));
                 let mut start_cache = start / PageCacheManager::CACHE_SZ;
                 let mut read_size = 0;
                 loop {
@@ -202,6 +235,7 @@ impl File for Ext4OSInode {
                 if start >= end {
                     return 0;
                 }
+                self.ondemand_readahead(start, end - start, &Arc::new(inode_ref.clone()));
                 let mut start_cache = start / PageCacheManager::CACHE_SZ;
                 let mut read_size = 0;
                 loop {
@@ -261,6 +295,7 @@ impl File for Ext4OSInode {
         match offset {
             Some(mut offset) => {
                 let mut offset = &mut offset;
+                self.ondemand_readahead(*offset, buf.len(), &Arc::new(inode_ref.clone()));
                 for slice in buf.buffers.iter_mut() {
                     let read_size =
                         self.read_at_block_cache(*offset, *slice, Arc::new(inode_ref.clone()));
@@ -273,6 +308,7 @@ impl File for Ext4OSInode {
             }
             None => {
                 let mut offset = self.offset.lock();
+                self.ondemand_readahead(*offset, buf.len(), &Arc::new(inode_ref.clone()));
                 for slice in buf.buffers.iter_mut() {
                     let read_size =
                         self.read_at_block_cache(*offset, *slice, Arc::new(inode_ref.clone()));
@@ -322,6 +358,11 @@ impl File for Ext4OSInode {
                 }
             }
         }
+        drop(inode_lock);
+        drop(inode_ref);
+        if total_write_size > 0 {
+            self.notify_modify();
+        }
         total_write_size
     }
 
@@ -340,28 +381,27 @@ impl File for Ext4OSInode {
         let mtime = inode_ref.inode.mtime();
         let ctime = inode_ref.inode.ctime();
 
-        let st_mod: u32 = {
-            if inode_ref.inode.get_file_type() == DiskInodeType::Directory {
-                (StatMode::S_IFDIR | StatMode::S_IRWXU | StatMode::S_IRWXG | StatMode::S_IRWXO)
-                    .bits()
-            } else {
-                (StatMode::S_IFREG | StatMode::S_IRWXU | StatMode::S_IRWXG | StatMode::S_IRWXO)
-                    .bits()
-            }
+        let file_type = inode_ref.inode.get_file_type();
+        let st_mod: u32 = file_type.stat_mode().bits() | (inode_ref.inode.mode() as u32 & 0o7777);
+        let rdev = match file_type {
+            DiskInodeType::Character | DiskInodeType::Block => inode_ref.inode.rdev(),
+            _ => 0,
         };
-        Stat::new(
+        let mut stat = Stat::new(
             // 下面的时间用i64有点逆天了
             // 后面可能得把Stat改一下
             crate::makedev!(8, 0),
             inode_ref.inode_num as u64,
             st_mod,
             1,
-            0,
+            rdev as u64,
             size as i64,
             atime as i64,
             mtime as i64,
             ctime as i64,
-        )
+        );
+        stat.set_owner(inode_ref.inode.owner_uid(), inode_ref.inode.owner_gid());
+        stat
     }
 
     /// 获取文件类型
@@ -382,16 +422,18 @@ impl File for Ext4OSInode {
     /// 打开文件
     fn open(&self, flags: OpenFlags, special_use: bool) -> Arc<dyn File> {
         Arc::new(Self {
-            readable: flags.contains(OpenFlags::O_RDONLY) || flags.contains(OpenFlags::O_RDWR),
-            writable: flags.contains(OpenFlags::O_WRONLY) || flags.contains(OpenFlags::O_RDWR),
+            readable: flags.readable(),
+            writable: flags.writable(),
             special_use,
             append: flags.contains(OpenFlags::O_APPEND),
+            opened: true,
             inode: self.inode.clone(),
             offset: Mutex::new(0),
             dirnode_ptr: self.dirnode_ptr.clone(),
             ext4fs: self.ext4fs.clone(),
             inode_lock: self.inode_lock.clone(),
             file_cache_manager: self.file_cache_manager.clone(),
+            ra: Mutex::new(FileReadahead::new()),
         })
     }
 
@@ -419,12 +461,14 @@ impl File for Ext4OSInode {
                 writable: true,
                 special_use: false,
                 append: false,
+                opened: false,
                 inode: Arc::new(Mutex::new(self.ext4fs.get_inode_ref(entry.inode))),
                 offset: Mutex::new(0),
                 dirnode_ptr: Arc::new(Mutex::new(Weak::new())),
                 ext4fs: self.ext4fs.clone(),
                 // maybe wrong
-                file_cache_manager: Arc::new(PageCacheManager::new()),
+                file_cache_manager: PageCacheManager::new(),
+                ra: Mutex::new(FileReadahead::new()),
             })
         };
 
@@ -446,6 +490,7 @@ impl File for Ext4OSInode {
         &self,
         name: &str,
         file_type: crate::fs::DiskInodeType,
+        mode: u32,
     ) -> Result<Arc<dyn File>, isize> {
         let inode_lock = self.inode_lock.write();
         // 如何获取inode_mode?
@@ -454,6 +499,8 @@ impl File for Ext4OSInode {
             DiskInodeType::Directory => InodeFileType::S_IFDIR.bits(),
             _ => todo!(),
         };
+        // 权限位由调用者给出，已经去掉了umask屏蔽的位
+        let inode_perm = (mode & 0o7777) as u16;
 
         let inode_ref = self.inode.lock();
         let mut nameoff = 0;
@@ -462,7 +509,7 @@ impl File for Ext4OSInode {
                 name,
                 &mut inode_ref.inode_num.clone(),
                 true,
-                inode_mode,
+                inode_mode | inode_perm,
                 &mut nameoff,
             );
             if let Ok(new_inode_num) = new_inode_num {
@@ -473,19 +520,20 @@ impl File for Ext4OSInode {
                     writable: true,
                     special_use: false,
                     append: false,
+                    opened: false,
                     inode: Arc::new(Mutex::new(new_inode_ref)),
                     offset: Mutex::new(0),
                     dirnode_ptr: Arc::new(Mutex::new(Weak::new())),
                     ext4fs: self.ext4fs.clone(),
                     // maybe wrong
-                    file_cache_manager: Arc::new(PageCacheManager::new()),
+                    file_cache_manager: PageCacheManager::new(),
+                    ra: Mutex::new(FileReadahead::new()),
                 }));
             } else {
                 panic!()
             }
         }
         println!("[kernel] inode_mode={}", inode_mode);
-        let inode_perm = (InodePerm::S_IREAD | InodePerm::S_IWRITE).bits();
         println!("[kernel] inode_perm={}", inode_perm);
         let new_inode_ref = self
             .ext4fs
@@ -498,18 +546,60 @@ impl File for Ext4OSInode {
                 writable: true,
                 special_use: false,
                 append: false,
+                opened: false,
                 inode: Arc::new(Mutex::new(inode_ref)),
                 offset: Mutex::new(0),
                 dirnode_ptr: Arc::new(Mutex::new(Weak::new())),
                 ext4fs: self.ext4fs.clone(),
                 // maybe wrong
-                file_cache_manager: Arc::new(PageCacheManager::new()),
+                file_cache_manager: PageCacheManager::new(),
+                ra: Mutex::new(FileReadahead::new()),
             }))
         } else {
             panic!()
         }
     }
 
+    /// 创建命名管道或设备节点
+    /// 文件类型保存在mode中，设备号保存在`i_block`中，这类inode不使用extent
+    fn mknod(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        rdev: u32,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
+        let inode_mode = match file_type {
+            DiskInodeType::FIFO => InodeFileType::S_IFIFO.bits(),
+            DiskInodeType::Character => InodeFileType::S_IFCHR.bits(),
+            DiskInodeType::Block => InodeFileType::S_IFBLK.bits(),
+            _ => return Err(EINVAL),
+        };
+        let inode_lock = self.inode_lock.write();
+        let inode_ref = self.inode.lock();
+        let inode_perm = (mode & 0o7777) as u16;
+        let mut new_inode_ref =
+            self.ext4fs
+                .create(inode_ref.inode_num, name, inode_mode | inode_perm)?;
+        new_inode_ref.inode.set_flags(0);
+        new_inode_ref.inode.set_rdev(rdev);
+        self.ext4fs.write_back_inode(&mut new_inode_ref);
+        Ok(Arc::new(Self {
+            inode_lock: Arc::new(RwLock::new(InodeLock {})),
+            readable: true,
+            writable: true,
+            special_use: false,
+            append: false,
+            opened: false,
+            inode: Arc::new(Mutex::new(new_inode_ref)),
+            offset: Mutex::new(0),
+            dirnode_ptr: Arc::new(Mutex::new(Weak::new())),
+            ext4fs: self.ext4fs.clone(),
+            file_cache_manager: PageCacheManager::new(),
+            ra: Mutex::new(FileReadahead::new()),
+        }))
+    }
+
     fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
     where
         Self: Sized,
@@ -653,6 +743,8 @@ impl File for Ext4OSInode {
         let mut inode_ref = self.inode.lock();
         let result = self.ext4fs.truncate_inode(&mut inode_ref, new_size as u64);
         if let Ok(result) = result {
+            drop(inode_ref);
+            self.notify_modify();
             Ok(())
         } else {
             panic!("truncate_inode failed: {:?}", result)
@@ -720,6 +812,8 @@ impl File for Ext4OSInode {
         //     "[kernel in get_all_caches] file size: {} cache_num: {}",
         //     file_size, cache_num
         // );
+        // 整个文件批量读入，避免逐块读取
+        self.readahead_pages(0..cache_num, &Arc::new(inode_ref.clone()));
         let mut cache_list = Vec::<Arc<Mutex<PageCache>>>::with_capacity(cache_num);
         // 使用自身的get_single_cache方法
         for cache_id in 0..cache_num {
@@ -732,6 +826,81 @@ impl File for Ext4OSInode {
         Ok(cache_list)
     }
 
+    fn chmod(&self, mode: u32) -> Result<(), isize> {
+        let mut inode_ref = self.inode.lock();
+        let file_type_bits = inode_ref.inode.mode() & EXT4_INODE_MODE_TYPE_MASK;
+        inode_ref
+            .inode
+            .set_mode(file_type_bits | (mode & 0o7777) as u16);
+        self.ext4fs.write_back_inode(&mut inode_ref);
+        Ok(())
+    }
+
+    fn chown(&self, uid: u32, gid: u32) -> Result<(), isize> {
+        let mut inode_ref = self.inode.lock();
+        inode_ref.inode.set_owner_uid(uid);
+        inode_ref.inode.set_owner_gid(gid);
+        self.ext4fs.write_back_inode(&mut inode_ref);
+        Ok(())
+    }
+
+    /// ext4 的写入是直写的，数据和inode在write时已经落盘，
+    /// 这里只需要写回页缓存中的脏页，之后写回inode和文件系统的块缓存
+    fn sync(&self, datasync: bool) -> Result<(), isize> {
+        let inode_lock = self.inode_lock.write();
+        let mut inode_ref = self.inode.lock();
+        let inode_ref_arc = Arc::new(inode_ref.clone());
+        self.file_cache_manager.sync_all(
+            |inner_cache_id| self.get_neighboring_blk(inner_cache_id, inode_ref_arc.clone()),
+            &self.ext4fs.block_device,
+        );
+        if !datasync {
+            self.ext4fs.write_back_inode(&mut inode_ref);
+        }
+        self.ext4fs
+            .cache_mgr
+            .lock()
+            .sync_all(&self.ext4fs.block_device);
+        Ok(())
+    }
+
+    fn fadvise(&self, offset: usize, len: usize, advice: usize) -> Result<(), isize> {
+        match advice {
+            POSIX_FADV_WILLNEED => {
+                let inode_ref = Arc::new(self.inode.lock().clone());
+                let size = inode_ref.inode.size() as usize;
+                self.readahead_pages(pages_of(offset, len, size), &inode_ref);
+            }
+            _ => self.ra.lock().set_advice(advice),
+        }
+        Ok(())
+    }
+
+    fn extents(&self) -> Result<Vec<(usize, usize)>, isize> {
+        if !self.is_file() {
+            return Err(EINVAL);
+        }
+        let inode_ref = self.inode.lock();
+        let blk_cnts = (inode_ref.inode.size() as usize + BLOCK_SIZE - 1) / BLOCK_SIZE;
+        let mut extents: Vec<(usize, usize)> = Vec::new();
+        for lblk in 0..blk_cnts {
+            let pblk = self.ext4fs.get_pblock_idx(&inode_ref, lblk as u32)? as usize;
+            // 文件中有空洞
+            if pblk == 0 {
+                return Err(EINVAL);
+            }
+            match extents.last_mut() {
+                Some((first, len)) if *first + *len == pblk => *len += 1,
+                _ => extents.push((pblk, 1)),
+            }
+        }
+        Ok(extents)
+    }
+
+    fn mappings(&self) -> Option<Arc<FileMappings>> {
+        self.get_dirtree_node().map(|node| node.mappings())
+    }
+
     /// 这个先不考虑实现
     fn oom(&self) -> usize {
         todo!()
@@ -908,6 +1077,29 @@ impl Ext4OSInode {
 }
 
 impl Ext4OSInode {
+    /// 根据本次读取的位置更新预读状态，并预读
+    fn ondemand_readahead(&self, offset: usize, len: usize, inode_ref: &Arc<Ext4InodeRef>) {
+        let size = inode_ref.inode.size() as usize;
+        let pages = pages_of(offset, len, size);
+        if pages.is_empty() {
+            return;
+        }
+        let nr_pages = pages_of(0, size, size).end;
+        let window = self.ra.lock().on_read(pages.start, pages.end - 1, nr_pages);
+        if let Some(window) = window {
+            self.readahead_pages(window, inode_ref);
+        }
+    }
+
+    /// 批量读入`range`内尚未缓存的页
+    fn readahead_pages(&self, range: Range<usize>, inode_ref: &Arc<Ext4InodeRef>) -> usize {
+        self.file_cache_manager.readahead(
+            range,
+            |inner_cache_id| self.get_neighboring_blk(inner_cache_id, inode_ref.clone()),
+            &self.ext4fs.block_device,
+        )
+    }
+
     fn update_block_cache(&self, offset: usize, buf: &[u8], inode_ref: Arc<Ext4InodeRef>) -> usize {
         let mut start = offset;
         let old_size = inode_ref.inode.get_file_size() as usize;
@@ -925,19 +1117,21 @@ impl Ext4OSInode {
             end_current_block = end_current_block.min(end);
             // write and update write size
             let block_write_size = end_current_block - start;
-            self.file_cache_manager
-                .get_cache(
-                    start_cache,
-                    || -> Vec<usize> { self.get_neighboring_blk(start_cache, inode_ref.clone()) },
-                    &self.ext4fs.block_device,
-                )
-                .lock()
-                .modify(0, |data_block: &mut [u8; PAGE_SIZE]| {
-                    let src = &buf[write_size..write_size + block_write_size];
-                    let dst = &mut data_block[start % PageCacheManager::CACHE_SZ
-                        ..start % PageCacheManager::CACHE_SZ + block_write_size];
-                    dst.copy_from_slice(src);
-                });
+            let cache = self.file_cache_manager.get_cache(
+                start_cache,
+                || -> Vec<usize> { self.get_neighboring_blk(start_cache, inode_ref.clone()) },
+                &self.ext4fs.block_device,
+            );
+            let mut cache_lock = cache.lock();
+            cache_lock.modify(0, |data_block: &mut [u8; PAGE_SIZE]| {
+                let src = &buf[write_size..write_size + block_write_size];
+                let dst = &mut data_block[start % PageCacheManager::CACHE_SZ
+                    ..start % PageCacheManager::CACHE_SZ + block_write_size];
+                dst.copy_from_slice(src);
+            });
+            // 数据已经由 write_at 直接写入磁盘，缓存与磁盘一致
+            cache_lock.mark_clean();
+            drop(cache_lock);
             write_size += block_write_size;
             // move to next block
             if end_current_block == end {
@@ -29,6 +29,12 @@ pub struct Fat {
 }
 
 impl Fat {
+    /// 将缓存中被修改过的fat扇区写回磁盘
+    /// # 参数
+    /// + `block_device`: 块设备对象
+    pub fn sync(&self, block_device: &Arc<dyn BlockDevice>) {
+        self.fat_cache_mgr.lock().sync_all(block_device);
+    }
     /// 获取当前fat表项指向的的下一个簇号
     /// # 参数
     /// + `current_clus_num`: 当前簇号
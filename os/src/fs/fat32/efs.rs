@@ -127,4 +127,7 @@ impl VFS for EasyFileSystem {
     fn get_filesystem_type(&self) -> FS_Type {
         FS_Type::Fat32
     }
+    fn sync(&self) {
+        self.fat.sync(&self.block_device);
+    }
 }
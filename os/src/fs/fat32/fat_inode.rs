@@ -15,6 +15,7 @@ use alloc::vec::Vec;
 use core::any::Any;
 use core::convert::TryInto;
 use core::ops::Mul;
+use core::ops::Range;
 use core::panic;
 use downcast_rs::{Downcast, DowncastSync};
 use spin::*;
@@ -61,7 +62,7 @@ pub struct FatInode {
     /// 文件内容
     file_content: RwLock<FileContent>,
     /// 与该Inode对应的文件缓存管理器
-    file_cache_mgr: PageCacheManager,
+    file_cache_mgr: Arc<PageCacheManager>,
     /// 文件类型
     file_type: Mutex<DiskInodeType>,
     /// 父目录的inode
@@ -83,29 +84,7 @@ impl Drop for FatInode {
             let length = lock.clus_list.len();
             self.dealloc_clus(&mut lock, length);
         } else {
-            if self.parent_dir.lock().is_none() {
-                return;
-            }
-            let par_dir_lock = self.parent_dir.lock();
-            let (parent_dir, offset) = par_dir_lock.as_ref().unwrap();
-
-            let par_inode_lock = parent_dir.write();
-            let dir_ent = parent_dir.get_dir_ent(&par_inode_lock, *offset).unwrap();
-            let mut short_dir_ent = *dir_ent.get_short_ent().unwrap();
-            // Modify size
-            short_dir_ent.file_size = self.get_file_size();
-            // Modify fst cluster
-            short_dir_ent.set_fst_clus(
-                self.get_first_clus_lock(&self.file_content.read())
-                    .unwrap_or(0),
-            );
-            // Modify time
-            // todo!
-            log::debug!("[Inode drop]: new_ent: {:?}", short_dir_ent);
-            // Write back
-            parent_dir
-                .set_dir_ent(&par_inode_lock, *offset, dir_ent)
-                .unwrap();
+            self.update_dir_ent();
         }
     }
 }
@@ -191,6 +170,21 @@ impl FatInode {
         //(size - 1 + clus_sz) / clus_sz
     }
 
+    /// 文件内容所在的扇区区间，按文件内的顺序给出(起始扇区, 扇区数)，物理上相邻的簇合并为一个区间
+    pub fn extents(&self) -> Vec<(usize, usize)> {
+        let lock = self.file_content.read();
+        let sec_per_clus = self.fs.sec_per_clus as usize;
+        let mut extents: Vec<(usize, usize)> = Vec::new();
+        for clus in lock.clus_list.iter() {
+            let start = self.fs.first_sector_of_cluster(*clus) as usize;
+            match extents.last_mut() {
+                Some((first, len)) if *first + *len == start => *len += sec_per_clus,
+                _ => extents.push((start, sec_per_clus)),
+            }
+        }
+        extents
+    }
+
     /// 获取由给定缓存索引表示的块ID(实际存入起始块号)列表
     /// # 参数
     /// + `clus_list`: 簇列表
@@ -512,6 +506,46 @@ impl FatInode {
         }
         Ok(())
     }
+    /// 将文件大小和第一个簇写入父目录中自己的短目录项
+    /// # 返回值
+    /// 目录项被修改返回true，目录项已经是最新的或没有父目录（根目录）返回false
+    /// # 警告
+    /// 这个函数会给parent_dir上锁，可能会导致死锁
+    fn update_dir_ent(&self) -> bool {
+        let par_dir_lock = self.parent_dir.lock();
+        let (parent_dir, offset) = match par_dir_lock.as_ref() {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let par_inode_lock = parent_dir.write();
+        let mut dir_ent = parent_dir.get_dir_ent(&par_inode_lock, *offset).unwrap();
+        // 目录的大小字段必须为0
+        let file_size = if self.is_dir() {
+            0
+        } else {
+            self.get_file_size()
+        };
+        let fst_clus = self
+            .get_first_clus_lock(&self.file_content.read())
+            .unwrap_or(0);
+        if dir_ent.get_short_ent().unwrap().file_size == file_size
+            && dir_ent.get_fst_clus() == fst_clus
+        {
+            return false;
+        }
+        // Modify size
+        dir_ent.set_size(file_size);
+        // Modify fst cluster
+        dir_ent.set_fst_clus(fst_clus);
+        // Modify time
+        // todo!
+        log::debug!("[update_dir_ent]: new_ent: {:?}", dir_ent);
+        // Write back
+        parent_dir
+            .set_dir_ent(&par_inode_lock, *offset, dir_ent)
+            .unwrap();
+        true
+    }
     /// Get directory entries, including short and long entries
     /// # Arguments
     /// + `inode_lock`: The lock of inode
@@ -1197,6 +1231,12 @@ impl InodeTrait for FatInode {
         // 确保文件内容不是CACHE_SZ整数倍时也可以多分配一个页面缓存
         let cache_num =
             (lock.size as usize + PageCacheManager::CACHE_SZ - 1) / PageCacheManager::CACHE_SZ;
+        // 整个文件批量读入，避免逐页读取
+        self.file_cache_mgr.readahead(
+            0..cache_num,
+            |inner_cache_id| self.get_neighboring_sec(&lock.clus_list, inner_cache_id),
+            &self.fs.block_device,
+        );
         // 初始化缓存列表，预先分配空间，避免多次重新分配内存
         let mut cache_list = Vec::<Arc<Mutex<PageCache>>>::with_capacity(cache_num);
         // 遍历所有缓存页，加入到缓存列表中
@@ -1361,6 +1401,48 @@ impl InodeTrait for FatInode {
         self.file_cache_mgr.oom(neighbor, &self.fs.block_device)
     }
 
+    /// 预读`range`内的页，超出文件末尾的部分被忽略
+    /// # 返回值
+    /// 新读入的页数量
+    fn readahead(&self, range: Range<usize>) -> usize {
+        let lock = self.file_content.read();
+        let nr_pages =
+            (lock.size as usize + PageCacheManager::CACHE_SZ - 1) / PageCacheManager::CACHE_SZ;
+        let range = range.start.min(nr_pages)..range.end.min(nr_pages);
+        self.file_cache_mgr.readahead(
+            range,
+            |inner_cache_id| self.get_neighboring_sec(&lock.clus_list, inner_cache_id),
+            &self.fs.block_device,
+        )
+    }
+
+    /// 按照 数据 -> fat表 -> 目录项 的顺序写回，保证目录项指向的簇链和数据都已经落盘
+    /// FAT32 中读取数据所需的元数据只有大小和簇链，所以`datasync`不影响写回的内容
+    fn sync(&self, datasync: bool) -> Result<(), isize> {
+        // 已经删除的文件不需要写回
+        if *self.deleted.lock() {
+            return Ok(());
+        }
+        let neighbor = |inner_cache_id| {
+            self.get_neighboring_sec(&self.file_content.read().clus_list, inner_cache_id)
+        };
+        self.file_cache_mgr
+            .sync_all(neighbor, &self.fs.block_device);
+        self.fs.fat.sync(&self.fs.block_device);
+        if self.update_dir_ent() {
+            let par_dir_lock = self.parent_dir.lock();
+            let (parent_dir, _) = par_dir_lock.as_ref().unwrap();
+            let neighbor = |inner_cache_id| {
+                parent_dir
+                    .get_neighboring_sec(&parent_dir.file_content.read().clus_list, inner_cache_id)
+            };
+            parent_dir
+                .file_cache_mgr
+                .sync_all(neighbor, &parent_dir.fs.block_device);
+        }
+        Ok(())
+    }
+
     /// 改变当前文件的大小
     /// This operation is ignored if the result size is negative
     /// # 参数
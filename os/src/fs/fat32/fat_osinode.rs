@@ -3,18 +3,51 @@ use alloc::{
     sync::{Arc, Weak},
     vec::Vec,
 };
+use core::convert::TryInto;
 use spin::Mutex;
 
 use crate::{
     fs::{
-        directory_tree::DirectoryTreeNode, fat32::layout::FATDiskInodeType, file_trait::File,
-        inode::InodeTrait, Dirent, OpenFlags, SeekWhence, Stat, StatMode,
+        directory_tree::DirectoryTreeNode,
+        fat32::layout::FATDiskInodeType,
+        file_trait::File,
+        inode::InodeTrait,
+        inotify::InotifyMask,
+        locks::LockOwner,
+        readahead::{pages_of, FileReadahead, POSIX_FADV_WILLNEED},
+        writeback::mark_inode_dirty,
+        Dirent, OpenFlags, SeekWhence, Stat, StatMode,
     },
-    mm::UserBuffer,
+    mm::{FileMappings, UserBuffer},
     syscall::errno::*,
 };
 
-use super::{DiskInodeType, PageCache};
+use super::{DiskInodeType, FatInode, PageCache};
+
+/// FAT32无法保存特殊文件，命名管道与设备节点以这样的普通文件表示：
+/// 大小恰为`SIDECAR_SIZE`字节，以`SIDECAR_MAGIC`开头，随后是小端序的文件类型位与设备号
+const SIDECAR_MAGIC: [u8; 8] = *b"NPUSPEC\0";
+const SIDECAR_SIZE: usize = 16;
+
+/// 若`inner`是特殊文件的表示，返回其文件类型与设备号
+fn read_sidecar(inner: &Arc<dyn InodeTrait>) -> Option<(DiskInodeType, u32)> {
+    if inner.is_dir() || inner.get_file_size() as usize != SIDECAR_SIZE {
+        return None;
+    }
+    let mut buf = [0u8; SIDECAR_SIZE];
+    if inner.read_at_block_cache(0, &mut buf) != SIDECAR_SIZE || buf[..8] != SIDECAR_MAGIC {
+        return None;
+    }
+    let mode = u32::from_le_bytes(buf[8..12].try_into().unwrap());
+    let file_type = match StatMode::from_bits_truncate(mode) & StatMode::S_IFMT {
+        StatMode::S_IFIFO => DiskInodeType::FIFO,
+        StatMode::S_IFCHR => DiskInodeType::Character,
+        StatMode::S_IFBLK => DiskInodeType::Block,
+        _ => return None,
+    };
+    let rdev = u32::from_le_bytes(buf[12..16].try_into().unwrap());
+    Some((file_type, rdev))
+}
 
 /// OSInode
 /// 对具体文件系统Inode的封装
@@ -27,12 +60,16 @@ pub struct FatOSInode {
     special_use: bool,
     /// 是否追加
     append: bool,
+    /// 由`open()`得到，释放时产生inotify关闭事件
+    opened: bool,
     /// 具体的Inode
     inner: Arc<dyn InodeTrait>,
     /// 文件偏移
     offset: Mutex<usize>,
     /// 目录树节点指针
     dirnode_ptr: Arc<Mutex<Weak<DirectoryTreeNode>>>,
+    /// 预读状态
+    ra: Mutex<FileReadahead>,
 }
 
 impl FatOSInode {
@@ -43,11 +80,33 @@ impl FatOSInode {
             writable: true,
             special_use: true,
             append: false,
+            opened: false,
             inner: root_inode,
             offset: Mutex::new(0),
             dirnode_ptr: Arc::new(Mutex::new(Weak::new())),
+            ra: Mutex::new(FileReadahead::new()),
         })
     }
+    // 根据本次读取的位置更新预读状态，并预读
+    fn ondemand_readahead(&self, offset: usize, len: usize) {
+        let size = self.inner.get_file_size() as usize;
+        let pages = pages_of(offset, len, size);
+        if pages.is_empty() {
+            return;
+        }
+        let nr_pages = pages_of(0, size, size).end;
+        let window = self.ra.lock().on_read(pages.start, pages.end - 1, nr_pages);
+        if let Some(window) = window {
+            self.inner.readahead(window);
+        }
+    }
+    // 文件内容或大小被修改后登记为脏文件，等待回写，同时产生inotify修改事件
+    fn mark_dirty(&self) {
+        if let Some(node) = self.get_dirtree_node() {
+            mark_inode_dirty(&node);
+            node.fsnotify(InotifyMask::IN_MODIFY);
+        }
+    }
 }
 
 impl Drop for FatOSInode {
@@ -59,6 +118,19 @@ impl Drop for FatOSInode {
                 None => {}
             }
         }
+        if self.opened {
+            if let Some(inode) = self.get_dirtree_node() {
+                // 最后一个引用被释放，该打开文件持有的flock()锁与OFD锁随之释放
+                inode
+                    .file_locks()
+                    .remove_owner(LockOwner::File(self as *const Self as usize));
+                inode.fsnotify(if self.writable {
+                    InotifyMask::IN_CLOSE_WRITE
+                } else {
+                    InotifyMask::IN_CLOSE_NOWRITE
+                });
+            }
+        }
     }
 }
 
@@ -77,9 +149,11 @@ impl File for FatOSInode {
             writable: self.writable,
             special_use: self.special_use,
             append: self.append,
+            opened: false,
             inner: self.inner.clone(),
             offset: Mutex::new(*self.offset.lock()),
             dirnode_ptr: self.dirnode_ptr.clone(),
+            ra: Mutex::new(self.ra.lock().clone()),
         })
     }
     fn readable(&self) -> bool {
@@ -98,12 +172,14 @@ impl File for FatOSInode {
     fn read(&self, offset: Option<&mut usize>, buffer: &mut [u8]) -> usize {
         match offset {
             Some(offset) => {
+                self.ondemand_readahead(*offset, buffer.len());
                 let len = self.inner.read_at_block_cache(*offset, buffer);
                 *offset += len;
                 len
             }
             None => {
                 let mut offset = self.offset.lock();
+                self.ondemand_readahead(*offset, buffer.len());
                 let len = self.inner.read_at_block_cache(*offset, buffer);
                 *offset += len;
                 len
@@ -118,6 +194,7 @@ impl File for FatOSInode {
     /// # Warning
     /// Buffer must be in kernel space
     fn write(&self, offset: Option<&mut usize>, buffer: &[u8]) -> usize {
+        self.mark_dirty();
         match offset {
             Some(offset) => {
                 let len = self.inner.write_at_block_cache(*offset, buffer);
@@ -151,6 +228,7 @@ impl File for FatOSInode {
         match offset {
             Some(mut offset) => {
                 let mut offset = &mut offset;
+                self.ondemand_readahead(*offset, buf.len());
                 for slice in buf.buffers.iter_mut() {
                     let read_size =
                         self.inner
@@ -164,6 +242,7 @@ impl File for FatOSInode {
             }
             None => {
                 let mut offset = self.offset.lock();
+                self.ondemand_readahead(*offset, buf.len());
                 for slice in buf.buffers.iter_mut() {
                     let read_size =
                         self.inner
@@ -181,6 +260,7 @@ impl File for FatOSInode {
 
     fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
         let mut total_write_size = 0usize;
+        self.mark_dirty();
 
         let inode_lock = self.inner.write();
         match offset {
@@ -217,21 +297,22 @@ impl File for FatOSInode {
     }
     fn get_stat(&self) -> Stat {
         let (size, atime, mtime, ctime, ino) = self.inner.stat_lock(&self.inner.read());
-        let st_mod: u32 = {
-            if self.inner.is_dir() {
-                (StatMode::S_IFDIR | StatMode::S_IRWXU | StatMode::S_IRWXG | StatMode::S_IRWXO)
-                    .bits()
-            } else {
-                (StatMode::S_IFREG | StatMode::S_IRWXU | StatMode::S_IRWXG | StatMode::S_IRWXO)
-                    .bits()
-            }
+        let file_type = self.inner.get_file_type();
+        let st_mod: u32 =
+            (file_type.stat_mode() | StatMode::S_IRWXU | StatMode::S_IRWXG | StatMode::S_IRWXO)
+                .bits();
+        // 特殊文件的内容是它的表示，不计入大小
+        let (size, rdev) = if file_type.is_special() {
+            (0, read_sidecar(&self.inner).map_or(0, |(_, rdev)| rdev))
+        } else {
+            (size, 0)
         };
         Stat::new(
             crate::makedev!(8, 0),
             ino,
             st_mod,
             1,
-            0,
+            rdev as u64,
             size,
             atime,
             mtime,
@@ -258,13 +339,15 @@ impl File for FatOSInode {
     /// + 本身
     fn open(&self, flags: OpenFlags, special_use: bool) -> Arc<dyn File> {
         Arc::new(Self {
-            readable: flags.contains(OpenFlags::O_RDONLY) || flags.contains(OpenFlags::O_RDWR),
-            writable: flags.contains(OpenFlags::O_WRONLY) || flags.contains(OpenFlags::O_RDWR),
+            readable: flags.readable(),
+            writable: flags.writable(),
             special_use,
             append: flags.contains(OpenFlags::O_APPEND),
+            opened: true,
             inner: self.inner.clone(),
             offset: Mutex::new(0),
             dirnode_ptr: self.dirnode_ptr.clone(),
+            ra: Mutex::new(FileReadahead::new()),
         })
     }
     /// 打开子文件
@@ -280,14 +363,20 @@ impl File for FatOSInode {
         // 返回值
         // + 一个File对象（OSInode）
         let get_dyn_file = |short_ent, offset| -> Arc<dyn File> {
+            let inner = self.inner.from_ent(&self.inner, short_ent, offset);
+            if let Some((file_type, _)) = read_sidecar(&inner) {
+                *inner.get_file_type_lock() = file_type;
+            }
             Arc::new(Self {
                 readable: true,
                 writable: true,
                 special_use: false,
                 append: false,
-                inner: self.inner.from_ent(&self.inner, short_ent, offset),
+                opened: false,
+                inner,
                 offset: Mutex::new(0),
                 dirnode_ptr: Arc::new(Mutex::new(Weak::new())),
+                ra: Mutex::new(FileReadahead::new()),
             })
         };
         Ok(self
@@ -302,9 +391,15 @@ impl File for FatOSInode {
     /// # 参数
     /// + name：文件名
     /// + file_type: 文件类型
+    /// + _mode: 权限位，FAT32不保存权限
     /// # 返回值
     /// + 文件对象
-    fn create(&self, name: &str, file_type: DiskInodeType) -> Result<Arc<dyn File>, isize> {
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        _mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
         // 加锁
         let inode_lock = self.inner.write();
         // 创建新文件
@@ -318,14 +413,39 @@ impl File for FatOSInode {
                 writable: true,
                 special_use: false,
                 append: false,
+                opened: false,
                 inner,
                 offset: Mutex::new(0),
                 dirnode_ptr: Arc::new(Mutex::new(Weak::new())),
+                ra: Mutex::new(FileReadahead::new()),
             }))
         } else {
             panic!()
         }
     }
+    /// 创建命名管道或设备节点，以约定格式的普通文件表示
+    fn mknod(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        rdev: u32,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
+        if !file_type.is_special() {
+            return Err(EINVAL);
+        }
+        let file = self.create(name, DiskInodeType::File, mode)?;
+        let inner = &file.downcast_ref::<Self>().unwrap().inner;
+        let mut buf = [0u8; SIDECAR_SIZE];
+        buf[..8].copy_from_slice(&SIDECAR_MAGIC);
+        buf[8..12].copy_from_slice(&file_type.stat_mode().bits().to_le_bytes());
+        buf[12..16].copy_from_slice(&rdev.to_le_bytes());
+        if inner.write_at_block_cache(0, &buf) != SIDECAR_SIZE {
+            return Err(ENOSPC);
+        }
+        *inner.get_file_type_lock() = file_type;
+        Ok(file)
+    }
     fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
     where
         Self: Sized,
@@ -429,6 +549,7 @@ impl File for FatOSInode {
     fn modify_size(&self, diff: isize) -> Result<(), isize> {
         let inode_lock = self.inner.write();
         self.inner.modify_size_lock(&inode_lock, diff, true);
+        self.mark_dirty();
         Ok(())
     }
     fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
@@ -436,6 +557,7 @@ impl File for FatOSInode {
         let old_size = self.inner.get_file_size_wlock(&inode_lock);
         self.inner
             .modify_size_lock(&inode_lock, new_size as isize - old_size as isize, true);
+        self.mark_dirty();
         Ok(())
     }
     fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {
@@ -467,6 +589,37 @@ impl File for FatOSInode {
     fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()> {
         Ok(self.inner.get_all_cache())
     }
+    /// FAT32没有权限位和属主，与vfat的`quiet`选项一样接受修改但不保存
+    fn chmod(&self, mode: u32) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn chown(&self, uid: u32, gid: u32) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn sync(&self, datasync: bool) -> Result<(), isize> {
+        self.inner.sync(datasync)
+    }
+    fn fadvise(&self, offset: usize, len: usize, advice: usize) -> Result<(), isize> {
+        match advice {
+            POSIX_FADV_WILLNEED => {
+                let size = self.inner.get_file_size() as usize;
+                self.inner.readahead(pages_of(offset, len, size));
+            }
+            _ => self.ra.lock().set_advice(advice),
+        }
+        Ok(())
+    }
+    fn extents(&self) -> Result<Vec<(usize, usize)>, isize> {
+        match self.inner.downcast_ref::<FatInode>() {
+            Some(inode) if self.inner.is_file() => Ok(inode.extents()),
+            _ => Err(EINVAL),
+        }
+    }
+    fn mappings(&self) -> Option<Arc<FileMappings>> {
+        self.get_dirtree_node().map(|node| node.mappings())
+    }
     fn oom(&self) -> usize {
         self.inner.oom()
     }
@@ -1,5 +1,11 @@
 use super::{
-    cache::PageCache, directory_tree::DirectoryTreeNode, dirent::Dirent, file_trait::File, Statx,
+    cache::PageCache,
+    directory_tree::DirectoryTreeNode,
+    dirent::Dirent,
+    file_trait::File,
+    permission::MAY_EXEC,
+    writeback::{balance_dirty_pages, clear_inode_dirty},
+    Statx,
 };
 use crate::{
     config::SYSTEM_FD_LIMIT,
@@ -20,6 +26,8 @@ use super::layout::{OpenFlags, SeekWhence, Stat};
 pub struct FileDescriptor {
     cloexec: bool,
     nonblock: bool,
+    /// 以O_PATH打开，只能用来定位文件，不能读写或映射
+    path_only: bool,
     pub file: Arc<dyn File>,
 }
 
@@ -29,6 +37,7 @@ impl FileDescriptor {
         Self {
             cloexec,
             nonblock,
+            path_only: false,
             file,
         }
     }
@@ -53,21 +62,39 @@ impl FileDescriptor {
     }
     /// Just used for cwd
     pub fn cd(&self, path: &str) -> Result<Arc<Self>, isize> {
-        match self.open(path, OpenFlags::O_DIRECTORY | OpenFlags::O_RDONLY, true) {
-            Ok(fd) => Ok(Arc::new(fd)),
+        // 进入目录需要的是搜索权限而不是读权限
+        match self.open(path, OpenFlags::O_DIRECTORY | OpenFlags::O_PATH, true) {
+            Ok(fd) => {
+                if let Some(inode) = fd.file.get_dirtree_node() {
+                    inode.permission(MAY_EXEC)?;
+                }
+                Ok(Arc::new(fd))
+            }
             Err(errno) => Err(errno),
         }
     }
+    pub fn get_path_only(&self) -> bool {
+        self.path_only
+    }
     pub fn readable(&self) -> bool {
-        self.file.readable()
+        !self.path_only && self.file.readable()
     }
     pub fn writable(&self) -> bool {
-        self.file.writable()
+        !self.path_only && self.file.writable()
+    }
+    /// 交换文件的块由交换区直接读写，swapon之前打开的描述符也不能再修改它
+    pub fn is_swapfile(&self) -> bool {
+        self.file
+            .get_dirtree_node()
+            .map_or(false, |inode| inode.is_swapfile())
     }
     pub fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
         self.file.read(offset, buf)
     }
     pub fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        if self.is_swapfile() {
+            return ETXTBSY as usize;
+        }
         self.file.write(offset, buf)
     }
     pub fn r_ready(&self) -> bool {
@@ -80,14 +107,19 @@ impl FileDescriptor {
         self.file.read_user(offset, buf)
     }
     pub fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
-        self.file.write_user(offset, buf)
+        if self.is_swapfile() {
+            return ETXTBSY as usize;
+        }
+        let written = self.file.write_user(offset, buf);
+        balance_dirty_pages();
+        written
     }
     pub fn get_stat(&self) -> Stat {
         self.file.get_stat()
     }
     pub fn get_statx(&self, mask: u32) -> Statx {
         let stat = self.file.get_stat();
-        Statx::new(
+        let mut statx = Statx::new(
             mask,
             stat.get_nlink(),
             stat.get_mode() as u16,
@@ -100,9 +132,21 @@ impl FileDescriptor {
             (stat.get_rdev() & 0xff) as u32,
             (stat.get_dev() & 0xffff_00) >> 8 as u32,
             (stat.get_dev() & 0xff) as u32,
-        )
+        );
+        statx.set_owner(stat.get_uid(), stat.get_gid());
+        statx
     }
     pub fn open(&self, path: &str, flags: OpenFlags, special_use: bool) -> Result<Self, isize> {
+        self.open_with_mode(path, flags, 0o644, special_use)
+    }
+    /// 同`open`，O_CREAT创建文件时权限位为`mode`
+    pub fn open_with_mode(
+        &self,
+        path: &str,
+        flags: OpenFlags,
+        mode: u32,
+        special_use: bool,
+    ) -> Result<Self, isize> {
         if path == "" {
             return Ok(self.clone());
         }
@@ -114,14 +158,16 @@ impl FileDescriptor {
             Some(inode) => inode,
             None => return Err(ENOENT),
         };
-        let file = match inode.open(path, flags, special_use) {
+        let file = match inode.open_with_mode(path, flags, mode, special_use) {
             Ok(file) => file,
             Err(errno) => return Err(errno),
         };
         let cloexec = flags.contains(OpenFlags::O_CLOEXEC);
-        Ok(Self::new(cloexec, false, file))
+        let mut file_descriptor = Self::new(cloexec, false, file);
+        file_descriptor.path_only = flags.contains(OpenFlags::O_PATH);
+        Ok(file_descriptor)
     }
-    pub fn mkdir(&self, path: &str) -> Result<(), isize> {
+    pub fn mkdir(&self, path: &str, mode: u32) -> Result<(), isize> {
         if self.file.is_file() && !path.starts_with('/') {
             return Err(ENOTDIR);
         }
@@ -130,7 +176,24 @@ impl FileDescriptor {
             Some(inode) => inode,
             None => return Err(ENOENT),
         };
-        inode.mkdir(path)
+        inode.mkdir(path, mode)
+    }
+    pub fn mknod(
+        &self,
+        path: &str,
+        file_type: DiskInodeType,
+        rdev: u32,
+        mode: u32,
+    ) -> Result<(), isize> {
+        if self.file.is_file() && !path.starts_with('/') {
+            return Err(ENOTDIR);
+        }
+        let inode = self.file.get_dirtree_node();
+        let inode = match inode {
+            Some(inode) => inode,
+            None => return Err(ENOENT),
+        };
+        inode.mknod(path, file_type, rdev, mode)
     }
     pub fn delete(&self, path: &str, delete_directory: bool) -> Result<(), isize> {
         if self.file.is_file() && !path.starts_with('/') {
@@ -198,8 +261,24 @@ impl FileDescriptor {
         if new_size < 0 || !self.writable() {
             return Err(EINVAL);
         }
-        // todo: support ETXTBSY
-        self.file.truncate_size(new_size as usize)
+        if self.is_swapfile() {
+            return Err(ETXTBSY);
+        }
+        let old_size = self.file.get_size();
+        self.file.truncate_size(new_size as usize)?;
+        if (new_size as usize) < old_size {
+            if let Some(mappings) = self.file.mappings() {
+                mappings.unmap_beyond_eof();
+            }
+        }
+        Ok(())
+    }
+    pub fn sync(&self, datasync: bool) -> Result<(), isize> {
+        // 先移出脏文件表，同步期间的新写入会重新登记
+        if let Some(inode) = self.file.get_dirtree_node() {
+            clear_inode_dirty(&inode);
+        }
+        self.file.sync(datasync)
     }
     pub fn set_timestamp(
         &self,
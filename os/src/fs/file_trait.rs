@@ -1,5 +1,8 @@
 use super::{dirent::Dirent, fat32::DiskInodeType};
-use crate::{mm::UserBuffer, syscall::errno::ENOTTY};
+use crate::{
+    mm::{FileMappings, UserBuffer},
+    syscall::errno::{EINVAL, ENOTTY, EPERM, ESPIPE},
+};
 use __alloc::string::String;
 use alloc::{
     sync::{Arc, Weak},
@@ -8,7 +11,12 @@ use alloc::{
 use downcast_rs::*;
 use spin::Mutex;
 
-use super::{cache::PageCache, directory_tree::DirectoryTreeNode, layout::*};
+use super::{
+    cache::PageCache,
+    directory_tree::DirectoryTreeNode,
+    layout::*,
+    poll::{PollEvent, PollWaitQueue},
+};
 
 pub trait File: DowncastSync {
     fn deep_clone(&self) -> Arc<dyn File>;
@@ -35,7 +43,22 @@ pub trait File: DowncastSync {
     fn open(&self, flags: OpenFlags, special_use: bool) -> Arc<dyn File>;
     fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize>;
     /// create
-    fn create(&self, name: &str, file_type: DiskInodeType) -> Result<Arc<dyn File>, isize>;
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize>;
+    /// 在目录下创建命名管道或设备节点，`rdev`为设备号。不支持的文件系统返回`EPERM`
+    fn mknod(
+        &self,
+        _name: &str,
+        _file_type: DiskInodeType,
+        _rdev: u32,
+        _mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
+        Err(EPERM)
+    }
     fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
     where
         Self: Sized;
@@ -56,10 +79,55 @@ pub trait File: DowncastSync {
     /// cache
     fn get_single_cache(&self, offset: usize) -> Result<Arc<Mutex<PageCache>>, ()>;
     fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()>;
+    /// 将文件的脏数据写回磁盘，`datasync`为真时只写回读取数据所必需的元数据（fdatasync）
+    fn sync(&self, _datasync: bool) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+    /// 修改权限位（包括set-user-ID、set-group-ID与粘滞位），`mode`只含低12位
+    fn chmod(&self, _mode: u32) -> Result<(), isize> {
+        Err(EPERM)
+    }
+    /// 修改属主与属组
+    fn chown(&self, _uid: u32, _gid: u32) -> Result<(), isize> {
+        Err(EPERM)
+    }
+    /// posix_fadvise，`advice`见`readahead.rs`
+    fn fadvise(&self, _offset: usize, _len: usize, _advice: usize) -> Result<(), isize> {
+        Err(ESPIPE)
+    }
+    /// 文件内容在块设备上的位置，按文件内的顺序给出`(起始块号, 块数)`的连续区间，块大小为`BLOCK_SZ`。
+    /// swapon通过它绕过页缓存直接读写交换文件，不支持的文件返回`EINVAL`
+    fn extents(&self) -> Result<Vec<(usize, usize)>, isize> {
+        Err(EINVAL)
+    }
+    /// 映射了该文件的地址空间，文件被截短时据此解除超出文件末尾的映射。
+    /// 返回`None`的文件不跟踪映射
+    fn mappings(&self) -> Option<Arc<FileMappings>> {
+        None
+    }
     /// memory related
     fn oom(&self) -> usize;
     /// poll, select related
     fn hang_up(&self) -> bool;
+    /// 返回`events`中已就绪的事件，`POLLERR`与`POLLHUP`总会被检查。
+    /// 默认由`hang_up`、`r_ready`与`w_ready`组合得到
+    fn poll(&self, events: PollEvent) -> PollEvent {
+        let mut revents = PollEvent::empty();
+        if self.hang_up() {
+            revents |= PollEvent::POLLHUP;
+        }
+        if events.contains(PollEvent::POLLIN) && self.r_ready() {
+            revents |= PollEvent::POLLIN;
+        }
+        if events.contains(PollEvent::POLLOUT) && self.w_ready() {
+            revents |= PollEvent::POLLOUT;
+        }
+        revents
+    }
+    /// 文件状态变化时会被通知的等待队列，返回`None`的文件只能被定时轮询
+    fn poll_queue(&self) -> Option<Arc<PollWaitQueue>> {
+        None
+    }
     /// iotcl
     fn ioctl(&self, _cmd: u32, _argp: usize) -> isize {
         ENOTTY
@@ -1,5 +1,6 @@
 use crate::fs::*;
 use core::any::Any;
+use core::ops::Range;
 
 use crate::fs::fat32::layout::FATDiskInodeType;
 use crate::fs::vfs::VFS;
@@ -75,6 +76,8 @@ pub trait InodeTrait: DowncastSync {
     fn stat_lock(&self, _inode_lock: &RwLockReadGuard<InodeLock>) -> (i64, i64, i64, i64, u64);
     fn time(&self) -> MutexGuard<InodeTime>;
     fn oom(&self) -> usize;
+    fn sync(&self, datasync: bool) -> Result<(), isize>;
+    fn readahead(&self, range: Range<usize>) -> usize;
     fn modify_size_lock(&self, inode_lock: &RwLockWriteGuard<InodeLock>, diff: isize, clear: bool);
     fn is_empty_dir_lock(&self, inode_lock: &RwLockWriteGuard<InodeLock>) -> bool;
 
@@ -183,3 +186,25 @@ pub enum DiskInodeType {
     Socket,
     Link,
 }
+
+impl DiskInodeType {
+    /// 对应的`st_mode`文件类型位
+    pub fn stat_mode(&self) -> StatMode {
+        match self {
+            DiskInodeType::File => StatMode::S_IFREG,
+            DiskInodeType::Directory => StatMode::S_IFDIR,
+            DiskInodeType::FIFO => StatMode::S_IFIFO,
+            DiskInodeType::Character => StatMode::S_IFCHR,
+            DiskInodeType::Block => StatMode::S_IFBLK,
+            DiskInodeType::Socket => StatMode::S_IFSOCK,
+            DiskInodeType::Link => StatMode::S_IFLNK,
+        }
+    }
+    /// 是否为mknod创建的特殊文件（命名管道或设备节点）
+    pub fn is_special(&self) -> bool {
+        matches!(
+            self,
+            DiskInodeType::FIFO | DiskInodeType::Character | DiskInodeType::Block
+        )
+    }
+}
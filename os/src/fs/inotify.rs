@@ -0,0 +1,481 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicU32, Ordering};
+use spin::Mutex;
+
+use crate::{
+    fs::{
+        directory_tree::DirectoryTreeNode,
+        file_trait::File,
+        layout::Stat,
+        poll::{signal_pending, PollEvent, PollWaitQueue, PollWaiter},
+        StatMode,
+    },
+    mm::UserBuffer,
+    syscall::errno::{EAGAIN, EEXIST, EINTR, EINVAL, ENOTDIR, ESPIPE},
+};
+
+/// 与`O_NONBLOCK`相同
+pub const IN_NONBLOCK: usize = 0o4000;
+/// 与`O_CLOEXEC`相同
+pub const IN_CLOEXEC: usize = 0o2000000;
+/// 每个inotify实例最多排队的事件数，超出后丢弃事件并产生`IN_Q_OVERFLOW`
+const INOTIFY_MAX_QUEUED_EVENTS: usize = 16384;
+/// `struct inotify_event`的固定部分大小，文件名按该大小对齐
+const INOTIFY_EVENT_SIZE: usize = 16;
+
+bitflags! {
+    pub struct InotifyMask: u32 {
+        const IN_ACCESS = 0x00000001;
+        const IN_MODIFY = 0x00000002;
+        const IN_ATTRIB = 0x00000004;
+        const IN_CLOSE_WRITE = 0x00000008;
+        const IN_CLOSE_NOWRITE = 0x00000010;
+        const IN_OPEN = 0x00000020;
+        const IN_MOVED_FROM = 0x00000040;
+        const IN_MOVED_TO = 0x00000080;
+        const IN_CREATE = 0x00000100;
+        const IN_DELETE = 0x00000200;
+        const IN_DELETE_SELF = 0x00000400;
+        const IN_MOVE_SELF = 0x00000800;
+        const IN_ALL_EVENTS = 0x00000fff;
+        /// 以下三项只会出现在读出的事件中
+        const IN_UNMOUNT = 0x00002000;
+        const IN_Q_OVERFLOW = 0x00004000;
+        const IN_IGNORED = 0x00008000;
+        /// 以下为`inotify_add_watch()`的选项
+        const IN_ONLYDIR = 0x01000000;
+        const IN_DONT_FOLLOW = 0x02000000;
+        const IN_EXCL_UNLINK = 0x04000000;
+        const IN_MASK_CREATE = 0x10000000;
+        const IN_MASK_ADD = 0x20000000;
+        const IN_ISDIR = 0x40000000;
+        const IN_ONESHOT = 0x80000000;
+    }
+}
+
+/// 同一次重命名产生的`IN_MOVED_FROM`与`IN_MOVED_TO`共享的cookie
+pub fn next_cookie() -> u32 {
+    static COOKIE: AtomicU32 = AtomicU32::new(1);
+    COOKIE.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(PartialEq, Eq)]
+struct InotifyEvent {
+    wd: i32,
+    mask: u32,
+    cookie: u32,
+    name: Option<String>,
+}
+
+impl InotifyEvent {
+    /// 文件名包括结尾的'\0'，并补齐到`INOTIFY_EVENT_SIZE`的倍数
+    fn name_len(&self) -> usize {
+        match &self.name {
+            Some(name) => {
+                (name.len() + INOTIFY_EVENT_SIZE) / INOTIFY_EVENT_SIZE * INOTIFY_EVENT_SIZE
+            }
+            None => 0,
+        }
+    }
+    fn size(&self) -> usize {
+        INOTIFY_EVENT_SIZE + self.name_len()
+    }
+    /// 按`struct inotify_event`的布局追加到`buf`末尾
+    fn write_to(&self, buf: &mut Vec<u8>) {
+        let name_len = self.name_len();
+        buf.extend_from_slice(&self.wd.to_ne_bytes());
+        buf.extend_from_slice(&self.mask.to_ne_bytes());
+        buf.extend_from_slice(&self.cookie.to_ne_bytes());
+        buf.extend_from_slice(&(name_len as u32).to_ne_bytes());
+        if let Some(name) = &self.name {
+            buf.extend_from_slice(name.as_bytes());
+            buf.resize(buf.len() + name_len - name.len(), 0);
+        }
+    }
+}
+
+/// 挂在目录树节点上的监视项，指向所属的inotify实例
+pub struct InotifyMark {
+    group: Weak<InotifyGroup>,
+    wd: i32,
+    mask: InotifyMask,
+}
+
+struct InotifyInner {
+    next_wd: i32,
+    /// 监视描述符到被监视节点，持有节点的强引用以保证监视期间节点不被释放
+    watches: BTreeMap<i32, Arc<DirectoryTreeNode>>,
+    events: VecDeque<InotifyEvent>,
+}
+
+/// inotify实例的共享部分，监视项通过弱引用向其投递事件
+pub struct InotifyGroup {
+    inner: Mutex<InotifyInner>,
+    wait_queue: Arc<PollWaitQueue>,
+}
+
+impl InotifyGroup {
+    fn queue_event(&self, wd: i32, mask: InotifyMask, cookie: u32, name: Option<&str>) {
+        let event = InotifyEvent {
+            wd,
+            mask: mask.bits(),
+            cookie,
+            name: name.map(|name| name.to_string()),
+        };
+        let mut inner = self.inner.lock();
+        // 与队尾完全相同的事件合并为一个
+        if inner.events.back() == Some(&event) {
+            return;
+        }
+        if inner.events.len() >= INOTIFY_MAX_QUEUED_EVENTS {
+            let overflow = InotifyEvent {
+                wd: -1,
+                mask: InotifyMask::IN_Q_OVERFLOW.bits(),
+                cookie: 0,
+                name: None,
+            };
+            if inner.events.back() == Some(&overflow) {
+                return;
+            }
+            inner.events.push_back(overflow);
+        } else {
+            inner.events.push_back(event);
+        }
+        drop(inner);
+        self.wait_queue
+            .notify(PollEvent::POLLIN | PollEvent::POLLRDNORM);
+    }
+    /// 监视项已从节点上摘除，移除监视描述符并通知用户
+    fn remove_watch(&self, wd: i32) {
+        if self.inner.lock().watches.remove(&wd).is_some() {
+            self.queue_event(wd, InotifyMask::IN_IGNORED, 0, None);
+        }
+    }
+}
+
+/// 向节点上的监视项投递事件，`name`为事件发生在目录下的子项时的文件名
+pub fn fsnotify_marks(
+    marks: &Mutex<Vec<InotifyMark>>,
+    mask: InotifyMask,
+    name: Option<&str>,
+    cookie: u32,
+) {
+    let mut targets = Vec::new();
+    let mut oneshot = Vec::new();
+    {
+        let mut marks = marks.lock();
+        if marks.is_empty() {
+            return;
+        }
+        let events = mask & InotifyMask::IN_ALL_EVENTS;
+        marks.retain(|mark| {
+            let group = match mark.group.upgrade() {
+                Some(group) => group,
+                None => return false,
+            };
+            if !mark.mask.intersects(events) {
+                return true;
+            }
+            targets.push((group.clone(), mark.wd));
+            if mark.mask.contains(InotifyMask::IN_ONESHOT) {
+                oneshot.push((group, mark.wd));
+                return false;
+            }
+            true
+        });
+    }
+    // 投递时不持有节点上的锁，避免与`inotify_add_watch()`的加锁顺序相反
+    for (group, wd) in targets {
+        group.queue_event(wd, mask, cookie, name);
+    }
+    for (group, wd) in oneshot {
+        group.remove_watch(wd);
+    }
+}
+
+/// 节点被删除，摘除其上的全部监视项并产生`IN_IGNORED`
+pub fn fsnotify_detach(marks: &Mutex<Vec<InotifyMark>>) {
+    let marks = core::mem::take(&mut *marks.lock());
+    for mark in marks {
+        if let Some(group) = mark.group.upgrade() {
+            group.remove_watch(mark.wd);
+        }
+    }
+}
+
+/// `inotify_init1()`创建的inotify实例
+pub struct InotifyInstance {
+    group: Arc<InotifyGroup>,
+    nonblock: bool,
+}
+
+impl InotifyInstance {
+    pub fn new(flags: usize) -> Self {
+        Self {
+            group: Arc::new(InotifyGroup {
+                inner: Mutex::new(InotifyInner {
+                    next_wd: 1,
+                    watches: BTreeMap::new(),
+                    events: VecDeque::new(),
+                }),
+                wait_queue: Arc::new(PollWaitQueue::new()),
+            }),
+            nonblock: flags & IN_NONBLOCK != 0,
+        }
+    }
+    /// 添加或修改对`node`的监视，返回监视描述符
+    pub fn add_watch(&self, node: Arc<DirectoryTreeNode>, mask: InotifyMask) -> Result<i32, isize> {
+        if !mask.intersects(InotifyMask::IN_ALL_EVENTS)
+            || mask.contains(InotifyMask::IN_MASK_ADD | InotifyMask::IN_MASK_CREATE)
+        {
+            return Err(EINVAL);
+        }
+        if mask.contains(InotifyMask::IN_ONLYDIR) && !node.file.is_dir() {
+            return Err(ENOTDIR);
+        }
+        let mut inner = self.group.inner.lock();
+        let existing = inner
+            .watches
+            .iter()
+            .find(|(_, watched)| Arc::ptr_eq(watched, &node))
+            .map(|(wd, _)| *wd);
+        let mut marks = node.inotify_marks().lock();
+        if let Some(wd) = existing {
+            if mask.contains(InotifyMask::IN_MASK_CREATE) {
+                return Err(EEXIST);
+            }
+            let mark = marks
+                .iter_mut()
+                .find(|mark| mark.wd == wd && mark.group.ptr_eq(&Arc::downgrade(&self.group)))
+                .unwrap();
+            if mask.contains(InotifyMask::IN_MASK_ADD) {
+                mark.mask |= mask;
+            } else {
+                mark.mask = mask;
+            }
+            return Ok(wd);
+        }
+        let wd = inner.next_wd;
+        inner.next_wd += 1;
+        marks.push(InotifyMark {
+            group: Arc::downgrade(&self.group),
+            wd,
+            mask,
+        });
+        drop(marks);
+        inner.watches.insert(wd, node);
+        Ok(wd)
+    }
+    pub fn rm_watch(&self, wd: i32) -> Result<(), isize> {
+        let node = match self.group.inner.lock().watches.get(&wd) {
+            Some(node) => node.clone(),
+            None => return Err(EINVAL),
+        };
+        let group = Arc::downgrade(&self.group);
+        node.inotify_marks()
+            .lock()
+            .retain(|mark| !(mark.wd == wd && mark.group.ptr_eq(&group)));
+        self.group.remove_watch(wd);
+        Ok(())
+    }
+    /// 读出尽可能多的完整事件，缓冲区连一个事件都放不下时返回`EINVAL`
+    fn read_events(&self, len: usize) -> Result<Vec<u8>, isize> {
+        let mut waiter = PollWaiter::new();
+        waiter.register_queue(Some(self.group.wait_queue.clone()), PollEvent::POLLIN);
+        loop {
+            let mut inner = self.group.inner.lock();
+            if !inner.events.is_empty() {
+                let mut buf = Vec::new();
+                while let Some(event) = inner.events.front() {
+                    if buf.len() + event.size() > len {
+                        break;
+                    }
+                    event.write_to(&mut buf);
+                    inner.events.pop_front();
+                }
+                if buf.is_empty() {
+                    return Err(EINVAL);
+                }
+                return Ok(buf);
+            }
+            drop(inner);
+            if self.nonblock {
+                return Err(EAGAIN);
+            }
+            if signal_pending() {
+                return Err(EINTR);
+            }
+            waiter.sleep(None);
+        }
+    }
+}
+
+impl Drop for InotifyInstance {
+    fn drop(&mut self) {
+        let group = Arc::downgrade(&self.group);
+        for node in self.group.inner.lock().watches.values() {
+            node.inotify_marks()
+                .lock()
+                .retain(|mark| !mark.group.ptr_eq(&group));
+        }
+    }
+}
+
+#[allow(unused)]
+impl File for InotifyInstance {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        todo!()
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        match self.read_events(buf.len()) {
+            Ok(events) => {
+                buf[..events.len()].copy_from_slice(&events);
+                events.len()
+            }
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn r_ready(&self) -> bool {
+        !self.group.inner.lock().events.is_empty()
+    }
+
+    fn w_ready(&self) -> bool {
+        false
+    }
+
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        match self.read_events(buf.len()) {
+            Ok(events) => buf.write(&events),
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        EINVAL as usize
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 13),
+            1,
+            StatMode::S_IFREG.bits() | 0o600,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(&self, dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        todo!()
+    }
+
+    fn open_subfile(
+        &self,
+    ) -> Result<alloc::vec::Vec<(alloc::string::String, alloc::sync::Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> alloc::vec::Vec<Dirent> {
+        alloc::vec::Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        offset: usize,
+    ) -> Result<Arc<spin::Mutex<crate::fs::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(
+        &self,
+    ) -> Result<alloc::vec::Vec<Arc<spin::Mutex<crate::fs::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn poll_queue(&self) -> Option<Arc<PollWaitQueue>> {
+        Some(self.group.wait_queue.clone())
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
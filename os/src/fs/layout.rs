@@ -28,6 +28,19 @@ bitflags! {
     }
 }
 
+impl OpenFlags {
+    /// 访问模式所占的位
+    const O_ACCMODE: u32 = 0o3;
+    /// 按访问模式是否可读，O_PATH既不可读也不可写
+    pub fn readable(&self) -> bool {
+        !self.contains(Self::O_PATH) && self.bits() & Self::O_ACCMODE != Self::O_WRONLY.bits()
+    }
+    /// 按访问模式是否可写
+    pub fn writable(&self) -> bool {
+        !self.contains(Self::O_PATH) && self.bits() & Self::O_ACCMODE != Self::O_RDONLY.bits()
+    }
+}
+
 bitflags! {
     pub struct SeekWhence: u32 {
         const SEEK_SET  =   0; /* set to offset bytes.  */
@@ -227,6 +240,10 @@ impl Statx {
             __statx_pad3: [0 as u64; 12],
         }
     }
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
+        self.stx_uid = uid;
+        self.stx_gid = gid;
+    }
 }
 #[allow(unused)]
 impl Stat {
@@ -243,6 +260,17 @@ impl Stat {
     pub fn get_nlink(&self) -> u32 {
         self.st_nlink
     }
+    pub fn get_uid(&self) -> u32 {
+        self.st_uid
+    }
+    pub fn get_gid(&self) -> u32 {
+        self.st_gid
+    }
+    /// `Stat::new`得到的属主为root，由记录了属主的文件系统另行设置
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
+        self.st_uid = uid;
+        self.st_gid = gid;
+    }
     pub fn get_dev(&self) -> u32 {
         self.st_dev as u32
     }
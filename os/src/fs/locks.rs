@@ -0,0 +1,415 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use lazy_static::*;
+use spin::Mutex;
+
+use crate::{
+    fs::{
+        file_trait::File,
+        poll::{signal_pending, PollEvent, PollWaitQueue, PollWaiter},
+        SeekWhence,
+    },
+    syscall::errno::{EAGAIN, EBADF, EDEADLK, EINTR, EINVAL},
+};
+
+/// `flock()`的操作
+pub const LOCK_SH: usize = 1;
+pub const LOCK_EX: usize = 2;
+pub const LOCK_NB: usize = 4;
+pub const LOCK_UN: usize = 8;
+
+/// `struct flock`中的`l_type`
+pub const F_RDLCK: i16 = 0;
+pub const F_WRLCK: i16 = 1;
+pub const F_UNLCK: i16 = 2;
+
+/// 死锁检测时沿等待链查找的最大步数
+const MAX_DEADLK_ITERATIONS: usize = 10;
+
+/// `fcntl()`记录锁使用的`struct flock`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Flock {
+    pub l_type: i16,
+    pub l_whence: i16,
+    pub l_start: i64,
+    pub l_len: i64,
+    pub l_pid: i32,
+}
+
+/// 锁的持有者
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockOwner {
+    /// 传统的POSIX记录锁属于进程，参数为线程组号
+    Process(usize),
+    /// `flock()`锁与OFD锁属于打开的文件，参数为文件对象的地址
+    File(usize),
+}
+
+impl LockOwner {
+    pub fn of_file(file: &Arc<dyn File>) -> Self {
+        LockOwner::File(Arc::as_ptr(file) as *const u8 as usize)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LockType {
+    Read,
+    Write,
+}
+
+#[derive(Clone, Copy)]
+struct FileLock {
+    owner: LockOwner,
+    ltype: LockType,
+    /// 锁住的字节范围，两端都包含在内
+    start: usize,
+    end: usize,
+}
+
+impl FileLock {
+    fn conflicts(&self, other: &FileLock) -> bool {
+        self.owner != other.owner
+            && self.start <= other.end
+            && other.start <= self.end
+            && (self.ltype == LockType::Write || other.ltype == LockType::Write)
+    }
+}
+
+lazy_static! {
+    /// 因POSIX记录锁而阻塞的进程，及其等待的锁的持有者
+    static ref POSIX_BLOCKERS: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+}
+
+/// 沿等待链查找，判断`waiter`等待`holder`是否会构成环
+fn posix_deadlock(blockers: &BTreeMap<usize, usize>, waiter: usize, mut holder: usize) -> bool {
+    for _ in 0..MAX_DEADLK_ITERATIONS {
+        if holder == waiter {
+            return true;
+        }
+        holder = match blockers.get(&holder) {
+            Some(next) => *next,
+            None => return false,
+        };
+    }
+    false
+}
+
+struct FileLockInner {
+    flocks: Vec<FileLock>,
+    posix: Vec<FileLock>,
+}
+
+/// 每个inode上的锁。`flock()`锁与记录锁互相独立，POSIX锁与OFD锁共用同一张表
+pub struct FileLockContext {
+    inner: Mutex<FileLockInner>,
+    wait_queue: Arc<PollWaitQueue>,
+}
+
+impl FileLockContext {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(FileLockInner {
+                flocks: Vec::new(),
+                posix: Vec::new(),
+            }),
+            wait_queue: Arc::new(PollWaitQueue::new()),
+        }
+    }
+    fn wake_waiters(&self) {
+        self.wait_queue.notify(PollEvent::POLLIN);
+    }
+    /// 反复尝试`op`，它返回冲突的锁时睡眠等待，直到锁被释放
+    fn wait_for(
+        &self,
+        wait: bool,
+        mut op: impl FnMut(&mut FileLockInner) -> Option<LockOwner>,
+        mut on_block: impl FnMut(&mut FileLockInner, LockOwner) -> Result<(), isize>,
+    ) -> Result<(), isize> {
+        let mut waiter = PollWaiter::new();
+        waiter.register_queue(Some(self.wait_queue.clone()), PollEvent::POLLIN);
+        loop {
+            let mut inner = self.inner.lock();
+            let blocker = match op(&mut inner) {
+                Some(blocker) => blocker,
+                None => return Ok(()),
+            };
+            if !wait {
+                return Err(EAGAIN);
+            }
+            on_block(&mut inner, blocker)?;
+            drop(inner);
+            if signal_pending() {
+                return Err(EINTR);
+            }
+            waiter.sleep(None);
+        }
+    }
+    /// 整个文件的`flock()`锁，`operation`为`LOCK_SH`、`LOCK_EX`或`LOCK_UN`，可附加`LOCK_NB`
+    pub fn flock(&self, owner: LockOwner, operation: usize) -> Result<(), isize> {
+        let ltype = match operation & !LOCK_NB {
+            LOCK_SH => LockType::Read,
+            LOCK_EX => LockType::Write,
+            LOCK_UN => {
+                self.inner.lock().flocks.retain(|lock| lock.owner != owner);
+                self.wake_waiters();
+                return Ok(());
+            }
+            _ => return Err(EINVAL),
+        };
+        let request = FileLock {
+            owner,
+            ltype,
+            start: 0,
+            end: usize::MAX,
+        };
+        self.wait_for(
+            operation & LOCK_NB == 0,
+            |inner| {
+                if let Some(lock) = inner.flocks.iter().find(|lock| lock.conflicts(&request)) {
+                    return Some(lock.owner);
+                }
+                inner.flocks.retain(|lock| lock.owner != owner);
+                inner.flocks.push(request);
+                None
+            },
+            |inner, _| {
+                // 转换锁的类型时若需要等待，先放弃原有的锁
+                let len = inner.flocks.len();
+                inner.flocks.retain(|lock| lock.owner != owner);
+                if inner.flocks.len() != len {
+                    self.wake_waiters();
+                }
+                Ok(())
+            },
+        )
+    }
+    /// 将`owner`在`[start, end]`范围内的记录锁去掉，必要时拆分已有的锁
+    fn posix_remove_range(locks: &mut Vec<FileLock>, owner: LockOwner, start: usize, end: usize) {
+        let mut result = Vec::with_capacity(locks.len() + 1);
+        for lock in locks.drain(..) {
+            if lock.owner != owner || lock.end < start || lock.start > end {
+                result.push(lock);
+                continue;
+            }
+            if lock.start < start {
+                result.push(FileLock {
+                    end: start - 1,
+                    ..lock
+                });
+            }
+            if lock.end > end {
+                result.push(FileLock {
+                    start: end + 1,
+                    ..lock
+                });
+            }
+        }
+        *locks = result;
+    }
+    /// 加入新的记录锁，并与同一持有者相邻或重叠的同类型锁合并
+    fn posix_insert(locks: &mut Vec<FileLock>, lock: FileLock) {
+        Self::posix_remove_range(locks, lock.owner, lock.start, lock.end);
+        let mut merged = lock;
+        locks.retain(|other| {
+            if other.owner == merged.owner
+                && other.ltype == merged.ltype
+                && other.start <= merged.end.saturating_add(1)
+                && merged.start <= other.end.saturating_add(1)
+            {
+                merged.start = merged.start.min(other.start);
+                merged.end = merged.end.max(other.end);
+                false
+            } else {
+                true
+            }
+        });
+        locks.push(merged);
+    }
+    /// `F_GETLK`：返回与请求冲突的第一个锁
+    fn posix_test(&self, request: &FileLock) -> Option<FileLock> {
+        self.inner
+            .lock()
+            .posix
+            .iter()
+            .find(|lock| lock.conflicts(request))
+            .copied()
+    }
+    /// `F_SETLK`/`F_SETLKW`，`ltype`为`None`表示解锁
+    fn posix_lock(
+        &self,
+        owner: LockOwner,
+        ltype: Option<LockType>,
+        start: usize,
+        end: usize,
+        wait: bool,
+    ) -> Result<(), isize> {
+        let ltype = match ltype {
+            Some(ltype) => ltype,
+            None => {
+                Self::posix_remove_range(&mut self.inner.lock().posix, owner, start, end);
+                self.wake_waiters();
+                return Ok(());
+            }
+        };
+        let request = FileLock {
+            owner,
+            ltype,
+            start,
+            end,
+        };
+        let result = self.wait_for(
+            wait,
+            |inner| {
+                if let Some(lock) = inner.posix.iter().find(|lock| lock.conflicts(&request)) {
+                    return Some(lock.owner);
+                }
+                Self::posix_insert(&mut inner.posix, request);
+                None
+            },
+            |_, blocker| {
+                // 只对进程持有的锁做死锁检测，OFD锁与Linux一样不检测
+                if let (LockOwner::Process(waiter), LockOwner::Process(holder)) = (owner, blocker) {
+                    let mut blockers = POSIX_BLOCKERS.lock();
+                    if posix_deadlock(&blockers, waiter, holder) {
+                        blockers.remove(&waiter);
+                        return Err(EDEADLK);
+                    }
+                    blockers.insert(waiter, holder);
+                }
+                Ok(())
+            },
+        );
+        if let LockOwner::Process(waiter) = owner {
+            if wait {
+                POSIX_BLOCKERS.lock().remove(&waiter);
+            }
+        }
+        // 加锁可能拆分或降级了原有的锁
+        self.wake_waiters();
+        result
+    }
+    /// 释放`owner`持有的全部记录锁与`flock()`锁
+    pub fn remove_owner(&self, owner: LockOwner) {
+        let mut inner = self.inner.lock();
+        let len = inner.flocks.len() + inner.posix.len();
+        inner.flocks.retain(|lock| lock.owner != owner);
+        inner.posix.retain(|lock| lock.owner != owner);
+        let changed = inner.flocks.len() + inner.posix.len() != len;
+        drop(inner);
+        if changed {
+            self.wake_waiters();
+        }
+    }
+}
+
+/// 根据`struct flock`计算锁住的字节范围
+fn flock_range(file: &Arc<dyn File>, flock: &Flock) -> Result<(usize, usize), isize> {
+    let base = match SeekWhence::from_bits(flock.l_whence as u32) {
+        Some(SeekWhence::SEEK_SET) => 0,
+        Some(SeekWhence::SEEK_CUR) => file.get_offset() as i64,
+        Some(SeekWhence::SEEK_END) => file.get_size() as i64,
+        _ => return Err(EINVAL),
+    };
+    let start = base.checked_add(flock.l_start).ok_or(EINVAL)?;
+    let (start, end) = if flock.l_len > 0 {
+        (start, start.checked_add(flock.l_len - 1).ok_or(EINVAL)?)
+    } else if flock.l_len == 0 {
+        (start, i64::MAX)
+    } else {
+        (start.checked_add(flock.l_len).ok_or(EINVAL)?, start - 1)
+    };
+    if start < 0 {
+        return Err(EINVAL);
+    }
+    let end = if end == i64::MAX {
+        usize::MAX
+    } else {
+        end as usize
+    };
+    Ok((start as usize, end))
+}
+
+/// `fcntl()`的`F_GETLK`与`F_OFD_GETLK`，结果写回`flock`
+pub fn fcntl_getlk(file: &Arc<dyn File>, owner: LockOwner, flock: &mut Flock) -> Result<(), isize> {
+    let ltype = match flock.l_type {
+        F_RDLCK => LockType::Read,
+        F_WRLCK => LockType::Write,
+        _ => return Err(EINVAL),
+    };
+    let (start, end) = flock_range(file, flock)?;
+    let node = match file.get_dirtree_node() {
+        Some(node) => node,
+        None => {
+            flock.l_type = F_UNLCK;
+            return Ok(());
+        }
+    };
+    let request = FileLock {
+        owner,
+        ltype,
+        start,
+        end,
+    };
+    match node.file_locks().posix_test(&request) {
+        Some(lock) => {
+            flock.l_type = match lock.ltype {
+                LockType::Read => F_RDLCK,
+                LockType::Write => F_WRLCK,
+            };
+            flock.l_whence = SeekWhence::SEEK_SET.bits() as i16;
+            flock.l_start = lock.start as i64;
+            flock.l_len = if lock.end == usize::MAX {
+                0
+            } else {
+                (lock.end - lock.start + 1) as i64
+            };
+            flock.l_pid = match lock.owner {
+                LockOwner::Process(pid) => pid as i32,
+                LockOwner::File(_) => -1,
+            };
+        }
+        None => flock.l_type = F_UNLCK,
+    }
+    Ok(())
+}
+
+/// `fcntl()`的`F_SETLK(W)`与`F_OFD_SETLK(W)`
+pub fn fcntl_setlk(
+    file: &Arc<dyn File>,
+    owner: LockOwner,
+    flock: &Flock,
+    wait: bool,
+) -> Result<(), isize> {
+    let ltype = match flock.l_type {
+        F_RDLCK if !file.readable() => return Err(EBADF),
+        F_WRLCK if !file.writable() => return Err(EBADF),
+        F_RDLCK => Some(LockType::Read),
+        F_WRLCK => Some(LockType::Write),
+        F_UNLCK => None,
+        _ => return Err(EINVAL),
+    };
+    let (start, end) = flock_range(file, flock)?;
+    match file.get_dirtree_node() {
+        Some(node) => node.file_locks().posix_lock(owner, ltype, start, end, wait),
+        // 管道等没有inode的文件无法被其他打开者共享，视为加锁成功
+        None => Ok(()),
+    }
+}
+
+/// `flock()`
+pub fn flock(file: &Arc<dyn File>, operation: usize) -> Result<(), isize> {
+    match file.get_dirtree_node() {
+        Some(node) => node.file_locks().flock(LockOwner::of_file(file), operation),
+        None => match operation & !LOCK_NB {
+            LOCK_SH | LOCK_EX | LOCK_UN => Ok(()),
+            _ => Err(EINVAL),
+        },
+    }
+}
+
+/// 进程关闭文件描述符时释放其在该文件上的POSIX记录锁
+pub fn locks_remove_posix(file: &Arc<dyn File>, pid: usize) {
+    if let Some(node) = file.get_dirtree_node() {
+        node.file_locks().remove_owner(LockOwner::Process(pid));
+    }
+}
@@ -0,0 +1,512 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use spin::Mutex;
+
+use crate::{
+    config::PAGE_SIZE,
+    fs::{
+        cache::PageCache, directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat,
+        SeekWhence, StatMode,
+    },
+    mm::{FileMappings, UserBuffer},
+    syscall::errno::{EACCES, EBUSY, EINVAL, ENOTDIR, EPERM},
+    task::current_task,
+};
+
+/// 与`O_CLOEXEC`作用相同
+pub const MFD_CLOEXEC: usize = 1;
+/// 允许通过`F_ADD_SEALS`添加封印，否则文件创建时就带有`F_SEAL_SEAL`
+pub const MFD_ALLOW_SEALING: usize = 2;
+/// 名字的最大长度，加上`memfd:`前缀后不超过`NAME_MAX`
+pub const MFD_NAME_MAX_LEN: usize = 249;
+
+bitflags! {
+    /// `F_ADD_SEALS`与`F_GET_SEALS`使用的封印，添加后不能去除
+    pub struct Seals: u32 {
+        /// 不能再添加封印
+        const F_SEAL_SEAL = 0x1;
+        /// 不能缩小文件
+        const F_SEAL_SHRINK = 0x2;
+        /// 不能扩大文件
+        const F_SEAL_GROW = 0x4;
+        /// 不能修改内容，存在可写的共享映射时不能添加
+        const F_SEAL_WRITE = 0x8;
+        /// 不能再写入或建立可写的共享映射，已有的映射不受影响
+        const F_SEAL_FUTURE_WRITE = 0x10;
+    }
+}
+
+/// 分配inode号
+static MEMFD_INO: AtomicUsize = AtomicUsize::new(1);
+
+struct MemFdState {
+    size: usize,
+    /// 文件内容所在的页，没有写入或映射过的页为`None`，读取时视为全零
+    pages: Vec<Option<Arc<Mutex<PageCache>>>>,
+    seals: Seals,
+    /// 可写的共享映射数量
+    writable_mappings: usize,
+}
+
+impl MemFdState {
+    /// 取出第`idx`页，不存在时分配一个清零的页
+    fn get_or_alloc_page(&mut self, idx: usize) -> Arc<Mutex<PageCache>> {
+        if self.pages.len() <= idx {
+            self.pages.resize(idx + 1, None);
+        }
+        self.pages[idx]
+            .get_or_insert_with(|| {
+                let page = PageCache::new();
+                page.get_tracker().ppn.get_bytes_array().fill(0);
+                Arc::new(Mutex::new(page))
+            })
+            .clone()
+    }
+    fn write_at(&mut self, offset: usize, buf: &[u8]) -> Result<usize, isize> {
+        if self
+            .seals
+            .intersects(Seals::F_SEAL_WRITE | Seals::F_SEAL_FUTURE_WRITE)
+        {
+            return Err(EPERM);
+        }
+        let end = offset + buf.len();
+        if end > self.size && self.seals.contains(Seals::F_SEAL_GROW) {
+            return Err(EPERM);
+        }
+        let mut pos = offset;
+        while pos < end {
+            let page_offset = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min(end - pos);
+            let page = self.get_or_alloc_page(pos / PAGE_SIZE);
+            let ppn = page.lock().get_tracker().ppn;
+            ppn.get_bytes_array()[page_offset..page_offset + len]
+                .copy_from_slice(&buf[pos - offset..pos - offset + len]);
+            pos += len;
+        }
+        self.size = self.size.max(end);
+        Ok(buf.len())
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        if offset >= self.size {
+            return 0;
+        }
+        let end = (offset + buf.len()).min(self.size);
+        let mut pos = offset;
+        while pos < end {
+            let page_offset = pos % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min(end - pos);
+            let dst = &mut buf[pos - offset..pos - offset + len];
+            match self.pages.get(pos / PAGE_SIZE).and_then(Option::as_ref) {
+                Some(page) => {
+                    let ppn = page.lock().get_tracker().ppn;
+                    dst.copy_from_slice(&ppn.get_bytes_array()[page_offset..page_offset + len]);
+                }
+                None => dst.fill(0),
+            }
+            pos += len;
+        }
+        end - offset
+    }
+    fn truncate(&mut self, new_size: usize) -> Result<(), isize> {
+        if new_size < self.size && self.seals.contains(Seals::F_SEAL_SHRINK)
+            || new_size > self.size && self.seals.contains(Seals::F_SEAL_GROW)
+        {
+            return Err(EPERM);
+        }
+        if new_size < self.size {
+            self.pages.truncate((new_size + PAGE_SIZE - 1) / PAGE_SIZE);
+            // 之后再扩大文件时，被截掉的部分应读出零
+            let page_offset = new_size % PAGE_SIZE;
+            if page_offset != 0 {
+                if let Some(Some(page)) = self.pages.get(new_size / PAGE_SIZE) {
+                    let ppn = page.lock().get_tracker().ppn;
+                    ppn.get_bytes_array()[page_offset..].fill(0);
+                }
+            }
+        }
+        self.size = new_size;
+        Ok(())
+    }
+}
+
+/// 同一个memfd的所有打开实例共享的内容
+struct MemFdInode {
+    ino: usize,
+    uid: u32,
+    gid: u32,
+    /// 是否已从`/dev/shm`中删除
+    unlinked: AtomicBool,
+    state: Mutex<MemFdState>,
+    /// 映射了该文件的地址空间
+    mappings: Arc<FileMappings>,
+}
+
+/// `memfd_create()`创建的匿名内存文件，内容保存在不会写回的页中。
+/// 这些页通过`get_single_cache`交给`MAP_SHARED`映射，多个进程映射同一个文件时共享同一组物理页
+pub struct MemFd {
+    inode: Arc<MemFdInode>,
+    offset: Mutex<usize>,
+    /// 是否由可写的共享映射持有，释放时减少`writable_mappings`。
+    /// mprotect()加上写权限后置位
+    writable_mapping: AtomicBool,
+}
+
+impl MemFd {
+    /// 属主为当前任务的文件系统用户与组
+    pub fn new(allow_sealing: bool) -> Self {
+        let (uid, gid) = match current_task() {
+            Some(task) => {
+                let cred = task.cred.lock();
+                (cred.fsuid, cred.fsgid)
+            }
+            None => (0, 0),
+        };
+        let seals = if allow_sealing {
+            Seals::empty()
+        } else {
+            Seals::F_SEAL_SEAL
+        };
+        Self {
+            inode: Arc::new(MemFdInode {
+                ino: MEMFD_INO.fetch_add(1, Ordering::Relaxed),
+                uid,
+                gid,
+                unlinked: AtomicBool::new(false),
+                state: Mutex::new(MemFdState {
+                    size: 0,
+                    pages: Vec::new(),
+                    seals,
+                    writable_mappings: 0,
+                }),
+                mappings: FileMappings::new(),
+            }),
+            offset: Mutex::new(0),
+            writable_mapping: AtomicBool::new(false),
+        }
+    }
+    /// 为`MAP_SHARED`映射生成一个实例。可写的映射受`F_SEAL_WRITE`与`F_SEAL_FUTURE_WRITE`限制，
+    /// 存在期间会阻止添加`F_SEAL_WRITE`
+    pub fn mmap_shared(&self, writable: bool) -> Result<Arc<dyn File>, isize> {
+        if writable {
+            let mut state = self.inode.state.lock();
+            if state
+                .seals
+                .intersects(Seals::F_SEAL_WRITE | Seals::F_SEAL_FUTURE_WRITE)
+            {
+                return Err(EPERM);
+            }
+            state.writable_mappings += 1;
+        }
+        Ok(Arc::new(Self {
+            inode: self.inode.clone(),
+            offset: Mutex::new(0),
+            writable_mapping: AtomicBool::new(writable),
+        }))
+    }
+    /// mprotect()为只读的共享映射加上写权限，与建立可写映射受同样的封印限制。
+    /// 之后该实例按可写映射计数，直到映射解除
+    pub fn mprotect_writable(&self) -> Result<(), isize> {
+        let mut state = self.inode.state.lock();
+        if self.writable_mapping.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        if state
+            .seals
+            .intersects(Seals::F_SEAL_WRITE | Seals::F_SEAL_FUTURE_WRITE)
+        {
+            return Err(EACCES);
+        }
+        state.writable_mappings += 1;
+        self.writable_mapping.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+    pub fn add_seals(&self, seals: u32) -> Result<(), isize> {
+        let seals = match Seals::from_bits(seals) {
+            Some(seals) => seals,
+            None => return Err(EINVAL),
+        };
+        let mut state = self.inode.state.lock();
+        if state.seals.contains(Seals::F_SEAL_SEAL) {
+            return Err(EPERM);
+        }
+        if seals.contains(Seals::F_SEAL_WRITE) && state.writable_mappings > 0 {
+            return Err(EBUSY);
+        }
+        state.seals |= seals;
+        Ok(())
+    }
+    pub fn get_seals(&self) -> u32 {
+        self.inode.state.lock().seals.bits()
+    }
+    /// 两个实例是否属于同一个文件
+    pub fn same_file(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inode, &other.inode)
+    }
+    pub fn is_unlinked(&self) -> bool {
+        self.inode.unlinked.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for MemFd {
+    fn drop(&mut self) {
+        if *self.writable_mapping.get_mut() {
+            self.inode.state.lock().writable_mappings -= 1;
+        }
+    }
+}
+
+#[allow(unused)]
+impl File for MemFd {
+    /// 与原实例共享内容，只复制文件偏移量
+    fn deep_clone(&self) -> Arc<dyn File> {
+        let mut state = self.inode.state.lock();
+        let writable_mapping = self.writable_mapping.load(Ordering::Relaxed);
+        if writable_mapping {
+            state.writable_mappings += 1;
+        }
+        drop(state);
+        Arc::new(Self {
+            inode: self.inode.clone(),
+            offset: Mutex::new(*self.offset.lock()),
+            writable_mapping: AtomicBool::new(writable_mapping),
+        })
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        let state = self.inode.state.lock();
+        match offset {
+            Some(offset) => {
+                let len = state.read_at(*offset, buf);
+                *offset += len;
+                len
+            }
+            None => {
+                let mut offset = self.offset.lock();
+                let len = state.read_at(*offset, buf);
+                *offset += len;
+                len
+            }
+        }
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        let mut state = self.inode.state.lock();
+        match offset {
+            Some(offset) => match state.write_at(*offset, buf) {
+                Ok(len) => {
+                    *offset += len;
+                    len
+                }
+                Err(errno) => errno as usize,
+            },
+            None => {
+                let mut offset = self.offset.lock();
+                match state.write_at(*offset, buf) {
+                    Ok(len) => {
+                        *offset += len;
+                        len
+                    }
+                    Err(errno) => errno as usize,
+                }
+            }
+        }
+    }
+
+    fn r_ready(&self) -> bool {
+        true
+    }
+
+    fn w_ready(&self) -> bool {
+        true
+    }
+
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        let state = self.inode.state.lock();
+        let mut local_offset = offset;
+        let mut file_offset = self.offset.lock();
+        let offset = match local_offset.as_mut() {
+            Some(offset) => offset,
+            None => &mut *file_offset,
+        };
+        let mut total_read_size = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let read_size = state.read_at(*offset, *slice);
+            if read_size == 0 {
+                break;
+            }
+            *offset += read_size;
+            total_read_size += read_size;
+        }
+        total_read_size
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        let mut state = self.inode.state.lock();
+        let mut local_offset = offset;
+        let mut file_offset = self.offset.lock();
+        let offset = match local_offset.as_mut() {
+            Some(offset) => offset,
+            None => &mut *file_offset,
+        };
+        let mut total_write_size = 0usize;
+        for slice in buf.buffers.iter() {
+            match state.write_at(*offset, *slice) {
+                Ok(write_size) => {
+                    *offset += write_size;
+                    total_write_size += write_size;
+                }
+                Err(errno) if total_write_size == 0 => return errno as usize,
+                Err(_) => break,
+            }
+        }
+        total_write_size
+    }
+
+    fn get_size(&self) -> usize {
+        self.inode.state.lock().size
+    }
+
+    fn get_stat(&self) -> Stat {
+        let nlink = if self.is_unlinked() { 0 } else { 1 };
+        let mut stat = Stat::new(
+            crate::makedev!(0, 1),
+            self.inode.ino as u64,
+            StatMode::S_IFREG.bits() | 0o777,
+            nlink,
+            0,
+            self.get_size() as i64,
+            0,
+            0,
+            0,
+        );
+        stat.set_owner(self.inode.uid, self.inode.gid);
+        stat
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(&self, dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        self.deep_clone()
+    }
+
+    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        self.inode.unlinked.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> Vec<Dirent> {
+        Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: SeekWhence) -> Result<usize, isize> {
+        // 与读写一致，先取文件内容的锁再取偏移量的锁
+        let size = self.get_size();
+        let mut file_offset = self.offset.lock();
+        let new_offset = match whence {
+            SeekWhence::SEEK_SET => offset,
+            SeekWhence::SEEK_CUR => *file_offset as isize + offset,
+            SeekWhence::SEEK_END => size as isize + offset,
+            _ => return Err(EINVAL),
+        };
+        if new_offset < 0 {
+            return Err(EINVAL);
+        }
+        *file_offset = new_offset as usize;
+        Ok(new_offset as usize)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        let mut state = self.inode.state.lock();
+        let new_size = state.size as isize + diff;
+        if new_size < 0 {
+            return Err(EINVAL);
+        }
+        state.truncate(new_size as usize)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        self.inode.state.lock().truncate(new_size)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(&self, offset: usize) -> Result<Arc<Mutex<PageCache>>, ()> {
+        if offset & (PAGE_SIZE - 1) != 0 {
+            return Err(());
+        }
+        Ok(self
+            .inode
+            .state
+            .lock()
+            .get_or_alloc_page(offset / PAGE_SIZE))
+    }
+
+    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()> {
+        Ok(self
+            .inode
+            .state
+            .lock()
+            .pages
+            .iter()
+            .flatten()
+            .cloned()
+            .collect())
+    }
+
+    fn mappings(&self) -> Option<Arc<FileMappings>> {
+        Some(self.inode.mappings.clone())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
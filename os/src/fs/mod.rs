@@ -1,20 +1,32 @@
 mod cache;
+mod dcache;
 pub mod dev;
 pub mod directory_tree;
+pub mod epoll;
+pub mod eventfd;
 mod ext4;
 pub mod fat32;
 pub mod file_trait;
 mod filesystem;
+pub mod inotify;
 mod layout;
+pub mod locks;
+pub mod memfd;
+pub mod permission;
 pub mod poll;
+pub mod readahead;
+pub mod shmfs;
+pub mod signalfd;
 #[cfg(feature = "swap")]
 pub mod swap;
 // Xein add this
 pub mod dirent;
 pub mod file_descriptor;
 mod inode;
+pub mod timerfd;
 mod timestamp;
 mod vfs;
+pub mod writeback;
 
 
 pub use self::dev::{
@@ -29,7 +41,7 @@ pub use self::layout::*;
 pub use self::fat32::DiskInodeType;
 pub use crate::drivers::block::BlockDevice;
 
-use self::cache::PageCache;
+pub use self::cache::{BufferCache, PageCache};
 use alloc::{
     string::String,
     sync::Arc,
@@ -0,0 +1,62 @@
+use crate::{
+    fs::{layout::StatMode, Stat},
+    syscall::errno::{EACCES, EPERM},
+    task::{Capabilities, Credentials},
+};
+
+/// 权限检查中请求的访问类型，与`access()`的`X_OK`、`W_OK`、`R_OK`一致
+pub const MAY_EXEC: u32 = 1;
+pub const MAY_WRITE: u32 = 2;
+pub const MAY_READ: u32 = 4;
+
+/// 按属主、属组、其他人的顺序选出一组权限位，检查是否包含`mask`请求的全部访问。
+/// 权限位不满足时，CAP_DAC_OVERRIDE可以越过读写检查，但执行普通文件时仍要求至少有一个执行位；
+/// CAP_DAC_READ_SEARCH只越过文件的读和目录的读与搜索
+pub fn generic_permission(stat: &Stat, mask: u32, cred: &Credentials) -> Result<(), isize> {
+    let mode = stat.get_mode();
+    let perm = if cred.fsuid == stat.get_uid() {
+        mode >> 6
+    } else if cred.in_group(stat.get_gid()) {
+        mode >> 3
+    } else {
+        mode
+    } & 0o7;
+    if perm & mask == mask {
+        return Ok(());
+    }
+    let is_dir = mode & StatMode::S_IFMT.bits() == StatMode::S_IFDIR.bits();
+    if cred.capable(Capabilities::CAP_DAC_OVERRIDE)
+        && (is_dir || mask & MAY_EXEC == 0 || mode & 0o111 != 0)
+    {
+        return Ok(());
+    }
+    if cred.capable(Capabilities::CAP_DAC_READ_SEARCH)
+        && mask & MAY_WRITE == 0
+        && (is_dir || mask == MAY_READ)
+    {
+        return Ok(());
+    }
+    Err(EACCES)
+}
+
+/// 设置了粘着位的目录中，只有文件属主、目录属主和拥有CAP_FOWNER的任务可以删除或重命名文件
+pub fn check_sticky(dir: &Stat, file: &Stat, cred: &Credentials) -> Result<(), isize> {
+    if dir.get_mode() & StatMode::S_ISVTX.bits() == 0
+        || cred.fsuid == dir.get_uid()
+        || cred.fsuid == file.get_uid()
+        || cred.capable(Capabilities::CAP_FOWNER)
+    {
+        Ok(())
+    } else {
+        Err(EPERM)
+    }
+}
+
+/// 只有文件属主和拥有CAP_FOWNER的任务可以修改文件的权限位等属性
+pub fn check_owner(stat: &Stat, cred: &Credentials) -> Result<(), isize> {
+    if cred.fsuid == stat.get_uid() || cred.capable(Capabilities::CAP_FOWNER) {
+        Ok(())
+    } else {
+        Err(EPERM)
+    }
+}
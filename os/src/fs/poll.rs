@@ -1,12 +1,26 @@
 use crate::{
-    mm::try_get_from_user, syscall::errno::EFAULT, task::signal::Signals, timer::TimeSpec,
+    mm::try_get_from_user,
+    syscall::errno::{EFAULT, EINTR},
+    task::signal::Signals,
+    timer::TimeSpec,
 };
-use alloc::vec::Vec;
-use core::ptr::null_mut;
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    ptr::null_mut,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use spin::Mutex;
 
+use super::file_trait::File;
 use crate::{
     mm::{copy_from_user_array, copy_to_user_array},
-    task::{current_task, sigprocmask, suspend_current_and_run_next, SigMaskHow},
+    task::{
+        block_current_and_run_next, current_task, sigprocmask, wait_with_timeout,
+        wake_interruptible, SigMaskHow, TaskControlBlock, TaskStatus,
+    },
 };
 
 ///  A scheduling  scheme  whereby  the  local  process  periodically  checks  until  the  pre-specified events (for example, read, write) have occurred.
@@ -28,7 +42,7 @@ bitflags! {
     /// These bits may be set in `events`(see `ppoll()`) to indicate the interesting event types;
     ///
     /// they will appear in `revents` to indicate the status of the file descriptor.
-    struct PollEvent:u16 {
+    pub struct PollEvent:u16 {
     /// There is data to read.
     const POLLIN = 0x001;
     /// There is urgent data to read.
@@ -70,19 +84,182 @@ bitflags! {
     }
 }
 
+/// 等待者回调，参数为发生的事件，返回`true`表示该等待者确实被唤醒
+pub type PollCallback = Arc<dyn Fn(PollEvent) -> bool + Send + Sync>;
+
+struct PollEntry {
+    key: usize,
+    mask: PollEvent,
+    exclusive: bool,
+    callback: PollCallback,
+}
+
+static NEXT_POLL_KEY: AtomicUsize = AtomicUsize::new(1);
+
+/// 文件的等待队列。
+/// 文件状态变化（可读、可写、挂断等）时由文件调用`notify()`，
+/// 以唤醒睡眠在其上的任务，或通知关注该文件的 epoll 实例。
+pub struct PollWaitQueue {
+    entries: Mutex<Vec<PollEntry>>,
+}
+
+impl PollWaitQueue {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+    /// 注册一个等待者，只有`mask`中的事件以及总会上报的`POLLERR`、`POLLHUP`会触发它。
+    /// 返回用于`remove()`的键值
+    pub fn add(&self, mask: PollEvent, exclusive: bool, callback: PollCallback) -> usize {
+        let key = NEXT_POLL_KEY.fetch_add(1, Ordering::Relaxed);
+        self.entries.lock().push(PollEntry {
+            key,
+            mask,
+            exclusive,
+            callback,
+        });
+        key
+    }
+    pub fn remove(&self, key: usize) {
+        self.entries.lock().retain(|entry| entry.key != key);
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+    /// 通知等待者发生了`events`。
+    /// 非独占等待者全部被调用；独占等待者按注册顺序调用，直到有一个返回`true`为止。
+    /// 回调在队列锁之外执行，因此回调中可以再次操作队列
+    pub fn notify(&self, events: PollEvent) {
+        let always = events.intersects(PollEvent::POLLERR | PollEvent::POLLHUP);
+        let callbacks: Vec<(bool, PollCallback)> = self
+            .entries
+            .lock()
+            .iter()
+            .filter(|entry| always || entry.mask.intersects(events))
+            .map(|entry| (entry.exclusive, entry.callback.clone()))
+            .collect();
+        let mut exclusive_woken = false;
+        for (exclusive, callback) in callbacks {
+            if !exclusive {
+                callback(events);
+            } else if !exclusive_woken {
+                exclusive_woken = callback(events);
+            }
+        }
+    }
+}
+
+impl Default for PollWaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 当前任务在若干文件等待队列上的一次睡眠。
+/// 注册后任一队列被通知都会唤醒任务，析构时自动从所有队列中注销
+pub struct PollWaiter {
+    task: Weak<TaskControlBlock>,
+    woken: Arc<AtomicUsize>,
+    callback: PollCallback,
+    registered: Vec<(Arc<PollWaitQueue>, usize)>,
+    /// 存在不提供等待队列的文件，只能每个时钟周期轮询一次
+    polling: bool,
+}
+
+impl PollWaiter {
+    pub fn new() -> Self {
+        let task = current_task().unwrap();
+        let woken = Arc::new(AtomicUsize::new(0));
+        let callback: PollCallback = {
+            let task = Arc::downgrade(&task);
+            let woken = woken.clone();
+            Arc::new(move |_| match task.upgrade() {
+                Some(task) => {
+                    woken.store(1, Ordering::Release);
+                    let mut inner = task.acquire_inner_lock();
+                    if inner.task_status == TaskStatus::Interruptible {
+                        inner.task_status = TaskStatus::Ready;
+                        drop(inner);
+                        wake_interruptible(task);
+                    }
+                    true
+                }
+                None => false,
+            })
+        };
+        Self {
+            task: Arc::downgrade(&task),
+            woken,
+            callback,
+            registered: Vec::new(),
+            polling: false,
+        }
+    }
+    /// 在`file`的等待队列上等待`mask`中的事件
+    pub fn register(&mut self, file: &Arc<dyn File>, mask: PollEvent) {
+        self.register_queue(file.poll_queue(), mask)
+    }
+    /// 在等待队列`queue`上等待`mask`中的事件，`None`表示只能定时轮询
+    pub fn register_queue(&mut self, queue: Option<Arc<PollWaitQueue>>, mask: PollEvent) {
+        match queue {
+            Some(queue) => {
+                let key = queue.add(mask, false, self.callback.clone());
+                self.registered.push((queue, key));
+            }
+            None => self.polling = true,
+        }
+    }
+    /// 睡眠直到被某个等待队列唤醒、到达`timeout`或收到信号。
+    /// 若注册后已经被通知过，则直接返回而不睡眠，避免丢失唤醒
+    pub fn sleep(&self, timeout: Option<TimeSpec>) {
+        if self.woken.swap(0, Ordering::AcqRel) != 0 {
+            return;
+        }
+        if let Some(timeout) = timeout {
+            wait_with_timeout(self.task.clone(), timeout);
+        }
+        if self.polling {
+            wait_with_timeout(self.task.clone(), TimeSpec::now());
+        }
+        block_current_and_run_next();
+        self.woken.store(0, Ordering::Release);
+    }
+}
+
+impl Drop for PollWaiter {
+    fn drop(&mut self) {
+        for (queue, key) in self.registered.iter() {
+            queue.remove(*key);
+        }
+    }
+}
+
+/// 当前任务是否有未被屏蔽的待处理信号
+pub fn signal_pending() -> bool {
+    let task = current_task().unwrap();
+    let inner = task.acquire_inner_lock();
+    !inner.sigpending.difference(inner.sigmask).is_empty()
+}
+
+/// 时钟中断与空闲循环中调用，为没有中断驱动的设备检查状态变化并唤醒等待者
+pub fn poll_tick() {
+    crate::fs::dev::tty::TTY.poll_tick();
+    crate::net::config::NET_INTERFACE.poll_tick();
+}
+
 /// Wait for one of the events in `poll_fd_p` to happen, or the time limit to run out if any.
 /// Unlike the function family of `select()` which are basically AND'S,
 /// `poll()`'s act like OR's for polling the files.
 /// # Arguments
 /// * `poll_fd`: The USER pointer to the array of file descriptors to be polled
 /// * `nfds`: The number stored in the previous array.
-/// * `time_spec`: The time, see `timer::TimeSpec` for information. NOT SUPPORTED and will be ignored!
+/// * `time_spec`: The time, see `timer::TimeSpec` for information.
 /// * `sigmask`: The pointer to the sigmask in use during the poll.
 /// # Note
 /// * `POLLHUP`, `POLLNVAL` and `POLLERR` are ALWAYS polled for all given files,
 ///   regardless of whether it is set in the array.
 /// # Unsupported Features
-/// * Timeout is not yet supported.
 /// * Other implementations are supported by specific files and may not be used by
 /// * Currently only user space structs are supported.
 /// # Return Conditions
@@ -126,38 +303,39 @@ pub fn ppoll(
         unsafe {
             poll_fd.set_len(nfds);
         }
-        for poll_fd in poll_fd.iter_mut() {
-            poll_fd.revents = PollEvent::empty();
-        }
-
-        loop {
+        // 负数的fd被忽略，无效的fd报告POLLNVAL
+        let files: Vec<Option<Arc<dyn File>>> = {
             let task = current_task().unwrap();
             let fd_table = task.files.lock();
-
-            for poll_fd in poll_fd.iter_mut() {
-                let fd = poll_fd.fd as usize;
-                match fd_table.get_ref(fd) {
-                    Ok(file_descriptor) => {
-                        let mut trigger = 0;
-                        if file_descriptor.file.hang_up() {
-                            poll_fd.revents |= PollEvent::POLLHUP;
-                            trigger = 1;
-                        }
-                        if poll_fd.events.contains(PollEvent::POLLIN)
-                            && file_descriptor.file.r_ready()
-                        {
-                            poll_fd.revents |= PollEvent::POLLIN;
-                            trigger = 1;
-                        }
-                        if poll_fd.events.contains(PollEvent::POLLOUT)
-                            && file_descriptor.file.w_ready()
-                        {
-                            poll_fd.revents |= PollEvent::POLLOUT;
-                            trigger = 1;
-                        }
-                        done += trigger;
+            poll_fd
+                .iter()
+                .map(|poll_fd| {
+                    if (poll_fd.fd as i32) < 0 {
+                        return None;
                     }
-                    Err(_) => continue,
+                    fd_table
+                        .get_ref(poll_fd.fd as usize)
+                        .ok()
+                        .map(|file_descriptor| file_descriptor.file.clone())
+                })
+                .collect()
+        };
+        let mut waiter = PollWaiter::new();
+        for (poll_fd, file) in poll_fd.iter().zip(files.iter()) {
+            if let Some(file) = file {
+                waiter.register(file, poll_fd.events);
+            }
+        }
+
+        loop {
+            for (poll_fd, file) in poll_fd.iter_mut().zip(files.iter()) {
+                poll_fd.revents = match file {
+                    Some(file) => file.poll(poll_fd.events),
+                    None if (poll_fd.fd as i32) < 0 => PollEvent::empty(),
+                    None => PollEvent::POLLNVAL,
+                };
+                if !poll_fd.revents.is_empty() {
+                    done += 1;
                 }
             }
             if done > 0 {
@@ -168,13 +346,18 @@ pub fn ppoll(
                     break;
                 }
             }
-            drop(fd_table);
-            drop(task);
-            suspend_current_and_run_next();
+            if signal_pending() {
+                done = EINTR;
+                break;
+            }
+            waiter.sleep(timeout);
         }
+        drop(waiter);
 
         log::trace!("[ppoll] result: {:?}", poll_fd);
-        copy_to_user_array(token, &poll_fd[0], fds, nfds).unwrap();
+        if nfds > 0 {
+            copy_to_user_array(token, &poll_fd[0], fds, nfds).unwrap();
+        }
     } else {
         log::error!(
             "[ppoll] Error copy_from_user_array(_, fds: {:?}, poll_fd.as_mut_ptr():{:?}, _)",
@@ -312,39 +495,41 @@ pub fn pselect(
         sigprocmask(SigMaskHow::SIG_SETMASK.bits(), sigmask, oldsig);
     }
 
-    let mut done = 0;
-    loop {
+    // 读、写集合中的文件，每项为(fd, 文件, 关注的事件)
+    let mut watched: Vec<(usize, Arc<dyn File>, PollEvent)> = Vec::new();
+    {
         let task = current_task().unwrap();
         let fd_table = task.files.lock();
-
-        // check read
-        if let Some(ref read_fds) = read_fds {
-            for i in 0..nfds {
-                if !read_fds.is_set(i) {
-                    continue;
-                }
-                if let Ok(fd) = fd_table.get_ref(i) {
-                    if fd.r_ready() {
-                        done += 1;
-                    }
-                }
+        for i in 0..nfds {
+            let mut events = PollEvent::empty();
+            if read_fds.as_ref().map_or(false, |fds| fds.is_set(i)) {
+                events |= PollEvent::POLLIN;
             }
-        }
-        // check write
-        if let Some(ref write_fds) = write_fds {
-            for i in 0..nfds {
-                if !write_fds.is_set(i) {
-                    continue;
-                }
-                if let Ok(fd) = fd_table.get_ref(i) {
-                    if fd.w_ready() {
-                        done += 1;
-                    }
-                }
+            if write_fds.as_ref().map_or(false, |fds| fds.is_set(i)) {
+                events |= PollEvent::POLLOUT;
+            }
+            if events.is_empty() {
+                continue;
             }
+            if let Ok(file_descriptor) = fd_table.get_ref(i) {
+                watched.push((i, file_descriptor.file.clone(), events));
+            }
+        }
+    }
+    let mut waiter = PollWaiter::new();
+    for (_, file, events) in watched.iter() {
+        waiter.register(file, *events);
+    }
+
+    let mut done = 0;
+    let mut revents = Vec::with_capacity(watched.len());
+    loop {
+        revents.clear();
+        for (_, file, events) in watched.iter() {
+            let ready = file.poll(*events) & (PollEvent::POLLIN | PollEvent::POLLOUT);
+            done += ready.bits().count_ones() as isize;
+            revents.push(ready);
         }
-        // check exception
-        // do nothing
 
         if done != 0 {
             break;
@@ -354,35 +539,38 @@ pub fn pselect(
                 break;
             }
         }
-
-        drop(fd_table);
-        drop(task);
-        suspend_current_and_run_next();
+        if signal_pending() {
+            done = EINTR;
+            break;
+        }
+        waiter.sleep(timeout);
     }
-    let task = current_task().unwrap();
-    let fd_table = task.files.lock();
-    // count read
-    if let Some(read_fds) = read_fds.as_mut() {
-        for i in 0..nfds {
-            if !read_fds.is_set(i) {
-                continue;
+    drop(waiter);
+    if done >= 0 {
+        // count read and write
+        for ((fd, _, _), ready) in watched.iter().zip(revents.iter()) {
+            if let Some(read_fds) = read_fds.as_mut() {
+                if !ready.contains(PollEvent::POLLIN) {
+                    read_fds.clr(*fd);
+                }
             }
-            if let Ok(fd) = fd_table.get_ref(i) {
-                if !fd.r_ready() {
-                    read_fds.clr(i);
+            if let Some(write_fds) = write_fds.as_mut() {
+                if !ready.contains(PollEvent::POLLOUT) {
+                    write_fds.clr(*fd);
                 }
             }
         }
-    }
-    // count write
-    if let Some(write_fds) = write_fds.as_mut() {
-        for i in 0..nfds {
-            if !write_fds.is_set(i) {
-                continue;
-            }
-            if let Ok(fd) = fd_table.get_ref(i) {
-                if !fd.w_ready() {
-                    write_fds.clr(i);
+        // 未被打开的fd不会就绪
+        let mut opened = FdSet::empty();
+        for (fd, _, _) in watched.iter() {
+            opened.set(*fd);
+        }
+        for fds in [read_fds.as_mut(), write_fds.as_mut()] {
+            if let Some(fds) = fds {
+                for i in 0..nfds {
+                    if !opened.is_set(i) {
+                        fds.clr(i);
+                    }
                 }
             }
         }
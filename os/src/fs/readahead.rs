@@ -0,0 +1,110 @@
+use crate::config::PAGE_SIZE;
+use core::ops::Range;
+
+/// 初始预读窗口（页）
+pub const RA_INIT_PAGES: usize = 4;
+/// 预读窗口上限（页）
+pub const RA_MAX_PAGES: usize = 32;
+
+/// posix_fadvise 的建议类型
+pub const POSIX_FADV_NORMAL: usize = 0;
+pub const POSIX_FADV_RANDOM: usize = 1;
+pub const POSIX_FADV_SEQUENTIAL: usize = 2;
+pub const POSIX_FADV_WILLNEED: usize = 3;
+pub const POSIX_FADV_DONTNEED: usize = 4;
+pub const POSIX_FADV_NOREUSE: usize = 5;
+
+/// `[offset, offset + len)`覆盖的页范围，`len`为0表示到文件末尾，超出文件末尾的部分被忽略
+pub fn pages_of(offset: usize, len: usize, size: usize) -> Range<usize> {
+    let end = if len == 0 {
+        size
+    } else {
+        offset.saturating_add(len).min(size)
+    };
+    if offset >= end {
+        return 0..0;
+    }
+    offset / PAGE_SIZE..(end + PAGE_SIZE - 1) / PAGE_SIZE
+}
+
+/// 每个打开的文件各自维护的预读状态
+/// + 检测到顺序读时，预读窗口在`[RA_INIT_PAGES, max_pages]`之间成倍增长
+/// + 读到当前窗口的后半部分时提前发起下一个窗口的预读
+/// + 随机读时窗口复位
+#[derive(Clone)]
+pub struct FileReadahead {
+    /// 当前窗口的起始页
+    start: usize,
+    /// 当前窗口的大小（页），为0表示还没有窗口
+    size: usize,
+    /// 上一次读取的最后一页
+    prev_page: usize,
+    /// 窗口上限，由 fadvise 调整，为0时关闭预读
+    max_pages: usize,
+}
+
+impl FileReadahead {
+    pub fn new() -> Self {
+        Self {
+            start: 0,
+            size: 0,
+            prev_page: usize::MAX,
+            max_pages: RA_MAX_PAGES,
+        }
+    }
+
+    /// 根据 posix_fadvise 的建议调整预读策略
+    pub fn set_advice(&mut self, advice: usize) {
+        match advice {
+            POSIX_FADV_NORMAL => self.max_pages = RA_MAX_PAGES,
+            POSIX_FADV_RANDOM => self.max_pages = 0,
+            POSIX_FADV_SEQUENTIAL => self.max_pages = RA_MAX_PAGES * 2,
+            _ => {}
+        }
+        self.size = 0;
+    }
+
+    /// 一次读取访问了`[first_page, last_page]`，返回需要预读的页范围
+    /// # 参数
+    /// + `first_page`: 本次读取的第一页
+    /// + `last_page`: 本次读取的最后一页
+    /// + `nr_pages`: 文件总页数
+    pub fn on_read(
+        &mut self,
+        first_page: usize,
+        last_page: usize,
+        nr_pages: usize,
+    ) -> Option<Range<usize>> {
+        let sequential = first_page == 0 && self.prev_page == usize::MAX
+            || first_page == self.prev_page
+            || first_page == self.prev_page.wrapping_add(1);
+        self.prev_page = last_page;
+        let window = if !sequential || self.max_pages == 0 {
+            // 随机读，窗口复位，只批量读入本次要读的页
+            self.size = 0;
+            if first_page == last_page {
+                return None;
+            }
+            first_page..last_page + 1
+        } else if self.size == 0 || last_page >= self.start + self.size {
+            // 第一次顺序读，或者已经越过了当前窗口
+            // 连同本次要读的页一起批量读入
+            self.start = last_page + 1;
+            self.size = RA_INIT_PAGES.min(self.max_pages);
+            first_page..self.start + self.size
+        } else if last_page >= self.start + self.size / 2 {
+            // 读到了窗口的后半部分，预读下一个更大的窗口
+            self.start += self.size;
+            self.size = (self.size * 2).min(self.max_pages);
+            self.start..self.start + self.size
+        } else {
+            return None;
+        };
+        let window = window.start.min(nr_pages)..window.end.min(nr_pages);
+        if window.is_empty() {
+            None
+        } else {
+            Some(window)
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::mem::size_of;
+use spin::Mutex;
+
+use crate::{
+    fs::{
+        cache::PageCache, directory_tree::DirectoryTreeNode, file_trait::File, layout::Stat,
+        memfd::MemFd, SeekWhence, StatMode,
+    },
+    mm::UserBuffer,
+    syscall::errno::{EINVAL, EISDIR, EPERM},
+};
+
+/// `/dev/shm`目录。其中的文件都是memfd，内容只保存在内存中，不会写回磁盘，
+/// `shm_open()`打开的文件由此得到。子节点由目录树管理，这里只记录名字用于列出目录
+pub struct ShmDir {
+    /// 创建过的文件，被删除的文件在下次列出目录时移除
+    entries: Arc<Mutex<Vec<(String, Arc<MemFd>)>>>,
+    offset: Mutex<usize>,
+}
+
+impl ShmDir {
+    pub fn new() -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(Vec::new())),
+            offset: Mutex::new(0),
+        }
+    }
+}
+
+#[allow(unused)]
+impl File for ShmDir {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        Arc::new(Self {
+            entries: self.entries.clone(),
+            offset: Mutex::new(*self.offset.lock()),
+        })
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        EISDIR as usize
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        EISDIR as usize
+    }
+
+    fn r_ready(&self) -> bool {
+        true
+    }
+
+    fn w_ready(&self) -> bool {
+        true
+    }
+
+    fn read_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        EISDIR as usize
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        EISDIR as usize
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 1),
+            0,
+            StatMode::S_IFDIR.bits() | 0o1777,
+            2,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::Directory
+    }
+
+    fn info_dirtree_node(&self, dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        Arc::new(Self {
+            entries: self.entries.clone(),
+            offset: Mutex::new(0),
+        })
+    }
+
+    fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize> {
+        Ok(Vec::new())
+    }
+
+    /// 只能创建普通文件
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
+        if file_type != DiskInodeType::File {
+            return Err(EPERM);
+        }
+        let file = Arc::new(MemFd::new(false));
+        self.entries.lock().push((String::from(name), file.clone()));
+        Ok(file)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(EPERM)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Err(EPERM)
+    }
+
+    fn get_dirent(&self, count: usize) -> Vec<Dirent> {
+        const DT_REG: u8 = 8;
+        let mut entries = self.entries.lock();
+        entries.retain(|(_, file)| !file.is_unlinked());
+        let mut offset = self.offset.lock();
+        let dirents: Vec<Dirent> = entries
+            .iter()
+            .enumerate()
+            .skip(*offset)
+            .take(count / size_of::<Dirent>())
+            .map(|(idx, (name, file))| {
+                Dirent::new(
+                    file.get_stat().get_ino(),
+                    (idx + 1) as isize,
+                    DT_REG,
+                    name.as_str(),
+                )
+            })
+            .collect();
+        *offset += dirents.len();
+        dirents
+    }
+
+    fn lseek(&self, offset: isize, whence: SeekWhence) -> Result<usize, isize> {
+        let mut file_offset = self.offset.lock();
+        match whence {
+            SeekWhence::SEEK_SET if offset >= 0 => *file_offset = offset as usize,
+            SeekWhence::SEEK_CUR if offset == 0 => {}
+            _ => return Err(EINVAL),
+        }
+        Ok(*file_offset)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EISDIR)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        Err(EISDIR)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(&self, offset: usize) -> Result<Arc<Mutex<PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(&self) -> Result<Vec<Arc<Mutex<PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
@@ -0,0 +1,295 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::mem::size_of;
+use spin::Mutex;
+
+use crate::{
+    fs::{
+        directory_tree::DirectoryTreeNode,
+        file_trait::File,
+        layout::Stat,
+        poll::{signal_pending, PollWaiter},
+        StatMode,
+    },
+    mm::UserBuffer,
+    syscall::errno::{EAGAIN, EINTR, EINVAL, ENOTDIR, ESPIPE},
+    task::{current_task, Signals},
+};
+
+/// 与`O_NONBLOCK`相同
+pub const SFD_NONBLOCK: usize = 0o4000;
+/// 与`O_CLOEXEC`相同
+pub const SFD_CLOEXEC: usize = 0o2000000;
+
+/// 从signalfd读出的`struct signalfd_siginfo`，固定为128字节
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalfdSiginfo {
+    ssi_signo: u32,
+    ssi_errno: i32,
+    ssi_code: i32,
+    ssi_pid: u32,
+    ssi_uid: u32,
+    ssi_fd: i32,
+    ssi_tid: u32,
+    ssi_band: u32,
+    ssi_overrun: u32,
+    ssi_trapno: u32,
+    ssi_status: i32,
+    ssi_int: i32,
+    ssi_ptr: u64,
+    ssi_utime: u64,
+    ssi_stime: u64,
+    ssi_addr: u64,
+    ssi_addr_lsb: u16,
+    __pad2: u16,
+    ssi_syscall: i32,
+    ssi_call_addr: u64,
+    ssi_arch: u32,
+    __pad: [u8; 28],
+}
+
+impl SignalfdSiginfo {
+    /// 目前不记录信号的发送者，只填写信号编号
+    fn new(signo: usize) -> Self {
+        let mut info: Self = unsafe { core::mem::zeroed() };
+        info.ssi_signo = signo as u32;
+        info
+    }
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as *const u8, size_of::<Self>()) }
+    }
+}
+
+/// `signalfd4()`创建的信号文件。
+/// 读取时从当前任务的`sigpending`中取出属于`mask`的信号，
+/// 这些信号通常已被屏蔽，因此不会再以信号处理函数的方式递送。
+pub struct SignalFd {
+    mask: Mutex<Signals>,
+    nonblock: bool,
+}
+
+impl SignalFd {
+    pub fn new(mask: Signals, flags: usize) -> Self {
+        Self {
+            mask: Mutex::new(Self::sanitize(mask)),
+            nonblock: flags & SFD_NONBLOCK != 0,
+        }
+    }
+    /// SIGKILL与SIGSTOP不能通过signalfd接收，静默忽略
+    fn sanitize(mask: Signals) -> Signals {
+        mask.difference(Signals::SIGKILL | Signals::SIGSTOP)
+    }
+    pub fn set_mask(&self, mask: Signals) {
+        *self.mask.lock() = Self::sanitize(mask);
+    }
+    /// 从当前任务的待处理信号中取出至多`max`个属于`mask`的信号
+    fn dequeue(&self, max: usize) -> Vec<SignalfdSiginfo> {
+        let mask = *self.mask.lock();
+        let task = current_task().unwrap();
+        let mut inner = task.acquire_inner_lock();
+        let mut infos = Vec::new();
+        while infos.len() < max {
+            let signum = match (inner.sigpending & mask).peek_front() {
+                Some(signum) => signum,
+                None => break,
+            };
+            inner
+                .sigpending
+                .remove(Signals::from_signum(signum).unwrap());
+            infos.push(SignalfdSiginfo::new(signum));
+        }
+        infos
+    }
+    fn read_signals(&self, max: usize) -> Result<Vec<SignalfdSiginfo>, isize> {
+        // 发送信号时会唤醒处于可中断睡眠的目标任务，因此无需注册等待队列
+        let waiter = PollWaiter::new();
+        loop {
+            let infos = self.dequeue(max);
+            if !infos.is_empty() {
+                return Ok(infos);
+            }
+            if self.nonblock {
+                return Err(EAGAIN);
+            }
+            if signal_pending() {
+                return Err(EINTR);
+            }
+            waiter.sleep(None);
+        }
+    }
+}
+
+#[allow(unused)]
+impl File for SignalFd {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        todo!()
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        let max = buf.len() / size_of::<SignalfdSiginfo>();
+        if max == 0 {
+            return EINVAL as usize;
+        }
+        match self.read_signals(max) {
+            Ok(infos) => {
+                let mut read = 0;
+                for info in infos.iter() {
+                    let bytes = info.as_bytes();
+                    buf[read..read + bytes.len()].copy_from_slice(bytes);
+                    read += bytes.len();
+                }
+                read
+            }
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn r_ready(&self) -> bool {
+        let mask = *self.mask.lock();
+        let task = current_task().unwrap();
+        let inner = task.acquire_inner_lock();
+        !(inner.sigpending & mask).is_empty()
+    }
+
+    fn w_ready(&self) -> bool {
+        false
+    }
+
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        let max = buf.len() / size_of::<SignalfdSiginfo>();
+        if max == 0 {
+            return EINVAL as usize;
+        }
+        match self.read_signals(max) {
+            Ok(infos) => {
+                let mut read = 0;
+                for info in infos.iter() {
+                    read += buf.write_at(read, info.as_bytes());
+                }
+                read
+            }
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        EINVAL as usize
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 13),
+            1,
+            StatMode::S_IFREG.bits() | 0o600,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(&self, dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        todo!()
+    }
+
+    fn open_subfile(
+        &self,
+    ) -> Result<alloc::vec::Vec<(alloc::string::String, alloc::sync::Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> alloc::vec::Vec<Dirent> {
+        alloc::vec::Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        offset: usize,
+    ) -> Result<Arc<spin::Mutex<crate::fs::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(
+        &self,
+    ) -> Result<alloc::vec::Vec<Arc<spin::Mutex<crate::fs::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
@@ -1,15 +1,27 @@
-use alloc::{sync::Arc, vec::Vec};
+//! 交换区。
+//! 交换区由swapon在运行时启用，是预先分配好空间的交换文件，第一页是mkswap写入的Linux交换区头部。
+//! 交换文件通过文件在块设备上的区间直接读写，不经过页缓存，启用期间文件被固定，不能写入、截断或删除。
+//! 块设备目前没有分区表支持，唯一的磁盘上就是根文件系统，因此不支持交换分区。
+//! 可以同时启用多个交换区，换出时优先使用优先级高的交换区，优先级相同时选择较空的一个
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
+use core::{convert::TryInto, fmt::Write};
 use spin::Mutex;
 
-use crate::{config::PAGE_SIZE, drivers::BLOCK_DEVICE, hal::BLOCK_SZ};
+use crate::{
+    config::PAGE_SIZE,
+    drivers::block::{BlockDevice, BLOCK_DEVICE},
+    fs::directory_tree::DirectoryTreeNode,
+    hal::BLOCK_SZ,
+    syscall::errno::*,
+};
 
-use super::directory_tree::FILE_SYSTEM;
 use lazy_static::*;
 
 lazy_static! {
-    pub static ref SWAP_DEVICE: Mutex<Swap> = Mutex::new(Swap::new(16));
+    pub static ref SWAP_DEVICE: Mutex<Swap> = Mutex::new(Swap::new());
 }
 
+/// 换出的一页，释放时归还交换区中的页槽
 #[derive(Debug)]
 pub struct SwapTracker(pub usize);
 
@@ -19,32 +31,173 @@ impl Drop for SwapTracker {
     }
 }
 
-pub struct Swap {
+/// 换出页编号的高位是交换区编号，低位是交换区中的页槽，与Linux的`swp_entry_t`相同
+const SWP_TYPE_SHIFT: usize = 56;
+/// 同时启用的交换区数量上限
+const MAX_SWAPFILES: usize = 32;
+const BLK_PER_PG: usize = PAGE_SIZE / BLOCK_SZ;
+
+/// mkswap写在第一页末尾的签名
+const SWAP_MAGIC: &[u8] = b"SWAPSPACE2";
+/// 第一页中交换区头部各字段的位置，见Linux的`union swap_header`
+const SWAP_VERSION_OFFSET: usize = 1024;
+const SWAP_LAST_PAGE_OFFSET: usize = 1028;
+const SWAP_NR_BADPAGES_OFFSET: usize = 1032;
+const SWAP_BADPAGES_OFFSET: usize = 1536;
+
+/// 换出页所在的交换区编号
+pub fn swp_type(entry: usize) -> usize {
+    entry >> SWP_TYPE_SHIFT
+}
+
+fn swp_offset(entry: usize) -> usize {
+    entry & ((1 << SWP_TYPE_SHIFT) - 1)
+}
+
+/// 交换区的来源，即交换文件所在的设备与inode号，swapoff据此找到要关闭的交换区
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SwapSource(pub u32, pub usize);
+
+/// 交换区中一段在设备上连续的页槽
+struct SwapExtent {
+    start_slot: usize,
+    nr_slots: usize,
+    /// 第一个页槽在设备上的块号
+    start_block: usize,
+}
+
+pub struct SwapArea {
+    source: SwapSource,
+    /// swapon时给出的路径
+    name: String,
+    priority: isize,
+    /// 交换文件，启用期间保持固定
+    inode: Arc<DirectoryTreeNode>,
+    device: Arc<dyn BlockDevice>,
+    /// 按页槽排序。头部、坏页以及交换文件中跨越不连续区间的页槽不被任何区间覆盖
+    extents: Vec<SwapExtent>,
+    /// 已用的页槽，不可用的页槽始终置位
     bitmap: Vec<u64>,
-    block_ids: Vec<usize>,
+    /// 可用的页槽数
+    pages: usize,
+    /// 已用的页槽数
+    inuse: usize,
+    /// swapoff正在把页换回内存，不再分配页槽
+    draining: bool,
 }
-const BLK_PER_PG: usize = PAGE_SIZE / BLOCK_SZ;
-const SWAP_SIZE: usize = 1024 * 1024;
-impl Swap {
-    /// size: the number of megabytes in swap
-    pub fn new(size: usize) -> Self {
-        let bit = size * (SWAP_SIZE / PAGE_SIZE); // 1MiB = 4KiB*256
-        let vec_len = bit / usize::MAX.count_ones() as usize;
-        let mut bitmap = Vec::<u64>::with_capacity(vec_len);
-        bitmap.resize(bitmap.capacity(), 0);
-        let blocks = size * (SWAP_SIZE / BLOCK_SZ); // 1MiB = 512B * 2048
-        Self {
-            bitmap,
-            block_ids: FILE_SYSTEM.alloc_blocks(blocks),
+
+impl SwapArea {
+    /// 打开交换文件，`blocks`是文件在块设备上的区间，见`File::extents`
+    pub fn file(
+        name: String,
+        source: SwapSource,
+        inode: Arc<DirectoryTreeNode>,
+        blocks: Vec<(usize, usize)>,
+        size: usize,
+    ) -> Result<Self, isize> {
+        // 第`i`个页槽是文件的第`i`页，只有整页落在一个区间中的页槽可用
+        let mut extents: Vec<SwapExtent> = Vec::new();
+        let mut file_block = 0;
+        for (start_block, nr_blocks) in blocks {
+            let first = (file_block + BLK_PER_PG - 1) / BLK_PER_PG;
+            let last = (file_block + nr_blocks) / BLK_PER_PG;
+            if first < last {
+                let start_block = start_block + first * BLK_PER_PG - file_block;
+                match extents.last_mut() {
+                    Some(extent)
+                        if extent.start_slot + extent.nr_slots == first
+                            && extent.start_block + extent.nr_slots * BLK_PER_PG == start_block =>
+                    {
+                        extent.nr_slots += last - first
+                    }
+                    _ => extents.push(SwapExtent {
+                        start_slot: first,
+                        nr_slots: last - first,
+                        start_block,
+                    }),
+                }
+            }
+            file_block += nr_blocks;
         }
+        Self::new(
+            source,
+            name,
+            inode,
+            BLOCK_DEVICE.clone(),
+            extents,
+            size / PAGE_SIZE,
+        )
     }
-    fn read_page(block_ids: &[usize], buf: &mut [u8]) {
-        assert!(block_ids[0] + BLK_PER_PG - 1 == block_ids[BLK_PER_PG - 1]);
-        BLOCK_DEVICE.read_block(block_ids[0], buf);
+    /// 读取并检查交换区头部，`max_slots`是交换区大小允许的页槽数
+    fn new(
+        source: SwapSource,
+        name: String,
+        inode: Arc<DirectoryTreeNode>,
+        device: Arc<dyn BlockDevice>,
+        extents: Vec<SwapExtent>,
+        max_slots: usize,
+    ) -> Result<Self, isize> {
+        let mut area = Self {
+            source,
+            name,
+            priority: 0,
+            inode,
+            device,
+            extents,
+            bitmap: Vec::new(),
+            pages: 0,
+            inuse: 0,
+            draining: false,
+        };
+        let mut header = vec![0u8; PAGE_SIZE];
+        let block = area.slot_block(0).ok_or(EINVAL)?;
+        area.device.try_read_block(block, &mut header)?;
+        if &header[PAGE_SIZE - SWAP_MAGIC.len()..] != SWAP_MAGIC {
+            return Err(EINVAL);
+        }
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap()) as usize
+        };
+        if read_u32(SWAP_VERSION_OFFSET) != 1 {
+            return Err(EINVAL);
+        }
+        let slots = (read_u32(SWAP_LAST_PAGE_OFFSET) + 1).min(max_slots);
+        area.bitmap = vec![u64::MAX; (slots + 63) / 64];
+        for slot in 1..slots {
+            if area.slot_block(slot).is_some() {
+                area.clear_bit(slot);
+            }
+        }
+        let nr_badpages = read_u32(SWAP_NR_BADPAGES_OFFSET);
+        if SWAP_BADPAGES_OFFSET + nr_badpages * 4 > PAGE_SIZE - SWAP_MAGIC.len() {
+            return Err(EINVAL);
+        }
+        for i in 0..nr_badpages {
+            let slot = read_u32(SWAP_BADPAGES_OFFSET + i * 4);
+            if slot < slots {
+                area.set_bit(slot);
+            }
+        }
+        area.pages = area
+            .bitmap
+            .iter()
+            .map(|bits| bits.count_zeros() as usize)
+            .sum();
+        if area.pages == 0 {
+            return Err(EINVAL);
+        }
+        Ok(area)
     }
-    fn write_page(block_ids: &[usize], buf: &[u8]) {
-        assert!(block_ids[0] + (BLK_PER_PG - 1) == block_ids[BLK_PER_PG - 1]);
-        BLOCK_DEVICE.write_block(block_ids[0], buf);
+    /// 页槽在设备上的起始块号，不可用的页槽返回`None`
+    fn slot_block(&self, slot: usize) -> Option<usize> {
+        let idx = self
+            .extents
+            .partition_point(|extent| extent.start_slot + extent.nr_slots <= slot);
+        let extent = self.extents.get(idx)?;
+        if slot < extent.start_slot {
+            return None;
+        }
+        Some(extent.start_block + (slot - extent.start_slot) * BLK_PER_PG)
     }
     fn set_bit(&mut self, pos: usize) {
         self.bitmap[pos / 64] |= 1 << (pos % 64);
@@ -52,29 +205,151 @@ impl Swap {
     fn clear_bit(&mut self, pos: usize) {
         self.bitmap[pos / 64] &= !(1 << (pos % 64));
     }
-    fn alloc_page(&self) -> Option<usize> {
-        for (i, bit) in self.bitmap.iter().enumerate() {
-            if !*bit == 0 {
-                continue;
+    fn alloc_slot(&mut self) -> Option<usize> {
+        let slot = self
+            .bitmap
+            .iter()
+            .enumerate()
+            .find(|(_, bit)| !**bit != 0)
+            .map(|(i, bit)| i * 64 + (!*bit).trailing_zeros() as usize)?;
+        self.set_bit(slot);
+        self.inuse += 1;
+        Some(slot)
+    }
+    fn free_slot(&mut self, slot: usize) {
+        self.clear_bit(slot);
+        self.inuse -= 1;
+    }
+}
+
+pub struct Swap {
+    /// 以交换区编号为下标
+    areas: Vec<Option<SwapArea>>,
+    /// 没有指定优先级的交换区依次使用-1, -2, ...
+    least_priority: isize,
+}
+
+impl Swap {
+    pub fn new() -> Self {
+        Self {
+            areas: Vec::new(),
+            least_priority: 0,
+        }
+    }
+    /// 启用交换区，`priority`为`None`时使用比已有交换区都低的优先级
+    pub fn swapon(&mut self, mut area: SwapArea, priority: Option<isize>) -> Result<(), isize> {
+        if self
+            .areas
+            .iter()
+            .flatten()
+            .any(|other| other.source == area.source)
+        {
+            return Err(EBUSY);
+        }
+        let idx = match self.areas.iter().position(|area| area.is_none()) {
+            Some(idx) => idx,
+            None if self.areas.len() < MAX_SWAPFILES => {
+                self.areas.push(None);
+                self.areas.len() - 1
             }
-            return Some(i * 64 + (!*bit).trailing_zeros() as usize);
+            None => return Err(EPERM),
+        };
+        area.priority = match priority {
+            Some(priority) => priority,
+            None => {
+                self.least_priority -= 1;
+                self.least_priority
+            }
+        };
+        area.inode.pin_swapfile();
+        log::info!(
+            "[swapon] {}: {} KiB, priority {}",
+            area.name,
+            area.pages * PAGE_SIZE / 1024,
+            area.priority
+        );
+        self.areas[idx] = Some(area);
+        Ok(())
+    }
+    /// 开始关闭由`source`启用的交换区，返回交换区编号，之后不再向其中换出页
+    pub fn start_swapoff(&mut self, source: SwapSource) -> Result<usize, isize> {
+        let idx = self
+            .areas
+            .iter()
+            .position(|area| {
+                area.as_ref()
+                    .map_or(false, |area| area.source == source && !area.draining)
+            })
+            .ok_or(EINVAL)?;
+        self.areas[idx].as_mut().unwrap().draining = true;
+        Ok(idx)
+    }
+    /// 交换区中的页都已换回内存时移除交换区，否则恢复使用并返回`EBUSY`
+    pub fn finish_swapoff(&mut self, idx: usize, drained: bool) -> Result<(), isize> {
+        let area = self.areas[idx].as_mut().unwrap();
+        if !drained || area.inuse != 0 {
+            area.draining = false;
+            return Err(EBUSY);
         }
-        None
+        log::info!("[swapoff] {}", area.name);
+        area.inode.unpin_swapfile();
+        self.areas[idx] = None;
+        Ok(())
     }
-    fn get_block_ids(&self, swap_id: usize) -> &[usize] {
-        &self.block_ids[swap_id * BLK_PER_PG + 0..swap_id * BLK_PER_PG + BLK_PER_PG]
+    /// 可以换出的交换区：优先级最高，优先级相同时已用页槽最少
+    fn pick_area(&self) -> Option<usize> {
+        self.areas
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, area)| area.as_ref().map(|area| (idx, area)))
+            .filter(|(_, area)| !area.draining && area.inuse < area.pages)
+            .max_by(|(_, a), (_, b)| a.priority.cmp(&b.priority).then(b.inuse.cmp(&a.inuse)))
+            .map(|(idx, _)| idx)
     }
-    pub fn read(&mut self, swap_id: usize, buf: &mut [u8]) {
-        Self::read_page(self.get_block_ids(swap_id), buf);
+    pub fn read(&mut self, entry: usize, buf: &mut [u8]) {
+        let area = self.areas[swp_type(entry)].as_ref().unwrap();
+        let block = area.slot_block(swp_offset(entry)).unwrap();
+        area.device.read_block(block, buf);
     }
-    pub fn write(&mut self, buf: &[u8]) -> Arc<SwapTracker> {
-        let swap_id = self.alloc_page().unwrap();
-        Self::write_page(self.get_block_ids(swap_id), buf);
-        self.set_bit(swap_id);
-        Arc::new(SwapTracker(swap_id))
+    /// 把一页写入交换区，没有可用的交换区时返回`None`
+    pub fn write(&mut self, buf: &[u8]) -> Option<Arc<SwapTracker>> {
+        let idx = self.pick_area()?;
+        let area = self.areas[idx].as_mut().unwrap();
+        let slot = area.alloc_slot()?;
+        let block = area.slot_block(slot).unwrap();
+        area.device.write_block(block, buf);
+        Some(Arc::new(SwapTracker((idx << SWP_TYPE_SHIFT) | slot)))
     }
     #[inline(always)]
-    pub fn discard(&mut self, swap_id: usize) {
-        self.clear_bit(swap_id);
+    pub fn discard(&mut self, entry: usize) {
+        if let Some(area) = self.areas[swp_type(entry)].as_mut() {
+            area.free_slot(swp_offset(entry));
+        }
+    }
+    /// 所有交换区的总页数与已用页数
+    pub fn stats(&self) -> (usize, usize) {
+        self.areas
+            .iter()
+            .flatten()
+            .fold((0, 0), |(pages, inuse), area| {
+                (pages + area.pages, inuse + area.inuse)
+            })
+    }
+}
+
+/// `/proc/swaps`的内容，大小以KiB为单位
+pub fn swaps() -> String {
+    let mut s = String::from("Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority\n");
+    for area in SWAP_DEVICE.lock().areas.iter().flatten() {
+        writeln!(
+            s,
+            "{:<40}file\t{}\t\t{}\t\t{}",
+            area.name,
+            area.pages * PAGE_SIZE / 1024,
+            area.inuse * PAGE_SIZE / 1024,
+            area.priority
+        )
+        .unwrap();
     }
+    s
 }
@@ -0,0 +1,343 @@
+use crate::fs::{dirent::Dirent, DiskInodeType};
+use alloc::sync::{Arc, Weak};
+use spin::Mutex;
+
+use crate::{
+    fs::{
+        directory_tree::DirectoryTreeNode,
+        file_trait::File,
+        layout::Stat,
+        poll::{signal_pending, PollEvent, PollWaitQueue, PollWaiter},
+        StatMode,
+    },
+    mm::UserBuffer,
+    syscall::errno::{EAGAIN, EINTR, EINVAL, ENOTDIR, ESPIPE},
+    task::{add_timer, TimerCallback},
+    timer::{TimeSpec, NSEC_PER_SEC},
+};
+
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+pub const CLOCK_BOOTTIME: usize = 7;
+pub const CLOCK_REALTIME_ALARM: usize = 8;
+pub const CLOCK_BOOTTIME_ALARM: usize = 9;
+
+/// `it_value`是绝对时间
+pub const TFD_TIMER_ABSTIME: usize = 1;
+/// 实时时钟被修改时取消定时器，本内核的时钟不可修改，因此忽略
+pub const TFD_TIMER_CANCEL_ON_SET: usize = 2;
+/// 与`O_NONBLOCK`相同
+pub const TFD_NONBLOCK: usize = 0o4000;
+/// 与`O_CLOEXEC`相同
+pub const TFD_CLOEXEC: usize = 0o2000000;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ITimerSpec {
+    /// 周期，为0表示一次性定时器
+    pub it_interval: TimeSpec,
+    /// 距离下次到期的时间，为0表示停止定时器
+    pub it_value: TimeSpec,
+}
+
+impl ITimerSpec {
+    pub fn new() -> Self {
+        Self {
+            it_interval: TimeSpec::new(),
+            it_value: TimeSpec::new(),
+        }
+    }
+}
+
+struct TimerFdInner {
+    /// 上次读取以来到期的次数
+    expirations: u64,
+    /// 下次到期时间，`None`表示定时器未启动
+    deadline: Option<TimeSpec>,
+    interval: TimeSpec,
+    /// 挂在全局超时等待队列上的回调，替换或释放它即取消旧的定时器
+    timer: Option<Arc<TimerCallback>>,
+}
+
+/// `timerfd_create()`创建的定时器文件。
+/// 定时器挂在`TIMEOUT_WAITQUEUE`上，到期时累加到期次数并唤醒读者。
+/// 本内核所有时钟都以启动时间为基准，各种`clockid`的行为相同。
+pub struct TimerFd {
+    clockid: usize,
+    nonblock: bool,
+    inner: Arc<Mutex<TimerFdInner>>,
+    wait_queue: Arc<PollWaitQueue>,
+}
+
+impl TimerFd {
+    pub fn new(clockid: usize, flags: usize) -> Self {
+        Self {
+            clockid,
+            nonblock: flags & TFD_NONBLOCK != 0,
+            inner: Arc::new(Mutex::new(TimerFdInner {
+                expirations: 0,
+                deadline: None,
+                interval: TimeSpec::new(),
+                timer: None,
+            })),
+            wait_queue: Arc::new(PollWaitQueue::new()),
+        }
+    }
+    pub fn clockid(&self) -> usize {
+        self.clockid
+    }
+    /// 定时器到期时由`do_wake_expired()`调用
+    fn expire(inner: &Weak<Mutex<TimerFdInner>>, wait_queue: &Weak<PollWaitQueue>, now: TimeSpec) {
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        let mut lock = inner.lock();
+        let deadline = match lock.deadline {
+            Some(deadline) if deadline <= now => deadline,
+            // 定时器已被重新设置
+            _ => return,
+        };
+        if lock.interval.is_zero() {
+            lock.expirations += 1;
+            lock.deadline = None;
+        } else {
+            // 错过的周期一并计入
+            let interval = lock.interval.to_ns();
+            let periods = (now - deadline).to_ns() / interval + 1;
+            lock.expirations += periods as u64;
+            let next = TimeSpec::from_ns(deadline.to_ns() + periods * interval);
+            lock.deadline = Some(next);
+            if let Some(timer) = lock.timer.as_ref() {
+                add_timer(timer, next);
+            }
+        }
+        drop(lock);
+        if let Some(wait_queue) = wait_queue.upgrade() {
+            wait_queue.notify(PollEvent::POLLIN | PollEvent::POLLRDNORM);
+        }
+    }
+    /// 返回定时器当前的剩余时间与周期
+    pub fn gettime(&self) -> ITimerSpec {
+        let inner = self.inner.lock();
+        ITimerSpec {
+            it_interval: inner.interval,
+            it_value: match inner.deadline {
+                // 已到期但回调尚未执行时，返回一个极小的剩余时间以表明定时器仍在运行
+                Some(deadline) => (deadline - TimeSpec::now()).max(TimeSpec::from_ns(1)),
+                None => TimeSpec::new(),
+            },
+        }
+    }
+    /// 启动或停止定时器，返回原先的设置
+    pub fn settime(&self, flags: usize, new: ITimerSpec) -> Result<ITimerSpec, isize> {
+        if new.it_value.tv_nsec >= NSEC_PER_SEC || new.it_interval.tv_nsec >= NSEC_PER_SEC {
+            return Err(EINVAL);
+        }
+        let old = self.gettime();
+        let mut inner = self.inner.lock();
+        inner.expirations = 0;
+        // 释放旧的回调即取消旧的定时器
+        inner.timer = None;
+        inner.interval = new.it_interval;
+        if new.it_value.is_zero() {
+            inner.deadline = None;
+            return Ok(old);
+        }
+        let deadline = if flags & TFD_TIMER_ABSTIME != 0 {
+            new.it_value
+        } else {
+            TimeSpec::now() + new.it_value
+        };
+        inner.deadline = Some(deadline);
+        let timer: Arc<TimerCallback> = {
+            let inner = Arc::downgrade(&self.inner);
+            let wait_queue = Arc::downgrade(&self.wait_queue);
+            Arc::new(move |now| Self::expire(&inner, &wait_queue, now))
+        };
+        add_timer(&timer, deadline);
+        inner.timer = Some(timer);
+        Ok(old)
+    }
+    /// 等待定时器至少到期一次，返回到期次数并清零
+    fn read_expirations(&self) -> Result<u64, isize> {
+        let mut waiter = PollWaiter::new();
+        waiter.register_queue(Some(self.wait_queue.clone()), PollEvent::POLLIN);
+        loop {
+            {
+                let mut inner = self.inner.lock();
+                if inner.expirations > 0 {
+                    let expirations = inner.expirations;
+                    inner.expirations = 0;
+                    return Ok(expirations);
+                }
+            }
+            if self.nonblock {
+                return Err(EAGAIN);
+            }
+            if signal_pending() {
+                return Err(EINTR);
+            }
+            waiter.sleep(None);
+        }
+    }
+}
+
+#[allow(unused)]
+impl File for TimerFd {
+    fn deep_clone(&self) -> Arc<dyn File> {
+        todo!()
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, offset: Option<&mut usize>, buf: &mut [u8]) -> usize {
+        if buf.len() < 8 {
+            return EINVAL as usize;
+        }
+        match self.read_expirations() {
+            Ok(expirations) => {
+                buf[..8].copy_from_slice(&expirations.to_ne_bytes());
+                8
+            }
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn write(&self, offset: Option<&mut usize>, buf: &[u8]) -> usize {
+        EINVAL as usize
+    }
+
+    fn r_ready(&self) -> bool {
+        self.inner.lock().expirations > 0
+    }
+
+    fn w_ready(&self) -> bool {
+        false
+    }
+
+    fn read_user(&self, offset: Option<usize>, mut buf: UserBuffer) -> usize {
+        if buf.len() < 8 {
+            return EINVAL as usize;
+        }
+        match self.read_expirations() {
+            Ok(expirations) => buf.write(&expirations.to_ne_bytes()),
+            Err(errno) => errno as usize,
+        }
+    }
+
+    fn write_user(&self, offset: Option<usize>, buf: UserBuffer) -> usize {
+        EINVAL as usize
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn get_stat(&self) -> Stat {
+        Stat::new(
+            crate::makedev!(0, 13),
+            1,
+            StatMode::S_IFREG.bits() | 0o600,
+            1,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    fn get_file_type(&self) -> DiskInodeType {
+        DiskInodeType::File
+    }
+
+    fn info_dirtree_node(&self, dirnode_ptr: Weak<DirectoryTreeNode>) {}
+
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>> {
+        None
+    }
+
+    fn open(&self, flags: crate::fs::layout::OpenFlags, special_use: bool) -> Arc<dyn File> {
+        todo!()
+    }
+
+    fn open_subfile(
+        &self,
+    ) -> Result<alloc::vec::Vec<(alloc::string::String, alloc::sync::Arc<dyn File>)>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        file_type: DiskInodeType,
+        mode: u32,
+    ) -> Result<Arc<dyn File>, isize> {
+        Err(ENOTDIR)
+    }
+
+    fn link_child(&self, name: &str, child: &Self) -> Result<(), isize>
+    where
+        Self: Sized,
+    {
+        Err(ENOTDIR)
+    }
+
+    fn unlink(&self, delete: bool) -> Result<(), isize> {
+        Ok(())
+    }
+
+    fn get_dirent(&self, count: usize) -> alloc::vec::Vec<Dirent> {
+        alloc::vec::Vec::new()
+    }
+
+    fn lseek(&self, offset: isize, whence: crate::fs::SeekWhence) -> Result<usize, isize> {
+        Err(ESPIPE)
+    }
+
+    fn modify_size(&self, diff: isize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn truncate_size(&self, new_size: usize) -> Result<(), isize> {
+        Err(EINVAL)
+    }
+
+    fn set_timestamp(&self, ctime: Option<usize>, atime: Option<usize>, mtime: Option<usize>) {}
+
+    fn get_single_cache(
+        &self,
+        offset: usize,
+    ) -> Result<Arc<spin::Mutex<crate::fs::PageCache>>, ()> {
+        Err(())
+    }
+
+    fn get_all_caches(
+        &self,
+    ) -> Result<alloc::vec::Vec<Arc<spin::Mutex<crate::fs::PageCache>>>, ()> {
+        Err(())
+    }
+
+    fn oom(&self) -> usize {
+        0
+    }
+
+    fn hang_up(&self) -> bool {
+        false
+    }
+
+    fn poll_queue(&self) -> Option<Arc<PollWaitQueue>> {
+        Some(self.wait_queue.clone())
+    }
+
+    fn fcntl(&self, cmd: u32, arg: u32) -> isize {
+        0
+    }
+}
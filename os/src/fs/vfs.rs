@@ -40,6 +40,9 @@ pub trait VFS: DowncastSync {
     fn alloc_blocks(&self, blocks: usize) -> Vec<usize>;
 
     fn get_filesystem_type(&self) -> FS_Type;
+
+    // 将文件系统自身的元数据（块缓存）写回磁盘
+    fn sync(&self);
 }
 impl_downcast!(sync VFS);
 
@@ -0,0 +1,186 @@
+use super::directory_tree::{DirectoryTreeNode, FILE_SYSTEM};
+use crate::drivers::BLOCK_DEVICE;
+use crate::timer::get_time_ms;
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use lazy_static::*;
+use spin::Mutex;
+
+/// 脏数据的最长存活时间（毫秒），超过该时间的脏文件会在下一次回写时被刷回磁盘
+pub const DIRTY_EXPIRE_MS: usize = 30_000;
+/// 两次周期性回写之间的最小间隔（毫秒）
+pub const WRITEBACK_INTERVAL_MS: usize = 5_000;
+/// 脏页占（空闲页 + 脏页）的百分比上限，超过后立即回写全部脏文件
+pub const DIRTY_RATIO: usize = 10;
+/// `writeback_run`每次最多回写的文件数，调度循环回写完一批后先运行下一个任务
+pub const WRITEBACK_BATCH: usize = 8;
+
+/// 当前页缓存中脏页的数量
+static NR_DIRTY_PAGES: AtomicUsize = AtomicUsize::new(0);
+/// `writeback_tick`标记需要回写的时间（毫秒），为0时没有待进行的回写
+static WRITEBACK_DUE: AtomicUsize = AtomicUsize::new(0);
+/// 是否有人正在进行`writeback_run`
+static WRITEBACK_RUNNING: AtomicBool = AtomicBool::new(false);
+
+struct DirtyInode {
+    /// 文件所在文件系统的编号
+    fs_id: usize,
+    /// 持有强引用，保证目录树节点在回写之前不会被释放
+    node: Arc<DirectoryTreeNode>,
+    /// 第一次变脏的时间（毫秒）
+    dirtied_at: usize,
+}
+
+lazy_static! {
+    /// 脏文件表，以目录树节点地址为键
+    static ref DIRTY_INODES: Mutex<BTreeMap<usize, DirtyInode>> = Mutex::new(BTreeMap::new());
+    /// 上一次标记周期性回写的时间（毫秒）
+    static ref LAST_WRITEBACK: Mutex<usize> = Mutex::new(0);
+}
+
+/// 页缓存由干净变脏时调用
+pub fn account_page_dirtied() {
+    NR_DIRTY_PAGES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 脏页被写回或被丢弃时调用
+pub fn account_page_cleaned() {
+    NR_DIRTY_PAGES.fetch_sub(1, Ordering::Relaxed);
+}
+
+pub fn nr_dirty_pages() -> usize {
+    NR_DIRTY_PAGES.load(Ordering::Relaxed)
+}
+
+/// 把一个文件登记为脏文件，已经登记过的文件保留最早的变脏时间
+pub fn mark_inode_dirty(node: &Arc<DirectoryTreeNode>) {
+    let key = Arc::as_ptr(node) as usize;
+    DIRTY_INODES
+        .lock()
+        .entry(key)
+        .or_insert_with(|| DirtyInode {
+            fs_id: node.fs_id(),
+            node: node.clone(),
+            dirtied_at: get_time_ms(),
+        });
+}
+
+/// 脏页是否超过了`DIRTY_RATIO`
+fn over_dirty_ratio() -> bool {
+    let dirty = nr_dirty_pages();
+    dirty * 100 > (crate::mm::unallocated_frames() + dirty) * DIRTY_RATIO
+}
+
+/// 从脏文件表中取出至多`max`个满足条件的文件
+fn take_dirty_inodes<F>(max: usize, filter: F) -> Vec<Arc<DirectoryTreeNode>>
+where
+    F: Fn(&DirtyInode) -> bool,
+{
+    let mut lock = DIRTY_INODES.lock();
+    let keys: Vec<usize> = lock
+        .iter()
+        .filter(|(_, inode)| filter(inode))
+        .map(|(key, _)| *key)
+        .take(max)
+        .collect();
+    keys.iter()
+        .map(|key| lock.remove(key).unwrap().node)
+        .collect()
+}
+
+/// 回写一组文件，先写数据和文件自身的元数据，最后写文件系统的元数据
+fn writeback_inodes(inodes: Vec<Arc<DirectoryTreeNode>>) {
+    for node in inodes {
+        if let Err(errno) = node.file.sync(false) {
+            log::warn!("[writeback] failed to sync {}, errno: {}", node.name, errno);
+        }
+    }
+    FILE_SYSTEM.sync();
+    // 数据落到设备的写缓存之后还需要刷到持久存储上
+    if let Err(errno) = BLOCK_DEVICE.flush() {
+        log::warn!("[writeback] failed to flush block device, errno: {}", errno);
+    }
+}
+
+/// 周期性回写的检查，由时钟中断调用。
+/// 这里只判断是否该回写并做标记，磁盘I/O由`writeback_run`在中断处理之外完成
+/// + 距离上次回写不足`WRITEBACK_INTERVAL_MS`且脏页未超过`DIRTY_RATIO`时直接返回
+/// + 否则标记需要回写
+pub fn writeback_tick() {
+    if WRITEBACK_DUE.load(Ordering::Relaxed) != 0 {
+        return;
+    }
+    let now = get_time_ms();
+    let mut last = match LAST_WRITEBACK.try_lock() {
+        Some(last) => last,
+        None => return,
+    };
+    if now.saturating_sub(*last) < WRITEBACK_INTERVAL_MS && !over_dirty_ratio() {
+        return;
+    }
+    *last = now;
+    WRITEBACK_DUE.store(now.max(1), Ordering::Relaxed);
+}
+
+/// 进行`writeback_tick`标记的回写，由调度循环在每次任务切换之后以及空闲时调用，
+/// 每次至多回写`WRITEBACK_BATCH`个文件。
+/// 回写所有变脏时间超过`DIRTY_EXPIRE_MS`的文件，脏页过多时回写全部脏文件，
+/// 一次没有写完时保留标记，下次调用继续
+pub fn writeback_run() {
+    let due = WRITEBACK_DUE.swap(0, Ordering::Relaxed);
+    if due == 0 {
+        return;
+    }
+    // 已经有人在回写，标记留给它之后的调用
+    if WRITEBACK_RUNNING.swap(true, Ordering::Acquire) {
+        WRITEBACK_DUE.store(due, Ordering::Relaxed);
+        return;
+    }
+    let now = get_time_ms();
+    let over_ratio = over_dirty_ratio();
+    let inodes = take_dirty_inodes(WRITEBACK_BATCH, |inode| {
+        over_ratio || now.saturating_sub(inode.dirtied_at) >= DIRTY_EXPIRE_MS
+    });
+    if inodes.len() == WRITEBACK_BATCH {
+        WRITEBACK_DUE.store(due, Ordering::Relaxed);
+    }
+    if !inodes.is_empty() {
+        log::debug!(
+            "[writeback] flushing {} inodes, dirty pages: {}",
+            inodes.len(),
+            nr_dirty_pages()
+        );
+        writeback_inodes(inodes);
+    }
+    WRITEBACK_RUNNING.store(false, Ordering::Release);
+}
+
+/// 由写文件的任务在写入之后调用。
+/// 标记的回写超过`WRITEBACK_INTERVAL_MS`仍未被调度循环完成，
+/// 或者脏页超过`DIRTY_RATIO`时，由产生脏数据的任务自己完成一批被标记的回写
+pub fn balance_dirty_pages() {
+    let due = WRITEBACK_DUE.load(Ordering::Relaxed);
+    if due != 0
+        && (get_time_ms().saturating_sub(due) >= WRITEBACK_INTERVAL_MS || over_dirty_ratio())
+    {
+        writeback_run();
+    }
+}
+
+/// 回写`fs_id`对应文件系统的全部脏数据，用于`syncfs`
+pub fn sync_fs(fs_id: usize) {
+    writeback_inodes(take_dirty_inodes(usize::MAX, |inode| inode.fs_id == fs_id));
+}
+
+/// 回写所有文件系统的全部脏数据，用于`sync`
+pub fn sync_all() {
+    writeback_inodes(take_dirty_inodes(usize::MAX, |_| true));
+}
+
+/// 文件被单独同步（fsync）后，将其移出脏文件表
+pub fn clear_inode_dirty(node: &DirectoryTreeNode) {
+    let key = node as *const DirectoryTreeNode as usize;
+    // 在释放锁之后再丢弃节点的引用
+    let inode = DIRTY_INODES.lock().remove(&key);
+    drop(inode);
+}
@@ -1,6 +1,9 @@
 use bit_field::BitField;
 
+use super::efi::{read_u32, read_u64, read_u8};
+use crate::config::PAGE_SIZE;
 use crate::hal::arch::board::ACPI_BASE;
+use crate::hal::bootinfo::BootInfo;
 
 const PM1_CNT_ADDR: usize = ACPI_BASE + 0x14;
 
@@ -45,3 +48,58 @@ pub enum SleepType {
     /// Soft off，只有唤醒电路上电，“软关机”
     S5 = 0b111,
 }
+
+/// RSDP的签名“RSD PTR ”
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+/// 系统描述表的公共头部长度
+const SDT_HEADER_SIZE: usize = 36;
+
+/// 系统描述表的签名
+unsafe fn signature(table: usize) -> [u8; 4] {
+    read_u32(table).to_le_bytes()
+}
+
+/// MCFG：头部之后保留8字节，然后是每个PCI段的配置空间基址、段号与总线号范围
+unsafe fn scan_mcfg(mcfg: usize, info: &mut BootInfo) {
+    let len = read_u32(mcfg + 4) as usize;
+    for entry in (mcfg + SDT_HEADER_SIZE + 8..mcfg + len).step_by(16) {
+        let base = read_u64(entry);
+        let end_bus = read_u8(entry + 11) as usize;
+        // 基址对应0号总线，每条总线占用1MiB
+        info.set_pci_ecam(base, (end_bus + 1) << 20);
+    }
+}
+
+/// SPCR：偏移40处是串口寄存器的通用地址结构，地址空间为0表示内存映射
+unsafe fn scan_spcr(spcr: usize, info: &mut BootInfo) {
+    let address = read_u64(spcr + 44);
+    if read_u8(spcr + 40) == 0 && address != 0 {
+        info.set_uart(address, PAGE_SIZE);
+    }
+}
+
+/// 从RSDP找到XSDT（ACPI 1.0时是RSDT），在其中查找MCFG与SPCR
+pub unsafe fn scan(rsdp: usize, info: &mut BootInfo) {
+    if read_u64(rsdp).to_le_bytes() != *RSDP_SIGNATURE {
+        return;
+    }
+    let xsdt = read_u64(rsdp + 24);
+    let (sdt, entry_size) = if read_u8(rsdp + 15) >= 2 && xsdt != 0 {
+        (xsdt, 8)
+    } else {
+        (read_u32(rsdp + 16) as usize, 4)
+    };
+    let len = read_u32(sdt + 4) as usize;
+    for entry in (sdt + SDT_HEADER_SIZE..sdt + len).step_by(entry_size) {
+        let table = if entry_size == 8 {
+            read_u64(entry)
+        } else {
+            read_u32(entry) as usize
+        };
+        match &signature(table) {
+            b"MCFG" => scan_mcfg(table, info),
+            b"SPCR" => scan_spcr(table, info),
+            _ => {}
+        }
+    }
+}
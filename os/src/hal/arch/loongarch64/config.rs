@@ -1,5 +1,5 @@
 // Sizes
-/// 内存大小，只有256MB？
+/// 没有EFI内存表时使用的内存大小
 pub const MEMORY_SIZE: usize = 0x1000_0000;
 pub const USER_STACK_SIZE: usize = PAGE_SIZE * 40;
 pub const USER_HEAP_SIZE: usize = PAGE_SIZE * 20;
@@ -10,6 +10,9 @@ pub const PAGE_SIZE_BITS: usize = PAGE_SIZE.trailing_zeros() as usize;
 pub const PTE_WIDTH: usize = 8;
 pub const PTE_WIDTH_BITS: usize = PTE_WIDTH.trailing_zeros() as usize;
 pub const DIR_WIDTH: usize = PAGE_SIZE_BITS - PTE_WIDTH_BITS;
+/// 大页（由二级目录项直接映射）包含的页数
+pub const HUGE_PAGE_PAGES: usize = 1 << DIR_WIDTH;
+pub const HUGE_PAGE_SIZE: usize = PAGE_SIZE * HUGE_PAGE_PAGES;
 
 #[cfg(debug_assertions)]
 pub const KSTACK_PG_NUM_SHIFT: usize = 16usize.trailing_zeros() as usize;
@@ -17,6 +20,7 @@ pub const KSTACK_PG_NUM_SHIFT: usize = 16usize.trailing_zeros() as usize;
 pub const KSTACK_PG_NUM_SHIFT: usize = 2usize.trailing_zeros() as usize;
 
 pub const KERNEL_STACK_SIZE: usize = PAGE_SIZE << KSTACK_PG_NUM_SHIFT;
+/// 启动时划出的内核堆的上限，实际大小为物理内存的1/4，用完后向帧分配器扩展
 pub const KERNEL_HEAP_SIZE: usize = PAGE_SIZE * 0x3000;
 
 // Addresses
@@ -67,8 +71,8 @@ pub const MMAP_BASE: usize = 0xFFFF_FF80_0000_0000;
 pub const MMAP_END: usize = 0xFFFF_FFFF_FFFF_0000;
 pub const SKIP_NUM: usize = 1;
 
-// 0x98000000
-pub const DISK_IMAGE_BASE: usize = 0x800_0000 + MEMORY_START;
+/// 没有initrd时内存盘的大小
+pub const RAM_DISK_SIZE: usize = 0x800_0000;
 // 256
 pub const BUFFER_CACHE_NUM: usize = 256 * 1024 * 1024 / 2048 * 4 / 2048;
 
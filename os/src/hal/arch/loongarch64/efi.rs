@@ -0,0 +1,133 @@
+//! EFI系统表。
+//! 按照LoongArch的启动约定，UEFI固件或者QEMU直接启动内核时在`a0`中传入非0值，
+//! 在`a1`中传入命令行，在`a2`中传入EFI系统表的地址。
+//! 内存表、initrd、设备树与ACPI表都通过系统表中的配置表找到
+use super::acpi;
+use crate::config::PALEN;
+use crate::hal::bootinfo::BootInfo;
+use crate::hal::fdt::Fdt;
+
+/// 系统表头部的签名“IBI SYST”
+const EFI_SYSTEM_TABLE_SIGNATURE: u64 = 0x5453_5953_2049_4249;
+/// 系统表中配置表项数与配置表地址的偏移
+const NR_TABLES_OFFSET: usize = 104;
+const TABLES_OFFSET: usize = 112;
+/// 配置表项：16字节的GUID与8字节的地址
+const CONFIG_TABLE_SIZE: usize = 24;
+
+/// 按照GUID在内存中的字节顺序存放
+#[derive(PartialEq, Eq)]
+struct Guid([u8; 16]);
+
+impl Guid {
+    const fn new(a: u32, b: u16, c: u16, d: [u8; 8]) -> Self {
+        let a = a.to_le_bytes();
+        let b = b.to_le_bytes();
+        let c = c.to_le_bytes();
+        Self([
+            a[0], a[1], a[2], a[3], b[0], b[1], c[0], c[1], d[0], d[1], d[2], d[3], d[4], d[5],
+            d[6], d[7],
+        ])
+    }
+}
+
+const ACPI_20_TABLE_GUID: Guid = Guid::new(
+    0x8868_e871,
+    0xe4f1,
+    0x11d3,
+    [0xbc, 0x22, 0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81],
+);
+const DEVICE_TREE_GUID: Guid = Guid::new(
+    0xb1b6_21d5,
+    0xf19c,
+    0x41a5,
+    [0x83, 0x0b, 0xd9, 0x15, 0x2c, 0x69, 0xaa, 0xe0],
+);
+/// Linux的EFI stub退出启动服务前保存的内存表
+const LINUX_EFI_BOOT_MEMMAP_GUID: Guid = Guid::new(
+    0x800f_683f,
+    0xd08b,
+    0x423a,
+    [0xa2, 0x93, 0x96, 0x5c, 0x3c, 0x6f, 0xe2, 0xb4],
+);
+const LINUX_EFI_INITRD_MEDIA_GUID: Guid = Guid::new(
+    0x5568_e427,
+    0x68fc,
+    0x4f3d,
+    [0xac, 0x74, 0xca, 0x55, 0x52, 0x31, 0xcc, 0x68],
+);
+
+/// 内存表中可以交给内核使用的类型：
+/// EfiLoaderCode、EfiLoaderData、EfiBootServicesCode、EfiBootServicesData、EfiConventionalMemory
+const EFI_USABLE_MEMORY: [u32; 5] = [1, 2, 3, 4, 7];
+
+/// 固件传来的地址可能带有直接映射窗口的段号，只保留物理地址部分
+pub fn phys(addr: usize) -> usize {
+    addr & ((1 << PALEN) - 1)
+}
+
+pub unsafe fn read_u8(pa: usize) -> u8 {
+    core::ptr::read_volatile(phys(pa) as *const u8)
+}
+
+pub unsafe fn read_u32(pa: usize) -> u32 {
+    core::ptr::read_unaligned(phys(pa) as *const u32)
+}
+
+pub unsafe fn read_u64(pa: usize) -> usize {
+    core::ptr::read_unaligned(phys(pa) as *const u64) as usize
+}
+
+/// 读取`efi_boot_memmap`：
+/// `map_size`、`desc_size`、`desc_ver`、`map_key`、`buff_size`之后是内存描述符数组
+unsafe fn scan_memmap(memmap: usize, info: &mut BootInfo) {
+    let map_size = read_u64(memmap);
+    let desc_size = read_u64(memmap + 8);
+    if desc_size < 40 {
+        return;
+    }
+    let map = memmap + 40;
+    for desc in (map..map + map_size).step_by(desc_size) {
+        let ty = read_u32(desc);
+        let phys_addr = read_u64(desc + 8);
+        let num_pages = read_u64(desc + 24);
+        // 内存表中的页总是4KiB
+        if EFI_USABLE_MEMORY.contains(&ty) {
+            info.add_memory(phys_addr, num_pages << 12);
+        }
+    }
+}
+
+/// 遍历系统表中的配置表，不是合法的系统表时返回`false`
+pub unsafe fn scan(systab: usize, info: &mut BootInfo) -> bool {
+    if systab == 0 || systab % 8 != 0 || read_u64(systab) as u64 != EFI_SYSTEM_TABLE_SIGNATURE {
+        return false;
+    }
+    let nr_tables = read_u64(systab + NR_TABLES_OFFSET);
+    let tables = read_u64(systab + TABLES_OFFSET);
+    let mut rsdp = None;
+    let mut fdt = None;
+    for i in 0..nr_tables {
+        let entry = tables + i * CONFIG_TABLE_SIZE;
+        let guid = Guid(core::ptr::read_unaligned(phys(entry) as *const [u8; 16]));
+        let table = phys(read_u64(entry + 16));
+        if guid == LINUX_EFI_BOOT_MEMMAP_GUID {
+            scan_memmap(table, info);
+        } else if guid == LINUX_EFI_INITRD_MEDIA_GUID {
+            let base = read_u64(table);
+            info.set_initrd(base, base + read_u64(table + 8));
+        } else if guid == ACPI_20_TABLE_GUID {
+            rsdp = Some(table);
+        } else if guid == DEVICE_TREE_GUID {
+            fdt = Fdt::from_ptr(table);
+        }
+    }
+    // ACPI表中的设备优先于设备树
+    if let Some(rsdp) = rsdp {
+        acpi::scan(rsdp, info);
+    }
+    if let Some(fdt) = fdt {
+        fdt.scan(info);
+    }
+    true
+}
@@ -1,18 +1,23 @@
 use super::{tlb::tlb_invalidate, tlb_global_invalidate};
 use crate::{
     config::{
-        MEMORY_HIGH_BASE, MEMORY_HIGH_BASE_VPN, MEMORY_SIZE, PAGE_SIZE, PAGE_SIZE_BITS, PALEN,
-        VA_MASK, VPN_SEG_MASK,
+        MEMORY_HIGH_BASE, MEMORY_HIGH_BASE_VPN, PAGE_SIZE_BITS, PALEN, VA_MASK, VPN_SEG_MASK,
     },
+    hal::boot_info,
     mm::{address::*, frame_alloc, FrameTracker, MapPermission, PageTable},
 };
 use _core::convert::TryFrom;
 use alloc::{sync::Arc, vec::Vec};
 use bitflags::*;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
 use log::trace;
-// todo: 因之前默认vpn从0开始，所以DIRTY数组相当于vpn从零到size，但是移到高地址启动后，DIRTY位有偏移
-// todo: 相当于低位无效，这里粗暴的增大整个数组长度
-static mut DIRTY: [bool; MEMORY_SIZE*10 / PAGE_SIZE] = [false; MEMORY_SIZE*10 / PAGE_SIZE];
+lazy_static! {
+    /// 直接映射窗口中的页没有页表项，脏位记录在这里，下标见`BootInfo::ram_page_index`
+    static ref DIRTY: Vec<AtomicBool> = (0..boot_info().ram_pages())
+        .map(|_| AtomicBool::new(false))
+        .collect();
+}
 use super::register::MemoryAccessType;
 
 bitflags! {
@@ -35,6 +40,8 @@ bitflags! {
         const MAT_WUC = 2 << 4;
         /// Global Bit (Basic PTE)
         const G = 1 << 6;
+        /// Huge Page Bit, the directory entry maps a huge page (Huge PTE)
+        const H = 1 << 6;
         /// Physical Bit, whether the physical page exists
         const P = 1 << 7;
         /// Writable Bit
@@ -145,6 +152,11 @@ impl LAFlexPageTableEntry {
     pub fn revoke_execute(&mut self) {
         self.bits |= LAPTEFlagBits::NX.bits() as usize;
     }
+    /// 目录项中映射大页的页表项
+    #[inline(always)]
+    pub fn is_huge(&self) -> bool {
+        self.flags().contains(LAPTEFlagBits::V | LAPTEFlagBits::H)
+    }
     #[inline(always)]
     pub fn set_permission(&mut self, flags: MapPermission) {
         if flags.contains(MapPermission::R) {
@@ -216,6 +228,7 @@ impl LAFlexPageTable {
         }
         ppn = PhysAddr::from((pte.ppn().0 << 12) | MEMORY_HIGH_BASE).floor();
         pte = &mut ppn.get_pte_array::<LAFlexPageTableEntry>()[idxs[1]];
+        assert!(!pte.is_huge(), "vpn {:?} is in a huge page", vpn);
         if !pte.is_valid() {
             let frame = frame_alloc().unwrap();
             *pte = LAFlexPageTableEntry::new(frame.ppn, LAPTEFlagBits::V);
@@ -225,9 +238,9 @@ impl LAFlexPageTable {
         pte = &mut ppn.get_pte_array::<LAFlexPageTableEntry>()[idxs[2]];
         Some(pte)
     }
-    /// Find and return reference the page table entry denoted by `vpn`, `None` if not found or invalid.
-    fn find_pte_refmut(&self, vpn: VirtPageNum) -> Option<&mut LAFlexPageTableEntry> {
-        //trace!("[find_pte_refmut] {:?}", vpn);
+    /// Find the leaf page table entry mapping `vpn`, which is the directory entry for huge pages.
+    /// Returns the entry and the offset of `vpn` in the page it maps.
+    fn find_leaf(&self, vpn: VirtPageNum) -> Option<(&mut LAFlexPageTableEntry, usize)> {
         let idxs = vpn.indexes::<3>();
         let mut ppn = self.get_root_ppn();
         let mut pte = &mut ppn.get_pte_array::<LAFlexPageTableEntry>()[idxs[0]];
@@ -239,9 +252,32 @@ impl LAFlexPageTable {
         if !pte.is_valid() {
             return None;
         }
+        if pte.is_huge() {
+            return Some((pte, idxs[2]));
+        }
         ppn = PhysAddr::from((pte.ppn().0 << 12) | MEMORY_HIGH_BASE).floor();
         pte = &mut ppn.get_pte_array::<LAFlexPageTableEntry>()[idxs[2]];
         if pte.is_valid() {
+            Some((pte, 0))
+        } else {
+            None
+        }
+    }
+    /// Find and return reference the page table entry denoted by `vpn`, `None` if not found or invalid.
+    /// The directory entry is returned if `vpn` is in a huge page.
+    fn find_pte_refmut(&self, vpn: VirtPageNum) -> Option<&mut LAFlexPageTableEntry> {
+        self.find_leaf(vpn).map(|(pte, _)| pte)
+    }
+    /// 映射`vpn`所在大页的目录项
+    fn find_huge_pte(&self, vpn: VirtPageNum) -> Option<&mut LAFlexPageTableEntry> {
+        let idxs = vpn.indexes::<3>();
+        let pte = &self.get_root_ppn().get_pte_array::<LAFlexPageTableEntry>()[idxs[0]];
+        if !pte.is_valid() {
+            return None;
+        }
+        let ppn = PhysAddr::from((pte.ppn().0 << 12) | MEMORY_HIGH_BASE).floor();
+        let pte = &mut ppn.get_pte_array::<LAFlexPageTableEntry>()[idxs[1]];
+        if pte.is_huge() {
             Some(pte)
         } else {
             None
@@ -253,11 +289,28 @@ impl LAFlexPageTable {
         //trace!("[find_pte(as refmut)] {:?}", vpn);
         self.find_pte_refmut(vpn).map(|i| &*i)
     }
+    /// 映射用户页时页表项的标志
+    fn pte_flags(flags: MapPermission) -> LAPTEFlagBits {
+        let mut flag = LAPTEFlagBits::V | LAPTEFlagBits::MAT_CC;
+        if !flags.contains(MapPermission::R) {
+            flag |= LAPTEFlagBits::NR;
+        }
+        if !flags.contains(MapPermission::X) {
+            flag |= LAPTEFlagBits::NX;
+        }
+        if flags.contains(MapPermission::W) {
+            flag |= LAPTEFlagBits::W;
+        }
+        if flags.contains(MapPermission::U) {
+            flag |= LAPTEFlagBits::PLV3;
+        }
+        flag
+    }
     pub fn set_dirty_bit(&mut self, vpn: VirtPageNum) -> Result<(), ()> {
         tlb_invalidate();
         if self.is_ident_map(vpn) {
-            unsafe {
-                DIRTY[vpn.0 & VA_MASK] = true;
+            if let Some(idx) = boot_info().ram_page_index(vpn.0 & VA_MASK) {
+                DIRTY[idx].store(true, Ordering::Relaxed);
             }
             return Ok(());
         }
@@ -339,19 +392,7 @@ impl PageTable for LAFlexPageTable {
         let pte = self.find_pte_create(vpn).unwrap();
         //log::trace!("[laflex::map] vpn: {:?}, ppn:{:?}", vpn, ppn);
         debug_assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
-        let mut flag = LAPTEFlagBits::V | LAPTEFlagBits::MAT_CC;
-        if !flags.contains(MapPermission::R) {
-            flag |= LAPTEFlagBits::NR;
-        }
-        if !flags.contains(MapPermission::X) {
-            flag |= LAPTEFlagBits::NX;
-        }
-        if flags.contains(MapPermission::W) {
-            flag |= LAPTEFlagBits::W;
-        }
-        if flags.contains(MapPermission::U) {
-            flag |= LAPTEFlagBits::PLV3;
-        }
+        let flag = Self::pte_flags(flags);
         //flag |= LAPTEFlagBits::D;
         let pte_new = LAFlexPageTableEntry::new(ppn, flag);
         //log::trace!("[laflex::map] pre_wr");
@@ -367,28 +408,82 @@ impl PageTable for LAFlexPageTable {
         debug_assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
         *pte = LAFlexPageTableEntry { bits: 0 };
     }
+    fn map_huge(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: MapPermission) {
+        let idxs = vpn.indexes::<3>();
+        let pte = &mut self.get_root_ppn().get_pte_array::<LAFlexPageTableEntry>()[idxs[0]];
+        if !pte.is_valid() {
+            let frame = frame_alloc().unwrap();
+            *pte = LAFlexPageTableEntry::new(frame.ppn, LAPTEFlagBits::V);
+            self.frames.push(frame);
+        }
+        let dir = PhysAddr::from((pte.ppn().0 << 12) | MEMORY_HIGH_BASE).floor();
+        let pte = &mut dir.get_pte_array::<LAFlexPageTableEntry>()[idxs[1]];
+        if pte.is_valid() {
+            // 大页中的普通页都解除映射后留下的空页表，释放掉
+            assert!(!pte.is_huge(), "vpn {:?} is mapped before mapping", vpn);
+            let table = pte.ppn();
+            assert!(
+                PhysAddr::from((table.0 << 12) | MEMORY_HIGH_BASE)
+                    .floor()
+                    .get_pte_array::<LAFlexPageTableEntry>()
+                    .iter()
+                    .all(|pte| !pte.is_valid()),
+                "vpn {:?} is mapped before mapping",
+                vpn
+            );
+            self.frames.retain(|frame| frame.ppn != table);
+        }
+        // 大页页表项的第6位是H，G移到了第12位
+        *pte = LAFlexPageTableEntry::new(ppn, Self::pte_flags(flags) | LAPTEFlagBits::H);
+    }
+    fn unmap_huge(&mut self, vpn: VirtPageNum) {
+        let pte = self.find_huge_pte(vpn).unwrap();
+        *pte = LAFlexPageTableEntry { bits: 0 };
+    }
+    fn is_huge(&self, vpn: VirtPageNum) -> bool {
+        self.find_huge_pte(vpn).is_some()
+    }
+    fn split_huge(&mut self, vpn: VirtPageNum) -> Result<(), ()> {
+        let pte = self.find_huge_pte(vpn).ok_or(())?;
+        let frame = frame_alloc().unwrap();
+        let base = pte.ppn();
+        let flags = pte.flags() - LAPTEFlagBits::H;
+        for (i, entry) in frame
+            .ppn
+            .get_pte_array::<LAFlexPageTableEntry>()
+            .iter_mut()
+            .enumerate()
+        {
+            *entry = LAFlexPageTableEntry::new(PhysPageNum(base.0 + i), flags);
+        }
+        *pte = LAFlexPageTableEntry::new(frame.ppn, LAPTEFlagBits::V);
+        self.frames.push(frame);
+        tlb_invalidate();
+        Ok(())
+    }
     /// Translate the `vpn` into its corresponding `Some(PageTableEntry)` if exists
     /// `None` is returned if nothing is found.
     fn translate(&self, vpn: VirtPageNum) -> Option<PhysPageNum> {
         // This is not the same map as we defined just now...
         // It is the map for func. programming.
-        self.find_pte(vpn).map(|pte| pte.ppn())
+        self.find_leaf(vpn)
+            .map(|(pte, offset)| PhysPageNum(pte.ppn().0 + offset))
     }
     /// Translate the virtual address into its corresponding `PhysAddr` if mapped in current page table.
     /// `None` is returned if nothing is found.
     fn translate_va(&self, va: VirtAddr) -> Option<PhysAddr> {
-        self.find_pte(va.clone().floor()).map(|pte| {
-            let aligned_pa: PhysAddr = pte.ppn().into();
+        self.translate(va.clone().floor()).map(|ppn| {
+            let aligned_pa: PhysAddr = ppn.into();
             let offset = va.page_offset();
             let aligned_pa_usize: usize = aligned_pa.into();
             (aligned_pa_usize + offset).into()
         })
     }
     fn block_and_ret_mut(&self, vpn: VirtPageNum) -> Option<PhysPageNum> {
-        if let Some(pte) = self.find_pte_refmut(vpn) {
+        if let Some((pte, offset)) = self.find_leaf(vpn) {
             pte.clear_dirty();
             pte.revoke_write();
-            Some(pte.ppn())
+            Some(PhysPageNum(pte.ppn().0 + offset))
         } else {
             None
         }
@@ -452,8 +547,8 @@ impl PageTable for LAFlexPageTable {
     fn clear_dirty_bit(&mut self, vpn: VirtPageNum) -> Result<(), ()> {
         tlb_invalidate();
         if self.is_ident_map(vpn) {
-            unsafe {
-                DIRTY[vpn.0 & VA_MASK] = false;
+            if let Some(idx) = boot_info().ram_page_index(vpn.0 & VA_MASK) {
+                DIRTY[idx].store(false, Ordering::Relaxed);
             }
             return Ok(());
         }
@@ -464,6 +559,10 @@ impl PageTable for LAFlexPageTable {
             Err(())
         }
     }
+    /// LA hasn't had access bit so far, nothing to set.
+    fn set_access_bit(&mut self, _vpn: VirtPageNum) -> Result<(), ()> {
+        Ok(())
+    }
     fn activate(&self) {
         tlb_global_invalidate();
         if self.is_kernel_pt() {
@@ -477,11 +576,19 @@ impl PageTable for LAFlexPageTable {
     }
     fn is_dirty(&self, vpn: VirtPageNum) -> Option<bool> {
         if self.is_ident_map(vpn) {
-            Some(unsafe { DIRTY[vpn.0 & VA_MASK] })
+            Some(
+                boot_info()
+                    .ram_page_index(vpn.0 & VA_MASK)
+                    .map_or(false, |idx| DIRTY[idx].load(Ordering::Relaxed)),
+            )
         } else {
             self.find_pte(vpn).map(|pte| pte.is_dirty())
         }
     }
+    /// LA hasn't had access bit so far, page reclaim relies on the order of LRU lists.
+    fn is_accessed(&self, _vpn: VirtPageNum) -> Option<bool> {
+        None
+    }
     fn readable(&self, vpn: VirtPageNum) -> Option<bool> {
         self.find_pte(vpn).map(|pte| pte.readable())
     }
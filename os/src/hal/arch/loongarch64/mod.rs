@@ -5,6 +5,7 @@ pub mod laflex;
 #[macro_use]
 mod mem_reg_macro;
 mod acpi;
+mod efi;
 mod sbi;
 pub mod switch;
 pub mod time;
@@ -16,11 +17,15 @@ pub use switch::__switch;
 pub use tlb::{tlb_global_invalidate, tlb_invalidate};
 
 use crate::{
-    config::{DIR_WIDTH, MMAP_BASE, PAGE_SIZE_BITS, PTE_WIDTH, PTE_WIDTH_BITS, SUC_DMW_VESG},
+    config::{
+        DIR_WIDTH, HIGH_BASE_EIGHT, MEMORY_END, MEMORY_START, MMAP_BASE, PAGE_SIZE, PAGE_SIZE_BITS,
+        PTE_WIDTH, PTE_WIDTH_BITS, SUC_DMW_VESG,
+    },
     hal::arch::loongarch64::{
-        board::UART_BASE,
+        board::{MMIO, PCI_ECAM_BASE, PCI_ECAM_SIZE, UART_BASE},
         trap::{set_kernel_trap_entry, set_machine_err_trap_ent},
     },
+    hal::bootinfo::BootInfo,
 };
 
 use self::{time::get_timer_freq_first_time, trap::strampoline};
@@ -116,3 +121,32 @@ pub fn bootstrap_init() {
     println!("[kernel] UART address: {:#x}", UART_BASE);
     println!("[bootstrap_init] {:?}", PRCfg1::read());
 }
+
+/// `a0`非0表示通过EFI启动，此时`a2`是EFI系统表的地址
+pub fn probe_boot_info(
+    info: &mut BootInfo,
+    efi_boot: usize,
+    _cmdline: usize,
+    systab: usize,
+) -> bool {
+    if efi_boot == 0 || !unsafe { efi::scan(systab, info) } {
+        return false;
+    }
+    if let Some(uart) = info.uart {
+        // 串口通过不经缓存的直接映射窗口访问
+        unsafe {
+            sbi::UART.base = uart | HIGH_BASE_EIGHT;
+        }
+    }
+    true
+}
+
+/// 没有EFI系统表时使用编译时的内存布局与开发板上的设备地址
+pub fn default_boot_info(info: &mut BootInfo) {
+    info.add_memory(MEMORY_START, MEMORY_END - MEMORY_START);
+    for &(addr, size) in MMIO {
+        info.add_mmio(addr, size);
+    }
+    info.set_uart(UART_BASE & !HIGH_BASE_EIGHT, PAGE_SIZE);
+    info.set_pci_ecam(PCI_ECAM_BASE, PCI_ECAM_SIZE);
+}
@@ -4,12 +4,15 @@ use self::context::GeneralRegs;
 
 use super::register::{self, Exception, Interrupt, Trap, ERA};
 use super::{pre_start_init, MErrEntry};
+use crate::fs::{poll::poll_tick, writeback::writeback_tick};
 use crate::hal::arch::get_clock_freq;
 use crate::hal::arch::loongarch64::laflex::LAFlexPageTable;
 use crate::hal::arch::loongarch64::register::{CrMd, ECfg, LineBasedInterrupt, PrMd, TCfg, TIClr};
 use crate::hal::arch::loongarch64::trap::mem_access::Instruction;
 use crate::hal::arch::TICKS_PER_SEC;
-use crate::mm::{copy_from_user, copy_to_user, frame_reserve, MemoryError, PageTable, VirtAddr};
+use crate::mm::{
+    copy_from_user, copy_to_user, frame_reserve, kswapd_tick, MemoryError, PageTable, VirtAddr,
+};
 use crate::syscall::syscall;
 use crate::task::{
     current_task, current_trap_cx, current_user_token, do_signal, do_wake_expired,
@@ -66,8 +69,21 @@ pub extern "C" fn __rfill() {
     lddir  $t0, $t0, 3
     addi.d $t0, $t0, -1
     lddir  $t0, $t0, 1
+    andi   $t0, $t0, 0x40
+    bnez   $t0, 3f
+    csrrd  $t0, 0x1b
+    lddir  $t0, $t0, 3
     addi.d $t0, $t0, -1
-
+    lddir  $t0, $t0, 1
+    addi.d $t0, $t0, -1
+    b      4f
+3:
+    # huge page: lddir returns the entry itself, keep its valid bit for ldpte
+    csrrd  $t0, 0x1b
+    lddir  $t0, $t0, 3
+    addi.d $t0, $t0, -1
+    lddir  $t0, $t0, 1
+4:
     ldpte  $t0, 0
     ldpte  $t0, 1
     csrrd  $t0, 0x8c
@@ -244,6 +260,9 @@ pub fn trap_handler() -> ! {
             do_wake_expired();
             TIClr::read().clear_timer().write();
             enable_timer_interrupt();
+            writeback_tick();
+            kswapd_tick();
+            poll_tick();
             suspend_current_and_run_next();
         }
         Trap::Exception(Exception::Breakpoint) => {
@@ -2,33 +2,32 @@
 mod loongarch64;
 #[cfg(feature = "loongarch64")]
 pub use loongarch64::{
-    board,
-    board::MMIO,
-    bootstrap_init, config,
+    __switch, board, bootstrap_init, config,
     config::BUFFER_CACHE_NUM,
     config::KERNEL_HEAP_SIZE,
-    config::MEMORY_END,
-    console_flush, console_getchar, console_putchar, machine_init, shutdown,
+    console_flush, console_getchar, console_putchar, default_boot_info, kstack_alloc, machine_init,
+    probe_boot_info, shutdown,
     time::{get_clock_freq, get_time, TICKS_PER_SEC},
-    KernelPageTableImpl, PageTableImpl, __switch, kstack_alloc, tlb_invalidate,
+    tlb_invalidate,
     trap::{
         get_bad_addr, get_bad_instruction, get_exception_cause, trap_handler, trap_return,
         MachineContext, TrapContext, TrapImpl, UserContext,
     },
-    trap_cx_bottom_from_tid, ustack_bottom_from_tid, KernelStack, BLOCK_SZ,
+    trap_cx_bottom_from_tid, ustack_bottom_from_tid, KernelPageTableImpl, KernelStack,
+    PageTableImpl, BLOCK_SZ,
 };
 #[cfg(feature = "riscv")]
 pub mod riscv;
 #[cfg(feature = "riscv")]
 pub use riscv::{
     bootstrap_init, config,
-    config::{BLOCK_SZ, BUFFER_CACHE_NUM, KERNEL_HEAP_SIZE, MEMORY_END},
+    config::{BLOCK_SZ, BUFFER_CACHE_NUM, KERNEL_HEAP_SIZE},
+    default_boot_info,
     kern_stack::kstack_alloc,
     kern_stack::trap_cx_bottom_from_tid,
     kern_stack::ustack_bottom_from_tid,
     kern_stack::KernelStack,
-    machine_init,
-    rv_board::MMIO,
+    machine_init, probe_boot_info,
     sbi::{console_flush, console_getchar, console_putchar, set_timer, shutdown},
     sv39::tlb_invalidate,
     switch::__switch,
@@ -8,6 +8,7 @@ pub const USER_STACK_SIZE: usize = PAGE_SIZE * 40;
 pub const USER_HEAP_SIZE: usize = PAGE_SIZE * 20;
 
 pub const KERNEL_STACK_SIZE: usize = PAGE_SIZE * 2;
+// 启动时划出的内核堆的上限，实际大小为物理内存的1/4，用完后向帧分配器扩展
 #[cfg(not(feature = "board_fu740"))]
 // pub const KERNEL_HEAP_SIZE: usize = PAGE_SIZE * 0x240;
 pub const KERNEL_HEAP_SIZE: usize = PAGE_SIZE * 0x3000;
@@ -19,7 +20,7 @@ pub const MMAP_BASE: usize = 0x6000_0000;
 pub const MMAP_END: usize = 0x8000_0000;
 pub const SKIP_NUM: usize = 2;
 
-// manually make usable memory space equal
+// 没有设备树时使用的物理内存范围
 pub const MEMORY_START: usize = 0x0000_0000_8000_0000;
 #[cfg(all(not(feature = "board_cv1811h"), not(feature = "board_fu740")))]
 pub const MEMORY_END: usize = MEMORY_START + MEMORY_SIZE;
@@ -29,14 +30,16 @@ pub const MEMORY_END: usize = 0x9000_0000;
 pub const MEMORY_END: usize = 0x9000_0000; //256M
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
+/// 大页（由二级页表项直接映射）包含的页数
+pub const HUGE_PAGE_PAGES: usize = 1 << 9;
+pub const HUGE_PAGE_SIZE: usize = PAGE_SIZE * HUGE_PAGE_PAGES;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const SIGNAL_TRAMPOLINE: usize = TRAMPOLINE - PAGE_SIZE;
 pub const TRAP_CONTEXT_BASE: usize = SIGNAL_TRAMPOLINE - PAGE_SIZE;
 
-pub const MEMORY_PHYS: usize = 0x800_0000;
-pub const DISK_IMAGE_BASE: usize = 0x8000_0000 + MEMORY_PHYS;
-// pub const DISK_IMAGE_BASE: usize = MEMORY_END;
+// 没有initrd时内存盘的大小
+pub const RAM_DISK_SIZE: usize = 0x800_0000;
 
 pub const SYSTEM_TASK_LIMIT: usize = 128;
 pub const SYSTEM_FD_LIMIT: usize = 256;
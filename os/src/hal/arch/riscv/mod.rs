@@ -15,6 +15,10 @@ pub fn machine_init() {
     set_next_trigger();
 }
 
+use super::super::bootinfo::BootInfo;
+use super::super::fdt::Fdt;
+use config::{MEMORY_END, MEMORY_START, PAGE_SIZE};
+use rv_board::{MMIO, UART_BASE, VIRTIO_MMIO};
 use time::set_next_trigger;
 
 pub use trap::context::MachineContext;
@@ -26,3 +30,31 @@ pub type InterruptImpl = riscv::register::scause::Interrupt;
 pub type ExceptionImpl = riscv::register::scause::Exception;
 
 pub fn bootstrap_init() {}
+
+/// OpenSBI在`a0`中传入hart编号，在`a1`中传入设备树的物理地址
+pub fn probe_boot_info(info: &mut BootInfo, _hart_id: usize, dtb: usize, _: usize) -> bool {
+    match unsafe { Fdt::from_ptr(dtb) } {
+        Some(fdt) => {
+            fdt.scan(info);
+            true
+        }
+        None => false,
+    }
+}
+
+/// 没有设备树时使用编译时的内存布局与设备地址
+pub fn default_boot_info(info: &mut BootInfo) {
+    extern "C" {
+        fn skernel();
+    }
+    info.add_memory(MEMORY_START, MEMORY_END - MEMORY_START);
+    // 内核之前是SBI固件
+    info.add_reserved(MEMORY_START, skernel as usize - MEMORY_START);
+    for &(addr, size) in MMIO {
+        info.add_mmio(addr, size);
+    }
+    info.set_uart(UART_BASE, PAGE_SIZE);
+    for &(addr, size) in VIRTIO_MMIO {
+        info.add_virtio_mmio(addr, size);
+    }
+}
@@ -59,6 +59,9 @@ impl Sv39PageTableEntry {
     pub fn is_dirty(&self) -> bool {
         (self.flags() & PTEFlags::D) != PTEFlags::empty()
     }
+    pub fn is_accessed(&self) -> bool {
+        (self.flags() & PTEFlags::A) != PTEFlags::empty()
+    }
     pub fn readable(&self) -> bool {
         (self.flags() & PTEFlags::R) != PTEFlags::empty()
     }
@@ -68,9 +71,17 @@ impl Sv39PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+    /// R、W、X不全为0的有效页表项是叶子，出现在二级页表中时映射一个大页
+    pub fn is_leaf(&self) -> bool {
+        self.is_valid()
+            && (self.flags() & (PTEFlags::R | PTEFlags::W | PTEFlags::X)) != PTEFlags::empty()
+    }
     pub fn clear_access(&mut self) {
         self.bits &= !(PTEFlags::A.bits() as usize);
     }
+    pub fn set_access(&mut self) {
+        self.bits |= PTEFlags::A.bits() as usize;
+    }
     pub fn clear_dirty(&mut self) {
         self.bits &= !(PTEFlags::D.bits() as usize);
     }
@@ -113,6 +124,7 @@ impl Sv39PageTable {
                 result = Some(pte);
                 break;
             }
+            assert!(!pte.is_leaf(), "vpn {:?} is in a huge page", vpn);
             if !pte.is_valid() {
                 let frame = frame_alloc().unwrap();
                 // xein TODO:
@@ -125,41 +137,49 @@ impl Sv39PageTable {
         }
         result
     }
-    /// Find the page table entry denoted by vpn, returning Some(&_) if found or None if not.
-    pub fn find_pte(&self, vpn: VirtPageNum) -> Option<&Sv39PageTableEntry> {
+    /// Find the leaf page table entry mapping `vpn`, which is in the second level for huge pages.
+    /// Returns the entry and the offset of `vpn` in the page it maps.
+    fn find_leaf(&self, vpn: VirtPageNum) -> Option<(&mut Sv39PageTableEntry, usize)> {
         let idxs: [usize; 3] = vpn.indexes();
         let mut ppn = self.root_ppn;
-        let mut result: Option<&Sv39PageTableEntry> = None;
         for i in 0..3 {
-            let pte = &ppn.get_pte_array::<Sv39PageTableEntry>()[idxs[i]];
+            let pte = &mut ppn.get_pte_array::<Sv39PageTableEntry>()[idxs[i]];
             if !pte.is_valid() {
                 return None;
             }
             if i == 2 {
-                result = Some(pte);
-                break;
+                return Some((pte, 0));
+            }
+            if i == 1 && pte.is_leaf() {
+                return Some((pte, idxs[2]));
             }
             ppn = pte.ppn();
         }
-        result
+        None
+    }
+    /// Find the page table entry denoted by vpn, returning Some(&_) if found or None if not.
+    /// The entry of the huge page is returned if `vpn` is in a huge page.
+    pub fn find_pte(&self, vpn: VirtPageNum) -> Option<&Sv39PageTableEntry> {
+        self.find_leaf(vpn).map(|(pte, _)| &*pte)
     }
     /// Find and return reference the page table entry denoted by `vpn`, `None` if not found.
+    /// The entry of the huge page is returned if `vpn` is in a huge page.
     fn find_pte_refmut(&self, vpn: VirtPageNum) -> Option<&mut Sv39PageTableEntry> {
+        self.find_leaf(vpn).map(|(pte, _)| pte)
+    }
+    /// 二级页表中映射`vpn`所在大页的页表项
+    fn find_huge_pte(&self, vpn: VirtPageNum) -> Option<&mut Sv39PageTableEntry> {
         let idxs: [usize; 3] = vpn.indexes();
-        let mut ppn = self.root_ppn;
-        let mut result: Option<&mut Sv39PageTableEntry> = None;
-        for i in 0..3 {
-            let pte = &mut ppn.get_pte_array::<Sv39PageTableEntry>()[idxs[i]];
-            if !pte.is_valid() {
-                return None;
-            }
-            if i == 2 {
-                result = Some(pte);
-                break;
-            }
-            ppn = pte.ppn();
+        let pte = &self.root_ppn.get_pte_array::<Sv39PageTableEntry>()[idxs[0]];
+        if !pte.is_valid() {
+            return None;
+        }
+        let pte = &mut pte.ppn().get_pte_array::<Sv39PageTableEntry>()[idxs[1]];
+        if pte.is_leaf() {
+            Some(pte)
+        } else {
+            None
         }
-        result
     }
 }
 /// Assume that it won't encounter oom when creating/mapping.
@@ -238,27 +258,80 @@ impl PageTable for Sv39PageTable {
         assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
         *pte = Sv39PageTableEntry::empty();
     }
+    fn map_huge(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: MapPermission) {
+        let idxs: [usize; 3] = vpn.indexes();
+        let pte = &mut self.root_ppn.get_pte_array::<Sv39PageTableEntry>()[idxs[0]];
+        if !pte.is_valid() {
+            let frame = frame_alloc().unwrap();
+            *pte = Sv39PageTableEntry::new(frame.ppn, PTEFlags::V);
+            self.frames.push(frame);
+        }
+        let pte = &mut pte.ppn().get_pte_array::<Sv39PageTableEntry>()[idxs[1]];
+        if pte.is_valid() {
+            // 大页中的普通页都解除映射后留下的空页表，释放掉
+            assert!(!pte.is_leaf(), "vpn {:?} is mapped before mapping", vpn);
+            let table = pte.ppn();
+            assert!(
+                table
+                    .get_pte_array::<Sv39PageTableEntry>()
+                    .iter()
+                    .all(|pte| !pte.is_valid()),
+                "vpn {:?} is mapped before mapping",
+                vpn
+            );
+            self.frames.retain(|frame| frame.ppn != table);
+        }
+        *pte = Sv39PageTableEntry::new(
+            ppn,
+            PTEFlags::from_bits(flags.bits()).unwrap() | PTEFlags::V | PTEFlags::A | PTEFlags::D,
+        );
+    }
+    fn unmap_huge(&mut self, vpn: VirtPageNum) {
+        let pte = self.find_huge_pte(vpn).unwrap();
+        *pte = Sv39PageTableEntry::empty();
+    }
+    fn is_huge(&self, vpn: VirtPageNum) -> bool {
+        self.find_huge_pte(vpn).is_some()
+    }
+    fn split_huge(&mut self, vpn: VirtPageNum) -> Result<(), ()> {
+        let pte = self.find_huge_pte(vpn).ok_or(())?;
+        let frame = frame_alloc().unwrap();
+        let (base, flags) = (pte.ppn(), pte.flags());
+        for (i, entry) in frame
+            .ppn
+            .get_pte_array::<Sv39PageTableEntry>()
+            .iter_mut()
+            .enumerate()
+        {
+            *entry = Sv39PageTableEntry::new(PhysPageNum(base.0 + i), flags);
+        }
+        *pte = Sv39PageTableEntry::new(frame.ppn, PTEFlags::V);
+        self.frames.push(frame);
+        tlb_invalidate();
+        Ok(())
+    }
     /// Translate the `vpn` into its corresponding `Some(PageTableEntry)` if exists
     /// `None` is returned if nothing is found.
     fn translate(&self, vpn: VirtPageNum) -> Option<PhysPageNum> {
         // This is not the same map as we defined just now...
         // It is the map for func. programming.
-        self.find_pte(vpn).map(|pte| pte.ppn())
+        self.find_leaf(vpn)
+            .map(|(pte, offset)| PhysPageNum(pte.ppn().0 + offset))
     }
     /// Translate the virtual address into its corresponding `PhysAddr` if mapped in current page table.
     /// `None` is returned if nothing is found.
     fn translate_va(&self, va: VirtAddr) -> Option<PhysAddr> {
-        self.find_pte(va.clone().floor()).map(|pte| {
-            let aligned_pa: PhysAddr = pte.ppn().into();
+        self.translate(va.clone().floor()).map(|ppn| {
+            let aligned_pa: PhysAddr = ppn.into();
             let offset = va.page_offset();
             let aligned_pa_usize: usize = aligned_pa.into();
             (aligned_pa_usize + offset).into()
         })
     }
     fn block_and_ret_mut(&self, vpn: VirtPageNum) -> Option<PhysPageNum> {
-        if let Some(pte) = self.find_pte_refmut(vpn) {
+        if let Some((pte, offset)) = self.find_leaf(vpn) {
             pte.revoke_write();
-            Some(pte.ppn())
+            Some(PhysPageNum(pte.ppn().0 + offset))
         } else {
             None
         }
@@ -326,6 +399,15 @@ impl PageTable for Sv39PageTable {
             Err(())
         }
     }
+    fn set_access_bit(&mut self, vpn: VirtPageNum) -> Result<(), ()> {
+        tlb_invalidate();
+        if let Some(pte) = self.find_pte_refmut(vpn) {
+            pte.set_access();
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
     fn activate(&self) {
         // TODO:
         let satp = self.token();
@@ -341,6 +423,9 @@ impl PageTable for Sv39PageTable {
     fn is_dirty(&self, vpn: VirtPageNum) -> Option<bool> {
         self.find_pte(vpn).map(|pte| pte.is_dirty())
     }
+    fn is_accessed(&self, vpn: VirtPageNum) -> Option<bool> {
+        self.find_pte(vpn).map(|pte| pte.is_accessed())
+    }
     fn readable(&self, vpn: VirtPageNum) -> Option<bool> {
         self.find_pte(vpn).map(|pte| pte.readable())
     }
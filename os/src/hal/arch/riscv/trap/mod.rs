@@ -5,8 +5,9 @@ use super::TrapImpl;
 use crate::config::TRAMPOLINE;
 use crate::fs::directory_tree::ROOT;
 use crate::fs::OpenFlags;
+use crate::fs::{poll::poll_tick, writeback::writeback_tick};
 use crate::hal::arch::riscv::time::set_next_trigger;
-use crate::mm::{frame_reserve, MemoryError, VirtAddr};
+use crate::mm::{frame_reserve, kswapd_tick, MemoryError, VirtAddr};
 use crate::syscall::syscall;
 use crate::task::{
     current_task, current_trap_cx, do_signal, do_wake_expired, suspend_current_and_run_next,
@@ -133,6 +134,9 @@ pub fn trap_handler() -> ! {
                 //}
             }
             set_next_trigger();
+            writeback_tick();
+            kswapd_tick();
+            poll_tick();
             suspend_current_and_run_next();
         }
         _ => {
@@ -0,0 +1,338 @@
+//! 启动时由引导程序传入的物理内存布局与设备地址。
+//! RISC-V从OpenSBI传入的设备树中读取，LoongArch从EFI系统表中的内存表、ACPI表与设备树中读取，
+//! 都找不到时退回到`config.rs`中编译时的布局。
+//! 这些信息在堆初始化之前就要用到，所以全部存放在定长数组中
+use super::KERNEL_HEAP_SIZE;
+use crate::config::{PAGE_SIZE, PAGE_SIZE_BITS};
+use crate::mm::{FRAME_DESC_SIZE, PAGE_DESC_SIZE};
+use core::fmt::{self, Debug, Formatter};
+
+/// 每一类区域最多记录的个数
+pub const MAX_REGIONS: usize = 32;
+
+/// 物理地址区间`[start, end)`
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemRegion {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl MemRegion {
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+    pub fn contains(&self, addr: usize) -> bool {
+        self.start <= addr && addr < self.end
+    }
+}
+
+impl Debug for MemRegion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("[{:#x}, {:#x})", self.start, self.end))
+    }
+}
+
+/// 定长的区域列表
+pub struct RegionList {
+    regions: [MemRegion; MAX_REGIONS],
+    len: usize,
+}
+
+impl RegionList {
+    const fn new() -> Self {
+        Self {
+            regions: [MemRegion::new(0, 0); MAX_REGIONS],
+            len: 0,
+        }
+    }
+    pub fn iter(&self) -> core::slice::Iter<'_, MemRegion> {
+        self.regions[..self.len].iter()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// 按顺序追加在末尾
+    fn push(&mut self, region: MemRegion) {
+        if self.len == MAX_REGIONS {
+            println!("[bootinfo] too many regions, {:?} is ignored", region);
+            return;
+        }
+        self.regions[self.len] = region;
+        self.len += 1;
+    }
+    /// 按起始地址有序插入，并与重叠或相邻的区域合并
+    fn insert(&mut self, mut region: MemRegion) {
+        if region.start >= region.end {
+            return;
+        }
+        let mut i = 0;
+        while i < self.len {
+            let other = self.regions[i];
+            if other.start <= region.end && region.start <= other.end {
+                region.start = region.start.min(other.start);
+                region.end = region.end.max(other.end);
+                self.regions.copy_within(i + 1..self.len, i);
+                self.len -= 1;
+            } else {
+                i += 1;
+            }
+        }
+        if self.len == MAX_REGIONS {
+            println!("[bootinfo] too many regions, {:?} is ignored", region);
+            return;
+        }
+        let pos = self
+            .iter()
+            .position(|other| other.start > region.start)
+            .unwrap_or(self.len);
+        self.regions.copy_within(pos..self.len, pos + 1);
+        self.regions[pos] = region;
+        self.len += 1;
+    }
+}
+
+fn page_floor(addr: usize) -> usize {
+    addr & !(PAGE_SIZE - 1)
+}
+
+fn page_ceil(addr: usize) -> usize {
+    page_floor(addr + PAGE_SIZE - 1)
+}
+
+/// 启动信息
+pub struct BootInfo {
+    /// 物理内存，按地址排序，首尾按页向内取整
+    pub memory: RegionList,
+    /// 物理内存中不交给帧分配器的部分：固件、内核镜像、设备树、initrd、页描述符表与内核堆等
+    pub reserved: RegionList,
+    /// 引导程序加载的initrd
+    pub initrd: Option<MemRegion>,
+    /// 串口寄存器的物理地址
+    pub uart: Option<usize>,
+    /// 每个virtio-mmio设备的寄存器
+    pub virtio_mmio: RegionList,
+    /// PCI配置空间（ECAM）
+    pub pci_ecam: Option<MemRegion>,
+    /// 内核页表需要映射的所有设备寄存器
+    pub mmio: RegionList,
+    /// 页描述符表`MEM_MAP`，每个物理页占`PAGE_DESC_SIZE`字节
+    pub mem_map: MemRegion,
+    /// 帧分配器的描述符表，每个物理页占`FRAME_DESC_SIZE`字节
+    pub frame_map: MemRegion,
+    /// 内核堆
+    pub heap: MemRegion,
+    /// 内存盘的根文件系统镜像，优先使用initrd
+    pub ram_disk: Option<MemRegion>,
+}
+
+impl BootInfo {
+    const fn new() -> Self {
+        Self {
+            memory: RegionList::new(),
+            reserved: RegionList::new(),
+            initrd: None,
+            uart: None,
+            virtio_mmio: RegionList::new(),
+            pci_ecam: None,
+            mmio: RegionList::new(),
+            mem_map: MemRegion::new(0, 0),
+            frame_map: MemRegion::new(0, 0),
+            heap: MemRegion::new(0, 0),
+            ram_disk: None,
+        }
+    }
+    pub(super) fn add_memory(&mut self, addr: usize, size: usize) {
+        self.memory
+            .insert(MemRegion::new(page_ceil(addr), page_floor(addr + size)));
+    }
+    pub(super) fn add_reserved(&mut self, addr: usize, size: usize) {
+        self.reserved
+            .insert(MemRegion::new(page_floor(addr), page_ceil(addr + size)));
+    }
+    pub(super) fn add_mmio(&mut self, addr: usize, size: usize) {
+        self.mmio
+            .insert(MemRegion::new(page_floor(addr), page_ceil(addr + size)));
+    }
+    pub(super) fn add_virtio_mmio(&mut self, addr: usize, size: usize) {
+        self.virtio_mmio.push(MemRegion::new(addr, addr + size));
+        self.add_mmio(addr, size);
+    }
+    /// 有多个串口时使用第一个
+    pub(super) fn set_uart(&mut self, addr: usize, size: usize) {
+        if self.uart.is_none() {
+            self.uart = Some(addr);
+            self.add_mmio(addr, size);
+        }
+    }
+    pub(super) fn set_pci_ecam(&mut self, addr: usize, size: usize) {
+        if self.pci_ecam.is_none() {
+            self.pci_ecam = Some(MemRegion::new(addr, addr + size));
+            self.add_mmio(addr, size);
+        }
+    }
+    pub(super) fn set_initrd(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.initrd = Some(MemRegion::new(start, end));
+            self.add_reserved(start, end - start);
+        }
+    }
+    /// 物理内存总量
+    pub fn total_ram(&self) -> usize {
+        self.memory.iter().map(|region| region.len()).sum()
+    }
+    /// 物理内存的总页数
+    pub fn ram_pages(&self) -> usize {
+        self.total_ram() >> PAGE_SIZE_BITS
+    }
+    /// 把所有内存区域的页依次排在一起时，物理页号`ppn`的下标。
+    /// 帧分配器与页描述符表都按这个下标存放，不在内存中的页返回`None`
+    pub fn ram_page_index(&self, ppn: usize) -> Option<usize> {
+        let mut base = 0;
+        for region in self.memory.iter() {
+            let (start, end) = (region.start >> PAGE_SIZE_BITS, region.end >> PAGE_SIZE_BITS);
+            if ppn < start {
+                return None;
+            }
+            if ppn < end {
+                return Some(base + ppn - start);
+            }
+            base += end - start;
+        }
+        None
+    }
+    /// `ram_page_index`的逆映射
+    pub fn ram_index_to_ppn(&self, mut idx: usize) -> usize {
+        for region in self.memory.iter() {
+            let pages = region.len() >> PAGE_SIZE_BITS;
+            if idx < pages {
+                return (region.start >> PAGE_SIZE_BITS) + idx;
+            }
+            idx -= pages;
+        }
+        panic!("[bootinfo] RAM page index {} out of range", idx);
+    }
+    /// 依次处理物理内存中除去保留区域后的每一段
+    pub fn for_each_free(&self, mut f: impl FnMut(MemRegion)) {
+        for region in self.memory.iter() {
+            let mut start = region.start;
+            for reserved in self.reserved.iter() {
+                if reserved.end <= start {
+                    continue;
+                }
+                if reserved.start >= region.end {
+                    break;
+                }
+                if reserved.start > start {
+                    f(MemRegion::new(start, reserved.start));
+                }
+                start = reserved.end;
+            }
+            if start < region.end {
+                f(MemRegion::new(start, region.end));
+            }
+        }
+    }
+    /// 除去保留区域后的内存大小
+    pub fn free_ram(&self) -> usize {
+        let mut size = 0;
+        self.for_each_free(|free| size += free.len());
+        size
+    }
+    /// 物理地址`addr`所在的页会交给帧分配器
+    pub fn is_free(&self, addr: usize) -> bool {
+        self.memory.iter().any(|region| region.contains(addr))
+            && !self.reserved.iter().any(|region| region.contains(addr))
+    }
+    /// 从最低的空闲内存中切出`size`字节并保留下来
+    fn alloc_early(&mut self, size: usize) -> Option<MemRegion> {
+        let size = page_ceil(size);
+        let mut found = None;
+        self.for_each_free(|free| {
+            if found.is_none() && free.len() >= size {
+                found = Some(MemRegion::new(free.start, free.start + size));
+            }
+        });
+        let region = found?;
+        self.reserved.insert(region);
+        Some(region)
+    }
+    fn print(&self) {
+        macro_rules! print_list {
+            ($name:literal, $list:expr) => {
+                for region in $list.iter() {
+                    println!("[bootinfo] {} {:?}", $name, region);
+                }
+            };
+        }
+        print_list!("memory", self.memory);
+        print_list!("reserved", self.reserved);
+        print_list!("mmio", self.mmio);
+        if let Some(initrd) = self.initrd {
+            println!("[bootinfo] initrd {:?}", initrd);
+        }
+        if let Some(uart) = self.uart {
+            println!("[bootinfo] uart {:#x}", uart);
+        }
+        print_list!("virtio-mmio", self.virtio_mmio);
+        if let Some(ecam) = self.pci_ecam {
+            println!("[bootinfo] pci-ecam {:?}", ecam);
+        }
+        println!(
+            "[bootinfo] total memory: {} MiB, free memory: {} MiB",
+            self.total_ram() >> 20,
+            self.free_ram() >> 20
+        );
+        println!(
+            "[bootinfo] page descriptors {:?}, frame descriptors {:?}, kernel heap {:?}",
+            self.mem_map, self.frame_map, self.heap
+        );
+    }
+}
+
+/// 只在启动早期单核运行时写入一次，之后只读
+static mut BOOT_INFO: BootInfo = BootInfo::new();
+
+/// 启动信息
+pub fn boot_info() -> &'static BootInfo {
+    unsafe { &*core::ptr::addr_of!(BOOT_INFO) }
+}
+
+/// 解析引导程序传入的参数`a0`到`a2`，确定物理内存布局并划出每页的元数据与内核堆。
+/// 必须在清空.bss之后、初始化堆之前调用
+pub fn init(arg0: usize, arg1: usize, arg2: usize) {
+    extern "C" {
+        fn skernel();
+        fn ekernel();
+    }
+    let info = unsafe { &mut *core::ptr::addr_of_mut!(BOOT_INFO) };
+    if !super::arch::probe_boot_info(info, arg0, arg1, arg2) || info.memory.is_empty() {
+        println!("[bootinfo] no memory map from the firmware, use the built-in layout");
+        *info = BootInfo::new();
+        super::arch::default_boot_info(info);
+    }
+    // 物理地址0所在的页不分配，避免与空指针混淆
+    info.add_reserved(0, PAGE_SIZE);
+    info.add_reserved(skernel as usize, ekernel as usize - skernel as usize);
+    // 每页的元数据与内存大小成正比，直接从内存中保留，不放在大小有上限的内核堆中
+    info.mem_map = info
+        .alloc_early(info.ram_pages() * PAGE_DESC_SIZE)
+        .expect("[bootinfo] no room for the page descriptors");
+    info.frame_map = info
+        .alloc_early(info.ram_pages() * FRAME_DESC_SIZE)
+        .expect("[bootinfo] no room for the frame descriptors");
+    // 内核堆按扣除元数据之后剩余的内存计算
+    let heap_size = (info.free_ram() / 4).min(KERNEL_HEAP_SIZE);
+    info.heap = info
+        .alloc_early(heap_size)
+        .expect("[bootinfo] no room for the kernel heap");
+    #[cfg(feature = "block_mem")]
+    {
+        info.ram_disk = info
+            .initrd
+            .or_else(|| info.alloc_early(crate::config::RAM_DISK_SIZE));
+    }
+    info.print();
+}
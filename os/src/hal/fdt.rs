@@ -0,0 +1,309 @@
+//! 扁平设备树（Flattened Device Tree）的只读解析。
+//! 解析发生在堆初始化之前，所以这里不分配任何内存，结果直接写入`BootInfo`
+use super::bootinfo::{BootInfo, MemRegion};
+
+/// 设备树头部的魔数
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+/// 解析时跟踪的最大节点深度
+const MAX_DEPTH: usize = 16;
+
+/// 读取`off`处的大端32位数
+fn be32(bytes: &[u8], off: usize) -> Option<u32> {
+    let b = bytes.get(off..off + 4)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// 读取由`cells`个32位单元组成的大端数，返回该数与剩余部分
+fn read_cells(bytes: &[u8], cells: usize) -> Option<(usize, &[u8])> {
+    if cells > 2 || bytes.len() < cells * 4 {
+        return None;
+    }
+    let mut val = 0;
+    for i in 0..cells {
+        val = (val << 32) | be32(bytes, i * 4)? as usize;
+    }
+    Some((val, &bytes[cells * 4..]))
+}
+
+/// 以0结尾的字符串
+fn cstr(bytes: &[u8]) -> Option<&str> {
+    let len = bytes.iter().position(|c| *c == 0)?;
+    core::str::from_utf8(&bytes[..len]).ok()
+}
+
+fn align4(off: usize) -> usize {
+    (off + 3) & !3
+}
+
+/// 结构块中的一个标记，`FDT_NOP`已经被跳过
+pub enum Token<'a> {
+    /// 节点开始，带有节点名（含单元地址）
+    BeginNode(&'a str),
+    EndNode,
+    /// 属性名与属性值
+    Prop(&'a str, &'a [u8]),
+}
+
+/// 按顺序遍历结构块
+pub struct Tokens<'a> {
+    structs: &'a [u8],
+    strings: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+    /// 遇到`FDT_END`或者损坏的数据时结束
+    fn next(&mut self) -> Option<Token<'a>> {
+        let (structs, strings) = (self.structs, self.strings);
+        loop {
+            let token = be32(structs, self.pos)?;
+            self.pos += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = cstr(structs.get(self.pos..)?)?;
+                    self.pos = align4(self.pos + name.len() + 1);
+                    return Some(Token::BeginNode(name));
+                }
+                FDT_END_NODE => return Some(Token::EndNode),
+                FDT_PROP => {
+                    let len = be32(structs, self.pos)? as usize;
+                    let name_off = be32(structs, self.pos + 4)? as usize;
+                    let value = structs.get(self.pos + 8..self.pos + 8 + len)?;
+                    self.pos = align4(self.pos + 8 + len);
+                    return Some(Token::Prop(cstr(strings.get(name_off..)?)?, value));
+                }
+                FDT_NOP => continue,
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// 一棵扁平设备树
+pub struct Fdt<'a> {
+    data: &'a [u8],
+    structs: &'a [u8],
+    strings: &'a [u8],
+    rsvmap: &'a [u8],
+}
+
+impl<'a> Fdt<'a> {
+    /// 检查物理地址`pa`处的头部，不是合法的设备树时返回`None`
+    /// # Safety
+    /// `pa`必须可以直接访问，并且至少有设备树头部那么长
+    pub unsafe fn from_ptr(pa: usize) -> Option<Self> {
+        // 规范要求设备树按8字节对齐
+        if pa == 0 || pa % 8 != 0 {
+            return None;
+        }
+        let header = core::slice::from_raw_parts(pa as *const u8, 40);
+        if be32(header, 0)? != FDT_MAGIC {
+            return None;
+        }
+        let total_size = be32(header, 4)? as usize;
+        Self::new(core::slice::from_raw_parts(pa as *const u8, total_size))
+    }
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let off_struct = be32(data, 8)? as usize;
+        let off_strings = be32(data, 12)? as usize;
+        let off_rsvmap = be32(data, 16)? as usize;
+        let version = be32(data, 20)?;
+        let size_strings = be32(data, 32)? as usize;
+        // 版本17之前头部没有结构块的长度
+        let structs = if version >= 17 {
+            data.get(off_struct..off_struct + be32(data, 36)? as usize)?
+        } else {
+            data.get(off_struct..)?
+        };
+        Some(Self {
+            data,
+            structs,
+            strings: data.get(off_strings..off_strings + size_strings)?,
+            rsvmap: data.get(off_rsvmap..)?,
+        })
+    }
+    /// 设备树本身占用的内存
+    pub fn region(&self) -> MemRegion {
+        let start = self.data.as_ptr() as usize;
+        MemRegion::new(start, start + self.data.len())
+    }
+    pub fn tokens(&self) -> Tokens<'a> {
+        Tokens {
+            structs: self.structs,
+            strings: self.strings,
+            pos: 0,
+        }
+    }
+    /// 内存保留块中的每一项`(地址, 大小)`，以全0项结尾
+    pub fn reserved_entries(&self) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.rsvmap
+            .chunks_exact(16)
+            .map(|entry| {
+                let (addr, rest) = read_cells(entry, 2).unwrap();
+                let (size, _) = read_cells(rest, 2).unwrap();
+                (addr, size)
+            })
+            .take_while(|(_, size)| *size != 0)
+    }
+    /// 从设备树中找出内存、保留区域、initrd与内核用到的设备，写入`info`
+    pub fn scan(&self, info: &mut BootInfo) {
+        info.add_reserved(self.region().start, self.region().len());
+        for (addr, size) in self.reserved_entries() {
+            info.add_reserved(addr, size);
+        }
+        let mut stack = [NodeState::default(); MAX_DEPTH];
+        let mut depth = 0;
+        for token in self.tokens() {
+            match token {
+                Token::BeginNode(name) => {
+                    if depth == MAX_DEPTH {
+                        log::warn!("[fdt] node {} is nested too deep", name);
+                        return;
+                    }
+                    stack[depth] = NodeState {
+                        name,
+                        ..NodeState::default()
+                    };
+                    depth += 1;
+                }
+                Token::Prop(name, value) => {
+                    if depth > 0 {
+                        stack[depth - 1].set_prop(name, value);
+                    }
+                }
+                Token::EndNode => {
+                    if depth == 0 {
+                        return;
+                    }
+                    depth -= 1;
+                    // 根节点的`reg`没有意义，按默认的单元数处理
+                    let parent = if depth > 0 {
+                        stack[depth - 1]
+                    } else {
+                        NodeState::default()
+                    };
+                    stack[depth].probe(&parent, info);
+                }
+            }
+        }
+    }
+}
+
+/// 扫描时每一层节点记录下的属性
+#[derive(Clone, Copy)]
+struct NodeState<'a> {
+    name: &'a str,
+    /// 子节点`reg`中地址与大小占用的单元数
+    address_cells: usize,
+    size_cells: usize,
+    reg: &'a [u8],
+    compatible: &'a [u8],
+    device_type: &'a [u8],
+    disabled: bool,
+    initrd_start: Option<usize>,
+    initrd_end: Option<usize>,
+}
+
+impl Default for NodeState<'_> {
+    /// 规范规定的默认单元数
+    fn default() -> Self {
+        Self {
+            name: "",
+            address_cells: 2,
+            size_cells: 1,
+            reg: &[],
+            compatible: &[],
+            device_type: &[],
+            disabled: false,
+            initrd_start: None,
+            initrd_end: None,
+        }
+    }
+}
+
+impl<'a> NodeState<'a> {
+    fn set_prop(&mut self, name: &str, value: &'a [u8]) {
+        match name {
+            "#address-cells" => self.address_cells = be32(value, 0).unwrap_or(2) as usize,
+            "#size-cells" => self.size_cells = be32(value, 0).unwrap_or(1) as usize,
+            "reg" => self.reg = value,
+            "compatible" => self.compatible = value,
+            "device_type" => self.device_type = value,
+            "status" => self.disabled = !matches!(cstr(value), Some("okay") | Some("ok")),
+            // 引导程序可能写成32位或64位
+            "linux,initrd-start" => {
+                self.initrd_start = read_cells(value, value.len() / 4).map(|(v, _)| v)
+            }
+            "linux,initrd-end" => {
+                self.initrd_end = read_cells(value, value.len() / 4).map(|(v, _)| v)
+            }
+            _ => {}
+        }
+    }
+    /// `compatible`是以0分隔的字符串列表
+    fn is_compatible(&self, name: &str) -> bool {
+        self.compatible
+            .split(|c| *c == 0)
+            .any(|s| s == name.as_bytes())
+    }
+    /// 按父节点的单元数解析`reg`中的每一项`(地址, 大小)`
+    fn for_each_reg(&self, parent: &NodeState, mut f: impl FnMut(usize, usize)) {
+        let mut reg = self.reg;
+        while !reg.is_empty() {
+            let (addr, rest) = match read_cells(reg, parent.address_cells) {
+                Some(cells) => cells,
+                None => return,
+            };
+            let (size, rest) = match read_cells(rest, parent.size_cells) {
+                Some(cells) => cells,
+                None => return,
+            };
+            f(addr, size);
+            reg = rest;
+        }
+    }
+    /// 第一项`reg`
+    fn first_reg(&self, parent: &NodeState) -> Option<MemRegion> {
+        let mut first = None;
+        self.for_each_reg(parent, |addr, size| {
+            first.get_or_insert(MemRegion::new(addr, addr + size));
+        });
+        first
+    }
+    /// 节点结束时，所有属性都已经读到，按节点的类型记录下来
+    fn probe(&self, parent: &NodeState, info: &mut BootInfo) {
+        if self.disabled {
+            return;
+        }
+        if self.name == "chosen" {
+            if let (Some(start), Some(end)) = (self.initrd_start, self.initrd_end) {
+                info.set_initrd(start, end);
+            }
+        } else if parent.name == "reserved-memory" {
+            self.for_each_reg(parent, |addr, size| info.add_reserved(addr, size));
+        } else if cstr(self.device_type) == Some("memory") {
+            self.for_each_reg(parent, |addr, size| info.add_memory(addr, size));
+        } else if self.is_compatible("virtio,mmio") {
+            if let Some(reg) = self.first_reg(parent) {
+                info.add_virtio_mmio(reg.start, reg.len());
+            }
+        } else if self.is_compatible("ns16550a") || self.is_compatible("ns16550") {
+            if let Some(reg) = self.first_reg(parent) {
+                info.set_uart(reg.start, reg.len());
+            }
+        } else if self.is_compatible("pci-host-ecam-generic") {
+            if let Some(reg) = self.first_reg(parent) {
+                info.set_pci_ecam(reg.start, reg.len());
+            }
+        } else if self.is_compatible("riscv,plic0") || self.is_compatible("sifive,plic-1.0.0") {
+            if let Some(reg) = self.first_reg(parent) {
+                info.add_mmio(reg.start, reg.len());
+            }
+        }
+    }
+}
@@ -1,4 +1,6 @@
 pub mod arch;
+pub mod bootinfo;
+mod fdt;
 pub use arch::__switch;
 pub use arch::config;
 pub use arch::kstack_alloc;
@@ -14,5 +16,5 @@ pub use arch::{
     KernelPageTableImpl, KernelStack, MachineContext, PageTableImpl, TrapContext, TrapImpl,
     UserContext,
 };
-pub use arch::{BLOCK_SZ, BUFFER_CACHE_NUM, KERNEL_HEAP_SIZE, MEMORY_END};
-pub use arch::{MMIO, TICKS_PER_SEC};
+pub use arch::{BLOCK_SZ, BUFFER_CACHE_NUM, KERNEL_HEAP_SIZE, TICKS_PER_SEC};
+pub use bootinfo::boot_info;
@@ -8,3 +8,6 @@ pub const BLOCK_SZ: usize = 2048;
 // warning: 不能移除“ + HIGH_BASE_EIGHT”，会导致开发板上地址错误
 pub const UART_BASE: usize = 0x1FE2_0000 + HIGH_BASE_EIGHT;
 pub const ACPI_BASE: usize = 0x1FE2_7000 + HIGH_BASE_EIGHT;
+// 查看手册得知，PCI配置空间位于 0xFE_0000_0000
+pub const PCI_ECAM_BASE: usize = 0xFE_0000_0000;
+pub const PCI_ECAM_SIZE: usize = 0x1000_0000;
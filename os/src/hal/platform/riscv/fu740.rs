@@ -1,4 +1,3 @@
-use crate::config::DISK_IMAGE_BASE;
 use fu740_hal::{clock::PrciExt, time::U32Ext};
 use fu740_pac::Peripherals;
 
@@ -10,8 +9,7 @@ use fu740_pac::Peripherals;
 pub const CLOCK_FREQ: usize = 1_000_000;
 
 pub const MMIO: &[(usize, usize)] = &[
-    (0x1000_0000, 0x1000),         // PRCI
-    (DISK_IMAGE_BASE, 0x800_0000), // disk image
+    (0x1000_0000, 0x1000), // PRCI
 ];
 
 pub type BlockDeviceImpl = crate::drivers::block::MemBlockWrapper;
@@ -1,5 +1,6 @@
 pub const CLOCK_FREQ: usize = 12500000;
 
+/// 没有设备树时内核映射的设备寄存器
 pub const MMIO: &[(usize, usize)] = &[
     // 前者为地址，后者为大小
     (0x1000_0000, 0x1000),
@@ -7,6 +8,10 @@ pub const MMIO: &[(usize, usize)] = &[
     (0xC00_0000, 0x40_0000),
 ];
 
+/// 没有设备树时使用的串口与virtio-mmio设备
+pub const UART_BASE: usize = 0x1000_0000;
+pub const VIRTIO_MMIO: &[(usize, usize)] = &[(0x1000_1000, 0x1000)];
+
 // pub type BlockDeviceImpl = crate::drivers::block::VirtIOBlock;
 
 // pub const VIRT_PLIC: usize = 0xC00_0000;
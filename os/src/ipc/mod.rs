@@ -0,0 +1,226 @@
+//! System V进程间通信：共享内存、信号量集与消息队列。
+//! 三类对象各有一张编号表，由键值或编号找到对象
+pub mod msg;
+pub mod sem;
+pub mod shm;
+
+use crate::{
+    syscall::errno::{EACCES, EEXIST, EINVAL, ENOENT, ENOSPC, EPERM},
+    task::{current_task, Capabilities, Credentials},
+};
+use alloc::{collections::BTreeMap, sync::Arc};
+use lazy_static::*;
+use msg::MsgQueue;
+use sem::SemSet;
+use shm::ShmSegment;
+use spin::Mutex;
+
+/// 总是创建新对象的键值
+pub const IPC_PRIVATE: i32 = 0;
+
+/// `*get()`的标志，低9位为新对象的权限位
+pub const IPC_CREAT: u32 = 0o1000;
+pub const IPC_EXCL: u32 = 0o2000;
+/// 操作无法立即完成时返回而不阻塞
+pub const IPC_NOWAIT: u32 = 0o4000;
+
+/// `*ctl()`的命令
+pub const IPC_RMID: u32 = 0;
+pub const IPC_SET: u32 = 1;
+pub const IPC_STAT: u32 = 2;
+pub const IPC_INFO: u32 = 3;
+/// libc在命令中附带的标志，表示使用64位的结构体，内核只支持这一种格式
+pub const IPC_64: u32 = 0x100;
+
+/// 权限检查中请求的访问，与`S_IRUGO`、`S_IWUGO`一致
+pub const IPC_READ: u32 = 0o444;
+pub const IPC_WRITE: u32 = 0o222;
+
+/// 编号由序列号与下标组成：编号 = 序列号 * IPCMNI + 下标，
+/// 删除对象后同一下标得到的编号不同，旧编号不会误用新对象
+const IPCMNI: i32 = 32768;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+/// 对象的属主与权限，与`struct ipc64_perm`的布局相同
+pub struct IpcPerm {
+    pub key: i32,
+    pub uid: u32,
+    pub gid: u32,
+    pub cuid: u32,
+    pub cgid: u32,
+    /// 低9位为权限位，高位由各类对象使用（如共享内存的`SHM_DEST`）
+    pub mode: u32,
+    pub seq: u16,
+    __pad: u16,
+    __unused: [usize; 2],
+}
+
+impl IpcPerm {
+    /// 新对象的属主与创建者都是当前任务的有效用户与组
+    fn new(key: i32, mode: u32, cred: &Credentials) -> Self {
+        Self {
+            key,
+            uid: cred.euid,
+            gid: cred.egid,
+            cuid: cred.euid,
+            cgid: cred.egid,
+            mode: mode & 0o777,
+            ..Default::default()
+        }
+    }
+    /// 按属主（或创建者）、属组、其他人的顺序选出一组权限位，检查是否包含`flag`请求的访问。
+    /// `flag`中三组权限位的并集为请求的访问，拥有CAP_IPC_OWNER时不受限制
+    pub fn check(&self, flag: u32) -> Result<(), isize> {
+        let task = current_task().unwrap();
+        let cred = task.cred.lock();
+        let requested = (flag >> 6 | flag >> 3 | flag) & 0o7;
+        let granted = if cred.euid == self.uid || cred.euid == self.cuid {
+            self.mode >> 6
+        } else if cred.in_group(self.gid) || cred.in_group(self.cgid) {
+            self.mode >> 3
+        } else {
+            self.mode
+        };
+        if requested & !granted & 0o7 == 0 || cred.capable(Capabilities::CAP_IPC_OWNER) {
+            Ok(())
+        } else {
+            Err(EACCES)
+        }
+    }
+    /// `IPC_SET`与`IPC_RMID`只允许属主、创建者和拥有CAP_SYS_ADMIN的任务执行
+    pub fn check_owner(&self) -> Result<(), isize> {
+        let task = current_task().unwrap();
+        let cred = task.cred.lock();
+        if cred.euid == self.uid
+            || cred.euid == self.cuid
+            || cred.capable(Capabilities::CAP_SYS_ADMIN)
+        {
+            Ok(())
+        } else {
+            Err(EPERM)
+        }
+    }
+    /// `IPC_SET`只修改属主、属组与权限位
+    pub fn set(&mut self, new: &IpcPerm) {
+        self.uid = new.uid;
+        self.gid = new.gid;
+        self.mode = self.mode & !0o777 | new.mode & 0o777;
+    }
+}
+
+/// 编号表中的对象
+pub trait IpcObject {
+    fn perm(&self) -> IpcPerm;
+}
+
+/// 同一类对象的编号表
+pub struct IpcIds<T: IpcObject> {
+    objects: BTreeMap<i32, Arc<T>>,
+    /// 键值到编号的映射，`IPC_PRIVATE`创建的对象和已删除的对象不在其中
+    keys: BTreeMap<i32, i32>,
+    seq: u16,
+    /// 对象数量上限
+    max: usize,
+}
+
+impl<T: IpcObject> IpcIds<T> {
+    fn new(max: usize) -> Self {
+        Self {
+            objects: BTreeMap::new(),
+            keys: BTreeMap::new(),
+            seq: 0,
+            max,
+        }
+    }
+    pub fn get(&self, id: i32) -> Result<Arc<T>, isize> {
+        self.objects.get(&id).cloned().ok_or(EINVAL)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&i32, &Arc<T>)> {
+        self.objects.iter()
+    }
+    /// 从编号表中删除对象，仍持有对象的任务可以继续访问它
+    pub fn remove(&mut self, id: i32) -> Option<Arc<T>> {
+        self.forget_key(id);
+        self.objects.remove(&id)
+    }
+    /// 对象不再能通过键值找到，但编号仍然有效
+    pub fn forget_key(&mut self, id: i32) {
+        self.keys.retain(|_, value| *value != id);
+    }
+    /// 选出最小的空闲下标分配编号，由`create`根据权限创建对象
+    fn insert(
+        &mut self,
+        mut perm: IpcPerm,
+        create: impl FnOnce(i32, IpcPerm) -> Result<T, isize>,
+    ) -> Result<i32, isize> {
+        if self.objects.len() >= self.max {
+            return Err(ENOSPC);
+        }
+        let idx = (0..IPCMNI)
+            .find(|idx| !self.objects.keys().any(|id| id % IPCMNI == *idx))
+            .ok_or(ENOSPC)?;
+        perm.seq = self.seq;
+        let id = self.seq as i32 * IPCMNI + idx;
+        self.seq = (self.seq + 1) % (i32::MAX / IPCMNI) as u16;
+        let object = create(id, perm)?;
+        if perm.key != IPC_PRIVATE {
+            self.keys.insert(perm.key, id);
+        }
+        self.objects.insert(id, Arc::new(object));
+        Ok(id)
+    }
+}
+
+/// `shmget()`、`semget()`与`msgget()`共同的查找与创建流程。
+/// 键值对应的对象已存在时检查权限，再由`check`检查参数（如共享内存段的大小）；
+/// 否则在`IPC_CREAT`时用`create`创建新对象
+pub fn ipcget<T: IpcObject>(
+    ids: &mut IpcIds<T>,
+    key: i32,
+    flags: u32,
+    check: impl FnOnce(&T) -> Result<(), isize>,
+    create: impl FnOnce(i32, IpcPerm) -> Result<T, isize>,
+) -> Result<i32, isize> {
+    if key != IPC_PRIVATE {
+        if let Some(id) = ids.keys.get(&key).copied() {
+            if flags & IPC_CREAT != 0 && flags & IPC_EXCL != 0 {
+                return Err(EEXIST);
+            }
+            let object = ids.get(id)?;
+            object.perm().check(flags)?;
+            check(&object)?;
+            return Ok(id);
+        }
+        if flags & IPC_CREAT == 0 {
+            return Err(ENOENT);
+        }
+    }
+    let perm = {
+        let task = current_task().unwrap();
+        let cred = task.cred.lock();
+        IpcPerm::new(key, flags, &cred)
+    };
+    ids.insert(perm, create)
+}
+
+/// 每类对象的编号表。目前系统中只有这一个IPC命名空间
+pub struct IpcNamespace {
+    pub shm_ids: Mutex<IpcIds<ShmSegment>>,
+    pub sem_ids: Mutex<IpcIds<SemSet>>,
+    pub msg_ids: Mutex<IpcIds<MsgQueue>>,
+}
+
+lazy_static! {
+    pub static ref IPC_NS: IpcNamespace = IpcNamespace {
+        shm_ids: Mutex::new(IpcIds::new(shm::SHMMNI)),
+        sem_ids: Mutex::new(IpcIds::new(sem::SEMMNI)),
+        msg_ids: Mutex::new(IpcIds::new(msg::MSGMNI)),
+    };
+}
+
+/// 线程组退出时撤销它在信号量上的`SEM_UNDO`操作，并回收已标记删除且无人映射的共享内存段
+pub fn exit_ipc(tgid: usize) {
+    sem::exit_sem(tgid);
+    shm::shm_reap(&mut IPC_NS.shm_ids.lock());
+}
@@ -0,0 +1,247 @@
+use super::{
+    ipcget, IpcObject, IpcPerm, IPC_64, IPC_NOWAIT, IPC_NS, IPC_READ, IPC_RMID, IPC_SET, IPC_STAT,
+    IPC_WRITE,
+};
+use crate::{
+    fs::poll::{signal_pending, PollEvent, PollWaitQueue, PollWaiter},
+    syscall::errno::{E2BIG, EAGAIN, EIDRM, EINTR, EINVAL, ENOMSG, EPERM},
+    task::{capable, current_task, Capabilities},
+    timer::get_time_sec,
+};
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+use spin::Mutex;
+
+/// `msgrcv()`的标志：消息过长时截断而不是返回E2BIG
+pub const MSG_NOERROR: u32 = 0o10000;
+/// `msgtyp`大于0时，接收第一条类型不等于`msgtyp`的消息
+pub const MSG_EXCEPT: u32 = 0o20000;
+
+/// 单条消息的最大长度
+pub const MSGMAX: usize = 8192;
+/// 队列中消息总长度的默认上限
+pub const MSGMNB: usize = 16384;
+pub const MSGMNI: usize = 32000;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+/// `struct msqid64_ds`
+pub struct MsqidDs {
+    pub msg_perm: IpcPerm,
+    pub msg_stime: usize,
+    pub msg_rtime: usize,
+    pub msg_ctime: usize,
+    pub msg_cbytes: usize,
+    pub msg_qnum: usize,
+    pub msg_qbytes: usize,
+    pub msg_lspid: i32,
+    pub msg_lrpid: i32,
+    __unused: [usize; 2],
+}
+
+struct Msg {
+    mtype: isize,
+    text: Vec<u8>,
+}
+
+struct MsgState {
+    perm: IpcPerm,
+    stime: usize,
+    rtime: usize,
+    ctime: usize,
+    messages: VecDeque<Msg>,
+    /// 队列中消息正文的总长度
+    cbytes: usize,
+    /// `cbytes`与消息数量的上限
+    qbytes: usize,
+    /// 最后一次发送与接收的进程
+    lspid: usize,
+    lrpid: usize,
+    /// 已被`IPC_RMID`删除，等待中的任务醒来后返回EIDRM
+    removed: bool,
+}
+
+impl MsgState {
+    /// 按`msgtyp`选出要接收的消息：为0时取第一条；大于0时取第一条类型等于（`MSG_EXCEPT`时为不等于）
+    /// `msgtyp`的消息；小于0时取类型不超过`msgtyp`绝对值的消息中类型最小的第一条
+    fn find(&self, msgtyp: isize, flags: u32) -> Option<usize> {
+        let mut iter = self.messages.iter().enumerate();
+        if msgtyp == 0 {
+            iter.next().map(|(idx, _)| idx)
+        } else if msgtyp > 0 {
+            let except = flags & MSG_EXCEPT != 0;
+            iter.find(|(_, msg)| (msg.mtype == msgtyp) != except)
+                .map(|(idx, _)| idx)
+        } else {
+            iter.filter(|(_, msg)| msg.mtype <= -msgtyp)
+                .min_by_key(|(idx, msg)| (msg.mtype, *idx))
+                .map(|(idx, _)| idx)
+        }
+    }
+}
+
+/// System V消息队列
+pub struct MsgQueue {
+    state: Mutex<MsgState>,
+    /// 有新消息（POLLIN）、有空间（POLLOUT）或队列被删除时唤醒等待者
+    wait_queue: Arc<PollWaitQueue>,
+}
+
+impl IpcObject for MsgQueue {
+    fn perm(&self) -> IpcPerm {
+        self.state.lock().perm
+    }
+}
+
+pub fn msgget(key: i32, flags: u32) -> Result<i32, isize> {
+    let mut ids = IPC_NS.msg_ids.lock();
+    ipcget(
+        &mut ids,
+        key,
+        flags,
+        |_| Ok(()),
+        |_, perm| {
+            Ok(MsgQueue {
+                state: Mutex::new(MsgState {
+                    perm,
+                    stime: 0,
+                    rtime: 0,
+                    ctime: get_time_sec(),
+                    messages: VecDeque::new(),
+                    cbytes: 0,
+                    qbytes: MSGMNB,
+                    lspid: 0,
+                    lrpid: 0,
+                    removed: false,
+                }),
+                wait_queue: Arc::new(PollWaitQueue::new()),
+            })
+        },
+    )
+}
+
+/// 发送一条消息，队列已满时睡眠直到有空间或收到信号
+pub fn msgsnd(msqid: i32, mtype: isize, text: Vec<u8>, flags: u32) -> Result<(), isize> {
+    if mtype < 1 || text.len() > MSGMAX {
+        return Err(EINVAL);
+    }
+    let queue = IPC_NS.msg_ids.lock().get(msqid)?;
+    queue.state.lock().perm.check(IPC_WRITE)?;
+    let mut waiter = PollWaiter::new();
+    waiter.register_queue(Some(queue.wait_queue.clone()), PollEvent::POLLOUT);
+    loop {
+        let mut state = queue.state.lock();
+        if state.removed {
+            return Err(EIDRM);
+        }
+        if text.len() > state.qbytes {
+            return Err(EINVAL);
+        }
+        // 消息数量也不能超过上限，避免大量空消息占满内存
+        if state.cbytes + text.len() <= state.qbytes && state.messages.len() < state.qbytes {
+            state.cbytes += text.len();
+            state.messages.push_back(Msg { mtype, text });
+            state.stime = get_time_sec();
+            state.lspid = current_task().unwrap().tgid;
+            drop(state);
+            queue.wait_queue.notify(PollEvent::POLLIN);
+            return Ok(());
+        }
+        drop(state);
+        if flags & IPC_NOWAIT != 0 {
+            return Err(EAGAIN);
+        }
+        if signal_pending() {
+            return Err(EINTR);
+        }
+        waiter.sleep(None);
+    }
+}
+
+/// 接收一条消息，返回消息类型与正文（最长`msgsz`字节）。
+/// 没有符合条件的消息时睡眠直到有新消息或收到信号
+pub fn msgrcv(
+    msqid: i32,
+    msgsz: usize,
+    msgtyp: isize,
+    flags: u32,
+) -> Result<(isize, Vec<u8>), isize> {
+    let queue = IPC_NS.msg_ids.lock().get(msqid)?;
+    queue.state.lock().perm.check(IPC_READ)?;
+    let mut waiter = PollWaiter::new();
+    waiter.register_queue(Some(queue.wait_queue.clone()), PollEvent::POLLIN);
+    loop {
+        let mut state = queue.state.lock();
+        if state.removed {
+            return Err(EIDRM);
+        }
+        if let Some(idx) = state.find(msgtyp, flags) {
+            // 过长的消息留在队列中
+            if state.messages[idx].text.len() > msgsz && flags & MSG_NOERROR == 0 {
+                return Err(E2BIG);
+            }
+            let mut msg = state.messages.remove(idx).unwrap();
+            state.cbytes -= msg.text.len();
+            state.rtime = get_time_sec();
+            state.lrpid = current_task().unwrap().tgid;
+            drop(state);
+            queue.wait_queue.notify(PollEvent::POLLOUT);
+            msg.text.truncate(msgsz);
+            return Ok((msg.mtype, msg.text));
+        }
+        drop(state);
+        if flags & IPC_NOWAIT != 0 {
+            return Err(ENOMSG);
+        }
+        if signal_pending() {
+            return Err(EINTR);
+        }
+        waiter.sleep(None);
+    }
+}
+
+pub fn msgctl(msqid: i32, cmd: u32, buf: &mut MsqidDs) -> Result<(), isize> {
+    let mut ids = IPC_NS.msg_ids.lock();
+    let queue = ids.get(msqid)?;
+    let mut state = queue.state.lock();
+    match cmd & !IPC_64 {
+        IPC_STAT => {
+            state.perm.check(IPC_READ)?;
+            *buf = MsqidDs {
+                msg_perm: state.perm,
+                msg_stime: state.stime,
+                msg_rtime: state.rtime,
+                msg_ctime: state.ctime,
+                msg_cbytes: state.cbytes,
+                msg_qnum: state.messages.len(),
+                msg_qbytes: state.qbytes,
+                msg_lspid: state.lspid as i32,
+                msg_lrpid: state.lrpid as i32,
+                ..Default::default()
+            };
+        }
+        IPC_SET => {
+            state.perm.check_owner()?;
+            // 超过系统默认值需要CAP_SYS_RESOURCE
+            if buf.msg_qbytes > MSGMNB && !capable(Capabilities::CAP_SYS_RESOURCE) {
+                return Err(EPERM);
+            }
+            state.perm.set(&buf.msg_perm);
+            state.qbytes = buf.msg_qbytes;
+            state.ctime = get_time_sec();
+            drop(state);
+            // 上限可能变大，让等待的发送者重新检查
+            queue.wait_queue.notify(PollEvent::POLLOUT);
+        }
+        IPC_RMID => {
+            state.perm.check_owner()?;
+            state.removed = true;
+            drop(state);
+            ids.remove(msqid);
+            queue
+                .wait_queue
+                .notify(PollEvent::POLLIN | PollEvent::POLLOUT);
+        }
+        _ => return Err(EINVAL),
+    }
+    Ok(())
+}
@@ -0,0 +1,373 @@
+use super::{
+    ipcget, IpcObject, IpcPerm, IPC_64, IPC_NOWAIT, IPC_NS, IPC_READ, IPC_RMID, IPC_SET, IPC_STAT,
+    IPC_WRITE,
+};
+use crate::{
+    fs::poll::{signal_pending, PollEvent, PollWaitQueue, PollWaiter},
+    syscall::errno::{E2BIG, EAGAIN, EFBIG, EIDRM, EINTR, EINVAL, ERANGE},
+    task::current_task,
+    timer::{get_time_sec, TimeSpec},
+};
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use spin::Mutex;
+
+/// `sembuf.sem_flg`：进程退出时撤销这次操作
+pub const SEM_UNDO: i16 = 0x1000;
+
+/// `semctl()`的命令
+pub const GETPID: u32 = 11;
+pub const GETVAL: u32 = 12;
+pub const GETALL: u32 = 13;
+pub const GETNCNT: u32 = 14;
+pub const GETZCNT: u32 = 15;
+pub const SETVAL: u32 = 16;
+pub const SETALL: u32 = 17;
+
+/// 每个信号量集中信号量的数量上限
+pub const SEMMSL: usize = 32000;
+pub const SEMMNI: usize = 32000;
+/// 每次`semop()`的操作数量上限
+pub const SEMOPM: usize = 500;
+/// 信号量的最大值
+pub const SEMVMX: i32 = 32767;
+/// 撤销值的绝对值上限
+pub const SEMAEM: i32 = SEMVMX;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+/// `struct sembuf`
+pub struct SemBuf {
+    pub sem_num: u16,
+    pub sem_op: i16,
+    pub sem_flg: i16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+/// `struct semid64_ds`
+pub struct SemidDs {
+    pub sem_perm: IpcPerm,
+    pub sem_otime: usize,
+    pub sem_ctime: usize,
+    pub sem_nsems: usize,
+    __unused: [usize; 2],
+}
+
+#[derive(Clone, Copy, Default)]
+struct Sem {
+    val: i32,
+    /// 最后一次操作该信号量的进程
+    pid: usize,
+    /// 等待信号量增加的任务数
+    ncnt: usize,
+    /// 等待信号量变为0的任务数
+    zcnt: usize,
+}
+
+struct SemState {
+    perm: IpcPerm,
+    otime: usize,
+    ctime: usize,
+    sems: Vec<Sem>,
+    /// 各线程组带`SEM_UNDO`的操作累计的撤销值，退出时加回信号量
+    undo: BTreeMap<usize, Vec<i32>>,
+    /// 已被`IPC_RMID`删除，等待中的任务醒来后返回EIDRM
+    removed: bool,
+}
+
+/// 一次`semop()`无法立即完成的原因
+#[derive(Clone, Copy)]
+enum SemBlock {
+    /// 等待信号量增加
+    Decrease(usize),
+    /// 等待信号量变为0
+    Zero(usize),
+}
+
+impl SemState {
+    /// 原子地执行`sops`中的全部操作，任一操作需要等待时不修改任何信号量
+    fn try_semop(&mut self, sops: &[SemBuf], tgid: usize) -> Result<Option<SemBlock>, isize> {
+        let mut vals: Vec<i32> = self.sems.iter().map(|sem| sem.val).collect();
+        for sop in sops {
+            let num = sop.sem_num as usize;
+            let val = vals[num] + sop.sem_op as i32;
+            if sop.sem_op == 0 && vals[num] != 0 {
+                return match sop.sem_flg as u32 & IPC_NOWAIT {
+                    0 => Ok(Some(SemBlock::Zero(num))),
+                    _ => Err(EAGAIN),
+                };
+            }
+            if val < 0 {
+                return match sop.sem_flg as u32 & IPC_NOWAIT {
+                    0 => Ok(Some(SemBlock::Decrease(num))),
+                    _ => Err(EAGAIN),
+                };
+            }
+            if val > SEMVMX {
+                return Err(ERANGE);
+            }
+            vals[num] = val;
+        }
+        if sops.iter().any(|sop| sop.sem_flg & SEM_UNDO != 0) {
+            let nsems = self.sems.len();
+            let undo = self.undo.entry(tgid).or_insert_with(|| vec![0; nsems]);
+            let mut adj = undo.clone();
+            for sop in sops.iter().filter(|sop| sop.sem_flg & SEM_UNDO != 0) {
+                let num = sop.sem_num as usize;
+                adj[num] -= sop.sem_op as i32;
+                if adj[num] < -SEMAEM - 1 || adj[num] > SEMAEM {
+                    return Err(ERANGE);
+                }
+            }
+            *undo = adj;
+        }
+        for sop in sops {
+            let sem = &mut self.sems[sop.sem_num as usize];
+            sem.val = vals[sop.sem_num as usize];
+            sem.pid = tgid;
+        }
+        self.otime = get_time_sec();
+        Ok(None)
+    }
+    /// 直接设置信号量的值后，各进程对它的撤销值都失效
+    fn clear_undo(&mut self, num: Option<usize>) {
+        for adj in self.undo.values_mut() {
+            match num {
+                Some(num) => adj[num] = 0,
+                None => adj.fill(0),
+            }
+        }
+    }
+}
+
+/// System V信号量集
+pub struct SemSet {
+    state: Mutex<SemState>,
+    /// 信号量的值改变或信号量集被删除时唤醒等待者
+    wait_queue: Arc<PollWaitQueue>,
+}
+
+impl IpcObject for SemSet {
+    fn perm(&self) -> IpcPerm {
+        self.state.lock().perm
+    }
+}
+
+impl SemSet {
+    fn notify(&self) {
+        self.wait_queue.notify(PollEvent::POLLIN);
+    }
+}
+
+pub fn semget(key: i32, nsems: usize, flags: u32) -> Result<i32, isize> {
+    let mut ids = IPC_NS.sem_ids.lock();
+    ipcget(
+        &mut ids,
+        key,
+        flags,
+        |set| {
+            if nsems > set.state.lock().sems.len() {
+                Err(EINVAL)
+            } else {
+                Ok(())
+            }
+        },
+        |_, perm| {
+            if nsems == 0 || nsems > SEMMSL {
+                return Err(EINVAL);
+            }
+            Ok(SemSet {
+                state: Mutex::new(SemState {
+                    perm,
+                    otime: 0,
+                    ctime: get_time_sec(),
+                    sems: vec![Sem::default(); nsems],
+                    undo: BTreeMap::new(),
+                    removed: false,
+                }),
+                wait_queue: Arc::new(PollWaitQueue::new()),
+            })
+        },
+    )
+}
+
+/// 执行一组信号量操作，无法立即完成时睡眠直到可以完成、`timeout`到期（返回EAGAIN）或收到信号
+pub fn semtimedop(semid: i32, sops: &[SemBuf], timeout: Option<TimeSpec>) -> Result<(), isize> {
+    if sops.is_empty() {
+        return Err(EINVAL);
+    }
+    if sops.len() > SEMOPM {
+        return Err(E2BIG);
+    }
+    let set = IPC_NS.sem_ids.lock().get(semid)?;
+    let alter = sops.iter().any(|sop| sop.sem_op != 0);
+    {
+        let state = set.state.lock();
+        if sops
+            .iter()
+            .any(|sop| sop.sem_num as usize >= state.sems.len())
+        {
+            return Err(EFBIG);
+        }
+        state.perm.check(if alter { IPC_WRITE } else { IPC_READ })?;
+    }
+    let tgid = current_task().unwrap().tgid;
+    let mut waiter = PollWaiter::new();
+    waiter.register_queue(Some(set.wait_queue.clone()), PollEvent::POLLIN);
+    loop {
+        let mut state = set.state.lock();
+        if state.removed {
+            return Err(EIDRM);
+        }
+        let block = match state.try_semop(sops, tgid)? {
+            Some(block) => block,
+            None => {
+                drop(state);
+                if alter {
+                    set.notify();
+                }
+                return Ok(());
+            }
+        };
+        if signal_pending() {
+            return Err(EINTR);
+        }
+        if timeout.map_or(false, |timeout| TimeSpec::now() >= timeout) {
+            return Err(EAGAIN);
+        }
+        match block {
+            SemBlock::Decrease(num) => state.sems[num].ncnt += 1,
+            SemBlock::Zero(num) => state.sems[num].zcnt += 1,
+        }
+        drop(state);
+        waiter.sleep(timeout);
+        let mut state = set.state.lock();
+        match block {
+            SemBlock::Decrease(num) => state.sems[num].ncnt -= 1,
+            SemBlock::Zero(num) => state.sems[num].zcnt -= 1,
+        }
+    }
+}
+
+/// `semctl()`的参数`union semun`，由调用者按命令从用户空间读出或写回
+pub enum SemctlArg<'a> {
+    Val(i32),
+    Array(&'a mut Vec<u16>),
+    Buf(&'a mut SemidDs),
+}
+
+/// 返回`GET*`命令读出的值，其余命令返回0
+pub fn semctl(semid: i32, semnum: usize, cmd: u32, arg: SemctlArg) -> Result<isize, isize> {
+    let mut ids = IPC_NS.sem_ids.lock();
+    let set = ids.get(semid)?;
+    let mut state = set.state.lock();
+    let cmd = cmd & !IPC_64;
+    if matches!(cmd, GETVAL | GETPID | GETNCNT | GETZCNT | SETVAL) && semnum >= state.sems.len() {
+        return Err(EINVAL);
+    }
+    let ret = match (cmd, arg) {
+        (IPC_STAT, SemctlArg::Buf(buf)) => {
+            state.perm.check(IPC_READ)?;
+            *buf = SemidDs {
+                sem_perm: state.perm,
+                sem_otime: state.otime,
+                sem_ctime: state.ctime,
+                sem_nsems: state.sems.len(),
+                ..Default::default()
+            };
+            0
+        }
+        (IPC_SET, SemctlArg::Buf(buf)) => {
+            state.perm.check_owner()?;
+            state.perm.set(&buf.sem_perm);
+            state.ctime = get_time_sec();
+            0
+        }
+        (IPC_RMID, _) => {
+            state.perm.check_owner()?;
+            state.removed = true;
+            drop(state);
+            ids.remove(semid);
+            set.notify();
+            return Ok(0);
+        }
+        (GETVAL | GETPID | GETNCNT | GETZCNT, _) => {
+            state.perm.check(IPC_READ)?;
+            let sem = &state.sems[semnum];
+            (match cmd {
+                GETVAL => sem.val as usize,
+                GETPID => sem.pid,
+                GETNCNT => sem.ncnt,
+                _ => sem.zcnt,
+            }) as isize
+        }
+        (GETALL, SemctlArg::Array(array)) => {
+            state.perm.check(IPC_READ)?;
+            *array = state.sems.iter().map(|sem| sem.val as u16).collect();
+            0
+        }
+        (SETVAL, SemctlArg::Val(val)) => {
+            state.perm.check(IPC_WRITE)?;
+            if !(0..=SEMVMX).contains(&val) {
+                return Err(ERANGE);
+            }
+            let tgid = current_task().unwrap().tgid;
+            state.sems[semnum].val = val;
+            state.sems[semnum].pid = tgid;
+            state.clear_undo(Some(semnum));
+            state.ctime = get_time_sec();
+            drop(state);
+            set.notify();
+            return Ok(0);
+        }
+        (SETALL, SemctlArg::Array(array)) => {
+            state.perm.check(IPC_WRITE)?;
+            if array.iter().any(|val| *val as i32 > SEMVMX) {
+                return Err(ERANGE);
+            }
+            let tgid = current_task().unwrap().tgid;
+            for (sem, val) in state.sems.iter_mut().zip(array.iter()) {
+                sem.val = *val as i32;
+                sem.pid = tgid;
+            }
+            state.clear_undo(None);
+            state.ctime = get_time_sec();
+            drop(state);
+            set.notify();
+            return Ok(0);
+        }
+        _ => return Err(EINVAL),
+    };
+    Ok(ret)
+}
+
+/// 信号量集中信号量的数量，`GETALL`与`SETALL`据此确定数组长度
+pub fn sem_nsems(semid: i32) -> Result<usize, isize> {
+    let set = IPC_NS.sem_ids.lock().get(semid)?;
+    let nsems = set.state.lock().sems.len();
+    Ok(nsems)
+}
+
+/// 线程组退出时将它累计的撤销值加回各信号量，结果限制在0与`SEMVMX`之间
+pub fn exit_sem(tgid: usize) {
+    let ids = IPC_NS.sem_ids.lock();
+    for (_, set) in ids.iter() {
+        let mut state = set.state.lock();
+        let adj = match state.undo.remove(&tgid) {
+            Some(adj) => adj,
+            None => continue,
+        };
+        if adj.iter().all(|adj| *adj == 0) {
+            continue;
+        }
+        for (sem, adj) in state.sems.iter_mut().zip(adj) {
+            if adj != 0 {
+                sem.val = (sem.val + adj).max(0).min(SEMVMX);
+                sem.pid = tgid;
+            }
+        }
+        state.otime = get_time_sec();
+        drop(state);
+        set.notify();
+    }
+}
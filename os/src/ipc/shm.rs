@@ -0,0 +1,281 @@
+use super::{
+    ipcget, IpcIds, IpcObject, IpcPerm, IPC_64, IPC_NS, IPC_PRIVATE, IPC_READ, IPC_RMID, IPC_SET,
+    IPC_STAT, IPC_WRITE,
+};
+use crate::{
+    config::PAGE_SIZE,
+    fs::{file_trait::File, memfd::MemFd},
+    mm::MapPermission,
+    syscall::errno::EINVAL,
+    task::{capable, current_task, Capabilities},
+    timer::get_time_sec,
+};
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use spin::Mutex;
+
+/// `shmat()`的标志
+pub const SHM_RDONLY: u32 = 0o10000;
+/// 将地址向下对齐到`SHMLBA`
+pub const SHM_RND: u32 = 0o20000;
+/// 替换地址范围内已有的映射
+pub const SHM_REMAP: u32 = 0o40000;
+pub const SHM_EXEC: u32 = 0o100000;
+
+/// `shmctl()`的命令
+pub const SHM_LOCK: u32 = 11;
+pub const SHM_UNLOCK: u32 = 12;
+
+/// `mode`中的状态位：段已被`IPC_RMID`标记删除，最后一次解除映射后销毁
+pub const SHM_DEST: u32 = 0o1000;
+/// 段已被`SHM_LOCK`锁定
+pub const SHM_LOCKED: u32 = 0o2000;
+
+/// 映射地址的对齐要求
+pub const SHMLBA: usize = PAGE_SIZE;
+pub const SHMMIN: usize = 1;
+pub const SHMMAX: usize = usize::MAX - (1 << 24);
+pub const SHMMNI: usize = 4096;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+/// `struct shmid64_ds`
+pub struct ShmidDs {
+    pub shm_perm: IpcPerm,
+    pub shm_segsz: usize,
+    pub shm_atime: usize,
+    pub shm_dtime: usize,
+    pub shm_ctime: usize,
+    pub shm_cpid: i32,
+    pub shm_lpid: i32,
+    pub shm_nattch: usize,
+    __unused: [usize; 2],
+}
+
+struct ShmState {
+    perm: IpcPerm,
+    atime: usize,
+    dtime: usize,
+    ctime: usize,
+    /// 最后一次`shmat()`或`shmdt()`的进程
+    lpid: usize,
+    /// 每次`shmat()`得到的映射文件。fork复制的区域与切开的区域都与原区域共用同一个`Arc`，
+    /// 因此所有强引用计数之和就是映射数
+    attaches: Vec<Weak<dyn File>>,
+}
+
+impl ShmState {
+    fn nattch(&self) -> usize {
+        self.attaches.iter().map(Weak::strong_count).sum()
+    }
+}
+
+/// System V共享内存段，内容保存在一个memfd中，映射时与共享的memfd映射相同
+pub struct ShmSegment {
+    size: usize,
+    cpid: usize,
+    memfd: MemFd,
+    state: Mutex<ShmState>,
+}
+
+impl IpcObject for ShmSegment {
+    fn perm(&self) -> IpcPerm {
+        self.state.lock().perm
+    }
+}
+
+impl ShmSegment {
+    fn new(perm: IpcPerm, size: usize) -> Result<Self, isize> {
+        let memfd = MemFd::new(false);
+        memfd.truncate_size(size)?;
+        Ok(Self {
+            size,
+            cpid: current_task().unwrap().tgid,
+            memfd,
+            state: Mutex::new(ShmState {
+                perm,
+                atime: 0,
+                dtime: 0,
+                ctime: get_time_sec(),
+                lpid: 0,
+                attaches: Vec::new(),
+            }),
+        })
+    }
+    /// `file`是否映射了这个段
+    fn is_mapped_by(&self, file: &Arc<dyn File>) -> bool {
+        file.downcast_ref::<MemFd>()
+            .map_or(false, |memfd| memfd.same_file(&self.memfd))
+    }
+    fn map_len(&self) -> usize {
+        (self.size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+    }
+}
+
+/// 销毁已被标记删除且不再被映射的段
+pub fn shm_reap(ids: &mut IpcIds<ShmSegment>) {
+    let dead: Vec<i32> = ids
+        .iter()
+        .filter(|(_, segment)| {
+            let state = segment.state.lock();
+            state.perm.mode & SHM_DEST != 0 && state.nattch() == 0
+        })
+        .map(|(id, _)| *id)
+        .collect();
+    for id in dead {
+        ids.remove(id);
+    }
+}
+
+pub fn shmget(key: i32, size: usize, flags: u32) -> Result<i32, isize> {
+    let mut ids = IPC_NS.shm_ids.lock();
+    shm_reap(&mut ids);
+    ipcget(
+        &mut ids,
+        key,
+        flags,
+        |segment| {
+            if size > segment.size {
+                Err(EINVAL)
+            } else {
+                Ok(())
+            }
+        },
+        |_, perm| {
+            if size < SHMMIN || size > SHMMAX {
+                return Err(EINVAL);
+            }
+            ShmSegment::new(perm, size)
+        },
+    )
+}
+
+/// 映射共享内存段，返回映射的起始地址
+pub fn shmat(shmid: i32, addr: usize, flags: u32) -> Result<usize, isize> {
+    let segment = IPC_NS.shm_ids.lock().get(shmid)?;
+    let addr = if addr & (SHMLBA - 1) == 0 {
+        addr
+    } else if flags & SHM_RND != 0 {
+        addr & !(SHMLBA - 1)
+    } else {
+        return Err(EINVAL);
+    };
+    let (mut mask, mut prot) = if flags & SHM_RDONLY != 0 {
+        (IPC_READ, MapPermission::R | MapPermission::U)
+    } else {
+        (
+            IPC_READ | IPC_WRITE,
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        )
+    };
+    if flags & SHM_EXEC != 0 {
+        mask |= 0o111;
+        prot |= MapPermission::X;
+    }
+    let task = current_task().unwrap();
+    let mut state = segment.state.lock();
+    state.perm.check(mask)?;
+    let file = segment.memfd.mmap_shared(prot.contains(MapPermission::W))?;
+    let start = task.vm.lock().shmat(
+        addr,
+        segment.map_len(),
+        prot,
+        file.clone(),
+        flags & SHM_REMAP != 0,
+    )?;
+    state.attaches.retain(|file| file.strong_count() > 0);
+    state.attaches.push(Arc::downgrade(&file));
+    state.atime = get_time_sec();
+    state.lpid = task.tgid;
+    Ok(start)
+}
+
+/// 解除`addr`处共享内存段的映射。映射可能已被`mprotect()`分成多个区域，
+/// 依次解除其中属于同一个段的部分
+pub fn shmdt(addr: usize) -> Result<(), isize> {
+    if addr & (SHMLBA - 1) != 0 {
+        return Err(EINVAL);
+    }
+    let task = current_task().unwrap();
+    let mut ids = IPC_NS.shm_ids.lock();
+    let (file, _) = task.vm.lock().file_area_at(addr).ok_or(EINVAL)?;
+    let segment = ids
+        .iter()
+        .map(|(_, segment)| segment)
+        .find(|segment| segment.is_mapped_by(&file))
+        .cloned()
+        .ok_or(EINVAL)?;
+    drop(file);
+    let end = addr + segment.map_len();
+    let mut vm = task.vm.lock();
+    let mut cursor = addr;
+    while cursor < end {
+        match vm.file_area_at(cursor) {
+            Some((file, len)) if segment.is_mapped_by(&file) => {
+                drop(file);
+                vm.munmap(cursor, len)?;
+                cursor += len;
+            }
+            _ => break,
+        }
+    }
+    drop(vm);
+    let mut state = segment.state.lock();
+    state.dtime = get_time_sec();
+    state.lpid = task.tgid;
+    drop(state);
+    shm_reap(&mut ids);
+    Ok(())
+}
+
+pub fn shmctl(shmid: i32, cmd: u32, buf: &mut ShmidDs) -> Result<(), isize> {
+    let mut ids = IPC_NS.shm_ids.lock();
+    shm_reap(&mut ids);
+    let segment = ids.get(shmid)?;
+    let mut state = segment.state.lock();
+    match cmd & !IPC_64 {
+        IPC_STAT => {
+            state.perm.check(IPC_READ)?;
+            *buf = ShmidDs {
+                shm_perm: state.perm,
+                shm_segsz: segment.size,
+                shm_atime: state.atime,
+                shm_dtime: state.dtime,
+                shm_ctime: state.ctime,
+                shm_cpid: segment.cpid as i32,
+                shm_lpid: state.lpid as i32,
+                shm_nattch: state.nattch(),
+                ..Default::default()
+            };
+        }
+        IPC_SET => {
+            state.perm.check_owner()?;
+            state.perm.set(&buf.shm_perm);
+            state.ctime = get_time_sec();
+        }
+        IPC_RMID => {
+            state.perm.check_owner()?;
+            // 之后不能再通过键值找到这个段，已有的映射不受影响
+            state.perm.mode |= SHM_DEST;
+            state.perm.key = IPC_PRIVATE;
+            drop(state);
+            ids.forget_key(shmid);
+            shm_reap(&mut ids);
+        }
+        SHM_LOCK | SHM_UNLOCK => {
+            if !capable(Capabilities::CAP_IPC_LOCK) {
+                state.perm.check_owner()?;
+            }
+            // 共享内存段的页不会被换出，锁定只记录状态
+            if cmd & !IPC_64 == SHM_LOCK {
+                state.perm.mode |= SHM_LOCKED;
+            } else {
+                state.perm.mode &= !SHM_LOCKED;
+            }
+        }
+        _ => return Err(EINVAL),
+    }
+    Ok(())
+}
@@ -29,6 +29,7 @@ mod console;
 mod drivers;
 mod fs;
 mod hal;
+mod ipc;
 mod lang_items;
 mod math;
 mod mm;
@@ -38,8 +39,6 @@ mod task;
 mod timer;
 mod utils;
 
-#[cfg(feature = "block_mem")]
-use crate::config::DISK_IMAGE_BASE;
 use crate::hal::bootstrap_init;
 use crate::hal::machine_init;
 #[cfg(feature = "loongarch64")]
@@ -55,20 +54,12 @@ core::arch::global_asm!(include_str!("preload_app.S"));
 #[cfg(all(not(feature = "block_mem"), feature = "riscv"))]
 core::arch::global_asm!(include_str!("preload_app-rv.S"));
 
+/// 清空.bss，zero_init时其余的空闲内存在初始化帧分配器时清空
 fn mem_clear() {
     extern "C" {
         fn sbss();
         fn ebss();
     }
-    #[cfg(feature = "zero_init")]
-    unsafe {
-        core::slice::from_raw_parts_mut(
-            sbss as usize as *mut u8,
-            crate::config::MEMORY_END - sbss as usize,
-        )
-        .fill(0);
-    }
-    #[cfg(not(feature = "zero_init"))]
     unsafe {
         core::slice::from_raw_parts_mut(sbss as usize as *mut u8, ebss as usize - sbss as usize)
             .fill(0);
@@ -82,16 +73,18 @@ fn move_to_high_address() {
         fn simg();
         fn eimg();
     }
+    let info = hal::boot_info();
+    // 引导程序已经通过initrd加载了根文件系统镜像
+    if info.initrd.is_some() {
+        return;
+    }
+    let ram_disk = info.ram_disk.expect("[kernel] no room for the RAM disk");
     unsafe {
         // 加载根文件系统镜像
         let img =
             core::slice::from_raw_parts(simg as usize as *mut u8, eimg as usize - simg as usize);
-        // 以DISK_IMAGE_BASE到MEMORY_END上的内存作为根文件系统镜像
-        let mem_disk = core::slice::from_raw_parts_mut(
-            DISK_IMAGE_BASE as *mut u8,
-            // 大小为128MB
-            0x800_0000,
-        );
+        // 以启动时保留的内存作为根文件系统镜像
+        let mem_disk = core::slice::from_raw_parts_mut(ram_disk.start as *mut u8, ram_disk.len());
         // 清空mem_disk上的内容
         mem_disk.fill(0);
         // 将img上的所有内容copy到mem_disk上，可能是因为这一步
@@ -100,20 +93,24 @@ fn move_to_high_address() {
     }
 }
 
+/// 引导程序传入的`a0`到`a2`原样交给`hal::bootinfo::init`
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(arg0: usize, arg1: usize, arg2: usize) -> ! {
     bootstrap_init();
     mem_clear();
+    console::log_init();
+    println!("[kernel] Console initialized.");
+    hal::bootinfo::init(arg0, arg1, arg2);
     // 这一行可能有误，需要后续处理
     #[cfg(all(feature = "block_mem"))]
     move_to_high_address();
-    console::log_init();
-    println!("[kernel] Console initialized.");
     mm::init();
     println!("[kernel] Hello, world!");
     // note that remap_test is currently NOT supported by LA64, for the whole kernel space is RW!
     // #[cfg(feature = "riscv")]
     // mm::remap_test();
+    // #[cfg(feature = "zram")]
+    // mm::zram_test();
 
     machine_init();
 
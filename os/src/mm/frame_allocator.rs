@@ -1,12 +1,15 @@
 #[cfg(feature = "oom_handler")]
 use super::super::fs;
-use super::{PhysAddr, PhysPageNum};
-use crate::hal::MEMORY_END;
-#[cfg(feature = "oom_handler")]
-use crate::task::current_task;
+use super::page::{ppn_to_page, PageFlags};
+use super::PhysPageNum;
+use crate::config::{HUGE_PAGE_PAGES, PAGE_SIZE_BITS};
+use crate::hal::boot_info;
 
 use alloc::{sync::Arc, vec::Vec};
 use core::fmt::{self, Debug, Formatter};
+use core::mem::size_of;
+#[cfg(feature = "oom_handler")]
+use core::sync::atomic::{AtomicBool, Ordering};
 use lazy_static::*;
 use spin::RwLock;
 
@@ -23,9 +26,12 @@ impl FrameTracker {
         for i in dwords_array {
             *i = 0;
         }
-        Self { ppn }
+        unsafe { Self::new_uninit(ppn) }
     }
     pub unsafe fn new_uninit(ppn: PhysPageNum) -> Self {
+        if let Some(page) = ppn_to_page(ppn) {
+            page.init();
+        }
         Self { ppn }
     }
 }
@@ -36,153 +42,359 @@ impl Debug for FrameTracker {
     }
 }
 impl Drop for FrameTracker {
-    // 自动回收物理帧
+    // 自动回收物理帧，`get_page`固定的页由最后一个`put_page`回收
     fn drop(&mut self) {
         // println!("do drop at {}", self.ppn.0);
-        frame_dealloc(self.ppn);
+        if ppn_to_page(self.ppn).map_or(true, |page| page.put()) {
+            frame_dealloc(self.ppn);
+        }
+    }
+}
+
+/// 伙伴系统的阶数，一次最多分配`1 << (MAX_ORDER - 1)`个连续的物理页
+pub const MAX_ORDER: usize = 11;
+
+/// 物理内存区域
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZoneType {
+    /// 低4GiB的物理内存，只能访问32位地址的DMA设备也可以使用
+    Dma32 = 0,
+    /// 其余物理内存
+    Normal = 1,
+}
+
+const ZONE_NUM: usize = 2;
+/// ZONE_DMA32的结束页号
+const DMA32_END_PPN: usize = 1 << (32 - PAGE_SIZE_BITS);
+/// 空闲链表的空指针
+const NIL: u32 = u32::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FrameState {
+    /// 空闲块的首页
+    Free,
+    /// 已分配块的首页
+    Allocated,
+    /// 块中除首页以外的页，状态由所在块的首页决定
+    Tail,
+}
+
+/// 物理页描述符，每个可分配的物理页对应一个
+#[derive(Clone, Copy)]
+struct FrameDesc {
+    state: FrameState,
+    /// 以该页为首页的块的阶
+    order: u8,
+    /// 自启动以来未被分配过，内容仍为初始值
+    fresh: bool,
+    /// 空闲链表中前后两个块的描述符下标
+    prev: u32,
+    next: u32,
+}
+
+/// 每个物理页的帧描述符占用的字节数，启动时据此保留帧描述符表
+pub const FRAME_DESC_SIZE: usize = size_of::<FrameDesc>();
+
+/// 一个物理内存区域，每一阶维护一条空闲块的双向链表
+struct Zone {
+    /// 区域的页号范围`[start, end)`
+    start: usize,
+    end: usize,
+    free_head: [u32; MAX_ORDER],
+    /// 每一阶的空闲块数
+    nr_free: [usize; MAX_ORDER],
+}
+
+impl Zone {
+    const fn empty() -> Self {
+        Self {
+            start: 0,
+            end: 0,
+            free_head: [NIL; MAX_ORDER],
+            nr_free: [0; MAX_ORDER],
+        }
+    }
+    fn contains(&self, ppn: usize) -> bool {
+        self.start <= ppn && ppn < self.end
     }
 }
 
 /// 帧分配器接口
 trait FrameAllocator {
     fn new() -> Self;
+    /// 分配`1 << order`个连续的物理页，返回首页页号
+    fn alloc_pages(&mut self, order: usize, zone: ZoneType) -> Option<PhysPageNum>;
+    /// 释放`alloc_pages`分配的块
+    fn dealloc_pages(&mut self, ppn: PhysPageNum, order: usize);
     /// 分配
     fn alloc(&mut self) -> Option<FrameTracker>;
     unsafe fn alloc_uninit(&mut self) -> Option<FrameTracker>;
     /// 释放
-    fn dealloc(&mut self, ppn: PhysPageNum);
+    fn dealloc(&mut self, ppn: PhysPageNum) {
+        self.dealloc_pages(ppn, 0);
+    }
 }
 
-/// 栈式帧分配器
-pub struct StackFrameAllocator {
-    // 当前分配器的位置，指向可分配区域的开始
-    current: usize,
-    // 分配器的结束地址，表示可分配内存区域的末尾
-    end: usize,
-    // 已回收的页面（内存框架）的列表
-    recycled: Vec<usize>,
+/// 伙伴系统帧分配器。
+/// 页描述符数组记录每个块的状态与阶，释放时可以O(1)地找到伙伴块并检查重复释放
+pub struct BuddyFrameAllocator {
+    /// 下标与页描述符表相同，见`BootInfo::ram_page_index`，存放在启动时保留的`BootInfo::frame_map`中
+    descs: &'static mut [FrameDesc],
+    zones: [Zone; ZONE_NUM],
+    /// 空闲页总数
+    free_frames: usize,
 }
 
-impl StackFrameAllocator {
-    /// 初始化方法
-    pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
-        self.current = l.0;
-        self.end = r.0;
-        let last_frames = self.end - self.current;
-        self.recycled.reserve(last_frames);
-        println!("last {} Physical Frames.", last_frames);
+impl BuddyFrameAllocator {
+    /// 初始化方法，为所有物理内存建立描述符，再把启动时没有保留的内存放入空闲链表
+    pub fn init(&mut self) {
+        let info = boot_info();
+        let l = info.memory.iter().next().unwrap().start >> PAGE_SIZE_BITS;
+        let r = info.memory.iter().last().unwrap().end >> PAGE_SIZE_BITS;
+        let base = info.frame_map.start as *mut FrameDesc;
+        assert!(info.frame_map.len() >= info.ram_pages() * FRAME_DESC_SIZE);
+        for idx in 0..info.ram_pages() {
+            let desc = FrameDesc {
+                state: FrameState::Tail,
+                order: 0,
+                fresh: true,
+                prev: NIL,
+                next: NIL,
+            };
+            unsafe { base.add(idx).write(desc) };
+        }
+        self.descs = unsafe { core::slice::from_raw_parts_mut(base, info.ram_pages()) };
+        self.zones[ZoneType::Dma32 as usize].start = l.min(DMA32_END_PPN);
+        self.zones[ZoneType::Dma32 as usize].end = r.min(DMA32_END_PPN);
+        self.zones[ZoneType::Normal as usize].start = l.max(DMA32_END_PPN);
+        self.zones[ZoneType::Normal as usize].end = r.max(DMA32_END_PPN);
+        info.for_each_free(|free| {
+            #[cfg(feature = "zero_init")]
+            unsafe {
+                core::slice::from_raw_parts_mut(free.start as *mut u8, free.len()).fill(0);
+            }
+            self.free_range(free.start >> PAGE_SIZE_BITS, free.end >> PAGE_SIZE_BITS);
+        });
+        println!("last {} Physical Frames.", self.free_frames);
+    }
+    /// 把`[l, r)`按对齐切成尽可能大的块放入空闲链表
+    fn free_range(&mut self, l: usize, r: usize) {
+        for zone in 0..ZONE_NUM {
+            let mut ppn = l.max(self.zones[zone].start);
+            let end = r.min(self.zones[zone].end);
+            while ppn < end {
+                let mut order = (ppn.trailing_zeros() as usize).min(MAX_ORDER - 1);
+                while ppn + (1 << order) > end {
+                    order -= 1;
+                }
+                self.push_free(zone, ppn, order);
+                self.free_frames += 1 << order;
+                ppn += 1 << order;
+            }
+        }
     }
     /// 计算未分配的大小
     pub fn unallocated_frames(&self) -> usize {
-        self.end - self.current + self.recycled.len()
+        self.free_frames
     }
-}
-
-impl FrameAllocator for StackFrameAllocator {
-    fn new() -> Self {
-        Self {
-            current: 0,
-            end: 0,
-            recycled: Vec::new(),
+    /// 区域中每一阶的空闲块数
+    pub fn free_area_stats(&self, zone: ZoneType) -> [usize; MAX_ORDER] {
+        self.zones[zone as usize].nr_free
+    }
+    /// 物理内存之间的空洞没有描述符
+    fn index(ppn: usize) -> Option<usize> {
+        boot_info().ram_page_index(ppn)
+    }
+    fn desc(&mut self, ppn: usize) -> &mut FrameDesc {
+        &mut self.descs[Self::index(ppn).unwrap()]
+    }
+    fn zone_of(&self, ppn: usize) -> usize {
+        if ppn < DMA32_END_PPN {
+            ZoneType::Dma32 as usize
+        } else {
+            ZoneType::Normal as usize
         }
     }
-
-    /// 分配一个物理页
-    fn alloc(&mut self) -> Option<FrameTracker> {
-        // 优先使用回收的帧
-        if let Some(ppn) = self.recycled.pop() {
-            let frame_tracker = FrameTracker::new(ppn.into());
-            log::trace!("[frame_alloc] {:?}", frame_tracker);
-            Some(frame_tracker)
-        } else if self.current == self.end {
-            // 无可用帧
-            None
+    /// 将以`ppn`为首页的块插入空闲链表头部
+    fn push_free(&mut self, zone: usize, ppn: usize, order: usize) {
+        let idx = Self::index(ppn).unwrap() as u32;
+        let head = self.zones[zone].free_head[order];
+        if head != NIL {
+            self.descs[head as usize].prev = idx;
+        }
+        let desc = self.desc(ppn);
+        desc.state = FrameState::Free;
+        desc.order = order as u8;
+        desc.prev = NIL;
+        desc.next = head;
+        self.zones[zone].free_head[order] = idx;
+        self.zones[zone].nr_free[order] += 1;
+    }
+    /// 将以`ppn`为首页的空闲块从链表中摘下，首页标记为`Tail`，由调用者设置新的状态
+    fn remove_free(&mut self, zone: usize, ppn: usize, order: usize) {
+        let FrameDesc { prev, next, .. } = *self.desc(ppn);
+        if prev != NIL {
+            self.descs[prev as usize].next = next;
         } else {
-            // 否则分配当前页
-            self.current += 1;
-            #[cfg(not(feature = "zero_init"))]
-            let frame_tracker = FrameTracker::new((self.current - 1).into());
-            #[cfg(feature = "zero_init")]
-            let frame_tracker = unsafe { FrameTracker::new_uninit((self.current - 1).into()) };
-            log::trace!("[frame_alloc] {:?}", frame_tracker);
-            Some(frame_tracker)
+            self.zones[zone].free_head[order] = next;
+        }
+        if next != NIL {
+            self.descs[next as usize].prev = prev;
         }
+        let desc = self.desc(ppn);
+        desc.state = FrameState::Tail;
+        desc.prev = NIL;
+        desc.next = NIL;
+        self.zones[zone].nr_free[order] -= 1;
     }
-    unsafe fn alloc_uninit(&mut self) -> Option<FrameTracker> {
-        if let Some(ppn) = self.recycled.pop() {
-            let frame_tracker = FrameTracker::new_uninit(ppn.into());
-            //log::trace!("[frame_alloc_uninit] {:?}", frame_tracker);
-            Some(frame_tracker)
-        } else if self.current == self.end {
-            None
+    fn alloc_from_zone(&mut self, zone: usize, order: usize) -> Option<usize> {
+        let mut current = (order..MAX_ORDER).find(|o| self.zones[zone].free_head[*o] != NIL)?;
+        let ppn = boot_info().ram_index_to_ppn(self.zones[zone].free_head[current] as usize);
+        self.remove_free(zone, ppn, current);
+        // 大块对半拆分，后一半放回低一阶的空闲链表
+        while current > order {
+            current -= 1;
+            self.push_free(zone, ppn + (1 << current), current);
+        }
+        let desc = self.desc(ppn);
+        desc.state = FrameState::Allocated;
+        desc.order = order as u8;
+        self.free_frames -= 1 << order;
+        Some(ppn)
+    }
+    /// 分配`pages`个连续的物理页，每一页都可以单独用`dealloc`释放。
+    /// 先分配能容纳`pages`的最小块，再把多出来的页还回去
+    pub fn alloc_contiguous(&mut self, pages: usize, zone: ZoneType) -> Option<PhysPageNum> {
+        if pages == 0 {
+            return None;
+        }
+        let order = pages.next_power_of_two().trailing_zeros() as usize;
+        let base = self.alloc_pages(order, zone)?.0;
+        for ppn in base..base + (1 << order) {
+            let desc = self.desc(ppn);
+            desc.state = FrameState::Allocated;
+            desc.order = 0;
+            desc.fresh = false;
+        }
+        for ppn in base + pages..base + (1 << order) {
+            self.dealloc_pages(ppn.into(), 0);
+        }
+        Some(base.into())
+    }
+    /// 分配内核堆与slab使用的块，块中的页都不再是初始值
+    fn alloc_kernel_pages(&mut self, order: usize) -> Option<PhysPageNum> {
+        let base = self.alloc_pages(order, ZoneType::Normal)?;
+        for ppn in base.0..base.0 + (1 << order) {
+            self.desc(ppn).fresh = false;
+        }
+        Some(base)
+    }
+    /// 分配一个物理页，`zeroed`为假时不清理页面内容
+    fn alloc_frame(&mut self, zeroed: bool) -> Option<FrameTracker> {
+        let ppn = self.alloc_pages(0, ZoneType::Normal)?;
+        let desc = self.desc(ppn.0);
+        // 启动后未使用过的页已经是0（zero_init）
+        let fresh = core::mem::replace(&mut desc.fresh, false);
+        if zeroed && !(cfg!(feature = "zero_init") && fresh) {
+            Some(FrameTracker::new(ppn))
         } else {
-            self.current += 1;
-            let frame_tracker = FrameTracker::new_uninit((self.current - 1).into());
-            log::trace!("[frame_alloc_uninit] {:?}", frame_tracker);
-            Some(frame_tracker)
+            Some(unsafe { FrameTracker::new_uninit(ppn) })
         }
     }
-    /// 释放一个物理页
-    fn dealloc(&mut self, ppn: PhysPageNum) {
+}
+
+impl FrameAllocator for BuddyFrameAllocator {
+    fn new() -> Self {
+        Self {
+            descs: &mut [],
+            zones: [Zone::empty(), Zone::empty()],
+            free_frames: 0,
+        }
+    }
+    /// 依次尝试请求的区域与更低的区域
+    fn alloc_pages(&mut self, order: usize, zone: ZoneType) -> Option<PhysPageNum> {
+        if order >= MAX_ORDER {
+            return None;
+        }
+        (0..=zone as usize)
+            .rev()
+            .find_map(|zone| self.alloc_from_zone(zone, order))
+            .map(PhysPageNum::from)
+    }
+    /// 释放块，并与空闲的伙伴块合并
+    fn dealloc_pages(&mut self, ppn: PhysPageNum, order: usize) {
         log::trace!("[frame_dealloc] {:?}", ppn);
-        let ppn = ppn.0;
-        // 验证帧的有效性（DEBUG模式下），RELEASE中这个检查不必要，并且这个检查可能会显著降低回收速度
-        if option_env!("MODE") == Some("debug") && ppn >= self.current
-            || self.recycled.iter().find(|&v| *v == ppn).is_some()
+        let mut ppn = ppn.0;
+        let zone = self.zone_of(ppn);
+        if !self.zones[zone].contains(ppn)
+            || Self::index(ppn).is_none()
+            || self.desc(ppn).state != FrameState::Allocated
+            || self.desc(ppn).order as usize != order
         {
             panic!("Frame ppn={:#x} has not been allocated!", ppn);
         }
-        // recycle
-        self.recycled.push(ppn);
+        self.free_frames += 1 << order;
+        let mut order = order;
+        while order < MAX_ORDER - 1 {
+            let buddy = ppn ^ (1 << order);
+            if !self.zones[zone].contains(buddy) || Self::index(buddy).is_none() {
+                break;
+            }
+            let desc = *self.desc(buddy);
+            if desc.state != FrameState::Free || desc.order as usize != order {
+                break;
+            }
+            self.remove_free(zone, buddy, order);
+            // 合并后的块以较低的页为首页，另一页成为块中的普通页
+            self.desc(ppn.max(buddy)).state = FrameState::Tail;
+            ppn = ppn.min(buddy);
+            order += 1;
+        }
+        self.push_free(zone, ppn, order);
+    }
+
+    /// 分配一个物理页
+    fn alloc(&mut self) -> Option<FrameTracker> {
+        let frame_tracker = self.alloc_frame(true);
+        log::trace!("[frame_alloc] {:?}", frame_tracker);
+        frame_tracker
+    }
+    unsafe fn alloc_uninit(&mut self) -> Option<FrameTracker> {
+        let frame_tracker = self.alloc_frame(false);
+        log::trace!("[frame_alloc_uninit] {:?}", frame_tracker);
+        frame_tracker
     }
 }
 
-type FrameAllocatorImpl = StackFrameAllocator;
+type FrameAllocatorImpl = BuddyFrameAllocator;
 
 lazy_static! {
     /// 全局帧分配器
     pub static ref FRAME_ALLOCATOR: RwLock<FrameAllocatorImpl> =
         RwLock::new(FrameAllocatorImpl::new());
 }
-/// 初始化全局帧分配器
+/// 初始化全局帧分配器，可用的物理内存在启动时确定
 pub fn init_frame_allocator() {
-    extern "C" {
-        // 内核结束地址？
-        fn ekernel();
-    }
-    FRAME_ALLOCATOR.write().init(
-        // 从内核结束地址ekernel
-        PhysAddr::from(ekernel as usize).ceil(),
-        // 到内存结束地址
-        PhysAddr::from(MEMORY_END).floor(),
-        // 作为可用物理内存
-    );
+    FRAME_ALLOCATOR.write().init();
 }
 
 /// 尝试使用所有可能的方法来释放制定数量为`req`的页
 /// 成功返回Ok(())，失败返回Err(())
 #[cfg(feature = "oom_handler")]
 pub fn oom_handler(req: usize) -> Result<(), ()> {
-    // step 1: 清理文件系统缓存
-    let mut released = 0;
-    released += fs::directory_tree::oom();
+    // step 1: 从LRU链表中回收冷页
+    let mut released = super::vmscan::try_to_free_pages(req);
     if released >= req {
         return Ok(());
     }
-    // step 2: 清理当前任务的内存
-    let task = current_task().unwrap();
-    if let Some(mut memory_set) = task.vm.try_lock() {
-        released += memory_set.do_shallow_clean();
-        log::warn!("[oom_handler] current task released: {}", released);
-    } else {
-        log::warn!("[oom_handler] try lock current task vm failed!");
-    }
+    // step 2: 链表中的页都回收不了时，收缩目录项缓存与所有文件的页缓存
+    log::warn!("[oom_handler] reclaimed: {}, shrink file caches!", released);
+    released += fs::directory_tree::oom();
     if released >= req {
-        return Ok(());
+        Ok(())
+    } else {
+        Err(())
     }
-    // step 3: 清理所有任务的内存
-    log::warn!("[oom_handler] notify all tasks!");
-    crate::task::do_oom(req - released)
 }
 
 #[cfg(feature = "oom_handler")]
@@ -256,11 +468,96 @@ pub unsafe fn frame_alloc_uninit() -> Option<Arc<FrameTracker>> {
         .map(|frame_tracker| Arc::new(frame_tracker))
 }
 
+/// 分配`pages`个物理地址连续且已清零的页，用于DMA等需要连续物理内存的场合。
+/// 返回首页页号，每一页都可以单独用`frame_dealloc`释放
+pub fn frame_alloc_contiguous(pages: usize, zone: ZoneType) -> Option<PhysPageNum> {
+    let result = FRAME_ALLOCATOR.write().alloc_contiguous(pages, zone);
+    let base = match result {
+        Some(base) => base,
+        #[cfg(feature = "oom_handler")]
+        None if frame_reserve_contiguous(pages) => {
+            FRAME_ALLOCATOR.write().alloc_contiguous(pages, zone)?
+        }
+        None => return None,
+    };
+    for ppn in base.0..base.0 + pages {
+        PhysPageNum::from(ppn).get_bytes_array().fill(0);
+    }
+    Some(base)
+}
+
+/// 为透明大页分配`HUGE_PAGE_PAGES`个按大页对齐且已清零的连续物理页，每一页有自己的`FrameTracker`。
+/// 分配失败时不回收内存，调用者退回普通页
+pub fn frame_alloc_huge() -> Option<Vec<Arc<FrameTracker>>> {
+    let base = FRAME_ALLOCATOR
+        .write()
+        .alloc_contiguous(HUGE_PAGE_PAGES, ZoneType::Normal)?;
+    Some(
+        (base.0..base.0 + HUGE_PAGE_PAGES)
+            .map(|ppn| Arc::new(FrameTracker::new(ppn.into())))
+            .collect(),
+    )
+}
+
+#[cfg(feature = "oom_handler")]
+/// 连续分配失败时尝试回收内存，回收的页不一定能拼成连续的块
+fn frame_reserve_contiguous(pages: usize) -> bool {
+    crate::show_frame_consumption! {
+        "GC";
+        let result = oom_handler(pages);
+    };
+    result.is_ok()
+}
+
+#[cfg(feature = "oom_handler")]
+/// 正在为内核堆回收内存，回收过程中的分配失败时不再递归回收
+static KERNEL_RECLAIMING: AtomicBool = AtomicBool::new(false);
+
+/// 为内核堆与slab分配`1 << order`个连续的页，不清零，也不经过`FrameTracker`。
+/// 由全局堆分配器调用，调用时不能持有堆或slab缓存的锁
+pub fn kernel_pages_alloc(order: usize) -> Option<PhysPageNum> {
+    let result = FRAME_ALLOCATOR.write().alloc_kernel_pages(order);
+    #[cfg(feature = "oom_handler")]
+    {
+        if result.is_none() && !KERNEL_RECLAIMING.swap(true, Ordering::AcqRel) {
+            let reclaimed = oom_handler(1 << order).is_ok();
+            KERNEL_RECLAIMING.store(false, Ordering::Release);
+            if reclaimed {
+                return FRAME_ALLOCATOR.write().alloc_kernel_pages(order);
+            }
+        }
+    }
+    result
+}
+
+/// 释放`kernel_pages_alloc`分配的块
+pub fn kernel_pages_free(ppn: PhysPageNum, order: usize) {
+    for ppn in ppn.0..ppn.0 + (1 << order) {
+        if let Some(page) = ppn_to_page(ppn.into()) {
+            page.clear_flags(PageFlags::SLAB | PageFlags::LARGE_ALLOC);
+        }
+    }
+    FRAME_ALLOCATOR.write().dealloc_pages(ppn, order);
+}
+
 /// 释放帧
 pub fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.write().dealloc(ppn);
 }
 
+/// 释放`frame_alloc_contiguous`分配的连续页
+pub fn frame_dealloc_contiguous(ppn: PhysPageNum, pages: usize) {
+    let mut allocator = FRAME_ALLOCATOR.write();
+    for ppn in ppn.0..ppn.0 + pages {
+        allocator.dealloc(ppn.into());
+    }
+}
+
+/// 区域中每一阶的空闲块数
+pub fn free_area_stats(zone: ZoneType) -> [usize; MAX_ORDER] {
+    FRAME_ALLOCATOR.read().free_area_stats(zone)
+}
+
 /// 计算可用帧数量
 pub fn unallocated_frames() -> usize {
     FRAME_ALLOCATOR.write().unallocated_frames()
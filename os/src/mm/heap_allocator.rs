@@ -1,9 +1,239 @@
-use crate::hal::KERNEL_HEAP_SIZE;
-use buddy_system_allocator::LockedHeap;
+//! 内核堆。
+//! 启动时划出的一段内存用完后，向帧分配器申请新的扩展区，扩展区全部空闲时再归还；
+//! 较大的分配直接使用帧分配器的页，`TaskControlBlock`等频繁分配的对象由slab缓存分配
+use super::frame_allocator::{kernel_pages_alloc, kernel_pages_free, MAX_ORDER};
+use super::page::{ppn_to_page, PageFlags};
+use super::slab;
+use super::PhysPageNum;
+use crate::config::{PAGE_SIZE, PAGE_SIZE_BITS};
+use crate::hal::boot_info;
+use buddy_system_allocator::Heap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{null_mut, NonNull};
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+/// 每个扩展区包含`1 << ARENA_ORDER`个页
+const ARENA_ORDER: usize = 9;
+/// 最多同时存在的扩展区个数
+const MAX_ARENAS: usize = 64;
+/// 超过这个大小的分配直接使用帧分配器的页
+const LARGE_ALLOC_SIZE: usize = PAGE_SIZE * 2;
+
+/// 一段交给伙伴系统堆管理的连续内存
+struct Arena {
+    start: usize,
+    end: usize,
+    heap: Heap<32>,
+}
+
+impl Arena {
+    const fn empty() -> Self {
+        Self {
+            start: 0,
+            end: 0,
+            heap: Heap::new(),
+        }
+    }
+    fn new(start: usize, end: usize) -> Self {
+        let mut arena = Self {
+            start,
+            end,
+            heap: Heap::new(),
+        };
+        unsafe { arena.heap.init(start, end - start) };
+        arena
+    }
+    fn contains(&self, addr: usize) -> bool {
+        self.start <= addr && addr < self.end
+    }
+    fn is_unused(&self) -> bool {
+        self.heap.stats_alloc_actual() == 0
+    }
+}
+
+/// 启动时划出的堆与所有扩展区
+struct KernelHeap {
+    boot: Arena,
+    arenas: [Option<Arena>; MAX_ARENAS],
+}
+
+impl KernelHeap {
+    const fn new() -> Self {
+        const NONE: Option<Arena> = None;
+        Self {
+            boot: Arena::empty(),
+            arenas: [NONE; MAX_ARENAS],
+        }
+    }
+    fn alloc(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        if let Ok(ptr) = self.boot.heap.alloc(layout) {
+            return Some(ptr);
+        }
+        self.arenas
+            .iter_mut()
+            .flatten()
+            .find_map(|arena| arena.heap.alloc(layout).ok())
+    }
+    /// 释放后扩展区全部空闲、并且已经有另一个全空的扩展区备用时，摘下这个扩展区交给调用者归还
+    fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) -> Option<Arena> {
+        let addr = ptr.as_ptr() as usize;
+        if self.boot.contains(addr) {
+            self.boot.heap.dealloc(ptr, layout);
+            return None;
+        }
+        let idx = self
+            .arenas
+            .iter()
+            .position(|arena| arena.as_ref().map_or(false, |arena| arena.contains(addr)))
+            .unwrap_or_else(|| panic!("[heap] {:#x} is not allocated from the heap", addr));
+        let arena = self.arenas[idx].as_mut().unwrap();
+        arena.heap.dealloc(ptr, layout);
+        if !arena.is_unused() {
+            return None;
+        }
+        let has_spare = self
+            .arenas
+            .iter()
+            .enumerate()
+            .any(|(i, arena)| i != idx && arena.as_ref().map_or(false, Arena::is_unused));
+        if has_spare {
+            self.arenas[idx].take()
+        } else {
+            None
+        }
+    }
+    /// 没有空位时把扩展区交还给调用者
+    fn add_arena(&mut self, arena: Arena) -> Result<(), Arena> {
+        match self.arenas.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(arena);
+                Ok(())
+            }
+            None => Err(arena),
+        }
+    }
+    fn stats(&self) -> HeapStats {
+        let mut stats = HeapStats::default();
+        for arena in core::iter::once(&self.boot).chain(self.arenas.iter().flatten()) {
+            stats.total += arena.heap.stats_total_bytes();
+            stats.allocated += arena.heap.stats_alloc_actual();
+        }
+        stats.arenas = self.arenas.iter().flatten().count();
+        stats
+    }
+}
+
+/// 内核堆的使用情况，不含直接使用帧分配器的大块分配与slab缓存
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapStats {
+    /// 启动时的堆与所有扩展区的总字节数
+    pub total: usize,
+    /// 已分配的字节数（按伙伴系统取整后）
+    pub allocated: usize,
+    /// 扩展区个数
+    pub arenas: usize,
+}
 
-#[global_allocator]
 /// 全局堆分配器
-static HEAP_ALLOCATOR: LockedHeap<32> = LockedHeap::empty();
+struct KernelAllocator {
+    heap: Mutex<KernelHeap>,
+    /// 帧分配器与页描述符表初始化之后才能扩展堆、使用slab与大块分配
+    growable: AtomicBool,
+}
+
+#[global_allocator]
+static HEAP_ALLOCATOR: KernelAllocator = KernelAllocator {
+    heap: Mutex::new(KernelHeap::new()),
+    growable: AtomicBool::new(false),
+};
+
+/// 大块分配使用的块的阶
+fn large_order(layout: &Layout) -> usize {
+    let pages = layout.size().max(layout.align()).div_ceil(PAGE_SIZE);
+    pages.next_power_of_two().trailing_zeros() as usize
+}
+
+impl KernelAllocator {
+    /// 申请一个新的扩展区，申请时不持有堆的锁，帧分配器可能需要回收内存
+    fn grow(&self) -> bool {
+        let ppn = match kernel_pages_alloc(ARENA_ORDER) {
+            Some(ppn) => ppn,
+            None => return false,
+        };
+        let start = ppn.0 << PAGE_SIZE_BITS;
+        let arena = Arena::new(start, start + (PAGE_SIZE << ARENA_ORDER));
+        let result = self.heap.lock().add_arena(arena);
+        match result {
+            Ok(()) => true,
+            Err(_) => {
+                kernel_pages_free(ppn, ARENA_ORDER);
+                false
+            }
+        }
+    }
+    unsafe fn alloc_large(&self, layout: &Layout) -> *mut u8 {
+        match kernel_pages_alloc(large_order(layout)) {
+            Some(ppn) => {
+                ppn_to_page(ppn).unwrap().set_flags(PageFlags::LARGE_ALLOC);
+                (ppn.0 << PAGE_SIZE_BITS) as *mut u8
+            }
+            None => null_mut(),
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for KernelAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let growable = self.growable.load(Ordering::Acquire);
+        if growable {
+            if let Some(cache) = slab::find_cache(&layout) {
+                return cache.alloc();
+            }
+            // 超过伙伴系统最大块的分配仍然使用堆
+            if layout.size() > LARGE_ALLOC_SIZE && large_order(&layout) < MAX_ORDER {
+                return self.alloc_large(&layout);
+            }
+        }
+        loop {
+            let result = self.heap.lock().alloc(layout);
+            match result {
+                Some(ptr) => return ptr.as_ptr(),
+                None if growable && self.grow() => continue,
+                None => return null_mut(),
+            }
+        }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // 页描述符的标志说明内存来自哪里，启动时的堆所在的页是保留页，两个标志都没有
+        let ppn = PhysPageNum::from(ptr as usize >> PAGE_SIZE_BITS);
+        let flags = if self.growable.load(Ordering::Acquire) {
+            ppn_to_page(ppn).map_or(PageFlags::empty(), |page| page.flags())
+        } else {
+            PageFlags::empty()
+        };
+        if flags.contains(PageFlags::SLAB) {
+            slab::find_cache(&layout)
+                .expect("[heap] slab object freed with a different layout")
+                .dealloc(ptr);
+        } else if flags.contains(PageFlags::LARGE_ALLOC) {
+            kernel_pages_free(ppn, large_order(&layout));
+        } else {
+            let released = self
+                .heap
+                .lock()
+                .dealloc(NonNull::new_unchecked(ptr), layout);
+            if let Some(arena) = released {
+                log::debug!(
+                    "[heap] release arena [{:#x}, {:#x})",
+                    arena.start,
+                    arena.end
+                );
+                kernel_pages_free((arena.start >> PAGE_SIZE_BITS).into(), ARENA_ORDER);
+            }
+        }
+    }
+}
 
 // 标记为全局分配错误处理器
 #[alloc_error_handler]
@@ -12,17 +242,20 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
 }
 
-/// 全局堆内存空间
-static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
-
-/// 初始化用于内核加载开始时的堆
+/// 初始化用于内核加载开始时的堆，堆的位置和大小在启动时根据物理内存确定
 pub fn init_heap() {
-    unsafe {
-        HEAP_ALLOCATOR
-            .lock()
-            // 起始地址和大小
-            .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
-    }
+    let heap = boot_info().heap;
+    HEAP_ALLOCATOR.heap.lock().boot = Arena::new(heap.start, heap.end);
+}
+
+/// 帧分配器与页描述符表初始化之后，允许堆向帧分配器扩展
+pub fn enable_heap_growth() {
+    HEAP_ALLOCATOR.growable.store(true, Ordering::Release);
+}
+
+/// 内核堆的使用情况
+pub fn heap_stats() -> HeapStats {
+    HEAP_ALLOCATOR.heap.lock().stats()
 }
 
 #[allow(unused)]
@@ -30,14 +263,10 @@ pub fn init_heap() {
 pub fn heap_test() {
     use alloc::boxed::Box;
     use alloc::vec::Vec;
-    extern "C" {
-        fn sbss();
-        fn ebss();
-    }
-    let bss_range = sbss as usize..ebss as usize;
+    let heap = boot_info().heap;
     let a = Box::new(5);
     assert_eq!(*a, 5);
-    assert!(bss_range.contains(&(a.as_ref() as *const _ as usize)));
+    assert!(heap.contains(a.as_ref() as *const _ as usize));
     drop(a);
     let mut v: Vec<usize> = Vec::new();
     for i in 0..500 {
@@ -46,7 +275,7 @@ pub fn heap_test() {
     for i in 0..500 {
         assert_eq!(v[i], i);
     }
-    assert!(bss_range.contains(&(v.as_ptr() as usize)));
+    assert!(heap.contains(v.as_ptr() as usize));
     drop(v);
     println!("heap_test passed!");
 }
@@ -1,6 +1,7 @@
 use core::fmt::Debug;
 
 use super::page_table::PageTable;
+use super::vmscan::lru_add_anon;
 #[cfg(feature = "zram")]
 use super::zram::{ZramTracker, ZRAM_DEVICE};
 use super::MemoryError;
@@ -8,14 +9,12 @@ use super::VPNRange;
 use super::KERNEL_SPACE;
 use super::{frame_alloc, FrameTracker};
 use super::{PhysPageNum, VirtAddr, VirtPageNum};
+use crate::config::{HUGE_PAGE_PAGES, PAGE_SIZE};
 use crate::fs::file_trait::File;
 #[cfg(feature = "swap")]
 use crate::fs::swap::{SwapTracker, SWAP_DEVICE};
-use crate::fs::SeekWhence;
-use crate::mm::frame_allocator::frame_alloc_uninit;
+use crate::mm::frame_allocator::{frame_alloc_huge, frame_alloc_uninit};
 
-#[cfg(feature = "oom_handler")]
-use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use log::{error, trace, warn};
@@ -60,16 +59,19 @@ impl Frame {
             _ => None,
         }
     }
-    pub fn gen_id(&mut self, frame_ref: &mut Arc<FrameTracker>) -> usize {
-        let swap_tracker = SWAP_DEVICE.lock().write(frame_ref.ppn.get_bytes_array());
-        swap_tracker.0
+    pub fn gen_id(&mut self, frame_ref: &mut Arc<FrameTracker>) -> Option<usize> {
+        let swap_tracker = SWAP_DEVICE.lock().write(frame_ref.ppn.get_bytes_array())?;
+        Some(swap_tracker.0)
     }
     #[cfg(feature = "oom_handler")]
     pub fn swap_out(&mut self) -> Result<usize, MemoryError> {
         match self {
             Frame::InMemory(frame_ref) => {
                 if Arc::strong_count(frame_ref) == 1 {
-                    let swap_tracker = SWAP_DEVICE.lock().write(frame_ref.ppn.get_bytes_array());
+                    let swap_tracker = SWAP_DEVICE
+                        .lock()
+                        .write(frame_ref.ppn.get_bytes_array())
+                        .ok_or(MemoryError::SwapIsFull)?;
                     let swap_id = swap_tracker.0;
                     // frame_tracker should be dropped
                     *self = Frame::SwappedOut(swap_tracker);
@@ -81,29 +83,13 @@ impl Frame {
             _ => Err(MemoryError::NotInMemory),
         }
     }
-    /// # Warning
-    /// This function do not check reference count of frame,
-    /// So it's possible that some pages was write to external storage, but no page is released.
-    #[cfg(feature = "oom_handler")]
-    pub fn force_swap_out(&mut self) -> Result<usize, MemoryError> {
-        match self {
-            Frame::InMemory(frame_ref) => {
-                let swap_tracker = SWAP_DEVICE.lock().write(frame_ref.ppn.get_bytes_array());
-                //let swap_id = self.gen_id();
-                let swap_id = swap_tracker.0;
-                // frame_tracker should be dropped
-                *self = Frame::SwappedOut(swap_tracker);
-                Ok(swap_id)
-            }
-            _ => Err(MemoryError::NotInMemory),
-        }
-    }
     #[cfg(feature = "oom_handler")]
     pub fn swap_in(&mut self) -> Result<PhysPageNum, MemoryError> {
         match self {
             Frame::SwappedOut(swap_tracker) => {
                 let frame = frame_alloc().unwrap();
                 let ppn = frame.ppn;
+                lru_add_anon(ppn);
                 SWAP_DEVICE
                     .lock()
                     .read(swap_tracker.0, ppn.get_bytes_array());
@@ -141,6 +127,7 @@ impl Frame {
             Frame::Compressed(zram_tracker) => {
                 let frame = frame_alloc().unwrap();
                 let ppn = frame.ppn;
+                lru_add_anon(ppn);
                 ZRAM_DEVICE
                     .lock()
                     .read(zram_tracker.0, ppn.get_bytes_array())
@@ -158,8 +145,6 @@ pub struct LinearMap {
     pub vpn_range: VPNRange,
     pub frames: Vec<Frame>,
     #[cfg(feature = "oom_handler")]
-    pub active: VecDeque<u16>,
-    #[cfg(feature = "oom_handler")]
     pub compressed: usize,
     #[cfg(feature = "oom_handler")]
     pub swapped: usize,
@@ -170,7 +155,6 @@ impl Debug for LinearMap {
         return f
             .debug_struct("LinearMap")
             .field("vpn_range", &self.vpn_range)
-            .field("active", &self.active.len())
             .field("compressed", &self.compressed)
             .field("swapped", &self.swapped)
             .finish();
@@ -188,8 +172,6 @@ impl LinearMap {
             vpn_range,
             frames: Vec::with_capacity(vpn_range.get_end().0 - vpn_range.get_start().0),
             #[cfg(feature = "oom_handler")]
-            active: VecDeque::new(),
-            #[cfg(feature = "oom_handler")]
             compressed: 0,
             #[cfg(feature = "oom_handler")]
             swapped: 0,
@@ -208,8 +190,6 @@ impl LinearMap {
             vpn_range,
             frames: Vec::with_capacity(len),
             #[cfg(feature = "oom_handler")]
-            active: VecDeque::new(),
-            #[cfg(feature = "oom_handler")]
             compressed: 0,
             #[cfg(feature = "oom_handler")]
             swapped: 0,
@@ -232,16 +212,12 @@ impl LinearMap {
     /// a key which exceeds the end of `vpn_range` would cause panic
     pub fn alloc_in_memory(&mut self, key: VirtPageNum, value: Arc<FrameTracker>) {
         let idx = key.0 - self.vpn_range.get_start().0;
-        #[cfg(feature = "oom_handler")]
-        self.active.push_back(idx as u16);
         self.frames[idx].insert_in_memory(value).unwrap()
     }
     /// # Warning
     /// a key which exceeds the end of `vpn_range` would cause panic
     pub fn remove_in_memory(&mut self, key: &VirtPageNum) -> Option<Arc<FrameTracker>> {
         let idx = key.0 - self.vpn_range.get_start().0;
-        #[cfg(feature = "oom_handler")]
-        self.active.retain(|&elem| elem as usize != idx);
         self.frames[idx].take_in_memory()
     }
     // /// # Warning
@@ -282,17 +258,13 @@ impl LinearMap {
         let second_frames = self.frames.split_off(cut.0 - vpn_start.0);
 
         #[cfg(feature = "oom_handler")]
-        let ((first_active, second_active), (first_compressed, first_swapped)) = (
-            LinearMap::split_active_into_two(&self.active, cut.0 - vpn_start.0),
-            self.count_compressed_and_swapped(0, cut.0 - vpn_start.0),
-        );
+        let (first_compressed, first_swapped) =
+            self.count_compressed_and_swapped(0, cut.0 - vpn_start.0);
 
         let second = LinearMap {
             vpn_range: VPNRange::new(cut, vpn_end),
             frames: second_frames,
             #[cfg(feature = "oom_handler")]
-            active: second_active,
-            #[cfg(feature = "oom_handler")]
             compressed: self.compressed - first_compressed,
             #[cfg(feature = "oom_handler")]
             swapped: self.swapped - first_swapped,
@@ -302,7 +274,6 @@ impl LinearMap {
 
         #[cfg(feature = "oom_handler")]
         {
-            self.active = first_active;
             self.compressed = first_compressed;
             self.swapped = first_swapped;
         }
@@ -337,51 +308,6 @@ impl LinearMap {
             )
         }
     }
-    fn split_active_into_two(
-        active: &VecDeque<u16>,
-        cut_idx: usize,
-    ) -> (VecDeque<u16>, VecDeque<u16>) {
-        if active.is_empty() {
-            (VecDeque::new(), VecDeque::new())
-        } else {
-            active.iter().fold(
-                (VecDeque::new(), VecDeque::new()),
-                |(mut first_active, mut second_active), &idx| {
-                    if (idx as usize) < cut_idx {
-                        first_active.push_back(idx);
-                    } else {
-                        second_active.push_back(idx - cut_idx as u16);
-                    }
-                    (first_active, second_active)
-                },
-            )
-        }
-    }
-    #[allow(unused)]
-    fn split_active_into_three(
-        active: &VecDeque<u16>,
-        first_cut_idx: usize,
-        second_cut_idx: usize,
-    ) -> (VecDeque<u16>, VecDeque<u16>, VecDeque<u16>) {
-        assert!(first_cut_idx < second_cut_idx);
-        if active.is_empty() {
-            (VecDeque::new(), VecDeque::new(), VecDeque::new())
-        } else {
-            active.iter().fold(
-                (VecDeque::new(), VecDeque::new(), VecDeque::new()),
-                |(mut first_active, mut second_active, mut third_active), &idx| {
-                    if (idx as usize) < first_cut_idx {
-                        first_active.push_back(idx);
-                    } else if (idx as usize) < second_cut_idx {
-                        second_active.push_back(idx - first_cut_idx as u16);
-                    } else {
-                        third_active.push_back(idx - second_cut_idx as u16)
-                    }
-                    (first_active, second_active, third_active)
-                },
-            )
-        }
-    }
 }
 impl Debug for MapArea {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -393,6 +319,10 @@ impl Debug for MapArea {
                 "map_file",
                 &if self.map_file.is_some() { "yes" } else { "no" },
             )
+            .field("map_shared", &self.map_shared)
+            .field("huge_page", &self.huge_page)
+            .field("locked", &self.locked)
+            .field("sequential", &self.sequential)
             .finish()
     }
 }
@@ -408,6 +338,32 @@ pub struct MapArea {
     /// Permissions which are the or of RWXU, where U stands for user.
     pub map_perm: MapPermission,
     pub map_file: Option<Arc<dyn File>>,
+    /// 区域起始处对应的文件偏移。切开的区域共用同一个`map_file`，各自记录偏移
+    pub file_offset: usize,
+    /// `MAP_SHARED`映射，页直接来自`map_file`的页缓存，写入对其他映射可见，fork后也不做写时复制
+    pub map_shared: bool,
+    /// 私有匿名映射是否使用大页
+    pub huge_page: HugePage,
+    /// 被mlock锁定，页不会被回收、压缩或换出
+    pub locked: bool,
+    /// madvise(MADV_SEQUENTIAL)：文件映射缺页时预读之后的页
+    pub sequential: bool,
+}
+
+/// 自动使用透明大页的区域至少有这么多页
+const THP_MIN_PAGES: usize = HUGE_PAGE_PAGES * 2;
+
+/// 区域使用大页的方式。
+/// 大页只用于私有匿名映射。文件映射和共享匿名映射的页来自页缓存，物理上不连续，
+/// 页缓存支持按大页分配之前它们总是使用4 KiB的页
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum HugePage {
+    /// 区域不小于`THP_MIN_PAGES`时，完整落在区域内的对齐的大页使用透明大页
+    Auto,
+    /// `MAP_HUGETLB`或madvise(MADV_HUGEPAGE)，不论区域大小
+    Always,
+    /// madvise(MADV_NOHUGEPAGE)
+    Never,
 }
 
 impl MapArea {
@@ -432,6 +388,11 @@ impl MapArea {
             map_type,
             map_perm,
             map_file,
+            file_offset: 0,
+            map_shared: false,
+            huge_page: HugePage::Auto,
+            locked: false,
+            sequential: false,
         }
     }
     /// Copier, but the physical pages are not allocated,
@@ -445,6 +406,11 @@ impl MapArea {
             map_type: another.map_type,
             map_perm: another.map_perm,
             map_file: another.map_file.clone(),
+            file_offset: another.file_offset,
+            map_shared: another.map_shared,
+            huge_page: another.huge_page,
+            locked: false,
+            sequential: another.sequential,
         }
     }
     /// Create `MapArea` from `Vec<Arc<FrameTracker>>`. This function should only be used to
@@ -466,14 +432,17 @@ impl MapArea {
             inner: LinearMap {
                 vpn_range: VPNRange::new(start_vpn, end_vpn),
                 frames,
-                // Unsafe if this `MapArea` is inserted to somewhere except `KERNEL_SPACE`.
-                active: VecDeque::new(),
                 compressed: 0,
                 swapped: 0,
             },
             map_type,
             map_perm,
             map_file: None,
+            file_offset: 0,
+            map_shared: false,
+            huge_page: HugePage::Auto,
+            locked: false,
+            sequential: false,
         }
     }
 
@@ -505,8 +474,9 @@ impl MapArea {
             MapType::Framed => {
                 let frame = unsafe { frame_alloc_uninit().unwrap() };
                 ppn = frame.ppn;
+                lru_add_anon(ppn);
                 self.inner.alloc_in_memory(vpn, frame);
-                page_table.map(vpn, ppn, self.map_perm);
+                page_table.map_page(vpn, ppn, self.map_perm);
             }
         }
         ppn
@@ -519,10 +489,121 @@ impl MapArea {
     ) -> PhysPageNum {
         let frame = frame_alloc().unwrap();
         let ppn = frame.ppn;
+        lru_add_anon(ppn);
         self.inner.alloc_in_memory(vpn, frame);
-        page_table.map(vpn, ppn, self.map_perm);
+        page_table.map_page(vpn, ppn, self.map_perm);
         ppn
     }
+    /// `vpn`所在的大页能否整体映射：区域为私有匿名映射，对齐的大页完整落在区域内，
+    /// 并且其中还没有分配任何页。返回大页的首页
+    pub fn huge_page_start(&self, vpn: VirtPageNum) -> Option<VirtPageNum> {
+        if self.map_type != MapType::Framed
+            || self.map_file.is_some()
+            || self.map_shared
+            || !self.map_perm.contains(MapPermission::U)
+        {
+            return None;
+        }
+        let area_start = self.inner.get_start();
+        let area_end = self.inner.get_end();
+        match self.huge_page {
+            HugePage::Never => return None,
+            HugePage::Auto if area_end.0 - area_start.0 < THP_MIN_PAGES => return None,
+            _ => {}
+        }
+        let start = VirtPageNum(vpn.0 & !(HUGE_PAGE_PAGES - 1));
+        if start < area_start || start.0 + HUGE_PAGE_PAGES > area_end.0 {
+            return None;
+        }
+        let idx = start.0 - area_start.0;
+        if self.inner.frames[idx..idx + HUGE_PAGE_PAGES]
+            .iter()
+            .all(|frame| matches!(frame, Frame::Unallocated))
+        {
+            Some(start)
+        } else {
+            None
+        }
+    }
+    /// 缺页时为`vpn`所在的整个大页分配连续的物理页并映射。
+    /// 每一页仍有自己的`FrameTracker`与反向映射，并分别加入LRU链表。
+    /// 不能使用大页或没有足够的连续物理页时返回`None`，由调用者映射普通页
+    pub fn map_huge_zeroed<T: PageTable>(
+        &mut self,
+        page_table: &mut T,
+        vpn: VirtPageNum,
+    ) -> Option<PhysPageNum> {
+        let start = self.huge_page_start(vpn)?;
+        let frames = frame_alloc_huge()?;
+        let base = frames[0].ppn;
+        for (i, frame) in frames.into_iter().enumerate() {
+            lru_add_anon(frame.ppn);
+            self.inner.alloc_in_memory(VirtPageNum(start.0 + i), frame);
+        }
+        page_table.map_huge_page(start, base, self.map_perm);
+        Some(PhysPageNum(base.0 + vpn.0 - start.0))
+    }
+    /// `vpn`所在大页中的页是否都只属于这个区域，fork之后的大页要拆开再逐页写时复制
+    fn huge_page_exclusive(&self, vpn: VirtPageNum) -> bool {
+        let start = vpn.0 & !(HUGE_PAGE_PAGES - 1);
+        if start < self.inner.get_start().0 || start + HUGE_PAGE_PAGES > self.inner.get_end().0 {
+            return false;
+        }
+        (start..start + HUGE_PAGE_PAGES).all(|vpn| {
+            self.inner
+                .get_in_memory(&VirtPageNum(vpn))
+                .map_or(false, |frame| Arc::strong_count(frame) == 1)
+        })
+    }
+    /// 解除`[start, end)`中的映射，返回其中是否有未映射的页。
+    /// 完整落在范围内的大页整体解除，部分落在范围内的先拆成普通页
+    fn unmap_range<T: PageTable>(
+        &mut self,
+        page_table: &mut T,
+        start: VirtPageNum,
+        end: VirtPageNum,
+    ) -> bool {
+        let mut has_unmapped_page = false;
+        let mut vpn = start;
+        while vpn < end {
+            if page_table.is_huge(vpn) {
+                if vpn.0 & (HUGE_PAGE_PAGES - 1) == 0 && vpn.0 + HUGE_PAGE_PAGES <= end.0 {
+                    page_table.unmap_huge_page(vpn);
+                    for i in 0..HUGE_PAGE_PAGES {
+                        self.inner.remove_in_memory(&VirtPageNum(vpn.0 + i));
+                    }
+                    vpn = VirtPageNum(vpn.0 + HUGE_PAGE_PAGES);
+                    continue;
+                }
+                page_table.split_huge(vpn).unwrap();
+            }
+            // it's normal to get an `Error` because we are using lazy alloc strategy
+            if let Err(_) = self.unmap_one(page_table, vpn) {
+                has_unmapped_page = true;
+            }
+            vpn = VirtPageNum(vpn.0 + 1);
+        }
+        has_unmapped_page
+    }
+    /// madvise(MADV_DONTNEED)：丢弃`[start, end)`中的页，包括已经被压缩或换出的页。
+    /// 之后访问匿名页得到零页，文件映射的页重新从页缓存映射
+    pub fn discard_range<T: PageTable>(
+        &mut self,
+        page_table: &mut T,
+        start: VirtPageNum,
+        end: VirtPageNum,
+    ) {
+        self.unmap_range(page_table, start, end);
+        #[cfg(feature = "oom_handler")]
+        for idx in start.0 - self.inner.get_start().0..end.0 - self.inner.get_start().0 {
+            match self.inner.frames[idx] {
+                Frame::Compressed(_) => self.inner.compressed -= 1,
+                Frame::SwappedOut(_) => self.inner.swapped -= 1,
+                _ => continue,
+            }
+            self.inner.frames[idx] = Frame::Unallocated;
+        }
+    }
     /// Unmap a page in current area.
     /// If it is framed, then the physical pages will be removed from the `data_frames` Btree.
     /// This is unnecessary if the area is directly mapped.
@@ -538,8 +619,8 @@ impl MapArea {
         }
         match self.map_type {
             MapType::Framed => {
+                page_table.unmap_page(vpn);
                 self.inner.remove_in_memory(&vpn);
-                page_table.unmap(vpn);
             }
             _ => {}
         }
@@ -552,11 +633,26 @@ impl MapArea {
         dst_page_table: &mut T,
         src_page_table: &mut T,
     ) -> Result<(), ()> {
+        // 共享映射的页由父子进程共同写入，保留写权限
+        if self.map_shared {
+            for vpn in self.inner.vpn_range {
+                if let Some(ppn) = src_page_table.translate(vpn) {
+                    if !dst_page_table.is_mapped(vpn) {
+                        dst_page_table.map_page(vpn, ppn, self.map_perm);
+                        // 脏位记录的是经由本页表的写入，由父进程的页表负责已有的修改
+                        dst_page_table.clear_dirty_bit(vpn).unwrap();
+                    } else {
+                        return Err(());
+                    }
+                }
+            }
+            return Ok(());
+        }
         let map_perm = self.map_perm.difference(MapPermission::W);
         for vpn in self.inner.vpn_range {
             if let Some(ppn) = src_page_table.block_and_ret_mut(vpn) {
                 if !dst_page_table.is_mapped(vpn) {
-                    dst_page_table.map(vpn, ppn, map_perm);
+                    dst_page_table.map_page(vpn, ppn, map_perm);
                 } else {
                     return Err(());
                 }
@@ -592,7 +688,7 @@ impl MapArea {
                 let ppn = frame.ppn;
                 if !page_table.is_mapped(vpn) {
                     self.inner.alloc_in_memory(vpn, frame.clone());
-                    page_table.map(vpn, ppn, self.map_perm);
+                    page_table.map_page(vpn, ppn, self.map_perm);
                 } else {
                     error!("[map_from_kernel_area] user vpn already mapped!");
                     return Err(());
@@ -607,14 +703,9 @@ impl MapArea {
     }
     /// Unmap all pages in `self` from `page_table` using unmap_one()
     pub fn unmap<T: PageTable>(&mut self, page_table: &mut T) -> Result<(), MemoryError> {
-        let mut has_unmapped_page = false;
-        for vpn in self.inner.vpn_range {
-            // it's normal to get an `Error` because we are using lazy alloc strategy
-            // we still need to unmap remaining pages of `self`, just throw this `Error` to caller
-            if let Err(MemoryError::NotMapped) = self.unmap_one(page_table, vpn) {
-                has_unmapped_page = true;
-            }
-        }
+        // we still need to unmap remaining pages of `self`, just throw this `Error` to caller
+        let has_unmapped_page =
+            self.unmap_range(page_table, self.inner.get_start(), self.inner.get_end());
         if has_unmapped_page {
             Err(MemoryError::NotMapped)
         } else {
@@ -626,6 +717,10 @@ impl MapArea {
         page_table: &mut T,
         vpn: VirtPageNum,
     ) -> Result<PhysPageNum, MemoryError> {
+        // 只属于这个区域的大页整体恢复写权限，否则拆开逐页复制
+        if page_table.is_huge(vpn) && !self.huge_page_exclusive(vpn) {
+            page_table.split_huge(vpn).unwrap();
+        }
         let old_frame = self.inner.remove_in_memory(&vpn).unwrap();
         if Arc::strong_count(&old_frame) == 1 {
             let old_ppn = old_frame.ppn;
@@ -637,12 +732,13 @@ impl MapArea {
         } else {
             // do copy in this case
             let old_ppn = old_frame.ppn;
-            page_table.unmap(vpn);
+            page_table.unmap_page(vpn);
             // alloc new frame
             let new_frame = unsafe { frame_alloc_uninit().unwrap() };
             let new_ppn = new_frame.ppn;
+            lru_add_anon(new_ppn);
             self.inner.alloc_in_memory(vpn, new_frame);
-            page_table.map(vpn, new_ppn, self.map_perm);
+            page_table.map_page(vpn, new_ppn, self.map_perm);
             // copy data
             new_ppn
                 .get_bytes_array()
@@ -683,12 +779,7 @@ impl MapArea {
             );
             return Err(());
         }
-        let mut has_unmapped_page = false;
-        for vpn in VPNRange::new(new_end_vpn, old_end_vpn) {
-            if let Err(_) = self.unmap_one(page_table, vpn) {
-                has_unmapped_page = true;
-            }
-        }
+        let has_unmapped_page = self.unmap_range(page_table, new_end_vpn, old_end_vpn);
         // `set_end` must be done after calling `map_one`
         // for the similar reason with `expand_to`
         self.inner.set_end(new_end_vpn)?;
@@ -714,15 +805,11 @@ impl MapArea {
             );
             return Err(());
         }
-        let mut has_unmapped_page = false;
-        for vpn in VPNRange::new(old_start_vpn, new_start_vpn) {
-            if let Err(_) = self.unmap_one(page_table, vpn) {
-                has_unmapped_page = true;
-            }
-        }
+        let has_unmapped_page = self.unmap_range(page_table, old_start_vpn, new_start_vpn);
         // `set_start` must be done after calling `map_one`
         // for the similar reason with `expand_to`
         self.inner.set_start(new_start_vpn)?;
+        self.file_offset += (new_start_vpn.0 - old_start_vpn.0) * PAGE_SIZE;
         if has_unmapped_page {
             warn!("[rshrink_to] Some pages are already unmapped, is it caused by lazy alloc?");
             Err(())
@@ -730,6 +817,11 @@ impl MapArea {
             Ok(())
         }
     }
+    /// 把区域整体移到从`new_start`开始的位置，页表项由调用者搬移
+    pub fn move_to(&mut self, new_start: VirtPageNum) {
+        let len = self.inner.get_end().0 - self.inner.get_start().0;
+        self.inner.vpn_range = VPNRange::new(new_start, VirtPageNum(new_start.0 + len));
+    }
     pub fn check_overlapping(
         &self,
         start_vpn: VirtPageNum,
@@ -753,27 +845,22 @@ impl MapArea {
             return Some((start, end));
         }
     }
+    /// 在`cut`处切开，返回后一半。文件映射的两半共用同一个`map_file`，
+    /// 这样System V共享内存按引用计数统计的映射数不会因切开而丢失
     pub fn into_two(&mut self, cut: VirtPageNum) -> Result<Self, ()> {
-        let second_file = if let Some(file) = &self.map_file {
-            let new_file = file.deep_clone();
-            new_file
-                .lseek(
-                    (file.get_offset() + VirtAddr::from(cut).0
-                        - VirtAddr::from(self.inner.vpn_range.get_start()).0)
-                        as isize,
-                    SeekWhence::SEEK_SET,
-                )
-                .unwrap();
-            Some(new_file)
-        } else {
-            None
-        };
+        let second_offset =
+            self.file_offset + (cut.0 - self.inner.vpn_range.get_start().0) * PAGE_SIZE;
         let second_frames = self.inner.into_two(cut)?;
         Ok(MapArea {
             inner: second_frames,
             map_type: self.map_type,
             map_perm: self.map_perm,
-            map_file: second_file,
+            map_file: self.map_file.clone(),
+            file_offset: second_offset,
+            map_shared: self.map_shared,
+            huge_page: self.huge_page,
+            locked: self.locked,
+            sequential: self.sequential,
         })
     }
     pub fn into_three(
@@ -781,82 +868,9 @@ impl MapArea {
         first_cut: VirtPageNum,
         second_cut: VirtPageNum,
     ) -> Result<(Self, Self), ()> {
-        if self.map_file.is_some() {
-            warn!("[into_three] break apart file-back MapArea!");
-            return Err(());
-        }
-        let (second_frames, third_frames) = self.inner.into_three(first_cut, second_cut)?;
-        Ok((
-            MapArea {
-                inner: second_frames,
-                map_type: self.map_type,
-                map_perm: self.map_perm,
-                map_file: None,
-            },
-            MapArea {
-                inner: third_frames,
-                map_type: self.map_type,
-                map_perm: self.map_perm,
-                map_file: None,
-            },
-        ))
-    }
-    #[cfg(feature = "oom_handler")]
-    pub fn do_oom<T: PageTable>(&mut self, page_table: &mut T) -> usize {
-        let start_vpn = self.get_inner().vpn_range.get_start();
-        let compressed_before = self.get_inner().compressed;
-        let swapped_before = self.get_inner().swapped;
-        warn!("{:?}", self.inner.active);
-        while let Some(idx) = self.inner.active.pop_front() {
-            let frame = &mut self.inner.frames[idx as usize];
-            // first, try to compress
-            match frame.zip() {
-                Ok(zram_id) => {
-                    page_table.unmap(VirtPageNum::from(start_vpn.0 + idx as usize));
-                    self.inner.compressed += 1;
-                    trace!("[do_oom] compress frame: {:?}, zram_id: {}", frame, zram_id);
-                    continue;
-                }
-                Err(MemoryError::SharedPage) => continue,
-                Err(MemoryError::ZramIsFull) => {}
-                _ => unreachable!(),
-            }
-            // zram is full, try to swap out
-            match frame.swap_out() {
-                Ok(swap_id) => {
-                    page_table.unmap(VirtPageNum::from(start_vpn.0 + idx as usize));
-                    self.inner.swapped += 1;
-                    trace!("[do_oom] swap out frame: {:?}, swap_id: {}", frame, swap_id);
-                    continue;
-                }
-                Err(MemoryError::SharedPage) => continue,
-                _ => unreachable!(),
-            }
-        }
-        self.inner.compressed + self.inner.swapped - compressed_before - swapped_before
-    }
-    #[cfg(feature = "oom_handler")]
-    pub fn force_swap<T: PageTable>(&mut self, page_table: &mut T) -> usize {
-        let start_vpn = self.inner.vpn_range.get_start();
-        let swapped_before = self.inner.swapped;
-        warn!("{:?}", self.inner.active);
-        while let Some(idx) = self.inner.active.pop_front() {
-            let frame = &mut self.inner.frames[idx as usize];
-            match frame.force_swap_out() {
-                Ok(swap_id) => {
-                    page_table.unmap(VirtPageNum::from(start_vpn.0 + idx as usize));
-                    self.inner.swapped += 1;
-                    trace!(
-                        "[force_swap] swap out frame: {:?}, swap_id: {}",
-                        frame,
-                        swap_id
-                    );
-                    continue;
-                }
-                _ => unreachable!(),
-            }
-        }
-        self.inner.swapped - swapped_before
+        let mut second = self.into_two(first_cut)?;
+        let third = second.into_two(second_cut)?;
+        Ok((second, third))
     }
 }
 
@@ -1,17 +1,21 @@
 use super::map_area::*;
+use super::page::page_remove_rmap;
 use super::page_table::PageTable;
-use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
+use super::{tlb_invalidate, PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use crate::config::*;
-use crate::fs::SeekWhence;
+use crate::fs::readahead::{POSIX_FADV_WILLNEED, RA_MAX_PAGES};
+#[cfg(feature = "oom_handler")]
+use crate::fs::swap::swp_type;
+use crate::fs::{file_trait::File, memfd::MemFd, writeback::mark_inode_dirty, SeekWhence};
 use crate::hal::TrapContext;
-use crate::hal::{MMIO, TICKS_PER_SEC};
+use crate::hal::{boot_info, TICKS_PER_SEC};
 use crate::should_map_trampoline;
 use crate::syscall::errno::*;
 use crate::task::{
     current_task, trap_cx_bottom_from_tid, ustack_bottom_from_tid, AuxvEntry, AuxvType, ELFInfo,
 };
 use alloc::string::String;
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
 use lazy_static::*;
 use log::{debug, error, info, trace, warn};
@@ -59,6 +63,34 @@ pub enum MemoryError {
     BeyondEOF,
 }
 
+/// 映射了同一个文件的用户地址空间，文件被截短时只需处理这些地址空间
+pub struct FileMappings {
+    vms: Mutex<Vec<Weak<Mutex<MemorySet<crate::mm::PageTableImpl>>>>>,
+}
+
+impl FileMappings {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            vms: Mutex::new(Vec::new()),
+        })
+    }
+    /// 登记映射了该文件的地址空间，顺便去掉已经释放的地址空间
+    pub fn add(&self, vm: &Arc<Mutex<MemorySet<crate::mm::PageTableImpl>>>) {
+        let mut vms = self.vms.lock();
+        vms.retain(|weak| weak.strong_count() > 0);
+        if !vms.iter().any(|weak| weak.as_ptr() == Arc::as_ptr(vm)) {
+            vms.push(Arc::downgrade(vm));
+        }
+    }
+    /// 文件被截短后，在登记过的地址空间中解除超出文件末尾的映射页
+    pub fn unmap_beyond_eof(self: &Arc<Self>) {
+        let vms: Vec<_> = self.vms.lock().iter().filter_map(Weak::upgrade).collect();
+        for vm in vms {
+            vm.lock().unmap_beyond_eof(self);
+        }
+    }
+}
+
 /// The memory "space" as in user space or kernel space
 pub struct MemorySet<T: PageTable> {
     /// 页表实现
@@ -67,6 +99,8 @@ pub struct MemorySet<T: PageTable> {
     /// 段是使用这种机制实现的，换句话说，它们可以被认为是MapArea的一个子集
     /// 但是，这个结构体中可能存在其他用途，比如说文件映射
     areas: Vec<MapArea>,
+    /// mlockall(MCL_FUTURE)：之后新建的映射同样被锁定
+    lock_future: bool,
 }
 
 impl<T: PageTable> MemorySet<T> {
@@ -76,6 +110,7 @@ impl<T: PageTable> MemorySet<T> {
         Self {
             page_table: T::new_kern_space(),
             areas: Vec::with_capacity(16),
+            lock_future: false,
         }
     }
     /// Create a new struct with no information at all.
@@ -83,6 +118,7 @@ impl<T: PageTable> MemorySet<T> {
         Self {
             page_table: T::new(),
             areas: Vec::with_capacity(16),
+            lock_future: false,
         }
     }
     /// Getter to the token of current memory space, or "this" page table.
@@ -225,7 +261,7 @@ impl<T: PageTable> MemorySet<T> {
             if !self.page_table.is_mapped(vpn) {
                 //if not mapped
                 self.page_table
-                    .map(vpn, frame.ppn.clone(), map_area.map_perm);
+                    .map_page(vpn, frame.ppn.clone(), map_area.map_perm);
             } else {
                 return Err(());
             }
@@ -296,17 +332,43 @@ impl<T: PageTable> MemorySet<T> {
             if !self.page_table.is_mapped(vpn) {
                 // lazy alloc file-backed page
                 if let Some(file) = area.map_file.clone() {
-                    let old_offset = file.get_offset();
+                    let old_offset = area.file_offset;
                     let page_start_va = VirtAddr::from(vpn).0;
                     let area_start_va = VirtAddr::from(area.get_start::<T>()).0;
                     let offset_in_area = page_start_va - area_start_va;
-                    // if offset exceed EOF, SIGBUS should be sent
-                    if old_offset + offset_in_area > (file.get_size() + PAGE_SIZE - 1) & !0xfff {
+                    // if offset exceed EOF of a regular file, SIGBUS should be sent
+                    if file.is_file()
+                        && old_offset + offset_in_area >= (file.get_size() + PAGE_SIZE - 1) & !0xfff
+                    {
                         return Err(MemoryError::BeyondEOF);
                     }
-                    if area.map_perm.contains(MapPermission::W) {
+                    // madvise(MADV_SEQUENTIAL)：每到一个预读窗口的开头就把整个窗口读入页缓存
+                    if area.sequential && (offset_in_area / PAGE_SIZE) % RA_MAX_PAGES == 0 {
+                        let len = (RA_MAX_PAGES * PAGE_SIZE)
+                            .min(VirtAddr::from(area.get_end::<T>()).0 - page_start_va);
+                        let _ = file.fadvise(old_offset + offset_in_area, len, POSIX_FADV_WILLNEED);
+                    }
+                    // map to the page cache directly to stay coherent with read/write,
+                    // private writable mappings get it read-only and copy on the first write
+                    if let Ok(cache) = file.get_single_cache(old_offset + offset_in_area) {
+                        let cache_phys_page = cache.lock().get_tracker();
+                        let cache_ppn = cache_phys_page.ppn;
+                        let map_perm = if area.map_shared {
+                            area.map_perm
+                        } else {
+                            area.map_perm.difference(MapPermission::W)
+                        };
+                        self.page_table.map_page(vpn, cache_ppn, map_perm);
+                        if area.map_shared {
+                            // the dirty bit tells whether the page is written through this mapping
+                            self.page_table.clear_dirty_bit(vpn).unwrap();
+                        }
+                        area.inner.alloc_in_memory(vpn, cache_phys_page);
+                        Ok(cache_ppn.offset(addr.page_offset()))
+                    // files without page cache (e.g. devices) are read into a private page
+                    } else {
                         let allocated_ppn = area.map_one_unchecked(&mut self.page_table, vpn);
-                        file.lseek(offset_in_area as isize, SeekWhence::SEEK_CUR)
+                        file.lseek((old_offset + offset_in_area) as isize, SeekWhence::SEEK_SET)
                             .unwrap();
                         file.read(None, unsafe {
                             core::slice::from_raw_parts_mut(
@@ -314,21 +376,7 @@ impl<T: PageTable> MemorySet<T> {
                                 PAGE_SIZE,
                             )
                         });
-                        file.lseek(old_offset as isize, SeekWhence::SEEK_SET)
-                            .unwrap();
                         Ok(allocated_ppn.offset(addr.page_offset()))
-                    // map to phys page directly
-                    } else {
-                        let cache_phys_page = file
-                            .get_single_cache(old_offset + offset_in_area)
-                            .unwrap()
-                            .try_lock()
-                            .unwrap()
-                            .get_tracker();
-                        let cache_ppn = cache_phys_page.ppn;
-                        self.page_table.map(vpn, cache_ppn, area.map_perm);
-                        area.inner.alloc_in_memory(vpn, cache_phys_page);
-                        Ok(cache_ppn.offset(addr.page_offset()))
                     }
                 } else {
                     let frame = area.inner.get_mut(&vpn);
@@ -342,22 +390,24 @@ impl<T: PageTable> MemorySet<T> {
                             unreachable!();
                         }
                         Frame::Unallocated => {
-                            info!("[do_page_fault] addr: {:?}, solution: lazy alloc", addr);
-                            let ppn = area.map_one_zeroed_unchecked(&mut self.page_table, vpn);
-                            let frame = area.inner.get_mut(&vpn);
-                            info!(
-                                "[do_page_fault map_one] addr: {:?}, vpn: {:?}, frame: {:?}",
-                                addr, vpn, frame
-                            );
-                            ppn
+                            if let Some(ppn) = area.map_huge_zeroed(&mut self.page_table, vpn) {
+                                info!("[do_page_fault] addr: {:?}, solution: huge page", addr);
+                                ppn
+                            } else {
+                                info!("[do_page_fault] addr: {:?}, solution: lazy alloc", addr);
+                                let ppn = area.map_one_zeroed_unchecked(&mut self.page_table, vpn);
+                                let frame = area.inner.get_mut(&vpn);
+                                info!(
+                                    "[do_page_fault map_one] addr: {:?}, vpn: {:?}, frame: {:?}",
+                                    addr, vpn, frame
+                                );
+                                ppn
+                            }
                         }
                         #[cfg(feature = "oom_handler")]
                         Frame::Compressed(_) => {
                             let ppn = frame.unzip().unwrap();
-                            self.page_table.map(vpn, ppn, area.map_perm);
-                            area.inner
-                                .active
-                                .push_back((vpn.0 - area.get_start::<T>().0) as u16);
+                            self.page_table.map_page(vpn, ppn, area.map_perm);
                             area.inner.compressed -= 1;
                             info!("[do_page_fault] addr: {:?}, solution: decompress", addr);
                             ppn
@@ -365,10 +415,7 @@ impl<T: PageTable> MemorySet<T> {
                         #[cfg(feature = "oom_handler")]
                         Frame::SwappedOut(_) => {
                             let ppn = frame.swap_in().unwrap();
-                            self.page_table.map(vpn, ppn, area.map_perm);
-                            area.inner
-                                .active
-                                .push_back((vpn.0 - area.get_start::<T>().0) as u16);
+                            self.page_table.map_page(vpn, ppn, area.map_perm);
                             area.inner.swapped -= 1;
                             info!("[do_page_fault] addr: {:?}, solution: swap in", addr);
                             ppn
@@ -378,7 +425,20 @@ impl<T: PageTable> MemorySet<T> {
                 }
             } else {
                 // mapped before the assignment
-                if area.map_perm.contains(MapPermission::W) {
+                if self.page_table.is_accessed(vpn) == Some(false) {
+                    // The access bit was cleared by page reclaim, and the hardware
+                    // faults instead of setting it.
+                    self.page_table.set_access_bit(vpn).unwrap();
+                    let ppn = self.page_table.translate(vpn).unwrap();
+                    Ok(ppn.offset(addr.page_offset()))
+                } else if area.map_perm.contains(MapPermission::W) && area.map_shared {
+                    // Shared pages are never copied. Remapping sets the dirty bit
+                    // for hardware that faults instead of setting it.
+                    let ppn = self.page_table.translate(vpn).unwrap();
+                    self.page_table.unmap(vpn);
+                    self.page_table.map(vpn, ppn, area.map_perm);
+                    Ok(ppn.offset(addr.page_offset()))
+                } else if area.map_perm.contains(MapPermission::W) {
                     // Whoever triggers this fault shall cause the area to be copied into a new area.
                     let allocated_ppn = area.copy_on_write(&mut self.page_table, vpn)?;
                     info!("[do_page_fault] addr: {:?}, solution: copy on write", addr);
@@ -398,72 +458,6 @@ impl<T: PageTable> MemorySet<T> {
             Err(MemoryError::BadAddress)
         }
     }
-    #[cfg(feature = "loongarch64")]
-    #[cfg(feature = "oom_handler")]
-    pub fn do_shallow_clean(&mut self) -> usize {
-        let page_table = &mut self.page_table;
-        self.areas
-            .iter_mut()
-            .filter(|area| {
-                let start_vpn = area.get_start::<T>();
-                start_vpn.0 >= (USR_MMAP_BASE >> PAGE_SIZE_BITS)
-                    && start_vpn.0 < (TASK_SIZE >> PAGE_SIZE_BITS)
-                    && area.map_file.is_none()
-            })
-            .map(|area| area.do_oom(page_table))
-            .sum()
-    }
-    #[cfg(feature = "riscv")]
-    #[cfg(feature = "oom_handler")]
-    pub fn do_shallow_clean(&mut self) -> usize {
-        let page_table = &mut self.page_table;
-        self.areas
-            .iter_mut()
-            .filter(|area| {
-                let start_vpn = area.get_start::<T>();
-                start_vpn.0 >= (MMAP_BASE >> PAGE_SIZE_BITS)
-                    && start_vpn.0 < (TASK_SIZE >> PAGE_SIZE_BITS)
-                    && area.map_file.is_none()
-            })
-            .map(|area| area.do_oom(page_table))
-            .sum()
-    }
-    #[cfg(feature = "loongarch64")]
-    #[cfg(feature = "oom_handler")]
-    pub fn do_deep_clean(&mut self) -> usize {
-        let page_table = &mut self.page_table;
-        self.areas
-            .iter_mut()
-            .filter(|area| {
-                area.get_start::<T>().0 < (TASK_SIZE >> PAGE_SIZE_BITS) && area.map_file.is_none()
-            })
-            .map(|area| {
-                if area.get_start::<T>().0 < USR_MMAP_BASE >> PAGE_SIZE_BITS {
-                    area.force_swap(page_table)
-                } else {
-                    area.do_oom(page_table)
-                }
-            })
-            .sum()
-    }
-    #[cfg(feature = "riscv")]
-    #[cfg(feature = "oom_handler")]
-    pub fn do_deep_clean(&mut self) -> usize {
-        let page_table = &mut self.page_table;
-        self.areas
-            .iter_mut()
-            .filter(|area| {
-                area.get_start::<T>().0 < (TASK_SIZE >> PAGE_SIZE_BITS) && area.map_file.is_none()
-            })
-            .map(|area| {
-                if area.get_start::<T>().0 < MMAP_BASE >> PAGE_SIZE_BITS {
-                    area.force_swap(page_table)
-                } else {
-                    area.do_oom(page_table)
-                }
-            })
-            .sum()
-    }
     /// Mention that trampoline is not collected by areas.
     fn map_trampoline(&mut self) {
         self.page_table.map(
@@ -536,18 +530,30 @@ impl<T: PageTable> MemorySet<T> {
             ebss,
             MapPermission::R | MapPermission::W
         );
-        anonymous_identical_map!(
-            "physical memory",
-            ekernel,
-            MEMORY_END,
-            MapPermission::R | MapPermission::W
-        );
+        println!("mapping physical memory");
+        // Everything except the kernel image, which is mapped section by section above.
+        for region in boot_info().memory.iter() {
+            if region.start < stext as usize {
+                anonymous_identical_map!(
+                    region.start,
+                    region.end.min(stext as usize),
+                    MapPermission::R | MapPermission::W
+                );
+            }
+            if region.end > ekernel as usize {
+                anonymous_identical_map!(
+                    region.start.max(ekernel as usize),
+                    region.end,
+                    MapPermission::R | MapPermission::W
+                );
+            }
+        }
 
         println!("mapping memory-mapped registers");
-        for pair in MMIO {
+        for region in boot_info().mmio.iter() {
             anonymous_identical_map!(
-                (*pair).0,
-                ((*pair).0 + (*pair).1),
+                region.start,
+                region.end,
                 MapPermission::R | MapPermission::W
             );
         }
@@ -700,6 +706,8 @@ impl<T: PageTable> MemorySet<T> {
         for i in 0..user_space.areas.len() - 1 {
             // user_space.areas[i]
             let mut new_area = user_space.areas[i].clone();
+            // 子进程不继承内存锁
+            new_area.locked = false;
             new_area
                 .map_from_existing_page_table(
                     &mut memory_set.page_table,
@@ -757,8 +765,96 @@ impl<T: PageTable> MemorySet<T> {
     }
     pub fn recycle_data_pages(&mut self) {
         //*self = Self::new_bare();
+        // 共享映射中写入的数据在页表释放前转交给页缓存
+        self.sync_shared_pages(VirtPageNum::from(0), VirtPageNum::from(usize::MAX));
+        self.remove_all_rmap();
         self.areas.clear();
     }
+    /// 地址空间被丢弃时页表项不会逐个解除，在这里删除所有映射页的反向映射
+    fn remove_all_rmap(&self) {
+        let token = self.page_table.token();
+        for area in self.areas.iter() {
+            let start_vpn = area.get_start::<T>();
+            for (idx, frame) in area.inner.frames.iter().enumerate() {
+                if let Frame::InMemory(tracker) = frame {
+                    page_remove_rmap(tracker.ppn, token, VirtPageNum::from(start_vpn.0 + idx));
+                }
+            }
+        }
+    }
+    /// Clear the access bit of `vpn` for page reclaim and return whether it was set.
+    /// Always `false` if the hardware does not keep access bits.
+    pub fn test_and_clear_accessed(&mut self, vpn: VirtPageNum) -> bool {
+        if self.page_table.is_accessed(vpn) == Some(true) {
+            self.page_table.clear_access_bit(vpn).unwrap();
+            true
+        } else {
+            false
+        }
+    }
+    /// Reference count of the anonymous page `ppn` mapped at `vpn`,
+    /// or `None` if the page is not there or must stay in memory.
+    #[cfg(feature = "oom_handler")]
+    pub fn anon_page_refs(&self, vpn: VirtPageNum, ppn: PhysPageNum) -> Option<usize> {
+        let area = self
+            .areas
+            .iter()
+            .find(|area| area.get_start::<T>() <= vpn && vpn < area.get_end::<T>())?;
+        // Trap contexts are accessed through physical addresses and have no U bit.
+        // Private pages of file mappings are read again from the file on the next fault.
+        if !area.map_perm.contains(MapPermission::U)
+            || area.map_file.is_some()
+            || area.map_shared
+            || area.locked
+        {
+            return None;
+        }
+        match area.inner.get_in_memory(&vpn) {
+            Some(tracker) if tracker.ppn == ppn => Some(Arc::strong_count(tracker)),
+            _ => None,
+        }
+    }
+    /// Replace the anonymous page at `vpn` with its compressed or swapped out copy
+    /// and unmap it. The page must be checked by `anon_page_refs` first.
+    #[cfg(feature = "oom_handler")]
+    pub fn replace_anon_page(&mut self, vpn: VirtPageNum, frame: Frame) {
+        let area = self
+            .areas
+            .iter_mut()
+            .find(|area| area.get_start::<T>() <= vpn && vpn < area.get_end::<T>())
+            .unwrap();
+        match frame {
+            Frame::Compressed(_) => area.inner.compressed += 1,
+            Frame::SwappedOut(_) => area.inner.swapped += 1,
+            _ => {}
+        }
+        // 回收大页中的一页时先把大页拆开
+        if self.page_table.is_huge(vpn) {
+            self.page_table.split_huge(vpn).unwrap();
+        }
+        self.page_table.unmap_page(vpn);
+        *area.inner.get_mut(&vpn) = frame;
+    }
+    /// Unmap the page cache page `ppn` at `vpn`, it will be mapped again on the next fault.
+    /// Pages written through a shared mapping are kept until they are handed to the page cache.
+    pub fn unmap_file_page(&mut self, vpn: VirtPageNum, ppn: PhysPageNum) -> bool {
+        let area = match self.areas.iter_mut().find(|area| {
+            area.map_file.is_some() && area.get_start::<T>() <= vpn && vpn < area.get_end::<T>()
+        }) {
+            Some(area) if !area.locked => area,
+            _ => return false,
+        };
+        match area.inner.get_in_memory(&vpn) {
+            Some(tracker) if tracker.ppn == ppn => {}
+            _ => return false,
+        }
+        if area.map_shared && self.page_table.is_dirty(vpn) == Some(true) {
+            return false;
+        }
+        self.page_table.unmap_page(vpn);
+        area.inner.remove_in_memory(&vpn);
+        true
+    }
     #[allow(unused)]
     // debug use only
     pub fn show_areas(&self) {
@@ -827,6 +923,23 @@ impl<T: PageTable> MemorySet<T> {
             return EINVAL;
         }
         let len = if len == 0 { PAGE_SIZE } else { len };
+        // 大页只用于私有匿名映射，长度按大页取整。
+        // 文件映射和共享匿名映射（由memfd承载）的页来自页缓存，物理上不连续，
+        // 既不支持MAP_HUGETLB也不会使用透明大页，对它们指定MAP_HUGETLB返回EINVAL，
+        // 而不是悄悄退回4 KiB的页
+        let huge = flags.contains(MapFlags::MAP_HUGETLB);
+        if huge
+            && (!flags.contains(MapFlags::MAP_ANONYMOUS)
+                || flags.contains(MapFlags::MAP_SHARED)
+                || flags.contains(MapFlags::MAP_FIXED) && start & (HUGE_PAGE_SIZE - 1) != 0)
+        {
+            return EINVAL;
+        }
+        let len = if huge {
+            (len + HUGE_PAGE_SIZE - 1) & !(HUGE_PAGE_SIZE - 1)
+        } else {
+            len
+        };
         let task = current_task().unwrap();
         let idx = self.last_mmap_area_idx();
         let start_va: VirtAddr = if flags.contains(MapFlags::MAP_FIXED) {
@@ -839,22 +952,21 @@ impl<T: PageTable> MemorySet<T> {
                 if flags.contains(MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS)
                     && prot == area.map_perm
                     && area.map_file.is_none()
+                    && !huge
+                    && area.huge_page == HugePage::Auto
+                    && area.locked == self.lock_future
                 {
                     debug!("[mmap] merge with previous area, call expand_to");
                     let end_va: VirtAddr = area.get_end::<T>().into();
                     area.expand_to::<T>(VirtAddr::from(end_va.0 + len)).unwrap();
                     return end_va.0 as isize;
                 }
-                area.get_end::<T>().into()
+            }
+            let start_va = self.next_mmap_start();
+            if huge {
+                VirtAddr::from((start_va.0 + HUGE_PAGE_SIZE - 1) & !(HUGE_PAGE_SIZE - 1))
             } else {
-                #[cfg(feature = "loongarch64")]
-                {
-                    USR_MMAP_BASE.into()
-                }
-                #[cfg(feature = "riscv")]
-                {
-                    MMAP_BASE.into()
-                }
+                start_va
             }
         };
         let mut new_area = MapArea::new(
@@ -869,24 +981,74 @@ impl<T: PageTable> MemorySet<T> {
             let fd_table = task.files.lock();
             match fd_table.get_ref(fd) {
                 Ok(file_descriptor) => {
+                    if file_descriptor.get_path_only() {
+                        return EBADF;
+                    }
                     if !file_descriptor.readable() {
                         return EACCES;
                     }
-                    let file = file_descriptor.file.deep_clone();
-                    file.lseek(offset as isize, SeekWhence::SEEK_SET).unwrap();
+                    let shared = flags.contains(MapFlags::MAP_SHARED);
+                    let writable = prot.contains(MapPermission::W);
+                    // 可写的共享映射会修改文件
+                    if shared && writable && !file_descriptor.writable() {
+                        return EACCES;
+                    }
+                    if shared && writable && file_descriptor.is_swapfile() {
+                        return ETXTBSY;
+                    }
+                    new_area.map_shared = shared;
+                    let memfd = file_descriptor.file.downcast_ref::<MemFd>();
+                    let file = match memfd {
+                        Some(memfd) if shared => match memfd.mmap_shared(writable) {
+                            Ok(file) => file,
+                            Err(errno) => return errno,
+                        },
+                        _ => file_descriptor.file.deep_clone(),
+                    };
                     new_area.map_file = Some(file);
+                    new_area.file_offset = offset;
                 }
                 Err(errno) => return errno,
             }
+        } else if flags.contains(MapFlags::MAP_SHARED) {
+            // 共享的匿名映射由一个不可见的memfd承载，fork后父子进程仍映射同一组页
+            let file = MemFd::new(false);
+            file.truncate_size(len).unwrap();
+            new_area.map_file = Some(Arc::new(file));
+            new_area.map_shared = true;
+        } else if huge {
+            new_area.huge_page = HugePage::Always;
         }
-        // insert MapArea and keep the order
+        new_area.locked = self.lock_future;
+        self.insert_mmap_area(new_area);
+        start_va.0 as isize
+    }
+    /// The start address of a new mmap area placed after the last one.
+    fn next_mmap_start(&self) -> VirtAddr {
+        match self.last_mmap_area_idx() {
+            Some(idx) => self.areas[idx].get_end::<T>().into(),
+            None => {
+                #[cfg(feature = "loongarch64")]
+                {
+                    USR_MMAP_BASE.into()
+                }
+                #[cfg(feature = "riscv")]
+                {
+                    MMAP_BASE.into()
+                }
+            }
+        }
+    }
+    /// Insert `new_area` into the mmap region and keep the order of areas.
+    fn insert_mmap_area(&mut self, new_area: MapArea) {
+        let start_vpn = new_area.get_start::<T>();
         #[cfg(feature = "loongarch64")]
         if let Some((idx, _)) = self
             .areas
             .iter()
             .enumerate()
             .skip_while(|(_, area)| area.get_start::<T>() >= VirtAddr::from(USR_MMAP_END).into())
-            .find(|(_, area)| area.get_start::<T>() >= start_va.into())
+            .find(|(_, area)| area.get_start::<T>() >= start_vpn)
         {
             self.areas.insert(idx, new_area);
         } else {
@@ -900,15 +1062,60 @@ impl<T: PageTable> MemorySet<T> {
             .iter()
             .enumerate()
             .skip_while(|(_, area)| area.get_start::<T>() >= VirtAddr::from(MMAP_END).into())
-            .find(|(_, area)| area.get_start::<T>() >= start_va.into())
+            .find(|(_, area)| area.get_start::<T>() >= start_vpn)
         {
             self.areas.insert(idx, new_area);
         } else {
             error!("[MemorySet::mmap] No area found higher than new_area {:?} in beginning address. TRAMPOLINES may have been mapped to wrong places!",new_area);
             self.areas.push(new_area);
         }
-
-        start_va.0 as isize
+    }
+    /// 将System V共享内存段映射到`start`处，`start`为0时在mmap区域中选择地址。
+    /// 与已有映射重叠时，`remap`（SHM_REMAP）为真则替换原映射，否则返回EINVAL
+    pub fn shmat(
+        &mut self,
+        start: usize,
+        len: usize,
+        prot: MapPermission,
+        file: Arc<dyn File>,
+        remap: bool,
+    ) -> Result<usize, isize> {
+        let start_va = if start != 0 {
+            let start_vpn = VirtAddr::from(start).floor();
+            let end_vpn = VirtAddr::from(start + len).ceil();
+            if self.areas.iter().any(|area| {
+                area.check_overlapping(start_vpn, end_vpn)
+                    .map_or(false, |(start, end)| start < end)
+            }) {
+                if !remap {
+                    return Err(EINVAL);
+                }
+                self.munmap(start, len)?;
+            }
+            VirtAddr::from(start)
+        } else {
+            self.next_mmap_start()
+        };
+        let mut new_area = MapArea::new(
+            start_va,
+            VirtAddr::from(start_va.0 + len),
+            MapType::Framed,
+            prot,
+            Some(file),
+        );
+        new_area.map_shared = true;
+        self.insert_mmap_area(new_area);
+        Ok(start_va.0)
+    }
+    /// 起始地址为`addr`的文件映射区域所映射的文件及区域长度
+    pub fn file_area_at(&self, addr: usize) -> Option<(Arc<dyn File>, usize)> {
+        let vpn = VirtAddr::from(addr).floor();
+        let area = self
+            .areas
+            .iter()
+            .find(|area| area.get_start::<T>() == vpn)?;
+        let len = (area.get_end::<T>().0 - vpn.0) * PAGE_SIZE;
+        Some((area.map_file.clone()?, len))
     }
     pub fn munmap(&mut self, start: usize, len: usize) -> Result<(), isize> {
         let start_va = VirtAddr::from(start);
@@ -919,6 +1126,7 @@ impl<T: PageTable> MemorySet<T> {
         }
         let start_vpn = start_va.floor();
         let end_vpn = end_va.ceil();
+        self.sync_shared_pages(start_vpn, end_vpn);
         let page_table = &mut self.page_table;
         let mut found_area = false;
         let mut delete: Vec<usize> = Vec::new();
@@ -972,6 +1180,264 @@ impl<T: PageTable> MemorySet<T> {
             Err(EINVAL)
         }
     }
+    /// mremap：把`[old_addr, old_addr + old_len)`调整为`new_len`字节，返回调整后的起始地址。
+    /// 范围必须落在同一个区域中。区域无法原地扩大时，`may_move`（MREMAP_MAYMOVE）允许把它移到
+    /// mmap区域的末尾，`fixed`（MREMAP_FIXED）则移到指定的地址并替换那里原有的映射。
+    /// 移动只搬移页表项，不复制物理页
+    pub fn mremap(
+        &mut self,
+        old_addr: usize,
+        old_len: usize,
+        new_len: usize,
+        may_move: bool,
+        fixed: Option<usize>,
+    ) -> Result<usize, isize> {
+        let old_start = VirtAddr::from(old_addr).floor();
+        let mut old_end = VirtAddr::from(old_addr + old_len).ceil();
+        let new_pages = VirtAddr::from(new_len).ceil().0;
+        let idx = match self.areas.iter().position(|area| {
+            area.map_perm.contains(MapPermission::U)
+                && area.get_start::<T>() <= old_start
+                && old_end <= area.get_end::<T>()
+        }) {
+            Some(idx) => idx,
+            None => return Err(EFAULT),
+        };
+        if let Some(new_addr) = fixed {
+            let new_start = VirtAddr::from(new_addr).floor();
+            if new_start.0 < old_end.0 && old_start.0 < new_start.0 + new_pages {
+                return Err(EINVAL);
+            }
+            // 先切开区域，munmap不能从文件映射的中间挖掉一段。目标位置可能没有映射
+            self.split_areas(new_start, VirtPageNum(new_start.0 + new_pages));
+            let _ = self.munmap(new_addr, new_pages * PAGE_SIZE);
+        }
+        // 缩小时先解除多出的部分
+        if new_pages < old_end.0 - old_start.0 {
+            let new_end = VirtPageNum(old_start.0 + new_pages);
+            self.split_areas(new_end, old_end);
+            self.munmap(
+                VirtAddr::from(new_end).0,
+                (old_end.0 - new_end.0) * PAGE_SIZE,
+            )?;
+            old_end = new_end;
+        }
+        if let Some(new_addr) = fixed {
+            let new_start = VirtAddr::from(new_addr).floor();
+            return Ok(self.move_pages(old_start, old_end, new_start, new_pages));
+        }
+        if new_pages == old_end.0 - old_start.0 {
+            return Ok(old_addr);
+        }
+        let grow_end = VirtPageNum(old_start.0 + new_pages);
+        if old_end == self.areas[idx].get_end::<T>()
+            && !self.areas.iter().any(|area| {
+                area.check_overlapping(old_end, grow_end)
+                    .map_or(false, |(begin, end)| begin < end)
+            })
+        {
+            trace!("[mremap] expand in place, call expand_to");
+            self.areas[idx]
+                .expand_to::<T>(VirtAddr::from(grow_end))
+                .unwrap();
+            return Ok(old_addr);
+        }
+        if !may_move {
+            return Err(ENOMEM);
+        }
+        let mut new_start = self.next_mmap_start().floor();
+        let area = &self.areas[idx];
+        // 私有匿名映射保持在大页内的偏移，已经映射的大页可以整体搬移
+        if area.map_file.is_none() && area.huge_page != HugePage::Never {
+            new_start = VirtPageNum(
+                new_start.0 + (old_start.0.wrapping_sub(new_start.0) & (HUGE_PAGE_PAGES - 1)),
+            );
+        }
+        Ok(self.move_pages(old_start, old_end, new_start, new_pages))
+    }
+    /// 把`[old_start, old_end)`切成单独的区域，连同页表项一起移到`new_start`，
+    /// 再把它扩大到`new_pages`页，返回新的起始地址。新旧范围不能重叠
+    fn move_pages(
+        &mut self,
+        old_start: VirtPageNum,
+        old_end: VirtPageNum,
+        new_start: VirtPageNum,
+        new_pages: usize,
+    ) -> usize {
+        // 页表项搬移后脏位会丢失，先把共享映射中写过的页交给页缓存
+        self.sync_shared_pages(old_start, old_end);
+        let idx = self.split_areas(old_start, old_end)[0];
+        let mut area = self.areas.remove(idx);
+        let page_table = &mut self.page_table;
+        let mut vpn = old_start;
+        while vpn < old_end {
+            let new_vpn = VirtPageNum(new_start.0 + vpn.0 - old_start.0);
+            let ppn = match page_table.translate(vpn) {
+                Some(ppn) => ppn,
+                None => {
+                    vpn = VirtPageNum(vpn.0 + 1);
+                    continue;
+                }
+            };
+            // 写时复制的页没有写权限，搬移后保持原样
+            let map_perm = if page_table.writable(vpn) == Some(true) {
+                area.map_perm
+            } else {
+                area.map_perm.difference(MapPermission::W)
+            };
+            if page_table.is_huge(vpn) {
+                if vpn.0 & (HUGE_PAGE_PAGES - 1) == 0
+                    && new_vpn.0 & (HUGE_PAGE_PAGES - 1) == 0
+                    && vpn.0 + HUGE_PAGE_PAGES <= old_end.0
+                {
+                    page_table.unmap_huge_page(vpn);
+                    page_table.map_huge_page(new_vpn, ppn, map_perm);
+                    vpn = VirtPageNum(vpn.0 + HUGE_PAGE_PAGES);
+                    continue;
+                }
+                page_table.split_huge(vpn).unwrap();
+            }
+            page_table.unmap_page(vpn);
+            page_table.map_page(new_vpn, ppn, map_perm);
+            if area.map_shared {
+                page_table.clear_dirty_bit(new_vpn).unwrap();
+            }
+            vpn = VirtPageNum(vpn.0 + 1);
+        }
+        tlb_invalidate();
+        area.move_to(new_start);
+        area.expand_to::<T>(VirtAddr::from(VirtPageNum(new_start.0 + new_pages)))
+            .unwrap();
+        self.insert_mmap_area(area);
+        VirtAddr::from(new_start).0
+    }
+    /// 收集`[addr, addr + len)`中文件映射对应的文件区间，用于 madvise(MADV_WILLNEED)
+    /// # 返回值
+    /// (文件, 文件内偏移, 长度) 的列表，范围内存在未映射的页时返回ENOMEM
+    pub fn file_ranges(
+        &self,
+        addr: usize,
+        len: usize,
+    ) -> Result<Vec<(Arc<dyn crate::fs::file_trait::File>, usize, usize)>, isize> {
+        let start_vpn = VirtAddr::from(addr).floor();
+        let end_vpn = VirtAddr::from(addr + len).ceil();
+        if !self.range_mapped(start_vpn, end_vpn) {
+            return Err(ENOMEM);
+        }
+        let mut ranges = Vec::new();
+        for area in self.areas.iter() {
+            let file = match &area.map_file {
+                Some(file) => file,
+                None => continue,
+            };
+            let (begin, end) = match area.check_overlapping(start_vpn, end_vpn) {
+                Some((begin, end)) if begin < end => (begin, end),
+                _ => continue,
+            };
+            let offset = area.file_offset + (begin.0 - area.get_start::<T>().0) * PAGE_SIZE;
+            let length = (end.0 - begin.0) * PAGE_SIZE;
+            ranges.push((file.clone(), offset, length));
+        }
+        Ok(ranges)
+    }
+    /// `[start_vpn, end_vpn)`中的页是否都属于某个区域
+    fn range_mapped(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        let mut areas: Vec<&MapArea> = self
+            .areas
+            .iter()
+            .filter(|area| area.get_start::<T>() < end_vpn && start_vpn < area.get_end::<T>())
+            .collect();
+        areas.sort_by_key(|area| area.get_start::<T>().0);
+        let mut covered = start_vpn;
+        for area in areas {
+            if area.get_start::<T>() > covered {
+                return false;
+            }
+            covered = area.get_end::<T>();
+        }
+        covered >= end_vpn
+    }
+    /// 把`[start_vpn, end_vpn)`内共享文件映射中被写过（页表项脏位置位）的页标记为脏页，
+    /// 并把文件登记到脏文件表等待回写，然后清除脏位，之后的写入会再次置位
+    /// # 返回值
+    /// 范围内有后备存储的共享映射文件
+    pub fn sync_shared_pages(
+        &mut self,
+        start_vpn: VirtPageNum,
+        end_vpn: VirtPageNum,
+    ) -> Vec<Arc<dyn File>> {
+        let mut files: Vec<Arc<dyn File>> = Vec::new();
+        for area in self.areas.iter().filter(|area| area.map_shared) {
+            let file = match &area.map_file {
+                Some(file) => file,
+                None => continue,
+            };
+            let (begin, end) = match area.check_overlapping(start_vpn, end_vpn) {
+                Some(range) => range,
+                None => continue,
+            };
+            // memfd与共享匿名映射不在目录树中，没有需要回写的磁盘
+            let node = match file.get_dirtree_node() {
+                Some(node) => node,
+                None => continue,
+            };
+            let area_start_vpn = area.get_start::<T>();
+            let mut dirtied = false;
+            for vpn in (begin.0..end.0).map(VirtPageNum::from) {
+                if self.page_table.is_dirty(vpn) != Some(true) {
+                    continue;
+                }
+                let offset = area.file_offset + (vpn.0 - area_start_vpn.0) * PAGE_SIZE;
+                if let Ok(cache) = file.get_single_cache(offset) {
+                    cache.lock().mark_dirty();
+                }
+                self.page_table.clear_dirty_bit(vpn).unwrap();
+                dirtied = true;
+            }
+            if dirtied {
+                mark_inode_dirty(&node);
+            }
+            files.push(file.clone());
+        }
+        files
+    }
+    /// 文件被截短后，解除`mappings`所属文件超出文件末尾的映射页（包括已经写时复制的私有页），
+    /// 之后访问这些页会因为`BeyondEOF`收到SIGBUS
+    pub fn unmap_beyond_eof(&mut self, mappings: &Arc<FileMappings>) {
+        let page_table = &mut self.page_table;
+        for area in self.areas.iter_mut() {
+            let file = match &area.map_file {
+                Some(file) => file.clone(),
+                None => continue,
+            };
+            match file.mappings() {
+                Some(file_mappings) if Arc::ptr_eq(&file_mappings, mappings) => {}
+                _ => continue,
+            }
+            let eof = (file.get_size() + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+            let file_offset = area.file_offset;
+            let area_start_vpn = area.get_start::<T>();
+            for vpn in area.inner.vpn_range {
+                if file_offset + (vpn.0 - area_start_vpn.0) * PAGE_SIZE >= eof
+                    && page_table.is_mapped(vpn)
+                {
+                    area.unmap_one(page_table, vpn).unwrap();
+                }
+            }
+        }
+    }
+    /// 各文件映射所属文件的映射表，fork出的地址空间需要登记到这些表中
+    pub fn file_mappings(&self) -> Vec<Arc<FileMappings>> {
+        let mut result: Vec<Arc<FileMappings>> = Vec::new();
+        for file in self.areas.iter().filter_map(|area| area.map_file.as_ref()) {
+            if let Some(mappings) = file.mappings() {
+                if !result.iter().any(|other| Arc::ptr_eq(other, &mappings)) {
+                    result.push(mappings);
+                }
+            }
+        }
+        result
+    }
     pub fn mprotect(&mut self, addr: usize, len: usize, prot: usize) -> Result<(), isize> {
         let start_va = VirtAddr::from(addr);
         let end_va = VirtAddr::from(addr + len);
@@ -988,6 +1454,9 @@ impl<T: PageTable> MemorySet<T> {
         );
         let start_vpn = start_va.floor();
         let end_vpn = end_va.ceil();
+        // 只修改大页的一部分时先拆开
+        self.split_huge_at(start_vpn);
+        self.split_huge_at(end_vpn);
         let result = self.areas.iter().enumerate().find(|(_, area)| {
             area.get_start::<T>() <= start_vpn && start_vpn < area.get_end::<T>()
         });
@@ -1001,6 +1470,28 @@ impl<T: PageTable> MemorySet<T> {
                     warn!("[mprotect] addr: {:X} is not in any MapArea", addr);
                     return Err(ENOMEM);
                 }
+                // 共享的memfd映射加上写权限时检查封印，并计入可写映射；交换文件不能加上写权限
+                let old_area = &self.areas[idx];
+                if old_area.map_shared
+                    && prot.contains(MapPermission::W)
+                    && !old_area.map_perm.contains(MapPermission::W)
+                {
+                    if let Some(memfd) = old_area
+                        .map_file
+                        .as_ref()
+                        .and_then(|file| file.downcast_ref::<MemFd>())
+                    {
+                        memfd.mprotect_writable()?;
+                    }
+                    if old_area
+                        .map_file
+                        .as_ref()
+                        .and_then(|file| file.get_dirtree_node())
+                        .map_or(false, |inode| inode.is_swapfile())
+                    {
+                        return Err(ETXTBSY);
+                    }
+                }
                 let area: &mut MapArea = if start_vpn == area_start_vpn && end_vpn == area_end_vpn {
                     trace!("[mprotect] change prot of whole area, idx: {}", idx);
                     &mut self.areas[idx]
@@ -1024,7 +1515,12 @@ impl<T: PageTable> MemorySet<T> {
                 };
                 let page_table = &mut self.page_table;
                 let mut has_unmapped_page = false;
+                // Sv39中不带R和X的大页表项会被当作指向下一级页表的目录项，只能先拆成小页
+                let split = !prot.intersects(MapPermission::R | MapPermission::X);
                 for vpn in area.inner.vpn_range {
+                    if split && page_table.is_huge(vpn) {
+                        page_table.split_huge(vpn).unwrap();
+                    }
                     // Clear W prot, or CoW pages may be written unexpectedly.
                     // And those pages will gain W prot by CoW.
                     if let Err(_) = page_table.set_pte_flags(vpn, prot - MapPermission::W) {
@@ -1044,6 +1540,252 @@ impl<T: PageTable> MemorySet<T> {
         }
         Ok(())
     }
+    /// 拆开跨过`vpn`的大页，在`vpn`处切开区域或只修改一部分页之前调用
+    fn split_huge_at(&mut self, vpn: VirtPageNum) {
+        if vpn.0 & (HUGE_PAGE_PAGES - 1) != 0 && self.page_table.is_huge(vpn) {
+            self.page_table.split_huge(vpn).unwrap();
+        }
+    }
+    /// 在`start_vpn`与`end_vpn`处切开区域，返回完整落在`[start_vpn, end_vpn)`中的区域下标
+    fn split_areas(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> Vec<usize> {
+        self.split_huge_at(start_vpn);
+        self.split_huge_at(end_vpn);
+        let mut indices = Vec::new();
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            let area = &mut self.areas[idx];
+            let (begin, end) = match area.check_overlapping(start_vpn, end_vpn) {
+                Some((begin, end)) if begin < end => (begin, end),
+                _ => {
+                    idx += 1;
+                    continue;
+                }
+            };
+            if begin > area.get_start::<T>() {
+                // 后一半在下一轮处理
+                let second = area.into_two(begin).unwrap();
+                self.areas.insert(idx + 1, second);
+                idx += 1;
+                continue;
+            }
+            if end < area.get_end::<T>() {
+                let second = area.into_two(end).unwrap();
+                self.areas.insert(idx + 1, second);
+            }
+            indices.push(idx);
+            idx += 1;
+        }
+        indices
+    }
+    /// madvise(MADV_HUGEPAGE/MADV_NOHUGEPAGE)：设置`[addr, addr + len)`内的区域使用大页的方式，
+    /// 区域只有一部分在范围内时先切开。只影响之后的缺页，已经映射的大页保持不变
+    pub fn set_huge_page(
+        &mut self,
+        addr: usize,
+        len: usize,
+        huge_page: HugePage,
+    ) -> Result<(), isize> {
+        let start_vpn = VirtAddr::from(addr).floor();
+        let end_vpn = VirtAddr::from(addr + len).ceil();
+        let indices = self.split_areas(start_vpn, end_vpn);
+        if indices.is_empty() {
+            return Err(ENOMEM);
+        }
+        for idx in indices {
+            // 文件映射与共享匿名映射不使用大页，建议被忽略
+            if self.areas[idx].map_file.is_none() {
+                self.areas[idx].huge_page = huge_page;
+            }
+        }
+        Ok(())
+    }
+    /// madvise(MADV_SEQUENTIAL/MADV_RANDOM/MADV_NORMAL)：设置`[addr, addr + len)`内的文件映射
+    /// 缺页时是否预读之后的页
+    pub fn set_sequential(
+        &mut self,
+        addr: usize,
+        len: usize,
+        sequential: bool,
+    ) -> Result<(), isize> {
+        let start_vpn = VirtAddr::from(addr).floor();
+        let end_vpn = VirtAddr::from(addr + len).ceil();
+        if !self.range_mapped(start_vpn, end_vpn) {
+            return Err(ENOMEM);
+        }
+        for idx in self.split_areas(start_vpn, end_vpn) {
+            self.areas[idx].sequential = sequential;
+        }
+        Ok(())
+    }
+    /// madvise(MADV_WILLNEED)：把`[addr, addr + len)`中被压缩或换出的匿名页换回内存
+    #[cfg(feature = "oom_handler")]
+    pub fn swap_in_range(&mut self, addr: usize, len: usize) {
+        let start_vpn = VirtAddr::from(addr).floor();
+        let end_vpn = VirtAddr::from(addr + len).ceil();
+        let mut vpns = Vec::new();
+        for area in self.areas.iter() {
+            if area.inner.compressed == 0 && area.inner.swapped == 0 {
+                continue;
+            }
+            if let Some((begin, end)) = area.check_overlapping(start_vpn, end_vpn) {
+                vpns.extend((begin.0..end.0).map(VirtPageNum).filter(|vpn| {
+                    matches!(
+                        area.inner.frames[vpn.0 - area.get_start::<T>().0],
+                        Frame::Compressed(_) | Frame::SwappedOut(_)
+                    )
+                }));
+            }
+        }
+        for vpn in vpns {
+            if self.do_page_fault(vpn.into()).is_err() {
+                break;
+            }
+        }
+    }
+    /// swapoff：把换出到第`swap_type`个交换区的页换回内存
+    #[cfg(feature = "oom_handler")]
+    pub fn swap_in_area(&mut self, swap_type: usize) -> Result<(), isize> {
+        let mut vpns = Vec::new();
+        for area in self.areas.iter() {
+            if area.inner.swapped == 0 {
+                continue;
+            }
+            let start_vpn = area.get_start::<T>();
+            vpns.extend(
+                area.inner
+                    .frames
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, frame)| match frame {
+                        Frame::SwappedOut(swap_tracker) => swp_type(swap_tracker.0) == swap_type,
+                        _ => false,
+                    })
+                    .map(|(idx, _)| VirtPageNum(start_vpn.0 + idx)),
+            );
+        }
+        for vpn in vpns {
+            self.do_page_fault(vpn.into()).map_err(|_| ENOMEM)?;
+        }
+        Ok(())
+    }
+    /// madvise(MADV_DONTNEED/MADV_FREE)：丢弃`[addr, addr + len)`中的页，
+    /// 共享映射中被写过的页先交给页缓存。`anon_only`（MADV_FREE）只允许私有匿名映射，
+    /// 这里不做延迟释放，与MADV_DONTNEED一样立即丢弃
+    pub fn discard(&mut self, addr: usize, len: usize, anon_only: bool) -> Result<(), isize> {
+        let start_vpn = VirtAddr::from(addr).floor();
+        let end_vpn = VirtAddr::from(addr + len).ceil();
+        if !self.range_mapped(start_vpn, end_vpn) {
+            return Err(ENOMEM);
+        }
+        // 锁定的页必须留在内存中，陷入上下文等内核使用的区域也不能丢弃
+        if self.areas.iter().any(|area| {
+            area.check_overlapping(start_vpn, end_vpn)
+                .map_or(false, |(begin, end)| begin < end)
+                && (area.locked
+                    || !area.map_perm.contains(MapPermission::U)
+                    || (anon_only && area.map_file.is_some()))
+        }) {
+            return Err(EINVAL);
+        }
+        self.sync_shared_pages(start_vpn, end_vpn);
+        let page_table = &mut self.page_table;
+        for area in self.areas.iter_mut() {
+            if let Some((begin, end)) = area.check_overlapping(start_vpn, end_vpn) {
+                area.discard_range(page_table, begin, end);
+            }
+        }
+        Ok(())
+    }
+    /// mlock/mlock2：锁定`[addr, addr + len)`内的区域，`populate`为真时立即为其中的页分配内存，
+    /// 否则（MLOCK_ONFAULT）在缺页时分配。范围内存在未映射的页时返回ENOMEM
+    pub fn mlock(&mut self, addr: usize, len: usize, populate: bool) -> Result<(), isize> {
+        let start_vpn = VirtAddr::from(addr).floor();
+        let end_vpn = VirtAddr::from(addr + len).ceil();
+        if !self.range_mapped(start_vpn, end_vpn) {
+            return Err(ENOMEM);
+        }
+        let indices = self.split_areas(start_vpn, end_vpn);
+        for &idx in indices.iter() {
+            self.areas[idx].locked = true;
+        }
+        if populate {
+            self.populate(&indices);
+        }
+        Ok(())
+    }
+    /// munlock：解除`[addr, addr + len)`内区域的锁定，页之后可以被回收
+    pub fn munlock(&mut self, addr: usize, len: usize) -> Result<(), isize> {
+        let start_vpn = VirtAddr::from(addr).floor();
+        let end_vpn = VirtAddr::from(addr + len).ceil();
+        if !self.range_mapped(start_vpn, end_vpn) {
+            return Err(ENOMEM);
+        }
+        for idx in self.split_areas(start_vpn, end_vpn) {
+            self.areas[idx].locked = false;
+        }
+        Ok(())
+    }
+    /// mlockall：`current`（MCL_CURRENT）锁定现有的用户区域，
+    /// `future`（MCL_FUTURE）锁定之后新建的映射，`populate`的含义同`mlock`
+    pub fn mlockall(&mut self, current: bool, future: bool, populate: bool) {
+        if current {
+            let indices: Vec<usize> = (0..self.areas.len())
+                .filter(|&idx| self.areas[idx].map_perm.contains(MapPermission::U))
+                .collect();
+            for &idx in indices.iter() {
+                self.areas[idx].locked = true;
+            }
+            if populate {
+                self.populate(&indices);
+            }
+        }
+        self.lock_future = future;
+    }
+    /// munlockall：解除所有区域的锁定并取消MCL_FUTURE
+    pub fn munlockall(&mut self) {
+        for area in self.areas.iter_mut() {
+            area.locked = false;
+        }
+        self.lock_future = false;
+    }
+    /// 为下标为`indices`的区域中还不在内存中的页触发缺页，
+    /// 被压缩或换出的页也会被换回。不可读的区域与文件末尾之后的页保持原样
+    fn populate(&mut self, indices: &[usize]) {
+        for &idx in indices {
+            if !self.areas[idx]
+                .map_perm
+                .contains(MapPermission::R | MapPermission::U)
+            {
+                continue;
+            }
+            for vpn in self.areas[idx].inner.vpn_range {
+                if !self.page_table.is_mapped(vpn) && self.do_page_fault(vpn.into()).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    /// mincore：`[addr, addr + len)`中每页一个字节，最低位表示页是否在内存中。
+    /// 还未分配以及被压缩或换出的页不在内存中
+    pub fn mincore(&self, addr: usize, len: usize) -> Result<Vec<u8>, isize> {
+        let start_vpn = VirtAddr::from(addr).floor();
+        let end_vpn = VirtAddr::from(addr + len).ceil();
+        if !self.range_mapped(start_vpn, end_vpn) {
+            return Err(ENOMEM);
+        }
+        let mut vec = Vec::new();
+        vec.resize(end_vpn.0 - start_vpn.0, 0u8);
+        for area in self.areas.iter() {
+            if let Some((begin, end)) = area.check_overlapping(start_vpn, end_vpn) {
+                for vpn in begin.0..end.0 {
+                    if area.inner.get_in_memory(&VirtPageNum(vpn)).is_some() {
+                        vec[vpn - start_vpn.0] = 1;
+                    }
+                }
+            }
+        }
+        Ok(vec)
+    }
     pub fn create_elf_tables(
         &self,
         mut user_sp: usize,
@@ -1231,8 +1973,15 @@ impl<T: PageTable> MemorySet<T> {
             .unwrap();
     }
 
-    pub fn is_dirty(&self, ppn: PhysPageNum) -> Option<bool> {
-        self.page_table.is_dirty((ppn.0).into())
+    pub fn is_dirty(&self, vpn: VirtPageNum) -> Option<bool> {
+        self.page_table.is_dirty(vpn)
+    }
+}
+
+impl<T: PageTable> Drop for MemorySet<T> {
+    fn drop(&mut self) {
+        self.remove_all_rmap();
+        super::vmscan::unregister_mm(self.token());
     }
 }
 
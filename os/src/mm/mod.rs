@@ -3,7 +3,10 @@ mod frame_allocator;
 mod heap_allocator;
 mod map_area;
 mod memory_set;
+mod page;
 mod page_table;
+mod slab;
+mod vmscan;
 #[cfg(feature = "zram")]
 mod zram;
 pub use crate::hal::{KernelPageTableImpl, PageTableImpl};
@@ -11,12 +14,19 @@ pub use address::PPNRange;
 use address::VPNRange;
 pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
 pub use frame_allocator::{
-    frame_alloc, frame_alloc_uninit, frame_dealloc, frame_reserve, unallocated_frames, FrameTracker,
+    frame_alloc, frame_alloc_contiguous, frame_alloc_uninit, frame_dealloc,
+    frame_dealloc_contiguous, frame_reserve, free_area_stats, unallocated_frames, FrameTracker,
+    ZoneType, FRAME_DESC_SIZE, MAX_ORDER,
 };
-pub use map_area::{Frame, MapFlags, MapPermission};
+pub use heap_allocator::{heap_stats, HeapStats};
+pub use map_area::{Frame, HugePage, MapFlags, MapPermission};
 pub use memory_set::kernel_token;
 pub use memory_set::MemoryError;
-pub use memory_set::{MemorySet, KERNEL_SPACE};
+pub use memory_set::{FileMappings, MemorySet, KERNEL_SPACE};
+pub use page::{
+    get_page, page_clear_flags, page_set_flags, ppn_to_page, put_page, Page, PageFlags, PageOwner,
+    Rmap, PAGE_DESC_SIZE,
+};
 pub use page_table::{
     copy_from_user,
     copy_from_user_array,
@@ -34,10 +44,21 @@ pub use page_table::{
     UserBuffer,
     // UserBufferIterator,
 };
+pub use slab::{slab_stats, slabinfo, SlabCache, SlabStats};
+#[cfg(feature = "oom_handler")]
+pub use vmscan::swap_in_all;
+pub use vmscan::{
+    kswapd_tick, lru_add_file, lru_stats, mark_page_accessed, register_mm, LruList, WMARK_HIGH,
+    WMARK_LOW,
+};
+#[cfg(feature = "zram")]
+pub use zram::{zram_attrs, zram_test};
 
 pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
+    page::init_mem_map();
+    heap_allocator::enable_heap_growth();
     KERNEL_SPACE.lock().activate();
 }
 pub use crate::hal::tlb_invalidate;
@@ -0,0 +1,292 @@
+//! 物理页描述符表。
+//! 每个物理页对应一个[`Page`]，记录引用计数、映射计数、状态标志与反向映射，
+//! 用于回收、迁移与统计时回答“这个页被谁映射”“这个页是否为脏页或正在I/O”等问题
+use super::{PhysPageNum, VirtPageNum};
+use crate::config::PAGE_SIZE_BITS;
+use crate::hal::boot_info;
+use alloc::boxed::Box;
+use alloc::sync::Weak;
+use alloc::vec::Vec;
+use core::mem::size_of;
+use core::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+use lazy_static::*;
+use spin::Mutex;
+
+bitflags! {
+    /// 物理页的状态标志
+    pub struct PageFlags: u16 {
+        /// 页面内容比后备存储新，需要写回
+        const DIRTY = 1 << 0;
+        /// 正在与磁盘交换数据，内容暂时不可信
+        const LOCKED = 1 << 1;
+        /// 匿名页，回收时需要写入swap或zram
+        const SWAP_BACKED = 1 << 2;
+        /// 属于某个文件的页缓存
+        const PAGE_CACHE = 1 << 3;
+        /// 内核镜像等不由帧分配器管理的页
+        const RESERVED = 1 << 4;
+        /// 在LRU链表中
+        const LRU = 1 << 5;
+        /// 在活跃链表中
+        const ACTIVE = 1 << 6;
+        /// 最近被内核（如`read`）访问过，与页表项的访问位一起决定页的冷热
+        const REFERENCED = 1 << 7;
+        /// 属于slab缓存，块中的每一页都有这个标志
+        const SLAB = 1 << 8;
+        /// 内核堆中较大的分配直接使用的块的首页
+        const LARGE_ALLOC = 1 << 9;
+    }
+}
+
+/// 页缓存所在的缓存池。回收文件页时通过页描述符找到它，再把页从缓存中删除
+pub trait PageOwner: Send + Sync {
+    /// 尝试把下标为`index`的页从缓存中删除，页仍在使用或是脏页时返回`false`
+    fn evict(&self, index: usize) -> bool;
+}
+
+/// 反向映射：页被映射在哪个地址空间（页表token）的哪个虚拟页上。
+/// 通过token可以找到所属的地址空间，再由虚拟页号找到对应的`MapArea`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rmap {
+    pub token: usize,
+    pub vpn: VirtPageNum,
+}
+
+/// 页描述符中不常用的部分，只有被映射或属于页缓存的页才分配
+#[derive(Default)]
+struct PageExt {
+    rmap: Vec<Rmap>,
+    /// 页缓存页所在的缓存池及页在其中的下标
+    owner: Option<(Weak<dyn PageOwner>, usize)>,
+}
+
+impl PageExt {
+    fn is_empty(&self) -> bool {
+        self.rmap.is_empty() && self.owner.is_none()
+    }
+}
+
+/// 物理页描述符。每个物理页都有一个，所以只保留定长的字段，反向映射与缓存池放在`ext`中
+pub struct Page {
+    /// 持有者数量：`FrameTracker`计1，`get_page`临时固定的页另计。归零时释放物理页
+    refcount: AtomicU32,
+    /// 映射这个页的页表项数量，与`rmap`的长度相同
+    mapcount: AtomicU32,
+    flags: AtomicU16,
+    /// LRU链表中前后两个页的描述符下标，只在持有LRU锁时访问
+    pub(super) lru_prev: AtomicU32,
+    pub(super) lru_next: AtomicU32,
+    ext: Mutex<Option<Box<PageExt>>>,
+}
+
+/// 每个物理页的描述符占用的字节数，启动时据此保留页描述符表
+pub const PAGE_DESC_SIZE: usize = size_of::<Page>();
+
+impl Page {
+    fn new(flags: PageFlags) -> Self {
+        Self {
+            refcount: AtomicU32::new(0),
+            mapcount: AtomicU32::new(0),
+            flags: AtomicU16::new(flags.bits()),
+            lru_prev: AtomicU32::new(u32::MAX),
+            lru_next: AtomicU32::new(u32::MAX),
+            ext: Mutex::new(None),
+        }
+    }
+    pub fn refcount(&self) -> usize {
+        self.refcount.load(Ordering::Acquire) as usize
+    }
+    pub fn mapcount(&self) -> usize {
+        self.mapcount.load(Ordering::Acquire) as usize
+    }
+    pub fn flags(&self) -> PageFlags {
+        PageFlags::from_bits_truncate(self.flags.load(Ordering::Acquire))
+    }
+    pub fn test_flags(&self, flags: PageFlags) -> bool {
+        self.flags().contains(flags)
+    }
+    pub fn set_flags(&self, flags: PageFlags) {
+        self.flags.fetch_or(flags.bits(), Ordering::AcqRel);
+    }
+    pub fn clear_flags(&self, flags: PageFlags) {
+        self.flags.fetch_and(!flags.bits(), Ordering::AcqRel);
+    }
+    /// 所有映射了这个页的位置
+    pub fn rmap(&self) -> Vec<Rmap> {
+        self.ext
+            .lock()
+            .as_ref()
+            .map_or(Vec::new(), |ext| ext.rmap.clone())
+    }
+    fn add_rmap(&self, rmap: Rmap) {
+        let mut ext = self.ext.lock();
+        let list = &mut ext.get_or_insert_with(Default::default).rmap;
+        list.push(rmap);
+        self.mapcount.store(list.len() as u32, Ordering::Release);
+    }
+    fn remove_rmap(&self, rmap: Rmap) {
+        let mut guard = self.ext.lock();
+        if let Some(ext) = guard.as_mut() {
+            if let Some(idx) = ext.rmap.iter().position(|item| *item == rmap) {
+                ext.rmap.swap_remove(idx);
+                self.mapcount
+                    .store(ext.rmap.len() as u32, Ordering::Release);
+            }
+            if ext.is_empty() {
+                *guard = None;
+            }
+        }
+    }
+    /// 清除并返回软件访问位
+    pub fn test_and_clear_referenced(&self) -> bool {
+        let old = self
+            .flags
+            .fetch_and(!PageFlags::REFERENCED.bits(), Ordering::AcqRel);
+        PageFlags::from_bits_truncate(old).contains(PageFlags::REFERENCED)
+    }
+    /// 记录页所在的缓存池，页被释放时自动清除
+    pub fn set_owner(&self, owner: Weak<dyn PageOwner>, index: usize) {
+        self.ext.lock().get_or_insert_with(Default::default).owner = Some((owner, index));
+    }
+    /// 让页所在的缓存池删除这个页，不属于任何缓存池时返回`false`
+    pub fn evict(&self) -> bool {
+        let owner = self.ext.lock().as_ref().and_then(|ext| ext.owner.clone());
+        match owner.and_then(|(owner, index)| owner.upgrade().map(|owner| (owner, index))) {
+            Some((owner, index)) => owner.evict(index),
+            None => false,
+        }
+    }
+    /// 页被分配出去时由`FrameTracker`调用
+    pub(super) fn init(&self) {
+        self.refcount.store(1, Ordering::Release);
+        self.flags.store(0, Ordering::Release);
+    }
+    /// 增加一个持有者
+    pub fn get(&self) {
+        let old = self.refcount.fetch_add(1, Ordering::AcqRel);
+        debug_assert!(old > 0, "get a free page");
+    }
+    /// 页未被释放时增加一个持有者，用于从LRU链表中取页，链表中的页可能正在被释放
+    pub fn get_unless_zero(&self) -> bool {
+        self.refcount
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count > 0).then(|| count + 1)
+            })
+            .is_ok()
+    }
+    /// 减少一个持有者，返回是否为最后一个
+    pub fn put(&self) -> bool {
+        let old = self.refcount.fetch_sub(1, Ordering::AcqRel);
+        debug_assert!(old > 0, "put a free page");
+        if old != 1 {
+            return false;
+        }
+        if self.test_flags(PageFlags::LRU) {
+            super::vmscan::lru_del(self);
+        }
+        // 换出与压缩先释放页再解除页表映射，残留的反向映射在这里清除
+        drop(self.ext.lock().take());
+        self.mapcount.store(0, Ordering::Release);
+        self.flags.store(0, Ordering::Release);
+        true
+    }
+}
+
+/// 物理页描述符表，下标见`BootInfo::ram_page_index`
+pub struct MemMap {
+    pages: &'static [Page],
+}
+
+lazy_static! {
+    /// 覆盖启动时发现的所有物理内存的全局页描述符表，不交给帧分配器的页标记为保留。
+    /// 表的大小与内存成正比，存放在启动时保留的`BootInfo::mem_map`中，不占用内核堆
+    pub static ref MEM_MAP: MemMap = {
+        let info = boot_info();
+        let base = info.mem_map.start as *mut Page;
+        assert!(info.mem_map.len() >= info.ram_pages() * PAGE_DESC_SIZE);
+        for idx in 0..info.ram_pages() {
+            let addr = info.ram_index_to_ppn(idx) << PAGE_SIZE_BITS;
+            let flags = if info.is_free(addr) {
+                PageFlags::empty()
+            } else {
+                PageFlags::RESERVED
+            };
+            unsafe { base.add(idx).write(Page::new(flags)) };
+        }
+        MemMap {
+            pages: unsafe { core::slice::from_raw_parts(base, info.ram_pages()) },
+        }
+    };
+}
+
+/// 建立页描述符表，避免第一次访问发生在持有帧分配器的锁时
+pub(super) fn init_mem_map() {
+    lazy_static::initialize(&MEM_MAP);
+}
+
+/// 描述符在表中的下标，用作LRU链表的节点编号
+pub(super) fn page_index(page: &Page) -> u32 {
+    let offset = page as *const Page as usize - MEM_MAP.pages.as_ptr() as usize;
+    (offset / PAGE_DESC_SIZE) as u32
+}
+
+/// 下标为`idx`的描述符
+pub(super) fn index_to_page(idx: u32) -> &'static Page {
+    &MEM_MAP.pages[idx as usize]
+}
+
+/// 下标为`idx`的描述符对应的物理页号
+pub(super) fn index_to_ppn(idx: u32) -> PhysPageNum {
+    PhysPageNum::from(boot_info().ram_index_to_ppn(idx as usize))
+}
+
+/// 物理页号对应的描述符，MMIO等不在内存范围内的页返回`None`
+pub fn ppn_to_page(ppn: PhysPageNum) -> Option<&'static Page> {
+    boot_info()
+        .ram_page_index(ppn.0)
+        .and_then(|idx| MEM_MAP.pages.get(idx))
+}
+
+/// 记录`ppn`被映射到了地址空间`token`的`vpn`处
+pub fn page_add_rmap(ppn: PhysPageNum, token: usize, vpn: VirtPageNum) {
+    if let Some(page) = ppn_to_page(ppn) {
+        page.add_rmap(Rmap { token, vpn });
+    }
+}
+
+/// 删除`page_add_rmap`记录的映射，不存在时什么也不做
+pub fn page_remove_rmap(ppn: PhysPageNum, token: usize, vpn: VirtPageNum) {
+    if let Some(page) = ppn_to_page(ppn) {
+        page.remove_rmap(Rmap { token, vpn });
+    }
+}
+
+/// 固定一个已分配的页，在对应的`put_page`之前不会被释放
+pub fn get_page(ppn: PhysPageNum) {
+    if let Some(page) = ppn_to_page(ppn) {
+        page.get();
+    }
+}
+
+/// 释放`get_page`固定的页，最后一个持有者负责把页还给帧分配器
+pub fn put_page(ppn: PhysPageNum) {
+    if let Some(page) = ppn_to_page(ppn) {
+        if page.put() {
+            super::frame_dealloc(ppn);
+        }
+    }
+}
+
+/// 设置`ppn`的状态标志
+pub fn page_set_flags(ppn: PhysPageNum, flags: PageFlags) {
+    if let Some(page) = ppn_to_page(ppn) {
+        page.set_flags(flags);
+    }
+}
+
+/// 清除`ppn`的状态标志
+pub fn page_clear_flags(ppn: PhysPageNum, flags: PageFlags) {
+    if let Some(page) = ppn_to_page(ppn) {
+        page.clear_flags(flags);
+    }
+}
@@ -1,7 +1,9 @@
 use core::ops::IndexMut;
 
 pub use super::memory_set::check_page_fault;
+use super::page::{page_add_rmap, page_remove_rmap};
 use super::{MapPermission, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+use crate::config::HUGE_PAGE_PAGES;
 use alloc::string::String;
 use alloc::vec::Vec;
 
@@ -28,6 +30,48 @@ pub trait PageTable {
     fn unmap_identical(&mut self, vpn: VirtPageNum) {
         self.unmap(vpn)
     }
+    /// 映射一个由`MapArea`管理的物理页，并在页描述符中记录反向映射
+    fn map_page(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: MapPermission) {
+        self.map(vpn, ppn, flags);
+        page_add_rmap(ppn, self.token(), vpn);
+    }
+    /// 解除`map_page`建立的映射，并删除反向映射
+    fn unmap_page(&mut self, vpn: VirtPageNum) {
+        if let Some(ppn) = self.translate(vpn) {
+            page_remove_rmap(ppn, self.token(), vpn);
+        }
+        self.unmap(vpn)
+    }
+    /// 用一个二级页表项映射从`vpn`开始的`HUGE_PAGE_PAGES`个页，`vpn`与`ppn`都按大页对齐。
+    /// 其余逐页的操作落在大页中时作用于整个大页，只修改其中一部分前要先用`split_huge`拆开
+    /// # 特例
+    /// Panics if some page in the huge page is mapped.
+    fn map_huge(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: MapPermission);
+    /// 解除`vpn`所在大页的映射
+    /// # Exceptions
+    /// Panics if `vpn` is not in a huge page.
+    fn unmap_huge(&mut self, vpn: VirtPageNum);
+    /// `vpn`是否由大页映射
+    fn is_huge(&self, vpn: VirtPageNum) -> bool;
+    /// 把`vpn`所在的大页拆成`HUGE_PAGE_PAGES`个权限相同的普通页表项，不在大页中时返回`Err`
+    fn split_huge(&mut self, vpn: VirtPageNum) -> Result<(), ()>;
+    /// 映射由`MapArea`管理的大页，为其中每一页记录反向映射
+    fn map_huge_page(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: MapPermission) {
+        self.map_huge(vpn, ppn, flags);
+        for i in 0..HUGE_PAGE_PAGES {
+            page_add_rmap(PhysPageNum(ppn.0 + i), self.token(), VirtPageNum(vpn.0 + i));
+        }
+    }
+    /// 解除`map_huge_page`建立的映射，并删除其中每一页的反向映射
+    fn unmap_huge_page(&mut self, vpn: VirtPageNum) {
+        let vpn = VirtPageNum(vpn.0 & !(HUGE_PAGE_PAGES - 1));
+        if let Some(ppn) = self.translate(vpn) {
+            for i in 0..HUGE_PAGE_PAGES {
+                page_remove_rmap(PhysPageNum(ppn.0 + i), self.token(), VirtPageNum(vpn.0 + i));
+            }
+        }
+        self.unmap_huge(vpn)
+    }
     /// Translate the `vpn` into its corresponding `Some(PageTableEntry)` if exists
     /// `None` is returned if nothing is found.
     fn translate(&self, vpn: VirtPageNum) -> Option<PhysPageNum>;
@@ -44,6 +88,8 @@ pub trait PageTable {
     fn set_pte_flags(&mut self, vpn: VirtPageNum, flags: MapPermission) -> Result<(), ()>;
     fn clear_access_bit(&mut self, vpn: VirtPageNum) -> Result<(), ()>;
     fn clear_dirty_bit(&mut self, vpn: VirtPageNum) -> Result<(), ()>;
+    /// 设置访问位，用于硬件不自动设置访问位时的缺页处理
+    fn set_access_bit(&mut self, vpn: VirtPageNum) -> Result<(), ()>;
     fn new() -> Self;
     #[inline(always)]
     fn new_kern_space() -> Self
@@ -61,6 +107,8 @@ pub trait PageTable {
     fn activate(&self);
     fn is_valid(&self, vpn: VirtPageNum) -> Option<bool>;
     fn is_dirty(&self, vpn: VirtPageNum) -> Option<bool>;
+    /// 访问位，页未映射或硬件不记录访问位时返回`None`
+    fn is_accessed(&self, vpn: VirtPageNum) -> Option<bool>;
     fn readable(&self, vpn: VirtPageNum) -> Option<bool>;
     fn writable(&self, vpn: VirtPageNum) -> Option<bool>;
     fn executable(&self, vpn: VirtPageNum) -> Option<bool>;
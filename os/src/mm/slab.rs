@@ -0,0 +1,281 @@
+//! slab缓存。
+//! 每个缓存只分配一种大小的对象，对象放在从帧分配器申请的块（slab）中，块的开头是块头。
+//! 帧分配器返回的块按自身大小对齐，释放对象时由地址直接找到所在的块。
+//! 全局堆分配器按`Layout`把频繁分配的内核对象交给对应的缓存
+use super::frame_allocator::{kernel_pages_alloc, kernel_pages_free};
+use super::page::{ppn_to_page, PageFlags};
+use crate::config::{PAGE_SIZE, PAGE_SIZE_BITS};
+use crate::fs::directory_tree::DirectoryTreeNode;
+use crate::fs::{BufferCache, PageCache};
+use crate::task::TaskControlBlock;
+use alloc::format;
+use alloc::string::String;
+use core::alloc::Layout;
+use core::mem::{align_of, size_of};
+use core::ptr::null_mut;
+use spin::Mutex;
+
+/// 一个块中至少放下的对象数
+const MIN_OBJECTS: usize = 8;
+/// 块的最大阶
+const MAX_SLAB_ORDER: usize = 3;
+
+const fn align_up(x: usize, align: usize) -> usize {
+    (x + align - 1) & !(align - 1)
+}
+
+/// 块头，位于块的开头
+struct Slab {
+    /// 有空闲对象的块组成的双向链表
+    prev: *mut Slab,
+    next: *mut Slab,
+    /// 空闲对象组成的单链表，下一个空闲对象的地址存放在对象的开头
+    free: *mut usize,
+    /// 已分配的对象数
+    inuse: usize,
+}
+
+/// 缓存中的块
+struct SlabList {
+    /// 有空闲对象的块，全满的块不在链表中
+    partial: *mut Slab,
+    slabs: usize,
+    /// 全空的块数，最多保留一个，其余的归还给帧分配器
+    empty: usize,
+    /// 已分配的对象数
+    active: usize,
+}
+
+// 块只在持有缓存的锁时访问
+unsafe impl Send for SlabList {}
+
+impl SlabList {
+    unsafe fn push(&mut self, slab: *mut Slab) {
+        (*slab).prev = null_mut();
+        (*slab).next = self.partial;
+        if !self.partial.is_null() {
+            (*self.partial).prev = slab;
+        }
+        self.partial = slab;
+    }
+    unsafe fn unlink(&mut self, slab: *mut Slab) {
+        let (prev, next) = ((*slab).prev, (*slab).next);
+        if prev.is_null() {
+            self.partial = next;
+        } else {
+            (*prev).next = next;
+        }
+        if !next.is_null() {
+            (*next).prev = prev;
+        }
+        (*slab).prev = null_mut();
+        (*slab).next = null_mut();
+    }
+}
+
+/// 一种固定大小对象的缓存
+pub struct SlabCache {
+    name: &'static str,
+    /// 对象的大小与对齐，也是每个对象在块中占用的空间
+    size: usize,
+    align: usize,
+    /// 块的阶
+    order: usize,
+    /// 每个块中的对象数
+    objects: usize,
+    /// 第一个对象在块中的偏移
+    offset: usize,
+    list: Mutex<SlabList>,
+}
+
+impl SlabCache {
+    /// 选择能放下至少`MIN_OBJECTS`个对象的最小的块
+    pub const fn new(name: &'static str, size: usize, align: usize) -> Self {
+        let align = if align > align_of::<usize>() {
+            align
+        } else {
+            align_of::<usize>()
+        };
+        let size = align_up(if size > 0 { size } else { 1 }, align);
+        let offset = align_up(size_of::<Slab>(), align);
+        let mut order = 0;
+        while order < MAX_SLAB_ORDER && ((PAGE_SIZE << order) - offset) / size < MIN_OBJECTS {
+            order += 1;
+        }
+        let objects = ((PAGE_SIZE << order) - offset) / size;
+        assert!(objects > 0, "object too large for a slab");
+        Self {
+            name,
+            size,
+            align,
+            order,
+            objects,
+            offset,
+            list: Mutex::new(SlabList {
+                partial: null_mut(),
+                slabs: 0,
+                empty: 0,
+                active: 0,
+            }),
+        }
+    }
+    /// `Arc<T>`分配的`ArcInner<T>`按`#[repr(C)]`布局：两个引用计数之后是`T`
+    pub const fn for_arc<T>(name: &'static str) -> Self {
+        let align = if align_of::<T>() > align_of::<usize>() {
+            align_of::<T>()
+        } else {
+            align_of::<usize>()
+        };
+        let offset = align_up(2 * size_of::<usize>(), align_of::<T>());
+        Self::new(name, align_up(offset + size_of::<T>(), align), align)
+    }
+    fn matches(&self, layout: &Layout) -> bool {
+        layout.size() == self.size && layout.align() == self.align
+    }
+    /// 申请新的块并把所有对象串成空闲链表
+    unsafe fn new_slab(&self) -> Option<*mut Slab> {
+        let ppn = kernel_pages_alloc(self.order)?;
+        for ppn in ppn.0..ppn.0 + (1 << self.order) {
+            ppn_to_page(ppn.into()).unwrap().set_flags(PageFlags::SLAB);
+        }
+        let slab = (ppn.0 << PAGE_SIZE_BITS) as *mut Slab;
+        let base = slab as usize + self.offset;
+        let mut free = null_mut();
+        for i in (0..self.objects).rev() {
+            let object = (base + i * self.size) as *mut usize;
+            *object = free as usize;
+            free = object;
+        }
+        slab.write(Slab {
+            prev: null_mut(),
+            next: null_mut(),
+            free,
+            inuse: 0,
+        });
+        Some(slab)
+    }
+    /// 分配一个对象，内存不足时返回空指针
+    pub unsafe fn alloc(&self) -> *mut u8 {
+        loop {
+            let mut list = self.list.lock();
+            let slab = list.partial;
+            if !slab.is_null() {
+                let object = (*slab).free;
+                (*slab).free = *object as *mut usize;
+                if (*slab).inuse == 0 {
+                    list.empty -= 1;
+                }
+                (*slab).inuse += 1;
+                list.active += 1;
+                if (*slab).free.is_null() {
+                    list.unlink(slab);
+                }
+                return object as *mut u8;
+            }
+            drop(list);
+            // 申请新块时不持有锁，帧分配器可能需要回收内存
+            let slab = match self.new_slab() {
+                Some(slab) => slab,
+                None => return null_mut(),
+            };
+            let mut list = self.list.lock();
+            list.push(slab);
+            list.slabs += 1;
+            list.empty += 1;
+        }
+    }
+    /// 释放`alloc`分配的对象
+    pub unsafe fn dealloc(&self, ptr: *mut u8) {
+        let slab = (ptr as usize & !((PAGE_SIZE << self.order) - 1)) as *mut Slab;
+        let object = ptr as *mut usize;
+        let mut list = self.list.lock();
+        let was_full = (*slab).free.is_null();
+        *object = (*slab).free as usize;
+        (*slab).free = object;
+        (*slab).inuse -= 1;
+        list.active -= 1;
+        if was_full {
+            list.push(slab);
+        }
+        if (*slab).inuse > 0 {
+            return;
+        }
+        if list.empty == 0 {
+            list.empty += 1;
+            return;
+        }
+        // 已经有一个全空的块备用，归还这一个
+        list.unlink(slab);
+        list.slabs -= 1;
+        drop(list);
+        kernel_pages_free((slab as usize >> PAGE_SIZE_BITS).into(), self.order);
+    }
+    pub fn stats(&self) -> SlabStats {
+        let list = self.list.lock();
+        SlabStats {
+            name: self.name,
+            object_size: self.size,
+            active_objects: list.active,
+            total_objects: list.slabs * self.objects,
+            objects_per_slab: self.objects,
+            pages_per_slab: 1 << self.order,
+            slabs: list.slabs,
+            empty_slabs: list.empty,
+        }
+    }
+}
+
+/// 缓存的使用情况
+#[derive(Clone, Copy, Debug)]
+pub struct SlabStats {
+    pub name: &'static str,
+    pub object_size: usize,
+    pub active_objects: usize,
+    pub total_objects: usize,
+    pub objects_per_slab: usize,
+    pub pages_per_slab: usize,
+    pub slabs: usize,
+    pub empty_slabs: usize,
+}
+
+/// 频繁分配的内核对象，都通过`Arc`分配。
+/// 布局相同的其他对象也会落在同一个缓存中
+static KMEM_CACHES: [SlabCache; 4] = [
+    SlabCache::for_arc::<TaskControlBlock>("task_struct"),
+    SlabCache::for_arc::<Mutex<PageCache>>("page_cache"),
+    SlabCache::for_arc::<Mutex<BufferCache>>("buffer_cache"),
+    SlabCache::for_arc::<DirectoryTreeNode>("dentry"),
+];
+
+/// `layout`对应的缓存
+pub(super) fn find_cache(layout: &Layout) -> Option<&'static SlabCache> {
+    KMEM_CACHES.iter().find(|cache| cache.matches(layout))
+}
+
+/// 所有缓存的使用情况
+pub fn slab_stats() -> impl Iterator<Item = SlabStats> {
+    KMEM_CACHES.iter().map(SlabCache::stats)
+}
+
+/// `/proc/slabinfo`：与Linux相同的格式
+pub fn slabinfo() -> String {
+    let mut content = String::from(
+        "slabinfo - version: 2.1\n\
+         # name            <active_objs> <num_objs> <objsize> <objperslab> <pagesperslab> \
+         : tunables <limit> <batchcount> <sharedfactor> : slabdata <active_slabs> <num_slabs> <sharedavail>\n",
+    );
+    for stats in slab_stats() {
+        content.push_str(&format!(
+            "{:<17} {:6} {:6} {:6} {:4} {:4} : tunables    0    0    0 : slabdata {:6} {:6}      0\n",
+            stats.name,
+            stats.active_objects,
+            stats.total_objects,
+            stats.object_size,
+            stats.objects_per_slab,
+            stats.pages_per_slab,
+            stats.slabs - stats.empty_slabs,
+            stats.slabs,
+        ));
+    }
+    content
+}
@@ -0,0 +1,470 @@
+//! 页面回收。
+//! 匿名页与文件页各有活跃、不活跃两个LRU链表，链表节点嵌在页描述符中。
+//! 回收时先按页表项访问位与软件访问位老化活跃链表，把一段时间没有被访问的页移到不活跃链表，
+//! 再从不活跃链表头部取页：文件页解除所有映射后从页缓存中删除，
+//! 匿名页写入zram（已满时写入swap）后通过反向映射把每个映射它的位置换成压缩或换出后的副本。
+//! 空闲页低于`WMARK_LOW`时由`kswapd_tick`在后台回收，分配失败时由`oom_handler`直接回收
+use super::page::{
+    index_to_page, index_to_ppn, page_index, ppn_to_page, put_page, Page, PageFlags,
+};
+#[cfg(feature = "oom_handler")]
+use super::zram::ZRAM_DEVICE;
+#[cfg(feature = "oom_handler")]
+use super::Frame;
+use super::{tlb_invalidate, unallocated_frames, MemorySet, PageTableImpl, PhysPageNum};
+#[cfg(feature = "oom_handler")]
+use crate::fs::swap::SWAP_DEVICE;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+use spin::Mutex;
+
+/// 空闲页少于该值时后台回收开始工作
+pub const WMARK_LOW: usize = 512;
+/// 后台回收的目标空闲页数
+pub const WMARK_HIGH: usize = 1024;
+/// 每次从一个链表中取出的页数
+const SCAN_BATCH: usize = 32;
+/// 没有匿名页的后备存储时，匿名页不进入LRU链表
+const RECLAIM_ANON: bool = cfg!(feature = "oom_handler");
+
+/// 链表的空节点
+const NIL: u32 = u32::MAX;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LruList {
+    InactiveAnon = 0,
+    ActiveAnon = 1,
+    InactiveFile = 2,
+    ActiveFile = 3,
+}
+
+impl LruList {
+    const NUM: usize = 4;
+    fn of(anon: bool, active: bool) -> Self {
+        match (anon, active) {
+            (true, false) => LruList::InactiveAnon,
+            (true, true) => LruList::ActiveAnon,
+            (false, false) => LruList::InactiveFile,
+            (false, true) => LruList::ActiveFile,
+        }
+    }
+    /// 页所在的链表，由`SWAP_BACKED`与`ACTIVE`标志决定
+    fn of_page(page: &Page) -> Self {
+        let flags = page.flags();
+        Self::of(
+            flags.contains(PageFlags::SWAP_BACKED),
+            flags.contains(PageFlags::ACTIVE),
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ListHead {
+    head: u32,
+    tail: u32,
+    len: usize,
+}
+
+/// 以描述符下标为节点的双向链表，头部是最久没有被访问的页
+struct Lru {
+    lists: [ListHead; LruList::NUM],
+}
+
+impl Lru {
+    const fn new() -> Self {
+        Self {
+            lists: [ListHead {
+                head: NIL,
+                tail: NIL,
+                len: 0,
+            }; LruList::NUM],
+        }
+    }
+    fn push_back(&mut self, list: LruList, idx: u32) {
+        let page = index_to_page(idx);
+        let head = &mut self.lists[list as usize];
+        page.lru_prev.store(head.tail, Ordering::Relaxed);
+        page.lru_next.store(NIL, Ordering::Relaxed);
+        if head.tail == NIL {
+            head.head = idx;
+        } else {
+            index_to_page(head.tail)
+                .lru_next
+                .store(idx, Ordering::Relaxed);
+        }
+        head.tail = idx;
+        head.len += 1;
+    }
+    fn remove(&mut self, list: LruList, idx: u32) {
+        let page = index_to_page(idx);
+        let prev = page.lru_prev.load(Ordering::Relaxed);
+        let next = page.lru_next.load(Ordering::Relaxed);
+        let head = &mut self.lists[list as usize];
+        if prev == NIL {
+            head.head = next;
+        } else {
+            index_to_page(prev).lru_next.store(next, Ordering::Relaxed);
+        }
+        if next == NIL {
+            head.tail = prev;
+        } else {
+            index_to_page(next).lru_prev.store(prev, Ordering::Relaxed);
+        }
+        head.len -= 1;
+    }
+    fn front(&self, list: LruList) -> Option<u32> {
+        let head = self.lists[list as usize].head;
+        (head != NIL).then(|| head)
+    }
+    fn len(&self, list: LruList) -> usize {
+        self.lists[list as usize].len
+    }
+}
+
+static LRU: Mutex<Lru> = Mutex::new(Lru::new());
+/// 后台回收正在进行
+static KSWAPD_RUNNING: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// 用户地址空间表，以页表token为键。反向映射通过它找到映射页所在的地址空间
+    static ref MM_TABLE: Mutex<BTreeMap<usize, Weak<Mutex<MemorySet<PageTableImpl>>>>> =
+        Mutex::new(BTreeMap::new());
+}
+
+/// 登记一个用户地址空间，使其中的页可以被回收。`exec`替换地址空间后需要重新登记
+pub fn register_mm(vm: &Arc<Mutex<MemorySet<PageTableImpl>>>) {
+    let token = vm.lock().token();
+    MM_TABLE.lock().insert(token, Arc::downgrade(vm));
+}
+
+/// 地址空间被释放时调用
+pub(super) fn unregister_mm(token: usize) {
+    MM_TABLE.lock().remove(&token);
+}
+
+/// 页表token对应的地址空间，内核地址空间与已经释放的地址空间返回`None`
+fn lookup_mm(token: usize) -> Option<Arc<Mutex<MemorySet<PageTableImpl>>>> {
+    MM_TABLE.lock().get(&token).and_then(|vm| vm.upgrade())
+}
+
+/// 把页加入LRU链表的尾部
+fn lru_add(ppn: PhysPageNum, active: bool) {
+    let page = match ppn_to_page(ppn) {
+        Some(page) => page,
+        None => return,
+    };
+    let mut lru = LRU.lock();
+    if page.test_flags(PageFlags::LRU) {
+        return;
+    }
+    if active {
+        page.set_flags(PageFlags::ACTIVE);
+    } else {
+        page.clear_flags(PageFlags::ACTIVE);
+    }
+    page.set_flags(PageFlags::LRU);
+    lru.push_back(LruList::of_page(page), page_index(page));
+}
+
+/// 新分配的匿名页，标记为匿名页并加入活跃链表
+pub fn lru_add_anon(ppn: PhysPageNum) {
+    super::page_set_flags(ppn, PageFlags::SWAP_BACKED);
+    if RECLAIM_ANON {
+        lru_add(ppn, true);
+    }
+}
+
+/// 刚加入页缓存的文件页放在不活跃链表中，再次被访问时才激活
+pub fn lru_add_file(ppn: PhysPageNum) {
+    lru_add(ppn, false);
+}
+
+/// 页被释放时从LRU链表中删除
+pub(super) fn lru_del(page: &Page) {
+    let mut lru = LRU.lock();
+    // 可能已经被回收扫描取出
+    if page.test_flags(PageFlags::LRU) {
+        lru.remove(LruList::of_page(page), page_index(page));
+        page.clear_flags(PageFlags::LRU | PageFlags::ACTIVE);
+    }
+}
+
+/// 内核访问了页（如`read`命中页缓存）。第一次访问只设置软件访问位，
+/// 已经设置过时把不活跃链表中的页激活
+pub fn mark_page_accessed(ppn: PhysPageNum) {
+    let page = match ppn_to_page(ppn) {
+        Some(page) => page,
+        None => return,
+    };
+    if !page.test_flags(PageFlags::REFERENCED) {
+        page.set_flags(PageFlags::REFERENCED);
+        return;
+    }
+    let mut lru = LRU.lock();
+    if page.test_flags(PageFlags::LRU) && !page.test_flags(PageFlags::ACTIVE) {
+        let idx = page_index(page);
+        lru.remove(LruList::of_page(page), idx);
+        page.set_flags(PageFlags::ACTIVE);
+        page.clear_flags(PageFlags::REFERENCED);
+        lru.push_back(LruList::of_page(page), idx);
+    }
+}
+
+/// 各链表中的页数，按`LruList`排列
+pub fn lru_stats() -> [usize; LruList::NUM] {
+    let lru = LRU.lock();
+    [
+        lru.len(LruList::InactiveAnon),
+        lru.len(LruList::ActiveAnon),
+        lru.len(LruList::InactiveFile),
+        lru.len(LruList::ActiveFile),
+    ]
+}
+
+/// 从链表头部取出至多`nr`个页。取出的页多持有一个引用，处理完后由`putback`放回或直接释放
+fn isolate(list: LruList, nr: usize) -> Vec<u32> {
+    let mut isolated = Vec::with_capacity(nr);
+    let mut lru = LRU.lock();
+    while isolated.len() < nr {
+        let idx = match lru.front(list) {
+            Some(idx) => idx,
+            None => break,
+        };
+        let page = index_to_page(idx);
+        lru.remove(list, idx);
+        page.clear_flags(PageFlags::LRU);
+        // 引用计数已经归零的页正在被释放，交给释放者处理
+        if page.get_unless_zero() {
+            isolated.push(idx);
+        }
+    }
+    isolated
+}
+
+/// 把`isolate`取出的页放回链表尾部，并释放取出时持有的引用
+fn putback(pages: Vec<u32>, active: bool) {
+    {
+        let mut lru = LRU.lock();
+        for &idx in pages.iter() {
+            let page = index_to_page(idx);
+            if active {
+                page.set_flags(PageFlags::ACTIVE);
+            } else {
+                page.clear_flags(PageFlags::ACTIVE);
+            }
+            page.set_flags(PageFlags::LRU);
+            lru.push_back(LruList::of_page(page), idx);
+        }
+    }
+    // 最后一个引用会把页从链表中删除，不能在持有链表锁时释放
+    for idx in pages {
+        put_page(index_to_ppn(idx));
+    }
+}
+
+/// 页最近是否被访问过，同时清除软件访问位与所有映射它的页表项的访问位
+fn page_referenced(page: &Page) -> bool {
+    let mut referenced = page.test_and_clear_referenced();
+    for rmap in page.rmap() {
+        let vm = match lookup_mm(rmap.token) {
+            Some(vm) => vm,
+            None => continue,
+        };
+        // 地址空间正在被使用时视为被访问过
+        let accessed = match vm.try_lock() {
+            Some(mut vm) => vm.test_and_clear_accessed(rmap.vpn),
+            None => true,
+        };
+        referenced |= accessed;
+    }
+    referenced
+}
+
+/// 解除文件页的所有映射，再把它从页缓存中删除。脏页留给回写
+fn reclaim_file(page: &Page, ppn: PhysPageNum) -> bool {
+    if page.test_flags(PageFlags::DIRTY) {
+        return false;
+    }
+    for rmap in page.rmap() {
+        let vm = match lookup_mm(rmap.token) {
+            Some(vm) => vm,
+            None => return false,
+        };
+        let unmapped = match vm.try_lock() {
+            Some(mut vm) => vm.unmap_file_page(rmap.vpn, ppn),
+            None => false,
+        };
+        if !unmapped {
+            return false;
+        }
+    }
+    tlb_invalidate();
+    page.evict()
+}
+
+/// 把匿名页写入zram（已满时写入swap），再把每个映射它的位置换成压缩或换出后的副本。
+/// fork后共享的页只写入一次，所有地址空间共用同一个副本
+#[cfg(feature = "oom_handler")]
+fn reclaim_anon(page: &Page, ppn: PhysPageNum) -> bool {
+    let rmaps = page.rmap();
+    if rmaps.is_empty() {
+        return false;
+    }
+    let vms = match rmaps
+        .iter()
+        .map(|rmap| lookup_mm(rmap.token))
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(vms) => vms,
+        None => return false,
+    };
+    let mut locked = Vec::with_capacity(vms.len());
+    for vm in vms.iter() {
+        match vm.try_lock() {
+            Some(vm) => locked.push(vm),
+            None => return false,
+        }
+    }
+    // 页表之外还有人持有这个页（如ELF缓存、内核区域）时不能回收
+    if !locked
+        .iter()
+        .zip(rmaps.iter())
+        .all(|(vm, rmap)| vm.anon_page_refs(rmap.vpn, ppn) == Some(rmaps.len()))
+    {
+        return false;
+    }
+    let zipped = ZRAM_DEVICE.lock().write(ppn.get_bytes_array());
+    let frame = match zipped {
+        Ok(zram_tracker) => Frame::Compressed(zram_tracker),
+        Err(_) => match SWAP_DEVICE.lock().write(ppn.get_bytes_array()) {
+            Some(swap_tracker) => Frame::SwappedOut(swap_tracker),
+            // 没有启用交换区或交换区已满
+            None => return false,
+        },
+    };
+    for (vm, rmap) in locked.iter_mut().zip(rmaps.iter()) {
+        vm.replace_anon_page(rmap.vpn, frame.clone());
+    }
+    tlb_invalidate();
+    true
+}
+
+#[cfg(not(feature = "oom_handler"))]
+fn reclaim_anon(_page: &Page, _ppn: PhysPageNum) -> bool {
+    false
+}
+
+/// 老化活跃链表：最近被访问过的页留在活跃链表尾部，其余的移到不活跃链表
+fn shrink_active_list(anon: bool, nr: usize) {
+    let (referenced, cold): (Vec<u32>, Vec<u32>) = isolate(LruList::of(anon, true), nr)
+        .into_iter()
+        .partition(|&idx| page_referenced(index_to_page(idx)));
+    putback(referenced, true);
+    putback(cold, false);
+}
+
+/// 回收不活跃链表头部的页，返回释放的页数
+fn shrink_inactive_list(anon: bool, nr: usize) -> usize {
+    let mut activate = Vec::new();
+    let mut keep = Vec::new();
+    let mut reclaimed = 0;
+    for idx in isolate(LruList::of(anon, false), nr) {
+        let page = index_to_page(idx);
+        let ppn = index_to_ppn(idx);
+        if page_referenced(page) {
+            activate.push(idx);
+            continue;
+        }
+        let freed = !page.test_flags(PageFlags::LOCKED)
+            && if anon {
+                reclaim_anon(page, ppn)
+            } else {
+                reclaim_file(page, ppn)
+            };
+        if freed {
+            // 页已经不在任何页表和缓存中，放掉取出时的引用即释放
+            put_page(ppn);
+            reclaimed += 1;
+        } else {
+            keep.push(idx);
+        }
+    }
+    putback(activate, true);
+    putback(keep, false);
+    reclaimed
+}
+
+/// 从LRU链表中回收`nr`个页，返回实际回收的页数。
+/// 文件页重新读入的代价更低，每一轮先回收文件页，不够时再回收匿名页
+fn shrink_lists(nr: usize) -> usize {
+    let total: usize = lru_stats().iter().sum();
+    let mut reclaimed = 0;
+    let mut scanned = 0;
+    // 每个页至多被扫描两遍：第一遍清除访问位，第二遍回收
+    while reclaimed < nr && scanned < 2 * total {
+        for anon in [false, true] {
+            if anon && !RECLAIM_ANON {
+                continue;
+            }
+            let (inactive, active) = {
+                let lru = LRU.lock();
+                (
+                    lru.len(LruList::of(anon, false)),
+                    lru.len(LruList::of(anon, true)),
+                )
+            };
+            if inactive < active {
+                shrink_active_list(anon, SCAN_BATCH);
+            }
+            reclaimed += shrink_inactive_list(anon, SCAN_BATCH);
+            scanned += 2 * SCAN_BATCH;
+            if reclaimed >= nr {
+                break;
+            }
+        }
+    }
+    reclaimed
+}
+
+/// 分配失败时直接回收，返回回收的页数
+pub(super) fn try_to_free_pages(nr: usize) -> usize {
+    let reclaimed = shrink_lists(nr);
+    log::debug!("[vmscan] direct reclaim: {}/{}", reclaimed, nr);
+    reclaimed
+}
+
+/// 后台回收，由时钟中断和空闲的调度循环调用。
+/// 空闲页低于`WMARK_LOW`时回收冷页，直到空闲页回到`WMARK_HIGH`
+pub fn kswapd_tick() {
+    let free = unallocated_frames();
+    if free >= WMARK_LOW {
+        return;
+    }
+    // 已经有人在回收
+    if KSWAPD_RUNNING.swap(true, Ordering::Acquire) {
+        return;
+    }
+    let reclaimed = shrink_lists(WMARK_HIGH - free);
+    KSWAPD_RUNNING.store(false, Ordering::Release);
+    log::debug!("[kswapd] free pages: {}, reclaimed: {}", free, reclaimed);
+}
+
+/// swapoff时把换出到第`swap_type`个交换区的页全部换回内存
+#[cfg(feature = "oom_handler")]
+pub fn swap_in_all(swap_type: usize) -> Result<(), isize> {
+    // 换入时会分配页并可能触发回收，不能持有用户地址空间表的锁
+    let vms: Vec<_> = MM_TABLE
+        .lock()
+        .values()
+        .filter_map(|vm| vm.upgrade())
+        .collect();
+    for vm in vms {
+        vm.lock().swap_in_area(swap_type)?;
+    }
+    // zram写回到这个交换区的页
+    ZRAM_DEVICE.lock().swap_in_area(swap_type);
+    Ok(())
+}
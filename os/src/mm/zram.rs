@@ -1,8 +1,18 @@
-use alloc::{sync::Arc, vec::Vec};
+//! zram：内存中的压缩块设备，匿名页回收时先压缩到这里，放不下或压缩效果差时再换出到交换区。
+//! 设备大小、压缩算法与内存上限可以在运行时通过`/sys/block/zram0`下的属性文件修改，
+//! 整页由同一个字填充（如全零页）时只记录这个字，压缩后仍超过`HUGE_THRESHOLD`的页不存放。
+//! 启用交换区后，标记为空闲且之后没有被读取的页可以写回到交换区，释放压缩数据占用的内存
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use core::{convert::TryInto, mem::size_of};
 use lazy_static::lazy_static;
 use lz4_flex::{compress_prepend_size, decompress_size_prepended};
 use spin::Mutex;
 
+use crate::config::PAGE_SIZE;
+#[cfg(feature = "swap")]
+use crate::fs::swap::{swp_type, SwapTracker, SWAP_DEVICE};
+use crate::syscall::errno::*;
+
 #[derive(Debug)]
 /// Zram错误枚举
 pub enum ZramError {
@@ -12,6 +22,8 @@ pub enum ZramError {
     NoSpace,
     /// 未分配
     NotAllocated,
+    /// 压缩后超过`HUGE_THRESHOLD`，不值得存放
+    Incompressible,
 }
 
 #[derive(Debug)]
@@ -25,118 +37,540 @@ impl Drop for ZramTracker {
     }
 }
 
+/// 默认可以存放的页数
+const DEFAULT_CAPACITY: usize = 2048;
+/// 压缩后超过该大小的页不存放，交给交换区
+const HUGE_THRESHOLD: usize = PAGE_SIZE * 3 / 4;
+
+/// 压缩算法
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CompAlgorithm {
+    Lz4,
+    /// 游程编码，每两个字节表示一段重复的字节（重复次数、字节），适合大段重复的页
+    Rle,
+}
+
+impl CompAlgorithm {
+    const ALL: [Self; 2] = [Self::Lz4, Self::Rle];
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Lz4 => "lz4",
+            Self::Rle => "rle",
+        }
+    }
+    fn compress(&self, src: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Lz4 => compress_prepend_size(src),
+            Self::Rle => {
+                let mut dst = Vec::new();
+                let mut i = 0;
+                while i < src.len() {
+                    let byte = src[i];
+                    let run = src[i..]
+                        .iter()
+                        .take(u8::MAX as usize)
+                        .take_while(|b| **b == byte)
+                        .count();
+                    dst.push(run as u8);
+                    dst.push(byte);
+                    i += run;
+                }
+                dst
+            }
+        }
+    }
+    fn decompress(&self, src: &[u8], dst: &mut [u8]) {
+        match self {
+            Self::Lz4 => dst.copy_from_slice(decompress_size_prepended(src).unwrap().as_slice()),
+            Self::Rle => {
+                let mut pos = 0;
+                for pair in src.chunks_exact(2) {
+                    let run = pair[0] as usize;
+                    dst[pos..pos + run].fill(pair[1]);
+                    pos += run;
+                }
+                assert_eq!(pos, dst.len());
+            }
+        }
+    }
+}
+
+/// zram中一个页槽的内容
+enum ZramSlot {
+    Free,
+    /// 整页由同一个字重复填充，只记录这个字
+    Same(usize),
+    Compressed(Vec<u8>),
+    /// 已写回到交换区
+    #[cfg(feature = "swap")]
+    WrittenBack(Arc<SwapTracker>),
+}
+
+struct ZramEntry {
+    slot: ZramSlot,
+    /// 被标记为空闲后没有被读取过，可以写回
+    idle: bool,
+}
+
+/// zram统计，与Linux的`mm_stat`、`bd_stat`对应
+#[derive(Clone, Copy, Default)]
+pub struct ZramStats {
+    /// 存放的页数，包括同值页与已写回的页
+    pub pages_stored: usize,
+    /// 压缩数据的总字节数
+    pub compr_data_size: usize,
+    /// 占用内存的峰值
+    pub mem_used_max: usize,
+    /// 同值页数
+    pub same_pages: usize,
+    /// 因压缩效果差而没有存放的页数
+    pub huge_pages_since: usize,
+    /// 已写回到交换区的页数
+    pub bd_count: usize,
+    /// 从交换区读回的页数
+    pub bd_reads: usize,
+    /// 写回到交换区的页数
+    pub bd_writes: usize,
+}
+
 /// Zram结构
 pub struct Zram {
-    /// 压缩数据存储
-    compressed: Vec<Option<Vec<u8>>>,
+    /// 页槽，按需增长
+    entries: Vec<ZramEntry>,
     /// 回收的索引
-    recycled: Vec<u16>,
-    /// 当前分配的位置
-    tail: u16,
+    recycled: Vec<usize>,
+    /// 可以存放的页数，由disksize决定
+    capacity: usize,
+    /// 占用内存的上限，0表示不限制
+    mem_limit: usize,
+    /// 当前使用的压缩算法，只能在zram为空时更换
+    algorithm: CompAlgorithm,
+    stats: ZramStats,
+}
+
+/// 整页由同一个字重复填充时返回这个字
+fn same_filled(buf: &[u8]) -> Option<usize> {
+    let mut words = buf
+        .chunks_exact(size_of::<usize>())
+        .map(|word| usize::from_ne_bytes(word.try_into().unwrap()));
+    let first = words.next()?;
+    words.all(|word| word == first).then(|| first)
 }
 
 impl Zram {
-    /// 构造方法
+    /// 构造方法，`capacity`为可以存放的页数
     pub fn new(capacity: usize) -> Self {
-        // 预分配制定容量的向量
-        let mut compressed = Vec::with_capacity(capacity);
-        // 初始化为全None状态
-        compressed.resize(compressed.capacity(), None);
         Self {
-            compressed,
+            entries: Vec::new(),
             // 回收列表为空
             recycled: Vec::new(),
-            // tail 从0开始
-            tail: 0,
+            capacity,
+            mem_limit: 0,
+            algorithm: CompAlgorithm::Lz4,
+            stats: ZramStats::default(),
         }
     }
+    /// 压缩数据与页槽表占用的内存
+    pub fn mem_used(&self) -> usize {
+        self.stats.compr_data_size + self.entries.capacity() * size_of::<ZramEntry>()
+    }
+    pub fn stats(&self) -> ZramStats {
+        self.stats
+    }
     /// 数据插入
-    fn insert(&mut self, data: Vec<u8>) -> Result<Arc<ZramTracker>, ZramError> {
+    fn insert(&mut self, slot: ZramSlot) -> Arc<ZramTracker> {
         // 优先使用回收的索引
         let zram_id = match self.recycled.pop() {
-            Some(zram_id) => zram_id as usize,
+            Some(zram_id) => {
+                self.entries[zram_id].slot = slot;
+                zram_id
+            }
             None => {
-                if self.tail as usize == self.compressed.len() {
-                    // 空间不足，返回错误
-                    return Err(ZramError::NoSpace);
-                } else {
-                    // 更新tail
-                    self.tail += 1;
-                    (self.tail - 1) as usize
-                }
+                self.entries.push(ZramEntry { slot, idle: false });
+                self.entries.len() - 1
             }
         };
-        // 存储数据
-        self.compressed[zram_id] = Some(data);
+        self.entries[zram_id].idle = false;
+        self.stats.pages_stored += 1;
+        self.stats.mem_used_max = self.stats.mem_used_max.max(self.mem_used());
         // 返回跟踪器
-        Ok(Arc::new(ZramTracker(zram_id)))
+        Arc::new(ZramTracker(zram_id))
     }
-    /// 获取数据
-    fn get(&self, zram_id: usize) -> Result<&Vec<u8>, ZramError> {
+    /// 获取页槽
+    fn get(&mut self, zram_id: usize) -> Result<&mut ZramEntry, ZramError> {
         // 如果zram_id大于容器大小
-        if zram_id >= self.compressed.len() {
-            return Err(ZramError::InvalidIndex);
-        }
-        match &self.compressed[zram_id] {
-            Some(compressed_data) => Ok(compressed_data),
-            None => Err(ZramError::NotAllocated),
+        let entry = self
+            .entries
+            .get_mut(zram_id)
+            .ok_or(ZramError::InvalidIndex)?;
+        match entry.slot {
+            ZramSlot::Free => Err(ZramError::NotAllocated),
+            _ => Ok(entry),
         }
     }
     /// 移除数据
-    fn remove(&mut self, zram_id: usize) -> Result<Vec<u8>, ZramError> {
-        // 如果索引大于容器大小
-        if zram_id >= self.compressed.len() {
-            return Err(ZramError::InvalidIndex);
-        }
-        // 刚好等于最后一个分配的id
-        if zram_id == (self.tail - 1) as usize {
-            // 回退tail
-            self.tail = zram_id as u16;
-        } else {
-            // 加入回收列表
-            self.recycled.push(zram_id as u16);
-        }
-        match self.compressed[zram_id].take() {
-            Some(compressed_data) => Ok(compressed_data),
-            None => Err(ZramError::NotAllocated),
+    fn remove(&mut self, zram_id: usize) -> Result<(), ZramError> {
+        let entry = self.get(zram_id)?;
+        let slot = core::mem::replace(&mut entry.slot, ZramSlot::Free);
+        match &slot {
+            ZramSlot::Free => unreachable!(),
+            ZramSlot::Same(_) => self.stats.same_pages -= 1,
+            ZramSlot::Compressed(data) => self.stats.compr_data_size -= data.len(),
+            #[cfg(feature = "swap")]
+            ZramSlot::WrittenBack(_) => self.stats.bd_count -= 1,
         }
+        self.stats.pages_stored -= 1;
+        // 加入回收列表
+        self.recycled.push(zram_id);
+        Ok(())
     }
     /// 读接口
     pub fn read(&mut self, zram_id: usize, buf: &mut [u8]) -> Result<(), ZramError> {
-        // 获取压缩数据
-        match self.get(zram_id) {
-            Ok(compressed_data) => {
-                // 解压数据
-                let decompressed_data =
-                    decompress_size_prepended(compressed_data.as_slice()).unwrap();
-                // 复制到输出缓冲区
-                buf.copy_from_slice(decompressed_data.as_slice());
-                Ok(())
+        let algorithm = self.algorithm;
+        let entry = self.get(zram_id)?;
+        entry.idle = false;
+        let from_backing = match &entry.slot {
+            ZramSlot::Free => unreachable!(),
+            ZramSlot::Same(word) => {
+                for chunk in buf.chunks_exact_mut(size_of::<usize>()) {
+                    chunk.copy_from_slice(&word.to_ne_bytes());
+                }
+                false
+            }
+            // 解压数据到输出缓冲区
+            ZramSlot::Compressed(data) => {
+                algorithm.decompress(data, buf);
+                false
             }
-            Err(error) => Err(error),
+            #[cfg(feature = "swap")]
+            ZramSlot::WrittenBack(swap_tracker) => {
+                SWAP_DEVICE.lock().read(swap_tracker.0, buf);
+                true
+            }
+        };
+        if from_backing {
+            self.stats.bd_reads += 1;
         }
+        Ok(())
     }
     /// 写接口
     pub fn write(&mut self, buf: &[u8]) -> Result<Arc<ZramTracker>, ZramError> {
+        if self.stats.pages_stored >= self.capacity {
+            // 空间不足，返回错误
+            return Err(ZramError::NoSpace);
+        }
+        if let Some(word) = same_filled(buf) {
+            self.stats.same_pages += 1;
+            return Ok(self.insert(ZramSlot::Same(word)));
+        }
         // 压缩输入数据
-        let mut compressed = compress_prepend_size(buf);
+        let mut compressed = self.algorithm.compress(buf);
+        log::trace!("[zram] compressed len: {}", compressed.len());
+        if compressed.len() > HUGE_THRESHOLD {
+            self.stats.huge_pages_since += 1;
+            return Err(ZramError::Incompressible);
+        }
+        if self.mem_limit != 0 && self.mem_used() + compressed.len() > self.mem_limit {
+            return Err(ZramError::NoSpace);
+        }
         // 释放多余容量
         compressed.shrink_to_fit();
-        log::trace!("[zram] compressed len: {}", compressed.len());
+        self.stats.compr_data_size += compressed.len();
         // 插入数据并返回跟踪器
-        self.insert(compressed)
+        Ok(self.insert(ZramSlot::Compressed(compressed)))
     }
     #[inline(always)]
     /// 释放
     pub fn discard(&mut self, zram_id: usize) -> Result<(), ZramError> {
-        match self.remove(zram_id) {
-            Ok(_) => Ok(()),
-            Err(error) => Err(error),
+        self.remove(zram_id)
+    }
+    /// 修改可以存放的页数，不能小于已经存放的页数
+    pub fn set_capacity(&mut self, capacity: usize) -> Result<(), isize> {
+        if capacity < self.stats.pages_stored {
+            return Err(EBUSY);
+        }
+        self.capacity = capacity;
+        Ok(())
+    }
+    /// 更换压缩算法，已经存放的页要用原来的算法解压，zram必须为空
+    pub fn set_algorithm(&mut self, algorithm: CompAlgorithm) -> Result<(), isize> {
+        if self.stats.pages_stored != 0 {
+            return Err(EBUSY);
+        }
+        self.algorithm = algorithm;
+        Ok(())
+    }
+    /// 把所有存放的页标记为空闲，之后被读取的页会清除标记
+    pub fn mark_idle(&mut self) {
+        for entry in self.entries.iter_mut() {
+            entry.idle = !matches!(entry.slot, ZramSlot::Free);
+        }
+    }
+    /// 把空闲的压缩页写回交换区，返回写回的页数
+    #[cfg(feature = "swap")]
+    pub fn writeback(&mut self) -> Result<usize, isize> {
+        let mut page = alloc::vec![0u8; PAGE_SIZE];
+        let mut written = 0;
+        for entry in self.entries.iter_mut() {
+            let len = match &entry.slot {
+                ZramSlot::Compressed(data) if entry.idle => {
+                    self.algorithm.decompress(data, &mut page);
+                    data.len()
+                }
+                _ => continue,
+            };
+            let swap_tracker = match SWAP_DEVICE.lock().write(&page) {
+                Some(swap_tracker) => swap_tracker,
+                // 没有启用交换区或交换区已满
+                None if written == 0 => return Err(ENOSPC),
+                None => break,
+            };
+            entry.slot = ZramSlot::WrittenBack(swap_tracker);
+            entry.idle = false;
+            self.stats.compr_data_size -= len;
+            self.stats.bd_count += 1;
+            self.stats.bd_writes += 1;
+            written += 1;
+        }
+        log::debug!("[zram] written back {} pages", written);
+        Ok(written)
+    }
+    /// swapoff：把写回到第`swap_type`个交换区的页读回来重新压缩
+    #[cfg(feature = "swap")]
+    pub fn swap_in_area(&mut self, swap_type: usize) {
+        let mut page = alloc::vec![0u8; PAGE_SIZE];
+        for entry in self.entries.iter_mut() {
+            match &entry.slot {
+                ZramSlot::WrittenBack(swap_tracker) if swp_type(swap_tracker.0) == swap_type => {
+                    SWAP_DEVICE.lock().read(swap_tracker.0, &mut page);
+                }
+                _ => continue,
+            }
+            let mut compressed = self.algorithm.compress(&page);
+            compressed.shrink_to_fit();
+            self.stats.compr_data_size += compressed.len();
+            self.stats.bd_count -= 1;
+            self.stats.bd_reads += 1;
+            // 释放交换区中的页槽
+            entry.slot = ZramSlot::Compressed(compressed);
         }
     }
 }
 
 lazy_static! {
     /// 全局ZRAM设备
-    pub static ref ZRAM_DEVICE: Arc<Mutex<Zram>> = Arc::new(Mutex::new(Zram::new(2048)));
+    pub static ref ZRAM_DEVICE: Arc<Mutex<Zram>> = Arc::new(Mutex::new(Zram::new(DEFAULT_CAPACITY)));
+}
+
+/// 解析带有K、M、G后缀的大小，与Linux的`memparse`相同
+fn memparse(s: &str) -> Result<usize, isize> {
+    let (digits, shift) = match s.as_bytes().last() {
+        Some(b'k' | b'K') => (&s[..s.len() - 1], 10),
+        Some(b'm' | b'M') => (&s[..s.len() - 1], 20),
+        Some(b'g' | b'G') => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    let size: usize = digits.parse().map_err(|_| EINVAL)?;
+    size.checked_mul(1 << shift).ok_or(EINVAL)
+}
+
+fn disksize_show() -> String {
+    format!("{}\n", ZRAM_DEVICE.lock().capacity * PAGE_SIZE)
+}
+
+fn disksize_store(s: &str) -> Result<(), isize> {
+    let size = memparse(s)?;
+    ZRAM_DEVICE.lock().set_capacity(size / PAGE_SIZE)
+}
+
+fn comp_algorithm_show() -> String {
+    let current = ZRAM_DEVICE.lock().algorithm;
+    let mut s = String::new();
+    for algorithm in CompAlgorithm::ALL {
+        if !s.is_empty() {
+            s.push(' ');
+        }
+        if algorithm == current {
+            s.push_str(&format!("[{}]", algorithm.name()));
+        } else {
+            s.push_str(algorithm.name());
+        }
+    }
+    s.push('\n');
+    s
+}
+
+fn comp_algorithm_store(s: &str) -> Result<(), isize> {
+    let algorithm = CompAlgorithm::ALL
+        .iter()
+        .copied()
+        .find(|algorithm| algorithm.name() == s)
+        .ok_or(EINVAL)?;
+    ZRAM_DEVICE.lock().set_algorithm(algorithm)
+}
+
+fn mem_limit_show() -> String {
+    format!("{}\n", ZRAM_DEVICE.lock().mem_limit)
+}
+
+fn mem_limit_store(s: &str) -> Result<(), isize> {
+    ZRAM_DEVICE.lock().mem_limit = memparse(s)?;
+    Ok(())
+}
+
+/// 与Linux相同的字段：原始数据大小 压缩数据大小 占用内存 内存上限 占用内存峰值 同值页数
+/// 整理过的页数 不可压缩页数 累计不可压缩页数，大小以字节为单位。不存放不可压缩页，也没有整理
+fn mm_stat_show() -> String {
+    let zram = ZRAM_DEVICE.lock();
+    let stats = zram.stats();
+    format!(
+        "{:8} {:8} {:8} {:8} {:8} {:8} {:8} {:8} {:8}\n",
+        stats.pages_stored * PAGE_SIZE,
+        stats.compr_data_size,
+        zram.mem_used(),
+        zram.mem_limit,
+        stats.mem_used_max,
+        stats.same_pages,
+        0,
+        0,
+        stats.huge_pages_since,
+    )
+}
+
+/// 写入`all`把所有页标记为空闲，配合`writeback`使用
+fn idle_store(s: &str) -> Result<(), isize> {
+    match s {
+        "all" => {
+            ZRAM_DEVICE.lock().mark_idle();
+            Ok(())
+        }
+        _ => Err(EINVAL),
+    }
+}
+
+/// 已写回的页数 读回的页数 写回的页数
+#[cfg(feature = "swap")]
+fn bd_stat_show() -> String {
+    let stats = ZRAM_DEVICE.lock().stats();
+    format!(
+        "{:8} {:8} {:8}\n",
+        stats.bd_count, stats.bd_reads, stats.bd_writes
+    )
+}
+
+/// 写入`idle`把空闲页写回交换区
+#[cfg(feature = "swap")]
+fn writeback_store(s: &str) -> Result<(), isize> {
+    match s {
+        "idle" => ZRAM_DEVICE.lock().writeback().map(|_| ()),
+        _ => Err(EINVAL),
+    }
+}
+
+/// 只写的属性文件读出为空
+fn empty_show() -> String {
+    String::new()
+}
+
+/// `/sys/block/zram0`下的属性文件：文件名、读取时生成内容的函数、写入时调用的函数
+pub type ZramAttr = (
+    &'static str,
+    fn() -> String,
+    Option<fn(&str) -> Result<(), isize>>,
+);
+
+pub fn zram_attrs() -> Vec<ZramAttr> {
+    let mut attrs: Vec<ZramAttr> = Vec::new();
+    attrs.push(("disksize", disksize_show, Some(disksize_store)));
+    attrs.push((
+        "comp_algorithm",
+        comp_algorithm_show,
+        Some(comp_algorithm_store),
+    ));
+    attrs.push(("mem_limit", mem_limit_show, Some(mem_limit_store)));
+    attrs.push(("mm_stat", mm_stat_show, None));
+    attrs.push(("idle", empty_show, Some(idle_store)));
+    #[cfg(feature = "swap")]
+    {
+        attrs.push(("bd_stat", bd_stat_show, None));
+        attrs.push(("writeback", empty_show, Some(writeback_store)));
+    }
+    attrs
+}
+
+#[allow(unused)]
+/// zram测试函数，需要在启动时zram为空的情况下调用。
+/// 检查同值页、不可压缩页、空闲页写回以及`mm_stat`、`bd_stat`中的统计
+pub fn zram_test() {
+    assert_eq!(ZRAM_DEVICE.lock().stats().pages_stored, 0);
+    let mut page = alloc::vec![0u8; PAGE_SIZE];
+    // 同值页只记录重复的字，不产生压缩数据
+    page.fill(0x5a);
+    let same = ZRAM_DEVICE.lock().write(&page).unwrap();
+    let stats = ZRAM_DEVICE.lock().stats();
+    assert_eq!((stats.pages_stored, stats.same_pages), (1, 1));
+    assert_eq!(stats.compr_data_size, 0);
+    // 可以压缩的页
+    for (i, byte) in page.iter_mut().enumerate() {
+        *byte = (i / 64) as u8;
+    }
+    let is_pattern = |page: &[u8]| page.iter().enumerate().all(|(i, b)| *b == (i / 64) as u8);
+    let compressed = ZRAM_DEVICE.lock().write(&page).unwrap();
+    let stats = ZRAM_DEVICE.lock().stats();
+    assert_eq!(stats.pages_stored, 2);
+    assert!(stats.compr_data_size > 0 && stats.compr_data_size <= HUGE_THRESHOLD);
+    assert!(stats.mem_used_max >= ZRAM_DEVICE.lock().mem_used());
+    // 伪随机数据压缩后超过HUGE_THRESHOLD，不存放
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    for byte in page.iter_mut() {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        *byte = seed as u8;
+    }
+    assert!(matches!(
+        ZRAM_DEVICE.lock().write(&page),
+        Err(ZramError::Incompressible)
+    ));
+    let stats = ZRAM_DEVICE.lock().stats();
+    assert_eq!((stats.pages_stored, stats.huge_pages_since), (2, 1));
+    // 读出的内容与写入时相同
+    ZRAM_DEVICE.lock().read(same.0, &mut page).unwrap();
+    assert!(page.iter().all(|byte| *byte == 0x5a));
+    ZRAM_DEVICE.lock().read(compressed.0, &mut page).unwrap();
+    assert!(is_pattern(&page));
+    // 标记空闲后，只有压缩页会被写回；没有启用交换区时写回失败，统计不变
+    #[cfg(feature = "swap")]
+    {
+        ZRAM_DEVICE.lock().mark_idle();
+        let swap_enabled = SWAP_DEVICE.lock().stats().0 > 0;
+        let result = ZRAM_DEVICE.lock().writeback();
+        let stats = ZRAM_DEVICE.lock().stats();
+        if swap_enabled {
+            assert_eq!(result, Ok(1));
+            assert_eq!((stats.bd_count, stats.bd_writes), (1, 1));
+            assert_eq!((stats.pages_stored, stats.compr_data_size), (2, 0));
+            page.fill(0);
+            ZRAM_DEVICE.lock().read(compressed.0, &mut page).unwrap();
+            assert!(is_pattern(&page));
+            assert_eq!(ZRAM_DEVICE.lock().stats().bd_reads, 1);
+        } else {
+            assert_eq!(result, Err(ENOSPC));
+            assert_eq!((stats.bd_count, stats.bd_writes), (0, 0));
+        }
+        // 读取清除空闲标记，之后不再写回
+        ZRAM_DEVICE.lock().mark_idle();
+        ZRAM_DEVICE.lock().read(compressed.0, &mut page).unwrap();
+        let bd_writes = ZRAM_DEVICE.lock().stats().bd_writes;
+        assert_eq!(ZRAM_DEVICE.lock().writeback(), Ok(0));
+        assert_eq!(ZRAM_DEVICE.lock().stats().bd_writes, bd_writes);
+    }
+    // 释放跟踪器后页槽被回收
+    drop(same);
+    drop(compressed);
+    let stats = ZRAM_DEVICE.lock().stats();
+    assert_eq!((stats.pages_stored, stats.same_pages), (0, 0));
+    assert_eq!((stats.compr_data_size, stats.bd_count), (0, 0));
+    assert_eq!(mm_stat_show().split_whitespace().count(), 9);
+    println!("zram_test passed!");
 }
@@ -1,5 +1,7 @@
+use crate::fs::poll::{PollEvent, PollWaitQueue, PollWaiter};
 use crate::timer::current_time_duration;
-use alloc::vec;
+use alloc::{sync::Arc, vec};
+use lazy_static::lazy_static;
 use smoltcp::{
     iface::{Config, Interface, SocketHandle, SocketSet},
     phy::{Device, Loopback, Medium},
@@ -12,6 +14,11 @@ use spin::Mutex;
 
 pub static NET_INTERFACE: NetInterface = NetInterface::new();
 
+lazy_static! {
+    /// 所有套接字共享的等待队列，协议栈处理后套接字状态可能变化时被通知
+    pub static ref NET_WAIT_QUEUE: Arc<PollWaitQueue> = Arc::new(PollWaitQueue::new());
+}
+
 pub fn init() {
     NET_INTERFACE.init();
 }
@@ -117,13 +124,32 @@ impl<'a> NetInterface<'a> {
     }
     pub fn _poll(&self) {
         log::debug!("[NetInterface::poll] poll...");
-        self.inner_handler(|inner| {
+        let changed = self.inner_handler(|inner| {
             inner.iface.poll(
                 Instant::from_millis(current_time_duration().as_millis() as i64),
                 &mut inner.device,
                 &mut inner.sockets,
-            );
+            )
         });
+        if changed {
+            NET_WAIT_QUEUE.notify(PollEvent::POLLIN | PollEvent::POLLOUT);
+        }
+    }
+    /// 睡眠直到协议栈状态发生变化，由套接字的阻塞操作调用
+    pub fn wait(&self) {
+        let mut waiter = PollWaiter::new();
+        waiter.register_queue(
+            Some(NET_WAIT_QUEUE.clone()),
+            PollEvent::POLLIN | PollEvent::POLLOUT,
+        );
+        waiter.sleep(None);
+    }
+    /// 时钟中断中调用：有任务在等待套接字时推进协议栈（如重传、延迟确认）
+    pub fn poll_tick(&self) {
+        if NET_WAIT_QUEUE.is_empty() || self.inner.lock().is_none() {
+            return;
+        }
+        self._poll();
     }
     pub fn remove(&self, handler: SocketHandle) {
         self._remove(handler)
@@ -2,7 +2,7 @@ use super::{Mutex, Socket};
 use crate::{
     fs::{file_trait::File, FileDescriptor, OpenFlags}, net::{
         address,
-        config::NET_INTERFACE,
+        config::{NET_INTERFACE, NET_WAIT_QUEUE},
         MAX_BUFFER_SIZE, SHUT_WR,
     }, task::current_task, utils::{
         error::{GeneralRet, SyscallErr, SyscallRet},
@@ -28,6 +28,7 @@ use alloc::string::String;
 use crate::fs::dirent::Dirent;
 use crate::fs::SeekWhence;
 use crate::fs::fat32::PageCache;
+use crate::fs::poll::{PollEvent, PollWaitQueue};
 
 
 
@@ -137,7 +138,7 @@ impl Socket for TcpSocket {
                     info!("[Tcp::connect] {} not connect yet, state {:?}", self.socket_handler, state);
                 }
             }
-            suspend_current_and_run_next();
+            NET_INTERFACE.wait();
             // thread::sleep(Duration::from_secs(1));
         }
     }
@@ -270,7 +271,7 @@ impl TcpSocket {
             match ret {
                 Ok(endpoint) => return GeneralRet::Ok(endpoint),
                 Err(SyscallErr::EAGAIN) => {
-                    suspend_current_and_run_next();
+                    NET_INTERFACE.wait();
                     // 如果返回 EAGAIN 错误，继续循环
                     continue;
                 }
@@ -362,12 +363,12 @@ impl File for TcpSocket {
     fn is_dir(&self) -> bool {todo!();}
     fn is_file(&self) -> bool {todo!();}
     fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>){todo!();}
-    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>>{todo!();}
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>>{None}
     /// open
     fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File>{todo!();}
     fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize>{todo!();}
     /// create
-    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize>{todo!();}
+    fn create(&self, _name: &str, _file_type: DiskInodeType, _mode: u32) -> Result<Arc<dyn File>, isize>{todo!();}
     fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize>{todo!();}
     /// delete(unlink)
     fn unlink(&self, _delete: bool) -> Result<(), isize>{todo!();}
@@ -388,6 +389,34 @@ impl File for TcpSocket {
     fn oom(&self) -> usize{todo!();}
     /// poll, select related
     fn hang_up(&self) -> bool{todo!();}
+    fn poll(&self, events: PollEvent) -> PollEvent {
+        let listening = self.inner.lock().last_state == tcp::State::Listen;
+        let revents = NET_INTERFACE.tcp_socket(self.socket_handler, |socket| {
+            let mut revents = PollEvent::empty();
+            match socket.state() {
+                // 监听套接字收到连接时可以accept
+                tcp::State::SynReceived | tcp::State::Established if listening => {
+                    revents |= PollEvent::POLLIN;
+                }
+                tcp::State::Closed => revents |= PollEvent::POLLHUP,
+                _ => {}
+            }
+            // 对端关闭后读操作会立即返回0
+            if socket.can_recv()
+                || (!listening && socket.state() != tcp::State::Closed && !socket.may_recv())
+            {
+                revents |= PollEvent::POLLIN;
+            }
+            if socket.can_send() {
+                revents |= PollEvent::POLLOUT;
+            }
+            revents
+        });
+        revents & (events | PollEvent::POLLHUP | PollEvent::POLLERR)
+    }
+    fn poll_queue(&self) -> Option<Arc<PollWaitQueue>> {
+        Some(NET_WAIT_QUEUE.clone())
+    }
     /// iotcl
     fn ioctl(&self, _cmd: u32, _argp: usize) -> isize {todo!();}
     /// fcntl
@@ -437,7 +466,7 @@ impl TcpSocket {
             match ret {
                 Ok(result) => return GeneralRet::Ok(result),
                 Err(SyscallErr::EAGAIN) => {
-                    suspend_current_and_run_next();
+                    NET_INTERFACE.wait();
                     // 如果返回 EAGAIN 错误，继续循环
                     continue;
                 }
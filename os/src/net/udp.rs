@@ -1,4 +1,8 @@
-use super::{address::SocketAddrv4, config::NET_INTERFACE, Mutex, Socket, MAX_BUFFER_SIZE};
+use super::{
+    address::SocketAddrv4,
+    config::{NET_INTERFACE, NET_WAIT_QUEUE},
+    Mutex, Socket, MAX_BUFFER_SIZE,
+};
 use crate::{
     fs::{file_trait::File, OpenFlags},
     net::address,
@@ -27,6 +31,7 @@ use alloc::string::String;
 use crate::fs::Dirent;
 use crate::fs::SeekWhence;
 use crate::fs::fat32::PageCache;
+use crate::fs::poll::{PollEvent, PollWaitQueue};
 
 pub struct UdpSocket {
     inner: Mutex<UdpSocketInner>,
@@ -256,12 +261,12 @@ impl File for UdpSocket {
     fn is_dir(&self) -> bool {todo!();}
     fn is_file(&self) -> bool {todo!();}
     fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>){todo!();}
-    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>>{todo!();}
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>>{None}
     /// open
     fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File>{todo!();}
     fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize>{todo!();}
     /// create
-    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize>{todo!();}
+    fn create(&self, _name: &str, _file_type: DiskInodeType, _mode: u32) -> Result<Arc<dyn File>, isize>{todo!();}
     fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize>{todo!();}
     /// delete(unlink)
     fn unlink(&self, _delete: bool) -> Result<(), isize>{todo!();}
@@ -282,6 +287,22 @@ impl File for UdpSocket {
     fn oom(&self) -> usize{todo!();}
     /// poll, select related
     fn hang_up(&self) -> bool{todo!();}
+    fn poll(&self, events: PollEvent) -> PollEvent {
+        let revents = NET_INTERFACE.udp_socket(self.socket_handler, |socket| {
+            let mut revents = PollEvent::empty();
+            if socket.can_recv() {
+                revents |= PollEvent::POLLIN;
+            }
+            if socket.can_send() {
+                revents |= PollEvent::POLLOUT;
+            }
+            revents
+        });
+        revents & events
+    }
+    fn poll_queue(&self) -> Option<Arc<PollWaitQueue>> {
+        Some(NET_WAIT_QUEUE.clone())
+    }
     /// iotcl
     fn ioctl(&self, _cmd: u32, _argp: usize) -> isize {todo!();}
     /// fcntl
@@ -318,7 +339,7 @@ impl UdpSocket {
             match ret {
                 Ok(result) => return GeneralRet::Ok(result),
                 Err(SyscallErr::EAGAIN) => {
-                    suspend_current_and_run_next();
+                    NET_INTERFACE.wait();
                     // 如果返回 EAGAIN 错误，继续循环
                     continue;
                 }
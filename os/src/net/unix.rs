@@ -120,12 +120,12 @@ impl<const N: usize> File for UnixSocket<N> {
     fn is_dir(&self) -> bool {todo!();}
     fn is_file(&self) -> bool {todo!();}
     fn info_dirtree_node(&self, _dirnode_ptr: Weak<DirectoryTreeNode>){todo!();}
-    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>>{todo!();}
+    fn get_dirtree_node(&self) -> Option<Arc<DirectoryTreeNode>>{None}
     /// open
     fn open(&self, _flags: OpenFlags, _special_use: bool) -> Arc<dyn File>{todo!();}
     fn open_subfile(&self) -> Result<Vec<(String, Arc<dyn File>)>, isize>{todo!();}
     /// create
-    fn create(&self, _name: &str, _file_type: DiskInodeType) -> Result<Arc<dyn File>, isize>{todo!();}
+    fn create(&self, _name: &str, _file_type: DiskInodeType, _mode: u32) -> Result<Arc<dyn File>, isize>{todo!();}
     fn link_child(&self, _name: &str, _child: &Self) -> Result<(), isize>{todo!();}
     /// delete(unlink)
     fn unlink(&self, _delete: bool) -> Result<(), isize>{todo!();}
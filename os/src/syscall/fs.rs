@@ -1,4 +1,10 @@
+use crate::drivers::BLOCK_DEVICE;
+use crate::fs::epoll::{EpollEvent, EpollInstance, EPOLL_CLOEXEC, EPOLL_CTL_DEL};
+use crate::fs::locks::{fcntl_getlk, fcntl_setlk, flock, locks_remove_posix, Flock, LockOwner};
 use crate::fs::poll::{ppoll, pselect, FdSet, PollFd};
+#[cfg(feature = "swap")]
+use crate::fs::swap::{SwapArea, SwapSource};
+use crate::fs::timerfd::ITimerSpec;
 use crate::fs::*;
 use crate::hal::BLOCK_SZ;
 use crate::mm::{
@@ -6,7 +12,10 @@ use crate::mm::{
     translated_byte_buffer, translated_byte_buffer_append_to_existing_vec, translated_refmut,
     translated_str, try_get_from_user, MapPermission, UserBuffer, VirtAddr,
 };
-use crate::task::{current_task, current_user_token};
+use crate::task::{
+    capable, current_task, current_user_token, sigprocmask, Capabilities, SigMaskHow,
+    TaskControlBlock,
+};
 use crate::timer::TimeSpec;
 use alloc::boxed::Box;
 use alloc::string::String;
@@ -17,6 +26,7 @@ use log::{debug, info, trace, warn};
 use num_enum::FromPrimitive;
 
 use super::errno::*;
+use super::process::optional_id;
 
 pub const AT_FDCWD: usize = 100usize.wrapping_neg();
 
@@ -151,7 +161,16 @@ fn __openat(dirfd: usize, path: &str) -> Result<FileDescriptor, isize> {
             }
         }
     };
-    file_descriptor.open(path, OpenFlags::O_RDONLY, false)
+    file_descriptor.open(path, OpenFlags::O_PATH, false)
+}
+
+/// 找到`dirfd`与`path`对应的目录树节点，不在目录树中的文件（管道等）返回`EPERM`
+fn __lookup_at(
+    dirfd: usize,
+    path: &str,
+) -> Result<alloc::sync::Arc<directory_tree::DirectoryTreeNode>, isize> {
+    let file_descriptor = __openat(dirfd, path)?;
+    file_descriptor.file.get_dirtree_node().ok_or(EPERM)
 }
 
 pub fn sys_getcwd(buf: usize, size: usize) -> isize {
@@ -495,7 +514,12 @@ pub fn sys_close(fd: usize) -> isize {
     let task = current_task().unwrap();
     let mut fd_table = task.files.lock();
     match fd_table.remove(fd) {
-        Ok(_) => SUCCESS,
+        Ok(file_descriptor) => {
+            drop(fd_table);
+            // 关闭任意一个指向该文件的描述符都会释放进程在其上的记录锁
+            locks_remove_posix(&file_descriptor.file, task.tgid);
+            SUCCESS
+        }
         Err(errno) => errno,
     }
 }
@@ -760,7 +784,7 @@ pub fn sys_fstatat(dirfd: usize, path: *const u8, buf: *mut u8, flags: u32) -> i
         }
     };
 
-    match file_descriptor.open(&path, OpenFlags::O_RDONLY, false) {
+    match file_descriptor.open(&path, OpenFlags::O_PATH, false) {
         Ok(file_descriptor) => {
             if copy_to_user(token, &file_descriptor.get_stat(), buf as *mut Stat).is_err() {
                 log::error!("[sys_fstatat] Failed to copy to {:?}", buf);
@@ -803,7 +827,7 @@ pub fn sys_statx(dirfd: usize, path: *const u8, flags: u32, mask: u32, buf: *mut
         }
     };
 
-    match file_descriptor.open(&path, OpenFlags::O_RDONLY, false) {
+    match file_descriptor.open(&path, OpenFlags::O_PATH, false) {
         Ok(file_descriptor) => {
             if copy_to_user(token, &file_descriptor.get_statx(mask), buf as *mut Statx).is_err() {
                 log::error!("[sys_statx] Failed to copy to {:?}", buf);
@@ -891,21 +915,148 @@ pub fn sys_statfs(_path: *const u8, buf: *mut Statfs) -> isize {
     SUCCESS
 }
 
-pub fn sys_fsync(fd: usize) -> isize {
+fn do_fsync(fd: usize, datasync: bool) -> isize {
     let task = current_task().unwrap();
+    // 写回期间不持有文件描述符表的锁
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
+        Err(errno) => return errno,
+    };
+    match file_descriptor
+        .sync(datasync)
+        .and_then(|()| BLOCK_DEVICE.flush())
+    {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
 
+pub fn sys_fsync(fd: usize) -> isize {
     info!("[sys_fsync] fd: {}", fd);
-    let fd_table = task.files.lock();
-    if let Err(errno) = fd_table.check(fd) {
-        return errno;
+    do_fsync(fd, false)
+}
+
+pub fn sys_fdatasync(fd: usize) -> isize {
+    info!("[sys_fdatasync] fd: {}", fd);
+    do_fsync(fd, true)
+}
+
+pub fn sys_sync() -> isize {
+    info!("[sys_sync]");
+    writeback::sync_all();
+    SUCCESS
+}
+
+pub fn sys_syncfs(fd: usize) -> isize {
+    let task = current_task().unwrap();
+    info!("[sys_syncfs] fd: {}", fd);
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
+        Err(errno) => return errno,
+    };
+    // 不属于目录树的文件（管道、套接字等）没有需要写回的数据
+    if let Some(inode) = file_descriptor.file.get_dirtree_node() {
+        writeback::sync_fs(inode.fs_id());
     }
     SUCCESS
 }
 
-pub fn sys_fchmodat() -> isize {
-    // baseline 未完成这个函数
-    println!("[kernel in sys_fchmodat] chmod is not supported for now!\n");
-    0
+pub fn sys_fchmodat(dirfd: usize, path: *const u8, mode: u32) -> isize {
+    let token = current_user_token();
+    let path = match translated_str(token, path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    info!(
+        "[sys_fchmodat] dirfd: {}, path: {:?}, mode: {:o}",
+        dirfd as isize, path, mode
+    );
+    if path.is_empty() {
+        return ENOENT;
+    }
+    let inode = match __lookup_at(dirfd, &path) {
+        Ok(inode) => inode,
+        Err(errno) => return errno,
+    };
+    match inode.chmod(mode) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_fchmod(fd: usize, mode: u32) -> isize {
+    info!("[sys_fchmod] fd: {}, mode: {:o}", fd, mode);
+    let task = current_task().unwrap();
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
+        Err(errno) => return errno,
+    };
+    let inode = match file_descriptor.file.get_dirtree_node() {
+        Some(inode) => inode,
+        None => return EPERM,
+    };
+    match inode.chmod(mode) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+bitflags! {
+    pub struct FchownatFlags: u32 {
+        const AT_SYMLINK_NOFOLLOW = 0x100;
+        const AT_EMPTY_PATH = 0x1000;
+    }
+}
+
+/// `owner`或`group`为-1时不修改对应的ID
+pub fn sys_fchownat(dirfd: usize, path: *const u8, owner: u32, group: u32, flags: u32) -> isize {
+    let token = current_user_token();
+    let path = match translated_str(token, path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let flags = match FchownatFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => {
+            warn!("[sys_fchownat] unknown flags");
+            return EINVAL;
+        }
+    };
+    info!(
+        "[sys_fchownat] dirfd: {}, path: {:?}, owner: {}, group: {}, flags: {:?}",
+        dirfd as isize, path, owner as i32, group as i32, flags
+    );
+    if path.is_empty() && !flags.contains(FchownatFlags::AT_EMPTY_PATH) {
+        return ENOENT;
+    }
+    let inode = match __lookup_at(dirfd, &path) {
+        Ok(inode) => inode,
+        Err(errno) => return errno,
+    };
+    match inode.chown(optional_id(owner), optional_id(group)) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_fchown(fd: usize, owner: u32, group: u32) -> isize {
+    info!(
+        "[sys_fchown] fd: {}, owner: {}, group: {}",
+        fd, owner as i32, group as i32
+    );
+    let task = current_task().unwrap();
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
+        Err(errno) => return errno,
+    };
+    let inode = match file_descriptor.file.get_dirtree_node() {
+        Some(inode) => inode,
+        None => return EPERM,
+    };
+    match inode.chown(optional_id(owner), optional_id(group)) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
 }
 
 pub fn sys_chdir(path: *const u8) -> isize {
@@ -942,10 +1093,12 @@ pub fn sys_openat(dirfd: usize, path: *const u8, flags: u32, mode: u32) -> isize
             return EINVAL;
         }
     };
-    let mode = StatMode::from_bits(mode);
     info!(
         "[sys_openat] dirfd: {}, path: {}, flags: {:?}, mode: {:?}",
-        dirfd as isize, path, flags, mode
+        dirfd as isize,
+        path,
+        flags,
+        StatMode::from_bits(mode)
     );
     let mut fd_table = task.files.lock();
     let file_descriptor = match dirfd {
@@ -956,7 +1109,8 @@ pub fn sys_openat(dirfd: usize, path: *const u8, flags: u32, mode: u32) -> isize
         },
     };
 
-    let new_file_descriptor = match file_descriptor.open(&path, flags, false) {
+    let mode = mode & !task.fs.lock().umask & 0o7777;
+    let new_file_descriptor = match file_descriptor.open_with_mode(&path, flags, mode, false) {
         Ok(file_descriptor) => file_descriptor,
         Err(errno) => return errno,
     };
@@ -1041,6 +1195,420 @@ pub fn sys_ppoll(fds: usize, nfds: usize, tmo_p: usize, sigmask: usize) -> isize
     )
 }
 
+pub fn sys_epoll_create1(flags: usize) -> isize {
+    if flags & !EPOLL_CLOEXEC != 0 {
+        warn!("[sys_epoll_create1] invalid flags: {:#X}", flags);
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let mut fd_table = task.files.lock();
+    match fd_table.insert(FileDescriptor::new(
+        flags & EPOLL_CLOEXEC != 0,
+        false,
+        alloc::sync::Arc::new(EpollInstance::new()),
+    )) {
+        Ok(fd) => fd as isize,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event: *const EpollEvent) -> isize {
+    info!(
+        "[sys_epoll_ctl] epfd: {}, op: {}, fd: {}, event: {:?}",
+        epfd, op, fd, event
+    );
+    let task = current_task().unwrap();
+    let (epoll_file, file) = {
+        let fd_table = task.files.lock();
+        let epoll_file = match fd_table.get_ref(epfd) {
+            Ok(file_descriptor) => file_descriptor.file.clone(),
+            Err(errno) => return errno,
+        };
+        let file = match fd_table.get_ref(fd) {
+            Ok(file_descriptor) => file_descriptor.file.clone(),
+            Err(errno) => return errno,
+        };
+        (epoll_file, file)
+    };
+    let epoll = match epoll_file.downcast_ref::<EpollInstance>() {
+        Some(epoll) => epoll,
+        None => return EINVAL,
+    };
+    if epfd == fd {
+        return EINVAL;
+    }
+    let event = if op == EPOLL_CTL_DEL {
+        EpollEvent::default()
+    } else {
+        match try_get_from_user(task.get_user_token(), event) {
+            Ok(Some(event)) => event,
+            Ok(None) => return EFAULT,
+            Err(errno) => return errno,
+        }
+    };
+    drop(task);
+    match epoll.ctl(op, fd, file, event) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+/// `timeout`以毫秒为单位，负数表示无限等待
+pub fn sys_epoll_pwait(
+    epfd: usize,
+    events: *mut EpollEvent,
+    maxevents: usize,
+    timeout: usize,
+    sigmask: *const crate::task::Signals,
+) -> isize {
+    let maxevents = maxevents as i32;
+    let timeout = timeout as i32;
+    if maxevents <= 0 {
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    let epoll_file = match task.files.lock().get_ref(epfd) {
+        Ok(file_descriptor) => file_descriptor.file.clone(),
+        Err(errno) => return errno,
+    };
+    let epoll = match epoll_file.downcast_ref::<EpollInstance>() {
+        Some(epoll) => epoll,
+        None => return EINVAL,
+    };
+    let timeout = if timeout < 0 {
+        None
+    } else {
+        Some(TimeSpec::now() + TimeSpec::from_ms(timeout as usize))
+    };
+    // push to the top of TrapContext page, make use of redundant space
+    let oldsig = ((task.trap_cx_user_va() + crate::config::PAGE_SIZE) as *mut crate::task::Signals)
+        .wrapping_sub(1);
+    drop(task);
+    if !sigmask.is_null() {
+        sigprocmask(SigMaskHow::SIG_SETMASK.bits(), sigmask, oldsig);
+    }
+    let ret = match epoll.wait(maxevents as usize, timeout) {
+        Ok(ready) => {
+            if ready.is_empty() {
+                0
+            } else if copy_to_user_array(token, &ready[0], events, ready.len()).is_err() {
+                EFAULT
+            } else {
+                ready.len() as isize
+            }
+        }
+        Err(errno) => errno,
+    };
+    if !sigmask.is_null() {
+        sigprocmask(
+            SigMaskHow::SIG_SETMASK.bits(),
+            oldsig,
+            core::ptr::null_mut::<crate::task::Signals>(),
+        );
+    }
+    ret
+}
+
+pub fn sys_eventfd2(initval: u32, flags: usize) -> isize {
+    use crate::fs::eventfd::{EventFd, EFD_CLOEXEC, EFD_NONBLOCK, EFD_SEMAPHORE};
+    info!("[sys_eventfd2] initval: {}, flags: {:#X}", initval, flags);
+    if flags & !(EFD_SEMAPHORE | EFD_NONBLOCK | EFD_CLOEXEC) != 0 {
+        warn!("[sys_eventfd2] invalid flags: {:#X}", flags);
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let mut fd_table = task.files.lock();
+    match fd_table.insert(FileDescriptor::new(
+        flags & EFD_CLOEXEC != 0,
+        flags & EFD_NONBLOCK != 0,
+        alloc::sync::Arc::new(EventFd::new(initval as u64, flags)),
+    )) {
+        Ok(fd) => fd as isize,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_memfd_create(name: *const u8, flags: usize) -> isize {
+    use crate::fs::memfd::{MemFd, MFD_ALLOW_SEALING, MFD_CLOEXEC, MFD_NAME_MAX_LEN};
+    let task = current_task().unwrap();
+    let name = match translated_str(task.get_user_token(), name) {
+        Ok(name) => name,
+        Err(errno) => return errno,
+    };
+    info!("[sys_memfd_create] name: {}, flags: {:#X}", name, flags);
+    if flags & !(MFD_CLOEXEC | MFD_ALLOW_SEALING) != 0 {
+        warn!("[sys_memfd_create] unsupported flags: {:#X}", flags);
+        return EINVAL;
+    }
+    if name.len() > MFD_NAME_MAX_LEN {
+        return EINVAL;
+    }
+    let mut fd_table = task.files.lock();
+    match fd_table.insert(FileDescriptor::new(
+        flags & MFD_CLOEXEC != 0,
+        false,
+        alloc::sync::Arc::new(MemFd::new(flags & MFD_ALLOW_SEALING != 0)),
+    )) {
+        Ok(fd) => fd as isize,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_timerfd_create(clockid: usize, flags: usize) -> isize {
+    use crate::fs::timerfd::*;
+    info!(
+        "[sys_timerfd_create] clockid: {}, flags: {:#X}",
+        clockid, flags
+    );
+    match clockid {
+        CLOCK_REALTIME | CLOCK_MONOTONIC | CLOCK_BOOTTIME | CLOCK_REALTIME_ALARM
+        | CLOCK_BOOTTIME_ALARM => {}
+        _ => return EINVAL,
+    }
+    if flags & !(TFD_NONBLOCK | TFD_CLOEXEC) != 0 {
+        warn!("[sys_timerfd_create] invalid flags: {:#X}", flags);
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let mut fd_table = task.files.lock();
+    match fd_table.insert(FileDescriptor::new(
+        flags & TFD_CLOEXEC != 0,
+        flags & TFD_NONBLOCK != 0,
+        alloc::sync::Arc::new(TimerFd::new(clockid, flags)),
+    )) {
+        Ok(fd) => fd as isize,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_timerfd_settime(
+    fd: usize,
+    flags: usize,
+    new_value: *const ITimerSpec,
+    old_value: *mut ITimerSpec,
+) -> isize {
+    use crate::fs::timerfd::{TimerFd, TFD_TIMER_ABSTIME, TFD_TIMER_CANCEL_ON_SET};
+    info!(
+        "[sys_timerfd_settime] fd: {}, flags: {:#X}, new_value: {:?}, old_value: {:?}",
+        fd, flags, new_value, old_value
+    );
+    if flags & !(TFD_TIMER_ABSTIME | TFD_TIMER_CANCEL_ON_SET) != 0 {
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    let file = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.file.clone(),
+        Err(errno) => return errno,
+    };
+    let timerfd = match file.downcast_ref::<TimerFd>() {
+        Some(timerfd) => timerfd,
+        None => return EINVAL,
+    };
+    let new_value = match try_get_from_user(token, new_value) {
+        Ok(Some(new_value)) => new_value,
+        Ok(None) => return EFAULT,
+        Err(errno) => return errno,
+    };
+    let old = match timerfd.settime(flags, new_value) {
+        Ok(old) => old,
+        Err(errno) => return errno,
+    };
+    if !old_value.is_null() && copy_to_user(token, &old, old_value).is_err() {
+        return EFAULT;
+    }
+    SUCCESS
+}
+
+pub fn sys_timerfd_gettime(fd: usize, curr_value: *mut ITimerSpec) -> isize {
+    use crate::fs::timerfd::TimerFd;
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    let file = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.file.clone(),
+        Err(errno) => return errno,
+    };
+    let timerfd = match file.downcast_ref::<TimerFd>() {
+        Some(timerfd) => timerfd,
+        None => return EINVAL,
+    };
+    if copy_to_user(token, &timerfd.gettime(), curr_value).is_err() {
+        return EFAULT;
+    }
+    SUCCESS
+}
+
+/// `fd`为-1时创建新的signalfd，否则修改已有signalfd的信号集
+pub fn sys_signalfd4(fd: usize, mask: *const u64, sizemask: usize, flags: usize) -> isize {
+    use crate::fs::signalfd::{SignalFd, SFD_CLOEXEC, SFD_NONBLOCK};
+    use crate::task::Signals;
+    info!(
+        "[sys_signalfd4] fd: {}, mask: {:?}, sizemask: {}, flags: {:#X}",
+        fd as isize, mask, sizemask, flags
+    );
+    if sizemask != size_of::<u64>() || flags & !(SFD_NONBLOCK | SFD_CLOEXEC) != 0 {
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let mask = match try_get_from_user(task.get_user_token(), mask) {
+        Ok(Some(mask)) => Signals::from_bits_truncate(mask as _),
+        Ok(None) => return EFAULT,
+        Err(errno) => return errno,
+    };
+    let mut fd_table = task.files.lock();
+    if fd as isize == -1 {
+        return match fd_table.insert(FileDescriptor::new(
+            flags & SFD_CLOEXEC != 0,
+            flags & SFD_NONBLOCK != 0,
+            alloc::sync::Arc::new(SignalFd::new(mask, flags)),
+        )) {
+            Ok(fd) => fd as isize,
+            Err(errno) => errno,
+        };
+    }
+    let file_descriptor = match fd_table.get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor,
+        Err(errno) => return errno,
+    };
+    match file_descriptor.file.downcast_ref::<SignalFd>() {
+        Some(signalfd) => {
+            signalfd.set_mask(mask);
+            fd as isize
+        }
+        None => EINVAL,
+    }
+}
+
+pub fn sys_inotify_init1(flags: usize) -> isize {
+    use crate::fs::inotify::{InotifyInstance, IN_CLOEXEC, IN_NONBLOCK};
+    if flags & !(IN_NONBLOCK | IN_CLOEXEC) != 0 {
+        warn!("[sys_inotify_init1] invalid flags: {:#X}", flags);
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let mut fd_table = task.files.lock();
+    match fd_table.insert(FileDescriptor::new(
+        flags & IN_CLOEXEC != 0,
+        flags & IN_NONBLOCK != 0,
+        alloc::sync::Arc::new(InotifyInstance::new(flags)),
+    )) {
+        Ok(fd) => fd as isize,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_inotify_add_watch(fd: usize, path: *const u8, mask: u32) -> isize {
+    use crate::fs::inotify::{InotifyInstance, InotifyMask};
+    let task = current_task().unwrap();
+    let path = match translated_str(task.get_user_token(), path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    info!(
+        "[sys_inotify_add_watch] fd: {}, path: {}, mask: {:?}",
+        fd,
+        path,
+        InotifyMask::from_bits(mask)
+    );
+    let file = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.file.clone(),
+        Err(errno) => return errno,
+    };
+    let inotify = match file.downcast_ref::<InotifyInstance>() {
+        Some(inotify) => inotify,
+        None => return EINVAL,
+    };
+    if path.is_empty() {
+        return ENOENT;
+    }
+    // 只查找路径而不打开文件，避免产生`IN_OPEN`事件
+    let node = match task.fs.lock().working_inode.file.get_dirtree_node() {
+        Some(cwd) => cwd.cd_path(&path),
+        None => Err(ENOENT),
+    };
+    let node = match node {
+        Ok(node) => node,
+        Err(errno) => return errno,
+    };
+    match inotify.add_watch(node, InotifyMask::from_bits_truncate(mask)) {
+        Ok(wd) => wd as isize,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_inotify_rm_watch(fd: usize, wd: i32) -> isize {
+    use crate::fs::inotify::InotifyInstance;
+    info!("[sys_inotify_rm_watch] fd: {}, wd: {}", fd, wd);
+    let task = current_task().unwrap();
+    let file = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.file.clone(),
+        Err(errno) => return errno,
+    };
+    let inotify = match file.downcast_ref::<InotifyInstance>() {
+        Some(inotify) => inotify,
+        None => return EINVAL,
+    };
+    match inotify.rm_watch(wd) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+/// 创建命名管道、设备节点或普通文件，`dev`为设备节点的设备号
+pub fn sys_mknodat(dirfd: usize, path: *const u8, mode: u32, dev: u32) -> isize {
+    let task = current_task().unwrap();
+    let token = task.get_user_token();
+    let path = match translated_str(token, path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    info!(
+        "[sys_mknodat] dirfd: {}, path: {}, mode: {:?}, dev: {:#x}",
+        dirfd as isize,
+        path,
+        StatMode::from_bits(mode),
+        dev
+    );
+    let file_descriptor = match dirfd {
+        AT_FDCWD => task.fs.lock().working_inode.as_ref().clone(),
+        fd => {
+            let fd_table = task.files.lock();
+            match fd_table.get_ref(fd) {
+                Ok(file_descriptor) => file_descriptor.clone(),
+                Err(errno) => return errno,
+            }
+        }
+    };
+    let perm = mode & !task.fs.lock().umask & 0o7777;
+    let file_type = match StatMode::from_bits_truncate(mode) & StatMode::S_IFMT {
+        StatMode::S_IFIFO => DiskInodeType::FIFO,
+        StatMode::S_IFCHR => DiskInodeType::Character,
+        StatMode::S_IFBLK => DiskInodeType::Block,
+        mode if mode.is_empty() || mode == StatMode::S_IFREG => {
+            return match file_descriptor.open_with_mode(
+                &path,
+                OpenFlags::O_CREAT | OpenFlags::O_EXCL,
+                perm,
+                false,
+            ) {
+                Ok(_) => SUCCESS,
+                Err(errno) => errno,
+            };
+        }
+        StatMode::S_IFSOCK => return EPERM,
+        _ => return EINVAL,
+    };
+    // 创建设备节点需要CAP_MKNOD，命名管道不需要
+    if file_type != DiskInodeType::FIFO && !capable(Capabilities::CAP_MKNOD) {
+        return EPERM;
+    }
+    match file_descriptor.mknod(&path, file_type, dev, perm) {
+        Ok(_) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
 pub fn sys_mkdirat(dirfd: usize, path: *const u8, mode: u32) -> isize {
     let task = current_task().unwrap();
     let token = task.get_user_token();
@@ -1064,7 +1632,8 @@ pub fn sys_mkdirat(dirfd: usize, path: *const u8, mode: u32) -> isize {
             }
         }
     };
-    match file_descriptor.mkdir(&path) {
+    let mode = mode & !task.fs.lock().umask & 0o7777;
+    match file_descriptor.mkdir(&path, mode) {
         Ok(_) => SUCCESS,
         Err(errno) => errno,
     }
@@ -1123,6 +1692,9 @@ bitflags! {
 }
 
 pub fn sys_umount2(target: *const u8, flags: u32) -> isize {
+    if !capable(Capabilities::CAP_SYS_ADMIN) {
+        return EPERM;
+    }
     if target.is_null() {
         return EINVAL;
     }
@@ -1181,6 +1753,9 @@ pub fn sys_mount(
     mountflags: usize,
     data: *const u8,
 ) -> isize {
+    if !capable(Capabilities::CAP_SYS_ADMIN) {
+        return EPERM;
+    }
     if source.is_null() || target.is_null() || filesystemtype.is_null() {
         return EINVAL;
     }
@@ -1207,6 +1782,101 @@ pub fn sys_mount(
     SUCCESS
 }
 
+/// 交换区的来源：块设备是交换分区，普通文件是交换文件
+#[cfg(feature = "swap")]
+fn swap_source(path: &str) -> Result<(FileDescriptor, SwapSource), isize> {
+    let file_descriptor = __openat(AT_FDCWD, path)?;
+    let stat = file_descriptor.get_stat();
+    // 没有分区表支持，块设备只有根文件系统所在的整个磁盘，不能用作交换分区
+    let source = match file_descriptor.file.get_file_type() {
+        DiskInodeType::File => SwapSource(stat.get_dev(), stat.get_ino()),
+        _ => return Err(EINVAL),
+    };
+    Ok((file_descriptor, source))
+}
+
+#[cfg(feature = "swap")]
+pub fn sys_swapon(path: *const u8, flags: u32) -> isize {
+    const SWAP_FLAG_PREFER: u32 = 0x8000;
+    const SWAP_FLAG_PRIO_MASK: u32 = 0x7fff;
+    const SWAP_FLAG_DISCARD: u32 = 0x10000;
+    const SWAP_FLAG_DISCARD_ONCE: u32 = 0x20000;
+    const SWAP_FLAG_DISCARD_PAGES: u32 = 0x40000;
+    if !capable(Capabilities::CAP_SYS_ADMIN) {
+        return EPERM;
+    }
+    if flags
+        & !(SWAP_FLAG_PREFER
+            | SWAP_FLAG_PRIO_MASK
+            | SWAP_FLAG_DISCARD
+            | SWAP_FLAG_DISCARD_ONCE
+            | SWAP_FLAG_DISCARD_PAGES)
+        != 0
+    {
+        return EINVAL;
+    }
+    let path = match translated_str(current_user_token(), path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    info!("[sys_swapon] path: {}, flags: {:#x}", path, flags);
+    let (file_descriptor, source) = match swap_source(&path) {
+        Ok(source) => source,
+        Err(errno) => return errno,
+    };
+    let inode = match file_descriptor.file.get_dirtree_node() {
+        Some(inode) => inode,
+        None => return EINVAL,
+    };
+    // 交换区头部由mkswap经页缓存写入，直接读设备前先写回
+    if let Err(errno) = file_descriptor.sync(true) {
+        return errno;
+    }
+    let area = match file_descriptor.file.extents() {
+        Ok(blocks) => SwapArea::file(path, source, inode, blocks, file_descriptor.get_size()),
+        Err(errno) => return errno,
+    };
+    let priority = if flags & SWAP_FLAG_PREFER != 0 {
+        Some((flags & SWAP_FLAG_PRIO_MASK) as isize)
+    } else {
+        None
+    };
+    // 没有discard支持，相关标志直接忽略
+    match area.and_then(|area| swap::SWAP_DEVICE.lock().swapon(area, priority)) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+#[cfg(feature = "swap")]
+pub fn sys_swapoff(path: *const u8) -> isize {
+    if !capable(Capabilities::CAP_SYS_ADMIN) {
+        return EPERM;
+    }
+    let path = match translated_str(current_user_token(), path) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    info!("[sys_swapoff] path: {}", path);
+    let source = match swap_source(&path) {
+        Ok((_, source)) => source,
+        Err(errno) => return errno,
+    };
+    let swap_type = match swap::SWAP_DEVICE.lock().start_swapoff(source) {
+        Ok(swap_type) => swap_type,
+        Err(errno) => return errno,
+    };
+    // 换入时不能持有交换区的锁
+    #[cfg(feature = "oom_handler")]
+    let drained = crate::mm::swap_in_all(swap_type).is_ok();
+    #[cfg(not(feature = "oom_handler"))]
+    let drained = true;
+    match swap::SWAP_DEVICE.lock().finish_swapoff(swap_type, drained) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
 bitflags! {
     pub struct UtimensatFlags: u32 {
         const AT_SYMLINK_NOFOLLOW = 0x100;
@@ -1273,6 +1943,64 @@ pub fn sys_utimensat(
     SUCCESS
 }
 
+/// `fcntl()`的记录锁命令，`arg`指向用户态的`struct flock`
+fn sys_fcntl_lock(
+    task: &TaskControlBlock,
+    file: alloc::sync::Arc<dyn file_trait::File>,
+    cmd: Fcntl_Command,
+    arg: usize,
+) -> isize {
+    let token = task.get_user_token();
+    let mut flock = match try_get_from_user(token, arg as *const Flock) {
+        Ok(Some(flock)) => flock,
+        Ok(None) => return EFAULT,
+        Err(errno) => return errno,
+    };
+    let owner = match cmd {
+        Fcntl_Command::OFD_GETLK | Fcntl_Command::OFD_SETLK | Fcntl_Command::OFD_SETLKW => {
+            // OFD锁要求`l_pid`为0
+            if flock.l_pid != 0 {
+                return EINVAL;
+            }
+            LockOwner::of_file(&file)
+        }
+        _ => LockOwner::Process(task.tgid),
+    };
+    let result = match cmd {
+        Fcntl_Command::GETLK | Fcntl_Command::OFD_GETLK => {
+            match fcntl_getlk(&file, owner, &mut flock) {
+                Ok(()) => {
+                    if copy_to_user(token, &flock, arg as *mut Flock).is_err() {
+                        return EFAULT;
+                    }
+                    Ok(())
+                }
+                Err(errno) => Err(errno),
+            }
+        }
+        Fcntl_Command::SETLK | Fcntl_Command::OFD_SETLK => fcntl_setlk(&file, owner, &flock, false),
+        _ => fcntl_setlk(&file, owner, &flock, true),
+    };
+    match result {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_flock(fd: usize, operation: usize) -> isize {
+    info!("[sys_flock] fd: {}, operation: {:#X}", fd, operation);
+    let task = current_task().unwrap();
+    let file = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.file.clone(),
+        Err(errno) => return errno,
+    };
+    drop(task);
+    match flock(&file, operation) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
 #[allow(non_camel_case_types)]
 #[derive(Debug, Eq, PartialEq, FromPrimitive)]
 #[repr(u32)]
@@ -1365,6 +2093,20 @@ pub fn sys_fcntl(fd: usize, cmd: u32, arg: usize) -> isize {
             }
             SUCCESS
         }
+        Fcntl_Command::GETLK
+        | Fcntl_Command::SETLK
+        | Fcntl_Command::SETLKW
+        | Fcntl_Command::OFD_GETLK
+        | Fcntl_Command::OFD_SETLK
+        | Fcntl_Command::OFD_SETLKW => {
+            let file = match fd_table.get_ref(fd) {
+                Ok(file_descriptor) => file_descriptor.file.clone(),
+                Err(errno) => return errno,
+            };
+            // 阻塞加锁时不能持有文件描述符表的锁
+            drop(fd_table);
+            sys_fcntl_lock(&task, file, Fcntl_Command::from_primitive(cmd), arg)
+        }
         Fcntl_Command::GETFL => {
             let file_descriptor = match fd_table.get_ref(fd) {
                 Ok(file_descriptor) => file_descriptor,
@@ -1377,6 +2119,25 @@ pub fn sys_fcntl(fd: usize, cmd: u32, arg: usize) -> isize {
             }
             res
         }
+        Fcntl_Command::ADD_SEALS | Fcntl_Command::GET_SEALS => {
+            use crate::fs::memfd::MemFd;
+            let file = match fd_table.get_ref(fd) {
+                Ok(file_descriptor) => file_descriptor.file.clone(),
+                Err(errno) => return errno,
+            };
+            // 只有memfd支持封印
+            let memfd = match file.downcast_ref::<MemFd>() {
+                Some(memfd) => memfd,
+                None => return EINVAL,
+            };
+            if Fcntl_Command::from_primitive(cmd) == Fcntl_Command::GET_SEALS {
+                return memfd.get_seals() as isize;
+            }
+            match memfd.add_seals(arg as u32) {
+                Ok(()) => SUCCESS,
+                Err(errno) => errno,
+            }
+        }
         command => {
             warn!("[fcntl] Unsupported command: {:?}", command);
             SUCCESS
@@ -1458,10 +2219,11 @@ pub fn sys_pselect(
 /// In current implementation, umask is always 0. This syscall won't do anything.
 pub fn sys_umask(mask: u32) -> isize {
     info!("[sys_umask] mask: {:o}", mask);
-    warn!(
-        "[sys_umask] In current implementation, umask is always 0. This syscall won't do anything."
-    );
-    0
+    let task = current_task().unwrap();
+    let mut fs = task.fs.lock();
+    let old_mask = fs.umask;
+    fs.umask = mask & 0o777;
+    old_mask as isize
 }
 
 bitflags! {
@@ -1503,10 +2265,22 @@ pub fn sys_faccessat2(dirfd: usize, pathname: *const u8, mode: u32, flags: u32)
         dirfd as isize, pathname, mode, flags
     );
 
-    // Do not check user's authority, because user group is not implemented yet.
-    // All existing files can be accessed.
-    match __openat(dirfd, pathname.as_str()) {
-        Ok(_) => SUCCESS,
+    let file_descriptor = match __openat(dirfd, pathname.as_str()) {
+        Ok(file_descriptor) => file_descriptor,
+        Err(errno) => return errno,
+    };
+    if mode == FaccessatMode::F_OK {
+        return SUCCESS;
+    }
+    // 默认按实际用户ID检查，AT_EACCESS时按有效ID（文件系统ID）检查
+    let task = current_task().unwrap();
+    let cred = if flags.contains(FaccessatFlags::AT_EACCESS) {
+        task.cred.lock().clone()
+    } else {
+        task.cred.lock().real()
+    };
+    match permission::generic_permission(&file_descriptor.get_stat(), mode.bits(), &cred) {
+        Ok(()) => SUCCESS,
         Err(errno) => errno,
     }
 }
@@ -1527,21 +2301,81 @@ pub fn sys_msync(addr: usize, length: usize, flags: u32) -> isize {
         Some(flags) => flags,
         None => return EINVAL,
     };
-    let task = current_task().unwrap();
-    if !task
-        .vm
-        .lock()
-        .contains_valid_buffer(addr, length, MapPermission::empty())
-    {
-        return ENOMEM;
+    if flags.contains(MsyncFlags::MS_ASYNC | MsyncFlags::MS_SYNC) {
+        return EINVAL;
     }
     info!(
         "[sys_msync] addr: {:X}, length: {:X}, flags: {:?}",
-        addr, flags, flags
+        addr, length, flags
     );
+    let task = current_task().unwrap();
+    // 共享映射直接使用页缓存，与read/write和其他进程的映射天然一致，MS_INVALIDATE无需额外处理；
+    // 被写过的页在这里转为页缓存的脏页，MS_ASYNC交给回写线程，MS_SYNC立即写回
+    let files = {
+        let mut vm = task.vm.lock();
+        if !vm.contains_valid_buffer(addr, length, MapPermission::empty()) {
+            return ENOMEM;
+        }
+        vm.sync_shared_pages(
+            VirtAddr::from(addr).floor(),
+            VirtAddr::from(addr + length).ceil(),
+        )
+    };
+    if flags.contains(MsyncFlags::MS_SYNC) {
+        for file in files {
+            if let Some(inode) = file.get_dirtree_node() {
+                writeback::clear_inode_dirty(&inode);
+            }
+            if let Err(errno) = file.sync(true) {
+                return errno;
+            }
+        }
+    }
     SUCCESS
 }
 
+pub fn sys_fadvise64(fd: usize, offset: usize, len: usize, advice: usize) -> isize {
+    info!(
+        "[sys_fadvise64] fd: {}, offset: {:X}, len: {:X}, advice: {}",
+        fd, offset, len, advice
+    );
+    if advice > readahead::POSIX_FADV_NOREUSE {
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
+        Err(errno) => return errno,
+    };
+    match file_descriptor.file.fadvise(offset, len, advice) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_readahead(fd: usize, offset: usize, count: usize) -> isize {
+    info!(
+        "[sys_readahead] fd: {}, offset: {:X}, count: {:X}",
+        fd, offset, count
+    );
+    let task = current_task().unwrap();
+    let file_descriptor = match task.files.lock().get_ref(fd) {
+        Ok(file_descriptor) => file_descriptor.clone(),
+        Err(errno) => return errno,
+    };
+    if !file_descriptor.readable() {
+        return EBADF;
+    }
+    match file_descriptor
+        .file
+        .fadvise(offset, count, readahead::POSIX_FADV_WILLNEED)
+    {
+        Ok(()) => SUCCESS,
+        // 不支持预读的文件类型
+        Err(_) => EINVAL,
+    }
+}
+
 pub fn sys_ftruncate(fd: usize, length: isize) -> isize {
     let task = current_task().unwrap();
     let fd_table = task.files.lock();
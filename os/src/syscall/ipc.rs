@@ -0,0 +1,264 @@
+use super::errno::*;
+use crate::ipc::{
+    msg::{msgctl, msgget, msgrcv, msgsnd, MsqidDs, MSGMAX},
+    sem::{
+        sem_nsems, semctl, semget, semtimedop, SemBuf, SemctlArg, SemidDs, GETALL, SEMOPM, SETALL,
+        SETVAL,
+    },
+    shm::{shmat, shmctl, shmdt, shmget, ShmidDs},
+    IPC_64, IPC_SET, IPC_STAT,
+};
+use crate::mm::{
+    copy_from_user, copy_from_user_array, copy_to_user, copy_to_user_array, get_from_user,
+    try_get_from_user,
+};
+use crate::task::current_user_token;
+use crate::timer::TimeSpec;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::size_of;
+use log::info;
+
+pub fn sys_shmget(key: i32, size: usize, shmflg: u32) -> isize {
+    info!(
+        "[sys_shmget] key: {}, size: {:#X}, shmflg: {:#o}",
+        key, size, shmflg
+    );
+    match shmget(key, size, shmflg) {
+        Ok(shmid) => shmid as isize,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_shmat(shmid: i32, shmaddr: usize, shmflg: u32) -> isize {
+    info!(
+        "[sys_shmat] shmid: {}, shmaddr: {:#X}, shmflg: {:#o}",
+        shmid, shmaddr, shmflg
+    );
+    match shmat(shmid, shmaddr, shmflg) {
+        Ok(addr) => addr as isize,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_shmdt(shmaddr: usize) -> isize {
+    info!("[sys_shmdt] shmaddr: {:#X}", shmaddr);
+    match shmdt(shmaddr) {
+        Ok(_) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_shmctl(shmid: i32, cmd: u32, buf: *mut ShmidDs) -> isize {
+    info!("[sys_shmctl] shmid: {}, cmd: {}", shmid, cmd);
+    let token = current_user_token();
+    let mut shmid_ds = ShmidDs::default();
+    if cmd & !IPC_64 == IPC_SET {
+        if let Err(errno) = copy_from_user(token, buf, &mut shmid_ds) {
+            return errno;
+        }
+    }
+    if let Err(errno) = shmctl(shmid, cmd, &mut shmid_ds) {
+        return errno;
+    }
+    if cmd & !IPC_64 == IPC_STAT {
+        if let Err(errno) = copy_to_user(token, &shmid_ds, buf) {
+            return errno;
+        }
+    }
+    SUCCESS
+}
+
+pub fn sys_semget(key: i32, nsems: usize, semflg: u32) -> isize {
+    info!(
+        "[sys_semget] key: {}, nsems: {}, semflg: {:#o}",
+        key, nsems, semflg
+    );
+    match semget(key, nsems, semflg) {
+        Ok(semid) => semid as isize,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_semtimedop(
+    semid: i32,
+    sops: *const SemBuf,
+    nsops: usize,
+    timeout: *const TimeSpec,
+) -> isize {
+    info!("[sys_semtimedop] semid: {}, nsops: {}", semid, nsops);
+    let token = current_user_token();
+    let timeout = match try_get_from_user(token, timeout) {
+        Ok(timeout) => timeout.map(|timeout| timeout + TimeSpec::now()),
+        Err(errno) => return errno,
+    };
+    if nsops == 0 {
+        return EINVAL;
+    }
+    if nsops > SEMOPM {
+        return E2BIG;
+    }
+    let mut buf: Vec<SemBuf> = Vec::with_capacity(nsops);
+    if let Err(errno) = copy_from_user_array(token, sops, buf.as_mut_ptr(), nsops) {
+        return errno;
+    }
+    unsafe { buf.set_len(nsops) };
+    match semtimedop(semid, &buf, timeout) {
+        Ok(_) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_semop(semid: i32, sops: *const SemBuf, nsops: usize) -> isize {
+    sys_semtimedop(semid, sops, nsops, core::ptr::null())
+}
+
+/// `arg`为`union semun`：`SETVAL`时是值，`GETALL`/`SETALL`时是`unsigned short`数组，
+/// `IPC_STAT`/`IPC_SET`时是`struct semid64_ds`
+pub fn sys_semctl(semid: i32, semnum: usize, cmd: u32, arg: usize) -> isize {
+    info!(
+        "[sys_semctl] semid: {}, semnum: {}, cmd: {}, arg: {:#X}",
+        semid, semnum, cmd, arg
+    );
+    let token = current_user_token();
+    match cmd & !IPC_64 {
+        IPC_STAT | IPC_SET => {
+            let buf = arg as *mut SemidDs;
+            let mut semid_ds = SemidDs::default();
+            if cmd & !IPC_64 == IPC_SET {
+                if let Err(errno) = copy_from_user(token, buf, &mut semid_ds) {
+                    return errno;
+                }
+            }
+            if let Err(errno) = semctl(semid, semnum, cmd, SemctlArg::Buf(&mut semid_ds)) {
+                return errno;
+            }
+            if cmd & !IPC_64 == IPC_STAT {
+                if let Err(errno) = copy_to_user(token, &semid_ds, buf) {
+                    return errno;
+                }
+            }
+            SUCCESS
+        }
+        GETALL | SETALL => {
+            let array = arg as *mut u16;
+            let nsems = match sem_nsems(semid) {
+                Ok(nsems) => nsems,
+                Err(errno) => return errno,
+            };
+            let mut vals = vec![0u16; nsems];
+            if cmd & !IPC_64 == SETALL {
+                if let Err(errno) = copy_from_user_array(token, array, vals.as_mut_ptr(), nsems) {
+                    return errno;
+                }
+            }
+            if let Err(errno) = semctl(semid, semnum, cmd, SemctlArg::Array(&mut vals)) {
+                return errno;
+            }
+            if cmd & !IPC_64 == GETALL {
+                // 信号量集可能在此期间被删除并重建，写回的长度不超过读取长度时的数量
+                vals.truncate(nsems);
+                if let Err(errno) = copy_to_user_array(token, vals.as_ptr(), array, vals.len()) {
+                    return errno;
+                }
+            }
+            SUCCESS
+        }
+        SETVAL => match semctl(semid, semnum, cmd, SemctlArg::Val(arg as i32)) {
+            Ok(ret) => ret,
+            Err(errno) => errno,
+        },
+        _ => match semctl(semid, semnum, cmd, SemctlArg::Val(0)) {
+            Ok(ret) => ret,
+            Err(errno) => errno,
+        },
+    }
+}
+
+pub fn sys_msgget(key: i32, msgflg: u32) -> isize {
+    info!("[sys_msgget] key: {}, msgflg: {:#o}", key, msgflg);
+    match msgget(key, msgflg) {
+        Ok(msqid) => msqid as isize,
+        Err(errno) => errno,
+    }
+}
+
+/// `msgp`指向`struct msgbuf`：开头是`long`类型的消息类型，之后是`msgsz`字节的正文
+pub fn sys_msgsnd(msqid: i32, msgp: *const u8, msgsz: usize, msgflg: u32) -> isize {
+    info!(
+        "[sys_msgsnd] msqid: {}, msgsz: {}, msgflg: {:#o}",
+        msqid, msgsz, msgflg
+    );
+    if msgsz > MSGMAX {
+        return EINVAL;
+    }
+    let token = current_user_token();
+    let mtype = match get_from_user(token, msgp as *const isize) {
+        Ok(mtype) => mtype,
+        Err(errno) => return errno,
+    };
+    let mut text = vec![0u8; msgsz];
+    if msgsz > 0 {
+        if let Err(errno) = copy_from_user_array(
+            token,
+            msgp.wrapping_add(size_of::<isize>()),
+            text.as_mut_ptr(),
+            msgsz,
+        ) {
+            return errno;
+        }
+    }
+    match msgsnd(msqid, mtype, text, msgflg) {
+        Ok(_) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_msgrcv(msqid: i32, msgp: *mut u8, msgsz: usize, msgtyp: isize, msgflg: u32) -> isize {
+    info!(
+        "[sys_msgrcv] msqid: {}, msgsz: {}, msgtyp: {}, msgflg: {:#o}",
+        msqid, msgsz, msgtyp, msgflg
+    );
+    if (msgsz as isize) < 0 {
+        return EINVAL;
+    }
+    let (mtype, text) = match msgrcv(msqid, msgsz, msgtyp, msgflg) {
+        Ok(msg) => msg,
+        Err(errno) => return errno,
+    };
+    let token = current_user_token();
+    if let Err(errno) = copy_to_user(token, &mtype, msgp as *mut isize) {
+        return errno;
+    }
+    if !text.is_empty() {
+        if let Err(errno) = copy_to_user_array(
+            token,
+            text.as_ptr(),
+            msgp.wrapping_add(size_of::<isize>()),
+            text.len(),
+        ) {
+            return errno;
+        }
+    }
+    text.len() as isize
+}
+
+pub fn sys_msgctl(msqid: i32, cmd: u32, buf: *mut MsqidDs) -> isize {
+    info!("[sys_msgctl] msqid: {}, cmd: {}", msqid, cmd);
+    let token = current_user_token();
+    let mut msqid_ds = MsqidDs::default();
+    if cmd & !IPC_64 == IPC_SET {
+        if let Err(errno) = copy_from_user(token, buf, &mut msqid_ds) {
+            return errno;
+        }
+    }
+    if let Err(errno) = msgctl(msqid, cmd, &mut msqid_ds) {
+        return errno;
+    }
+    if cmd & !IPC_64 == IPC_STAT {
+        if let Err(errno) = copy_to_user(token, &msqid_ds, buf) {
+            return errno;
+        }
+    }
+    SUCCESS
+}
@@ -3,12 +3,14 @@ mod syscall_macro;
 
 pub mod errno;
 pub mod fs;
+mod ipc;
 mod net;
 mod process;
 mod syscall_id;
 
 use core::convert::TryFrom;
 use fs::*;
+use ipc::*;
 use log::{error, info};
 use net::*;
 pub use process::CloneFlags;
@@ -18,12 +20,20 @@ pub fn syscall_name(id: usize) -> &'static str {
     match id {
         SYSCALL_DUP => "dup",
         SYSCALL_DUP2 => "dup2",
-        SYSCALL_DUP3 => "dup3",
+        SYSCALL_EVENTFD2 => "eventfd2",
+        SYSCALL_EPOLL_CREATE1 => "epoll_create1",
+        SYSCALL_EPOLL_CTL => "epoll_ctl",
+        SYSCALL_EPOLL_PWAIT => "epoll_pwait",
         SYSCALL_OPEN => "open",
         SYSCALL_GET_TIME => "get_time",
         SYSCALL_GETCWD => "getcwd",
         SYSCALL_FCNTL => "fcntl",
+        SYSCALL_INOTIFY_INIT1 => "inotify_init1",
+        SYSCALL_INOTIFY_ADD_WATCH => "inotify_add_watch",
+        SYSCALL_INOTIFY_RM_WATCH => "inotify_rm_watch",
         SYSCALL_IOCTL => "ioctl",
+        SYSCALL_FLOCK => "flock",
+        SYSCALL_MKNODAT => "mknodat",
         SYSCALL_MKDIRAT => "mkdirat",
         SYSCALL_UNLINKAT => "unlinkat",
         SYSCALL_LINKAT => "linkat",
@@ -31,7 +41,10 @@ pub fn syscall_name(id: usize) -> &'static str {
         SYSCALL_MOUNT => "mount",
         SYSCALL_FACCESSAT => "faccessat",
         SYSCALL_CHDIR => "chdir",
+        SYSCALL_FCHMOD => "fchmod",
         SYSCALL_FCHMODAT => "fchmodat",
+        SYSCALL_FCHOWNAT => "fchownat",
+        SYSCALL_FCHOWN => "fchown",
         SYSCALL_OPENAT => "openat",
         SYSCALL_CLOSE => "close",
         SYSCALL_PIPE2 => "pipe2",
@@ -46,12 +59,18 @@ pub fn syscall_name(id: usize) -> &'static str {
         SYSCALL_SENDFILE => "sendfile",
         SYSCALL_PSELECT6 => "pselect6",
         SYSCALL_PPOLL => "ppoll",
+        SYSCALL_SIGNALFD4 => "signalfd4",
         SYSCALL_READLINKAT => "readlinkat",
         SYSCALL_FSTATAT => "fstatat",
         SYSCALL_FSTAT => "fstat",
         SYSCALL_STATFS => "statfs",
         SYSCALL_FTRUNCATE => "ftruncate",
+        SYSCALL_SYNC => "sync",
         SYSCALL_FSYNC => "fsync",
+        SYSCALL_FDATASYNC => "fdatasync",
+        SYSCALL_TIMERFD_CREATE => "timerfd_create",
+        SYSCALL_TIMERFD_SETTIME => "timerfd_settime",
+        SYSCALL_TIMERFD_GETTIME => "timerfd_gettime",
         SYSCALL_UTIMENSAT => "utimensat",
         SYSCALL_EXIT => "exit",
         SYSCALL_EXIT_GROUP => "exit_GROUP",
@@ -78,6 +97,7 @@ pub fn syscall_name(id: usize) -> &'static str {
         SYSCALL_UNAME => "uname",
         SYSCALL_GETRUSAGE => "getrusage",
         SYSCALL_UMASK => "umask",
+        SYSCALL_PRCTL => "prctl",
         SYSCALL_GET_TIME_OF_DAY => "get_time_of_day",
         SYSCALL_GETPID => "getpid",
         SYSCALL_GETPPID => "getppid",
@@ -85,8 +105,36 @@ pub fn syscall_name(id: usize) -> &'static str {
         SYSCALL_GETEUID => "geteuid",
         SYSCALL_GETGID => "getgid",
         SYSCALL_GETEGID => "getegid",
+        SYSCALL_CAPGET => "capget",
+        SYSCALL_CAPSET => "capset",
+        SYSCALL_SETPRIORITY => "setpriority",
+        SYSCALL_GETPRIORITY => "getpriority",
+        SYSCALL_SETREGID => "setregid",
+        SYSCALL_SETGID => "setgid",
+        SYSCALL_SETREUID => "setreuid",
+        SYSCALL_SETUID => "setuid",
+        SYSCALL_SETRESUID => "setresuid",
+        SYSCALL_GETRESUID => "getresuid",
+        SYSCALL_SETRESGID => "setresgid",
+        SYSCALL_GETRESGID => "getresgid",
+        SYSCALL_SETFSUID => "setfsuid",
+        SYSCALL_SETFSGID => "setfsgid",
+        SYSCALL_GETGROUPS => "getgroups",
+        SYSCALL_SETGROUPS => "setgroups",
         SYSCALL_GETTID => "gettid",
         SYSCALL_SYSINFO => "sysinfo",
+        SYSCALL_MSGGET => "msgget",
+        SYSCALL_MSGCTL => "msgctl",
+        SYSCALL_MSGRCV => "msgrcv",
+        SYSCALL_MSGSND => "msgsnd",
+        SYSCALL_SEMGET => "semget",
+        SYSCALL_SEMCTL => "semctl",
+        SYSCALL_SEMTIMEDOP => "semtimedop",
+        SYSCALL_SEMOP => "semop",
+        SYSCALL_SHMGET => "shmget",
+        SYSCALL_SHMCTL => "shmctl",
+        SYSCALL_SHMAT => "shmat",
+        SYSCALL_SHMDT => "shmdt",
         SYSCALL_SOCKET => "socket",
         SYSCALL_BIND => "bind",
         SYSCALL_LISTEN => "listen",
@@ -99,20 +147,34 @@ pub fn syscall_name(id: usize) -> &'static str {
         SYSCALL_SETSOCKOPT => "setsockopt",
         SYSCALL_GETSOCKOPT => "getsockopt",
         SYSCALL_SBRK => "sbrk",
+        SYSCALL_READAHEAD => "readahead",
         SYSCALL_BRK => "brk",
         SYSCALL_MUNMAP => "munmap",
+        SYSCALL_MREMAP => "mremap",
         SYSCALL_CLONE => "clone",
         SYSCALL_EXECVE => "execve",
         SYSCALL_MMAP => "mmap",
+        SYSCALL_FADVISE64 => "fadvise64",
+        SYSCALL_SWAPON => "swapon",
+        SYSCALL_SWAPOFF => "swapoff",
         SYSCALL_MPROTECT => "mprotect",
         SYSCALL_MSYNC => "msync",
+        SYSCALL_MLOCK => "mlock",
+        SYSCALL_MUNLOCK => "munlock",
+        SYSCALL_MLOCKALL => "mlockall",
+        SYSCALL_MUNLOCKALL => "munlockall",
+        SYSCALL_MINCORE => "mincore",
+        SYSCALL_MADVISE => "madvise",
         SYSCALL_WAIT4 => "wait4",
         SYSCALL_PRLIMIT => "prlimit",
+        SYSCALL_SYNCFS => "syncfs",
         SYSCALL_RENAMEAT2 => "renameat2",
         SYSCALL_FACCESSAT2 => "faccessat2",
         SYSCALL_MEMBARRIER => "membarrier",
+        SYSCALL_MLOCK2 => "mlock2",
         SYSCALL_STATX => "statx",
         SYSCALL_GETRANDOM => "getrandom",
+        SYSCALL_MEMFD_CREATE => "memfd_create",
         // non-standard
         SYSCALL_LS => "ls",
         SYSCALL_SHUTDOWN => "shutdown",
@@ -121,9 +183,13 @@ pub fn syscall_name(id: usize) -> &'static str {
     }
 }
 use crate::{
-    fs::poll::FdSet,
+    fs::{epoll::EpollEvent, poll::FdSet, timerfd::ITimerSpec},
+    ipc::{msg::MsqidDs, sem::SemBuf, shm::ShmidDs},
     syscall::errno::Errno,
-    task::Rusage,
+    task::{
+        capability::{CapUserData, CapUserHeader},
+        Rusage,
+    },
     timer::{ITimerVal, TimeSpec, Times},
 };
 
@@ -163,10 +229,34 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
     let ret = match syscall_id {
         SYSCALL_GETCWD => sys_getcwd(args[0], args[1]),
         SYSCALL_DUP => sys_dup(args[0]),
-        SYSCALL_DUP2 => sys_dup2(args[0], args[1]),
-        SYSCALL_DUP3 => sys_dup3(args[0], args[1], args[2] as u32),
+        SYSCALL_DUP2 => match args[2] {
+            0 => sys_dup2(args[0], args[1]),
+            flags => sys_dup3(args[0], args[1], flags as u32),
+        },
+        SYSCALL_EVENTFD2 => sys_eventfd2(args[0] as u32, args[1]),
+        SYSCALL_EPOLL_CREATE1 => sys_epoll_create1(args[0]),
+        SYSCALL_EPOLL_CTL => sys_epoll_ctl(args[0], args[1], args[2], args[3] as *const EpollEvent),
+        SYSCALL_EPOLL_PWAIT => sys_epoll_pwait(
+            args[0],
+            args[1] as *mut EpollEvent,
+            args[2],
+            args[3],
+            args[4] as *const crate::task::Signals,
+        ),
         SYSCALL_FCNTL => sys_fcntl(args[0], args[1] as u32, args[2]),
+        SYSCALL_INOTIFY_INIT1 => sys_inotify_init1(args[0]),
+        SYSCALL_INOTIFY_ADD_WATCH => {
+            sys_inotify_add_watch(args[0], args[1] as *const u8, args[2] as u32)
+        }
+        SYSCALL_INOTIFY_RM_WATCH => sys_inotify_rm_watch(args[0], args[1] as i32),
         SYSCALL_IOCTL => sys_ioctl(args[0], args[1] as u32, args[2]),
+        SYSCALL_FLOCK => sys_flock(args[0], args[1]),
+        SYSCALL_MKNODAT => sys_mknodat(
+            args[0],
+            args[1] as *const u8,
+            args[2] as u32,
+            args[3] as u32,
+        ),
         SYSCALL_MKDIRAT => sys_mkdirat(args[0], args[1] as *const u8, args[2] as u32),
         SYSCALL_UNLINKAT => sys_unlinkat(args[0], args[1] as *const u8, args[2] as u32),
         SYSCALL_UMOUNT2 => sys_umount2(args[0] as *const u8, args[1] as u32),
@@ -179,7 +269,16 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         ),
         SYSCALL_FACCESSAT => sys_faccessat2(args[0], args[1] as *const u8, args[2] as u32, 0u32),
         SYSCALL_CHDIR => sys_chdir(args[0] as *const u8),
-        SYSCALL_FCHMODAT => sys_fchmodat(),
+        SYSCALL_FCHMOD => sys_fchmod(args[0], args[1] as u32),
+        SYSCALL_FCHMODAT => sys_fchmodat(args[0], args[1] as *const u8, args[2] as u32),
+        SYSCALL_FCHOWNAT => sys_fchownat(
+            args[0],
+            args[1] as *const u8,
+            args[2] as u32,
+            args[3] as u32,
+            args[4] as u32,
+        ),
+        SYSCALL_FCHOWN => sys_fchown(args[0], args[1] as u32, args[2] as u32),
         SYSCALL_OPEN => sys_openat(AT_FDCWD, args[0] as *const u8, args[1] as u32, 0o777u32),
         SYSCALL_OPENAT => sys_openat(
             args[0],
@@ -217,7 +316,17 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         ),
         SYSCALL_FSTAT => sys_fstat(args[0], args[1] as *mut u8),
         SYSCALL_FTRUNCATE => sys_ftruncate(args[0], args[1] as isize),
+        SYSCALL_SYNC => sys_sync(),
         SYSCALL_FSYNC => sys_fsync(args[0]),
+        SYSCALL_FDATASYNC => sys_fdatasync(args[0]),
+        SYSCALL_TIMERFD_CREATE => sys_timerfd_create(args[0], args[1]),
+        SYSCALL_TIMERFD_SETTIME => sys_timerfd_settime(
+            args[0],
+            args[1],
+            args[2] as *const ITimerSpec,
+            args[3] as *mut ITimerSpec,
+        ),
+        SYSCALL_TIMERFD_GETTIME => sys_timerfd_gettime(args[0], args[1] as *mut ITimerSpec),
         SYSCALL_UTIMENSAT => sys_utimensat(
             args[0],
             args[1] as *const u8,
@@ -282,6 +391,7 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
             args[2] as *const RLimit,
             args[3] as *mut RLimit,
         ),
+        SYSCALL_SYNCFS => sys_syncfs(args[0]),
         SYSCALL_SET_TID_ADDRESS => sys_set_tid_address(args[0]),
         SYSCALL_FUTEX => sys_futex(
             args[0] as *mut u32,
@@ -299,13 +409,80 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         SYSCALL_GETEUID => sys_geteuid(),
         SYSCALL_GETGID => sys_getgid(),
         SYSCALL_GETEGID => sys_getegid(),
+        SYSCALL_CAPGET => sys_capget(args[0] as *mut CapUserHeader, args[1] as *mut CapUserData),
+        SYSCALL_CAPSET => sys_capset(args[0] as *mut CapUserHeader, args[1] as *const CapUserData),
+        SYSCALL_SETPRIORITY => sys_setpriority(args[0] as u32, args[1], args[2] as i32),
+        SYSCALL_GETPRIORITY => sys_getpriority(args[0] as u32, args[1]),
+        SYSCALL_PRCTL => sys_prctl(args[0], args[1]),
+        SYSCALL_SETREGID => sys_setregid(args[0] as u32, args[1] as u32),
+        SYSCALL_SETGID => sys_setgid(args[0] as u32),
+        SYSCALL_SETREUID => sys_setreuid(args[0] as u32, args[1] as u32),
+        SYSCALL_SETUID => sys_setuid(args[0] as u32),
+        SYSCALL_SETRESUID => sys_setresuid(args[0] as u32, args[1] as u32, args[2] as u32),
+        SYSCALL_GETRESUID => sys_getresuid(
+            args[0] as *mut u32,
+            args[1] as *mut u32,
+            args[2] as *mut u32,
+        ),
+        SYSCALL_SETRESGID => sys_setresgid(args[0] as u32, args[1] as u32, args[2] as u32),
+        SYSCALL_GETRESGID => sys_getresgid(
+            args[0] as *mut u32,
+            args[1] as *mut u32,
+            args[2] as *mut u32,
+        ),
+        SYSCALL_SETFSUID => sys_setfsuid(args[0] as u32),
+        SYSCALL_SETFSGID => sys_setfsgid(args[0] as u32),
+        SYSCALL_GETGROUPS => sys_getgroups(args[0], args[1] as *mut u32),
+        SYSCALL_SETGROUPS => sys_setgroups(args[0], args[1] as *const u32),
         SYSCALL_GETTID => sys_gettid(),
         SYSCALL_SYSINFO => sys_sysinfo(args[0] as *mut Sysinfo),
+        SYSCALL_MSGGET => sys_msgget(args[0] as i32, args[1] as u32),
+        SYSCALL_MSGCTL => sys_msgctl(args[0] as i32, args[1] as u32, args[2] as *mut MsqidDs),
+        SYSCALL_MSGRCV => sys_msgrcv(
+            args[0] as i32,
+            args[1] as *mut u8,
+            args[2],
+            args[3] as isize,
+            args[4] as u32,
+        ),
+        SYSCALL_MSGSND => sys_msgsnd(
+            args[0] as i32,
+            args[1] as *const u8,
+            args[2],
+            args[3] as u32,
+        ),
+        SYSCALL_SEMGET => sys_semget(args[0] as i32, args[1], args[2] as u32),
+        SYSCALL_SEMCTL => sys_semctl(args[0] as i32, args[1], args[2] as u32, args[3]),
+        SYSCALL_SEMTIMEDOP => sys_semtimedop(
+            args[0] as i32,
+            args[1] as *const SemBuf,
+            args[2],
+            args[3] as *const TimeSpec,
+        ),
+        SYSCALL_SEMOP => sys_semop(args[0] as i32, args[1] as *const SemBuf, args[2]),
+        SYSCALL_SHMGET => sys_shmget(args[0] as i32, args[1], args[2] as u32),
+        SYSCALL_SHMCTL => sys_shmctl(args[0] as i32, args[1] as u32, args[2] as *mut ShmidDs),
+        SYSCALL_SHMAT => sys_shmat(args[0] as i32, args[1], args[2] as u32),
+        SYSCALL_SHMDT => sys_shmdt(args[0]),
         SYSCALL_SBRK => sys_sbrk(args[0] as isize),
         SYSCALL_BRK => sys_brk(args[0]),
         SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
         SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_MREMAP => sys_mremap(args[0], args[1], args[2], args[3] as u32, args[4]),
         SYSCALL_MPROTECT => sys_mprotect(args[0], args[1], args[2]),
+        SYSCALL_MADVISE => sys_madvise(args[0], args[1], args[2]),
+        SYSCALL_MLOCK => sys_mlock(args[0], args[1]),
+        SYSCALL_MLOCK2 => sys_mlock2(args[0], args[1], args[2] as u32),
+        SYSCALL_MUNLOCK => sys_munlock(args[0], args[1]),
+        SYSCALL_MLOCKALL => sys_mlockall(args[0] as u32),
+        SYSCALL_MUNLOCKALL => sys_munlockall(),
+        SYSCALL_MINCORE => sys_mincore(args[0], args[1], args[2] as *mut u8),
+        SYSCALL_READAHEAD => sys_readahead(args[0], args[1], args[2]),
+        SYSCALL_FADVISE64 => sys_fadvise64(args[0], args[1], args[2], args[3]),
+        #[cfg(feature = "swap")]
+        SYSCALL_SWAPON => sys_swapon(args[0] as *const u8, args[1] as u32),
+        #[cfg(feature = "swap")]
+        SYSCALL_SWAPOFF => sys_swapoff(args[0] as *const u8),
         SYSCALL_PSELECT6 => sys_pselect(
             args[0],
             args[1] as *mut FdSet,
@@ -315,6 +492,7 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
             args[5] as *const crate::task::Signals,
         ),
         SYSCALL_PPOLL => sys_ppoll(args[0], args[1], args[2], args[3]),
+        SYSCALL_SIGNALFD4 => sys_signalfd4(args[0], args[1] as *const u64, args[2], args[3]),
         SYSCALL_FACCESSAT2 => sys_faccessat2(
             args[0],
             args[1] as *const u8,
@@ -383,6 +561,7 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         ),
         SYSCALL_SOCK_SHUTDOWN => sys_sock_shutdown(args[0] as u32, args[1] as u32),
         SYSCALL_GETRANDOM => sys_getrandom(args[0] as usize, args[1] as usize, args[2] as u32),
+        SYSCALL_MEMFD_CREATE => sys_memfd_create(args[0] as *const u8, args[1]),
         SYSCALL_SHUTDOWN => sys_shutdown(),
         _ => {
             error!(
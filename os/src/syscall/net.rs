@@ -4,7 +4,7 @@ use crate::{
         address::{self, SocketAddrv4},
         make_unix_socket_pair, Socket, SocketType, TCP_MSS,
     }, 
-    task::current_task,
+    task::{capable, current_task, Capabilities},
 };
 use super::errno::*;
 
@@ -22,12 +22,23 @@ const TCP_CONGESTION: u32 = 13;
 const SO_SNDBUF: u32 = 7;
 const SO_RCVBUF: u32 = 8;
 const SO_KEEPALIVE: u32 = 9;
+/// socket type
+const SOCK_TYPE_MASK: u32 = 0xf;
+const SOCK_RAW: u32 = 3;
+const SOCK_PACKET: u32 = 10;
+/// domain
+const AF_PACKET: u32 = 17;
 
 pub fn sys_socket(domain: u32, socket_type: u32, protocol: u32) -> isize {
     info!(
         "[sys_socket] domain: {}, type: {}, protocol: {}",
         domain, socket_type, protocol
     );
+    // 原始套接字可以收发任意报文，需要CAP_NET_RAW
+    let raw = matches!(socket_type & SOCK_TYPE_MASK, SOCK_RAW | SOCK_PACKET) || domain == AF_PACKET;
+    if raw && !capable(Capabilities::CAP_NET_RAW) {
+        return EPERM;
+    }
     let result = match <dyn Socket>::alloc(domain, socket_type){
         Ok(sockfd) => {
             info!("[sys_socket] new sockfd: {}", sockfd);
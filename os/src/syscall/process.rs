@@ -1,30 +1,40 @@
 use crate::config::{PAGE_SIZE, SYSTEM_TASK_LIMIT, USER_STACK_SIZE};
-use crate::fs::OpenFlags;
+use crate::fs::permission::MAY_EXEC;
+use crate::fs::readahead::POSIX_FADV_WILLNEED;
+use crate::fs::{OpenFlags, StatMode};
 use crate::hal::shutdown;
 use crate::hal::{MachineContext, TrapContext};
 use crate::mm::{
-    copy_from_user, copy_to_user, copy_to_user_string, get_from_user, translated_byte_buffer,
-    translated_ref, translated_refmut, translated_str, try_get_from_user, MapFlags, MapPermission,
-    UserBuffer,
+    copy_from_user, copy_from_user_array, copy_to_user, copy_to_user_array, copy_to_user_string,
+    get_from_user, translated_byte_buffer, translated_ref, translated_refmut, translated_str,
+    try_get_from_user, HugePage, MapFlags, MapPermission, UserBuffer, VirtAddr,
 };
 use crate::show_frame_consumption;
 use crate::syscall::errno::*;
+use crate::task::capability::{
+    CapUserData, CapUserHeader, LINUX_CAPABILITY_VERSION_1, LINUX_CAPABILITY_VERSION_2,
+    LINUX_CAPABILITY_VERSION_3,
+};
 use crate::task::threads::{do_futex_wait, FutexCmd};
 use crate::task::{
-    add_task, block_current_and_run_next, current_task, current_user_token,
+    add_task, block_current_and_run_next, capable, current_task, current_user_token,
     exit_current_and_run_next, exit_group_and_run_next, find_task_by_pid, find_task_by_tgid,
     procs_count, signal::*, suspend_current_and_run_next, threads, wait_with_timeout,
-    wake_interruptible, Rusage, TaskStatus,
+    wake_interruptible, Capabilities, Rusage, TaskControlBlock, TaskStatus, NGROUPS_MAX,
 };
 use crate::timer::{get_time_ms, get_time_sec, ITimerVal, TimeSpec, TimeVal, TimeZone, Times};
 use alloc::boxed::Box;
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::mem::size_of;
 use log::{debug, error, info, trace, warn};
 use num_enum::FromPrimitive;
 pub fn sys_shutdown() -> isize {
+    if !capable(Capabilities::CAP_SYS_BOOT) {
+        return EPERM;
+    }
     shutdown()
 }
 pub fn sys_exit(exit_code: u32) -> ! {
@@ -56,9 +66,16 @@ pub enum SyslogAction {
 
 pub fn sys_syslog(type_: u32, buf: *mut u8, len: u32) -> isize {
     const LOG_BUF_LEN: usize = 4096;
+    let type_ = SyslogAction::from(type_);
+    // 与`dmesg_restrict`开启时一致，除打开和关闭外的操作都需要CAP_SYSLOG
+    if type_ != SyslogAction::CLOSE
+        && type_ != SyslogAction::OPEN
+        && !capable(Capabilities::CAP_SYSLOG)
+    {
+        return EPERM;
+    }
     const LOG: &str = "<5>[    0.000000] Linux version 5.10.102.1-microsoft-standard-WSL2 (rtrt@TEAM-NPUCORE) (gcc (Ubuntu 9.4.0-1ubuntu1~20.04) 9.4.0, GNU ld (GNU Binutils for Ubuntu) 2.34) #1 SMP Thu Mar 10 13:31:47 CST 2022";
     let token = current_user_token();
-    let type_ = SyslogAction::from(type_);
     let len = LOG.len().min(len as usize);
     match type_ {
         SyslogAction::CLOSE | SyslogAction::OPEN => SUCCESS,
@@ -86,6 +103,13 @@ pub fn sys_yield() -> isize {
     SUCCESS
 }
 
+/// 当前任务能否向`task`发送信号
+fn may_signal(task: &TaskControlBlock) -> bool {
+    // 先复制发送者的凭证，避免同时持有两个任务的凭证锁
+    let cred = current_task().unwrap().cred.lock().clone();
+    cred.may_signal(&task.cred.lock())
+}
+
 pub fn sys_kill(pid: usize, sig: usize) -> isize {
     let signal = match Signals::from_signum(sig) {
         Ok(signal) => signal,
@@ -100,6 +124,9 @@ pub fn sys_kill(pid: usize, sig: usize) -> isize {
         // signal will be sent to an arbitrary task with target `pid` (`tgid` more precisely).
         // But manual also require that the target task should not mask this signal.
         if let Some(task) = find_task_by_tgid(pid) {
+            if !may_signal(&task) {
+                return EPERM;
+            }
             if !signal.is_empty() {
                 let mut inner = task.acquire_inner_lock();
                 inner.add_signal(signal);
@@ -131,6 +158,9 @@ pub fn sys_tkill(tid: usize, sig: usize) -> isize {
     };
     if tid > 0 {
         if let Some(task) = find_task_by_pid(tid) {
+            if !may_signal(&task) {
+                return EPERM;
+            }
             if !signal.is_empty() {
                 let mut inner = task.acquire_inner_lock();
                 inner.add_signal(signal);
@@ -287,19 +317,363 @@ pub fn sys_getppid() -> isize {
 }
 
 pub fn sys_getuid() -> isize {
-    0 // root user
+    current_task().unwrap().cred.lock().ruid as isize
 }
 
 pub fn sys_geteuid() -> isize {
-    0 // root user
+    current_task().unwrap().cred.lock().euid as isize
 }
 
 pub fn sys_getgid() -> isize {
-    0 // root group
+    current_task().unwrap().cred.lock().rgid as isize
 }
 
 pub fn sys_getegid() -> isize {
-    0 // root group
+    current_task().unwrap().cred.lock().egid as isize
+}
+
+/// 传入的ID为-1时表示不修改
+pub fn optional_id(id: u32) -> Option<u32> {
+    if id == u32::MAX {
+        None
+    } else {
+        Some(id)
+    }
+}
+
+pub fn sys_setuid(uid: u32) -> isize {
+    info!("[sys_setuid] uid: {}", uid);
+    if optional_id(uid).is_none() {
+        return EINVAL;
+    }
+    match current_task().unwrap().cred.lock().setuid(uid) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_setgid(gid: u32) -> isize {
+    info!("[sys_setgid] gid: {}", gid);
+    if optional_id(gid).is_none() {
+        return EINVAL;
+    }
+    match current_task().unwrap().cred.lock().setgid(gid) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_setreuid(ruid: u32, euid: u32) -> isize {
+    info!(
+        "[sys_setreuid] ruid: {}, euid: {}",
+        ruid as i32, euid as i32
+    );
+    match current_task()
+        .unwrap()
+        .cred
+        .lock()
+        .setreuid(optional_id(ruid), optional_id(euid))
+    {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_setregid(rgid: u32, egid: u32) -> isize {
+    info!(
+        "[sys_setregid] rgid: {}, egid: {}",
+        rgid as i32, egid as i32
+    );
+    match current_task()
+        .unwrap()
+        .cred
+        .lock()
+        .setregid(optional_id(rgid), optional_id(egid))
+    {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_setresuid(ruid: u32, euid: u32, suid: u32) -> isize {
+    info!(
+        "[sys_setresuid] ruid: {}, euid: {}, suid: {}",
+        ruid as i32, euid as i32, suid as i32
+    );
+    match current_task().unwrap().cred.lock().setresuid(
+        optional_id(ruid),
+        optional_id(euid),
+        optional_id(suid),
+    ) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_setresgid(rgid: u32, egid: u32, sgid: u32) -> isize {
+    info!(
+        "[sys_setresgid] rgid: {}, egid: {}, sgid: {}",
+        rgid as i32, egid as i32, sgid as i32
+    );
+    match current_task().unwrap().cred.lock().setresgid(
+        optional_id(rgid),
+        optional_id(egid),
+        optional_id(sgid),
+    ) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+/// 把三个ID依次写到用户态的三个地址
+fn put_resid(ids: [u32; 3], ptrs: [*mut u32; 3]) -> isize {
+    let token = current_user_token();
+    for (id, ptr) in ids.iter().zip(ptrs.iter()) {
+        if copy_to_user(token, id, *ptr).is_err() {
+            return EFAULT;
+        }
+    }
+    SUCCESS
+}
+
+pub fn sys_getresuid(ruid: *mut u32, euid: *mut u32, suid: *mut u32) -> isize {
+    let cred = current_task().unwrap().cred.lock().clone();
+    put_resid([cred.ruid, cred.euid, cred.suid], [ruid, euid, suid])
+}
+
+pub fn sys_getresgid(rgid: *mut u32, egid: *mut u32, sgid: *mut u32) -> isize {
+    let cred = current_task().unwrap().cred.lock().clone();
+    put_resid([cred.rgid, cred.egid, cred.sgid], [rgid, egid, sgid])
+}
+
+/// 总是返回原来的文件系统用户ID，调用者需要再次调用才能知道是否修改成功
+pub fn sys_setfsuid(fsuid: u32) -> isize {
+    current_task().unwrap().cred.lock().setfsuid(fsuid) as isize
+}
+
+pub fn sys_setfsgid(fsgid: u32) -> isize {
+    current_task().unwrap().cred.lock().setfsgid(fsgid) as isize
+}
+
+/// `size`为0时只返回附加组的数量
+pub fn sys_getgroups(size: usize, list: *mut u32) -> isize {
+    let groups = current_task().unwrap().cred.lock().groups.clone();
+    if size == 0 {
+        return groups.len() as isize;
+    }
+    if size < groups.len() {
+        return EINVAL;
+    }
+    if !groups.is_empty()
+        && copy_to_user_array(current_user_token(), groups.as_ptr(), list, groups.len()).is_err()
+    {
+        return EFAULT;
+    }
+    groups.len() as isize
+}
+
+pub fn sys_setgroups(size: usize, list: *const u32) -> isize {
+    info!("[sys_setgroups] size: {}", size);
+    if size > NGROUPS_MAX {
+        return EINVAL;
+    }
+    let mut groups = vec![0u32; size];
+    if size != 0
+        && copy_from_user_array(current_user_token(), list, groups.as_mut_ptr(), size).is_err()
+    {
+        return EFAULT;
+    }
+    match current_task().unwrap().cred.lock().setgroups(groups) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+/// 检查`capget`/`capset`头部的版本号，返回数据项个数。
+/// 版本号不支持时写回首选的版本号并返回`EINVAL`
+fn cap_validate_magic(token: usize, header: *mut CapUserHeader) -> Result<usize, isize> {
+    let mut hdr = get_from_user(token, header)?;
+    match hdr.version {
+        LINUX_CAPABILITY_VERSION_1 => Ok(1),
+        LINUX_CAPABILITY_VERSION_2 | LINUX_CAPABILITY_VERSION_3 => Ok(2),
+        _ => {
+            hdr.version = LINUX_CAPABILITY_VERSION_3;
+            copy_to_user(token, &hdr, header)?;
+            Err(EINVAL)
+        }
+    }
+}
+
+pub fn sys_capget(header: *mut CapUserHeader, data: *mut CapUserData) -> isize {
+    let token = current_user_token();
+    let count = match cap_validate_magic(token, header) {
+        Ok(count) => count,
+        // `data`为空时只是查询内核支持的版本号
+        Err(EINVAL) if data.is_null() => return SUCCESS,
+        Err(errno) => return errno,
+    };
+    if data.is_null() {
+        return SUCCESS;
+    }
+    let pid = match get_from_user(token, header) {
+        Ok(hdr) => hdr.pid,
+        Err(errno) => return errno,
+    };
+    let task = match pid {
+        pid if pid < 0 => return EINVAL,
+        0 => current_task().unwrap(),
+        pid => match find_task_by_pid(pid as usize) {
+            Some(task) => task,
+            None => return ESRCH,
+        },
+    };
+    let (effective, permitted, inheritable) = {
+        let cred = task.cred.lock();
+        (
+            cred.cap_effective.bits(),
+            cred.cap_permitted.bits(),
+            cred.cap_inheritable.bits(),
+        )
+    };
+    let kdata = [
+        CapUserData {
+            effective: effective as u32,
+            permitted: permitted as u32,
+            inheritable: inheritable as u32,
+        },
+        CapUserData {
+            effective: (effective >> 32) as u32,
+            permitted: (permitted >> 32) as u32,
+            inheritable: (inheritable >> 32) as u32,
+        },
+    ];
+    match copy_to_user_array(token, kdata.as_ptr(), data, count) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+/// 只能修改当前任务自己的能力
+pub fn sys_capset(header: *mut CapUserHeader, data: *const CapUserData) -> isize {
+    let token = current_user_token();
+    let count = match cap_validate_magic(token, header) {
+        Ok(count) => count,
+        Err(errno) => return errno,
+    };
+    let task = current_task().unwrap();
+    let pid = match get_from_user(token, header) {
+        Ok(hdr) => hdr.pid,
+        Err(errno) => return errno,
+    };
+    if pid != 0 && pid as usize != task.pid.0 {
+        return EPERM;
+    }
+    let mut kdata = [CapUserData::default(); 2];
+    if let Err(errno) = copy_from_user_array(token, data, kdata.as_mut_ptr(), count) {
+        return errno;
+    }
+    let combine =
+        |low: u32, high: u32| Capabilities::from_bits_truncate(low as u64 | ((high as u64) << 32));
+    info!("[sys_capset] data: {:?}", &kdata[..count]);
+    match task.cred.lock().capset(
+        combine(kdata[0].effective, kdata[1].effective),
+        combine(kdata[0].permitted, kdata[1].permitted),
+        combine(kdata[0].inheritable, kdata[1].inheritable),
+    ) {
+        Ok(()) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+/// `prctl()`的选项
+const PR_GET_KEEPCAPS: usize = 7;
+const PR_SET_KEEPCAPS: usize = 8;
+const PR_CAPBSET_READ: usize = 23;
+const PR_CAPBSET_DROP: usize = 24;
+
+/// 目前只支持能力相关的选项
+pub fn sys_prctl(option: usize, arg2: usize) -> isize {
+    info!("[sys_prctl] option: {}, arg2: {:X}", option, arg2);
+    let task = current_task().unwrap();
+    let mut cred = task.cred.lock();
+    match option {
+        PR_GET_KEEPCAPS => cred.keep_caps as isize,
+        PR_SET_KEEPCAPS => match arg2 {
+            0 | 1 => {
+                cred.keep_caps = arg2 == 1;
+                SUCCESS
+            }
+            _ => EINVAL,
+        },
+        PR_CAPBSET_READ => match Capabilities::from_cap(arg2) {
+            Some(cap) => cred.cap_bset.contains(cap) as isize,
+            None => EINVAL,
+        },
+        PR_CAPBSET_DROP => match Capabilities::from_cap(arg2) {
+            Some(cap) => match cred.capbset_drop(cap) {
+                Ok(()) => SUCCESS,
+                Err(errno) => errno,
+            },
+            None => EINVAL,
+        },
+        _ => {
+            warn!("[sys_prctl] unsupported option: {}", option);
+            EINVAL
+        }
+    }
+}
+
+/// `setpriority`/`getpriority`的`which`
+const PRIO_PROCESS: u32 = 0;
+
+/// 找到`PRIO_PROCESS`对应的任务，`who`为0时为当前任务
+fn find_prio_task(which: u32, who: usize) -> Result<Arc<TaskControlBlock>, isize> {
+    if which != PRIO_PROCESS {
+        warn!(
+            "[priority] only PRIO_PROCESS is supported, which: {}",
+            which
+        );
+        return Err(EINVAL);
+    }
+    match who {
+        0 => Ok(current_task().unwrap()),
+        who => find_task_by_pid(who).ok_or(ESRCH),
+    }
+}
+
+/// 返回`20 - nice`，避免与错误码混淆
+pub fn sys_getpriority(which: u32, who: usize) -> isize {
+    match find_prio_task(which, who) {
+        Ok(task) => 20 - task.acquire_inner_lock().nice,
+        Err(errno) => errno,
+    }
+}
+
+/// 修改其他用户的任务需要CAP_SYS_NICE，降低nice值（提高优先级）同样需要CAP_SYS_NICE
+pub fn sys_setpriority(which: u32, who: usize, niceval: i32) -> isize {
+    info!(
+        "[sys_setpriority] which: {}, who: {}, niceval: {}",
+        which, who, niceval
+    );
+    let task = match find_prio_task(which, who) {
+        Ok(task) => task,
+        Err(errno) => return errno,
+    };
+    let nice = (niceval as isize).max(-20).min(19);
+    let cred = current_task().unwrap().cred.lock().clone();
+    let sys_nice = cred.capable(Capabilities::CAP_SYS_NICE);
+    {
+        let target = task.cred.lock();
+        if !sys_nice && cred.euid != target.euid && cred.euid != target.ruid {
+            return EPERM;
+        }
+    }
+    let mut inner = task.acquire_inner_lock();
+    if nice < inner.nice && !sys_nice {
+        return EACCES;
+    }
+    inner.nice = nice;
+    SUCCESS
 }
 
 // Warning, we don't support this syscall in fact, task.setpgid() won't take effect for some reason
@@ -379,7 +753,7 @@ pub fn sys_sysinfo(info: *mut Sysinfo) -> isize {
                 procs as usize * LINUX_SYSINFO_LOADS_SCALE / SEC_5_MIN,
                 procs as usize * LINUX_SYSINFO_LOADS_SCALE / SEC_15_MIN,
             ],
-            totalram: crate::config::MEMORY_END - crate::config::MEMORY_START,
+            totalram: crate::hal::boot_info().total_ram(),
             freeram: crate::mm::unallocated_frames() * PAGE_SIZE,
             sharedram: UNIMPLEMENT,
             bufferram: UNIMPLEMENT,
@@ -598,9 +972,30 @@ pub fn sys_execve(
     // 获取当前工作目录的文件描述符
     let working_inode = &task.fs.lock().working_inode;
 
-    match working_inode.open(&path, OpenFlags::O_RDONLY, false) {
+    match working_inode.open(&path, OpenFlags::O_PATH, false) {
         // 检查打开的文件
         Ok(file) => {
+            // 只能执行有执行权限的普通文件
+            if !file.file.is_file() {
+                return EACCES;
+            }
+            if let Some(inode) = file.file.get_dirtree_node() {
+                if let Err(errno) = inode.permission(MAY_EXEC) {
+                    return errno;
+                }
+            }
+            // set-user-ID位使有效用户ID变为文件属主，
+            // set-group-ID位只有在组可执行时才生效
+            let stat = file.get_stat();
+            let mode = stat.get_mode();
+            let mut setuid = None;
+            let mut setgid = None;
+            if mode & StatMode::S_ISUID.bits() != 0 {
+                setuid = Some(stat.get_uid());
+            }
+            if mode & StatMode::S_ISGID.bits() != 0 && mode & StatMode::S_IXGRP.bits() != 0 {
+                setgid = Some(stat.get_gid());
+            }
             // 若文件大小小于4，则返回ENOEXEC
             // 即非可执行文件
             if file.get_size() < 4 {
@@ -616,6 +1011,9 @@ pub fn sys_execve(
                 // 脚本文件
                 // 用默认Shell即bash加载
                 b"#!" => {
+                    // 脚本的set-user-ID与set-group-ID位被忽略
+                    setuid = None;
+                    setgid = None;
                     let shell_file = working_inode
                         .open(DEFAULT_SHELL, OpenFlags::O_RDONLY, false)
                         .unwrap();
@@ -633,6 +1031,7 @@ pub fn sys_execve(
                     return errno;
                 };
             }
+            task.cred.lock().exec_setid(setuid, setgid);
             // should return 0 in success
             SUCCESS
         }
@@ -987,7 +1386,17 @@ pub fn sys_mmap(
         "[mmap] start:{:X}; len:{:X}; prot:{:?}; flags:{:?}; fd:{}; offset:{:X}",
         start, len, prot, flags, fd as isize, offset
     );
-    memory_set.mmap(start, len, prot, flags, fd, offset)
+    let result = memory_set.mmap(start, len, prot, flags, fd, offset);
+    drop(memory_set);
+    // 登记到文件的映射表，文件被截短时据此解除超出文件末尾的映射
+    if result >= 0 && !flags.contains(MapFlags::MAP_ANONYMOUS) {
+        if let Ok(file_descriptor) = task.files.lock().get_ref(fd) {
+            if let Some(mappings) = file_descriptor.file.mappings() {
+                mappings.add(&task.vm);
+            }
+        }
+    }
+    result
 }
 
 /// # Versions
@@ -1012,6 +1421,187 @@ pub fn sys_munmap(start: usize, len: usize) -> isize {
     }
 }
 
+const MREMAP_MAYMOVE: u32 = 1;
+const MREMAP_FIXED: u32 = 2;
+
+pub fn sys_mremap(
+    old_addr: usize,
+    old_len: usize,
+    new_len: usize,
+    flags: u32,
+    new_addr: usize,
+) -> isize {
+    info!(
+        "[sys_mremap] old_addr: {:X}, old_len: {:X}, new_len: {:X}, flags: {:X}, new_addr: {:X}",
+        old_addr, old_len, new_len, flags, new_addr
+    );
+    if !VirtAddr::from(old_addr).aligned()
+        || old_len == 0
+        || new_len == 0
+        || flags & !(MREMAP_MAYMOVE | MREMAP_FIXED) != 0
+    {
+        return EINVAL;
+    }
+    let fixed = if flags & MREMAP_FIXED != 0 {
+        if flags & MREMAP_MAYMOVE == 0 || !VirtAddr::from(new_addr).aligned() {
+            return EINVAL;
+        }
+        Some(new_addr)
+    } else {
+        None
+    };
+    let task = current_task().unwrap();
+    let result = task.vm.lock().mremap(
+        old_addr,
+        old_len,
+        new_len,
+        flags & MREMAP_MAYMOVE != 0,
+        fixed,
+    );
+    match result {
+        Ok(addr) => addr as isize,
+        Err(errno) => errno,
+    }
+}
+
+const MADV_NORMAL: usize = 0;
+const MADV_RANDOM: usize = 1;
+const MADV_SEQUENTIAL: usize = 2;
+const MADV_WILLNEED: usize = 3;
+const MADV_DONTNEED: usize = 4;
+const MADV_FREE: usize = 8;
+const MADV_HUGEPAGE: usize = 14;
+const MADV_NOHUGEPAGE: usize = 15;
+
+pub fn sys_madvise(addr: usize, len: usize, advice: usize) -> isize {
+    if !VirtAddr::from(addr).aligned() {
+        return EINVAL;
+    }
+    info!(
+        "[sys_madvise] addr: {:X}, len: {:X}, advice: {}",
+        addr, len, advice
+    );
+    let task = current_task().unwrap();
+    let ranges = match task.vm.lock().file_ranges(addr, len) {
+        Ok(ranges) => ranges,
+        Err(errno) => return errno,
+    };
+    let result = match advice {
+        // 预读文件映射对应的内容，预读期间不持有地址空间的锁
+        MADV_WILLNEED => {
+            for (file, offset, length) in ranges {
+                if let Err(errno) = file.fadvise(offset, length, POSIX_FADV_WILLNEED) {
+                    warn!("[sys_madvise] readahead failed, errno: {}", errno);
+                }
+            }
+            #[cfg(feature = "oom_handler")]
+            task.vm.lock().swap_in_range(addr, len);
+            Ok(())
+        }
+        MADV_DONTNEED | MADV_FREE => task.vm.lock().discard(addr, len, advice == MADV_FREE),
+        MADV_NORMAL | MADV_RANDOM | MADV_SEQUENTIAL => {
+            task.vm
+                .lock()
+                .set_sequential(addr, len, advice == MADV_SEQUENTIAL)
+        }
+        MADV_HUGEPAGE | MADV_NOHUGEPAGE => {
+            let huge_page = if advice == MADV_HUGEPAGE {
+                HugePage::Always
+            } else {
+                HugePage::Never
+            };
+            task.vm.lock().set_huge_page(addr, len, huge_page)
+        }
+        _ => {
+            warn!("[sys_madvise] advice {} is ignored", advice);
+            Ok(())
+        }
+    };
+    match result {
+        Ok(_) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+const MLOCK_ONFAULT: u32 = 1;
+
+const MCL_CURRENT: u32 = 1;
+const MCL_FUTURE: u32 = 2;
+const MCL_ONFAULT: u32 = 4;
+
+pub fn sys_mlock(addr: usize, len: usize) -> isize {
+    sys_mlock2(addr, len, 0)
+}
+
+/// `MLOCK_ONFAULT`时不立即分配页，页在缺页时分配并被锁定
+pub fn sys_mlock2(addr: usize, len: usize, flags: u32) -> isize {
+    if flags & !MLOCK_ONFAULT != 0 {
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let result = task.vm.lock().mlock(addr, len, flags & MLOCK_ONFAULT == 0);
+    match result {
+        Ok(_) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_munlock(addr: usize, len: usize) -> isize {
+    let task = current_task().unwrap();
+    let result = task.vm.lock().munlock(addr, len);
+    match result {
+        Ok(_) => SUCCESS,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_mlockall(flags: u32) -> isize {
+    if flags & !(MCL_CURRENT | MCL_FUTURE | MCL_ONFAULT) != 0
+        || flags & (MCL_CURRENT | MCL_FUTURE) == 0
+    {
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    task.vm.lock().mlockall(
+        flags & MCL_CURRENT != 0,
+        flags & MCL_FUTURE != 0,
+        flags & MCL_ONFAULT == 0,
+    );
+    SUCCESS
+}
+
+pub fn sys_munlockall() -> isize {
+    let task = current_task().unwrap();
+    task.vm.lock().munlockall();
+    SUCCESS
+}
+
+/// 向`vec`写入`[addr, addr + len)`中每页是否在内存中
+pub fn sys_mincore(addr: usize, len: usize, vec: *mut u8) -> isize {
+    if !VirtAddr::from(addr).aligned() {
+        return EINVAL;
+    }
+    let task = current_task().unwrap();
+    let residency = match task.vm.lock().mincore(addr, len) {
+        Ok(residency) => residency,
+        Err(errno) => return errno,
+    };
+    if residency.is_empty() {
+        return SUCCESS;
+    }
+    if copy_to_user_array(
+        current_user_token(),
+        residency.as_ptr(),
+        vec,
+        residency.len(),
+    )
+    .is_err()
+    {
+        return EFAULT;
+    }
+    SUCCESS
+}
+
 pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
     let task = current_task().unwrap();
     let result = task.vm.lock().mprotect(addr, len, prot);
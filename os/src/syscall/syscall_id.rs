@@ -1,9 +1,18 @@
 pub const SYSCALL_GETCWD: usize = 17;
-pub const SYSCALL_DUP3: usize = 20;
+pub const SYSCALL_EVENTFD2: usize = 19;
+pub const SYSCALL_EPOLL_CREATE1: usize = 20;
+pub const SYSCALL_EPOLL_CTL: usize = 21;
+pub const SYSCALL_EPOLL_PWAIT: usize = 22;
 pub const SYSCALL_DUP: usize = 23;
+/// 通用系统调用表中24号是dup3，`flags`为0时按dup2处理
 pub const SYSCALL_DUP2: usize = 24;
 pub const SYSCALL_FCNTL: usize = 25;
+pub const SYSCALL_INOTIFY_INIT1: usize = 26;
+pub const SYSCALL_INOTIFY_ADD_WATCH: usize = 27;
+pub const SYSCALL_INOTIFY_RM_WATCH: usize = 28;
 pub const SYSCALL_IOCTL: usize = 29;
+pub const SYSCALL_FLOCK: usize = 32;
+pub const SYSCALL_MKNODAT: usize = 33;
 pub const SYSCALL_MKDIRAT: usize = 34;
 pub const SYSCALL_UNLINKAT: usize = 35;
 pub const SYSCALL_LINKAT: usize = 37;
@@ -13,7 +22,10 @@ pub const SYSCALL_STATFS: usize = 43;
 pub const SYSCALL_FTRUNCATE: usize = 46;
 pub const SYSCALL_FACCESSAT: usize = 48;
 pub const SYSCALL_CHDIR: usize = 49;
+pub const SYSCALL_FCHMOD: usize = 52;
 pub const SYSCALL_FCHMODAT: usize = 53;
+pub const SYSCALL_FCHOWNAT: usize = 54;
+pub const SYSCALL_FCHOWN: usize = 55;
 pub const SYSCALL_OPENAT: usize = 56;
 pub const SYSCALL_CLOSE: usize = 57;
 pub const SYSCALL_PIPE2: usize = 59;
@@ -28,12 +40,20 @@ pub const SYSCALL_PWRITE: usize = 68;
 pub const SYSCALL_SENDFILE: usize = 71;
 pub const SYSCALL_PSELECT6: usize = 72;
 pub const SYSCALL_PPOLL: usize = 73;
+pub const SYSCALL_SIGNALFD4: usize = 74;
 pub const SYSCALL_SPLICE: usize = 76;
 pub const SYSCALL_READLINKAT: usize = 78;
 pub const SYSCALL_FSTATAT: usize = 79;
 pub const SYSCALL_FSTAT: usize = 80;
+pub const SYSCALL_SYNC: usize = 81;
 pub const SYSCALL_FSYNC: usize = 82;
+pub const SYSCALL_FDATASYNC: usize = 83;
+pub const SYSCALL_TIMERFD_CREATE: usize = 85;
+pub const SYSCALL_TIMERFD_SETTIME: usize = 86;
+pub const SYSCALL_TIMERFD_GETTIME: usize = 87;
 pub const SYSCALL_UTIMENSAT: usize = 88;
+pub const SYSCALL_CAPGET: usize = 90;
+pub const SYSCALL_CAPSET: usize = 91;
 pub const SYSCALL_EXIT: usize = 93;
 pub const SYSCALL_EXIT_GROUP: usize = 94;
 pub const SYSCALL_SET_TID_ADDRESS: usize = 96;
@@ -52,13 +72,28 @@ pub const SYSCALL_SIGACTION: usize = 134;
 pub const SYSCALL_SIGPROCMASK: usize = 135;
 pub const SYSCALL_SIGTIMEDWAIT: usize = 137;
 pub const SYSCALL_SIGRETURN: usize = 139;
+pub const SYSCALL_SETPRIORITY: usize = 140;
+pub const SYSCALL_GETPRIORITY: usize = 141;
+pub const SYSCALL_SETREGID: usize = 143;
+pub const SYSCALL_SETGID: usize = 144;
+pub const SYSCALL_SETREUID: usize = 145;
+pub const SYSCALL_SETUID: usize = 146;
+pub const SYSCALL_SETRESUID: usize = 147;
+pub const SYSCALL_GETRESUID: usize = 148;
+pub const SYSCALL_SETRESGID: usize = 149;
+pub const SYSCALL_GETRESGID: usize = 150;
+pub const SYSCALL_SETFSUID: usize = 151;
+pub const SYSCALL_SETFSGID: usize = 152;
 pub const SYSCALL_TIMES: usize = 153;
 pub const SYSCALL_SETPGID: usize = 154;
 pub const SYSCALL_GETPGID: usize = 155;
 pub const SYSCALL_SETSID: usize = 157;
+pub const SYSCALL_GETGROUPS: usize = 158;
+pub const SYSCALL_SETGROUPS: usize = 159;
 pub const SYSCALL_UNAME: usize = 160;
 pub const SYSCALL_GETRUSAGE: usize = 165;
 pub const SYSCALL_UMASK: usize = 166;
+pub const SYSCALL_PRCTL: usize = 167;
 pub const SYSCALL_GET_TIME_OF_DAY: usize = 169;
 pub const SYSCALL_GETPID: usize = 172;
 pub const SYSCALL_GETPPID: usize = 173;
@@ -68,6 +103,18 @@ pub const SYSCALL_GETGID: usize = 176;
 pub const SYSCALL_GETEGID: usize = 177;
 pub const SYSCALL_GETTID: usize = 178;
 pub const SYSCALL_SYSINFO: usize = 179;
+pub const SYSCALL_MSGGET: usize = 186;
+pub const SYSCALL_MSGCTL: usize = 187;
+pub const SYSCALL_MSGRCV: usize = 188;
+pub const SYSCALL_MSGSND: usize = 189;
+pub const SYSCALL_SEMGET: usize = 190;
+pub const SYSCALL_SEMCTL: usize = 191;
+pub const SYSCALL_SEMTIMEDOP: usize = 192;
+pub const SYSCALL_SEMOP: usize = 193;
+pub const SYSCALL_SHMGET: usize = 194;
+pub const SYSCALL_SHMCTL: usize = 195;
+pub const SYSCALL_SHMAT: usize = 196;
+pub const SYSCALL_SHMDT: usize = 197;
 pub const SYSCALL_SOCKET: usize = 198;
 pub const SYSCALL_SOCKETPAIR: usize = 199;
 pub const SYSCALL_BIND: usize = 200;
@@ -81,25 +128,40 @@ pub const SYSCALL_RECVFROM: usize = 207;
 pub const SYSCALL_SETSOCKOPT: usize = 208;
 pub const SYSCALL_GETSOCKOPT: usize = 209;
 pub const SYSCALL_SOCK_SHUTDOWN: usize = 210;
-pub const SYSCALL_SBRK: usize = 213;
+pub const SYSCALL_READAHEAD: usize = 213;
 pub const SYSCALL_BRK: usize = 214;
 pub const SYSCALL_MUNMAP: usize = 215;
+pub const SYSCALL_MREMAP: usize = 216;
 // Warning, we don't implement clone, we implement fork instead.
 pub const SYSCALL_CLONE: usize = 220; // fork is implemented as clone(SIGCHLD, 0) in lib.
 pub const SYSCALL_EXECVE: usize = 221;
 pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_FADVISE64: usize = 223;
+pub const SYSCALL_SWAPON: usize = 224;
+pub const SYSCALL_SWAPOFF: usize = 225;
 pub const SYSCALL_MPROTECT: usize = 226;
 pub const SYSCALL_MSYNC: usize = 227;
+pub const SYSCALL_MLOCK: usize = 228;
+pub const SYSCALL_MUNLOCK: usize = 229;
+pub const SYSCALL_MLOCKALL: usize = 230;
+pub const SYSCALL_MUNLOCKALL: usize = 231;
+pub const SYSCALL_MINCORE: usize = 232;
+pub const SYSCALL_MADVISE: usize = 233;
 pub const SYSCALL_WAIT4: usize = 260; // wait is implemented as wait4(pid, status, options, 0) in pub lib.
 pub const SYSCALL_PRLIMIT: usize = 261;
+pub const SYSCALL_SYNCFS: usize = 267;
 pub const SYSCALL_RENAMEAT2: usize = 276;
 pub const SYSCALL_GETRANDOM: usize = 278;
+pub const SYSCALL_MEMFD_CREATE: usize = 279;
 pub const SYSCALL_MEMBARRIER: usize = 283;
+pub const SYSCALL_MLOCK2: usize = 284;
 pub const SYSCALL_STATX: usize = 291;
 pub const SYSCALL_FACCESSAT2: usize = 439;
 // Not standard POSIX sys_call
 pub const SYSCALL_LS: usize = 500;
 pub const SYSCALL_SHUTDOWN: usize = 501;
 pub const SYSCALL_CLEAR: usize = 502;
+// 213是readahead，sbrk移到自定义编号
+pub const SYSCALL_SBRK: usize = 503;
 pub const SYSCALL_OPEN: usize = 506; //where?
 pub const SYSCALL_GET_TIME: usize = 1690; //you mean get time of day by 169?
@@ -0,0 +1,104 @@
+use super::current_task;
+
+bitflags! {
+    /// 能力集合，每一位对应Linux中的一个`CAP_*`
+    pub struct Capabilities: u64 {
+        const CAP_CHOWN             = 1 << 0;
+        const CAP_DAC_OVERRIDE      = 1 << 1;
+        const CAP_DAC_READ_SEARCH   = 1 << 2;
+        const CAP_FOWNER            = 1 << 3;
+        const CAP_FSETID            = 1 << 4;
+        const CAP_KILL              = 1 << 5;
+        const CAP_SETGID            = 1 << 6;
+        const CAP_SETUID            = 1 << 7;
+        const CAP_SETPCAP           = 1 << 8;
+        const CAP_LINUX_IMMUTABLE   = 1 << 9;
+        const CAP_NET_BIND_SERVICE  = 1 << 10;
+        const CAP_NET_BROADCAST     = 1 << 11;
+        const CAP_NET_ADMIN         = 1 << 12;
+        const CAP_NET_RAW           = 1 << 13;
+        const CAP_IPC_LOCK          = 1 << 14;
+        const CAP_IPC_OWNER         = 1 << 15;
+        const CAP_SYS_MODULE        = 1 << 16;
+        const CAP_SYS_RAWIO         = 1 << 17;
+        const CAP_SYS_CHROOT        = 1 << 18;
+        const CAP_SYS_PTRACE        = 1 << 19;
+        const CAP_SYS_PACCT         = 1 << 20;
+        const CAP_SYS_ADMIN         = 1 << 21;
+        const CAP_SYS_BOOT          = 1 << 22;
+        const CAP_SYS_NICE          = 1 << 23;
+        const CAP_SYS_RESOURCE      = 1 << 24;
+        const CAP_SYS_TIME          = 1 << 25;
+        const CAP_SYS_TTY_CONFIG    = 1 << 26;
+        const CAP_MKNOD             = 1 << 27;
+        const CAP_LEASE             = 1 << 28;
+        const CAP_AUDIT_WRITE       = 1 << 29;
+        const CAP_AUDIT_CONTROL     = 1 << 30;
+        const CAP_SETFCAP           = 1 << 31;
+        const CAP_MAC_OVERRIDE      = 1 << 32;
+        const CAP_MAC_ADMIN         = 1 << 33;
+        const CAP_SYSLOG            = 1 << 34;
+        const CAP_WAKE_ALARM        = 1 << 35;
+        const CAP_BLOCK_SUSPEND     = 1 << 36;
+        const CAP_AUDIT_READ        = 1 << 37;
+        const CAP_PERFMON           = 1 << 38;
+        const CAP_BPF               = 1 << 39;
+        const CAP_CHECKPOINT_RESTORE = 1 << 40;
+    }
+}
+
+/// 最大的能力编号
+pub const CAP_LAST_CAP: usize = 40;
+
+impl Capabilities {
+    /// 由能力编号得到对应的能力，编号超出范围时返回`None`
+    pub fn from_cap(cap: usize) -> Option<Self> {
+        if cap > CAP_LAST_CAP {
+            None
+        } else {
+            Some(Self::from_bits_truncate(1 << cap))
+        }
+    }
+
+    /// 文件系统用户ID在0与非0之间切换时随之增减的能力
+    pub fn fs_mask() -> Self {
+        Self::CAP_CHOWN
+            | Self::CAP_DAC_OVERRIDE
+            | Self::CAP_DAC_READ_SEARCH
+            | Self::CAP_FOWNER
+            | Self::CAP_FSETID
+            | Self::CAP_LINUX_IMMUTABLE
+            | Self::CAP_MKNOD
+            | Self::CAP_MAC_OVERRIDE
+    }
+}
+
+/// 当前任务的有效能力集是否包含`cap`，内核自身（没有当前任务）拥有全部能力
+pub fn capable(cap: Capabilities) -> bool {
+    match current_task() {
+        Some(task) => task.cred.lock().capable(cap),
+        None => true,
+    }
+}
+
+/// `capget`/`capset`的版本号，版本1只有32位，版本2和3有64位
+pub const LINUX_CAPABILITY_VERSION_1: u32 = 0x19980330;
+pub const LINUX_CAPABILITY_VERSION_2: u32 = 0x20071026;
+pub const LINUX_CAPABILITY_VERSION_3: u32 = 0x20080522;
+
+/// `capget`/`capset`的`cap_user_header_t`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CapUserHeader {
+    pub version: u32,
+    pub pid: i32,
+}
+
+/// `capget`/`capset`的`cap_user_data_t`，64位的集合分为低32位和高32位两项
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CapUserData {
+    pub effective: u32,
+    pub permitted: u32,
+    pub inheritable: u32,
+}
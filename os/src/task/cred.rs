@@ -0,0 +1,339 @@
+use super::capability::Capabilities;
+use crate::syscall::errno::{EINVAL, EPERM};
+use alloc::vec::Vec;
+
+/// 附加组数量上限
+pub const NGROUPS_MAX: usize = 65536;
+
+#[derive(Clone, Debug)]
+/// 任务的身份凭证
+/// 同一线程组的线程共享同一份凭证
+pub struct Credentials {
+    /// 实际用户ID
+    pub ruid: u32,
+    /// 有效用户ID
+    pub euid: u32,
+    /// 保存的设置用户ID
+    pub suid: u32,
+    /// 文件系统用户ID，访问文件时用于权限检查
+    pub fsuid: u32,
+    /// 实际组ID
+    pub rgid: u32,
+    /// 有效组ID
+    pub egid: u32,
+    /// 保存的设置组ID
+    pub sgid: u32,
+    /// 文件系统组ID
+    pub fsgid: u32,
+    /// 附加组
+    pub groups: Vec<u32>,
+    /// 执行新程序时可以继承的能力
+    pub cap_inheritable: Capabilities,
+    /// 允许的能力，是有效能力集的上限
+    pub cap_permitted: Capabilities,
+    /// 有效的能力，权限检查时使用
+    pub cap_effective: Capabilities,
+    /// 能力边界集，执行新程序后允许的能力不会超出这一集合
+    pub cap_bset: Capabilities,
+    /// `PR_SET_KEEPCAPS`，所有用户ID都变为非0时保留允许的能力
+    pub keep_caps: bool,
+}
+
+impl Credentials {
+    /// root用户的凭证，用于initproc
+    pub fn root() -> Self {
+        Self {
+            ruid: 0,
+            euid: 0,
+            suid: 0,
+            fsuid: 0,
+            rgid: 0,
+            egid: 0,
+            sgid: 0,
+            fsgid: 0,
+            groups: Vec::new(),
+            cap_inheritable: Capabilities::empty(),
+            cap_permitted: Capabilities::all(),
+            cap_effective: Capabilities::all(),
+            cap_bset: Capabilities::all(),
+            keep_caps: false,
+        }
+    }
+    /// `gid`是否为文件系统组ID或附加组之一
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.fsgid == gid || self.groups.contains(&gid)
+    }
+    /// `uid`是否为实际、有效或保存的用户ID之一，非特权进程只能切换到这些ID
+    fn owns_uid(&self, uid: u32) -> bool {
+        uid == self.ruid || uid == self.euid || uid == self.suid
+    }
+    fn owns_gid(&self, gid: u32) -> bool {
+        gid == self.rgid || gid == self.egid || gid == self.sgid
+    }
+    /// 有效能力集是否包含`cap`
+    pub fn capable(&self, cap: Capabilities) -> bool {
+        self.cap_effective.contains(cap)
+    }
+    /// faccessat()用实际ID代替文件系统ID进行检查
+    pub fn real(&self) -> Self {
+        Self {
+            fsuid: self.ruid,
+            fsgid: self.rgid,
+            ..self.clone()
+        }
+    }
+
+    /// 用户ID变化后调整能力，与Linux的`cap_emulate_setxuid`一致：
+    /// 有效用户ID离开0时清空有效能力，回到0时恢复为允许的能力；
+    /// 三个用户ID都离开0时（没有设置`keep_caps`）同时清空允许的能力
+    fn fixup_uid_caps(&mut self, old_ruid: u32, old_euid: u32, old_suid: u32) {
+        if (old_ruid == 0 || old_euid == 0 || old_suid == 0)
+            && self.ruid != 0
+            && self.euid != 0
+            && self.suid != 0
+            && !self.keep_caps
+        {
+            self.cap_permitted = Capabilities::empty();
+            self.cap_effective = Capabilities::empty();
+        }
+        if old_euid == 0 && self.euid != 0 {
+            self.cap_effective = Capabilities::empty();
+        }
+        if old_euid != 0 && self.euid == 0 {
+            self.cap_effective = self.cap_permitted;
+        }
+    }
+
+    /// 修改用户ID，调整能力后文件系统用户ID跟随有效用户ID
+    fn change_uid(&mut self, ruid: u32, euid: u32, suid: u32) {
+        let (old_ruid, old_euid, old_suid) = (self.ruid, self.euid, self.suid);
+        self.ruid = ruid;
+        self.euid = euid;
+        self.suid = suid;
+        self.fixup_uid_caps(old_ruid, old_euid, old_suid);
+        self.fsuid = self.euid;
+    }
+
+    pub fn setuid(&mut self, uid: u32) -> Result<(), isize> {
+        if self.capable(Capabilities::CAP_SETUID) {
+            self.change_uid(uid, uid, uid);
+        } else if uid == self.ruid || uid == self.suid {
+            self.change_uid(self.ruid, uid, self.suid);
+        } else {
+            return Err(EPERM);
+        }
+        Ok(())
+    }
+
+    pub fn setgid(&mut self, gid: u32) -> Result<(), isize> {
+        if self.capable(Capabilities::CAP_SETGID) {
+            self.rgid = gid;
+            self.sgid = gid;
+        } else if gid != self.rgid && gid != self.sgid {
+            return Err(EPERM);
+        }
+        self.egid = gid;
+        self.fsgid = gid;
+        Ok(())
+    }
+
+    /// `None`表示不修改（系统调用中传入-1）
+    pub fn setreuid(&mut self, ruid: Option<u32>, euid: Option<u32>) -> Result<(), isize> {
+        if !self.capable(Capabilities::CAP_SETUID) {
+            if ruid.map_or(false, |ruid| ruid != self.ruid && ruid != self.euid) {
+                return Err(EPERM);
+            }
+            if euid.map_or(false, |euid| !self.owns_uid(euid)) {
+                return Err(EPERM);
+            }
+        }
+        let new_ruid = ruid.unwrap_or(self.ruid);
+        let new_euid = euid.unwrap_or(self.euid);
+        // 修改了实际用户ID，或有效用户ID被设为与原实际用户ID不同的值时，更新保存的设置用户ID
+        let new_suid = if ruid.is_some() || euid.map_or(false, |euid| euid != self.ruid) {
+            new_euid
+        } else {
+            self.suid
+        };
+        self.change_uid(new_ruid, new_euid, new_suid);
+        Ok(())
+    }
+
+    pub fn setregid(&mut self, rgid: Option<u32>, egid: Option<u32>) -> Result<(), isize> {
+        let old_rgid = self.rgid;
+        if !self.capable(Capabilities::CAP_SETGID) {
+            if rgid.map_or(false, |rgid| rgid != self.rgid && rgid != self.egid) {
+                return Err(EPERM);
+            }
+            if egid.map_or(false, |egid| !self.owns_gid(egid)) {
+                return Err(EPERM);
+            }
+        }
+        if let Some(rgid) = rgid {
+            self.rgid = rgid;
+        }
+        if let Some(egid) = egid {
+            self.egid = egid;
+        }
+        if rgid.is_some() || egid.map_or(false, |egid| egid != old_rgid) {
+            self.sgid = self.egid;
+        }
+        self.fsgid = self.egid;
+        Ok(())
+    }
+
+    pub fn setresuid(
+        &mut self,
+        ruid: Option<u32>,
+        euid: Option<u32>,
+        suid: Option<u32>,
+    ) -> Result<(), isize> {
+        if !self.capable(Capabilities::CAP_SETUID)
+            && [ruid, euid, suid]
+                .iter()
+                .flatten()
+                .any(|id| !self.owns_uid(*id))
+        {
+            return Err(EPERM);
+        }
+        self.change_uid(
+            ruid.unwrap_or(self.ruid),
+            euid.unwrap_or(self.euid),
+            suid.unwrap_or(self.suid),
+        );
+        Ok(())
+    }
+
+    pub fn setresgid(
+        &mut self,
+        rgid: Option<u32>,
+        egid: Option<u32>,
+        sgid: Option<u32>,
+    ) -> Result<(), isize> {
+        if !self.capable(Capabilities::CAP_SETGID)
+            && [rgid, egid, sgid]
+                .iter()
+                .flatten()
+                .any(|id| !self.owns_gid(*id))
+        {
+            return Err(EPERM);
+        }
+        if let Some(rgid) = rgid {
+            self.rgid = rgid;
+        }
+        if let Some(egid) = egid {
+            self.egid = egid;
+        }
+        if let Some(sgid) = sgid {
+            self.sgid = sgid;
+        }
+        self.fsgid = self.egid;
+        Ok(())
+    }
+
+    /// 返回原文件系统用户ID，没有权限时不做修改。
+    /// 文件系统用户ID离开0时去掉文件相关的有效能力，回到0时从允许的能力中恢复
+    pub fn setfsuid(&mut self, fsuid: u32) -> u32 {
+        let old = self.fsuid;
+        if self.capable(Capabilities::CAP_SETUID) || self.owns_uid(fsuid) || fsuid == self.fsuid {
+            self.fsuid = fsuid;
+            if old == 0 && fsuid != 0 {
+                self.cap_effective -= Capabilities::fs_mask();
+            } else if old != 0 && fsuid == 0 {
+                self.cap_effective |= self.cap_permitted & Capabilities::fs_mask();
+            }
+        }
+        old
+    }
+
+    pub fn setfsgid(&mut self, fsgid: u32) -> u32 {
+        let old = self.fsgid;
+        if self.capable(Capabilities::CAP_SETGID) || self.owns_gid(fsgid) || fsgid == self.fsgid {
+            self.fsgid = fsgid;
+        }
+        old
+    }
+
+    pub fn setgroups(&mut self, groups: Vec<u32>) -> Result<(), isize> {
+        if !self.capable(Capabilities::CAP_SETGID) {
+            return Err(EPERM);
+        }
+        if groups.len() > NGROUPS_MAX {
+            return Err(EINVAL);
+        }
+        self.groups = groups;
+        Ok(())
+    }
+
+    /// 执行设置了set-user-ID/set-group-ID位的程序时切换有效ID，
+    /// 之后保存的设置ID与文件系统ID都跟随有效ID。
+    /// 文件没有附加能力，root执行时允许的能力为继承集与边界集之并，
+    /// 有效用户为root时全部生效；非root执行后不再拥有任何能力
+    pub fn exec_setid(&mut self, uid: Option<u32>, gid: Option<u32>) {
+        if let Some(uid) = uid {
+            self.euid = uid;
+        }
+        if let Some(gid) = gid {
+            self.egid = gid;
+        }
+        self.suid = self.euid;
+        self.fsuid = self.euid;
+        self.sgid = self.egid;
+        self.fsgid = self.egid;
+        if self.euid == 0 || self.ruid == 0 {
+            self.cap_permitted = self.cap_inheritable | self.cap_bset;
+        } else {
+            self.cap_permitted = Capabilities::empty();
+        }
+        if self.euid == 0 {
+            self.cap_effective = self.cap_permitted;
+        } else {
+            self.cap_effective = Capabilities::empty();
+        }
+        self.keep_caps = false;
+    }
+
+    /// 发送信号的权限：拥有CAP_KILL，或者发送者的实际或有效用户ID
+    /// 与接收者的实际或保存的设置用户ID相同
+    pub fn may_signal(&self, target: &Self) -> bool {
+        self.capable(Capabilities::CAP_KILL)
+            || self.euid == target.ruid
+            || self.euid == target.suid
+            || self.ruid == target.ruid
+            || self.ruid == target.suid
+    }
+
+    /// `capset`修改能力集，继承集不能超出原继承集与允许集之并（有CAP_SETPCAP时不受此限）
+    /// 及边界集，允许集只能缩小，有效集不能超出新的允许集
+    pub fn capset(
+        &mut self,
+        effective: Capabilities,
+        permitted: Capabilities,
+        inheritable: Capabilities,
+    ) -> Result<(), isize> {
+        let inheritable_limit = if self.capable(Capabilities::CAP_SETPCAP) {
+            Capabilities::all()
+        } else {
+            self.cap_inheritable | self.cap_permitted
+        };
+        if !(inheritable_limit & (self.cap_inheritable | self.cap_bset)).contains(inheritable)
+            || !self.cap_permitted.contains(permitted)
+            || !permitted.contains(effective)
+        {
+            return Err(EPERM);
+        }
+        self.cap_effective = effective;
+        self.cap_permitted = permitted;
+        self.cap_inheritable = inheritable;
+        Ok(())
+    }
+
+    /// `PR_CAPBSET_DROP`，需要CAP_SETPCAP
+    pub fn capbset_drop(&mut self, cap: Capabilities) -> Result<(), isize> {
+        if !self.capable(Capabilities::CAP_SETPCAP) {
+            return Err(EPERM);
+        }
+        self.cap_bset -= cap;
+        Ok(())
+    }
+}
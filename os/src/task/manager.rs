@@ -4,9 +4,6 @@
 */
 use core::cmp::Ordering;
 
-#[cfg(feature = "oom_handler")]
-use crate::config::SYSTEM_TASK_LIMIT;
-#[cfg(feature = "oom_handler")]
 use alloc::vec::Vec;
 
 use crate::timer::TimeSpec;
@@ -17,75 +14,17 @@ use alloc::sync::{Arc, Weak};
 use lazy_static::*;
 use spin::Mutex;
 
-#[cfg(feature = "oom_handler")]
-/// 任务的激活状态跟踪器
-pub struct ActiveTracker {
-    /// 存储激活状态的位图
-    bitmap: Vec<u64>,
-}
-
-#[cfg(feature = "oom_handler")]
-#[allow(unused)]
-impl ActiveTracker {
-    /// 默认大小为128
-    pub const DEFAULT_SIZE: usize = SYSTEM_TASK_LIMIT;
-    /// 构造函数
-    pub fn new() -> Self {
-        // 计算位图长度，向上取整
-        let len = (Self::DEFAULT_SIZE + 63) / 64;
-        // 初始化位图
-        let mut bitmap = Vec::with_capacity(len);
-        // 位图全部置0
-        bitmap.resize(len, 0);
-        Self { bitmap }
-    }
-    /// 检查制定pid的任务是否处于激活状态
-    pub fn check_active(&self, pid: usize) -> bool {
-        (self.bitmap[pid / 64] & (1 << (pid % 64))) != 0
-    }
-    /// 检查制定pid的任务是否处于非激活状态
-    pub fn check_inactive(&self, pid: usize) -> bool {
-        (self.bitmap[pid / 64] & (1 << (pid % 64))) == 0
-    }
-    /// 标记指定pid的任务为激活状态
-    pub fn mark_active(&mut self, pid: usize) {
-        self.bitmap[pid / 64] |= 1 << (pid % 64)
-    }
-    /// 标记指定pid的任务为非激活状态
-    pub fn mark_inactive(&mut self, pid: usize) {
-        self.bitmap[pid / 64] &= !(1 << (pid % 64))
-    }
-}
-
-#[cfg(feature = "oom_handler")]
 /// 任务管理器
 pub struct TaskManager {
     /// 一个双端队列，用于存储就绪态任务
     pub ready_queue: VecDeque<Arc<TaskControlBlock>>,
     /// 一个双端队列，用于存储可中断状态任务
     pub interruptible_queue: VecDeque<Arc<TaskControlBlock>>,
-    /// 任务激活状态跟踪器，用于跟踪任务的激活状态，并在OOM时释放内存
-    pub active_tracker: ActiveTracker,
-}
-
-#[cfg(not(feature = "oom_handler"))]
-pub struct TaskManager {
-    pub ready_queue: VecDeque<Arc<TaskControlBlock>>,
-    pub interruptible_queue: VecDeque<Arc<TaskControlBlock>>,
 }
 
 /// 简单的FIFO调度器
 impl TaskManager {
-    #[cfg(feature = "oom_handler")]
     /// 构造函数
-    pub fn new() -> Self {
-        Self {
-            ready_queue: VecDeque::new(),
-            interruptible_queue: VecDeque::new(),
-            active_tracker: ActiveTracker::new(),
-        }
-    }
-    #[cfg(not(feature = "oom_handler"))]
     pub fn new() -> Self {
         Self {
             ready_queue: VecDeque::new(),
@@ -97,18 +36,6 @@ impl TaskManager {
         self.ready_queue.push_back(task);
     }
     /// 从就绪队列中取出一个任务
-    #[cfg(feature = "oom_handler")]
-    pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        match self.ready_queue.pop_front() {
-            Some(task) => {
-                // 标记任务为激活状态
-                self.active_tracker.mark_active(task.pid.0);
-                Some(task)
-            }
-            None => None,
-        }
-    }
-    #[cfg(not(feature = "oom_handler"))]
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
         self.ready_queue.pop_front()
     }
@@ -209,58 +136,6 @@ pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     TASK_MANAGER.lock().fetch()
 }
 
-/// 尝试释放所有任务的内存空间，直到释放`req`页。
-#[cfg(feature = "oom_handler")]
-pub fn do_oom(req: usize) -> Result<(), ()> {
-    let mut manager = TASK_MANAGER.lock();
-    let mut cleaned = Vec::with_capacity(16);
-    let mut total_released = 0;
-    for task in manager
-        .interruptible_queue
-        .iter()
-        .filter(|task| manager.active_tracker.check_active(task.pid.0))
-    {
-        let released = task.vm.lock().do_deep_clean();
-        log::warn!("deep clean on task: {}, released: {}", task.tgid, released);
-        cleaned.push(task.pid.0);
-        total_released += released;
-        if total_released >= req {
-            while let Some(pid) = cleaned.pop() {
-                manager.active_tracker.mark_inactive(pid)
-            }
-            return Ok(());
-        };
-    }
-    for task in manager
-        .ready_queue
-        .iter()
-        .rev()
-        .filter(|task| manager.active_tracker.check_active(task.pid.0))
-    {
-        let released = task.vm.lock().do_shallow_clean();
-        log::warn!(
-            "shallow clean on task: {}, released: {}",
-            task.tgid,
-            released
-        );
-        cleaned.push(task.pid.0);
-        total_released += released;
-        if total_released >= req {
-            while let Some(pid) = cleaned.pop() {
-                manager.active_tracker.mark_inactive(pid)
-            }
-            return Ok(());
-        };
-    }
-    Err(())
-}
-
-#[cfg(not(feature = "oom_handler"))]
-#[allow(unused)]
-pub fn do_oom() {
-    // do nothing
-}
-
 /// 这个函数会将`task`加入到`interruptible_queue`，
 /// 但不会从`ready_queue`中删除。
 /// 所以需要确保`task`不会出现在`ready_queue`中。
@@ -414,10 +289,22 @@ impl WaitQueue {
     }
 }
 
-/// 表示一个等待超时的任务
+/// 定时器回调，参数为当前时间。
+/// 在释放所有调度相关的锁后才被调用，因此回调中可以唤醒任务或重新设置定时器
+pub type TimerCallback = dyn Fn(TimeSpec) + Send + Sync;
+
+/// 超时后要唤醒的对象
+enum TimeoutTarget {
+    /// 唤醒处于可中断睡眠的任务
+    Task(Weak<TaskControlBlock>),
+    /// 调用定时器回调，所有者释放回调即相当于取消定时器
+    Timer(Weak<TimerCallback>),
+}
+
+/// 表示一个等待超时的任务或定时器
 pub struct TimeoutWaiter {
-    /// 任务的弱引用
-    task: Weak<TaskControlBlock>,
+    /// 超时后要唤醒的对象
+    target: TimeoutTarget,
     /// 任务超时时间
     timeout: TimeSpec,
 }
@@ -460,10 +347,22 @@ impl TimeoutWaitQueue {
     /// 这个函数会将一个`task`添加到`WaitQueue`但是**不会**阻塞这个任务，
     /// 如果想要阻塞一个`task`，使用`block_current_and_run_next()`函数
     pub fn add_task(&mut self, task: Weak<TaskControlBlock>, timeout: TimeSpec) {
-        self.inner.push(TimeoutWaiter { task, timeout });
+        self.inner.push(TimeoutWaiter {
+            target: TimeoutTarget::Task(task),
+            timeout,
+        });
+    }
+    /// 添加一个在`timeout`到期的定时器，到期时`wake_expired()`将其返回给调用者执行
+    pub fn add_timer(&mut self, timer: Weak<TimerCallback>, timeout: TimeSpec) {
+        self.inner.push(TimeoutWaiter {
+            target: TimeoutTarget::Timer(timer),
+            timeout,
+        });
     }
     /// 唤醒所有超时的任务
-    pub fn wake_expired(&mut self, now: TimeSpec) {
+    /// 返回到期的定时器回调，调用者应在释放锁后执行它们
+    pub fn wake_expired(&mut self, now: TimeSpec) -> Vec<Arc<TimerCallback>> {
+        let mut timers = Vec::new();
         // 获取任务管理器
         let mut manager = TASK_MANAGER.lock();
         // 循环处理超时任务
@@ -480,8 +379,18 @@ impl TimeoutWaitQueue {
                 break;
             // 唤醒超时任务
             } else {
+                let task = match waiter.target {
+                    TimeoutTarget::Task(ref task) => task,
+                    TimeoutTarget::Timer(ref timer) => {
+                        // 定时器已被取消则忽略
+                        if let Some(timer) = timer.upgrade() {
+                            timers.push(timer);
+                        }
+                        continue;
+                    }
+                };
                 // 将弱引用升级为强引用
-                match waiter.task.upgrade() {
+                match task.upgrade() {
                     Some(task) => {
                         // 获取内部锁
                         let mut inner = task.acquire_inner_lock();
@@ -508,6 +417,7 @@ impl TimeoutWaitQueue {
                 }
             }
         }
+        timers
     }
     #[allow(unused)]
     // debug use only
@@ -531,7 +441,16 @@ pub fn wait_with_timeout(task: Weak<TaskControlBlock>, timeout: TimeSpec) {
 
 /// 唤醒全局超时等待队列中所有已超时的任务
 pub fn do_wake_expired() {
+    let now = crate::timer::TimeSpec::now();
+    let timers = TIMEOUT_WAITQUEUE.lock().wake_expired(now);
+    for timer in timers {
+        timer(now);
+    }
+}
+
+/// 设置一个在`timeout`到期的定时器，`timer`的所有强引用被释放后定时器自动失效
+pub fn add_timer(timer: &Arc<TimerCallback>, timeout: TimeSpec) {
     TIMEOUT_WAITQUEUE
         .lock()
-        .wake_expired(crate::timer::TimeSpec::now());
+        .add_timer(Arc::downgrade(timer), timeout)
 }
@@ -1,4 +1,6 @@
+pub mod capability;
 mod context;
+mod cred;
 mod elf;
 mod manager;
 pub mod pid;
@@ -9,18 +11,21 @@ pub mod threads;
 
 use crate::hal::__switch;
 use crate::{
-    fs::{OpenFlags, ROOT_FD},
+    fs::{locks::locks_remove_posix, OpenFlags, ROOT_FD},
+    ipc::exit_ipc,
     mm::translated_refmut,
 };
 use alloc::{collections::VecDeque, sync::Arc};
+pub use capability::{capable, Capabilities};
 pub use context::TaskContext;
+pub use cred::{Credentials, NGROUPS_MAX};
 pub use elf::{load_elf_interp, AuxvEntry, AuxvType, ELFInfo};
 use lazy_static::*;
 use log::warn;
 use manager::fetch_task;
 pub use manager::{
-    add_task, do_oom, do_wake_expired, find_task_by_pid, find_task_by_tgid, procs_count,
-    sleep_interruptible, wait_with_timeout, wake_interruptible,
+    add_task, add_timer, do_wake_expired, find_task_by_pid, find_task_by_tgid, procs_count,
+    sleep_interruptible, wait_with_timeout, wake_interruptible, TimerCallback,
 };
 // pub use pid::RecycleAllocator;
 pub use pid::{pid_alloc, trap_cx_bottom_from_tid, ustack_bottom_from_tid, PidHandle};
@@ -148,6 +153,14 @@ pub fn do_exit(task: Arc<TaskControlBlock>, exit_code: u32) {
     }
     drop(inner);
     // **** release current PCB lock
+    // release POSIX record locks held by the process when its main thread exits
+    if task.pid.0 == task.tgid {
+        for file_descriptor in task.files.lock().iter().flatten() {
+            locks_remove_posix(&file_descriptor.file, task.tgid);
+        }
+        // undo semaphore operations with SEM_UNDO
+        exit_ipc(task.tgid);
+    }
     // drop task manually to maintain rc correctly
     log::info!("[do_exit] Pid {} exited with {}", task.pid.0, exit_code);
 }
@@ -74,12 +74,20 @@ pub fn run_tasks() {
                 // 调用__switch 函数(汇编)切换任务
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
+            // 任务让出处理器后回到这里。系统一直繁忙时空闲分支不会执行，
+            // 由时钟中断标记的周期性回写在每次任务切换之后进行
+            crate::fs::writeback::writeback_run();
         } else {
             // 如果没有任务
             // 释放处理器的锁
             drop(processor);
             // 没有就绪的任务，尝试唤醒一些任务
             do_wake_expired();
+            // 空闲时顺便回写一批到期的脏数据、回收内存，并检查没有中断的设备是否就绪
+            crate::fs::writeback::writeback_tick();
+            crate::fs::writeback::writeback_run();
+            crate::mm::kswapd_tick();
+            crate::fs::poll::poll_tick();
         }
     }
 }
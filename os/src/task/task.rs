@@ -2,6 +2,7 @@ use super::manager::TASK_MANAGER;
 use super::pid::RecycleAllocator;
 use super::signal::*;
 use super::threads::Futex;
+use super::Credentials;
 use super::TaskContext;
 use super::{pid_alloc, PidHandle};
 use crate::config::MMAP_BASE;
@@ -13,7 +14,7 @@ use crate::hal::TrapImpl;
 use crate::hal::{kstack_alloc, KernelStack};
 use crate::hal::{trap_handler, TrapContext};
 use crate::mm::PageTableImpl;
-use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::mm::{register_mm, MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
 use crate::net::SocketTable;
 use crate::syscall::CloneFlags;
 use crate::timer::{ITimerVal, TimeVal};
@@ -30,6 +31,8 @@ use spin::{Mutex, MutexGuard};
 pub struct FsStatus {
     /// 当前工作目录的文件描述符
     pub working_inode: Arc<FileDescriptor>,
+    /// 创建文件时屏蔽的权限位
+    pub umask: u32,
 }
 
 /// 任务控制块
@@ -67,6 +70,8 @@ pub struct TaskControlBlock {
     pub sighand: Arc<Mutex<Vec<Option<Box<SigAction>>>>>,
     /// 快速用户空间互斥锁
     pub futex: Arc<Mutex<Futex>>,
+    /// 身份凭证
+    pub cred: Arc<Mutex<Credentials>>,
 }
 
 /// 任务控制块内部状态
@@ -103,6 +108,8 @@ pub struct TaskControlBlockInner {
     pub clock: ProcClock,
     /// 定时器
     pub timer: [ITimerVal; 3],
+    /// nice值，范围为-20到19，目前只记录而不影响调度
+    pub nice: isize,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -374,6 +381,7 @@ impl TaskControlBlock {
                         .open(".", OpenFlags::O_RDONLY | OpenFlags::O_DIRECTORY, true)
                         .unwrap(),
                 ),
+                umask: 0o022,
             })),
             vm: Arc::new(Mutex::new(memory_set)),
             sighand: Arc::new(Mutex::new({
@@ -382,6 +390,7 @@ impl TaskControlBlock {
                 vec
             })),
             futex: Arc::new(Mutex::new(Futex::new())),
+            cred: Arc::new(Mutex::new(Credentials::root())),
             inner: Mutex::new(TaskControlBlockInner {
                 sigmask: Signals::empty(),
                 sigpending: Signals::empty(),
@@ -399,8 +408,10 @@ impl TaskControlBlock {
                 rusage: Rusage::new(),
                 clock: ProcClock::new(),
                 timer: [ITimerVal::new(); 3],
+                nice: 0,
             }),
         };
+        register_mm(&task_control_block.vm);
         // 准备用户空间的陷阱上下文
         let trap_cx = task_control_block.acquire_inner_lock().get_trap_cx();
         // 初始化陷阱上下文
@@ -481,8 +492,12 @@ impl TaskControlBlock {
             }
             None => (),
         });
-        // 替换内存映射
-        *self.vm.lock() = memory_set;
+        // 替换内存映射，旧地址空间中共享映射写入的数据先交给页缓存
+        let mut vm = self.vm.lock();
+        vm.recycle_data_pages();
+        *vm = memory_set;
+        drop(vm);
+        register_mm(&self.vm);
         // 清空信号处理函数表
         for sigact in self.sighand.lock().iter_mut() {
             *sigact = None;
@@ -519,9 +534,16 @@ impl TaskControlBlock {
         } else {
             // 复制地址空间（进程）
             crate::mm::frame_reserve(16);
-            Arc::new(Mutex::new(MemorySet::from_existing_user(
+            let vm = Arc::new(Mutex::new(MemorySet::from_existing_user(
                 &mut self.vm.lock(),
-            )))
+            )));
+            register_mm(&vm);
+            // 复制来的文件映射同样需要在文件截短时被处理
+            let file_mappings = vm.lock().file_mappings();
+            for mappings in file_mappings {
+                mappings.add(&vm);
+            }
+            vm
         };
 
         // 复制线程ID分配器
@@ -596,9 +618,15 @@ impl TaskControlBlock {
                 // maybe should do clone here?
                 Arc::new(Mutex::new(Futex::new()))
             },
+            cred: if flags.contains(CloneFlags::CLONE_THREAD) {
+                self.cred.clone()
+            } else {
+                Arc::new(Mutex::new(self.cred.lock().clone()))
+            },
             inner: Mutex::new(TaskControlBlockInner {
                 // inherited
                 pgid: parent_inner.pgid,
+                nice: parent_inner.nice,
                 heap_bottom: parent_inner.heap_bottom,
                 heap_pt: parent_inner.heap_pt,
                 // clone
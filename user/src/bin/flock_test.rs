@@ -0,0 +1,149 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fcntl, flock, fork, getpid, openat, unlinkat, waitpid, write};
+
+const AT_FDCWD: isize = -100;
+const O_RDWR: u32 = 0o2;
+const O_CREAT: u32 = 0o100;
+const O_TRUNC: u32 = 0o1000;
+
+const LOCK_SH: usize = 1;
+const LOCK_EX: usize = 2;
+const LOCK_NB: usize = 4;
+const LOCK_UN: usize = 8;
+
+const F_GETLK: u32 = 5;
+const F_SETLK: u32 = 6;
+const F_OFD_SETLK: u32 = 37;
+const F_RDLCK: i16 = 0;
+const F_WRLCK: i16 = 1;
+const F_UNLCK: i16 = 2;
+const SEEK_SET: i16 = 0;
+
+const EAGAIN: isize = -11;
+
+const PATH: &str = "flock_test.tmp\0";
+
+#[repr(C)]
+struct Flock {
+    l_type: i16,
+    l_whence: i16,
+    l_start: i64,
+    l_len: i64,
+    l_pid: i32,
+}
+
+impl Flock {
+    fn new(l_type: i16, l_start: i64, l_len: i64) -> Self {
+        Self {
+            l_type,
+            l_whence: SEEK_SET,
+            l_start,
+            l_len,
+            l_pid: 0,
+        }
+    }
+}
+
+fn open_file() -> usize {
+    let fd = openat(AT_FDCWD, PATH, O_RDWR | O_CREAT, 0o644);
+    assert!(fd >= 0, "open failed: {}", fd);
+    fd as usize
+}
+
+/// 同一进程中两次open得到的两个打开文件之间，flock锁互相冲突
+fn test_flock() {
+    let fd1 = open_file();
+    let fd2 = open_file();
+    assert_eq!(flock(fd1, LOCK_EX), 0);
+    assert_eq!(flock(fd2, LOCK_EX | LOCK_NB), EAGAIN);
+    assert_eq!(flock(fd2, LOCK_SH | LOCK_NB), EAGAIN);
+    // 锁可以原地降级为共享锁
+    assert_eq!(flock(fd1, LOCK_SH), 0);
+    assert_eq!(flock(fd2, LOCK_SH | LOCK_NB), 0);
+    assert_eq!(flock(fd1, LOCK_EX | LOCK_NB), EAGAIN);
+    assert_eq!(flock(fd2, LOCK_UN), 0);
+    assert_eq!(flock(fd1, LOCK_EX | LOCK_NB), 0);
+    // 关闭打开文件时释放其上的锁
+    close(fd1);
+    assert_eq!(flock(fd2, LOCK_EX | LOCK_NB), 0);
+    // 子进程继承的是同一个打开文件，不会与父进程冲突；自己打开的则会
+    let pid = fork();
+    if pid == 0 {
+        if flock(fd2, LOCK_EX | LOCK_NB) != 0 {
+            exit(1);
+        }
+        let fd = open_file();
+        if flock(fd, LOCK_SH | LOCK_NB) != EAGAIN {
+            exit(2);
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    close(fd2);
+    println!("flock: ok");
+}
+
+/// POSIX记录锁属于进程，F_GETLK报告冲突的锁及其持有者
+fn test_posix_lock() {
+    let fd = open_file();
+    assert_eq!(write(fd, &[0u8; 64]), 64);
+    let lock = Flock::new(F_WRLCK, 0, 16);
+    assert_eq!(fcntl(fd, F_SETLK, &lock as *const _ as usize), 0);
+    let parent = getpid() as i32;
+    let pid = fork();
+    if pid == 0 {
+        let mut test = Flock::new(F_RDLCK, 8, 16);
+        if fcntl(fd, F_GETLK, &mut test as *mut _ as usize) != 0 {
+            exit(1);
+        }
+        if test.l_type != F_WRLCK || test.l_start != 0 || test.l_len != 16 || test.l_pid != parent {
+            exit(2);
+        }
+        // 与父进程的写锁重叠
+        if fcntl(fd, F_SETLK, &test as *const _ as usize) != EAGAIN {
+            exit(3);
+        }
+        // 不重叠的部分可以加锁
+        let other = Flock::new(F_WRLCK, 16, 16);
+        if fcntl(fd, F_SETLK, &other as *const _ as usize) != 0 {
+            exit(4);
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    // 子进程退出后它的锁被释放
+    let mut test = Flock::new(F_WRLCK, 16, 16);
+    assert_eq!(fcntl(fd, F_GETLK, &mut test as *mut _ as usize), 0);
+    assert_eq!(test.l_type, F_UNLCK);
+    // 同一进程内的另一个打开文件不冲突，但OFD锁按打开文件区分
+    let fd2 = open_file();
+    let ofd = Flock::new(F_WRLCK, 0, 16);
+    assert_eq!(fcntl(fd2, F_SETLK, &ofd as *const _ as usize), 0);
+    assert_eq!(fcntl(fd2, F_OFD_SETLK, &ofd as *const _ as usize), EAGAIN);
+    // 关闭进程的任意一个描述符都会释放进程的POSIX锁
+    close(fd2);
+    assert_eq!(fcntl(fd, F_OFD_SETLK, &ofd as *const _ as usize), 0);
+    close(fd);
+    println!("fcntl locks: ok");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = openat(AT_FDCWD, PATH, O_RDWR | O_CREAT | O_TRUNC, 0o644);
+    assert!(fd >= 0, "create failed: {}", fd);
+    close(fd as usize);
+    test_flock();
+    test_posix_lock();
+    unlinkat(AT_FDCWD, PATH, 0);
+    println!("flock_test passed!");
+    0
+}
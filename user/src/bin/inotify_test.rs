@@ -0,0 +1,144 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, inotify_add_watch, inotify_init1, inotify_rm_watch, openat, read, unlinkat, write,
+};
+
+const AT_FDCWD: isize = -100;
+const O_RDONLY: u32 = 0;
+const O_WRONLY: u32 = 0o1;
+const O_CREAT: u32 = 0o100;
+
+const IN_NONBLOCK: usize = 0o4000;
+const IN_MODIFY: u32 = 0x2;
+const IN_CLOSE_WRITE: u32 = 0x8;
+const IN_CLOSE_NOWRITE: u32 = 0x10;
+const IN_OPEN: u32 = 0x20;
+const IN_CREATE: u32 = 0x100;
+const IN_DELETE: u32 = 0x200;
+const IN_IGNORED: u32 = 0x8000;
+const IN_ONLYDIR: u32 = 0x01000000;
+
+const EAGAIN: isize = -11;
+const EINVAL: isize = -22;
+const ENOTDIR: isize = -20;
+
+const NAME: &str = "inotify_test.tmp";
+const PATH: &str = "inotify_test.tmp\0";
+
+/// 读出的一个事件
+#[derive(Clone, Copy)]
+struct Event {
+    wd: i32,
+    mask: u32,
+    name_len: usize,
+    name: [u8; 64],
+}
+
+impl Event {
+    fn name(&self) -> &str {
+        let len = self.name[..self.name_len]
+            .iter()
+            .position(|c| *c == 0)
+            .unwrap_or(self.name_len);
+        core::str::from_utf8(&self.name[..len]).unwrap()
+    }
+}
+
+/// 把`read`得到的字节流拆成事件，返回事件数
+fn parse(buf: &[u8], events: &mut [Event]) -> usize {
+    let mut pos = 0;
+    let mut count = 0;
+    while pos + 16 <= buf.len() && count < events.len() {
+        let field = |offset: usize| {
+            u32::from_ne_bytes([
+                buf[pos + offset],
+                buf[pos + offset + 1],
+                buf[pos + offset + 2],
+                buf[pos + offset + 3],
+            ])
+        };
+        let event = &mut events[count];
+        event.wd = field(0) as i32;
+        event.mask = field(4);
+        event.name_len = field(12) as usize;
+        event.name[..event.name_len].copy_from_slice(&buf[pos + 16..pos + 16 + event.name_len]);
+        pos += 16 + event.name_len;
+        count += 1;
+    }
+    assert_eq!(pos, buf.len());
+    count
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = inotify_init1(IN_NONBLOCK);
+    assert!(fd >= 0, "inotify_init1 failed: {}", fd);
+    let fd = fd as usize;
+    assert_eq!(inotify_init1(1), EINVAL);
+    let wd = inotify_add_watch(
+        fd,
+        ".\0",
+        IN_CREATE | IN_MODIFY | IN_CLOSE_WRITE | IN_DELETE,
+    );
+    assert!(wd > 0, "inotify_add_watch failed: {}", wd);
+    // 没有事件时非阻塞读返回EAGAIN
+    let mut buf = [0u8; 1024];
+    assert_eq!(read(fd, &mut buf), EAGAIN);
+
+    let file = openat(AT_FDCWD, PATH, O_WRONLY | O_CREAT, 0o644);
+    assert!(file >= 0, "create failed: {}", file);
+    assert_eq!(write(file as usize, b"hello"), 5);
+    // 连续相同的事件被合并
+    assert_eq!(write(file as usize, b"world"), 5);
+    close(file as usize);
+    // 只读打开不在监视的事件中
+    let file = openat(AT_FDCWD, PATH, O_RDONLY, 0);
+    close(file as usize);
+    assert_eq!(unlinkat(AT_FDCWD, PATH, 0), 0);
+
+    let len = read(fd, &mut buf);
+    assert!(len > 0, "read events failed: {}", len);
+    let mut events = [Event {
+        wd: 0,
+        mask: 0,
+        name_len: 0,
+        name: [0; 64],
+    }; 8];
+    let count = parse(&buf[..len as usize], &mut events);
+    let expected = [IN_CREATE, IN_MODIFY, IN_CLOSE_WRITE, IN_DELETE];
+    assert_eq!(count, expected.len());
+    for (event, mask) in events.iter().zip(expected.iter()) {
+        assert_eq!(event.wd, wd as i32);
+        assert_eq!(event.mask, *mask);
+        assert_eq!(event.name(), NAME);
+    }
+    assert!(events[..count]
+        .iter()
+        .all(|event| event.mask & (IN_OPEN | IN_CLOSE_NOWRITE) == 0));
+
+    // 同一路径再次添加时更新已有的监视项
+    assert_eq!(inotify_add_watch(fd, ".\0", IN_CREATE), wd);
+    let file = openat(AT_FDCWD, PATH, O_WRONLY | O_CREAT, 0o644);
+    close(file as usize);
+    assert_eq!(read(fd, &mut buf) as usize, 16 + events[0].name_len);
+    // IN_ONLYDIR要求目标是目录
+    assert_eq!(inotify_add_watch(fd, PATH, IN_MODIFY | IN_ONLYDIR), ENOTDIR);
+    unlinkat(AT_FDCWD, PATH, 0);
+
+    // 移除监视项时产生IN_IGNORED
+    assert_eq!(inotify_rm_watch(fd, wd as i32), 0);
+    assert_eq!(inotify_rm_watch(fd, wd as i32), EINVAL);
+    let len = read(fd, &mut buf);
+    assert_eq!(len, 16);
+    let count = parse(&buf[..16], &mut events);
+    assert_eq!(count, 1);
+    assert_eq!(events[0].mask, IN_IGNORED);
+    close(fd);
+    println!("inotify_test passed!");
+    0
+}
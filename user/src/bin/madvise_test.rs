@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{madvise, mincore, mmap, munmap};
+
+const PROT_READ: usize = 0x1;
+const PROT_WRITE: usize = 0x2;
+const MAP_PRIVATE: usize = 0x2;
+const MAP_ANONYMOUS: usize = 0x20;
+
+const MADV_NORMAL: usize = 0;
+const MADV_RANDOM: usize = 1;
+const MADV_SEQUENTIAL: usize = 2;
+const MADV_WILLNEED: usize = 3;
+const MADV_DONTNEED: usize = 4;
+const MADV_HUGEPAGE: usize = 14;
+const MADV_NOHUGEPAGE: usize = 15;
+
+const EINVAL: isize = -22;
+const ENOMEM: isize = -12;
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 4;
+
+fn residency(addr: usize) -> [u8; PAGES] {
+    let mut vec = [0xffu8; PAGES];
+    assert_eq!(mincore(addr, PAGES * PAGE_SIZE, &mut vec), 0);
+    vec
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let addr = mmap(
+        0,
+        PAGES * PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+    assert!(addr > 0, "mmap failed: {}", addr);
+    let addr = addr as usize;
+    // 匿名映射在第一次访问时才分配页
+    assert_eq!(residency(addr), [0, 0, 0, 0]);
+    unsafe {
+        *(addr as *mut u8) = 1;
+        *((addr + 2 * PAGE_SIZE) as *mut u8) = 3;
+    }
+    assert_eq!(residency(addr), [1, 0, 1, 0]);
+    // 地址未按页对齐或范围内有未映射的页
+    let mut vec = [0u8; PAGES + 1];
+    assert_eq!(mincore(addr + 1, PAGE_SIZE, &mut vec), EINVAL);
+    assert_eq!(mincore(addr, (PAGES + 1) * PAGE_SIZE, &mut vec), ENOMEM);
+
+    // MADV_DONTNEED丢弃私有匿名页，再次访问时读出零
+    assert_eq!(madvise(addr, PAGES * PAGE_SIZE, MADV_DONTNEED), 0);
+    assert_eq!(residency(addr), [0, 0, 0, 0]);
+    assert_eq!(unsafe { *(addr as *const u8) }, 0);
+    assert_eq!(unsafe { *((addr + 2 * PAGE_SIZE) as *const u8) }, 0);
+
+    // 只是建议的advice不改变内容
+    unsafe { *((addr + PAGE_SIZE) as *mut u8) = 2 };
+    for advice in [
+        MADV_WILLNEED,
+        MADV_SEQUENTIAL,
+        MADV_RANDOM,
+        MADV_NORMAL,
+        MADV_HUGEPAGE,
+        MADV_NOHUGEPAGE,
+    ] {
+        assert_eq!(madvise(addr, PAGES * PAGE_SIZE, advice), 0);
+    }
+    assert_eq!(unsafe { *((addr + PAGE_SIZE) as *const u8) }, 2);
+    // 只对一部分范围设置大页建议时区域被切开，之后仍能整体解除映射
+    assert_eq!(madvise(addr + PAGE_SIZE, PAGE_SIZE, MADV_HUGEPAGE), 0);
+    assert_eq!(madvise(addr + 1, PAGE_SIZE, MADV_DONTNEED), EINVAL);
+    assert_eq!(munmap(addr, PAGES * PAGE_SIZE), 0);
+    assert_eq!(madvise(addr, PAGES * PAGE_SIZE, MADV_DONTNEED), ENOMEM);
+    println!("madvise_test passed!");
+    0
+}
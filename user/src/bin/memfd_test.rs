@@ -0,0 +1,154 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fcntl, fork, ftruncate, lseek, memfd_create, mmap, mprotect, munmap, read,
+    waitpid, write,
+};
+
+const MFD_ALLOW_SEALING: usize = 2;
+const F_ADD_SEALS: u32 = 1033;
+const F_GET_SEALS: u32 = 1034;
+const F_SEAL_SEAL: usize = 0x1;
+const F_SEAL_SHRINK: usize = 0x2;
+const F_SEAL_GROW: usize = 0x4;
+const F_SEAL_WRITE: usize = 0x8;
+
+const PROT_READ: usize = 0x1;
+const PROT_WRITE: usize = 0x2;
+const MAP_SHARED: usize = 0x1;
+const MAP_PRIVATE: usize = 0x2;
+
+const EPERM: isize = -1;
+const EACCES: isize = -13;
+const EBUSY: isize = -16;
+const PAGE_SIZE: usize = 4096;
+
+/// 共享映射与fork后的子进程看到同一组页
+fn test_shared_mapping() {
+    let fd = memfd_create("memfd_test\0", 0);
+    assert!(fd >= 0, "memfd_create failed: {}", fd);
+    let fd = fd as usize;
+    assert_eq!(ftruncate(fd, PAGE_SIZE), 0);
+    let addr = mmap(
+        0,
+        PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED,
+        fd as isize,
+        0,
+    );
+    assert!(addr > 0, "mmap failed: {}", addr);
+    let page = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, PAGE_SIZE) };
+    page[0] = 1;
+    let pid = fork();
+    if pid == 0 {
+        if page[0] != 1 {
+            exit(1);
+        }
+        page[1] = 2;
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    assert_eq!(page[1], 2);
+    // 通过write看到的也是同一组页
+    let mut buf = [0u8; 2];
+    assert_eq!(lseek(fd, 0, 0), 0);
+    assert_eq!(read(fd, &mut buf), 2);
+    assert_eq!(buf, [1, 2]);
+    // 不允许封印的memfd创建时就带有F_SEAL_SEAL
+    assert_eq!(fcntl(fd, F_GET_SEALS, 0), F_SEAL_SEAL as isize);
+    assert_eq!(fcntl(fd, F_ADD_SEALS, F_SEAL_WRITE), EPERM);
+    munmap(addr as usize, PAGE_SIZE);
+    close(fd);
+    println!("shared mapping: ok");
+}
+
+fn test_seals() {
+    let fd = memfd_create("memfd_seal\0", MFD_ALLOW_SEALING);
+    assert!(fd >= 0, "memfd_create failed: {}", fd);
+    let fd = fd as usize;
+    assert_eq!(fcntl(fd, F_GET_SEALS, 0), 0);
+    assert_eq!(write(fd, &[7u8; PAGE_SIZE]), PAGE_SIZE as isize);
+
+    assert_eq!(fcntl(fd, F_ADD_SEALS, F_SEAL_SHRINK), 0);
+    assert_eq!(ftruncate(fd, 0), EPERM);
+    assert_eq!(ftruncate(fd, 2 * PAGE_SIZE), 0);
+
+    assert_eq!(fcntl(fd, F_ADD_SEALS, F_SEAL_GROW), 0);
+    assert_eq!(ftruncate(fd, 3 * PAGE_SIZE), EPERM);
+    assert_eq!(lseek(fd, 0, 2), 2 * PAGE_SIZE as isize);
+    assert_eq!(write(fd, &[1u8]), EPERM);
+    // 不改变大小的写入仍然允许
+    assert_eq!(lseek(fd, 0, 0), 0);
+    assert_eq!(write(fd, &[1u8]), 1);
+
+    // 存在可写的共享映射时不能添加F_SEAL_WRITE，私有映射不受影响
+    let shared = mmap(
+        0,
+        PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED,
+        fd as isize,
+        0,
+    );
+    assert!(shared > 0, "mmap failed: {}", shared);
+    let private = mmap(
+        0,
+        PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE,
+        fd as isize,
+        0,
+    );
+    assert!(private > 0, "mmap failed: {}", private);
+    assert_eq!(fcntl(fd, F_ADD_SEALS, F_SEAL_WRITE), EBUSY);
+    assert_eq!(munmap(shared as usize, PAGE_SIZE), 0);
+    assert_eq!(fcntl(fd, F_ADD_SEALS, F_SEAL_WRITE), 0);
+    munmap(private as usize, PAGE_SIZE);
+
+    assert_eq!(lseek(fd, 0, 0), 0);
+    assert_eq!(write(fd, &[2u8]), EPERM);
+    assert_eq!(
+        mmap(
+            0,
+            PAGE_SIZE,
+            PROT_READ | PROT_WRITE,
+            MAP_SHARED,
+            fd as isize,
+            0
+        ),
+        EPERM
+    );
+    // 只读的共享映射可以建立，但不能再通过mprotect加上写权限
+    let shared = mmap(0, PAGE_SIZE, PROT_READ, MAP_SHARED, fd as isize, 0);
+    assert!(shared > 0, "mmap failed: {}", shared);
+    assert_eq!(unsafe { *(shared as *const u8) }, 1);
+    assert_eq!(
+        mprotect(shared as usize, PAGE_SIZE, PROT_READ | PROT_WRITE),
+        EACCES
+    );
+    munmap(shared as usize, PAGE_SIZE);
+
+    assert_eq!(fcntl(fd, F_ADD_SEALS, F_SEAL_SEAL), 0);
+    assert_eq!(
+        fcntl(fd, F_GET_SEALS, 0),
+        (F_SEAL_SEAL | F_SEAL_SHRINK | F_SEAL_GROW | F_SEAL_WRITE) as isize
+    );
+    assert_eq!(fcntl(fd, F_ADD_SEALS, F_SEAL_SHRINK), EPERM);
+    close(fd);
+    println!("seals: ok");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    test_shared_mapping();
+    test_seals();
+    println!("memfd_test passed!");
+    0
+}
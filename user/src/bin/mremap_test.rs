@@ -0,0 +1,125 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mincore, mmap, mremap, munmap};
+
+const PROT_READ: usize = 0x1;
+const PROT_WRITE: usize = 0x2;
+const MAP_PRIVATE: usize = 0x2;
+const MAP_ANONYMOUS: usize = 0x20;
+const MREMAP_MAYMOVE: u32 = 1;
+const MREMAP_FIXED: u32 = 2;
+
+const EINVAL: isize = -22;
+const ENOMEM: isize = -12;
+const PAGE_SIZE: usize = 4096;
+
+fn map(pages: usize, prot: usize) -> usize {
+    let addr = mmap(
+        0,
+        pages * PAGE_SIZE,
+        prot,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+    assert!(addr > 0, "mmap failed: {}", addr);
+    addr as usize
+}
+
+/// 在每一页的开头写入页号
+fn fill(addr: usize, pages: usize) {
+    for page in 0..pages {
+        unsafe { *((addr + page * PAGE_SIZE) as *mut usize) = page + 1 };
+    }
+}
+
+fn check(addr: usize, pages: usize) {
+    for page in 0..pages {
+        assert_eq!(
+            unsafe { *((addr + page * PAGE_SIZE) as *const usize) },
+            page + 1
+        );
+    }
+}
+
+fn test_in_place() {
+    let addr = map(2, PROT_READ | PROT_WRITE);
+    fill(addr, 2);
+    // 后面没有其他映射时原地扩大，新的页读出零
+    assert_eq!(
+        mremap(addr, 2 * PAGE_SIZE, 4 * PAGE_SIZE, 0, 0),
+        addr as isize
+    );
+    check(addr, 2);
+    assert_eq!(unsafe { *((addr + 3 * PAGE_SIZE) as *const usize) }, 0);
+    fill(addr, 4);
+    // 缩小时解除多出的部分
+    assert_eq!(mremap(addr, 4 * PAGE_SIZE, PAGE_SIZE, 0, 0), addr as isize);
+    check(addr, 1);
+    let mut vec = [0u8; 3];
+    assert_eq!(mincore(addr + PAGE_SIZE, 3 * PAGE_SIZE, &mut vec), ENOMEM);
+    munmap(addr, PAGE_SIZE);
+    println!("in place: ok");
+}
+
+fn test_move() {
+    let addr = map(2, PROT_READ | PROT_WRITE);
+    fill(addr, 2);
+    // 权限不同的映射紧跟在后面，无法原地扩大
+    let blocker = map(1, PROT_READ);
+    assert_eq!(blocker, addr + 2 * PAGE_SIZE);
+    assert_eq!(mremap(addr, 2 * PAGE_SIZE, 3 * PAGE_SIZE, 0, 0), ENOMEM);
+    let moved = mremap(addr, 2 * PAGE_SIZE, 3 * PAGE_SIZE, MREMAP_MAYMOVE, 0);
+    assert!(moved > 0, "mremap failed: {}", moved);
+    let moved = moved as usize;
+    assert_ne!(moved, addr);
+    check(moved, 2);
+    // 旧的位置不再映射
+    let mut vec = [0u8; 2];
+    assert_eq!(mincore(addr, 2 * PAGE_SIZE, &mut vec), ENOMEM);
+    fill(moved, 3);
+    munmap(blocker, PAGE_SIZE);
+
+    // MREMAP_FIXED把映射搬到指定位置，替换掉那里原有的映射
+    let target = map(3, PROT_READ);
+    assert_eq!(
+        mremap(moved, 3 * PAGE_SIZE, 3 * PAGE_SIZE, MREMAP_FIXED, target),
+        EINVAL
+    );
+    assert_eq!(
+        mremap(
+            moved,
+            3 * PAGE_SIZE,
+            3 * PAGE_SIZE,
+            MREMAP_MAYMOVE | MREMAP_FIXED,
+            target
+        ),
+        target as isize
+    );
+    check(target, 3);
+    // 新旧范围重叠
+    assert_eq!(
+        mremap(
+            target,
+            3 * PAGE_SIZE,
+            3 * PAGE_SIZE,
+            MREMAP_MAYMOVE | MREMAP_FIXED,
+            target + PAGE_SIZE
+        ),
+        EINVAL
+    );
+    munmap(target, 3 * PAGE_SIZE);
+    println!("move: ok");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    test_in_place();
+    test_move();
+    println!("mremap_test passed!");
+    0
+}
@@ -0,0 +1,100 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fsync, ftruncate, lseek, openat, read, swapoff, swapon, unlinkat, write};
+
+const AT_FDCWD: isize = -100;
+const O_RDONLY: u32 = 0;
+const O_RDWR: u32 = 0o2;
+const O_CREAT: u32 = 0o100;
+const O_TRUNC: u32 = 0o1000;
+
+const SWAP_FLAG_PREFER: u32 = 0x8000;
+
+const EINVAL: isize = -22;
+const EBUSY: isize = -16;
+const ETXTBSY: isize = -26;
+const PAGE_SIZE: usize = 4096;
+/// 交换文件的页数，第一页是头部
+const SWAP_PAGES: usize = 64;
+
+const PATH: &str = "/swapon_test.swap\0";
+const NAME: &str = "/swapon_test.swap";
+
+/// 按mkswap的格式写交换区头部，`valid`为假时不写签名
+fn write_header(fd: usize, valid: bool) {
+    let mut header = [0u8; PAGE_SIZE];
+    // version
+    header[1024..1028].copy_from_slice(&1u32.to_le_bytes());
+    // last_page
+    header[1028..1032].copy_from_slice(&((SWAP_PAGES - 1) as u32).to_le_bytes());
+    if valid {
+        header[PAGE_SIZE - 10..].copy_from_slice(b"SWAPSPACE2");
+    }
+    assert_eq!(lseek(fd, 0, 0), 0);
+    assert_eq!(write(fd, &header), PAGE_SIZE as isize);
+    assert_eq!(fsync(fd), 0);
+}
+
+/// `/proc/swaps`中是否列出了测试用的交换文件
+fn listed() -> bool {
+    let fd = openat(AT_FDCWD, "/proc/swaps\0", O_RDONLY, 0);
+    assert!(fd >= 0, "open /proc/swaps failed: {}", fd);
+    let mut buf = [0u8; 1024];
+    let len = read(fd as usize, &mut buf);
+    close(fd as usize);
+    assert!(len > 0);
+    let text = core::str::from_utf8(&buf[..len as usize]).unwrap();
+    text.lines().any(|line| line.starts_with(NAME))
+}
+
+/// 需要内核启用`swap`特性，并以root身份运行
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = openat(AT_FDCWD, PATH, O_RDWR | O_CREAT | O_TRUNC, 0o600);
+    assert!(fd >= 0, "create failed: {}", fd);
+    let fd = fd as usize;
+    // 交换文件不能有空洞，先把全部内容写出来
+    let zero = [0u8; PAGE_SIZE];
+    for _ in 0..SWAP_PAGES {
+        assert_eq!(write(fd, &zero), PAGE_SIZE as isize);
+    }
+
+    write_header(fd, false);
+    assert_eq!(swapon(PATH, 0), EINVAL);
+    write_header(fd, true);
+    assert_eq!(swapon(PATH, 0x80000000), EINVAL);
+
+    assert_eq!(swapon(PATH, 0), 0);
+    assert!(listed());
+    assert_eq!(swapon(PATH, 0), EBUSY);
+    // 启用期间交换文件被固定，swapon之前打开的描述符也不能修改它
+    assert_eq!(openat(AT_FDCWD, PATH, O_RDWR, 0), ETXTBSY);
+    assert_eq!(openat(AT_FDCWD, PATH, O_RDONLY | O_TRUNC, 0), ETXTBSY);
+    assert_eq!(write(fd, &[0u8]), ETXTBSY);
+    assert_eq!(ftruncate(fd, PAGE_SIZE), ETXTBSY);
+    assert_eq!(unlinkat(AT_FDCWD, PATH, 0), EBUSY);
+    assert_eq!(swapoff(PATH), 0);
+    assert!(!listed());
+    assert_eq!(swapoff(PATH), EINVAL);
+    // swapoff之后恢复可写
+    assert_eq!(lseek(fd, 0, 2), (SWAP_PAGES * PAGE_SIZE) as isize);
+    assert_eq!(write(fd, &[0u8]), 1);
+    assert_eq!(ftruncate(fd, SWAP_PAGES * PAGE_SIZE), 0);
+
+    // 指定优先级
+    assert_eq!(swapon(PATH, SWAP_FLAG_PREFER | 5), 0);
+    assert_eq!(swapoff(PATH), 0);
+
+    // 目录和设备都不能作为交换区
+    assert_eq!(swapon("/\0", 0), EINVAL);
+    assert_eq!(swapon("/dev/null\0", 0), EINVAL);
+
+    close(fd);
+    unlinkat(AT_FDCWD, PATH, 0);
+    println!("swapon_test passed!");
+    0
+}
@@ -0,0 +1,184 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, msgctl, msgget, msgrcv, msgsnd, semctl, semget, semop, shmat, shmctl, shmdt,
+    shmget, waitpid, Sembuf,
+};
+
+const IPC_PRIVATE: i32 = 0;
+const IPC_CREAT: u32 = 0o1000;
+const IPC_EXCL: u32 = 0o2000;
+const IPC_NOWAIT: u32 = 0o4000;
+const IPC_RMID: u32 = 0;
+const GETVAL: u32 = 12;
+const SETVAL: u32 = 16;
+
+const EEXIST: isize = -17;
+const EAGAIN: isize = -11;
+const EINVAL: isize = -22;
+const ENOMSG: isize = -42;
+const E2BIG: isize = -7;
+
+const SHM_SIZE: usize = 8192;
+/// 测试用的键，IPC_EXCL时要求不存在
+const KEY: i32 = 0x4e50_5543;
+
+fn test_shm() {
+    // 删除上次运行失败时留下的段
+    let stale = shmget(KEY, 0, 0);
+    if stale >= 0 {
+        shmctl(stale as usize, IPC_RMID, 0);
+    }
+    let id = shmget(KEY, SHM_SIZE, IPC_CREAT | IPC_EXCL | 0o600);
+    assert!(id >= 0, "shmget failed: {}", id);
+    let id = id as usize;
+    assert_eq!(shmget(KEY, SHM_SIZE, IPC_CREAT | IPC_EXCL | 0o600), EEXIST);
+    assert_eq!(shmget(KEY, SHM_SIZE, 0), id as isize);
+    // 已有的段不能按更大的大小取得
+    assert_eq!(shmget(KEY, SHM_SIZE * 2, 0), EINVAL);
+    let addr = shmat(id, 0, 0);
+    assert!(addr > 0, "shmat failed: {}", addr);
+    let seg = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, SHM_SIZE) };
+    seg[0] = 0x5a;
+    seg[SHM_SIZE - 1] = 0xa5;
+    let pid = fork();
+    if pid == 0 {
+        // fork继承的映射与重新attach的映射都指向同一个段
+        let other = shmat(id, 0, 0);
+        if other <= 0 {
+            exit(1);
+        }
+        let other = unsafe { core::slice::from_raw_parts_mut(other as *mut u8, SHM_SIZE) };
+        if other[0] != 0x5a || seg[SHM_SIZE - 1] != 0xa5 {
+            exit(2);
+        }
+        other[1] = 0x33;
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    assert_eq!(seg[1], 0x33);
+    // 标记删除后键不再可见，但已经attach的映射仍然有效
+    assert_eq!(shmctl(id, IPC_RMID, 0), 0);
+    assert!(shmget(KEY, SHM_SIZE, 0) < 0);
+    seg[2] = 1;
+    assert_eq!(shmdt(addr as usize), 0);
+    assert_eq!(shmdt(addr as usize), EINVAL);
+    println!("shm: ok");
+}
+
+/// 消息缓冲区：开头是`long`类型的消息类型，之后是正文
+fn message(mtype: isize, text: &[u8]) -> [u8; 40] {
+    let mut buf = [0u8; 40];
+    buf[..8].copy_from_slice(&mtype.to_ne_bytes());
+    buf[8..8 + text.len()].copy_from_slice(text);
+    buf
+}
+
+fn mtype_of(buf: &[u8]) -> isize {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[..8]);
+    isize::from_ne_bytes(bytes)
+}
+
+fn test_msg() {
+    let id = msgget(IPC_PRIVATE, IPC_CREAT | 0o600);
+    assert!(id >= 0, "msgget failed: {}", id);
+    let id = id as usize;
+    assert_eq!(msgsnd(id, &message(2, b"two"), 3, 0), 0);
+    assert_eq!(msgsnd(id, &message(1, b"one"), 3, 0), 0);
+    let mut buf = [0u8; 40];
+    // 按类型选择消息
+    assert_eq!(msgrcv(id, &mut buf, 32, 1, 0), 3);
+    assert_eq!(mtype_of(&buf), 1);
+    assert_eq!(&buf[8..11], b"one");
+    // 正文比缓冲区大且没有MSG_NOERROR时失败，消息留在队列中
+    assert_eq!(msgrcv(id, &mut buf, 2, 0, 0), E2BIG);
+    assert_eq!(msgrcv(id, &mut buf, 32, 0, 0), 3);
+    assert_eq!(mtype_of(&buf), 2);
+    assert_eq!(&buf[8..11], b"two");
+    assert_eq!(msgrcv(id, &mut buf, 32, 0, IPC_NOWAIT), ENOMSG);
+    // 阻塞的接收者在消息到达后被唤醒
+    let pid = fork();
+    if pid == 0 {
+        let mut buf = [0u8; 40];
+        if msgrcv(id, &mut buf, 32, 3, 0) != 5 || &buf[8..13] != b"three" {
+            exit(1);
+        }
+        exit(0);
+    }
+    assert_eq!(msgsnd(id, &message(3, b"three"), 5, 0), 0);
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    assert_eq!(msgctl(id, IPC_RMID, 0), 0);
+    assert!(msgsnd(id, &message(1, b"one"), 3, 0) < 0);
+    println!("msg: ok");
+}
+
+fn test_sem() {
+    let id = semget(IPC_PRIVATE, 2, IPC_CREAT | 0o600);
+    assert!(id >= 0, "semget failed: {}", id);
+    let id = id as usize;
+    assert_eq!(semctl(id, 0, SETVAL, 0), 0);
+    assert_eq!(semctl(id, 1, SETVAL, 3), 0);
+    assert_eq!(semctl(id, 1, GETVAL, 0), 3);
+    let down = [Sembuf {
+        sem_num: 0,
+        sem_op: -1,
+        sem_flg: IPC_NOWAIT as i16,
+    }];
+    assert_eq!(semop(id, &down), EAGAIN);
+    // 一组操作要么全部完成，要么都不做
+    let both = [
+        Sembuf {
+            sem_num: 1,
+            sem_op: -1,
+            sem_flg: 0,
+        },
+        Sembuf {
+            sem_num: 0,
+            sem_op: -1,
+            sem_flg: IPC_NOWAIT as i16,
+        },
+    ];
+    assert_eq!(semop(id, &both), EAGAIN);
+    assert_eq!(semctl(id, 1, GETVAL, 0), 3);
+    // 父进程阻塞在信号量0上，直到子进程释放它
+    let pid = fork();
+    if pid == 0 {
+        let up = [Sembuf {
+            sem_num: 0,
+            sem_op: 1,
+            sem_flg: 0,
+        }];
+        exit(if semop(id, &up) == 0 { 0 } else { 1 });
+    }
+    let wait = [Sembuf {
+        sem_num: 0,
+        sem_op: -1,
+        sem_flg: 0,
+    }];
+    assert_eq!(semop(id, &wait), 0);
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, 0);
+    assert_eq!(semctl(id, 0, GETVAL, 0), 0);
+    assert_eq!(semctl(id, 0, IPC_RMID, 0), 0);
+    assert!(semctl(id, 0, GETVAL, 0) < 0);
+    println!("sem: ok");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    test_shm();
+    test_msg();
+    test_sem();
+    println!("sysv_ipc_test passed!");
+    0
+}
@@ -114,3 +114,11 @@ bitflags! {
         const TRUNC = 1 << 10;
     }
 }
+
+/// semop使用的操作，与Linux的`struct sembuf`布局相同
+#[repr(C)]
+pub struct Sembuf {
+    pub sem_num: u16,
+    pub sem_op: i16,
+    pub sem_flg: i16,
+}
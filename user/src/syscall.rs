@@ -6,12 +6,17 @@ const SYSCALL_GETCWD: usize = 17;
 const SYSCALL_DUP: usize = 23;
 const SYSCALL_DUP3: usize = 24;
 const SYSCALL_FCNTL: usize = 25;
+const SYSCALL_INOTIFY_INIT1: usize = 26;
+const SYSCALL_INOTIFY_ADD_WATCH: usize = 27;
+const SYSCALL_INOTIFY_RM_WATCH: usize = 28;
 const SYSCALL_IOCTL: usize = 29;
+const SYSCALL_FLOCK: usize = 32;
 const SYSCALL_MKDIRAT: usize = 34;
 const SYSCALL_UNLINKAT: usize = 35;
 const SYSCALL_LINKAT: usize = 37;
 const SYSCALL_UMOUNT2: usize = 39;
 const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_FTRUNCATE: usize = 46;
 const SYSCALL_FACCESSAT: usize = 48;
 const SYSCALL_CHDIR: usize = 49;
 const SYSCALL_OPENAT: usize = 56;
@@ -56,21 +61,38 @@ const SYSCALL_GETEUID: usize = 175;
 const SYSCALL_GETGID: usize = 176;
 const SYSCALL_GETEGID: usize = 177;
 const SYSCALL_GETTID: usize = 178;
-const SYSCALL_SBRK: usize = 213;
+const SYSCALL_MSGGET: usize = 186;
+const SYSCALL_MSGCTL: usize = 187;
+const SYSCALL_MSGRCV: usize = 188;
+const SYSCALL_MSGSND: usize = 189;
+const SYSCALL_SEMGET: usize = 190;
+const SYSCALL_SEMCTL: usize = 191;
+const SYSCALL_SEMOP: usize = 193;
+const SYSCALL_SHMGET: usize = 194;
+const SYSCALL_SHMCTL: usize = 195;
+const SYSCALL_SHMAT: usize = 196;
+const SYSCALL_SHMDT: usize = 197;
 const SYSCALL_BRK: usize = 214;
 const SYSCALL_MUNMAP: usize = 215;
+const SYSCALL_MREMAP: usize = 216;
 const SYSCALL_CLONE: usize = 220;
 const SYSCALL_EXECVE: usize = 221;
 const SYSCALL_MMAP: usize = 222;
+const SYSCALL_SWAPON: usize = 224;
+const SYSCALL_SWAPOFF: usize = 225;
 const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_MINCORE: usize = 232;
+const SYSCALL_MADVISE: usize = 233;
 const SYSCALL_WAIT4: usize = 260;
 const SYSCALL_PRLIMIT: usize = 261;
 const SYSCALL_RENAMEAT2: usize = 276;
+const SYSCALL_MEMFD_CREATE: usize = 279;
 const SYSCALL_STATX: usize = 291;
 // Not standard POSIX sys_call
 const SYSCALL_LS: usize = 500;
 const SYSCALL_SHUTDOWN: usize = 501;
 const SYSCALL_CLEAR: usize = 502;
+const SYSCALL_SBRK: usize = 503;
 const SYSCALL_OPEN: usize = 506; //where?
 const SYSCALL_GET_TIME: usize = 1690; //you mean get time of day by 169?
 
@@ -80,6 +102,18 @@ global_asm!(include_str!("syscall.S"));
 extern "C" {
     pub fn __syscall(id: usize, args0: usize, args1: usize, args2: usize) -> isize;
 }
+#[cfg(target_arch = "loongarch64")]
+extern "C" {
+    pub fn __syscall6(
+        id: usize,
+        args0: usize,
+        args1: usize,
+        args2: usize,
+        args3: usize,
+        args4: usize,
+        args5: usize,
+    ) -> isize;
+}
 
 fn syscall(id: usize, args: [usize; 3]) -> isize {
     #[cfg(target_arch = "loongarch64")]
@@ -102,6 +136,31 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
     }
 }
 
+/// 带6个参数的系统调用，用于mmap等参数较多的调用
+fn syscall6(id: usize, args: [usize; 6]) -> isize {
+    #[cfg(target_arch = "loongarch64")]
+    unsafe {
+        __syscall6(id, args[0], args[1], args[2], args[3], args[4], args[5])
+    }
+    #[cfg(target_arch = "riscv64")]
+    {
+        let mut ret: isize;
+        unsafe {
+            core::arch::asm!(
+                "ecall",
+                inlateout("x10") args[0] => ret,
+                in("x11") args[1],
+                in("x12") args[2],
+                in("x13") args[3],
+                in("x14") args[4],
+                in("x15") args[5],
+                in("x17") id
+            );
+        }
+        ret
+    }
+}
+
 pub fn sys_dup(fd: usize) -> isize {
     syscall(SYSCALL_DUP, [fd, 0, 0])
 }
@@ -168,3 +227,174 @@ pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
 pub fn sys_shutdown() -> isize {
     syscall(SYSCALL_SHUTDOWN, [0, 0, 0])
 }
+
+pub fn sys_openat(dirfd: isize, path: &str, flags: u32, mode: u32) -> isize {
+    syscall6(
+        SYSCALL_OPENAT,
+        [
+            dirfd as usize,
+            path.as_ptr() as usize,
+            flags as usize,
+            mode as usize,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_unlinkat(dirfd: isize, path: &str, flags: u32) -> isize {
+    syscall(
+        SYSCALL_UNLINKAT,
+        [dirfd as usize, path.as_ptr() as usize, flags as usize],
+    )
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    syscall(SYSCALL_LSEEK, [fd, offset as usize, whence])
+}
+
+pub fn sys_ftruncate(fd: usize, length: usize) -> isize {
+    syscall(SYSCALL_FTRUNCATE, [fd, length, 0])
+}
+
+pub fn sys_fsync(fd: usize) -> isize {
+    syscall(SYSCALL_FSYNC, [fd, 0, 0])
+}
+
+pub fn sys_fcntl(fd: usize, cmd: u32, arg: usize) -> isize {
+    syscall(SYSCALL_FCNTL, [fd, cmd as usize, arg])
+}
+
+pub fn sys_flock(fd: usize, operation: usize) -> isize {
+    syscall(SYSCALL_FLOCK, [fd, operation, 0])
+}
+
+pub fn sys_inotify_init1(flags: usize) -> isize {
+    syscall(SYSCALL_INOTIFY_INIT1, [flags, 0, 0])
+}
+
+pub fn sys_inotify_add_watch(fd: usize, path: &str, mask: u32) -> isize {
+    syscall(
+        SYSCALL_INOTIFY_ADD_WATCH,
+        [fd, path.as_ptr() as usize, mask as usize],
+    )
+}
+
+pub fn sys_inotify_rm_watch(fd: usize, wd: i32) -> isize {
+    syscall(SYSCALL_INOTIFY_RM_WATCH, [fd, wd as usize, 0])
+}
+
+pub fn sys_memfd_create(name: &str, flags: usize) -> isize {
+    syscall(SYSCALL_MEMFD_CREATE, [name.as_ptr() as usize, flags, 0])
+}
+
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: isize,
+    offset: usize,
+) -> isize {
+    syscall6(SYSCALL_MMAP, [addr, len, prot, flags, fd as usize, offset])
+}
+
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, [addr, len, 0])
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    syscall(SYSCALL_MPROTECT, [addr, len, prot])
+}
+
+pub fn sys_mremap(
+    old_addr: usize,
+    old_len: usize,
+    new_len: usize,
+    flags: u32,
+    new_addr: usize,
+) -> isize {
+    syscall6(
+        SYSCALL_MREMAP,
+        [old_addr, old_len, new_len, flags as usize, new_addr, 0],
+    )
+}
+
+pub fn sys_madvise(addr: usize, len: usize, advice: usize) -> isize {
+    syscall(SYSCALL_MADVISE, [addr, len, advice])
+}
+
+pub fn sys_mincore(addr: usize, len: usize, vec: &mut [u8]) -> isize {
+    syscall(SYSCALL_MINCORE, [addr, len, vec.as_mut_ptr() as usize])
+}
+
+pub fn sys_shmget(key: i32, size: usize, shmflg: u32) -> isize {
+    syscall(SYSCALL_SHMGET, [key as usize, size, shmflg as usize])
+}
+
+pub fn sys_shmat(shmid: usize, addr: usize, shmflg: u32) -> isize {
+    syscall(SYSCALL_SHMAT, [shmid, addr, shmflg as usize])
+}
+
+pub fn sys_shmdt(addr: usize) -> isize {
+    syscall(SYSCALL_SHMDT, [addr, 0, 0])
+}
+
+pub fn sys_shmctl(shmid: usize, cmd: u32, buf: usize) -> isize {
+    syscall(SYSCALL_SHMCTL, [shmid, cmd as usize, buf])
+}
+
+pub fn sys_msgget(key: i32, msgflg: u32) -> isize {
+    syscall(SYSCALL_MSGGET, [key as usize, msgflg as usize, 0])
+}
+
+pub fn sys_msgsnd(msqid: usize, msgp: &[u8], msgsz: usize, msgflg: u32) -> isize {
+    syscall6(
+        SYSCALL_MSGSND,
+        [msqid, msgp.as_ptr() as usize, msgsz, msgflg as usize, 0, 0],
+    )
+}
+
+pub fn sys_msgrcv(
+    msqid: usize,
+    msgp: &mut [u8],
+    msgsz: usize,
+    msgtyp: isize,
+    msgflg: u32,
+) -> isize {
+    syscall6(
+        SYSCALL_MSGRCV,
+        [
+            msqid,
+            msgp.as_mut_ptr() as usize,
+            msgsz,
+            msgtyp as usize,
+            msgflg as usize,
+            0,
+        ],
+    )
+}
+
+pub fn sys_msgctl(msqid: usize, cmd: u32, buf: usize) -> isize {
+    syscall(SYSCALL_MSGCTL, [msqid, cmd as usize, buf])
+}
+
+pub fn sys_semget(key: i32, nsems: usize, semflg: u32) -> isize {
+    syscall(SYSCALL_SEMGET, [key as usize, nsems, semflg as usize])
+}
+
+pub fn sys_semop(semid: usize, sops: &[crate::Sembuf]) -> isize {
+    syscall(SYSCALL_SEMOP, [semid, sops.as_ptr() as usize, sops.len()])
+}
+
+pub fn sys_semctl(semid: usize, semnum: usize, cmd: u32, arg: usize) -> isize {
+    syscall6(SYSCALL_SEMCTL, [semid, semnum, cmd as usize, arg, 0, 0])
+}
+
+pub fn sys_swapon(path: &str, flags: u32) -> isize {
+    syscall(SYSCALL_SWAPON, [path.as_ptr() as usize, flags as usize, 0])
+}
+
+pub fn sys_swapoff(path: &str) -> isize {
+    syscall(SYSCALL_SWAPOFF, [path.as_ptr() as usize, 0, 0])
+}
@@ -55,4 +55,100 @@ pub fn sleep(period_ms: usize) {
 }
 pub fn shutdown() -> isize{
     sys_shutdown()
-}
\ No newline at end of file
+}
+pub fn openat(dirfd: isize, path: &str, flags: u32, mode: u32) -> isize {
+    sys_openat(dirfd, path, flags, mode)
+}
+pub fn unlinkat(dirfd: isize, path: &str, flags: u32) -> isize {
+    sys_unlinkat(dirfd, path, flags)
+}
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset, whence)
+}
+pub fn ftruncate(fd: usize, length: usize) -> isize {
+    sys_ftruncate(fd, length)
+}
+pub fn fsync(fd: usize) -> isize {
+    sys_fsync(fd)
+}
+pub fn fcntl(fd: usize, cmd: u32, arg: usize) -> isize {
+    sys_fcntl(fd, cmd, arg)
+}
+pub fn flock(fd: usize, operation: usize) -> isize {
+    sys_flock(fd, operation)
+}
+pub fn inotify_init1(flags: usize) -> isize {
+    sys_inotify_init1(flags)
+}
+pub fn inotify_add_watch(fd: usize, path: &str, mask: u32) -> isize {
+    sys_inotify_add_watch(fd, path, mask)
+}
+pub fn inotify_rm_watch(fd: usize, wd: i32) -> isize {
+    sys_inotify_rm_watch(fd, wd)
+}
+pub fn memfd_create(name: &str, flags: usize) -> isize {
+    sys_memfd_create(name, flags)
+}
+pub fn mmap(addr: usize, len: usize, prot: usize, flags: usize, fd: isize, offset: usize) -> isize {
+    sys_mmap(addr, len, prot, flags, fd, offset)
+}
+pub fn munmap(addr: usize, len: usize) -> isize {
+    sys_munmap(addr, len)
+}
+pub fn mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    sys_mprotect(addr, len, prot)
+}
+pub fn mremap(
+    old_addr: usize,
+    old_len: usize,
+    new_len: usize,
+    flags: u32,
+    new_addr: usize,
+) -> isize {
+    sys_mremap(old_addr, old_len, new_len, flags, new_addr)
+}
+pub fn madvise(addr: usize, len: usize, advice: usize) -> isize {
+    sys_madvise(addr, len, advice)
+}
+pub fn mincore(addr: usize, len: usize, vec: &mut [u8]) -> isize {
+    sys_mincore(addr, len, vec)
+}
+pub fn shmget(key: i32, size: usize, shmflg: u32) -> isize {
+    sys_shmget(key, size, shmflg)
+}
+pub fn shmat(shmid: usize, addr: usize, shmflg: u32) -> isize {
+    sys_shmat(shmid, addr, shmflg)
+}
+pub fn shmdt(addr: usize) -> isize {
+    sys_shmdt(addr)
+}
+pub fn shmctl(shmid: usize, cmd: u32, buf: usize) -> isize {
+    sys_shmctl(shmid, cmd, buf)
+}
+pub fn msgget(key: i32, msgflg: u32) -> isize {
+    sys_msgget(key, msgflg)
+}
+pub fn msgsnd(msqid: usize, msgp: &[u8], msgsz: usize, msgflg: u32) -> isize {
+    sys_msgsnd(msqid, msgp, msgsz, msgflg)
+}
+pub fn msgrcv(msqid: usize, msgp: &mut [u8], msgsz: usize, msgtyp: isize, msgflg: u32) -> isize {
+    sys_msgrcv(msqid, msgp, msgsz, msgtyp, msgflg)
+}
+pub fn msgctl(msqid: usize, cmd: u32, buf: usize) -> isize {
+    sys_msgctl(msqid, cmd, buf)
+}
+pub fn semget(key: i32, nsems: usize, semflg: u32) -> isize {
+    sys_semget(key, nsems, semflg)
+}
+pub fn semop(semid: usize, sops: &[crate::Sembuf]) -> isize {
+    sys_semop(semid, sops)
+}
+pub fn semctl(semid: usize, semnum: usize, cmd: u32, arg: usize) -> isize {
+    sys_semctl(semid, semnum, cmd, arg)
+}
+pub fn swapon(path: &str, flags: u32) -> isize {
+    sys_swapon(path, flags)
+}
+pub fn swapoff(path: &str) -> isize {
+    sys_swapoff(path)
+}